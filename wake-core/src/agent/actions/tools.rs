@@ -175,13 +175,18 @@ impl AgentCore {
         mut internal_rx: broadcast::Receiver<InternalAgentEvent>,
     ) -> JoinHandle<ToolResult> {
         tokio::spawn(async move {
-            // check permission, we allow all Read Tool
+            // check permission, we allow all Read Tool.
+            // Tools that modify attached hardware (flashing, erasing) ask on
+            // every call: claims and sudo mode do not cover them
+            let modifies_device = tool.capabilities().contains(&ToolCapability::Write)
+                && tool.capabilities().contains(&ToolCapability::Device);
             let can_run = tool.capabilities().is_empty()
                 || tool.capabilities() == &[ToolCapability::Read]
-                || claims
-                    .read()
-                    .await
-                    .is_permitted(tool.name(), &call.parameters);
+                || (!modifies_device
+                    && claims
+                        .read()
+                        .await
+                        .is_permitted(tool.name(), &call.parameters));
 
             // request permission if needed (|| is short-circuiting, so won't call if can_run is true)
             let can_run = can_run
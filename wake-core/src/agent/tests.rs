@@ -1,7 +1,7 @@
 use super::brain::{Brain, ThinkerContext};
 use super::builder::AgentBuilder;
 use super::error::AgentError;
-use super::{AgentRequest, PublicAgentState, ThinkerDecision};
+use super::{AgentEvent, AgentRequest, PermissionResponse, PublicAgentState, ThinkerDecision};
 use crate::agent::Agent;
 use crate::logging::LoggingConfig;
 use crate::tools::tool;
@@ -9,6 +9,7 @@ use crate::tools::{AnyTool, LsTool, ReadTool, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Once};
 use std::time::Duration;
 use tokio::sync::Mutex;
@@ -55,6 +56,23 @@ impl SleepingTool {
     }
 }
 
+// Test tool that changes attached hardware, like flashing a target
+struct DeviceWriteTool {
+    runs: Arc<AtomicUsize>,
+}
+
+#[tool(
+    name = "device_write_tool",
+    description = "A tool that writes to attached hardware",
+    capabilities = [ToolCapability::Write, ToolCapability::Device]
+)]
+impl DeviceWriteTool {
+    async fn execute(&self, params: SleepParams) -> ToolResult {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        ToolResult::success("Written".to_string())
+    }
+}
+
 struct MockLlm {}
 
 // Test thinker that calls the sleeping tool once then completes
@@ -514,3 +532,92 @@ async fn test_agent_with_real_tools() {
         }
     }
 }
+
+// Test thinker that calls one tool a number of times, one call per step
+struct RepeatingThinker {
+    tool: &'static str,
+    remaining: u32,
+}
+
+#[async_trait]
+impl Brain for RepeatingThinker {
+    async fn next_step(&mut self, _: ThinkerContext) -> Result<ThinkerDecision, AgentError> {
+        if self.remaining == 0 {
+            return Ok(ThinkerDecision::agent_pause(ChatMessage::Assistant {
+                content: Some(ChatMessageContent::Text("we are done".to_string())),
+                reasoning_content: None,
+                tool_calls: None,
+                name: None,
+                audio: None,
+                refusal: None,
+            }));
+        }
+        self.remaining -= 1;
+        Ok(ThinkerDecision::agent_continue(ChatMessage::Assistant {
+            content: None,
+            reasoning_content: None,
+            tool_calls: Some(vec![wake_llm::ToolCall {
+                id: format!("call_{}", self.remaining),
+                r#type: "function".to_string(),
+                function: wake_llm::Function {
+                    name: self.tool.to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]),
+            name: None,
+            audio: None,
+            refusal: None,
+        }))
+    }
+}
+
+#[tokio::test]
+async fn test_device_write_asks_every_call() {
+    init_test_logging();
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let tool: Box<dyn AnyTool> = Box::new(DeviceWriteTool { runs: runs.clone() });
+    let mut agent = AgentBuilder::new(Box::new(RepeatingThinker {
+        tool: "device_write_tool",
+        remaining: 2,
+    }))
+    .id("test-device-write-agent")
+    .goal("Flash the target twice")
+    .tools(vec![tool])
+    .build();
+
+    let mut events = agent.watch();
+    let mut controller = agent.controller();
+    let handle = tokio::spawn(async move { agent.run().await });
+
+    // Answer the first prompt with "allow always" the way the TUI does,
+    // which turns on sudo mode; the second call must still ask
+    let mut prompts = 0;
+    while prompts < 2 {
+        match tokio::time::timeout(Duration::from_secs(5), events.recv()).await {
+            Ok(Ok(AgentEvent::PermissionRequired { request_id, .. })) => {
+                prompts += 1;
+                let response = if prompts == 1 {
+                    controller.sudo().await.unwrap();
+                    PermissionResponse::AllowAlways
+                } else {
+                    PermissionResponse::Allow
+                };
+                controller
+                    .response_permission_request(request_id, response)
+                    .await
+                    .unwrap();
+            }
+            Ok(Ok(_)) => {}
+            _ => break,
+        }
+    }
+    assert_eq!(prompts, 2, "every call to a device write tool must ask");
+
+    controller
+        .drop()
+        .await
+        .expect("failed to drop the controller");
+    handle.await.unwrap().expect("agent failed");
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,189 @@
+//! CAN bit timing.
+//!
+//! Finds prescaler and segment values for a controller clock and nominal
+//! bitrate, following the CiA 301 sample-point recommendations. Segment
+//! limits are those of classic controllers (bxCAN, MCP2515, SJA1000-style
+//! BTR registers), which also fit inside the wider FDCAN/M_CAN ranges.
+
+use super::netlist::format_si;
+
+const MAX_BRP: u32 = 1024;
+const MAX_TSEG1: u32 = 16;
+const MAX_TSEG2: u32 = 8;
+const MIN_TQ: u32 = 8;
+const MAX_TQ: u32 = 25;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitTiming {
+    pub clock: u32,
+    pub brp: u32,
+    /// Propagation plus phase segment 1, in time quanta
+    pub tseg1: u32,
+    pub tseg2: u32,
+    pub sjw: u32,
+    pub bitrate: f64,
+    /// Deviation from the requested bitrate
+    pub error_percent: f64,
+}
+
+impl BitTiming {
+    /// Time quanta per bit, including the sync segment
+    pub fn quanta(&self) -> u32 {
+        1 + self.tseg1 + self.tseg2
+    }
+
+    pub fn sample_point(&self) -> f64 {
+        (1 + self.tseg1) as f64 / self.quanta() as f64 * 100.0
+    }
+
+    pub fn time_quantum(&self) -> f64 {
+        self.brp as f64 / self.clock as f64
+    }
+
+    /// STM32 bxCAN `CAN_BTR` value (mode bits clear)
+    pub fn bxcan_btr(&self) -> u32 {
+        (self.sjw - 1) << 24 | (self.tseg2 - 1) << 20 | (self.tseg1 - 1) << 16 | (self.brp - 1)
+    }
+}
+
+/// CiA recommended sample point for a bitrate, in percent
+pub fn target_sample_point(bitrate: u32) -> f64 {
+    match bitrate {
+        1_000_000.. => 75.0,
+        800_000.. => 80.0,
+        _ => 87.5,
+    }
+}
+
+/// Sample points this close to the target count as equally good
+const SAMPLE_POINT_TOLERANCE: f64 = 2.0;
+
+/// Best timings first: exact bitrate, sample point near the target, then the
+/// smallest prescaler (more quanta per bit for finer resynchronisation)
+pub fn solve(clock: u32, bitrate: u32) -> Vec<BitTiming> {
+    if clock == 0 || bitrate == 0 {
+        return Vec::new();
+    }
+    let target = target_sample_point(bitrate);
+    let mut timings = Vec::new();
+    for brp in 1..=MAX_BRP {
+        let tq_clock = clock as f64 / brp as f64;
+        let quanta = (tq_clock / bitrate as f64).round() as u32;
+        if !(MIN_TQ..=MAX_TQ).contains(&quanta) {
+            continue;
+        }
+        let actual = tq_clock / quanta as f64;
+        let error_percent = (actual - bitrate as f64) / bitrate as f64 * 100.0;
+        if error_percent.abs() > 1.0 {
+            continue;
+        }
+        // Place the sample point as close to the target as the segments allow
+        let before = ((quanta as f64 * target / 100.0).round() as u32).clamp(2, quanta - 1);
+        let tseg2 = (quanta - before).clamp(1, MAX_TSEG2);
+        let tseg1 = quanta - 1 - tseg2;
+        if !(1..=MAX_TSEG1).contains(&tseg1) {
+            continue;
+        }
+        timings.push(BitTiming {
+            clock,
+            brp,
+            tseg1,
+            tseg2,
+            sjw: tseg2.min(4),
+            bitrate: actual,
+            error_percent,
+        });
+    }
+    timings.sort_by(|a, b| {
+        let key = |t: &BitTiming| {
+            let off_target = (t.sample_point() - target).abs();
+            (
+                t.error_percent.abs(),
+                (off_target > SAMPLE_POINT_TOLERANCE).then_some(off_target),
+                t.brp,
+            )
+        };
+        key(a).partial_cmp(&key(b)).unwrap()
+    });
+    timings
+}
+
+/// Markdown report of the best timing and a few alternatives
+pub fn report(clock: u32, bitrate: u32) -> String {
+    let timings = solve(clock, bitrate);
+    let mut out = format!(
+        "Clock {}, bitrate {}, target sample point {:.1}%\n\n",
+        format_si(clock as f64, "Hz"),
+        format_si(bitrate as f64, "bit/s"),
+        target_sample_point(bitrate)
+    );
+    let Some(best) = timings.first() else {
+        out.push_str(&format!(
+            "No prescaler gives {}-{} time quanta per bit within 1% of the bitrate. \
+             Pick a clock that is a multiple of the bitrate (e.g. 8, 16, 40 or 80 MHz).\n",
+            MIN_TQ, MAX_TQ
+        ));
+        return out;
+    };
+    out.push_str("| BRP | TSEG1 | TSEG2 | SJW | Tq/bit | Sample point | Bitrate error |\n");
+    out.push_str("|-----|-------|-------|-----|--------|--------------|---------------|\n");
+    for timing in timings.iter().take(5) {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {:.1}% | {:+.3}% |\n",
+            timing.brp,
+            timing.tseg1,
+            timing.tseg2,
+            timing.sjw,
+            timing.quanta(),
+            timing.sample_point(),
+            timing.error_percent
+        ));
+    }
+    out.push_str(&format!(
+        "\nRecommended: BRP={} TSEG1={} TSEG2={} SJW={} (Tq = {}), \
+         bxCAN `CAN_BTR = 0x{:08X}`\n",
+        best.brp,
+        best.tseg1,
+        best.tseg2,
+        best.sjw,
+        format_si(best.time_quantum(), "s"),
+        best.bxcan_btr()
+    ));
+    out.push_str(&format!(
+        "SocketCAN: `ip link set can0 type can bitrate {} sample-point {:.3}`\n",
+        bitrate,
+        best.sample_point() / 100.0
+    ));
+    if best.error_percent.abs() > 0.0 {
+        out.push_str(
+            "⚠️  The bitrate is not exact; keep the combined error of all nodes well under 0.5%.\n",
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_bit_timing() {
+        // 36 MHz APB1 at 500 kbit/s: 18 tq, BRP 4, 88.9% sample point
+        let best = &solve(36_000_000, 500_000)[0];
+        assert_eq!((best.brp, best.quanta()), (4, 18));
+        assert_eq!((best.tseg1, best.tseg2, best.sjw), (15, 2, 2));
+        assert_eq!(best.error_percent, 0.0);
+        assert_eq!(best.bxcan_btr(), 0x011E_0003);
+
+        let fast = &solve(80_000_000, 1_000_000)[0];
+        assert_eq!(fast.sample_point(), 75.0);
+        assert_eq!(fast.bitrate, 1_000_000.0);
+
+        let text = report(16_000_000, 250_000);
+        assert!(text.contains("Recommended: BRP=4 TSEG1=13 TSEG2=2"));
+        assert!(text.contains("sample-point 0.875"));
+
+        assert!(solve(1_000_000, 500_000).is_empty());
+        assert!(report(3_000_000, 1_000_000).contains("No prescaler"));
+    }
+}
@@ -0,0 +1,819 @@
+//! CAN trace import and bus statistics.
+//!
+//! Reads SocketCAN `candump` output (log format `(time) can0 123#DEADBEEF`
+//! and the default `can0  123   [4]  DE AD BE EF` listing), Vector ASC logs
+//! and CSV exports with time/ID/data columns. Bus load counts the exact
+//! on-wire length of each classic frame including stuff bits; per-message
+//! statistics give the period, cycle-time jitter and dropouts.
+
+use super::dbc::Database;
+use super::netlist::format_si;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// SocketCAN error frame flag in the CAN ID
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+
+/// Cycle-time jitter above this share of the period is reported
+pub const JITTER_LIMIT_PERCENT: f64 = 10.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Seconds
+    pub time: f64,
+    pub channel: Option<String>,
+    pub id: u32,
+    pub extended: bool,
+    pub remote: bool,
+    pub fd: bool,
+    /// Error frame; `id` then holds the SocketCAN error class bits, if known
+    pub error: bool,
+    pub dlc: u8,
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    fn data(time: f64, id: u32, extended: bool, data: Vec<u8>) -> Self {
+        Self {
+            time,
+            channel: None,
+            id,
+            extended,
+            remote: false,
+            fd: false,
+            error: false,
+            dlc: data.len().min(15) as u8,
+            data,
+        }
+    }
+
+    fn error(time: f64, class: u32) -> Self {
+        Self {
+            error: true,
+            ..Self::data(time, class, false, Vec::new())
+        }
+    }
+
+    pub fn id_label(&self) -> String {
+        id_label(self.id, self.extended)
+    }
+
+    /// Bits on the wire: stuffed SOF-to-CRC, then CRC delimiter, ACK, EOF and
+    /// intermission. FD frames are counted at the nominal rate without stuffing.
+    pub fn bits(&self) -> u32 {
+        if self.error {
+            // Error flag, echoed flags and delimiter, then intermission
+            return 6 + 6 + 8 + 3;
+        }
+        let payload = if self.remote {
+            0
+        } else {
+            self.data.len() as u32 * 8
+        };
+        if self.fd {
+            let header = if self.extended { 38 } else { 19 };
+            let crc = if self.data.len() > 16 { 21 } else { 17 };
+            return 1 + header + 4 + 4 + payload + crc + 4 + 2 + 7 + 3;
+        }
+
+        let mut bits = vec![false];
+        let push = |bits: &mut Vec<bool>, value: u32, width: u32| {
+            bits.extend((0..width).rev().map(|b| value >> b & 1 == 1));
+        };
+        if self.extended {
+            push(&mut bits, self.id >> 18, 11);
+            bits.extend([true, true]); // SRR, IDE
+            push(&mut bits, self.id & 0x3FFFF, 18);
+            bits.extend([self.remote, false, false]); // RTR, r1, r0
+        } else {
+            push(&mut bits, self.id, 11);
+            bits.extend([self.remote, false, false]); // RTR, IDE, r0
+        }
+        push(&mut bits, self.dlc.min(8) as u32, 4);
+        if !self.remote {
+            for byte in &self.data {
+                push(&mut bits, *byte as u32, 8);
+            }
+        }
+        let crc = bits.iter().fold(0u32, |crc, bit| {
+            let next = (crc << 1) & 0x7FFF;
+            if *bit != (crc >> 14 & 1 == 1) {
+                next ^ 0x4599
+            } else {
+                next
+            }
+        });
+        push(&mut bits, crc, 15);
+
+        let mut stuffed = 0;
+        let (mut last, mut run) = (bits[0], 1);
+        for bit in &bits[1..] {
+            if *bit == last {
+                run += 1;
+            } else {
+                (last, run) = (*bit, 1);
+            }
+            if run == 5 {
+                stuffed += 1;
+                (last, run) = (!last, 1);
+            }
+        }
+        bits.len() as u32 + stuffed + 1 + 2 + 7 + 3
+    }
+}
+
+pub fn id_label(id: u32, extended: bool) -> String {
+    if extended {
+        format!("0x{:08X}", id)
+    } else {
+        format!("0x{:03X}", id)
+    }
+}
+
+/// SocketCAN error class names for the bits of an error frame's ID
+pub fn error_classes(class: u32) -> Vec<&'static str> {
+    const CLASSES: [(u32, &str); 10] = [
+        (0x001, "TX timeout"),
+        (0x002, "lost arbitration"),
+        (0x004, "controller problem"),
+        (0x008, "protocol violation"),
+        (0x010, "transceiver problem"),
+        (0x020, "no ACK"),
+        (0x040, "bus off"),
+        (0x080, "bus error"),
+        (0x100, "controller restarted"),
+        (0x200, "error counters"),
+    ];
+    CLASSES
+        .iter()
+        .filter(|(bit, _)| class & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn hex_bytes<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec<u8>, String> {
+    tokens
+        .map(|t| u8::from_str_radix(t, 16).map_err(|_| format!("invalid data byte '{}'", t)))
+        .collect()
+}
+
+fn parse_hex_id(text: &str) -> Result<u32, String> {
+    let digits = text
+        .trim()
+        .trim_start_matches("0x")
+        .trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("invalid CAN ID '{}'", text))
+}
+
+/// Parse a trace, telling the format from its content
+pub fn parse(text: &str) -> Result<Vec<Frame>, String> {
+    let first = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("");
+    let lower = first.to_lowercase();
+    if lower.starts_with("date") || lower.starts_with("base") || lower.starts_with("//") {
+        parse_asc(text)
+    } else if first.starts_with('(')
+        || first
+            .split_whitespace()
+            .nth(2)
+            .is_some_and(|t| t.starts_with('['))
+    {
+        parse_candump(text)
+    } else if first.contains(',') {
+        parse_csv(text)
+    } else {
+        parse_candump(text)
+    }
+}
+
+/// Load a `.log`/`.candump`, `.asc` or `.csv` trace
+pub fn load(path: &Path) -> Result<Vec<Frame>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "asc" => parse_asc(&text),
+        "csv" => parse_csv(&text),
+        _ => parse(&text),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// `candump -l` log lines and the default `candump` listing
+pub fn parse_candump(text: &str) -> Result<Vec<Frame>, String> {
+    let mut frames = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let mut tokens = line.split_whitespace().peekable();
+        let time = match tokens.peek() {
+            Some(t) if t.starts_with('(') => {
+                let stamp = tokens.next().unwrap_or("");
+                stamp
+                    .trim_matches(|c| c == '(' || c == ')')
+                    .parse::<f64>()
+                    .map_err(|_| error(format!("invalid timestamp '{}'", stamp)))?
+            }
+            // The plain listing has no timestamps
+            _ => 0.0,
+        };
+        let channel = tokens
+            .next()
+            .ok_or_else(|| error("missing interface".into()))?;
+        let frame_text = tokens.next().ok_or_else(|| error("missing frame".into()))?;
+
+        let mut frame = if let Some((id, payload)) = frame_text.split_once('#') {
+            // Log format: 123#DEADBEEF, 123#R, 123##1DEADBEEF
+            let mut frame = Frame::data(
+                time,
+                parse_hex_id(id).map_err(error)?,
+                id.len() > 3,
+                Vec::new(),
+            );
+            if let Some(fd) = payload.strip_prefix('#') {
+                frame.fd = true;
+                let bytes = fd.get(1..).unwrap_or("");
+                frame.data = hex_bytes((0..bytes.len() / 2).map(|i| &bytes[i * 2..i * 2 + 2]))
+                    .map_err(error)?;
+            } else if let Some(dlc) = payload.strip_prefix('R') {
+                frame.remote = true;
+                frame.dlc = dlc.parse().unwrap_or(0);
+            } else {
+                let bytes = payload.replace('.', "");
+                frame.data = hex_bytes((0..bytes.len() / 2).map(|i| &bytes[i * 2..i * 2 + 2]))
+                    .map_err(error)?;
+            }
+            if !frame.remote {
+                frame.dlc = frame.data.len().min(15) as u8;
+            }
+            frame
+        } else {
+            // Listing: 123   [4]  DE AD BE EF
+            let id = frame_text;
+            let length = tokens.next().unwrap_or("");
+            let dlc: usize = length
+                .trim_matches(|c| c == '[' || c == ']')
+                .parse()
+                .map_err(|_| error(format!("invalid length '{}'", length)))?;
+            let rest: Vec<&str> = tokens.collect();
+            let mut frame = Frame::data(
+                time,
+                parse_hex_id(id).map_err(error)?,
+                id.len() > 3,
+                Vec::new(),
+            );
+            frame.fd = length.len() == 4;
+            if rest.first() == Some(&"remote") {
+                frame.remote = true;
+            } else {
+                frame.data = hex_bytes(rest.iter().take(dlc).copied()).map_err(error)?;
+            }
+            frame.dlc = dlc.min(15) as u8;
+            frame
+        };
+        if frame.extended && frame.id & CAN_ERR_FLAG != 0 {
+            frame = Frame::error(time, frame.id & 0x1FFF_FFFF);
+        }
+        frame.channel = Some(channel.to_string());
+        frames.push(frame);
+    }
+    if frames.is_empty() {
+        return Err("no CAN frames".to_string());
+    }
+    Ok(frames)
+}
+
+/// Vector ASC log: `time channel id Rx d dlc bytes...`, `CANFD` lines and `ErrorFrame`
+pub fn parse_asc(text: &str) -> Result<Vec<Frame>, String> {
+    let mut frames = Vec::new();
+    let mut radix = 16;
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(time) = tokens.first().and_then(|t| t.parse::<f64>().ok()) else {
+            if tokens.first() == Some(&"base") {
+                radix = if tokens.get(1) == Some(&"dec") {
+                    10
+                } else {
+                    16
+                };
+            }
+            continue;
+        };
+        let parse_id = |text: &str| -> Result<(u32, bool), String> {
+            let extended = text.ends_with(['x', 'X']);
+            let digits = text.trim_end_matches(['x', 'X']);
+            u32::from_str_radix(digits, radix)
+                .map(|id| (id, extended))
+                .map_err(|_| error(format!("invalid CAN ID '{}'", text)))
+        };
+
+        if tokens.get(1) == Some(&"CANFD") {
+            // time CANFD channel dir id [name] brs esi dlc length data...
+            let mut rest = tokens.get(4..).unwrap_or(&[]).iter();
+            let (id, extended) = parse_id(rest.next().copied().unwrap_or(""))?;
+            let mut fields: Vec<&str> = rest.copied().collect();
+            if fields.first().is_some_and(|f| f.parse::<u8>().is_err()) {
+                fields.remove(0);
+            }
+            let length: usize = fields
+                .get(3)
+                .and_then(|l| l.parse().ok())
+                .ok_or_else(|| error("invalid CAN FD data length".into()))?;
+            let mut frame = Frame::data(
+                time,
+                id,
+                extended,
+                hex_bytes(fields.iter().skip(4).take(length).copied()).map_err(error)?,
+            );
+            frame.fd = true;
+            frame.channel = Some(tokens[2].to_string());
+            frames.push(frame);
+            continue;
+        }
+
+        match tokens.get(2).copied() {
+            Some("ErrorFrame") => {
+                let mut frame = Frame::error(time, 0);
+                frame.channel = Some(tokens[1].to_string());
+                frames.push(frame);
+            }
+            Some(id) if tokens.len() >= 5 && matches!(tokens[3], "Rx" | "Tx") => {
+                let (id, extended) = parse_id(id)?;
+                let mut frame = Frame::data(time, id, extended, Vec::new());
+                frame.channel = Some(tokens[1].to_string());
+                frame.dlc = tokens
+                    .get(5)
+                    .and_then(|d| u8::from_str_radix(d, 16).ok())
+                    .unwrap_or(0);
+                if tokens[4] == "r" {
+                    frame.remote = true;
+                } else {
+                    frame.data = hex_bytes(
+                        tokens
+                            .iter()
+                            .skip(6)
+                            .take(frame.dlc.min(8) as usize)
+                            .copied(),
+                    )
+                    .map_err(error)?;
+                }
+                frames.push(frame);
+            }
+            // Start of measurement, statistics and other events
+            _ => {}
+        }
+    }
+    if frames.is_empty() {
+        return Err("no CAN frames".to_string());
+    }
+    Ok(frames)
+}
+
+/// CSV with a time column, an ID column and either a hex data column or one
+/// column per byte (`D1`..`D8`, `Byte 0`..). Times are seconds unless the
+/// header says `us`/`ms` (SavvyCAN's `Time Stamp` is microseconds).
+pub fn parse_csv(text: &str) -> Result<Vec<Frame>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = lines.next().ok_or("empty trace")?;
+    let columns: Vec<String> = header.split(',').map(|c| c.trim().to_lowercase()).collect();
+    let find = |names: &[&str]| {
+        columns
+            .iter()
+            .position(|c| names.iter().any(|n| c.contains(n)))
+    };
+
+    let time = find(&["time"]).ok_or("CSV trace has no time column")?;
+    let id = columns
+        .iter()
+        .position(|c| {
+            c == "id"
+                || c.contains("identifier")
+                || c.starts_with("id ")
+                || c.contains("arbitration")
+        })
+        .ok_or("CSV trace has no ID column")?;
+    let scale = match columns[time].as_str() {
+        c if c.contains("us") || c.contains("µs") || c == "time stamp" => 1e-6,
+        c if c.contains("ms") => 1e-3,
+        _ => 1.0,
+    };
+    let extended = columns
+        .iter()
+        .position(|c| c.contains("extended") || c == "ide");
+    let error_column = find(&["error", "type"]);
+    let data = columns
+        .iter()
+        .position(|c| c == "data" || c.starts_with("data "));
+    let bytes: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| {
+            let digits = c.trim_start_matches("byte").trim_start_matches('d').trim();
+            c.len() > digits.len()
+                && !digits.is_empty()
+                && digits.chars().all(|d| d.is_ascii_digit())
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let length = find(&["dlc", "len"]);
+
+    let mut frames = Vec::new();
+    for (number, line) in lines {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+        let cell = |index: usize| cells.get(index).copied().unwrap_or("");
+        let t = cell(time)
+            .parse::<f64>()
+            .map_err(|_| error(format!("invalid time '{}'", cell(time))))?
+            * scale;
+        if error_column.is_some_and(|c| cell(c).to_lowercase().contains("error")) {
+            frames.push(Frame::error(t, 0));
+            continue;
+        }
+        let raw_id = parse_hex_id(cell(id)).map_err(error)?;
+        let is_extended = match extended.map(|c| cell(c).to_lowercase()) {
+            Some(flag) if matches!(flag.as_str(), "true" | "1" | "x" | "ext" | "extended") => true,
+            Some(flag) if matches!(flag.as_str(), "false" | "0" | "std" | "standard") => false,
+            _ => raw_id > 0x7FF,
+        };
+        let mut payload = match data {
+            Some(column) => hex_bytes(cell(column).split_whitespace()).map_err(error)?,
+            None => hex_bytes(bytes.iter().map(|c| cell(*c)).filter(|c| !c.is_empty()))
+                .map_err(error)?,
+        };
+        if let Some(dlc) = length.and_then(|c| cell(c).parse::<usize>().ok()) {
+            payload.truncate(dlc);
+        }
+        frames.push(Frame::data(t, raw_id, is_extended, payload));
+    }
+    if frames.is_empty() {
+        return Err("no CAN frames".to_string());
+    }
+    Ok(frames)
+}
+
+/// Bus load in percent over the whole trace and the busiest `window` seconds
+pub fn bus_load(frames: &[Frame], bitrate: u32, window: f64) -> (f64, f64) {
+    let (Some(first), Some(last)) = (frames.first(), frames.last()) else {
+        return (0.0, 0.0);
+    };
+    let busy: Vec<f64> = frames
+        .iter()
+        .map(|f| f.bits() as f64 / bitrate as f64)
+        .collect();
+    // The last frame's own duration belongs to the trace
+    let duration = (last.time - first.time) + busy[busy.len() - 1];
+    let overall = busy.iter().sum::<f64>() / duration * 100.0;
+
+    let (mut peak, mut sum, mut start) = (0.0f64, 0.0, 0);
+    for end in 0..frames.len() {
+        sum += busy[end];
+        while frames[end].time - frames[start].time > window {
+            sum -= busy[start];
+            start += 1;
+        }
+        peak = peak.max(sum / window * 100.0);
+    }
+    (overall, if duration > window { peak } else { overall })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageStats {
+    pub id: u32,
+    pub extended: bool,
+    pub count: usize,
+    /// Data lengths seen, in order of first appearance
+    pub lengths: Vec<usize>,
+    /// Median interval between frames in seconds
+    pub period: Option<f64>,
+    /// Largest deviation from the period among regular intervals, in seconds
+    pub jitter: f64,
+    /// Intervals longer than 1.5 periods
+    pub dropouts: usize,
+    pub longest_gap: f64,
+}
+
+impl MessageStats {
+    pub fn jitter_percent(&self) -> Option<f64> {
+        self.period.map(|p| self.jitter / p * 100.0)
+    }
+}
+
+/// Per-ID counts, lengths, period and jitter, ordered by ID
+pub fn message_stats(frames: &[Frame]) -> Vec<MessageStats> {
+    let mut by_id: BTreeMap<(bool, u32), Vec<&Frame>> = BTreeMap::new();
+    for frame in frames.iter().filter(|f| !f.error) {
+        by_id
+            .entry((frame.extended, frame.id))
+            .or_default()
+            .push(frame);
+    }
+    by_id
+        .into_iter()
+        .map(|((extended, id), frames)| {
+            let mut lengths = Vec::new();
+            for frame in &frames {
+                let length = if frame.remote {
+                    frame.dlc as usize
+                } else {
+                    frame.data.len()
+                };
+                if !lengths.contains(&length) {
+                    lengths.push(length);
+                }
+            }
+            let intervals: Vec<f64> = frames.windows(2).map(|w| w[1].time - w[0].time).collect();
+            let mut sorted = intervals.clone();
+            sorted.sort_by(f64::total_cmp);
+            let period = sorted.get(sorted.len() / 2).copied().filter(|p| *p > 0.0);
+            let (mut jitter, mut dropouts, mut longest_gap) = (0.0f64, 0, 0.0f64);
+            if let Some(period) = period {
+                for interval in &intervals {
+                    longest_gap = longest_gap.max(*interval);
+                    if *interval > period * 1.5 {
+                        dropouts += 1;
+                    } else {
+                        jitter = jitter.max((interval - period).abs());
+                    }
+                }
+            }
+            MessageStats {
+                id,
+                extended,
+                count: frames.len(),
+                lengths,
+                period,
+                jitter,
+                dropouts,
+                longest_gap,
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    ErrorFrame,
+    /// Payload length differs from the DBC definition
+    LengthMismatch,
+    /// Signs of two nodes sending the same ID
+    IdCollision,
+    Jitter,
+    Dropout,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub time: f64,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// Error frames, length mismatches, ID collisions, jitter and dropouts,
+/// ordered by time
+pub fn check(frames: &[Frame], stats: &[MessageStats], dbc: Option<&Database>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let mut errors: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+    for frame in frames.iter().filter(|f| f.error) {
+        let classes = error_classes(frame.id);
+        let label = if classes.is_empty() {
+            "unspecified".to_string()
+        } else {
+            classes.join(", ")
+        };
+        errors.entry(label).or_insert((frame.time, 0)).1 += 1;
+    }
+    for (label, (time, count)) in errors {
+        issues.push(Issue {
+            time,
+            kind: IssueKind::ErrorFrame,
+            message: format!("{} error frame(s): {}", count, label),
+        });
+    }
+
+    for s in stats {
+        let message = dbc.and_then(|db| db.message(s.id, s.extended));
+        let name = match message {
+            Some(m) => format!("{} {}", id_label(s.id, s.extended), m.name),
+            None => id_label(s.id, s.extended),
+        };
+        let mut sent = frames
+            .iter()
+            .filter(|f| !f.error && f.id == s.id && f.extended == s.extended);
+        let first = sent.clone().next().map_or(0.0, |f| f.time);
+        let mut issue = |time, kind, message| {
+            issues.push(Issue {
+                time,
+                kind,
+                message,
+            })
+        };
+
+        if s.lengths.len() > 1 {
+            let time = sent
+                .find(|f| !f.remote && f.data.len() != s.lengths[0])
+                .map_or(first, |f| f.time);
+            let lengths: Vec<String> = s.lengths.iter().map(|l| l.to_string()).collect();
+            issue(
+                time,
+                IssueKind::IdCollision,
+                format!(
+                    "{} sent with {} data bytes: two nodes may be using this ID",
+                    name,
+                    lengths.join(" and ")
+                ),
+            );
+        }
+        if let Some(m) = message {
+            if let Some(length) = s.lengths.iter().find(|l| **l != m.size) {
+                issue(
+                    first,
+                    IssueKind::LengthMismatch,
+                    format!(
+                        "{} has {} data bytes; the DBC defines {}",
+                        name, length, m.size
+                    ),
+                );
+            }
+            if let (Some(period), Some(cycle)) = (s.period, m.cycle_time) {
+                if period < cycle * 0.6 {
+                    issue(
+                        first,
+                        IssueKind::IdCollision,
+                        format!(
+                            "{} every {} but its DBC cycle time is {}: another node may be sending this ID",
+                            name,
+                            format_si(period, "s"),
+                            format_si(cycle, "s")
+                        ),
+                    );
+                }
+            }
+        }
+        if let (Some(period), Some(jitter)) = (s.period, s.jitter_percent()) {
+            if jitter > JITTER_LIMIT_PERCENT {
+                issue(
+                    first,
+                    IssueKind::Jitter,
+                    format!(
+                        "{} cycle-time jitter {:.0}% (period {}, worst deviation {})",
+                        name,
+                        jitter,
+                        format_si(period, "s"),
+                        format_si(s.jitter, "s")
+                    ),
+                );
+            }
+        }
+        if s.dropouts > 0 {
+            issue(
+                first,
+                IssueKind::Dropout,
+                format!(
+                    "{} missed {} cycle(s); longest gap {}",
+                    name,
+                    s.dropouts,
+                    format_si(s.longest_gap, "s")
+                ),
+            );
+        }
+    }
+
+    issues.sort_by(|a, b| a.time.total_cmp(&b.time));
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candump_formats() {
+        let log = "(1436509052.249713) can0 123#DEADBEEF\n\
+            (1436509052.259713) can0 12345678#R\n\
+            (1436509052.269713) can0 20000080#0000000000000000\n\
+            (1436509052.279713) can1 456##1112233\n";
+        let frames = parse(log).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].data, vec![0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(frames[0].id_label(), "0x123");
+        assert!(frames[1].extended && frames[1].remote);
+        assert!(frames[2].error);
+        assert_eq!(error_classes(frames[2].id), vec!["bus error"]);
+        assert!(frames[3].fd && frames[3].data == vec![0x11, 0x22, 0x33]);
+        assert_eq!(frames[3].channel.as_deref(), Some("can1"));
+
+        let listing = " (000.000000)  can0  7DF   [8]  02 01 0C 00 00 00 00 00\n\
+             (000.010000)  can0  7E8   [2]  remote request\n";
+        let frames = parse(listing).unwrap();
+        assert_eq!(frames[0].id, 0x7DF);
+        assert_eq!(frames[0].data.len(), 8);
+        assert!(frames[1].remote && frames[1].dlc == 2);
+        assert!(parse_candump("can0 XYZ#00")
+            .unwrap_err()
+            .starts_with("line 1"));
+    }
+
+    #[test]
+    fn test_asc_and_csv() {
+        let asc = "date Mon Jan 1 10:00:00 am 2024\nbase hex  timestamps absolute\n\
+            Begin Triggerblock Mon Jan 1 10:00:00 am 2024\n   0.000000 Start of measurement\n\
+               0.010000 1  123             Rx   d 8 00 01 02 03 04 05 06 07  Length = 0\n\
+               0.020000 1  1ABCDEF0x       Rx   d 2 AA BB\n\
+               0.030000 1  ErrorFrame\n\
+               0.040000 CANFD   1 Rx        321  Msg 1 0 9 12 00 01 02 03 04 05 06 07 08 09 0A 0B\n\
+            End TriggerBlock\n";
+        let frames = parse(asc).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!((frames[1].id, frames[1].extended), (0x1ABCDEF0, true));
+        assert!(frames[2].error);
+        assert_eq!(frames[3].data.len(), 12);
+
+        let savvy = "Time Stamp,ID,Extended,Dir,Bus,LEN,D1,D2,D3,D4,D5,D6,D7,D8\n\
+            1000,00000123,false,Rx,0,2,11,22,,,,,,\n\
+            11000,18FEF100,true,Rx,0,8,01,02,03,04,05,06,07,08\n";
+        let frames = parse(savvy).unwrap();
+        assert_eq!(frames[0].time, 1e-3);
+        assert_eq!(frames[0].data, vec![0x11, 0x22]);
+        assert!(frames[1].extended && frames[1].data.len() == 8);
+    }
+
+    #[test]
+    fn test_frame_bits_and_statistics() {
+        // Standard 8-byte frame of zeros: 47 + 64 bits plus worst-case stuffing
+        let zeros = Frame::data(0.0, 0x000, false, vec![0; 8]);
+        assert!(
+            zeros.bits() > 111 && zeros.bits() <= 111 + 24,
+            "{}",
+            zeros.bits()
+        );
+        let alternating = Frame::data(0.0, 0x555, false, vec![0x55; 8]);
+        assert!(alternating.bits() < zeros.bits());
+
+        let mut frames: Vec<Frame> = (0..100)
+            .map(|i| Frame::data(i as f64 * 0.010, 0x100, false, vec![0xAA; 8]))
+            .collect();
+        frames[50].time += 0.002; // 20% late
+        frames.remove(80); // one dropout
+        let stats = message_stats(&frames);
+        assert_eq!(stats.len(), 1);
+        assert!((stats[0].period.unwrap() - 0.010).abs() < 1e-9);
+        assert!((stats[0].jitter_percent().unwrap() - 20.0).abs() < 1e-6);
+        assert_eq!(stats[0].dropouts, 1);
+
+        // 100 frames of ~130 bits every 10 ms at 125 kbit/s is about 10% load
+        let (overall, peak) = bus_load(&frames, 125_000, 0.1);
+        assert!(overall > 9.0 && overall < 12.0, "{}", overall);
+        assert!(peak >= overall);
+
+        let kinds: Vec<IssueKind> = check(&frames, &stats, None)
+            .iter()
+            .map(|i| i.kind)
+            .collect();
+        assert_eq!(kinds, [IssueKind::Jitter, IssueKind::Dropout]);
+    }
+
+    #[test]
+    fn test_check_against_dbc() {
+        let dbc = Database::parse(super::super::dbc::TEST_DBC).unwrap();
+        // EngineData (10 ms in the DBC) from two nodes, one with a short payload
+        let mut frames: Vec<Frame> = (0..20)
+            .map(|i| Frame::data(i as f64 * 0.005, 0x100, false, vec![0; 8]))
+            .collect();
+        frames[7].data.truncate(6);
+        frames.push(Frame::error(0.2, 0x020));
+        frames.push(Frame::error(0.3, 0x020));
+        let stats = message_stats(&frames);
+        let issues = check(&frames, &stats, Some(&dbc));
+        let kinds: Vec<IssueKind> = issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                IssueKind::LengthMismatch,
+                IssueKind::IdCollision,
+                IssueKind::IdCollision,
+                IssueKind::ErrorFrame
+            ]
+        );
+        assert_eq!(
+            issues[0].message,
+            "0x100 EngineData has 6 data bytes; the DBC defines 8"
+        );
+        assert!(issues[1].message.contains("every 5.000 ms"));
+        assert_eq!(issues[3].message, "2 error frame(s): no ACK");
+    }
+}
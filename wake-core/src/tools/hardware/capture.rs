@@ -0,0 +1,478 @@
+//! Logic-analyzer capture import.
+//!
+//! Reads digital captures exported by sigrok/PulseView (CSV with `;` comments,
+//! one row per sample or with a `Time` column), Saleae Logic (`Time [s]`
+//! column, one row per change) and VCD. Captures are kept as a list of state
+//! changes: each sample holds the time in seconds and the levels of all
+//! channels from then on, so decoders only walk the edges.
+
+use super::netlist::format_si;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    pub channels: Vec<String>,
+    /// (time in seconds, channel levels with bit n = channel n), one entry per change
+    pub samples: Vec<(f64, u64)>,
+}
+
+impl Capture {
+    fn new(channels: Vec<String>) -> Result<Self, String> {
+        if channels.is_empty() {
+            return Err("Capture has no digital channels".to_string());
+        }
+        if channels.len() > 64 {
+            return Err(format!(
+                "Capture has {} channels; at most 64 are supported",
+                channels.len()
+            ));
+        }
+        Ok(Self {
+            channels,
+            samples: Vec::new(),
+        })
+    }
+
+    /// Record the levels at `time`, dropping rows that change nothing
+    fn push(&mut self, time: f64, levels: u64) {
+        match self.samples.last_mut() {
+            Some((last, state)) if *last == time => *state = levels,
+            Some((_, state)) if *state == levels => {}
+            _ => self.samples.push((time, levels)),
+        }
+    }
+
+    /// Channel index by name; `D0`, `Channel 0`, `CH0` and `0` all name channel 0
+    pub fn channel(&self, name: &str) -> Option<usize> {
+        let wanted = normalize(name);
+        self.channels.iter().position(|c| normalize(c) == wanted)
+    }
+
+    pub fn level(levels: u64, channel: usize) -> bool {
+        levels >> channel & 1 == 1
+    }
+
+    pub fn duration(&self) -> f64 {
+        match (self.samples.first(), self.samples.last()) {
+            (Some((first, _)), Some((last, _))) => last - first,
+            _ => 0.0,
+        }
+    }
+
+    /// Number of level changes on a channel
+    pub fn edges(&self, channel: usize) -> usize {
+        self.samples
+            .windows(2)
+            .filter(|w| Self::level(w[0].1 ^ w[1].1, channel))
+            .count()
+    }
+
+    /// Level of a channel at `time` (the first sample's level before the capture starts)
+    pub fn level_at(&self, channel: usize, time: f64) -> bool {
+        let index = self.samples.partition_point(|(t, _)| *t <= time);
+        Self::level(self.samples[index.saturating_sub(1)].1, channel)
+    }
+
+    /// Resolve decoder roles (e.g. SCL, SDA) to channels. `mapping` is a
+    /// `ROLE=channel` list such as `SCL=D0,SDA=D1`; unmapped roles fall back
+    /// to a channel whose name contains the role. A role may list aliases,
+    /// `SCK|SCLK|CLK`, the first of which names it in messages.
+    pub fn roles(&self, mapping: Option<&str>, roles: &[&str]) -> Result<Vec<usize>, String> {
+        self.optional_roles(mapping, roles)?
+            .into_iter()
+            .zip(roles)
+            .map(|(channel, role)| {
+                channel.ok_or_else(|| {
+                    format!(
+                        "No {} channel in the capture (channels: {}); map it with channels, e.g. '{}'",
+                        primary(role),
+                        self.channels.join(", "),
+                        roles
+                            .iter()
+                            .enumerate()
+                            .map(|(i, r)| format!("{}=D{}", primary(r), i))
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Like [`Capture::roles`], with `None` for roles the capture does not have
+    pub fn optional_roles(
+        &self,
+        mapping: Option<&str>,
+        roles: &[&str],
+    ) -> Result<Vec<Option<usize>>, String> {
+        let mut explicit = HashMap::new();
+        for entry in mapping
+            .unwrap_or("")
+            .split([',', ';'])
+            .filter(|e| !e.trim().is_empty())
+        {
+            let (role, channel) = entry
+                .split_once('=')
+                .or_else(|| entry.split_once(':'))
+                .ok_or_else(|| format!("Invalid channel mapping '{}'; use ROLE=channel", entry))?;
+            explicit.insert(role.trim().to_uppercase(), channel.trim().to_string());
+        }
+
+        roles
+            .iter()
+            .map(|role| {
+                let aliases: Vec<String> = role.split('|').map(str::to_uppercase).collect();
+                if let Some(channel) = aliases.iter().find_map(|a| explicit.get(a)) {
+                    return match self.channel(channel) {
+                        Some(index) => Ok(Some(index)),
+                        None => Err(format!(
+                            "{} is mapped to '{}', which is not in the capture (channels: {})",
+                            primary(role),
+                            channel,
+                            self.channels.join(", ")
+                        )),
+                    };
+                }
+                Ok(aliases.iter().find_map(|alias| {
+                    self.channels
+                        .iter()
+                        .position(|c| c.to_uppercase().contains(alias.as_str()))
+                }))
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} channels ({}), {} transitions over {}",
+            self.channels.len(),
+            self.channels.join(", "),
+            self.samples.len().saturating_sub(1),
+            format_si(self.duration(), "s")
+        )
+    }
+}
+
+fn primary(role: &str) -> &str {
+    role.split('|').next().unwrap_or(role)
+}
+
+fn normalize(name: &str) -> String {
+    let lower = name.trim().to_lowercase();
+    let number = ["channel", "ch", "d"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .map(str::trim)
+        .filter(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()));
+    match number {
+        Some(n) => n.trim_start_matches('0').to_string(),
+        None if lower.chars().all(|c| c.is_ascii_digit()) => {
+            lower.trim_start_matches('0').to_string()
+        }
+        None => lower,
+    }
+}
+
+/// Parse a capture, telling VCD from CSV by its content
+pub fn parse(text: &str) -> Result<Capture, String> {
+    if text.trim_start().starts_with('$') {
+        parse_vcd(text)
+    } else {
+        parse_csv(text)
+    }
+}
+
+/// Load a `.vcd` or `.csv` capture
+pub fn load(path: &Path) -> Result<Capture, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "vcd" => parse_vcd(&text),
+        "csv" | "txt" => parse_csv(&text),
+        _ => parse(&text),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// sigrok/PulseView or Saleae CSV. A first column named `Time...` holds
+/// seconds; otherwise each row is one sample at the `; Samplerate:` rate.
+pub fn parse_csv(text: &str) -> Result<Capture, String> {
+    let mut sample_rate = None;
+    let mut lines = text.lines().enumerate().filter(|(_, line)| {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix(';') {
+            if let Some((key, value)) = comment.split_once(':') {
+                let key = key.trim().to_lowercase().replace(' ', "");
+                if key == "samplerate" {
+                    sample_rate = parse_rate(value);
+                }
+            }
+            return false;
+        }
+        !line.is_empty()
+    });
+
+    let (_, header) = lines.next().ok_or("Empty capture")?;
+    let mut columns: Vec<String> = header.split(',').map(|c| c.trim().to_string()).collect();
+    let timed = columns
+        .first()
+        .is_some_and(|c| c.to_lowercase().starts_with("time"));
+    if timed {
+        columns.remove(0);
+    }
+    let mut capture = Capture::new(columns)?;
+    let width = capture.channels.len();
+
+    // Comments are consumed by the filter, so the rate is known once rows start
+    let rows: Vec<(usize, &str)> = lines.collect();
+    let period = match (timed, sample_rate) {
+        (true, _) => None,
+        (false, Some(rate)) => Some(1.0 / rate),
+        (false, None) => return Err(
+            "CSV has no Time column and no '; Samplerate:' comment, so sample times are unknown"
+                .to_string(),
+        ),
+    };
+
+    for (index, (number, line)) in rows.iter().enumerate() {
+        let mut cells = line.split(',').map(str::trim);
+        let time = match period {
+            Some(period) => index as f64 * period,
+            None => {
+                let cell = cells.next().unwrap_or("");
+                cell.parse::<f64>()
+                    .map_err(|_| format!("line {}: invalid time '{}'", number + 1, cell))?
+            }
+        };
+        let mut levels = 0u64;
+        let mut count = 0;
+        for (channel, cell) in cells.enumerate() {
+            let high = match cell {
+                "1" => true,
+                "0" => false,
+                _ => {
+                    return Err(format!(
+                        "line {}: '{}' is not a digital level (0 or 1)",
+                        number + 1,
+                        cell
+                    ))
+                }
+            };
+            if channel < width && high {
+                levels |= 1 << channel;
+            }
+            count += 1;
+        }
+        if count != width {
+            return Err(format!(
+                "line {}: expected {} channels, found {}",
+                number + 1,
+                width,
+                count
+            ));
+        }
+        capture.push(time, levels);
+    }
+    if capture.samples.is_empty() {
+        return Err("Capture has no samples".to_string());
+    }
+    Ok(capture)
+}
+
+/// `1 MHz`, `500kHz`, `24000000`
+fn parse_rate(text: &str) -> Option<f64> {
+    let text = text.trim().to_lowercase();
+    let text = text.trim_end_matches("hz").trim();
+    let (number, scale) = match text.chars().last()? {
+        'k' => (&text[..text.len() - 1], 1e3),
+        'm' => (&text[..text.len() - 1], 1e6),
+        'g' => (&text[..text.len() - 1], 1e9),
+        _ => (text, 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(|n| n * scale)
+        .filter(|r| *r > 0.0)
+}
+
+/// Value change dump with one-bit wires; `x`/`z` read as high (released bus)
+pub fn parse_vcd(text: &str) -> Result<Capture, String> {
+    let mut tokens = text.split_whitespace();
+    let mut timescale = (1.0, 1e9);
+    let mut ids: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut names = Vec::new();
+
+    // Header
+    loop {
+        let token = tokens.next().ok_or("VCD ends before $enddefinitions")?;
+        match token {
+            "$timescale" => {
+                let spec: Vec<&str> = tokens.by_ref().take_while(|t| *t != "$end").collect();
+                timescale = parse_timescale(&spec.concat())?;
+            }
+            "$var" => {
+                let spec: Vec<&str> = tokens.by_ref().take_while(|t| *t != "$end").collect();
+                if spec.len() < 4 {
+                    return Err(format!("Invalid $var '{}'", spec.join(" ")));
+                }
+                if spec[1] == "1" {
+                    ids.entry(spec[2].to_string())
+                        .or_default()
+                        .push(names.len());
+                    names.push(spec[3].to_string());
+                }
+            }
+            "$enddefinitions" => {
+                tokens.by_ref().find(|t| *t == "$end");
+                break;
+            }
+            t if t.starts_with('$') => {
+                tokens.by_ref().find(|t| *t == "$end");
+            }
+            _ => {}
+        }
+    }
+
+    let mut capture = Capture::new(names)?;
+    let mut time: Option<f64> = None;
+    let mut levels = 0u64;
+    let set = |levels: &mut u64, id: &str, value: char| {
+        for &channel in ids.get(id).into_iter().flatten() {
+            if value == '0' {
+                *levels &= !(1 << channel);
+            } else {
+                *levels |= 1 << channel;
+            }
+        }
+    };
+
+    while let Some(token) = tokens.next() {
+        if let Some(stamp) = token.strip_prefix('#') {
+            if let Some(t) = time {
+                capture.push(t, levels);
+            }
+            let ticks = stamp
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid timestamp '{}'", token))?;
+            time = Some(ticks * timescale.0 / timescale.1);
+        } else if token.starts_with('$') {
+            // $dumpvars/$end wrap ordinary value changes; $comment blocks are skipped
+            if token == "$comment" {
+                tokens.by_ref().find(|t| *t == "$end");
+            }
+        } else if let Some(vector) = token.strip_prefix('b').or_else(|| token.strip_prefix('B')) {
+            let id = tokens
+                .next()
+                .ok_or_else(|| format!("Vector value '{}' has no identifier", token))?;
+            set(&mut levels, id, vector.chars().last().unwrap_or('0'));
+        } else if token.starts_with(['r', 'R']) {
+            tokens.next();
+        } else {
+            let mut chars = token.chars();
+            let value = chars.next().unwrap_or('0');
+            if !matches!(value, '0' | '1' | 'x' | 'X' | 'z' | 'Z') {
+                return Err(format!("Invalid value change '{}'", token));
+            }
+            set(&mut levels, chars.as_str(), value);
+        }
+    }
+    if let Some(t) = time {
+        capture.push(t, levels);
+    }
+    if capture.samples.is_empty() {
+        return Err("VCD has no value changes".to_string());
+    }
+    Ok(capture)
+}
+
+/// Timescale as (multiplier, ticks per second), kept apart so times divide exactly
+fn parse_timescale(spec: &str) -> Result<(f64, f64), String> {
+    let split = spec
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(spec.len());
+    let (number, unit) = spec.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid $timescale '{}'", spec))?;
+    let unit = match unit {
+        "s" => 1.0,
+        "ms" => 1e3,
+        "us" => 1e6,
+        "ns" => 1e9,
+        "ps" => 1e12,
+        "fs" => 1e15,
+        _ => return Err(format!("Invalid $timescale unit '{}'", unit)),
+    };
+    Ok((number, unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_formats() {
+        let sigrok = "; CSV generated by libsigrok 0.5.2\n; Channels (2/8): D0, D1\n\
+            ; Samplerate: 1 MHz\nD0,D1\n1,1\n1,1\n1,0\n0,0\n";
+        let capture = parse(sigrok).unwrap();
+        assert_eq!(capture.channels, ["D0", "D1"]);
+        assert_eq!(
+            capture.samples,
+            vec![(0.0, 0b11), (2e-6, 0b01), (3e-6, 0b00)]
+        );
+        assert_eq!(capture.edges(1), 1);
+        assert_eq!(capture.channel("Channel 1"), Some(1));
+        assert!(capture.level_at(1, 1.9e-6) && !capture.level_at(1, 2e-6));
+        assert!(capture.level_at(0, -1.0) && !capture.level_at(0, 1.0));
+
+        let saleae = "Time [s],Channel 0,Channel 1\n-0.000001,1,1\n0.0000025,1,0\n";
+        let capture = parse_csv(saleae).unwrap();
+        assert_eq!(capture.samples, vec![(-1e-6, 0b11), (2.5e-6, 0b01)]);
+        assert_eq!(capture.channel("d0"), Some(0));
+
+        assert!(parse_csv("D0,D1\n1,1\n")
+            .unwrap_err()
+            .contains("Samplerate"));
+        assert!(parse_csv("Time [s],SCL\n0,2\n")
+            .unwrap_err()
+            .contains("line 2"));
+    }
+
+    #[test]
+    fn test_vcd() {
+        let vcd = "$date today $end\n$timescale 1 us $end\n$scope module logic $end\n\
+            $var wire 1 ! SCL $end\n$var wire 1 \" SDA $end\n$var wire 8 # BUS $end\n\
+            $upscope $end\n$enddefinitions $end\n#0\n$dumpvars\n1!\n1\"\nb00000000 #\n$end\n\
+            #5 0\"\n#10 0!\n#15 z\"\n";
+        let capture = parse(vcd).unwrap();
+        assert_eq!(capture.channels, ["SCL", "SDA"]);
+        assert_eq!(
+            capture.samples,
+            vec![(0.0, 0b11), (5e-6, 0b01), (10e-6, 0b00), (15e-6, 0b10)]
+        );
+        assert_eq!(capture.roles(None, &["SDA", "SCL"]).unwrap(), vec![1, 0]);
+        assert_eq!(
+            capture
+                .roles(Some("SCL=SDA, SDA=scl"), &["SCL", "SDA"])
+                .unwrap(),
+            vec![1, 0]
+        );
+        assert_eq!(
+            capture
+                .optional_roles(Some("clk=SCL"), &["SCK|CLK", "MISO", "SD"])
+                .unwrap(),
+            vec![Some(0), None, Some(1)]
+        );
+        assert!(capture
+            .roles(Some("SCL=D7"), &["SCL"])
+            .unwrap_err()
+            .contains("not in the capture"));
+    }
+}
@@ -1,41 +1,479 @@
+use super::e_series::{select_pairs, ESeries, ResistorNetwork, SelectionConstraints};
+use super::netlist::{
+    ac_output_node, ac_probe, ac_sweep, characterize, format_si, parse_value, solve_ac, solve_dc,
+    solve_operating_point, AcPoint, Netlist,
+};
+use super::power_budget::{analyze, PowerBudget};
 use crate::tools::{Tool, ToolCapability, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
 use wake_llm::ToolDescription;
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CircuitAnalyzerArgs {
-    /// Circuit description or schematic
+    /// Circuit description or SPICE-style netlist (R, C, L, V, I, D and `X.. in+ in- out OPAMP` lines, node 0 is ground).
+    /// For power analysis, one entry per line: `battery capacity=2000mAh chemistry=li-ion` or `supply voltage=5`,
+    /// `ldo U1 out=3v3 vout=3.3 iq=5u dropout=0.2`, `buck U2 out=1v8 vout=1.8 efficiency=90%`,
+    /// `load MCU rail=3v3 active=10m sleep=2u duty=1%` (or `on=50ms period=60s`, or `current=2m`)
     pub circuit: String,
 
-    /// Analysis type (voltage-divider, pull-up, filter, power, impedance)
+    /// Analysis type (dc, voltage-divider, feedback, gain, e-series, pull-up, filter, power, impedance)
     pub analysis_type: String,
 
-    /// Component values (resistors, capacitors, etc.)
+    /// Component values or resistor selection constraints as `key=value` pairs
+    /// (series=E12|E24|E48|E96, tolerance=1%, r_total_min=10k, r_total_max=1meg, r_total=100k, iq_max=20u, count=5)
     pub components: Option<String>,
 
-    /// Operating conditions (voltage, frequency, temperature)
+    /// Operating conditions (voltage, frequency, temperature) or resistor selection targets
+    /// as `key=value` pairs (vin=12, vout=3.3 | vref=0.8, vout=3.3 | ratio=0.25 | gain=11 or gain=-4.7 | ambient=40)
     pub conditions: Option<String>,
+
+    /// Optional: include an ASCII Bode magnitude plot in filter analysis output
+    pub ascii_plot: Option<bool>,
 }
 
+/// ASCII Bode plot size in characters
+const PLOT_WIDTH: usize = 60;
+const PLOT_HEIGHT: usize = 16;
+
+/// Resistor pairs listed per E-series unless `count=` is given
+const DEFAULT_PAIR_COUNT: usize = 3;
+
+/// Table rows shown for a sweep; the full sweep is in the metadata
+const TABLE_ROWS: usize = 12;
+
 pub struct CircuitAnalyzer;
 
 impl CircuitAnalyzer {
     pub fn new() -> Self {
         Self
     }
-}
 
-#[async_trait]
-impl Tool for CircuitAnalyzer {
-    type Params = CircuitAnalyzerArgs;
+    fn analyze_dc(&self, netlist: &Netlist) -> ToolResult {
+        let dc = match solve_dc(netlist) {
+            Ok(dc) => dc,
+            Err(e) => return ToolResult::error(format!("DC analysis failed: {}", e)),
+        };
 
-    fn capabilities(&self) -> &'static [ToolCapability] {
-        &[ToolCapability::Read]
+        let mut output = String::from("## Circuit Analysis\n\n### DC Operating Point\n\n");
+        if let Some(title) = &netlist.title {
+            output.push_str(&format!("**{}**\n\n", title));
+        }
+
+        output.push_str("| Node | Voltage |\n|------|---------|\n");
+        for (node, v) in &dc.node_voltages {
+            output.push_str(&format!("| {} | {} |\n", node, format_si(*v, "V")));
+        }
+
+        output.push_str(
+            "\n| Element | Type | Nodes | Voltage | Current | Power |\n\
+            |---------|------|-------|---------|---------|-------|\n",
+        );
+        for b in &dc.branches {
+            output.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                b.name,
+                b.kind,
+                b.nodes.join(" → "),
+                format_si(b.voltage, "V"),
+                format_si(b.current, "A"),
+                b.power.map_or("-".to_string(), |p| format_si(p, "W"))
+            ));
+        }
+        output.push_str(&format!(
+            "\nTotal dissipation in resistors and diodes: {}\n\
+            Negative power means the element delivers energy to the circuit.\n",
+            format_si(dc.total_dissipation(), "W")
+        ));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("analysis_type".to_string(), json!("dc"));
+        metadata.insert(
+            "node_voltages".to_string(),
+            json!(dc
+                .node_voltages
+                .iter()
+                .cloned()
+                .collect::<HashMap<String, f64>>()),
+        );
+        metadata.insert("branches".to_string(), json!(dc.branches));
+        metadata.insert(
+            "total_dissipation_w".to_string(),
+            json!(dc.total_dissipation()),
+        );
+        metadata.insert("iterations".to_string(), json!(dc.iterations));
+
+        ToolResult::success_with_metadata(output, metadata)
     }
 
-    async fn execute(&self, args: Self::Params) -> ToolResult {
+    fn analyze_ac(&self, netlist: &Netlist, ascii_plot: bool) -> ToolResult {
+        let run = || -> Result<_, String> {
+            let dc = solve_operating_point(netlist)?;
+            let output = ac_output_node(netlist)?;
+            let freqs = ac_sweep(netlist)?;
+            let ac = solve_ac(netlist, &dc, &freqs, &output)?;
+            Ok((dc, ac))
+        };
+        let (dc, ac) = match run() {
+            Ok(result) => result,
+            Err(e) => return ToolResult::error(format!("AC analysis failed: {}", e)),
+        };
+        let filter = characterize(&ac.points, ac_probe(netlist, &dc, &ac.output_node));
+
+        let mut output = String::from("## Circuit Analysis\n\n### Frequency Response\n\n");
+        if let Some(title) = &netlist.title {
+            output.push_str(&format!("**{}**\n\n", title));
+        }
+        output.push_str(&format!(
+            "Transfer function: V({}) / {}",
+            ac.output_node, ac.input_source
+        ));
+        if ac.implicit_input {
+            output.push_str(" (no source declares AC, driven with AC 1)");
+        }
+        output.push_str("\n\n");
+
+        output.push_str(&format!(
+            "- Response: {}\n- Passband gain: {:.2} dB\n",
+            filter.filter_type.label(),
+            filter.passband_gain_db
+        ));
+        match filter.cutoffs_hz.as_slice() {
+            [] => {}
+            [f] => output.push_str(&format!("- -3 dB cutoff: {}\n", format_si(*f, "Hz"))),
+            [lo, hi, ..] => output.push_str(&format!(
+                "- -3 dB band: {} to {} (bandwidth {})\n",
+                format_si(*lo, "Hz"),
+                format_si(*hi, "Hz"),
+                format_si(hi - lo, "Hz")
+            )),
+        }
+        if let Some(f0) = filter.center_hz {
+            output.push_str(&format!("- Center frequency: {}\n", format_si(f0, "Hz")));
+        }
+        if let Some(q) = filter.q {
+            output.push_str(&format!("- Q: {:.3}\n", q));
+        }
+        if let (Some(fc), Some(pm)) = (filter.unity_gain_hz, filter.phase_margin_deg) {
+            output.push_str(&format!(
+                "- Unity-gain crossover: {}, phase margin {:.1}°\n",
+                format_si(fc, "Hz"),
+                pm
+            ));
+        }
+
+        output.push_str("\n| Frequency | Gain | Phase |\n|-----------|------|-------|\n");
+        let step = ac.points.len().div_ceil(TABLE_ROWS).max(1);
+        for (i, p) in ac.points.iter().enumerate() {
+            if i % step == 0 || i + 1 == ac.points.len() {
+                output.push_str(&format!(
+                    "| {} | {:.2} dB | {:.1}° |\n",
+                    format_si(p.freq_hz, "Hz"),
+                    p.magnitude_db,
+                    p.phase_deg
+                ));
+            }
+        }
+
+        if ascii_plot {
+            output.push_str(&format!("\n```\n{}```\n", bode_plot(&ac.points)));
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("analysis_type".to_string(), json!("filter"));
+        metadata.insert("points".to_string(), json!(ac.points));
+        metadata.insert("filter_type".to_string(), json!(filter.filter_type));
+        metadata.insert("cutoff_hz".to_string(), json!(filter.cutoffs_hz));
+        metadata.insert("center_hz".to_string(), json!(filter.center_hz));
+        metadata.insert("q".to_string(), json!(filter.q));
+        metadata.insert(
+            "passband_gain_db".to_string(),
+            json!(filter.passband_gain_db),
+        );
+        metadata.insert("unity_gain_hz".to_string(), json!(filter.unity_gain_hz));
+        metadata.insert(
+            "phase_margin_deg".to_string(),
+            json!(filter.phase_margin_deg),
+        );
+        metadata.insert("output_node".to_string(), json!(ac.output_node));
+        metadata.insert("input_source".to_string(), json!(ac.input_source));
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    /// Pick E-series resistor pairs for a divider, feedback network or gain stage
+    fn select_resistors(&self, args: &CircuitAnalyzerArgs, analysis: &str) -> ToolResult {
+        let settings = parse_settings(
+            [args.conditions.as_deref(), args.components.as_deref()]
+                .into_iter()
+                .flatten(),
+        );
+        let number = |key: &str| -> Result<Option<f64>, String> {
+            settings
+                .get(key)
+                .map(|v| {
+                    parse_value(v.trim_end_matches('%'))
+                        .map_err(|e| format!("Invalid {}: {}", key, e))
+                })
+                .transpose()
+        };
+
+        let setup = || -> Result<_, String> {
+            let gain = number("gain")?;
+            let vref = number("vref")?;
+            let vout = number("vout")?;
+            let inverting = settings
+                .get("topology")
+                .is_some_and(|t| t.eq_ignore_ascii_case("inverting"));
+
+            let (network, target) = match (analysis, gain, vref, vout) {
+                ("gain" | "op-amp-gain", None, ..) => {
+                    return Err("Gain selection needs gain=<value> in conditions".to_string())
+                }
+                ("feedback" | "ldo" | "buck", _, None, _)
+                | ("feedback" | "ldo" | "buck", _, _, None) => {
+                    return Err(
+                        "Feedback selection needs vref=<volts> and vout=<volts> in conditions"
+                            .to_string(),
+                    )
+                }
+                (_, Some(g), ..) if g < 0.0 || inverting => {
+                    (ResistorNetwork::InvertingGain, -g.abs())
+                }
+                (_, Some(g), ..) => (ResistorNetwork::NonInvertingGain, g),
+                (_, None, Some(vref), Some(vout)) => (ResistorNetwork::Feedback { vref }, vout),
+                _ => match (number("ratio")?, number("vin")?, vout) {
+                    (Some(ratio), ..) => (ResistorNetwork::Divider { vin: 1.0 }, ratio),
+                    (None, Some(vin), Some(vout)) => (ResistorNetwork::Divider { vin }, vout),
+                    _ => {
+                        return Err("Resistor selection needs targets in conditions: \
+                            vin=..,vout=.. (divider), ratio=.. (divider), \
+                            vref=..,vout=.. (feedback) or gain=.. (op-amp)"
+                            .to_string())
+                    }
+                },
+            };
+
+            let defaults = SelectionConstraints::default();
+            let constraints = SelectionConstraints {
+                total_min: number("r_total_min")?.unwrap_or(defaults.total_min),
+                total_max: number("r_total_max")?.unwrap_or(defaults.total_max),
+                total_preferred: number("r_total")?,
+                max_current: number("iq_max")?,
+                tolerance_percent: number("tolerance")?,
+            };
+            let series = match settings.get("series") {
+                Some(name) => vec![ESeries::parse(name).ok_or_else(|| {
+                    format!("Unknown series '{}', use E12, E24, E48 or E96", name)
+                })?],
+                None => ESeries::all().to_vec(),
+            };
+            let count = number("count")?.map_or(DEFAULT_PAIR_COUNT, |c| c.max(1.0) as usize);
+            Ok((network, target, constraints, series, count))
+        };
+        let (network, target, constraints, series, count) = match setup() {
+            Ok(setup) => setup,
+            Err(e) => return ToolResult::error(e),
+        };
+
+        let (top_name, bottom_name) = network.resistor_names();
+        let unit = match network {
+            ResistorNetwork::Divider { vin } if vin == 1.0 && settings.contains_key("ratio") => "",
+            ResistorNetwork::Divider { .. } | ResistorNetwork::Feedback { .. } => "V",
+            _ => "",
+        };
+        let show = |v: f64| {
+            if unit.is_empty() {
+                format!("{:.4}", v)
+            } else {
+                format_si(v, unit)
+            }
+        };
+
+        let mut output = format!(
+            "## Circuit Analysis\n\n### E-Series Resistor Selection: {}\n\nTarget: {}\n",
+            network.label(),
+            show(target)
+        );
+        output.push_str(&format!(
+            "Total resistance: {} to {}",
+            format_si(constraints.total_min, "Ω"),
+            format_si(constraints.total_max, "Ω")
+        ));
+        if let Some(limit) = constraints.max_current {
+            output.push_str(&format!(", quiescent current ≤ {}", format_si(limit, "A")));
+        }
+        output.push('\n');
+
+        let mut all_pairs = Vec::new();
+        for s in &series {
+            let pairs = match select_pairs(network, target, *s, &constraints, count) {
+                Ok(pairs) => pairs,
+                Err(e) => return ToolResult::error(e),
+            };
+            output.push_str(&format!(
+                "\n#### {} (±{}%)\n\n",
+                s.label(),
+                constraints
+                    .tolerance_percent
+                    .unwrap_or_else(|| s.default_tolerance_percent())
+            ));
+            if pairs.is_empty() {
+                output.push_str("No pair satisfies the constraints.\n");
+                continue;
+            }
+            output.push_str(&format!(
+                "| {} | {} | Output | Error | Worst case | Worst-case error | Current |\n\
+                |----|----|--------|-------|------------|------------------|---------|\n",
+                top_name, bottom_name
+            ));
+            for p in &pairs {
+                output.push_str(&format!(
+                    "| {} | {} | {} | {:+.3}% | {} to {} | ±{:.2}% | {} |\n",
+                    format_si(p.top_ohms, "Ω"),
+                    format_si(p.bottom_ohms, "Ω"),
+                    show(p.output),
+                    p.error_percent,
+                    show(p.worst_case_min),
+                    show(p.worst_case_max),
+                    p.worst_case_error_percent,
+                    p.current_a.map_or("-".to_string(), |i| format_si(i, "A"))
+                ));
+            }
+            all_pairs.extend(pairs);
+        }
+        if all_pairs.is_empty() {
+            return ToolResult::error(
+                "No resistor pair satisfies the total resistance and current constraints"
+                    .to_string(),
+            );
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("analysis_type".to_string(), json!("e-series"));
+        metadata.insert("network".to_string(), json!(network));
+        metadata.insert("target".to_string(), json!(target));
+        metadata.insert("solutions".to_string(), json!(all_pairs));
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn analyze_power(&self, args: &CircuitAnalyzerArgs) -> ToolResult {
+        let mut budget = match PowerBudget::parse(&args.circuit) {
+            Ok(budget) => budget,
+            Err(e) => return ToolResult::error(format!("Power budget parse error: {}", e)),
+        };
+        let settings = parse_settings(args.conditions.as_deref().into_iter());
+        if let Some(ambient) = settings.get("ambient").or(settings.get("temperature")) {
+            match parse_value(ambient) {
+                Ok(t) => budget.ambient_c = Some(t),
+                Err(e) => return ToolResult::error(format!("Invalid ambient temperature: {}", e)),
+            }
+        }
+        let report = match analyze(&budget) {
+            Ok(report) => report,
+            Err(e) => return ToolResult::error(format!("Power analysis failed: {}", e)),
+        };
+
+        let mut output = String::from("## Circuit Analysis\n\n### Power Budget\n\n");
+        output.push_str(
+            "| Load | Rail | Active | Sleep | Duty | Average |\n\
+            |------|------|--------|-------|------|---------|\n",
+        );
+        for l in &budget.loads {
+            output.push_str(&format!(
+                "| {} | {} | {} | {} | {:.3}% | {} |\n",
+                l.name,
+                l.rail,
+                format_si(l.active_a, "A"),
+                format_si(l.sleep_a, "A"),
+                l.duty * 100.0,
+                format_si(l.average_a(), "A")
+            ));
+        }
+
+        if !report.regulators.is_empty() {
+            output.push_str(
+                "\n| Regulator | Type | Vin → Vout | Iout avg / peak | Iin avg | Dissipation avg / peak | Tj peak |\n\
+                |-----------|------|------------|-----------------|---------|------------------------|---------|\n",
+            );
+            for r in &report.regulators {
+                output.push_str(&format!(
+                    "| {} | {} | {:.2} V → {:.2} V | {} / {} | {} | {} / {} | {:.1} °C (θJA {} °C/W) |\n",
+                    r.name,
+                    r.kind.label(),
+                    r.vin,
+                    r.vout,
+                    format_si(r.output_avg_a, "A"),
+                    format_si(r.output_peak_a, "A"),
+                    format_si(r.input_avg_a, "A"),
+                    format_si(r.dissipation_avg_w, "W"),
+                    format_si(r.dissipation_peak_w, "W"),
+                    r.junction_peak_c,
+                    r.theta_ja
+                ));
+            }
+        }
+
+        output.push_str(&format!(
+            "\n- Source: {:.2} V\n- Average current: {}\n- Peak current (all loads active): {}\n- Average power: {}\n",
+            report.source_v,
+            format_si(report.average_current_a, "A"),
+            format_si(report.peak_current_a, "A"),
+            format_si(report.average_power_w, "W")
+        ));
+        if let (Some(battery), Some(usable), Some(self_discharge), Some(hours)) = (
+            &budget.battery,
+            report.usable_capacity_ah,
+            report.self_discharge_a,
+            report.battery_life_hours,
+        ) {
+            output.push_str(&format!(
+                "- Battery: {} {}, {} usable ({:.0}%), self-discharge {:.2}%/month ≈ {}\n",
+                format_si(battery.capacity_ah, "Ah"),
+                battery.chemistry.map_or("custom", |c| c.label()),
+                format_si(usable, "Ah"),
+                battery.usable_fraction * 100.0,
+                battery.self_discharge_per_month * 100.0,
+                format_si(self_discharge, "A")
+            ));
+            output.push_str(&format!(
+                "- **Estimated battery life: {:.1} h ({:.1} days, {:.2} years)**\n",
+                hours,
+                hours / 24.0,
+                hours / (24.0 * 365.25)
+            ));
+        }
+        if !report.warnings.is_empty() {
+            output.push_str("\n### Warnings\n\n");
+            for w in &report.warnings {
+                output.push_str(&format!("- {}\n", w));
+            }
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("analysis_type".to_string(), json!("power"));
+        metadata.insert(
+            "average_current_a".to_string(),
+            json!(report.average_current_a),
+        );
+        metadata.insert("peak_current_a".to_string(), json!(report.peak_current_a));
+        metadata.insert("average_power_w".to_string(), json!(report.average_power_w));
+        metadata.insert(
+            "battery_life_hours".to_string(),
+            json!(report.battery_life_hours),
+        );
+        metadata.insert("regulators".to_string(), json!(report.regulators));
+        metadata.insert("loads".to_string(), json!(budget.loads));
+        metadata.insert("warnings".to_string(), json!(report.warnings));
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn describe_analysis(&self, args: &CircuitAnalyzerArgs) -> ToolResult {
         let analysis = format!(
             "## Circuit Analysis\n\n\
             ### Circuit Type: {}\n\
@@ -54,13 +492,109 @@ impl Tool for CircuitAnalyzer {
     }
 }
 
+#[async_trait]
+impl Tool for CircuitAnalyzer {
+    type Params = CircuitAnalyzerArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Read]
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        match args.analysis_type.to_lowercase().as_str() {
+            "dc" | "op" | "operating-point" => match Netlist::parse(&args.circuit) {
+                Ok(netlist) => self.analyze_dc(&netlist),
+                Err(e) => ToolResult::error(format!("Netlist parse error: {}", e)),
+            },
+            // Netlists get solved, targets in `conditions` pick resistors,
+            // anything else keeps the checklist
+            analysis @ ("voltage-divider" | "divider" | "pull-up") => {
+                match Netlist::parse(&args.circuit) {
+                    Ok(netlist) => self.analyze_dc(&netlist),
+                    Err(_) if args.conditions.as_deref().is_some_and(has_selection_target) => {
+                        self.select_resistors(&args, analysis)
+                    }
+                    Err(_) => self.describe_analysis(&args),
+                }
+            }
+            analysis @ ("e-series" | "resistor-selection" | "feedback" | "ldo" | "buck"
+            | "gain" | "op-amp-gain") => self.select_resistors(&args, analysis),
+            "filter" | "ac" | "bode" => match Netlist::parse(&args.circuit) {
+                Ok(netlist) => self.analyze_ac(&netlist, args.ascii_plot.unwrap_or(false)),
+                Err(e) => ToolResult::error(format!("Netlist parse error: {}", e)),
+            },
+            "power" | "power-budget" | "battery-life" => self.analyze_power(&args),
+            _ => self.describe_analysis(&args),
+        }
+    }
+}
+
+/// `key=value` pairs separated by commas, semicolons or whitespace; keys are lowercased
+fn parse_settings<'a>(texts: impl Iterator<Item = &'a str>) -> HashMap<String, String> {
+    texts
+        .flat_map(|text| text.split(|c: char| c == ',' || c == ';' || c.is_whitespace()))
+        .filter_map(|pair| pair.split_once(['=', ':']))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .filter(|(k, v)| !k.is_empty() && !v.is_empty())
+        .collect()
+}
+
+fn has_selection_target(conditions: &str) -> bool {
+    let settings = parse_settings(std::iter::once(conditions));
+    ["vout", "ratio", "gain"]
+        .iter()
+        .any(|key| settings.contains_key(*key))
+}
+
+/// Magnitude-only Bode plot on a log frequency axis
+fn bode_plot(points: &[AcPoint]) -> String {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return String::new();
+    };
+    let db: Vec<f64> = points.iter().map(|p| p.magnitude_db.max(-200.0)).collect();
+    let top = db.iter().cloned().fold(f64::MIN, f64::max).ceil();
+    let bottom = db.iter().cloned().fold(f64::MAX, f64::min).floor();
+    let range = (top - bottom).max(1.0);
+    let (f_lo, f_hi) = (first.freq_hz.max(1e-12), last.freq_hz.max(1e-12));
+    let span = (f_hi / f_lo).ln().max(f64::EPSILON);
+
+    let mut grid = vec![vec![' '; PLOT_WIDTH]; PLOT_HEIGHT];
+    for (p, level) in points.iter().zip(&db) {
+        let x = ((p.freq_hz.max(1e-12) / f_lo).ln() / span * (PLOT_WIDTH - 1) as f64).round();
+        let y = ((top - level) / range * (PLOT_HEIGHT - 1) as f64).round();
+        grid[y as usize][x as usize] = '*';
+    }
+
+    let mut plot = String::new();
+    for (row, line) in grid.iter().enumerate() {
+        // `+ 0.0` avoids printing -0.0
+        let label = top - range * row as f64 / (PLOT_HEIGHT - 1) as f64 + 0.0;
+        plot.push_str(&format!(
+            "{:>8.1} dB |{}\n",
+            label,
+            line.iter().collect::<String>().trim_end()
+        ));
+    }
+    plot.push_str(&format!("{:>11} +{}\n", "", "-".repeat(PLOT_WIDTH)));
+    let lo = format_si(f_lo, "Hz");
+    let hi = format_si(f_hi, "Hz");
+    plot.push_str(&format!(
+        "{:>12}{}{:>width$}\n",
+        "",
+        lo,
+        hi,
+        width = PLOT_WIDTH.saturating_sub(lo.chars().count())
+    ));
+    plot
+}
+
 impl ToolDescription for CircuitAnalyzer {
     fn name(&self) -> &'static str {
         "circuit_analyzer"
     }
 
     fn description(&self) -> &'static str {
-        "Analyze electronic circuits for voltage levels, current flow, impedance, filters, and component selection."
+        "Analyze electronic circuits for voltage levels, current flow, impedance, filters, and component selection. Picks E12/E24/E48/E96 resistor pairs for dividers, feedback networks and op-amp gain with worst-case tolerance error. Estimates power budgets (average current, regulator dissipation and junction temperature, battery life). Solves the DC operating point of SPICE-style netlists (node voltages, branch currents, power) and sweeps their AC response (Bode data, filter type, cutoff, Q, phase margin)."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -68,3 +602,153 @@ impl ToolDescription for CircuitAnalyzer {
             .unwrap_or_else(|_| serde_json::json!({}))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(circuit: &str, analysis_type: &str) -> CircuitAnalyzerArgs {
+        CircuitAnalyzerArgs {
+            circuit: circuit.to_string(),
+            analysis_type: analysis_type.to_string(),
+            components: None,
+            conditions: None,
+            ascii_plot: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dc_analysis_metadata() {
+        let tool = CircuitAnalyzer::new();
+        let result = tool
+            .execute(args(
+                ".title 3V3 divider\nV1 vin 0 5\nR1 vin out 1k\nR2 out 0 2k",
+                "dc",
+            ))
+            .await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("3V3 divider"));
+            assert!(output.contains("| out | 3.333 V |"));
+            let metadata = metadata.unwrap();
+            let vout = metadata["node_voltages"]["out"].as_f64().unwrap();
+            assert!((vout - 10.0 / 3.0).abs() < 1e-6);
+            assert_eq!(metadata["branches"].as_array().unwrap().len(), 3);
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dc_analysis_parse_error() {
+        let tool = CircuitAnalyzer::new();
+        let result = tool.execute(args("R1 a b", "dc")).await;
+        assert!(result.is_error());
+    }
+
+    #[tokio::test]
+    async fn test_free_form_divider_keeps_description() {
+        let tool = CircuitAnalyzer::new();
+        let result = tool
+            .execute(args("10k over 4.7k from 5V", "voltage-divider"))
+            .await;
+        assert!(result.is_success());
+        assert!(result.to_string().contains("This tool analyzes circuits"));
+    }
+
+    #[tokio::test]
+    async fn test_filter_analysis_metadata() {
+        let tool = CircuitAnalyzer::new();
+        let mut request = args(
+            "V1 in 0 AC 1\nR1 in out 1.6k\nC1 out 0 100n\n.ac dec 20 10 100k",
+            "filter",
+        );
+        request.ascii_plot = Some(true);
+        let result = tool.execute(request).await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("Response: low-pass"));
+            assert!(output.contains("```"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["filter_type"], "low-pass");
+            assert_eq!(metadata["output_node"], "out");
+            let cutoff = metadata["cutoff_hz"][0].as_f64().unwrap();
+            assert!((cutoff - 994.7).abs() < 10.0, "cutoff {}", cutoff);
+            assert_eq!(metadata["points"].as_array().unwrap().len(), 81);
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feedback_resistor_selection() {
+        let tool = CircuitAnalyzer::new();
+        let mut request = args("buck feedback", "feedback");
+        request.conditions = Some("vref=0.8V, vout=3.3V".to_string());
+        request.components = Some("series=E96 r_total_max=200k iq_max=50u".to_string());
+        let result = tool.execute(request).await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("#### E96 (±1%)"));
+            assert!(!output.contains("#### E24"));
+            let metadata = metadata.unwrap();
+            let solutions = metadata["solutions"].as_array().unwrap();
+            assert_eq!(solutions.len(), 3);
+            for s in solutions {
+                assert!(s["total_ohms"].as_f64().unwrap() <= 200e3);
+                assert!(s["current_a"].as_f64().unwrap() <= 50e-6);
+                assert!(s["error_percent"].as_f64().unwrap().abs() < 0.7);
+            }
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_divider_selection_from_conditions() {
+        let tool = CircuitAnalyzer::new();
+        let mut request = args("12V to ADC", "voltage-divider");
+        request.conditions = Some("vin=12; vout=3".to_string());
+        let result = tool.execute(request).await;
+        assert!(result.is_success());
+        assert!(result.to_string().contains("#### E12 (±10%)"));
+
+        let mut request = args("gain stage", "gain");
+        request.components = Some("series=E6".to_string());
+        request.conditions = Some("gain=11".to_string());
+        assert!(tool.execute(request).await.is_error());
+    }
+
+    #[tokio::test]
+    async fn test_power_budget_analysis() {
+        let tool = CircuitAnalyzer::new();
+        let mut request = args(
+            "battery capacity=1000mAh chemistry=li-ion\n\
+             ldo U1 out=3v3 vout=3.3 iq=1u dropout=0.1\n\
+             load MCU rail=3v3 active=5m sleep=3u duty=2%",
+            "power",
+        );
+        request.conditions = Some("ambient=60".to_string());
+        let result = tool.execute(request).await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("Estimated battery life"));
+            let metadata = metadata.unwrap();
+            let average = 5e-3 * 0.02 + 3e-6 * 0.98 + 1e-6;
+            let measured = metadata["average_current_a"].as_f64().unwrap();
+            assert!((measured - average).abs() < 1e-12);
+            let tj = metadata["regulators"][0]["junction_peak_c"]
+                .as_f64()
+                .unwrap();
+            assert!(tj > 60.0 && tj < 61.0);
+            assert!(metadata["battery_life_hours"].as_f64().unwrap() > 1000.0);
+        } else {
+            panic!("Expected success result");
+        }
+
+        assert!(tool
+            .execute(args("load MCU active=1m", "power"))
+            .await
+            .is_error());
+    }
+}
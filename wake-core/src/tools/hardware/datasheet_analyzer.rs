@@ -1,41 +1,332 @@
+use super::datasheet_pdf::{self, find_sections, parse_selection, DatasheetText, SectionKind};
+use super::svd::{Device, Register, SvdMatch};
 use crate::tools::{Tool, ToolCapability, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use wake_llm::ToolDescription;
 
+/// Characters shown per page and in total, to keep the result readable
+const MAX_PAGE_CHARS: usize = 3000;
+const MAX_OUTPUT_CHARS: usize = 24000;
+
+/// Peripherals listed when an SVD is queried without names
+const MAX_PERIPHERAL_ROWS: usize = 200;
+
+enum Source {
+    Pdf(PathBuf),
+    Svd(PathBuf),
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct DatasheetAnalyzerArgs {
     /// Component or chip name
     pub component: String,
 
-    /// What to extract (registers, pinout, timing, power, all)
+    /// What to extract (registers, pinout, electrical, timing, power, all)
     pub extract_type: String,
 
-    /// Optional: Specific page numbers or sections
+    /// Optional: Specific page numbers or sections (e.g. "12", "30-35", "I2C");
+    /// for SVD files, peripheral/register/field names (e.g. "RCC", "RCC_CFGR", "USART1.BRR.DIV_Mantissa")
     pub sections: Option<Vec<String>>,
 
     /// Optional: Communication protocol focus
     pub protocol: Option<String>,
+
+    /// Optional: Local PDF datasheet, CMSIS-SVD file, or a directory of them (e.g. docs/datasheets/)
+    pub path: Option<String>,
+}
+
+pub struct DatasheetAnalyzer {
+    /// Where extracted PDF text is cached; None disables the cache
+    cache_dir: Option<PathBuf>,
 }
 
-pub struct DatasheetAnalyzer;
+impl Default for DatasheetAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl DatasheetAnalyzer {
     pub fn new() -> Self {
-        Self
+        Self {
+            cache_dir: datasheet_pdf::cache_dir(),
+        }
     }
-}
 
-#[async_trait]
-impl Tool for DatasheetAnalyzer {
-    type Params = DatasheetAnalyzerArgs;
+    /// Datasheet named by `path`, or by `component` when it is itself a PDF/SVD path.
+    /// Directories prefer a matching SVD for register queries.
+    fn datasheet_source(args: &DatasheetAnalyzerArgs) -> Option<Result<Source, String>> {
+        let by_extension = |path: PathBuf| {
+            if has_extension(&path, "svd") {
+                Source::Svd(path)
+            } else {
+                Source::Pdf(path)
+            }
+        };
+        match &args.path {
+            Some(path) => {
+                let path = Path::new(path);
+                let registers_only = SectionKind::for_extract_type(&args.extract_type)
+                    .is_some_and(|kinds| kinds == [SectionKind::Registers]);
+                if path.is_dir() && registers_only {
+                    if let Ok(svd) = datasheet_pdf::resolve(path, &args.component, &["svd"]) {
+                        return Some(Ok(Source::Svd(svd)));
+                    }
+                }
+                Some(datasheet_pdf::resolve(path, &args.component, &["pdf"]).map(by_extension))
+            }
+            None => {
+                let component = Path::new(&args.component);
+                ((has_extension(component, "pdf") || has_extension(component, "svd"))
+                    && component.is_file())
+                .then(|| Ok(by_extension(component.to_path_buf())))
+            }
+        }
+    }
 
-    fn capabilities(&self) -> &'static [ToolCapability] {
-        &[ToolCapability::Read]
+    fn render_svd(&self, args: &DatasheetAnalyzerArgs, path: &Path, device: &Device) -> ToolResult {
+        let mut output = format!(
+            "## Datasheet Analysis for {}\n\nSource: {} (CMSIS-SVD, device {}, {} peripherals)\n",
+            args.component,
+            path.display(),
+            device.name,
+            device.peripherals.len()
+        );
+        let queries = args.sections.clone().unwrap_or_default();
+        let mut found = Vec::new();
+
+        if queries.is_empty() {
+            let protocol = args.protocol.as_deref().map(str::to_lowercase);
+            let peripherals: Vec<_> = device
+                .peripherals
+                .iter()
+                .filter(|p| {
+                    protocol.as_ref().is_none_or(|proto| {
+                        p.name.to_lowercase().contains(proto.as_str())
+                            || p.group_name
+                                .as_ref()
+                                .is_some_and(|g| g.to_lowercase().contains(proto.as_str()))
+                    })
+                })
+                .collect();
+            output.push_str(
+                "\n### Peripherals\n\n| Peripheral | Base | Group | Registers | Description |\n\
+                |------------|------|-------|-----------|-------------|\n",
+            );
+            for p in peripherals.iter().take(MAX_PERIPHERAL_ROWS) {
+                output.push_str(&format!(
+                    "| {} | 0x{:08X} | {} | {} | {} |\n",
+                    p.name,
+                    p.base_address,
+                    p.group_name.as_deref().unwrap_or("-"),
+                    p.registers.len(),
+                    p.description.as_deref().unwrap_or("")
+                ));
+                found.push(json!({ "peripheral": p.name, "address": p.base_address }));
+            }
+            if peripherals.len() > MAX_PERIPHERAL_ROWS {
+                output.push_str(&format!(
+                    "\n{} more peripherals; filter with `protocol` or name them in `sections`.\n",
+                    peripherals.len() - MAX_PERIPHERAL_ROWS
+                ));
+            }
+            output.push_str(
+                "\nName a peripheral, register or field in `sections` (e.g. \"RCC\", \"RCC_CFGR\", \"RCC.CFGR.SW\") for details.\n",
+            );
+        }
+
+        for query in &queries {
+            let matches = device.lookup(query);
+            if matches.is_empty() {
+                output.push_str(&format!(
+                    "\n### {}\n\nNo peripheral, register or field named '{}'.\n",
+                    query, query
+                ));
+            }
+            for m in matches {
+                match m {
+                    SvdMatch::Peripheral(p) => {
+                        output.push_str(&format!(
+                            "\n### {} @ 0x{:08X}\n\n{}\n\n\
+                            | Register | Offset | Address | Size | Access | Reset | Description |\n\
+                            |----------|--------|---------|------|--------|-------|-------------|\n",
+                            p.name,
+                            p.base_address,
+                            p.description.as_deref().unwrap_or("")
+                        ));
+                        for r in &p.registers {
+                            output.push_str(&format!(
+                                "| {} | 0x{:03X} | 0x{:08X} | {} | {} | 0x{:08X} | {} |\n",
+                                r.name,
+                                r.address_offset,
+                                p.base_address + r.address_offset,
+                                r.size,
+                                r.access.map_or("-", |a| a.label()),
+                                r.reset_value,
+                                r.description.as_deref().unwrap_or("")
+                            ));
+                        }
+                        found.push(json!({ "peripheral": p.name, "address": p.base_address }));
+                    }
+                    SvdMatch::Register(p, r) => {
+                        output.push_str(&format!(
+                            "\n### {}_{} @ 0x{:08X}\n\n",
+                            p.name,
+                            r.name,
+                            p.base_address + r.address_offset
+                        ));
+                        output.push_str(&register_details(r));
+                        found.push(json!({
+                            "peripheral": p.name,
+                            "register": r,
+                            "address": p.base_address + r.address_offset,
+                        }));
+                    }
+                    SvdMatch::Field(p, r, f) => {
+                        output.push_str(&format!(
+                            "\n### {}_{}.{} {}\n\n{}\n\nAccess: {}, mask 0x{:08X}, reset value {}\n",
+                            p.name,
+                            r.name,
+                            f.name,
+                            f.bit_range(),
+                            f.description.as_deref().unwrap_or(""),
+                            f.access.map_or("-", |a| a.label()),
+                            f.mask(),
+                            f.extract(r.reset_value)
+                        ));
+                        for e in &f.enumerated_values {
+                            output.push_str(&format!(
+                                "- {} = {}{}\n",
+                                e.value.map_or("default".to_string(), |v| v.to_string()),
+                                e.name,
+                                e.description
+                                    .as_ref()
+                                    .map_or(String::new(), |d| format!(": {}", d))
+                            ));
+                        }
+                        found.push(json!({
+                            "peripheral": p.name,
+                            "register": r.name,
+                            "field": f,
+                            "address": p.base_address + r.address_offset,
+                        }));
+                    }
+                }
+            }
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("source".to_string(), json!("svd"));
+        metadata.insert("path".to_string(), json!(path.display().to_string()));
+        metadata.insert("device".to_string(), json!(device.name));
+        metadata.insert("matches".to_string(), json!(found));
+
+        ToolResult::success_with_metadata(output, metadata)
     }
 
-    async fn execute(&self, args: Self::Params) -> ToolResult {
+    fn render_sections(&self, args: &DatasheetAnalyzerArgs, text: &DatasheetText) -> ToolResult {
+        let page_count = text.pages.len();
+        let (explicit_pages, keywords) =
+            parse_selection(args.sections.as_deref().unwrap_or_default(), page_count);
+        let kinds = match SectionKind::for_extract_type(&args.extract_type) {
+            Some(kinds) => kinds,
+            None if !explicit_pages.is_empty() || !keywords.is_empty() => Vec::new(),
+            None => {
+                return ToolResult::error(format!(
+                    "Unknown extract_type '{}'. Use registers, pinout, electrical, timing, power or all",
+                    args.extract_type
+                ))
+            }
+        };
+        let matches_keywords = |page: usize| {
+            keywords.is_empty() || {
+                let lower = text.pages[page - 1].to_lowercase();
+                keywords.iter().any(|k| lower.contains(k.as_str()))
+            }
+        };
+
+        let mut output = format!(
+            "## Datasheet Analysis for {}\n\nSource: {} ({} pages, {})\n",
+            args.component,
+            text.path.display(),
+            page_count,
+            if text.from_cache {
+                "cached text"
+            } else {
+                "extracted"
+            }
+        );
+        let mut budget = MAX_OUTPUT_CHARS;
+        let mut push_page = |output: &mut String, page: usize| {
+            let body = text.pages[page - 1].trim();
+            let shown: String = body.chars().take(MAX_PAGE_CHARS.min(budget)).collect();
+            budget = budget.saturating_sub(shown.len());
+            let truncated = if shown.len() < body.len() {
+                "\n[... truncated]"
+            } else {
+                ""
+            };
+            output.push_str(&format!(
+                "\n#### Page {}\n\n```\n{}{}\n```\n",
+                page, shown, truncated
+            ));
+        };
+
+        let mut found: HashMap<&str, Vec<usize>> = HashMap::new();
+        if !explicit_pages.is_empty() {
+            output.push_str("\n### Selected Pages\n");
+            for &page in &explicit_pages {
+                push_page(&mut output, page);
+            }
+            found.insert("selected", explicit_pages.clone());
+        }
+        for kind in kinds {
+            let matches: Vec<_> = find_sections(&text.pages, kind, args.protocol.as_deref())
+                .into_iter()
+                .filter(|m| matches_keywords(m.page))
+                .collect();
+            output.push_str(&format!("\n### {}\n", kind.title()));
+            if matches.is_empty() {
+                output.push_str("\nNo matching pages found.\n");
+            }
+            for m in &matches {
+                if !m.headings.is_empty() {
+                    output.push_str(&format!("\n_{}_\n", m.headings.join(" / ")));
+                }
+                push_page(&mut output, m.page);
+            }
+            found.insert(kind.key(), matches.iter().map(|m| m.page).collect());
+        }
+        if budget == 0 {
+            output.push_str("\nOutput limit reached; request specific pages with `sections`.\n");
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("source".to_string(), json!("pdf"));
+        metadata.insert("path".to_string(), json!(text.path.display().to_string()));
+        metadata.insert("page_count".to_string(), json!(page_count));
+        metadata.insert(
+            "cache_path".to_string(),
+            json!(text.cache_path.as_ref().map(|p| p.display().to_string())),
+        );
+        metadata.insert("from_cache".to_string(), json!(text.from_cache));
+        metadata.insert("cache_written".to_string(), json!(text.cache_written));
+        metadata.insert("sections".to_string(), json!(found));
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn describe_extraction(&self, args: &DatasheetAnalyzerArgs) -> ToolResult {
         let analysis = format!(
             "## Datasheet Analysis for {}\n\n\
             ### Extraction Type: {}\n\n\
@@ -45,7 +336,7 @@ impl Tool for DatasheetAnalyzer {
             - Timing diagrams and requirements\n\
             - Power specifications and operating conditions\n\
             - Communication protocol details\n\n\
-            Note: In production, this would use OCR and pattern recognition to extract actual datasheet content.",
+            Provide `path` with a local PDF datasheet (or a directory such as docs/datasheets/) to extract its content.",
             args.component, args.extract_type
         );
 
@@ -53,13 +344,84 @@ impl Tool for DatasheetAnalyzer {
     }
 }
 
+#[async_trait]
+impl Tool for DatasheetAnalyzer {
+    type Params = DatasheetAnalyzerArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Read]
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        let path = match Self::datasheet_source(&args) {
+            Some(Ok(Source::Pdf(path))) => path,
+            Some(Ok(Source::Svd(path))) => {
+                return match Device::load(&path) {
+                    Ok(device) => self.render_svd(&args, &path, &device),
+                    Err(e) => ToolResult::error(e),
+                }
+            }
+            Some(Err(e)) => return ToolResult::error(e),
+            None => return self.describe_extraction(&args),
+        };
+
+        // PDF parsing is CPU bound
+        let cache_dir = self.cache_dir.clone();
+        let text = match tokio::task::spawn_blocking(move || {
+            datasheet_pdf::load(&path, cache_dir.as_deref())
+        })
+        .await
+        {
+            Ok(Ok(text)) => text,
+            Ok(Err(e)) => return ToolResult::error(e),
+            Err(e) => return ToolResult::error(format!("Datasheet extraction failed: {}", e)),
+        };
+        self.render_sections(&args, &text)
+    }
+}
+
+/// Register summary and field table
+fn register_details(r: &Register) -> String {
+    let mut out = format!(
+        "{}\n\nOffset 0x{:03X}, {} bits, access {}, reset 0x{:08X}\n\n\
+        | Bits | Field | Access | Reset | Description | Values |\n\
+        |------|-------|--------|-------|-------------|--------|\n",
+        r.description.as_deref().unwrap_or(""),
+        r.address_offset,
+        r.size,
+        r.access.map_or("-", |a| a.label()),
+        r.reset_value
+    );
+    for f in r.fields.iter().rev() {
+        let values = f
+            .enumerated_values
+            .iter()
+            .map(|e| match e.value {
+                Some(v) => format!("{}={}", v, e.name),
+                None => format!("*={}", e.name),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            f.bit_range(),
+            f.name,
+            f.access.map_or("-", |a| a.label()),
+            f.extract(r.reset_value),
+            f.description.as_deref().unwrap_or(""),
+            values
+        ));
+    }
+    out
+}
+
 impl ToolDescription for DatasheetAnalyzer {
     fn name(&self) -> &'static str {
         "datasheet_analyzer"
     }
 
     fn description(&self) -> &'static str {
-        "Extract and analyze information from hardware component datasheets including register maps, pinouts, timing diagrams, and specifications."
+        "Extract and analyze information from hardware component datasheets including register maps, pinouts, timing diagrams, and specifications. Reads local PDF datasheets (a file or a directory such as docs/datasheets/) page by page and caches the extracted text in the user cache directory. Answers register queries from CMSIS-SVD files by peripheral, register or field name."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -67,3 +429,170 @@ impl ToolDescription for DatasheetAnalyzer {
             .unwrap_or_else(|_| serde_json::json!({}))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::datasheet_pdf::write_test_pdf;
+    use crate::tools::hardware::svd::TEST_SVD;
+    use tempfile::TempDir;
+
+    fn args(component: &str, extract_type: &str, path: Option<&Path>) -> DatasheetAnalyzerArgs {
+        DatasheetAnalyzerArgs {
+            component: component.to_string(),
+            extract_type: extract_type.to_string(),
+            sections: None,
+            protocol: None,
+            path: path.map(|p| p.display().to_string()),
+        }
+    }
+
+    fn write_sensor_pdf(dir: &Path) -> PathBuf {
+        let pdf = dir.join("ACME-TMP117.pdf");
+        write_test_pdf(
+            &pdf,
+            &[
+                &["TMP117 digital temperature sensor", "1 Overview"],
+                &[
+                    "5 Pin Configuration and Functions",
+                    "Pin 1 ADD0 address select",
+                    "Pin 2 GND ground",
+                    "Pin 6 SDA I2C data I/O",
+                ],
+                &[
+                    "6 Electrical Characteristics",
+                    "VDD supply voltage min 1.8 typ 3.3 max 5.5 V",
+                    "Supply current typ 3.5 uA",
+                ],
+                &[
+                    "7 Timing Requirements",
+                    "SCL clock frequency max 400 kHz",
+                    "Data setup time min 100 ns",
+                ],
+                &[
+                    "8 Register Map",
+                    "0x01 Configuration register reset value 0x0220",
+                    "Bit 15 HIGH_Alert read-only",
+                ],
+            ],
+        );
+        pdf
+    }
+
+    fn cached_tool(cache: &TempDir) -> DatasheetAnalyzer {
+        DatasheetAnalyzer {
+            cache_dir: Some(cache.path().to_path_buf()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_registers_from_directory() {
+        let dir = TempDir::new().unwrap();
+        write_sensor_pdf(dir.path());
+        let cache = TempDir::new().unwrap();
+        let tool = cached_tool(&cache);
+        let result = tool
+            .execute(args("TMP117", "registers", Some(dir.path())))
+            .await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("### Register Tables"));
+            assert!(output.contains("reset value 0x0220"));
+            assert!(!output.contains("Supply current"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["page_count"], 5);
+            assert_eq!(metadata["sections"]["registers"], json!([5]));
+            assert_eq!(metadata["from_cache"], false);
+            let cached = metadata["cache_path"].as_str().unwrap();
+            assert!(Path::new(cached).starts_with(cache.path()));
+        } else {
+            panic!("Expected success result");
+        }
+        // The datasheet directory is left untouched
+        assert!(!dir.path().join("ACME-TMP117.pdf.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cached_all_sections_and_page_selection() {
+        let dir = TempDir::new().unwrap();
+        let pdf = write_sensor_pdf(dir.path());
+        let cache = TempDir::new().unwrap();
+        let tool = cached_tool(&cache);
+        tool.execute(args("TMP117", "pinout", Some(&pdf))).await;
+
+        let result = tool.execute(args("TMP117", "all", Some(&pdf))).await;
+        if let ToolResult::Success { metadata, .. } = result {
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["from_cache"], true);
+            assert_eq!(metadata["sections"]["pinout"], json!([2]));
+            assert_eq!(metadata["sections"]["electrical"], json!([3]));
+            assert_eq!(metadata["sections"]["timing"], json!([4]));
+        } else {
+            panic!("Expected success result");
+        }
+
+        let mut request = args(&pdf.display().to_string(), "pages", None);
+        request.sections = Some(vec!["1".to_string()]);
+        let result = tool.execute(request).await;
+        assert!(result.is_success());
+        assert!(result.to_string().contains("digital temperature sensor"));
+    }
+
+    #[tokio::test]
+    async fn test_svd_register_lookup() {
+        let dir = TempDir::new().unwrap();
+        write_sensor_pdf(dir.path());
+        std::fs::write(dir.path().join("STM32F4x.svd"), TEST_SVD).unwrap();
+        let cache = TempDir::new().unwrap();
+        let tool = cached_tool(&cache);
+
+        let mut request = args("STM32F4", "registers", Some(dir.path()));
+        request.sections = Some(vec![
+            "RCC_CFGR".to_string(),
+            "usart2.brr.div_mantissa".to_string(),
+        ]);
+        let result = tool.execute(request).await;
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("### RCC_CFGR @ 0x40023808"));
+            assert!(output
+                .contains("| [1:0] | SW | RW | 0 | System clock switch | 0=HSI, 1=HSE, 2=PLL |"));
+            assert!(output.contains("### USART2_BRR.DIV_Mantissa [15:4]"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["source"], "svd");
+            assert_eq!(metadata["matches"][1]["address"], 0x4000_4408u64);
+        } else {
+            panic!("Expected success result");
+        }
+
+        // Without names the peripherals are listed; protocol narrows the list
+        let mut request = args("STM32F4", "registers", Some(dir.path()));
+        request.protocol = Some("usart".to_string());
+        let result = tool.execute(request).await;
+        let output = result.to_string();
+        assert!(output.contains("| USART2 | 0x40004400 |"));
+        assert!(!output.contains("| RCC |"));
+
+        // Non-register queries on the same directory still read the PDF
+        let result = tool
+            .execute(args("TMP117", "timing", Some(dir.path())))
+            .await;
+        assert!(result.to_string().contains("Data setup time"));
+    }
+
+    #[tokio::test]
+    async fn test_without_path_describes_and_missing_path_errors() {
+        let tool = DatasheetAnalyzer::new();
+        let result = tool.execute(args("TMP117", "registers", None)).await;
+        assert!(result.is_success());
+        assert!(result.to_string().contains("Provide `path`"));
+
+        let result = tool
+            .execute(args(
+                "TMP117",
+                "registers",
+                Some(Path::new("/nonexistent/ds")),
+            ))
+            .await;
+        assert!(result.is_error());
+    }
+}
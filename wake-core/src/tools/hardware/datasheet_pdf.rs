@@ -0,0 +1,543 @@
+//! Local PDF datasheet text extraction and section lookup.
+//!
+//! Text is extracted page by page and cached in the user cache directory
+//! (`$XDG_CACHE_HOME/wake/datasheets/`), one page per form feed, so repeated
+//! queries skip the PDF parser without writing into the datasheet's directory.
+
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+/// Page separator in the cache file (same convention as pdftotext)
+const PAGE_SEPARATOR: char = '\u{c}';
+
+/// Pages returned per section kind
+pub const MAX_PAGES_PER_SECTION: usize = 5;
+
+/// Keyword score a page needs when it has no section heading
+const MIN_KEYWORD_SCORE: usize = 8;
+
+/// Occurrences of one keyword that count towards a page's score
+const MAX_KEYWORD_HITS: usize = 5;
+
+#[derive(Clone, Debug)]
+pub struct DatasheetText {
+    pub path: PathBuf,
+    /// None when there is no cache directory
+    pub cache_path: Option<PathBuf>,
+    pub pages: Vec<String>,
+    /// Loaded from the cache instead of the PDF
+    pub from_cache: bool,
+    /// False when the cache could not be written (e.g. no writable cache directory)
+    pub cache_written: bool,
+}
+
+/// Directory holding extracted datasheet text
+pub fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(dirs::cache_dir)
+        .map(|dir| dir.join("wake").join("datasheets"))
+}
+
+/// `<cache_dir>/<file>-<hash of the absolute path>.txt`, so PDFs with the same
+/// name in different directories do not share a cache entry
+pub fn cache_path(cache_dir: &Path, pdf: &Path) -> PathBuf {
+    let absolute = fs::canonicalize(pdf).unwrap_or_else(|_| pdf.to_path_buf());
+    let mut hasher = DefaultHasher::new();
+    absolute.hash(&mut hasher);
+    let name = pdf.file_name().unwrap_or_default().to_string_lossy();
+    cache_dir.join(format!("{}-{:016x}.txt", name, hasher.finish()))
+}
+
+/// Extract (or load cached) per-page text of a PDF, caching it under `cache_dir`
+pub fn load(pdf: &Path, cache_dir: Option<&Path>) -> Result<DatasheetText, String> {
+    let cache = cache_dir.map(|dir| cache_path(dir, pdf));
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let pdf_modified =
+        modified(pdf).ok_or_else(|| format!("Cannot read datasheet {}", pdf.display()))?;
+
+    if let Some(cache) = &cache {
+        if modified(cache).is_some_and(|c| c >= pdf_modified) {
+            if let Ok(text) = fs::read_to_string(cache) {
+                return Ok(DatasheetText {
+                    path: pdf.to_path_buf(),
+                    cache_path: Some(cache.clone()),
+                    pages: text.split(PAGE_SEPARATOR).map(str::to_string).collect(),
+                    from_cache: true,
+                    cache_written: true,
+                });
+            }
+        }
+    }
+
+    let bytes = fs::read(pdf).map_err(|e| format!("Cannot read {}: {}", pdf.display(), e))?;
+    // The parser panics on some malformed files; report those as errors
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&bytes))
+        .map_err(|_| format!("PDF parser crashed on {}", pdf.display()))?
+        .map_err(|e| format!("Cannot extract text from {}: {}", pdf.display(), e))?;
+    let pages: Vec<String> = pages
+        .into_iter()
+        .map(|p| p.replace(PAGE_SEPARATOR, "\n"))
+        .collect();
+
+    let joined = pages.join(&PAGE_SEPARATOR.to_string());
+    let cache_written = cache.as_ref().is_some_and(|cache| {
+        cache
+            .parent()
+            .is_some_and(|dir| fs::create_dir_all(dir).is_ok())
+            && fs::write(cache, joined).is_ok()
+    });
+    Ok(DatasheetText {
+        path: pdf.to_path_buf(),
+        cache_path: cache,
+        pages,
+        from_cache: false,
+        cache_written,
+    })
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Pick the file for `component` with one of `extensions` from a file path or a directory
+pub fn resolve(path: &Path, component: &str, extensions: &[&str]) -> Result<PathBuf, String> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    if !path.is_dir() {
+        return Err(format!("Datasheet path {} does not exist", path.display()));
+    }
+
+    let files: Vec<PathBuf> = WalkDir::new(path)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|e| e.into_path())
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+        })
+        .collect();
+
+    let wanted = normalize(component);
+    if wanted.is_empty() {
+        return Err(format!(
+            "Component name '{}' has no letters or digits to match files in {}",
+            component,
+            path.display()
+        ));
+    }
+    let stem = |p: &PathBuf| normalize(&p.file_stem().unwrap_or_default().to_string_lossy());
+    let best = files
+        .iter()
+        .filter(|p| {
+            let stem = stem(p);
+            !stem.is_empty() && (stem.contains(&wanted) || wanted.contains(&stem))
+        })
+        .min_by_key(|p| stem(p).len());
+
+    match (best, files.as_slice()) {
+        (Some(p), _) => Ok(p.clone()),
+        (None, [only]) => Ok(only.clone()),
+        (None, []) => Err(format!(
+            "No .{} files in {}",
+            extensions.join("/."),
+            path.display()
+        )),
+        (None, many) => Err(format!(
+            "No .{} file in {} matches '{}'. Available: {}",
+            extensions.join("/."),
+            path.display(),
+            component,
+            many.iter()
+                .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionKind {
+    Registers,
+    Pinout,
+    Electrical,
+    Timing,
+}
+
+impl SectionKind {
+    pub fn all() -> [SectionKind; 4] {
+        [
+            SectionKind::Registers,
+            SectionKind::Pinout,
+            SectionKind::Electrical,
+            SectionKind::Timing,
+        ]
+    }
+
+    /// Kinds requested by an `extract_type`; None for unknown types
+    pub fn for_extract_type(extract_type: &str) -> Option<Vec<SectionKind>> {
+        match extract_type.to_lowercase().as_str() {
+            "registers" | "register" | "register-map" => Some(vec![SectionKind::Registers]),
+            "pinout" | "pins" | "pin" => Some(vec![SectionKind::Pinout]),
+            "electrical" | "power" | "characteristics" => Some(vec![SectionKind::Electrical]),
+            "timing" => Some(vec![SectionKind::Timing]),
+            "all" => Some(SectionKind::all().to_vec()),
+            _ => None,
+        }
+    }
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            SectionKind::Registers => "registers",
+            SectionKind::Pinout => "pinout",
+            SectionKind::Electrical => "electrical",
+            SectionKind::Timing => "timing",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            SectionKind::Registers => "Register Tables",
+            SectionKind::Pinout => "Pin Descriptions",
+            SectionKind::Electrical => "Electrical Characteristics",
+            SectionKind::Timing => "Timing",
+        }
+    }
+
+    fn headings(&self) -> &'static [&'static str] {
+        match self {
+            SectionKind::Registers => &[
+                "register map",
+                "register description",
+                "register summary",
+                "register definitions",
+                "memory map",
+            ],
+            SectionKind::Pinout => &[
+                "pin description",
+                "pin configuration",
+                "pin assignment",
+                "pin definitions",
+                "pinout",
+                "alternate function",
+            ],
+            SectionKind::Electrical => &[
+                "electrical characteristics",
+                "absolute maximum ratings",
+                "operating conditions",
+                "dc characteristics",
+                "thermal characteristics",
+            ],
+            SectionKind::Timing => &[
+                "timing characteristics",
+                "timing requirements",
+                "timing diagram",
+                "ac characteristics",
+                "switching characteristics",
+            ],
+        }
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            SectionKind::Registers => &[
+                "reset value",
+                "address offset",
+                "offset",
+                "reserved",
+                "r/w",
+                "rw",
+                "read-only",
+                "bit ",
+            ],
+            SectionKind::Pinout => &["gpio", "i/o", "vdd", "vss", "lqfp", "qfn", "tssop", "pin "],
+            SectionKind::Electrical => &[
+                "min",
+                "typ",
+                "max",
+                "supply current",
+                "ma",
+                "µa",
+                "esd",
+                "°c",
+            ],
+            SectionKind::Timing => &[
+                "setup time",
+                "hold time",
+                "propagation",
+                "rise time",
+                "fall time",
+                "clock frequency",
+                "ns",
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageMatch {
+    /// 1-based page number
+    pub page: usize,
+    pub score: usize,
+    /// Heading lines that matched
+    pub headings: Vec<String>,
+}
+
+fn toc_line() -> &'static Regex {
+    static TOC: OnceLock<Regex> = OnceLock::new();
+    TOC.get_or_init(|| Regex::new(r"(\.\s?){5,}\s*\d+\s*$").unwrap())
+}
+
+/// Table-of-contents pages mention every heading, so they are skipped
+fn is_toc(page: &str) -> bool {
+    page.lines().filter(|l| toc_line().is_match(l)).count() >= 5
+}
+
+/// Pages most likely to hold `kind`, in page order
+pub fn find_sections(
+    pages: &[String],
+    kind: SectionKind,
+    protocol: Option<&str>,
+) -> Vec<PageMatch> {
+    let protocol = protocol.map(str::to_lowercase);
+    let mut matches: Vec<PageMatch> = pages
+        .iter()
+        .enumerate()
+        .filter(|(_, text)| !is_toc(text))
+        .filter_map(|(i, text)| {
+            let lower = text.to_lowercase();
+            let headings: Vec<String> = text
+                .lines()
+                .filter(|line| {
+                    let line = line.to_lowercase();
+                    kind.headings().iter().any(|h| line.contains(h))
+                })
+                .map(|line| line.trim().chars().take(80).collect())
+                .take(3)
+                .collect();
+            let keyword_score: usize = kind
+                .keywords()
+                .iter()
+                .map(|k| lower.matches(k).count().min(MAX_KEYWORD_HITS))
+                .sum();
+            if headings.is_empty() && keyword_score < MIN_KEYWORD_SCORE {
+                return None;
+            }
+            let protocol_bonus = match &protocol {
+                Some(p) if lower.contains(p.as_str()) => 10,
+                _ => 0,
+            };
+            Some(PageMatch {
+                page: i + 1,
+                score: headings.len() * 10 + keyword_score + protocol_bonus,
+                headings,
+            })
+        })
+        .collect();
+
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.page.cmp(&b.page)));
+    matches.truncate(MAX_PAGES_PER_SECTION);
+    matches.sort_by_key(|m| m.page);
+    matches
+}
+
+/// Split `sections` into explicit pages (`12`, `30-35`) and keyword filters
+pub fn parse_selection(sections: &[String], page_count: usize) -> (Vec<usize>, Vec<String>) {
+    let mut pages = Vec::new();
+    let mut keywords = Vec::new();
+    for section in sections {
+        let s = section.trim();
+        let range = s
+            .split_once('-')
+            .and_then(|(a, b)| Some((a.trim().parse::<usize>().ok()?, b.trim().parse().ok()?)));
+        match (s.parse::<usize>(), range) {
+            (Ok(p), _) => pages.push(p),
+            (_, Some((a, b))) => pages.extend(a..=b),
+            _ if !s.is_empty() => keywords.push(s.to_lowercase()),
+            _ => {}
+        }
+    }
+    pages.retain(|p| (1..=page_count).contains(p));
+    pages.sort_unstable();
+    pages.dedup();
+    (pages, keywords)
+}
+
+/// Write a minimal PDF with one Helvetica text line per entry on each page
+#[cfg(test)]
+pub(crate) fn write_test_pdf(path: &Path, pages: &[&[&str]]) {
+    let escape = |s: &str| {
+        s.replace('\\', "\\\\")
+            .replace('(', "\\(")
+            .replace(')', "\\)")
+    };
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", 4 + 2 * i))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (i, lines) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + 2 * i
+        ));
+        let mut content = String::from("BT /F1 10 Tf 14 TL 50 750 Td\n");
+        for line in lines.iter() {
+            content.push_str(&format!("({}) Tj T*\n", escape(line)));
+        }
+        content.push_str("ET");
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    fs::write(path, pdf).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pages(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_extract_and_cache() {
+        let dir = TempDir::new().unwrap();
+        let pdf = dir.path().join("sensor.pdf");
+        write_test_pdf(
+            &pdf,
+            &[&["Pin description", "SDA pin 3"], &["Register map", "CTRL"]],
+        );
+
+        let cache = TempDir::new().unwrap();
+        let first = load(&pdf, Some(cache.path())).unwrap();
+        assert!(!first.from_cache);
+        assert!(first.cache_written);
+        assert_eq!(first.pages.len(), 2);
+        assert!(first.pages[0].contains("Pin description"));
+        assert!(first.pages[1].contains("Register map"));
+        let cached = first.cache_path.unwrap();
+        assert!(cached.starts_with(cache.path()));
+        assert!(!dir.path().join("sensor.pdf.txt").exists());
+
+        let second = load(&pdf, Some(cache.path())).unwrap();
+        assert!(second.from_cache);
+        assert_eq!(second.pages, first.pages);
+
+        // Same file name elsewhere gets its own entry
+        let other = dir.path().join("other").join("sensor.pdf");
+        assert_ne!(cache_path(cache.path(), &other), cached);
+
+        let uncached = load(&pdf, None).unwrap();
+        assert!(!uncached.from_cache && !uncached.cache_written);
+    }
+
+    #[test]
+    fn test_invalid_pdf() {
+        let dir = TempDir::new().unwrap();
+        let pdf = dir.path().join("broken.pdf");
+        fs::write(&pdf, b"not a pdf").unwrap();
+        assert!(load(&pdf, None).is_err());
+    }
+
+    #[test]
+    fn test_resolve_directory() {
+        let dir = TempDir::new().unwrap();
+        for name in ["STM32F401RE.pdf", "BME280-datasheet.pdf", "notes.txt"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let found = resolve(dir.path(), "bme280", &["pdf"]).unwrap();
+        assert!(found.ends_with("BME280-datasheet.pdf"));
+        let found = resolve(dir.path(), "STM32F401", &["pdf"]).unwrap();
+        assert!(found.ends_with("STM32F401RE.pdf"));
+        let err = resolve(dir.path(), "nrf52840", &["pdf"]).unwrap_err();
+        assert!(err.contains("BME280-datasheet.pdf"));
+        assert!(resolve(dir.path(), "bme280", &["svd"]).is_err());
+
+        // Names without letters or digits would match every file
+        fs::write(dir.path().join("-.pdf"), b"").unwrap();
+        let err = resolve(dir.path(), "--", &["pdf"]).unwrap_err();
+        assert!(err.contains("no letters or digits"), "{}", err);
+        let found = resolve(dir.path(), "nrf52840", &["pdf"]);
+        assert!(found.is_err());
+    }
+
+    #[test]
+    fn test_find_sections_skips_toc() {
+        let toc = (1..=6)
+            .map(|i| format!("{} Register map .......... {}", i, i * 10))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let pages = pages(&[
+            &toc,
+            "7 Electrical characteristics\nVDD min 1.8 typ 3.3 max 3.6",
+            "8 Register map\nCTRL address offset 0x00 reset value 0x00",
+            "Unrelated text",
+        ]);
+        let registers = find_sections(&pages, SectionKind::Registers, None);
+        assert_eq!(registers.len(), 1);
+        assert_eq!(registers[0].page, 3);
+        assert_eq!(registers[0].headings, vec!["8 Register map".to_string()]);
+
+        let electrical = find_sections(&pages, SectionKind::Electrical, None);
+        assert_eq!(electrical[0].page, 2);
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        let (pages, keywords) = parse_selection(
+            &[
+                "3".to_string(),
+                "5-7".to_string(),
+                "I2C".to_string(),
+                "99".to_string(),
+            ],
+            10,
+        );
+        assert_eq!(pages, vec![3, 5, 6, 7]);
+        assert_eq!(keywords, vec!["i2c".to_string()]);
+    }
+}
@@ -0,0 +1,372 @@
+//! DBC CAN database.
+//!
+//! Loads messages (`BO_`), signals (`SG_`, including simple multiplexing),
+//! value tables (`VAL_`) and the `GenMsgCycleTime` attribute, and decodes
+//! frame payloads into scaled physical values. Intel (`@1`) signals count
+//! bits LSB first from the start bit; Motorola (`@0`) signals start at their
+//! most significant bit in the DBC sawtooth numbering.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Extended frames are marked by bit 31 of the DBC message ID
+const EXTENDED_FLAG: u32 = 0x8000_0000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mux {
+    None,
+    /// Selects which multiplexed signals are present
+    Multiplexor,
+    /// Present when the multiplexor has this value
+    Multiplexed(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub start: u32,
+    pub size: u32,
+    pub little_endian: bool,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub mux: Mux,
+    pub values: BTreeMap<i64, String>,
+}
+
+impl Signal {
+    /// Raw bits of the signal, or `None` when the payload is too short
+    pub fn raw(&self, data: &[u8]) -> Option<u64> {
+        let bit = |pos: u32| {
+            data.get(pos as usize / 8)
+                .map(|b| (b >> (pos % 8) & 1) as u64)
+        };
+        let mut value = 0u64;
+        if self.little_endian {
+            for i in 0..self.size {
+                value |= bit(self.start + i)? << i;
+            }
+        } else {
+            let mut pos = self.start;
+            for _ in 0..self.size {
+                value = value << 1 | bit(pos)?;
+                pos = if pos.is_multiple_of(8) {
+                    pos + 15
+                } else {
+                    pos - 1
+                };
+            }
+        }
+        Some(value)
+    }
+
+    /// Raw value with the sign applied
+    pub fn integer(&self, data: &[u8]) -> Option<i64> {
+        let raw = self.raw(data)?;
+        Some(
+            if self.signed && self.size < 64 && raw >> (self.size - 1) & 1 == 1 {
+                raw as i64 - (1i64 << self.size)
+            } else {
+                raw as i64
+            },
+        )
+    }
+
+    pub fn value(&self, data: &[u8]) -> Option<f64> {
+        self.integer(data)
+            .map(|raw| raw as f64 * self.factor + self.offset)
+    }
+
+    /// Physical value with its unit, or the value-table entry
+    pub fn format(&self, data: &[u8]) -> Option<String> {
+        let raw = self.integer(data)?;
+        if let Some(name) = self.values.get(&raw) {
+            return Some(name.clone());
+        }
+        let number = number(raw as f64 * self.factor + self.offset);
+        Some(if self.unit.is_empty() {
+            number
+        } else {
+            format!("{} {}", number, self.unit)
+        })
+    }
+}
+
+/// Physical value with at most three decimals and no trailing zeros
+pub fn number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.3}", value)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    /// Payload length in bytes
+    pub size: usize,
+    pub sender: String,
+    pub signals: Vec<Signal>,
+    /// `GenMsgCycleTime` in seconds
+    pub cycle_time: Option<f64>,
+}
+
+impl Message {
+    /// Signals present in this payload, honouring the multiplexor
+    pub fn active_signals<'a>(&'a self, data: &[u8]) -> Vec<&'a Signal> {
+        let selector = self
+            .signals
+            .iter()
+            .find(|s| s.mux == Mux::Multiplexor)
+            .and_then(|s| s.raw(data));
+        self.signals
+            .iter()
+            .filter(|s| match s.mux {
+                Mux::Multiplexed(value) => selector == Some(value),
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Database {
+    pub messages: Vec<Message>,
+}
+
+impl Database {
+    pub fn message(&self, id: u32, extended: bool) -> Option<&Message> {
+        self.messages
+            .iter()
+            .find(|m| m.id == id && m.extended == extended)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut database = Database::default();
+        let mut cycle_times = Vec::new();
+        let mut value_tables = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            if let Some(rest) = line.strip_prefix("BO_ ") {
+                // BO_ 256 EngineData: 8 Engine
+                let (head, tail) = rest.split_once(':').ok_or_else(|| error("invalid BO_"))?;
+                let mut head = head.split_whitespace();
+                let raw: u32 = head
+                    .next()
+                    .and_then(|id| id.parse().ok())
+                    .ok_or_else(|| error("invalid message ID"))?;
+                let name = head.next().ok_or_else(|| error("missing message name"))?;
+                let mut tail = tail.split_whitespace();
+                let size = tail
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| error("invalid message size"))?;
+                database.messages.push(Message {
+                    id: raw & !EXTENDED_FLAG,
+                    extended: raw & EXTENDED_FLAG != 0,
+                    name: name.to_string(),
+                    size,
+                    sender: tail.next().unwrap_or("").to_string(),
+                    signals: Vec::new(),
+                    cycle_time: None,
+                });
+            } else if let Some(rest) = line.strip_prefix("SG_ ") {
+                let signal = parse_signal(rest).map_err(|e| error(&e))?;
+                database
+                    .messages
+                    .last_mut()
+                    .ok_or_else(|| error("SG_ outside a message"))?
+                    .signals
+                    .push(signal);
+            } else if let Some(rest) = line.strip_prefix("BA_ \"GenMsgCycleTime\" BO_ ") {
+                let mut fields = rest.trim_end_matches(';').split_whitespace();
+                if let (Some(Ok(id)), Some(Ok(ms))) = (
+                    fields.next().map(str::parse::<u32>),
+                    fields.next().map(str::parse::<f64>),
+                ) {
+                    cycle_times.push((id, ms));
+                }
+            } else if let Some(rest) = line.strip_prefix("VAL_ ") {
+                value_tables.push(rest.to_string());
+            }
+        }
+
+        for (raw, ms) in cycle_times {
+            let (id, extended) = (raw & !EXTENDED_FLAG, raw & EXTENDED_FLAG != 0);
+            if let Some(message) = database
+                .messages
+                .iter_mut()
+                .find(|m| m.id == id && m.extended == extended)
+            {
+                message.cycle_time = (ms > 0.0).then_some(ms / 1000.0);
+            }
+        }
+        for table in value_tables {
+            // VAL_ 256 Gear 0 "P" 1 "R" ;
+            let mut head = table.splitn(3, ' ');
+            let (Some(id), Some(signal), Some(body)) = (head.next(), head.next(), head.next())
+            else {
+                continue;
+            };
+            let Ok(raw) = id.parse::<u32>() else {
+                continue;
+            };
+            let (id, extended) = (raw & !EXTENDED_FLAG, raw & EXTENDED_FLAG != 0);
+            let Some(signal) = database
+                .messages
+                .iter_mut()
+                .find(|m| m.id == id && m.extended == extended)
+                .and_then(|m| m.signals.iter_mut().find(|s| s.name == signal))
+            else {
+                continue;
+            };
+            let mut parts = body.split('"');
+            while let (Some(value), Some(name)) = (parts.next(), parts.next()) {
+                if let Ok(value) = value.trim().parse::<i64>() {
+                    signal.values.insert(value, name.to_string());
+                }
+            }
+        }
+
+        if database.messages.is_empty() {
+            return Err("no BO_ messages".to_string());
+        }
+        Ok(database)
+    }
+}
+
+/// `Name [M|mN] : start|size@order sign (factor,offset) [min|max] "unit" receivers`
+fn parse_signal(text: &str) -> Result<Signal, String> {
+    let (head, body) = text
+        .split_once(':')
+        .ok_or_else(|| format!("invalid SG_ '{}'", text))?;
+    let mut head = head.split_whitespace();
+    let name = head.next().ok_or("missing signal name")?.to_string();
+    let mux = match head.next() {
+        None => Mux::None,
+        Some("M") => Mux::Multiplexor,
+        Some(m) => Mux::Multiplexed(
+            m.trim_start_matches('m')
+                .trim_end_matches('M')
+                .parse()
+                .map_err(|_| format!("invalid multiplexer '{}' on {}", m, name))?,
+        ),
+    };
+    let invalid = || format!("invalid layout for signal {}", name);
+
+    let body = body.trim();
+    let (layout, rest) = body.split_once(' ').ok_or_else(invalid)?;
+    let (start, rest_layout) = layout.split_once('|').ok_or_else(invalid)?;
+    let (size, order) = rest_layout.split_once('@').ok_or_else(invalid)?;
+    let mut order = order.chars();
+    let little_endian = order.next() == Some('1');
+    let signed = order.next() == Some('-');
+
+    let between = |open: char, close: char| -> Option<&str> {
+        let from = rest.find(open)? + 1;
+        let to = from + rest[from..].find(close)?;
+        Some(&rest[from..to])
+    };
+    let pair = |text: Option<&str>, separator: char| -> Option<(f64, f64)> {
+        let (a, b) = text?.split_once(separator)?;
+        Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+    };
+    let (factor, offset) = pair(between('(', ')'), ',').ok_or_else(invalid)?;
+    let (min, max) = pair(between('[', ']'), '|').unwrap_or((0.0, 0.0));
+
+    let start = start.parse().map_err(|_| invalid())?;
+    let size = size
+        .parse()
+        .ok()
+        .filter(|s| (1..=64).contains(s))
+        .ok_or_else(invalid)?;
+    Ok(Signal {
+        name,
+        start,
+        size,
+        little_endian,
+        signed,
+        factor,
+        offset,
+        min,
+        max,
+        unit: between('"', '"').unwrap_or("").to_string(),
+        mux,
+        values: BTreeMap::new(),
+    })
+}
+
+#[cfg(test)]
+pub(crate) const TEST_DBC: &str = r#"VERSION ""
+
+BU_: Engine Gateway
+
+BO_ 256 EngineData: 8 Engine
+ SG_ EngineSpeed : 0|16@1+ (0.25,0) [0|16383.75] "rpm" Gateway
+ SG_ CoolantTemp : 16|8@1- (1,-40) [-40|215] "degC" Gateway
+ SG_ Gear : 24|3@1+ (1,0) [0|7] "" Gateway
+
+BO_ 2566844672 Diagnostics: 8 Gateway
+ SG_ Page M : 7|8@0+ (1,0) [0|255] "" Engine
+ SG_ Voltage m1 : 15|16@0+ (0.001,0) [0|65.535] "V" Engine
+ SG_ Errors m2 : 15|8@0+ (1,0) [0|255] "" Engine
+
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 65535;
+BA_ "GenMsgCycleTime" BO_ 256 10;
+VAL_ 256 Gear 0 "P" 1 "R" 2 "N" 3 "D" ;
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_decode() {
+        let db = Database::parse(TEST_DBC).unwrap();
+        assert_eq!(db.messages.len(), 2);
+        let engine = db.message(256, false).unwrap();
+        assert_eq!(engine.cycle_time, Some(0.010));
+
+        // 3000 rpm, 50 degC, gear D
+        let data = [0xE0, 0x2E, 0x5A, 0x03, 0, 0, 0, 0];
+        let values: Vec<String> = engine
+            .active_signals(&data)
+            .iter()
+            .map(|s| format!("{}={}", s.name, s.format(&data).unwrap()))
+            .collect();
+        assert_eq!(
+            values,
+            ["EngineSpeed=3000 rpm", "CoolantTemp=50 degC", "Gear=D"]
+        );
+        assert_eq!(engine.signals[1].value(&[0, 0, 0xEC]), Some(-60.0));
+
+        // Extended ID 0x18FEF100, Motorola signals multiplexed on Page
+        let diagnostics = db.message(0x18FE_F100, true).unwrap();
+        let page1 = [0x01, 0x30, 0x39, 0, 0, 0, 0, 0];
+        let active = diagnostics.active_signals(&page1);
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[1].format(&page1).unwrap(), "12.345 V");
+        let page2 = [0x02, 0x05, 0, 0, 0, 0, 0, 0];
+        assert_eq!(diagnostics.active_signals(&page2)[1].name, "Errors");
+        assert_eq!(diagnostics.signals[1].raw(&[0x01]), None);
+
+        assert!(Database::parse("BO_ x").unwrap_err().starts_with("line 1"));
+    }
+}
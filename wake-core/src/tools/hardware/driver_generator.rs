@@ -1,7 +1,13 @@
+use super::package::{self, PackageFile};
+use super::register_driver::{self, DriverModel, Interface};
+use super::scaffold::{self, Binding, CDriver, Framework};
 use crate::tools::{Tool, ToolCapability, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use wake_llm::ToolDescription;
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -9,7 +15,9 @@ pub struct DriverGeneratorArgs {
     /// The hardware component or chip name (e.g., "MPU6050", "BMP280", "SSD1306")
     pub component: String,
 
-    /// Target platform (e.g., "Arduino", "STM32", "ESP32", "RaspberryPi")
+    /// Target platform (e.g., "Arduino", "STM32", "ESP32", "RaspberryPi"), or a framework for a
+    /// build-system-ready C/C++ package: "Zephyr" (module with devicetree binding, Kconfig and
+    /// CMakeLists), "ESP-IDF" (component with idf_component.yml) or "PlatformIO" (library.json library)
     pub platform: String,
 
     /// Programming language (e.g., "C", "C++", "Rust", "MicroPython")
@@ -23,6 +31,24 @@ pub struct DriverGeneratorArgs {
 
     /// Optional: Include example usage code
     pub include_examples: Option<bool>,
+
+    /// Optional: Register map of the part: SVD, YAML or JSON file, a directory holding one named
+    /// after the component, or inline YAML/JSON. Replaces the placeholder registers with the real ones
+    pub register_map: Option<String>,
+
+    /// Optional: Peripheral of a multi-peripheral register map (e.g. an SVD) to generate the driver for
+    pub peripheral: Option<String>,
+
+    /// Optional: Device-ID check as REGISTER=VALUE (e.g. "WHO_AM_I=0x68"); defaults to the reset
+    /// value of an ID/WHO_AM_I register in the map
+    pub device_id: Option<String>,
+
+    /// Optional: For Rust, generate an embedded-hal-async driver instead of a blocking
+    /// embedded-hal 1.0 one (default: false)
+    pub async_hal: Option<bool>,
+
+    /// Optional: Directory to write the driver package to (default: <component>_driver)
+    pub output_dir: Option<String>,
 }
 
 pub struct DriverGenerator;
@@ -32,20 +58,182 @@ impl DriverGenerator {
         Self
     }
 
-    fn generate_driver_template(&self, args: &DriverGeneratorArgs) -> String {
+    /// Files of the driver package, relative to the output directory
+    fn package(
+        &self,
+        args: &DriverGeneratorArgs,
+        model: Option<&DriverModel>,
+    ) -> Result<Vec<PackageFile>, String> {
         let component = &args.component;
         let platform = &args.platform;
         let protocol = &args.protocol;
+        let stem = register_driver::file_stem(component);
+        let examples = args.include_examples != Some(false);
 
+        let mut files = Vec::new();
         match args.language.as_str() {
-            "C" | "C++" => self.generate_c_driver(component, platform, protocol),
-            "Rust" => self.generate_rust_driver(component, platform, protocol),
-            "MicroPython" => self.generate_micropython_driver(component, platform, protocol),
-            _ => format!(
-                "Unsupported language: {}. Supported: C, C++, Rust, MicroPython",
-                args.language
-            ),
+            "C" | "C++" => {
+                let (header_ext, source_ext) = if args.language == "C" {
+                    ("h", "c")
+                } else {
+                    ("hpp", "cpp")
+                };
+                let header = format!("{}.{}", stem, header_ext);
+                let (header_code, source_code) = match model {
+                    Some(model) => (
+                        register_driver::generate_c(
+                            model,
+                            component,
+                            platform,
+                            protocol,
+                            self.get_platform_includes(platform),
+                        ),
+                        register_driver::generate_c_source(model, component, &header),
+                    ),
+                    None => (
+                        self.generate_c_driver(component, platform, protocol),
+                        self.generate_c_source(component, platform, protocol, &header),
+                    ),
+                };
+                if let Some(framework) = Framework::parse(platform) {
+                    let example = examples.then(|| match model {
+                        Some(model) => register_driver::c_example(model, component, &header),
+                        None => self.generate_c_example(component, &header),
+                    });
+                    let binding = match model {
+                        Some(model) if model.interface == Interface::Mmio => Binding::Mmio {
+                            base: model.peripheral.base_address,
+                            size: model.block_size(),
+                        },
+                        Some(model) => Binding::Callbacks {
+                            interface: model.interface,
+                            wide_address: model.wide_address(),
+                        },
+                        None => Binding::Template {
+                            protocol: protocol.clone(),
+                        },
+                    };
+                    return scaffold::scaffold(
+                        framework,
+                        &CDriver {
+                            component: component.clone(),
+                            stem,
+                            header_ext,
+                            source_ext,
+                            header: header_code,
+                            source: source_code,
+                            example,
+                            binding,
+                        },
+                    );
+                }
+                files.push(PackageFile::new(header.clone(), header_code));
+                files.push(PackageFile::new(
+                    format!("{}.{}", stem, source_ext),
+                    source_code,
+                ));
+                if examples {
+                    let include = format!("../{}", header);
+                    let example = match model {
+                        Some(model) => register_driver::c_example(model, component, &include),
+                        None => self.generate_c_example(component, &include),
+                    };
+                    files.push(PackageFile::new(
+                        format!("examples/main.{}", source_ext),
+                        example,
+                    ));
+                }
+            }
+            language if Framework::parse(platform).is_some() => {
+                return Err(format!(
+                    "{} scaffolding is generated for C and C++ drivers, not {}",
+                    Framework::parse(platform).map_or("", |f| f.label()),
+                    language
+                ))
+            }
+            "Rust" => {
+                let crate_name = format!("{}-driver", stem.replace('_', "-"));
+                let asynchronous = args.async_hal.unwrap_or(false);
+                let (lib, bus) = match model {
+                    Some(model) => (
+                        register_driver::generate_rust(
+                            model,
+                            component,
+                            platform,
+                            protocol,
+                            asynchronous,
+                        ),
+                        model.interface != Interface::Mmio,
+                    ),
+                    None => (
+                        self.generate_rust_driver(component, platform, protocol, asynchronous)?,
+                        true,
+                    ),
+                };
+                files.push(PackageFile::new(
+                    "Cargo.toml",
+                    self.generate_cargo_toml(component, &crate_name, bus, asynchronous),
+                ));
+                files.push(PackageFile::new("src/lib.rs", lib));
+                if examples {
+                    let example = match model {
+                        Some(model) => register_driver::rust_example(
+                            model,
+                            component,
+                            &crate_name,
+                            asynchronous,
+                        ),
+                        None => register_driver::bus_example(
+                            component,
+                            &crate_name,
+                            protocol.eq_ignore_ascii_case("SPI"),
+                            asynchronous,
+                        ),
+                    };
+                    files.push(PackageFile::new("examples/basic.rs", example));
+                }
+            }
+            "MicroPython" => {
+                let module = match model {
+                    Some(model) => {
+                        register_driver::generate_micropython(model, component, platform, protocol)
+                    }
+                    None => self.generate_micropython_driver(component, platform, protocol),
+                };
+                files.push(PackageFile::new(format!("{}.py", stem), module));
+                if examples {
+                    let example = match model {
+                        Some(model) => {
+                            register_driver::micropython_example(model, component, &stem)
+                        }
+                        None => self.generate_micropython_example(component, protocol, &stem),
+                    };
+                    files.push(PackageFile::new("example.py", example));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported language: {}. Supported: C, C++, Rust, MicroPython",
+                    args.language
+                ))
+            }
         }
+        Ok(files)
+    }
+
+    fn generate_cargo_toml(
+        &self,
+        component: &str,
+        crate_name: &str,
+        bus: bool,
+        asynchronous: bool,
+    ) -> String {
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\ndescription = \"{} driver\"\n\n{}",
+            crate_name,
+            component,
+            register_driver::cargo_dependencies(bus, asynchronous)
+        )
     }
 
     fn generate_c_driver(&self, component: &str, platform: &str, protocol: &str) -> String {
@@ -67,6 +255,10 @@ impl DriverGenerator {
 // Hardware-specific includes
 {}
 
+#ifdef __cplusplus
+extern "C" {{
+#endif
+
 // {} Register Definitions
 {}
 
@@ -140,9 +332,11 @@ int {}_reset({}_t* dev);
  */
 uint8_t {}_get_status({}_t* dev);
 
-#endif // {}_H
+#ifdef __cplusplus
+}}
+#endif
 
-/* Implementation file would go here with actual hardware communication code */
+#endif // {}_H
 "#,
             component,
             platform,
@@ -183,22 +377,165 @@ uint8_t {}_get_status({}_t* dev);
         )
     }
 
-    fn generate_rust_driver(&self, component: &str, platform: &str, protocol: &str) -> String {
+    fn generate_c_source(
+        &self,
+        component: &str,
+        platform: &str,
+        protocol: &str,
+        header: &str,
+    ) -> String {
+        format!(
+            r#"/**
+ * {c} Driver for {platform}
+ * Communication Protocol: {protocol}
+ * Generated by Wake - Hardware-First Coding Agent
+ */
+
+#include "{header}"
+
+#include <stddef.h>
+
+int {l}_init({c}_t* dev, const {c}_config_t* config)
+{{
+    if (dev == NULL || config == NULL) {{
+        return -1;
+    }}
+    dev->config = *config;
+    dev->initialized = false;
+    if ({l}_reset(dev) != 0 || {l}_set_mode(dev, config->mode) != 0) {{
+        return -1;
+    }}
+    dev->initialized = true;
+    return 0;
+}}
+
+int {l}_read_data({c}_t* dev, void* data)
+{{
+    (void)dev;
+    (void)data;
+    /* TODO: read {u}_REG_DATA_HIGH/{u}_REG_DATA_LOW over {protocol} */
+    return -1;
+}}
+
+int {l}_write_register({c}_t* dev, uint8_t reg, uint8_t value)
+{{
+    (void)dev;
+    (void)reg;
+    (void)value;
+    /* TODO: send reg and value over {protocol} through dev->interface */
+    return -1;
+}}
+
+int {l}_set_mode({c}_t* dev, uint8_t mode)
+{{
+    return {l}_write_register(dev, {u}_REG_CONFIG, mode);
+}}
+
+bool {l}_self_test({c}_t* dev)
+{{
+    return ({l}_get_status(dev) & 0x01U) == 0U;
+}}
+
+int {l}_reset({c}_t* dev)
+{{
+    return {l}_write_register(dev, {u}_REG_CTRL, 0x80);
+}}
+
+uint8_t {l}_get_status({c}_t* dev)
+{{
+    (void)dev;
+    /* TODO: read {u}_REG_STATUS over {protocol} */
+    return 0;
+}}
+"#,
+            c = component,
+            platform = platform,
+            protocol = protocol,
+            header = header,
+            l = component.to_lowercase(),
+            u = component.to_uppercase()
+        )
+    }
+
+    fn generate_c_example(&self, component: &str, header: &str) -> String {
         format!(
-            r#"//! {} Driver for {}
-//! Communication Protocol: {}
+            r#"/* {c} example */
+
+#include <stddef.h>
+#include "{header}"
+
+int main(void)
+{{
+    {c}_t dev;
+    {c}_config_t config = {{ .address = 0x00, .mode = 0x01, .sample_rate = 0 }};
+    dev.interface = NULL; /* The board's bus handle */
+    if ({l}_init(&dev, &config) != 0) {{
+        return 1;
+    }}
+    return {l}_self_test(&dev) ? 0 : 1;
+}}
+"#,
+            c = component,
+            header = header,
+            l = component.to_lowercase()
+        )
+    }
+
+    fn generate_rust_driver(
+        &self,
+        component: &str,
+        platform: &str,
+        protocol: &str,
+        asynchronous: bool,
+    ) -> Result<String, String> {
+        let spi = match protocol.to_uppercase().as_str() {
+            "I2C" => false,
+            "SPI" => true,
+            _ => {
+                return Err(format!(
+                    "Rust drivers target the embedded-hal 1.0 I2c and SpiDevice traits; use I2C or SPI, not {}",
+                    protocol
+                ))
+            }
+        };
+        let (hal, asyncness, wait) = if asynchronous {
+            ("embedded_hal_async", "async ", ".await")
+        } else {
+            ("embedded_hal", "", "")
+        };
+        let (bus, field, import, new, read, write) = if spi {
+            (
+                "SPI",
+                "spi: SPI,",
+                "spi::{Operation, SpiDevice}",
+                "/// Create a new driver instance on an SPI device (bus plus chip select)\n    pub fn new(spi: SPI) -> Self {\n        Self {\n            spi,\n            config: Config::default(),\n        }\n    }\n\n    /// Release the SPI device\n    pub fn release(self) -> SPI {\n        self.spi\n    }",
+                "self\n            .spi\n            .transaction(&mut [Operation::Write(&[reg | 0x80]), Operation::Read(buf)])",
+                "self.spi.write(&[reg, value])",
+            )
+        } else {
+            (
+                "I2C",
+                "i2c: I2C,\n    address: u8,",
+                "i2c::I2c",
+                "/// Create a new driver instance at a 7-bit I2C address\n    pub fn new(i2c: I2C, address: u8) -> Self {\n        Self {\n            i2c,\n            address,\n            config: Config::default(),\n        }\n    }\n\n    /// Release the I2C bus\n    pub fn release(self) -> I2C {\n        self.i2c\n    }",
+                "self.i2c.write_read(self.address, &[reg], buf)",
+                "self.i2c.write(self.address, &[reg, value])",
+            )
+        };
+        let driver = register_driver::camel_ident(component);
+        let bound = if spi { "SpiDevice" } else { "I2c" };
+        Ok(format!(
+            r#"//! {c} Driver for {platform}
+//! Communication Protocol: {protocol}
 //! Generated by Wake - Hardware-First Coding Agent
 //! 
 //! Copyright (c) 2024 Wind
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
-use embedded_hal::{{
-    blocking::{},
-    digital::v2::OutputPin,
-}};
+use {hal}::{import};
 
-/// {} register definitions
+/// {c} register definitions
 pub mod registers {{
     pub const DEVICE_ID: u8 = 0x00;
     pub const CONFIG: u8 = 0x01;
@@ -207,8 +544,11 @@ pub mod registers {{
     // Add more registers as needed
 }}
 
-/// {} operating modes
-#[derive(Clone, Copy, Debug)]
+/// Soft-reset command written to CONFIG
+pub const RESET_COMMAND: u8 = 0x80;
+
+/// {c} operating modes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OperatingMode {{
     Sleep,
     Normal,
@@ -216,7 +556,18 @@ pub enum OperatingMode {{
     LowPower,
 }}
 
-/// {} configuration
+impl OperatingMode {{
+    fn bits(self) -> u8 {{
+        match self {{
+            OperatingMode::Sleep => 0x00,
+            OperatingMode::Normal => 0x01,
+            OperatingMode::HighPerformance => 0x02,
+            OperatingMode::LowPower => 0x03,
+        }}
+    }}
+}}
+
+/// {c} configuration
 #[derive(Clone, Copy, Debug)]
 pub struct Config {{
     pub mode: OperatingMode,
@@ -234,117 +585,166 @@ impl Default for Config {{
     }}
 }}
 
-/// {} driver structure
-pub struct {}<I, CS> {{
-    interface: I,
-    cs_pin: CS,
+/// Driver errors
+#[derive(Debug)]
+pub enum Error<E> {{
+    /// Bus error
+    Bus(E),
+}}
+
+/// {c} driver structure
+pub struct {d}<{bus}> {{
+    {field}
     config: Config,
 }}
 
-impl<I, CS, E> {}<I, CS>
-where
-    I: {},
-    CS: OutputPin,
-{{
-    /// Create a new {} driver instance
-    pub fn new(interface: I, cs_pin: CS) -> Result<Self, E> {{
-        Ok(Self {{
-            interface,
-            cs_pin,
-            config: Config::default(),
-        }})
+impl<{bus}: {bound}> {d}<{bus}> {{
+    {new}
+
+    /// Initialize the {c} device: reset, then apply the configuration
+    pub {a}fn init(&mut self) -> Result<(), Error<{bus}::Error>> {{
+        self.reset(){w}?;
+        self.configure(self.config){w}
     }}
-    
-    /// Initialize the {} device
-    pub fn init(&mut self) -> Result<(), E> {{
-        // Reset device
-        self.reset()?;
-        
-        // Configure device
-        self.configure()?;
-        
-        // Verify device ID
-        self.verify_device_id()?;
-        
-        Ok(())
+
+    /// Read the device ID; compare it with the value from the datasheet
+    pub {a}fn read_device_id(&mut self) -> Result<u8, Error<{bus}::Error>> {{
+        self.read_register(registers::DEVICE_ID){w}
     }}
-    
-    /// Read device ID
-    pub fn read_device_id(&mut self) -> Result<u8, E> {{
-        self.read_register(registers::DEVICE_ID)
+
+    /// Read the status register
+    pub {a}fn read_status(&mut self) -> Result<u8, Error<{bus}::Error>> {{
+        self.read_register(registers::STATUS){w}
     }}
-    
+
     /// Read from a register
-    fn read_register(&mut self, reg: u8) -> Result<u8, E> {{
-        // Implementation depends on protocol
-        todo!("Implement {} read for {}")
+    {a}fn read_register(&mut self, reg: u8) -> Result<u8, Error<{bus}::Error>> {{
+        let mut buf = [0u8; 1];
+        self.read_registers(reg, &mut buf){w}?;
+        Ok(buf[0])
     }}
-    
-    /// Write to a register
-    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), E> {{
-        // Implementation depends on protocol
-        todo!("Implement {} write for {}")
+
+    /// Read consecutive registers starting at `reg`
+    {a}fn read_registers(&mut self, reg: u8, buf: &mut [u8]) -> Result<(), Error<{bus}::Error>> {{
+        let result = {read}{w};
+        result.map_err(Error::Bus)
     }}
-    
-    /// Reset the device
-    pub fn reset(&mut self) -> Result<(), E> {{
-        // Send reset command
-        self.write_register(registers::CONFIG, 0x80)?;
-        
-        // Wait for reset to complete
-        // Note: In real implementation, use proper delay
-        
-        Ok(())
+
+    /// Write to a register
+    {a}fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<{bus}::Error>> {{
+        let result = {write}{w};
+        result.map_err(Error::Bus)
     }}
-    
-    /// Configure the device
-    pub fn configure(&mut self) -> Result<(), E> {{
-        let config_value = match self.config.mode {{
-            OperatingMode::Sleep => 0x00,
-            OperatingMode::Normal => 0x01,
-            OperatingMode::HighPerformance => 0x02,
-            OperatingMode::LowPower => 0x03,
-        }};
-        
-        self.write_register(registers::CONFIG, config_value)?;
-        
-        Ok(())
+
+    /// Reset the device; wait the datasheet's reset time before further access
+    pub {a}fn reset(&mut self) -> Result<(), Error<{bus}::Error>> {{
+        self.write_register(registers::CONFIG, RESET_COMMAND){w}
     }}
-    
-    /// Verify device ID
-    fn verify_device_id(&mut self) -> Result<(), E> {{
-        let id = self.read_device_id()?;
-        // Check if ID matches expected value
-        // This would be device-specific
+
+    /// Apply a configuration
+    pub {a}fn configure(&mut self, config: Config) -> Result<(), Error<{bus}::Error>> {{
+        self.write_register(registers::CONFIG, config.mode.bits()){w}?;
+        self.config = config;
         Ok(())
     }}
-    
-    /// Read sensor data
-    pub fn read_data(&mut self) -> Result<u16, E> {{
-        let high = self.read_register(registers::DATA_OUT)?;
-        let low = self.read_register(registers::DATA_OUT + 1)?;
-        Ok(((high as u16) << 8) | (low as u16))
+
+    /// Read sensor data (DATA_OUT is the high byte, the next register the low byte)
+    pub {a}fn read_data(&mut self) -> Result<u16, Error<{bus}::Error>> {{
+        let mut buf = [0u8; 2];
+        self.read_registers(registers::DATA_OUT, &mut buf){w}?;
+        Ok(u16::from_be_bytes(buf))
     }}
 }}
-"#,
-            component,
-            platform,
-            protocol,
-            self.get_rust_hal_traits(protocol),
-            component,
-            component,
-            component,
-            component,
-            component.to_lowercase(),
-            component,
-            self.get_rust_interface_trait(protocol),
-            component,
-            component,
-            protocol,
-            component,
-            protocol,
-            component
-        )
+{tests}"#,
+            c = component,
+            platform = platform,
+            protocol = protocol,
+            hal = hal,
+            import = import,
+            d = driver,
+            bus = bus,
+            bound = bound,
+            field = field,
+            new = new,
+            a = asyncness,
+            w = wait,
+            read = read,
+            write = write,
+            tests = self.generate_rust_tests(&driver, spi, asynchronous)
+        ))
+    }
+
+    /// Mock-bus tests of the template driver's init and read sequences
+    fn generate_rust_tests(&self, driver: &str, spi: bool, asynchronous: bool) -> String {
+        let write = |data: &str| {
+            if spi {
+                format!("            Transaction::transaction_start(),\n            Transaction::write_vec(vec![{}]),\n            Transaction::transaction_end(),\n", data)
+            } else {
+                format!("            Transaction::write(ADDRESS, vec![{}]),\n", data)
+            }
+        };
+        let read = |reg: u8, data: &str| {
+            if spi {
+                format!("            Transaction::transaction_start(),\n            Transaction::write_vec(vec![0x{:02X}]),\n            Transaction::read_vec(vec![{}]),\n            Transaction::transaction_end(),\n", reg | 0x80, data)
+            } else {
+                format!(
+                    "            Transaction::write_read(ADDRESS, vec![0x{:02X}], vec![{}]),\n",
+                    reg, data
+                )
+            }
+        };
+        let call = |expr: &str| {
+            if asynchronous {
+                format!("block_on({})", expr)
+            } else {
+                expr.to_string()
+            }
+        };
+        let new = if spi {
+            format!("{}::new(bus.clone())", driver)
+        } else {
+            format!("{}::new(bus.clone(), ADDRESS)", driver)
+        };
+        let test = |name: &str, expectations: String, body: String| {
+            format!(
+                "\n    #[test]\n    fn {}() {{\n        let mut bus = Mock::new(&[\n{}        ]);\n        let mut device = {};\n        {}\n        bus.done();\n    }}\n",
+                name, expectations, new, body
+            )
+        };
+
+        let mut out = format!(
+            "\n#[cfg(test)]\nmod tests {{\n    use super::*;\n    use embedded_hal_mock::eh1::{}::{{Mock, Transaction}};\n",
+            if spi { "spi" } else { "i2c" }
+        );
+        if asynchronous {
+            out.push_str("    use futures::executor::block_on;\n");
+        }
+        if !spi {
+            out.push_str("\n    const ADDRESS: u8 = 0x42;\n");
+        }
+        out.push_str(&test(
+            "init_resets_then_configures",
+            write("0x01, 0x80") + &write("0x01, 0x01"),
+            format!("{}.unwrap();", call("device.init()")),
+        ));
+        out.push_str(&test(
+            "read_device_id",
+            read(0x00, "0x60"),
+            format!(
+                "assert_eq!({}.unwrap(), 0x60);",
+                call("device.read_device_id()")
+            ),
+        ));
+        out.push_str(&test(
+            "read_data_is_msb_first",
+            read(0x03, "0x12, 0x34"),
+            format!(
+                "assert_eq!({}.unwrap(), 0x1234);",
+                call("device.read_data()")
+            ),
+        ));
+        out.push_str("}\n");
+        out
     }
 
     fn generate_micropython_driver(
@@ -354,8 +754,8 @@ where
         protocol: &str,
     ) -> String {
         format!(
-            r#"""
-{}Driver for MicroPython
+            r#""""
+{} Driver for MicroPython
 Communication Protocol: {}
 Generated by Wake - Hardware-First Coding Agent
 
@@ -482,26 +882,6 @@ class {}:
         # Implement device-specific self-test
         status = self.get_status()
         return (status & 0x01) == 0
-
-# Example usage
-if __name__ == "__main__":
-    # Initialize {} interface
-    {} = {}
-    
-    # Create driver instance
-    device = {}({})
-    
-    # Initialize device
-    if device.init():
-        print("Device initialized successfully")
-        
-        # Read data
-        data = device.read_data()
-        print(f"Data: {{data}}")
-        
-        # Check status
-        status = device.get_status()
-        print(f"Status: {{status:#x}}")
 "#,
             component,
             protocol,
@@ -518,12 +898,41 @@ if __name__ == "__main__":
             component,
             component,
             self.generate_micropython_read(protocol),
-            self.generate_micropython_write(protocol),
-            protocol,
-            self.get_micropython_param(protocol),
-            self.get_micropython_init(protocol),
-            component,
-            self.get_micropython_param(protocol)
+            self.generate_micropython_write(protocol)
+        )
+    }
+
+    fn generate_micropython_example(
+        &self,
+        component: &str,
+        protocol: &str,
+        module: &str,
+    ) -> String {
+        let interface = self.get_micropython_interface(protocol);
+        let imports = if interface == "Pin" {
+            interface.to_string()
+        } else {
+            format!("{}, Pin", interface)
+        };
+        format!(
+            r#""""{c} example"""
+
+from machine import {imports}
+from {module} import {c}
+
+{param} = {init}
+device = {c}({param})
+
+if device.init():
+    print("Device initialized successfully")
+    print(f"Data: {{device.read_data()}}")
+    print(f"Status: {{device.get_status():#x}}")
+"#,
+            c = component,
+            imports = imports,
+            module = module,
+            param = self.get_micropython_param(protocol),
+            init = self.get_micropython_init(protocol)
         )
     }
 
@@ -558,24 +967,6 @@ if __name__ == "__main__":
         )
     }
 
-    fn get_rust_hal_traits(&self, protocol: &str) -> &str {
-        match protocol {
-            "I2C" => "i2c::{Read, Write}",
-            "SPI" => "spi::{Transfer, Write}",
-            "UART" => "serial::{Read, Write}",
-            _ => "delay::DelayMs",
-        }
-    }
-
-    fn get_rust_interface_trait(&self, protocol: &str) -> &str {
-        match protocol {
-            "I2C" => "embedded_hal::blocking::i2c::WriteRead",
-            "SPI" => "embedded_hal::blocking::spi::Transfer<u8>",
-            "UART" => "embedded_hal::serial::Write<u8>",
-            _ => "embedded_hal::blocking::delay::DelayMs<u16>",
-        }
-    }
-
     fn get_micropython_interface(&self, protocol: &str) -> &str {
         match protocol {
             "I2C" => "I2C",
@@ -627,46 +1018,130 @@ impl Tool for DriverGenerator {
     type Params = DriverGeneratorArgs;
 
     fn capabilities(&self) -> &'static [ToolCapability] {
-        &[ToolCapability::Read]
+        &[ToolCapability::Read, ToolCapability::Write]
+    }
+
+    async fn execute_preview(&self, args: Self::Params) -> Option<ToolResult> {
+        let dir = output_dir(&args);
+        let files = match load_model(&args).await {
+            Ok(model) => self.package(&args, model.as_ref()),
+            Err(e) => Err(e),
+        };
+        Some(match files {
+            Ok(files) => {
+                let preview = package::preview(&dir, &files);
+                let mut metadata = HashMap::new();
+                metadata.insert("path".to_string(), json!(dir.display().to_string()));
+                metadata.insert(
+                    "files".to_string(),
+                    json!(files.iter().map(|f| &f.path).collect::<Vec<_>>()),
+                );
+                metadata.insert("content_length".to_string(), json!(preview.len()));
+                metadata.insert("line_count".to_string(), json!(preview.lines().count()));
+                metadata.insert("operation".to_string(), json!("write_preview"));
+                ToolResult::success_with_metadata(preview, metadata)
+            }
+            Err(e) => ToolResult::error(e),
+        })
     }
 
     async fn execute(&self, args: Self::Params) -> ToolResult {
-        let driver_code = self.generate_driver_template(&args);
-
-        let file_extension = match args.language.as_str() {
-            "C" => "h",
-            "C++" => "hpp",
-            "Rust" => "rs",
-            "MicroPython" => "py",
-            _ => "txt",
+        let model = match load_model(&args).await {
+            Ok(model) => model,
+            Err(e) => return ToolResult::error(e),
+        };
+        let files = match self.package(&args, model.as_ref()) {
+            Ok(files) => files,
+            Err(e) => return ToolResult::error(e),
         };
 
-        let filename = format!(
-            "{}_{}_driver.{}",
-            args.component.to_lowercase(),
-            args.platform.to_lowercase(),
-            file_extension
-        );
+        let mut metadata = HashMap::new();
+        if let Some(model) = &model {
+            metadata.insert("register_map".to_string(), json!(model.source));
+            metadata.insert("peripheral".to_string(), json!(model.peripheral.name));
+            metadata.insert(
+                "registers".to_string(),
+                json!(model.peripheral.registers.len()),
+            );
+            if let Some(id) = &model.device_id {
+                metadata.insert(
+                    "device_id".to_string(),
+                    json!({ "register": id.register, "value": id.value }),
+                );
+            }
+        }
 
-        ToolResult::success(format!(
-            "Generated {} driver for {} on {} platform using {} protocol.\n\nDriver code saved as: {}\n\n{}",
+        let dir = output_dir(&args);
+        let tree = package::tree(&dir, &files);
+        let written = match package::write(&dir, &files) {
+            Ok(written) => written,
+            Err(e) => return ToolResult::error(e),
+        };
+
+        let mut output = format!(
+            "Generated {} driver package for {} on {} platform using {} protocol.\n\nWrote {} files to {}:\n{}",
             args.language,
             args.component,
             args.platform,
             args.protocol,
-            filename,
-            driver_code
-        ))
+            written.len(),
+            dir.display(),
+            tree
+        );
+        for file in &files {
+            output.push_str(&format!("\n{}:\n\n{}", file.path, file.content));
+        }
+
+        metadata.insert("path".to_string(), json!(dir.display().to_string()));
+        metadata.insert(
+            "files".to_string(),
+            json!(files.iter().map(|f| &f.path).collect::<Vec<_>>()),
+        );
+        metadata.insert("written".to_string(), json!(written));
+        ToolResult::success_with_metadata(output, metadata)
     }
 }
 
+/// Package directory: `output_dir`, or `<component>_driver` in the working directory
+fn output_dir(args: &DriverGeneratorArgs) -> PathBuf {
+    match &args.output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(format!(
+            "{}_driver",
+            register_driver::file_stem(&args.component)
+        )),
+    }
+}
+
+/// Register model of the part, when a register map was given
+async fn load_model(args: &DriverGeneratorArgs) -> Result<Option<DriverModel>, String> {
+    let Some(register_map) = args.register_map.clone() else {
+        return Ok(None);
+    };
+    let component = args.component.clone();
+    // Vendor SVD files run to several megabytes
+    let (source, device) =
+        tokio::task::spawn_blocking(move || register_driver::load(&register_map, &component))
+            .await
+            .map_err(|e| format!("Loading register map failed: {}", e))??;
+    DriverModel::new(
+        &source,
+        &device,
+        &args.component,
+        args.peripheral.as_deref(),
+        &args.protocol,
+        args.device_id.as_deref(),
+    )
+    .map(Some)
+}
+
 impl ToolDescription for DriverGenerator {
     fn name(&self) -> &'static str {
         "driver_generator"
     }
 
     fn description(&self) -> &'static str {
-        "Generate hardware device drivers from component specifications. Supports multiple platforms (Arduino, STM32, ESP32, Raspberry Pi) and languages (C, C++, Rust, MicroPython)."
+        "Generate hardware device driver packages from component specifications and write them to output_dir: header and source for C/C++, a Cargo crate with src/lib.rs, examples/ and mock-bus unit tests for Rust (embedded-hal 1.0, or embedded-hal-async with async_hal), a module and example for MicroPython. Supports multiple platforms (Arduino, STM32, ESP32, Raspberry Pi); the Zephyr, ESP-IDF and PlatformIO platforms lay C/C++ drivers out as a Zephyr module (devicetree binding, Kconfig, CMakeLists and device glue), an ESP-IDF component (idf_component.yml and bus glue) or a PlatformIO library (library.json). Given a register map (SVD, YAML or JSON), the driver carries the part's real register addresses, bitfield accessors, reset values and device-ID check."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -674,3 +1149,201 @@ impl ToolDescription for DriverGenerator {
             .unwrap_or_else(|_| serde_json::json!({}))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::register_map::TEST_YAML;
+    use tempfile::TempDir;
+
+    fn args(language: &str, register_map: Option<String>, dir: &TempDir) -> DriverGeneratorArgs {
+        DriverGeneratorArgs {
+            component: "BME280".to_string(),
+            platform: "STM32".to_string(),
+            language: language.to_string(),
+            protocol: "I2C".to_string(),
+            features: None,
+            include_examples: None,
+            register_map,
+            peripheral: None,
+            device_id: None,
+            async_hal: None,
+            output_dir: Some(dir.path().join(language).display().to_string()),
+        }
+    }
+
+    fn read(dir: &TempDir, path: &str) -> String {
+        std::fs::read_to_string(dir.path().join(path)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_register_map_driver() {
+        let tool = DriverGenerator::new();
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("bme280.yaml"), TEST_YAML).unwrap();
+
+        let mut request = args("C", Some(dir.path().display().to_string()), &dir);
+        request.device_id = Some("STATUS=0x00".to_string());
+        let result = tool.execute(request).await;
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("Wrote 3 files to"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["registers"], 2);
+            assert_eq!(metadata["device_id"]["register"], "STATUS");
+            assert_eq!(
+                metadata["files"],
+                json!(["bme280.h", "bme280.c", "examples/main.c"])
+            );
+        } else {
+            panic!("Expected success result");
+        }
+        let header = read(&dir, "C/bme280.h");
+        assert!(header.contains("#define BME280_REG_CTRL_MEAS 0xF4U"));
+        assert!(header.contains("#include \"stm32f4xx_hal.h\""));
+        assert!(!header.contains("BME280_REG_DEVICE_ID"));
+        assert!(read(&dir, "C/bme280.c").contains("#include \"bme280.h\""));
+        assert!(read(&dir, "C/examples/main.c").contains("#include \"../bme280.h\""));
+
+        // Inline YAML
+        tool.execute(args("Rust", Some(TEST_YAML.to_string()), &dir))
+            .await;
+        let lib = read(&dir, "Rust/src/lib.rs");
+        assert!(lib.contains("pub mod ctrl_meas {"));
+        assert!(lib.contains("// The register map has no ID register"));
+        assert!(read(&dir, "Rust/Cargo.toml").contains("name = \"bme280-driver\""));
+        assert!(
+            read(&dir, "Rust/examples/basic.rs").contains("use bme280_driver::{Error, Bme280};")
+        );
+
+        tool.execute(args("MicroPython", Some(TEST_YAML.to_string()), &dir))
+            .await;
+        assert!(read(&dir, "MicroPython/example.py").contains("from bme280 import Bme280"));
+
+        // Without a map the template is split into header and source
+        let mut request = args("C", None, &dir);
+        request.include_examples = Some(false);
+        request.output_dir = Some(dir.path().join("template").display().to_string());
+        tool.execute(request).await;
+        assert!(read(&dir, "template/bme280.h").contains("#define BME280_REG_DEVICE_ID    0x00"));
+        assert!(read(&dir, "template/bme280.c").contains("int bme280_init(BME280_t* dev"));
+        assert!(!dir.path().join("template/examples").exists());
+
+        match tool
+            .execute(args("Go", Some(TEST_YAML.to_string()), &dir))
+            .await
+        {
+            ToolResult::Error { error, .. } => assert!(error.contains("Unsupported language")),
+            _ => panic!("Expected error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rust_template_targets_embedded_hal_1() {
+        let tool = DriverGenerator::new();
+        let dir = TempDir::new().unwrap();
+
+        let mut request = args("Rust", None, &dir);
+        request.protocol = "SPI".to_string();
+        request.async_hal = Some(true);
+        tool.execute(request).await;
+        let lib = read(&dir, "Rust/src/lib.rs");
+        assert!(lib.contains("use embedded_hal_async::spi::{Operation, SpiDevice};"));
+        assert!(lib.contains("impl<SPI: SpiDevice> Bme280<SPI> {"));
+        assert!(lib.contains("pub async fn init(&mut self) -> Result<(), Error<SPI::Error>> {"));
+        assert!(lib.contains("Transaction::write_vec(vec![0x83]),\n            Transaction::read_vec(vec![0x12, 0x34]),"));
+        assert!(!lib.contains("todo!"));
+        assert!(read(&dir, "Rust/Cargo.toml").contains("embedded-hal-async = \"1.0\""));
+
+        tool.execute(args("Rust", None, &dir)).await;
+        let lib = read(&dir, "Rust/src/lib.rs");
+        assert!(lib.contains("impl<I2C: I2c> Bme280<I2C> {"));
+        assert!(lib.contains("Transaction::write(ADDRESS, vec![0x01, 0x80]),"));
+        assert!(read(&dir, "Rust/examples/basic.rs").contains("fn bring_up<I2C: I2c>(i2c: I2C)"));
+
+        let mut request = args("Rust", None, &dir);
+        request.protocol = "UART".to_string();
+        match tool.execute(request).await {
+            ToolResult::Error { error, .. } => assert!(error.contains("I2c and SpiDevice")),
+            _ => panic!("Expected error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_framework_scaffolding() {
+        let tool = DriverGenerator::new();
+        let dir = TempDir::new().unwrap();
+
+        let mut request = args("C", Some(TEST_YAML.to_string()), &dir);
+        request.platform = "Zephyr".to_string();
+        let result = tool.execute(request).await;
+        if let ToolResult::Success { metadata, .. } = result {
+            let files = metadata.unwrap()["files"].clone();
+            assert!(files
+                .as_array()
+                .unwrap()
+                .contains(&json!("dts/bindings/wake,bme280.yaml")));
+        } else {
+            panic!("Expected success result");
+        }
+        assert!(read(&dir, "C/include/bme280.h").contains("#define BME280_REG_CTRL_MEAS 0xF4U"));
+        assert!(read(&dir, "C/Kconfig").contains("config WAKE_BME280\n"));
+        assert!(read(&dir, "C/bme280_zephyr.c").contains("i2c_write_read_dt"));
+
+        let mut request = args("C++", None, &dir);
+        request.platform = "ESP-IDF".to_string();
+        request.output_dir = Some(dir.path().join("idf").display().to_string());
+        tool.execute(request).await;
+        assert!(
+            read(&dir, "idf/CMakeLists.txt").contains("idf_component_register(SRCS \"bme280.cpp\"")
+        );
+        assert!(dir.path().join("idf/idf_component.yml").exists());
+
+        let mut request = args("C", Some(TEST_YAML.to_string()), &dir);
+        request.platform = "PlatformIO".to_string();
+        request.output_dir = Some(dir.path().join("pio").display().to_string());
+        tool.execute(request).await;
+        assert!(read(&dir, "pio/library.json").contains("\"name\": \"bme280-driver\""));
+        assert!(read(&dir, "pio/examples/basic/main.c").contains("#include \"bme280.h\""));
+
+        let mut request = args("Rust", None, &dir);
+        request.platform = "Zephyr".to_string();
+        match tool.execute(request).await {
+            ToolResult::Error { error, .. } => {
+                assert!(error.contains("Zephyr scaffolding is generated for C and C++ drivers"))
+            }
+            _ => panic!("Expected error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_package_preview() {
+        let tool = DriverGenerator::new();
+        let dir = TempDir::new().unwrap();
+        assert!(tool.capabilities().contains(&ToolCapability::Write));
+
+        let request = args("Rust", Some(TEST_YAML.to_string()), &dir);
+        match tool.execute_preview(request.clone()).await.unwrap() {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("Cargo.toml (new,"));
+                assert!(output.contains("+++ src/lib.rs\n"));
+                assert_eq!(metadata.unwrap()["operation"], "write_preview");
+            }
+            _ => panic!("Expected preview"),
+        }
+        assert!(!dir.path().join("Rust").exists());
+
+        tool.execute(request.clone()).await;
+        std::fs::write(dir.path().join("Rust/Cargo.toml"), "[package]\n").unwrap();
+        let preview = tool.execute_preview(request).await.unwrap().to_string();
+        assert!(preview.contains("Cargo.toml (modified,"));
+        assert!(preview.contains("lib.rs (unchanged,"));
+        assert!(preview.contains("--- a/Cargo.toml\n+++ b/Cargo.toml\n"));
+
+        let mut request = args("C", Some("/nonexistent/map.svd".to_string()), &dir);
+        request.output_dir = None;
+        assert!(matches!(
+            tool.execute_preview(request).await,
+            Some(ToolResult::Error { .. })
+        ));
+    }
+}
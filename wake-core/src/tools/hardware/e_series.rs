@@ -0,0 +1,450 @@
+//! Standard E-series resistor selection for two-resistor networks.
+//!
+//! Covers voltage dividers, regulator feedback networks and op-amp gain
+//! stages. Candidates are ranked by nominal error, then by the worst-case
+//! output over resistor tolerance.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+const E12: [u16; 12] = [10, 12, 15, 18, 22, 27, 33, 39, 47, 56, 68, 82];
+
+const E24: [u16; 24] = [
+    10, 11, 12, 13, 15, 16, 18, 20, 22, 24, 27, 30, 33, 36, 39, 43, 47, 51, 56, 62, 68, 75, 82, 91,
+];
+
+/// E48 is every other E96 value
+const E96: [u16; 96] = [
+    100, 102, 105, 107, 110, 113, 115, 118, 121, 124, 127, 130, 133, 137, 140, 143, 147, 150, 154,
+    158, 162, 165, 169, 174, 178, 182, 187, 191, 196, 200, 205, 210, 215, 221, 226, 232, 237, 243,
+    249, 255, 261, 267, 274, 280, 287, 294, 301, 309, 316, 324, 332, 340, 348, 357, 365, 374, 383,
+    392, 402, 412, 422, 432, 442, 453, 464, 475, 487, 499, 511, 523, 536, 549, 562, 576, 590, 604,
+    619, 634, 649, 665, 681, 698, 715, 732, 750, 768, 787, 806, 825, 845, 866, 887, 909, 931, 953,
+    976,
+];
+
+/// Decades searched: 1 Ω to 9.76 MΩ
+const DECADES: std::ops::Range<i32> = 0..7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum ESeries {
+    E12,
+    E24,
+    E48,
+    E96,
+}
+
+impl ESeries {
+    pub fn all() -> [ESeries; 4] {
+        [ESeries::E12, ESeries::E24, ESeries::E48, ESeries::E96]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ESeries::E12 => "E12",
+            ESeries::E24 => "E24",
+            ESeries::E48 => "E48",
+            ESeries::E96 => "E96",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ESeries> {
+        match name.trim().to_uppercase().as_str() {
+            "E12" | "12" => Some(ESeries::E12),
+            "E24" | "24" => Some(ESeries::E24),
+            "E48" | "48" => Some(ESeries::E48),
+            "E96" | "96" => Some(ESeries::E96),
+            _ => None,
+        }
+    }
+
+    /// Tolerance the series is normally sold in
+    pub fn default_tolerance_percent(&self) -> f64 {
+        match self {
+            ESeries::E12 => 10.0,
+            ESeries::E24 => 5.0,
+            ESeries::E48 => 2.0,
+            ESeries::E96 => 1.0,
+        }
+    }
+
+    /// Mantissas in [1, 10)
+    fn mantissas(&self) -> Vec<f64> {
+        match self {
+            ESeries::E12 => E12.iter().map(|&v| v as f64 / 10.0).collect(),
+            ESeries::E24 => E24.iter().map(|&v| v as f64 / 10.0).collect(),
+            ESeries::E48 => E96.iter().step_by(2).map(|&v| v as f64 / 100.0).collect(),
+            ESeries::E96 => E96.iter().map(|&v| v as f64 / 100.0).collect(),
+        }
+    }
+
+    /// Every value of the series over the searched decades, ascending
+    pub fn values(&self) -> Vec<f64> {
+        let mantissas = self.mantissas();
+        DECADES
+            .flat_map(|decade| {
+                let scale = 10f64.powi(decade);
+                // Round away float noise so 4.7 * 1000 prints as 4700
+                mantissas
+                    .iter()
+                    .map(move |m| (m * scale * 1000.0).round() / 1000.0)
+            })
+            .collect()
+    }
+}
+
+/// Two-resistor network and how its output depends on the top/bottom resistor
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResistorNetwork {
+    /// Vout = Vin · Rbottom / (Rtop + Rbottom)
+    Divider { vin: f64 },
+    /// Regulator feedback, Vout = Vref · (1 + Rtop / Rbottom)
+    Feedback { vref: f64 },
+    /// G = 1 + Rf / Rg
+    NonInvertingGain,
+    /// G = -Rf / Rin
+    InvertingGain,
+}
+
+impl ResistorNetwork {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResistorNetwork::Divider { .. } => "voltage divider",
+            ResistorNetwork::Feedback { .. } => "feedback network",
+            ResistorNetwork::NonInvertingGain => "non-inverting gain",
+            ResistorNetwork::InvertingGain => "inverting gain",
+        }
+    }
+
+    /// Names of the (top, bottom) resistors
+    pub fn resistor_names(&self) -> (&'static str, &'static str) {
+        match self {
+            ResistorNetwork::Divider { .. } | ResistorNetwork::Feedback { .. } => {
+                ("R1 (top)", "R2 (bottom)")
+            }
+            ResistorNetwork::NonInvertingGain => ("Rf", "Rg"),
+            ResistorNetwork::InvertingGain => ("Rf", "Rin"),
+        }
+    }
+
+    pub fn output(&self, top: f64, bottom: f64) -> f64 {
+        match self {
+            ResistorNetwork::Divider { vin } => vin * bottom / (top + bottom),
+            ResistorNetwork::Feedback { vref } => vref * (1.0 + top / bottom),
+            ResistorNetwork::NonInvertingGain => 1.0 + top / bottom,
+            ResistorNetwork::InvertingGain => -top / bottom,
+        }
+    }
+
+    /// Exact top resistor that hits `target` for a given bottom resistor
+    fn ideal_top(&self, bottom: f64, target: f64) -> f64 {
+        match self {
+            ResistorNetwork::Divider { vin } => bottom * (vin / target - 1.0),
+            ResistorNetwork::Feedback { vref } => bottom * (target / vref - 1.0),
+            ResistorNetwork::NonInvertingGain => bottom * (target - 1.0),
+            ResistorNetwork::InvertingGain => bottom * -target,
+        }
+    }
+
+    /// Current through the string, where the network sits across a known voltage
+    fn current(&self, top: f64, bottom: f64, target: f64) -> Option<f64> {
+        match self {
+            ResistorNetwork::Divider { vin } => Some(vin / (top + bottom)),
+            ResistorNetwork::Feedback { .. } => Some(target / (top + bottom)),
+            _ => None,
+        }
+    }
+
+    fn validate(&self, target: f64) -> Result<(), String> {
+        let ok = match self {
+            ResistorNetwork::Divider { vin } => {
+                *vin != 0.0 && target / vin > 0.0 && target / vin < 1.0
+            }
+            ResistorNetwork::Feedback { vref } => *vref > 0.0 && target > *vref,
+            ResistorNetwork::NonInvertingGain => target > 1.0,
+            ResistorNetwork::InvertingGain => target < 0.0,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(match self {
+                ResistorNetwork::Divider { .. } => {
+                    "Divider output must lie strictly between 0 and the input voltage"
+                }
+                ResistorNetwork::Feedback { .. } => {
+                    "Feedback output voltage must be above the reference voltage"
+                }
+                ResistorNetwork::NonInvertingGain => "Non-inverting gain must be greater than 1",
+                ResistorNetwork::InvertingGain => "Inverting gain must be negative",
+            }
+            .to_string())
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectionConstraints {
+    /// Allowed range for Rtop + Rbottom in ohms
+    pub total_min: f64,
+    pub total_max: f64,
+    /// Preferred Rtop + Rbottom; defaults to the geometric middle of the range
+    pub total_preferred: Option<f64>,
+    /// Quiescent current budget in amps (dividers and feedback networks)
+    pub max_current: Option<f64>,
+    /// Overrides the series' usual tolerance
+    pub tolerance_percent: Option<f64>,
+}
+
+impl Default for SelectionConstraints {
+    fn default() -> Self {
+        Self {
+            total_min: 1e3,
+            total_max: 1e6,
+            total_preferred: None,
+            max_current: None,
+            tolerance_percent: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ResistorPair {
+    pub series: ESeries,
+    pub top_ohms: f64,
+    pub bottom_ohms: f64,
+    pub output: f64,
+    pub error_percent: f64,
+    pub tolerance_percent: f64,
+    pub worst_case_min: f64,
+    pub worst_case_max: f64,
+    /// Largest deviation from the target over all tolerance corners
+    pub worst_case_error_percent: f64,
+    pub total_ohms: f64,
+    pub current_a: Option<f64>,
+}
+
+/// Best `count` distinct ratios from one series that satisfy the constraints
+pub fn select_pairs(
+    network: ResistorNetwork,
+    target: f64,
+    series: ESeries,
+    constraints: &SelectionConstraints,
+    count: usize,
+) -> Result<Vec<ResistorPair>, String> {
+    network.validate(target)?;
+    if constraints.total_min > constraints.total_max {
+        return Err("Minimum total resistance is above the maximum".to_string());
+    }
+    let tolerance = constraints
+        .tolerance_percent
+        .unwrap_or_else(|| series.default_tolerance_percent());
+    let preferred = constraints
+        .total_preferred
+        .unwrap_or_else(|| (constraints.total_min * constraints.total_max).sqrt());
+    let values = series.values();
+
+    // Keyed by the top/bottom ratio so 10k/2k and 100k/20k count once
+    let mut best: HashMap<i64, ResistorPair> = HashMap::new();
+    for &bottom in &values {
+        let ideal = network.ideal_top(bottom, target);
+        if ideal <= 0.0 {
+            continue;
+        }
+        let above = values.partition_point(|&v| v < ideal);
+        for &top in values[above.saturating_sub(1)..(above + 1).min(values.len())].iter() {
+            let total = top + bottom;
+            if total < constraints.total_min || total > constraints.total_max {
+                continue;
+            }
+            let current = network.current(top, bottom, target);
+            if let (Some(limit), Some(i)) = (constraints.max_current, current) {
+                if i.abs() > limit {
+                    continue;
+                }
+            }
+
+            let pair = evaluate(network, target, series, tolerance, top, bottom, current);
+            let key = ((top / bottom).ln() * 1e9).round() as i64;
+            let distance = |p: &ResistorPair| (p.total_ohms / preferred).ln().abs();
+            match best.get(&key) {
+                Some(existing) if distance(existing) <= distance(&pair) => {}
+                _ => {
+                    best.insert(key, pair);
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<ResistorPair> = best.into_values().collect();
+    pairs.sort_by(|a, b| {
+        a.error_percent
+            .abs()
+            .total_cmp(&b.error_percent.abs())
+            .then(
+                a.worst_case_error_percent
+                    .total_cmp(&b.worst_case_error_percent),
+            )
+    });
+    pairs.truncate(count);
+    Ok(pairs)
+}
+
+fn evaluate(
+    network: ResistorNetwork,
+    target: f64,
+    series: ESeries,
+    tolerance: f64,
+    top: f64,
+    bottom: f64,
+    current: Option<f64>,
+) -> ResistorPair {
+    let output = network.output(top, bottom);
+    let t = tolerance / 100.0;
+    // Output is monotonic in each resistor, so the extremes are at the corners
+    let corners = [
+        network.output(top * (1.0 - t), bottom * (1.0 - t)),
+        network.output(top * (1.0 - t), bottom * (1.0 + t)),
+        network.output(top * (1.0 + t), bottom * (1.0 - t)),
+        network.output(top * (1.0 + t), bottom * (1.0 + t)),
+    ];
+    let worst_case_min = corners.iter().cloned().fold(f64::MAX, f64::min);
+    let worst_case_max = corners.iter().cloned().fold(f64::MIN, f64::max);
+    let deviation = (worst_case_min - target)
+        .abs()
+        .max((worst_case_max - target).abs());
+
+    ResistorPair {
+        series,
+        top_ohms: top,
+        bottom_ohms: bottom,
+        output,
+        error_percent: (output - target) / target.abs() * 100.0,
+        tolerance_percent: tolerance,
+        worst_case_min,
+        worst_case_max,
+        worst_case_error_percent: deviation / target.abs() * 100.0,
+        total_ohms: top + bottom,
+        current_a: current,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series_values() {
+        assert_eq!(ESeries::E12.values().len(), 12 * 7);
+        assert_eq!(ESeries::E48.mantissas()[1], 1.05);
+        assert!(ESeries::E24.values().contains(&4700.0));
+        assert!(ESeries::E96.values().contains(&4990.0));
+        assert_eq!(ESeries::parse("e96"), Some(ESeries::E96));
+        assert_eq!(ESeries::parse("E6"), None);
+    }
+
+    #[test]
+    fn test_exact_divider_ratio() {
+        // 12 V -> 3 V is exactly 3:1 (30/10 exists in E24, not in E12)
+        let pairs = select_pairs(
+            ResistorNetwork::Divider { vin: 12.0 },
+            3.0,
+            ESeries::E24,
+            &SelectionConstraints::default(),
+            3,
+        )
+        .unwrap();
+        assert_eq!(pairs[0].error_percent, 0.0);
+        assert_eq!(pairs[0].top_ohms / pairs[0].bottom_ohms, 3.0);
+        // 5% parts, R1 low and R2 high: 12 · 1.05 / (3 · 0.95 + 1.05) = 3.231 V
+        assert!((pairs[0].worst_case_max - 3.2308).abs() < 1e-3);
+        assert!((pairs[0].worst_case_error_percent - 7.69).abs() < 0.01);
+        assert_eq!(pairs.len(), 3);
+    }
+
+    #[test]
+    fn test_feedback_network_e96() {
+        // Vout = 0.8 V · (1 + R1/R2) = 3.3 V; no E96 pair is better than 357/115
+        let pairs = select_pairs(
+            ResistorNetwork::Feedback { vref: 0.8 },
+            3.3,
+            ESeries::E96,
+            &SelectionConstraints::default(),
+            5,
+        )
+        .unwrap();
+        let best = &pairs[0];
+        assert_eq!((best.top_ohms, best.bottom_ohms), (35700.0, 11500.0));
+        assert!((best.error_percent + 0.5007).abs() < 1e-3);
+        assert!(best.worst_case_error_percent < 2.0);
+        assert!(pairs
+            .windows(2)
+            .all(|w| w[0].error_percent.abs() <= w[1].error_percent.abs()));
+    }
+
+    #[test]
+    fn test_quiescent_current_budget() {
+        let constraints = SelectionConstraints {
+            total_max: 10e6,
+            max_current: Some(5e-6),
+            ..Default::default()
+        };
+        let pairs = select_pairs(
+            ResistorNetwork::Divider { vin: 12.0 },
+            3.3,
+            ESeries::E24,
+            &constraints,
+            5,
+        )
+        .unwrap();
+        assert!(!pairs.is_empty());
+        assert!(pairs.iter().all(|p| p.current_a.unwrap() <= 5e-6));
+        assert!(pairs.iter().all(|p| p.total_ohms >= 12.0 / 5e-6));
+    }
+
+    #[test]
+    fn test_gain_networks() {
+        let pairs = select_pairs(
+            ResistorNetwork::NonInvertingGain,
+            11.0,
+            ESeries::E24,
+            &SelectionConstraints::default(),
+            1,
+        )
+        .unwrap();
+        assert_eq!(pairs[0].output, 11.0);
+
+        let pairs = select_pairs(
+            ResistorNetwork::InvertingGain,
+            -4.7,
+            ESeries::E12,
+            &SelectionConstraints::default(),
+            1,
+        )
+        .unwrap();
+        assert_eq!(pairs[0].output, -4.7);
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        let constraints = SelectionConstraints::default();
+        let divider = ResistorNetwork::Divider { vin: 5.0 };
+        assert!(select_pairs(divider, 6.0, ESeries::E24, &constraints, 3).is_err());
+        assert!(select_pairs(
+            ResistorNetwork::Feedback { vref: 1.25 },
+            1.0,
+            ESeries::E24,
+            &constraints,
+            3
+        )
+        .is_err());
+        assert!(select_pairs(
+            ResistorNetwork::InvertingGain,
+            2.0,
+            ESeries::E24,
+            &constraints,
+            3
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Section headers, load addresses and the symbol table of an ELF file.
+
+use super::memory_usage::{Image, Section, SectionKind, Symbol};
+use object::elf;
+use object::read::elf::{ElfFile, FileHeader, ProgramHeader};
+use object::{Endianness, FileKind, Object, ObjectSection, ObjectSymbol, SectionFlags, SymbolKind};
+use std::collections::HashSet;
+
+pub fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(b"\x7fELF")
+}
+
+pub fn read(data: &[u8]) -> Result<Image, String> {
+    match FileKind::parse(data) {
+        Ok(FileKind::Elf32) => read_elf::<elf::FileHeader32<Endianness>>(data),
+        Ok(FileKind::Elf64) => read_elf::<elf::FileHeader64<Endianness>>(data),
+        _ => Err("Not an ELF file".to_string()),
+    }
+}
+
+fn read_elf<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Result<Image, String> {
+    let file = ElfFile::<Elf>::parse(data).map_err(|e| format!("Invalid ELF file: {}", e))?;
+    let endian = file.endian();
+    // (virtual address, physical address, size) of each loaded segment
+    let loads: Vec<(u64, u64, u64)> = file
+        .elf_program_headers()
+        .iter()
+        .filter(|ph| ph.p_type(endian) == elf::PT_LOAD)
+        .map(|ph| {
+            (
+                ph.p_vaddr(endian).into(),
+                ph.p_paddr(endian).into(),
+                ph.p_memsz(endian).into(),
+            )
+        })
+        .collect();
+    let thumb = file.elf_header().e_machine(endian) == elf::EM_ARM;
+
+    let mut image = Image::default();
+    for section in file.sections() {
+        let flags = match section.flags() {
+            SectionFlags::Elf { sh_flags } => sh_flags,
+            _ => 0,
+        };
+        if flags & u64::from(elf::SHF_ALLOC) == 0 || section.size() == 0 {
+            continue;
+        }
+        let addr = section.address();
+        let kind = if matches!(
+            section.kind(),
+            object::SectionKind::UninitializedData | object::SectionKind::UninitializedTls
+        ) {
+            SectionKind::Bss
+        } else if flags & u64::from(elf::SHF_WRITE) != 0 {
+            SectionKind::Data
+        } else {
+            SectionKind::Code
+        };
+        let lma = loads
+            .iter()
+            .find(|(vaddr, _, size)| addr >= *vaddr && addr < vaddr + size)
+            .map_or(addr, |(vaddr, paddr, _)| addr - vaddr + paddr);
+        image.sections.push(Section {
+            name: section.name().unwrap_or("?").to_string(),
+            addr,
+            lma,
+            size: section.size(),
+            kind,
+        });
+    }
+
+    let mut seen = HashSet::new();
+    for symbol in file.symbols() {
+        if symbol.size() == 0 || !matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data) {
+            continue;
+        }
+        let Ok(name) = symbol.name() else { continue };
+        // `$t`/`$d` mapping symbols mark code and data inside sections
+        if name.is_empty() || name.starts_with('$') {
+            continue;
+        }
+        let mut addr = symbol.address();
+        if thumb && symbol.kind() == SymbolKind::Text {
+            addr &= !1;
+        }
+        // Aliases such as IRQ handlers weakly bound to Default_Handler
+        if !seen.insert((addr, symbol.size())) {
+            continue;
+        }
+        let section = symbol
+            .section_index()
+            .and_then(|index| file.section_by_index(index).ok())
+            .and_then(|s| s.name().ok().map(str::to_string))
+            .unwrap_or_default();
+        image.symbols.push(Symbol {
+            name: name.to_string(),
+            addr,
+            size: symbol.size(),
+            section,
+        });
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_elf::{self, TestSection, TestSymbol};
+    use super::*;
+
+    #[test]
+    fn test_read_elf_sections_and_symbols() {
+        let data = test_elf::build(
+            &[
+                TestSection::code(".isr_vector", 0x0800_0000, 0x40),
+                TestSection::code(".text", 0x0800_0040, 0x200),
+                TestSection::data(".data", 0x2000_0000, 0x0800_0240, 0x10),
+                TestSection::bss(".bss", 0x2000_0010, 0x400),
+                TestSection::info(".comment", b"GCC 13.2".to_vec()),
+            ],
+            &[
+                TestSymbol::func("main", 0x0800_0041, 0x80, 1),
+                TestSymbol::func("Default_Handler", 0x0800_00C1, 4, 1),
+                TestSymbol::func("USART1_IRQHandler", 0x0800_00C1, 4, 1),
+                TestSymbol::object("counter", 0x2000_0000, 4, 2),
+                TestSymbol::object("rx_buffer", 0x2000_0010, 0x400, 3),
+            ],
+        );
+        assert!(is_elf(&data));
+        let image = read(&data).unwrap();
+
+        let names: Vec<&str> = image.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, [".isr_vector", ".text", ".data", ".bss"]);
+        let data_section = image.section(".data").unwrap();
+        assert_eq!(
+            (data_section.lma, data_section.kind),
+            (0x0800_0240, SectionKind::Data)
+        );
+        assert_eq!(image.section(".bss").unwrap().kind, SectionKind::Bss);
+        assert_eq!(image.totals(), (0x240, 0x10, 0x400));
+
+        let symbols: Vec<(&str, u64)> = image
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.addr))
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("main", 0x0800_0040),
+                ("Default_Handler", 0x0800_00C0),
+                ("counter", 0x2000_0000),
+                ("rx_buffer", 0x2000_0010)
+            ]
+        );
+        assert_eq!(image.symbols[3].section, ".bss");
+        assert!(read(b"not an elf").is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use super::fault_status::{
+    active_exception, describe_exc_return, is_exc_return, memory_region, FaultBit, FaultDump,
+};
+use super::symbolizer::{Resolved, Symbolizer};
+use crate::tools::{Tool, ToolCapability, ToolResult};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use wake_llm::ToolDescription;
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FaultDecoderArgs {
+    /// Fault dump as printed by the HardFault handler or a debugger: stacked R0-R3, R12, LR, PC, xPSR and CFSR, HFSR, MMFAR, BFAR (MMFSR/BFSR/UFSR, SP and EXC_RETURN are also read). Values are hexadecimal
+    pub dump: String,
+
+    /// Optional: Firmware ELF that was running, ideally built with -g, to resolve PC, LR and fault addresses to functions and file:line
+    pub elf: Option<String>,
+}
+
+/// Addresses below this are treated as NULL pointer dereferences
+const NULL_PAGE: u32 = 0x1000;
+
+pub struct FaultDecoder;
+
+impl FaultDecoder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// What a data address (BFAR/MMFAR) points at
+    fn describe_data_address(addr: u32, symbols: Option<&Symbolizer>) -> String {
+        if addr < NULL_PAGE {
+            return format!(
+                "0x{:08X}, just above address 0: a NULL pointer dereference (offset 0x{:x} into the pointed-to object)",
+                addr, addr
+            );
+        }
+        let region = memory_region(addr);
+        let resolved = symbols.map(|s| s.resolve(u64::from(addr)));
+        match resolved.as_ref() {
+            Some(r) if r.symbol.is_some() || r.section.is_some() => {
+                format!("0x{:08X} ({})", addr, r.describe())
+            }
+            _ if region == "peripheral" => format!(
+                "0x{:08X} in the peripheral region: the peripheral's clock is probably not enabled, or the part has no peripheral at this address",
+                addr
+            ),
+            Some(_) if region == "SRAM" => format!(
+                "0x{:08X} in SRAM, outside the ELF's data sections (heap, a stack, or past the end of RAM)",
+                addr
+            ),
+            Some(_) if region == "code (flash)" => format!(
+                "0x{:08X} in the code region, outside the firmware image (erased flash or past the end of flash)",
+                addr
+            ),
+            _ => format!("0x{:08X} in the {} region", addr, region),
+        }
+    }
+
+    /// `at PC 0x08000420 (read_sensor at src/sensor.c:31)`
+    fn at(addr: u32, resolved: Option<&Resolved>) -> String {
+        match resolved {
+            Some(r) => format!("at PC 0x{:08X} ({})", addr, r.describe()),
+            None => format!("at PC 0x{:08X}", addr),
+        }
+    }
+
+    fn causes(
+        dump: &FaultDump,
+        bits: &[FaultBit],
+        pc: Option<&Resolved>,
+        lr: Option<&Resolved>,
+        symbols: Option<&Symbolizer>,
+    ) -> Vec<String> {
+        let has = |bit| bits.contains(&bit);
+        let at_pc = dump.pc.map_or(String::new(), |addr| Self::at(addr, pc));
+        let caller = match (dump.lr, lr) {
+            (Some(value), _) if is_exc_return(value) => {
+                "LR holds EXC_RETURN, so the code was itself interrupted".to_string()
+            }
+            (Some(value), Some(r)) => format!("LR 0x{:08X} returns into {}", value, r.describe()),
+            (Some(value), None) => format!("LR is 0x{:08X}", value),
+            (None, _) => "LR is not in the dump".to_string(),
+        };
+        // `PC 0x08000420 (read_sensor at ...)` for use mid-sentence
+        let pc_text = at_pc.strip_prefix("at ").unwrap_or("the stacked PC");
+        let mut causes = Vec::new();
+
+        if has(FaultBit::Mstkerr) || has(FaultBit::Stkerr) || has(FaultBit::Stkof) {
+            let sp = dump.sp.map_or(String::new(), |sp| {
+                format!(" SP was 0x{:08X} ({}).", sp, memory_region(sp))
+            });
+            causes.push(format!(
+                "**Stack overflow.** The core could not push the exception frame, so the stack pointer had run past the end of its stack.{} The stacked R0-PC values are unreliable. Increase the stack (linker script `_Min_Stack_Size`, RTOS task stack size) or look for large local arrays and deep recursion.",
+                sp
+            ));
+        }
+        if has(FaultBit::Munstkerr) || has(FaultBit::Unstkerr) {
+            causes.push("**Stack corrupted before an exception return.** Unstacking the saved frame failed: something overwrote the stack or changed SP while the handler ran, e.g. a local buffer overrun or a bad PSP in an RTOS context switch.".to_string());
+        }
+        if has(FaultBit::Mlsperr) || has(FaultBit::Lsperr) {
+            causes.push("**FPU lazy stacking failed.** The space reserved for the FP registers on the stack was not accessible, usually a stack overflow in a task that uses the FPU.".to_string());
+        }
+        if has(FaultBit::Preciserr) {
+            let target = match dump.bfar {
+                Some(bfar) if has(FaultBit::Bfarvalid) => {
+                    Self::describe_data_address(bfar, symbols)
+                }
+                _ => "an address BFAR did not capture; check the pointers in R0-R3".to_string(),
+            };
+            causes.push(format!(
+                "**Bad data access {}.** The instruction accessed {}.",
+                at_pc, target
+            ));
+        }
+        if has(FaultBit::Impreciserr) {
+            causes.push(format!(
+                "**Buffered write failed.** A store to an invalid address completed in the write buffer after the core moved on, so the faulting store is a few instructions before {}. To make the fault precise while debugging, disable write buffering with `SCnSCB->ACTLR |= SCnSCB_ACTLR_DISDEFWBUF_Msk`.",
+                pc_text
+            ));
+        }
+        if has(FaultBit::Daccviol) {
+            let target = match dump.mmfar {
+                Some(mmfar) if has(FaultBit::Mmarvalid) => {
+                    Self::describe_data_address(mmfar, symbols)
+                }
+                _ => "an address MMFAR did not capture".to_string(),
+            };
+            causes.push(format!(
+                "**MPU violation {}.** The instruction accessed {}, which the MPU configuration does not allow.",
+                at_pc, target
+            ));
+        }
+        if has(FaultBit::Iaccviol) || has(FaultBit::Ibuserr) {
+            let pc_location = match (dump.pc, pc) {
+                (Some(addr), Some(r)) if r.is_code() => {
+                    format!("PC 0x{:08X} is inside {}", addr, r.describe())
+                }
+                (Some(addr), Some(_)) => format!(
+                    "PC 0x{:08X} is not in the firmware's code ({})",
+                    addr,
+                    memory_region(addr)
+                ),
+                (Some(addr), None) => format!("PC 0x{:08X} ({})", addr, memory_region(addr)),
+                (None, _) => "PC is not in the dump".to_string(),
+            };
+            causes.push(format!(
+                "**Jump to an invalid code address.** {}. The core tried to execute from memory that is not executable, usually through a corrupted function pointer or a return address overwritten on the stack; {}.",
+                pc_location, caller
+            ));
+        }
+        if has(FaultBit::Undefinstr) {
+            let detail = match pc {
+                Some(r) if r.is_code() => "PC is inside a function, so the code there was overwritten, the image is corrupt, or the instruction is not supported by this core (check -mcpu and -mfpu)",
+                Some(_) => "PC is not in the firmware's code, so execution jumped into data through a corrupted function pointer or return address",
+                None => "If PC is inside a function the code was overwritten or built for another core (-mcpu); otherwise execution jumped into data through a corrupted function pointer or return address",
+            };
+            causes.push(format!(
+                "**Undefined instruction {}.** {}; {}.",
+                at_pc, detail, caller
+            ));
+        }
+        if has(FaultBit::Invstate) {
+            let thumb = dump.xpsr.map_or(String::new(), |xpsr| {
+                format!(" xPSR.T is {}.", (xpsr >> 24) & 1)
+            });
+            causes.push(format!(
+                "**Branch without the Thumb bit.** Cortex-M only executes Thumb code, but {} was reached through a BX/BLX or function pointer with bit 0 clear: a function pointer built from an even address or from data, or a vector table entry missing `| 1`.{} {}.",
+                pc_text,
+                thumb,
+                caller
+            ));
+        }
+        if has(FaultBit::Invpc) {
+            causes.push("**Invalid exception return.** LR did not hold a valid EXC_RETURN when a handler returned: the handler's stack or LR was corrupted, or an RTOS context switch restored a bad frame.".to_string());
+        }
+        if has(FaultBit::Nocp) {
+            causes.push(format!(
+                "**FPU disabled.** Floating-point code {} ran before the FPU was enabled. Set CPACR bits 20-23 (`SCB->CPACR |= 0xF << 20`) in SystemInit, or build with -mfloat-abi=soft.",
+                at_pc
+            ));
+        }
+        if has(FaultBit::Unaligned) {
+            causes.push(format!(
+                "**Unaligned access {}.** Usually a byte buffer cast to a wider type or packed struct, or LDRD/STRD/LDM on an address that is not word aligned (CCR.UNALIGN_TRP traps all unaligned accesses).",
+                at_pc
+            ));
+        }
+        if has(FaultBit::Divbyzero) {
+            causes.push(format!(
+                "**Division by zero {}.** The divisor was 0 and CCR.DIV_0_TRP is set.",
+                at_pc
+            ));
+        }
+        if has(FaultBit::Vecttbl) {
+            causes.push("**Vector table read failed** on exception entry: VTOR points to an invalid address, or the table has no entry for the interrupt that fired.".to_string());
+        }
+        if has(FaultBit::Debugevt) {
+            causes.push("**Breakpoint without a debugger.** A BKPT instruction ran with no debugger attached: semihosting output (rdimon.specs, printf over semihosting) or an assert/`__BKPT()` left in the build.".to_string());
+        }
+
+        let configurable: Vec<&str> = bits
+            .iter()
+            .filter(|bit| bit.position().0 == "CFSR")
+            .map(|bit| bit.class())
+            .fold(Vec::new(), |mut classes, class| {
+                if !classes.contains(&class) {
+                    classes.push(class);
+                }
+                classes
+            });
+        if has(FaultBit::Forced) {
+            if configurable.is_empty() {
+                causes.push("**Escalated fault with CFSR clear.** HFSR.FORCED is set but no configurable fault bit is, so CFSR was cleared or read after the fact; read it first thing in the handler.".to_string());
+            } else {
+                causes.push(format!(
+                    "The {} escalated to HardFault because its handler is disabled in SHCSR, or it occurred at the same or higher priority (e.g. inside another fault handler).",
+                    configurable.join("/")
+                ));
+            }
+        }
+        if bits.is_empty() {
+            causes.push(format!(
+                "**No fault status bits are set.** Either CFSR/HFSR were not captured, or this is a Cortex-M0/M0+ (ARMv6-M has no CFSR; every fault is a HardFault). Look at the instruction {} and the pointer values in R0-R3; {}.",
+                if at_pc.is_empty() { "at PC" } else { &at_pc },
+                caller
+            ));
+        }
+        if let Some(exception) = dump.xpsr.and_then(active_exception) {
+            causes.push(format!(
+                "The fault occurred in the {} handler (IPSR = {}), not in thread code.",
+                exception,
+                dump.xpsr.unwrap_or(0) & 0x1FF
+            ));
+        }
+        causes
+    }
+
+    fn format_report(
+        dump: &FaultDump,
+        bits: &[FaultBit],
+        pc: Option<&Resolved>,
+        lr: Option<&Resolved>,
+        symbols: Option<&Symbolizer>,
+        causes: &[String],
+    ) -> String {
+        let mut out = String::from("# Cortex-M fault decode\n\n");
+        out.push_str("| Register | Value | Notes |\n|---|---|---|\n");
+        for (name, value) in dump.registers() {
+            let note = match name {
+                "PC" => pc.map(Resolved::describe).unwrap_or_default(),
+                "LR" if is_exc_return(value) => {
+                    format!("EXC_RETURN: {}", describe_exc_return(value))
+                }
+                "LR" => lr
+                    .map(|r| format!("returns into {}", r.describe()))
+                    .unwrap_or_default(),
+                "EXC_RETURN" => describe_exc_return(value),
+                "xPSR" => {
+                    let mut note = format!("T={}", (value >> 24) & 1);
+                    if let Some(exception) = active_exception(value) {
+                        note.push_str(&format!(", in {}", exception));
+                    }
+                    note
+                }
+                "SP" => memory_region(value).to_string(),
+                "BFAR" if !bits.contains(&FaultBit::Bfarvalid) => "not valid".to_string(),
+                "MMFAR" if !bits.contains(&FaultBit::Mmarvalid) => "not valid".to_string(),
+                "BFAR" | "MMFAR" => Self::describe_data_address(value, symbols),
+                _ => String::new(),
+            };
+            out.push_str(&format!("| {} | 0x{:08X} | {} |\n", name, value, note));
+        }
+        out.push('\n');
+
+        out.push_str("## Fault status\n\n");
+        if bits.is_empty() {
+            out.push_str("No CFSR or HFSR bits set.\n\n");
+        } else {
+            out.push_str("| Bit | Register | Fault | Meaning |\n|---|---|---|---|\n");
+            for bit in bits {
+                let (register, position) = bit.position();
+                out.push_str(&format!(
+                    "| {} | {}[{}] | {} | {} |\n",
+                    bit.name(),
+                    register,
+                    position,
+                    bit.class(),
+                    bit.meaning()
+                ));
+            }
+            out.push('\n');
+        }
+
+        let frames: Vec<(&str, u32, &Resolved)> = [("PC", dump.pc, pc), ("LR", dump.lr, lr)]
+            .into_iter()
+            .filter_map(|(name, value, resolved)| Some((name, value?, resolved?)))
+            .collect();
+        if !frames.is_empty() {
+            out.push_str("## Source\n\n");
+            for (name, value, resolved) in frames {
+                let role = if name == "PC" {
+                    "faulting instruction"
+                } else {
+                    "caller"
+                };
+                out.push_str(&format!(
+                    "- {} 0x{:08X} ({}): {}\n",
+                    name,
+                    value,
+                    role,
+                    resolved.describe()
+                ));
+            }
+            if symbols.is_some_and(|s| !s.has_debug_info()) {
+                out.push_str("- The ELF has no DWARF debug info; rebuild with -g for file:line.\n");
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Likely cause\n\n");
+        for cause in causes {
+            out.push_str(&format!("- {}\n", cause));
+        }
+        out
+    }
+}
+
+impl Default for FaultDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for FaultDecoder {
+    type Params = FaultDecoderArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Read]
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        let dump = FaultDump::parse(&args.dump);
+        if dump.registers().is_empty() {
+            return ToolResult::error(
+                "No registers found in the dump. Expected lines such as `PC = 0x08000420` or `CFSR: 00008200`".to_string(),
+            );
+        }
+        let data = match &args.elf {
+            Some(path) => match std::fs::read(path) {
+                Ok(data) => Some(data),
+                Err(e) => return ToolResult::error(format!("Cannot read {}: {}", path, e)),
+            },
+            None => None,
+        };
+        let symbols = match data.as_deref().map(Symbolizer::new).transpose() {
+            Ok(symbols) => symbols,
+            Err(e) => return ToolResult::error(e),
+        };
+
+        let bits = FaultBit::decode(dump.cfsr.unwrap_or(0), dump.hfsr.unwrap_or(0));
+        let pc = dump
+            .pc
+            .and_then(|pc| Some(symbols.as_ref()?.resolve(u64::from(pc & !1))));
+        // The return address follows the call; look up the call instruction
+        let lr = dump
+            .lr
+            .filter(|&lr| !is_exc_return(lr) && lr >= 2)
+            .and_then(|lr| Some(symbols.as_ref()?.resolve(u64::from((lr & !1) - 2))));
+        let causes = Self::causes(&dump, &bits, pc.as_ref(), lr.as_ref(), symbols.as_ref());
+        let mut out = Self::format_report(
+            &dump,
+            &bits,
+            pc.as_ref(),
+            lr.as_ref(),
+            symbols.as_ref(),
+            &causes,
+        );
+        if symbols.is_none() {
+            out.push_str("\nPass `elf` to resolve PC and LR to functions and source lines.\n");
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            "bits".to_string(),
+            json!(bits.iter().map(|b| b.name()).collect::<Vec<_>>()),
+        );
+        if let Some(pc) = &pc {
+            metadata.insert("pc_location".to_string(), json!(pc.describe()));
+            metadata.insert("pc_function".to_string(), json!(pc.function()));
+        }
+        if let Some(lr) = &lr {
+            metadata.insert("lr_location".to_string(), json!(lr.describe()));
+        }
+        ToolResult::success_with_metadata(out.trim_end().to_string(), metadata)
+    }
+}
+
+impl ToolDescription for FaultDecoder {
+    fn name(&self) -> &'static str {
+        "fault_decode"
+    }
+
+    fn description(&self) -> &'static str {
+        "Decode a Cortex-M HardFault dump (stacked R0-R3, R12, LR, PC, xPSR plus CFSR, HFSR, MMFAR, BFAR): names every set fault status bit, resolves PC and LR to function and file:line through the firmware ELF's DWARF info, and explains the likely cause using the actual addresses (NULL dereference, stack overflow, imprecise bus error, bad function pointer, disabled FPU, ...)."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(FaultDecoderArgs))
+            .unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_elf::{self, TestFunction, TestSection, TestSymbol};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_decode_fault_with_elf() {
+        let functions = [
+            TestFunction {
+                name: "main",
+                addr: 0x0800_0100,
+                size: 0x20,
+                lines: vec![(0, 20), (0x10, 24)],
+            },
+            TestFunction {
+                name: "read_sensor",
+                addr: 0x0800_0120,
+                size: 0x10,
+                lines: vec![(0, 40), (0x4, 42)],
+            },
+        ];
+        let mut sections = vec![
+            TestSection::code(".text", 0x0800_0100, 0x30),
+            TestSection::bss(".bss", 0x2000_0000, 0x100),
+        ];
+        sections.extend(test_elf::debug_sections("src/sensor.c", &functions));
+        let data = test_elf::build(
+            &sections,
+            &[
+                TestSymbol::func("main", 0x0800_0101, 0x20, 0),
+                TestSymbol::func("read_sensor", 0x0800_0121, 0x10, 0),
+            ],
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let elf = dir.path().join("app.elf");
+        std::fs::write(&elf, data).unwrap();
+
+        // A NULL struct pointer read in read_sensor, called from main
+        let dump = "HardFault!\n\
+                    R0 = 0x00000000 R1 = 0x20000010 R2 = 0x00000001 R3 = 0x00000000\n\
+                    R12 = 0x00000000 LR = 0x08000115 PC = 0x08000126 xPSR = 0x61000000\n\
+                    CFSR = 0x00008200 HFSR = 0x40000000 MMFAR = 0xE000ED34 BFAR = 0x00000008";
+        let result = FaultDecoder::new()
+            .execute(FaultDecoderArgs {
+                dump: dump.to_string(),
+                elf: Some(elf.display().to_string()),
+            })
+            .await;
+        let ToolResult::Success { output, metadata } = result else {
+            panic!("{:?}", result);
+        };
+        assert!(output.contains("| PRECISERR | CFSR[9] | BusFault |"));
+        assert!(output.contains("| FORCED | HFSR[30] | HardFault |"));
+        assert!(output.contains("| MMFAR | 0xE000ED34 | not valid |"));
+        assert!(output.contains(
+            "- PC 0x08000126 (faulting instruction): read_sensor at /work/src/sensor.c:42"
+        ));
+        assert!(output.contains("- LR 0x08000115 (caller): main at /work/src/sensor.c:24"));
+        assert!(output.contains(
+            "**Bad data access at PC 0x08000126 (read_sensor at /work/src/sensor.c:42).** The instruction accessed 0x00000008, just above address 0: a NULL pointer dereference"
+        ));
+        assert!(output.contains("The BusFault escalated to HardFault"));
+        let metadata = metadata.unwrap();
+        assert_eq!(
+            metadata["bits"],
+            json!(["PRECISERR", "BFARVALID", "FORCED"])
+        );
+        assert_eq!(metadata["pc_function"], json!("read_sensor"));
+
+        // Without an ELF: stack overflow on a Cortex-M4, decoded from the bits alone
+        let result = FaultDecoder::new()
+            .execute(FaultDecoderArgs {
+                dump: "CFSR: 00001000\nHFSR: 40000000\nPSP: 1FFFFFE0".to_string(),
+                elf: None,
+            })
+            .await;
+        let ToolResult::Success { output, .. } = result else {
+            panic!("{:?}", result);
+        };
+        assert!(output.contains("**Stack overflow.**"));
+        assert!(output.contains("SP was 0x1FFFFFE0"));
+        assert!(output.contains("Pass `elf`"));
+
+        let result = FaultDecoder::new()
+            .execute(FaultDecoderArgs {
+                dump: "it crashed".to_string(),
+                elf: None,
+            })
+            .await;
+        assert!(matches!(result, ToolResult::Error { .. }));
+    }
+}
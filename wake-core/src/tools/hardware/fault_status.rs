@@ -0,0 +1,368 @@
+//! Cortex-M fault registers: parsing pasted dumps and decoding CFSR/HFSR.
+//!
+//! Bit definitions follow the ARMv7-M Architecture Reference Manual (B3.2.15
+//! and B3.2.16); STKOF is the ARMv8-M stack limit check.
+
+use regex::Regex;
+
+/// Registers a HardFault handler typically prints. The first eight are the
+/// frame the core stacked on exception entry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultDump {
+    pub r0: Option<u32>,
+    pub r1: Option<u32>,
+    pub r2: Option<u32>,
+    pub r3: Option<u32>,
+    pub r12: Option<u32>,
+    pub lr: Option<u32>,
+    pub pc: Option<u32>,
+    pub xpsr: Option<u32>,
+    pub cfsr: Option<u32>,
+    pub hfsr: Option<u32>,
+    pub mmfar: Option<u32>,
+    pub bfar: Option<u32>,
+    /// Stack pointer the frame was pushed to (MSP or PSP)
+    pub sp: Option<u32>,
+    /// LR inside the fault handler
+    pub exc_return: Option<u32>,
+}
+
+impl FaultDump {
+    /// Reads `NAME = 0x...`, `NAME: ...` or gdb `info registers` lines.
+    /// Values are hexadecimal with or without `0x`; the first occurrence of
+    /// each register wins. MMFSR/BFSR/UFSR are combined when CFSR is missing.
+    pub fn parse(text: &str) -> Self {
+        let field = Regex::new(
+            r"(?i)\b(r0|r1|r2|r3|r12|r14|r15|lr|pc|xpsr|psr|cfsr|hfsr|mmfar|mmar|bfar|sp|msp|psp|exc_return|mmfsr|bfsr|ufsr)\b\s*(?:\[[^\]\n]*\])?\s*[:=]?\s*(?:0x)?([0-9a-f]{1,8})\b",
+        )
+        .unwrap();
+        let mut dump = Self::default();
+        let (mut mmfsr, mut bfsr, mut ufsr) = (None, None, None);
+        for c in field.captures_iter(text) {
+            let Ok(value) = u32::from_str_radix(&c[2], 16) else {
+                continue;
+            };
+            let slot = match c[1].to_lowercase().as_str() {
+                "r0" => &mut dump.r0,
+                "r1" => &mut dump.r1,
+                "r2" => &mut dump.r2,
+                "r3" => &mut dump.r3,
+                "r12" => &mut dump.r12,
+                "lr" | "r14" => &mut dump.lr,
+                "pc" | "r15" => &mut dump.pc,
+                "xpsr" | "psr" => &mut dump.xpsr,
+                "cfsr" => &mut dump.cfsr,
+                "hfsr" => &mut dump.hfsr,
+                "mmfar" | "mmar" => &mut dump.mmfar,
+                "bfar" => &mut dump.bfar,
+                "sp" | "msp" | "psp" => &mut dump.sp,
+                "exc_return" => &mut dump.exc_return,
+                "mmfsr" => &mut mmfsr,
+                "bfsr" => &mut bfsr,
+                _ => &mut ufsr,
+            };
+            slot.get_or_insert(value);
+        }
+        if dump.cfsr.is_none() && (mmfsr.is_some() || bfsr.is_some() || ufsr.is_some()) {
+            dump.cfsr = Some(
+                (mmfsr.unwrap_or(0) & 0xFF)
+                    | (bfsr.unwrap_or(0) & 0xFF) << 8
+                    | (ufsr.unwrap_or(0) & 0xFFFF) << 16,
+            );
+        }
+        dump
+    }
+
+    /// (name, value) of every register present, in dump order
+    pub fn registers(&self) -> Vec<(&'static str, u32)> {
+        [
+            ("R0", self.r0),
+            ("R1", self.r1),
+            ("R2", self.r2),
+            ("R3", self.r3),
+            ("R12", self.r12),
+            ("LR", self.lr),
+            ("PC", self.pc),
+            ("xPSR", self.xpsr),
+            ("SP", self.sp),
+            ("EXC_RETURN", self.exc_return),
+            ("CFSR", self.cfsr),
+            ("HFSR", self.hfsr),
+            ("MMFAR", self.mmfar),
+            ("BFAR", self.bfar),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultBit {
+    Iaccviol,
+    Daccviol,
+    Munstkerr,
+    Mstkerr,
+    Mlsperr,
+    Mmarvalid,
+    Ibuserr,
+    Preciserr,
+    Impreciserr,
+    Unstkerr,
+    Stkerr,
+    Lsperr,
+    Bfarvalid,
+    Undefinstr,
+    Invstate,
+    Invpc,
+    Nocp,
+    Stkof,
+    Unaligned,
+    Divbyzero,
+    Vecttbl,
+    Forced,
+    Debugevt,
+}
+
+impl FaultBit {
+    pub const ALL: [FaultBit; 23] = [
+        FaultBit::Iaccviol,
+        FaultBit::Daccviol,
+        FaultBit::Munstkerr,
+        FaultBit::Mstkerr,
+        FaultBit::Mlsperr,
+        FaultBit::Mmarvalid,
+        FaultBit::Ibuserr,
+        FaultBit::Preciserr,
+        FaultBit::Impreciserr,
+        FaultBit::Unstkerr,
+        FaultBit::Stkerr,
+        FaultBit::Lsperr,
+        FaultBit::Bfarvalid,
+        FaultBit::Undefinstr,
+        FaultBit::Invstate,
+        FaultBit::Invpc,
+        FaultBit::Nocp,
+        FaultBit::Stkof,
+        FaultBit::Unaligned,
+        FaultBit::Divbyzero,
+        FaultBit::Vecttbl,
+        FaultBit::Forced,
+        FaultBit::Debugevt,
+    ];
+
+    /// Register and bit position
+    pub fn position(self) -> (&'static str, u32) {
+        match self {
+            FaultBit::Iaccviol => ("CFSR", 0),
+            FaultBit::Daccviol => ("CFSR", 1),
+            FaultBit::Munstkerr => ("CFSR", 3),
+            FaultBit::Mstkerr => ("CFSR", 4),
+            FaultBit::Mlsperr => ("CFSR", 5),
+            FaultBit::Mmarvalid => ("CFSR", 7),
+            FaultBit::Ibuserr => ("CFSR", 8),
+            FaultBit::Preciserr => ("CFSR", 9),
+            FaultBit::Impreciserr => ("CFSR", 10),
+            FaultBit::Unstkerr => ("CFSR", 11),
+            FaultBit::Stkerr => ("CFSR", 12),
+            FaultBit::Lsperr => ("CFSR", 13),
+            FaultBit::Bfarvalid => ("CFSR", 15),
+            FaultBit::Undefinstr => ("CFSR", 16),
+            FaultBit::Invstate => ("CFSR", 17),
+            FaultBit::Invpc => ("CFSR", 18),
+            FaultBit::Nocp => ("CFSR", 19),
+            FaultBit::Stkof => ("CFSR", 20),
+            FaultBit::Unaligned => ("CFSR", 24),
+            FaultBit::Divbyzero => ("CFSR", 25),
+            FaultBit::Vecttbl => ("HFSR", 1),
+            FaultBit::Forced => ("HFSR", 30),
+            FaultBit::Debugevt => ("HFSR", 31),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FaultBit::Iaccviol => "IACCVIOL",
+            FaultBit::Daccviol => "DACCVIOL",
+            FaultBit::Munstkerr => "MUNSTKERR",
+            FaultBit::Mstkerr => "MSTKERR",
+            FaultBit::Mlsperr => "MLSPERR",
+            FaultBit::Mmarvalid => "MMARVALID",
+            FaultBit::Ibuserr => "IBUSERR",
+            FaultBit::Preciserr => "PRECISERR",
+            FaultBit::Impreciserr => "IMPRECISERR",
+            FaultBit::Unstkerr => "UNSTKERR",
+            FaultBit::Stkerr => "STKERR",
+            FaultBit::Lsperr => "LSPERR",
+            FaultBit::Bfarvalid => "BFARVALID",
+            FaultBit::Undefinstr => "UNDEFINSTR",
+            FaultBit::Invstate => "INVSTATE",
+            FaultBit::Invpc => "INVPC",
+            FaultBit::Nocp => "NOCP",
+            FaultBit::Stkof => "STKOF",
+            FaultBit::Unaligned => "UNALIGNED",
+            FaultBit::Divbyzero => "DIVBYZERO",
+            FaultBit::Vecttbl => "VECTTBL",
+            FaultBit::Forced => "FORCED",
+            FaultBit::Debugevt => "DEBUGEVT",
+        }
+    }
+
+    /// Fault class the bit belongs to
+    pub fn class(self) -> &'static str {
+        match self.position() {
+            ("HFSR", _) => "HardFault",
+            (_, 0..=7) => "MemManage",
+            (_, 8..=15) => "BusFault",
+            _ => "UsageFault",
+        }
+    }
+
+    pub fn meaning(self) -> &'static str {
+        match self {
+            FaultBit::Iaccviol => {
+                "Instruction fetch from a location the MPU or XN attribute forbids"
+            }
+            FaultBit::Daccviol => "Data access to a location the MPU forbids",
+            FaultBit::Munstkerr => "MPU fault while unstacking on exception return",
+            FaultBit::Mstkerr => "MPU fault while stacking on exception entry",
+            FaultBit::Mlsperr => "MPU fault during lazy floating-point state preservation",
+            FaultBit::Mmarvalid => "MMFAR holds the faulting address",
+            FaultBit::Ibuserr => "Bus error on instruction fetch",
+            FaultBit::Preciserr => "Precise data bus error: PC is the faulting instruction",
+            FaultBit::Impreciserr => {
+                "Imprecise data bus error: a buffered write failed after PC moved on"
+            }
+            FaultBit::Unstkerr => "Bus error while unstacking on exception return",
+            FaultBit::Stkerr => "Bus error while stacking on exception entry",
+            FaultBit::Lsperr => "Bus error during lazy floating-point state preservation",
+            FaultBit::Bfarvalid => "BFAR holds the faulting address",
+            FaultBit::Undefinstr => "Undefined instruction",
+            FaultBit::Invstate => "Invalid state: executed with the Thumb bit clear",
+            FaultBit::Invpc => "Invalid EXC_RETURN value on exception return",
+            FaultBit::Nocp => "Coprocessor (FPU) instruction while the coprocessor is disabled",
+            FaultBit::Stkof => "Stack pointer went below its stack limit register",
+            FaultBit::Unaligned => {
+                "Unaligned access with unaligned trapping or a multi-word access"
+            }
+            FaultBit::Divbyzero => "Integer division by zero with DIV_0_TRP set",
+            FaultBit::Vecttbl => "Bus error reading the vector table on exception entry",
+            FaultBit::Forced => "A configurable fault escalated to HardFault",
+            FaultBit::Debugevt => "Debug event (BKPT) with no debugger attached",
+        }
+    }
+
+    /// Bits set in the dump, in register order
+    pub fn decode(cfsr: u32, hfsr: u32) -> Vec<FaultBit> {
+        Self::ALL
+            .into_iter()
+            .filter(|bit| {
+                let (register, position) = bit.position();
+                let value = if register == "HFSR" { hfsr } else { cfsr };
+                value & (1 << position) != 0
+            })
+            .collect()
+    }
+}
+
+/// Standard Cortex-M memory map regions
+pub fn memory_region(addr: u32) -> &'static str {
+    match addr {
+        0x0000_0000..=0x1FFF_FFFF => "code (flash)",
+        0x2000_0000..=0x3FFF_FFFF => "SRAM",
+        0x4000_0000..=0x5FFF_FFFF => "peripheral",
+        0x6000_0000..=0x9FFF_FFFF => "external RAM",
+        0xA000_0000..=0xDFFF_FFFF => "external device",
+        0xE000_0000..=0xE00F_FFFF => "private peripheral bus (SCS, NVIC, SysTick)",
+        _ => "vendor system",
+    }
+}
+
+/// EXC_RETURN values start with 0xFF in the top byte
+pub fn is_exc_return(value: u32) -> bool {
+    value >> 24 == 0xFF
+}
+
+/// Mode and stack an EXC_RETURN value returns to
+pub fn describe_exc_return(value: u32) -> String {
+    let mode = if value & 0x8 != 0 {
+        "Thread mode"
+    } else {
+        "Handler mode"
+    };
+    let stack = if value & 0x4 != 0 { "PSP" } else { "MSP" };
+    let frame = if value & 0x10 == 0 {
+        ", extended frame with FP registers"
+    } else {
+        ""
+    };
+    format!("{}, {} stack{}", mode, stack, frame)
+}
+
+/// Exception that was active when the frame was stacked, from IPSR
+pub fn active_exception(xpsr: u32) -> Option<String> {
+    let number = xpsr & 0x1FF;
+    let name = match number {
+        0 => return None,
+        2 => "NMI".to_string(),
+        3 => "HardFault".to_string(),
+        4 => "MemManage".to_string(),
+        5 => "BusFault".to_string(),
+        6 => "UsageFault".to_string(),
+        11 => "SVCall".to_string(),
+        12 => "DebugMonitor".to_string(),
+        14 => "PendSV".to_string(),
+        15 => "SysTick".to_string(),
+        n if n >= 16 => format!("IRQ {}", n - 16),
+        n => format!("reserved exception {}", n),
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dump_and_decode_bits() {
+        let dump = FaultDump::parse(
+            "[HardFault]\n\
+             r0 = 0x00000000, r1 = 20000ABC, r2: 0x1, r3 = 0x2\n\
+             r12 = 0x0\n\
+             lr [R14] = 0x08000463  subroutine call return address\n\
+             pc [R15] = 0x08000420  program counter\n\
+             psr = 0x21000000\n\
+             BFAR = 0x00000004 MMFSR = 0x00 BFSR = 0x82 UFSR = 0x0000\n\
+             HFSR = 0x40000000\n\
+             pc             0x8000999           0x8000999 <main+4>\n",
+        );
+        assert_eq!(dump.r1, Some(0x2000_0ABC));
+        assert_eq!(dump.r12, Some(0));
+        assert_eq!(dump.lr, Some(0x0800_0463));
+        assert_eq!(dump.pc, Some(0x0800_0420));
+        assert_eq!(dump.xpsr, Some(0x2100_0000));
+        assert_eq!(dump.cfsr, Some(0x8200));
+        assert_eq!(dump.bfar, Some(4));
+        assert_eq!(dump.mmfar, None);
+
+        let bits = FaultBit::decode(0x8200, 0x4000_0000);
+        assert_eq!(
+            bits,
+            [FaultBit::Preciserr, FaultBit::Bfarvalid, FaultBit::Forced]
+        );
+        assert_eq!(FaultBit::Preciserr.class(), "BusFault");
+        assert_eq!(
+            FaultBit::decode(0x0200_0001, 0),
+            [FaultBit::Iaccviol, FaultBit::Divbyzero]
+        );
+
+        assert_eq!(memory_region(0x4001_3800), "peripheral");
+        assert!(is_exc_return(0xFFFF_FFFD));
+        assert_eq!(
+            describe_exc_return(0xFFFF_FFED),
+            "Thread mode, PSP stack, extended frame with FP registers"
+        );
+        assert_eq!(active_exception(0x2100_0025).as_deref(), Some("IRQ 21"));
+        assert_eq!(active_exception(0x2100_0000), None);
+    }
+}
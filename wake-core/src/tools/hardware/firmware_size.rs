@@ -0,0 +1,594 @@
+use super::elf_image;
+use super::linker_map::{self, short_object};
+use super::memory_usage::{format_bytes, parse_memory, parse_size, Image, Layout};
+use crate::tools::{Tool, ToolCapability, ToolResult};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use wake_llm::ToolDescription;
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FirmwareSizeArgs {
+    /// Optional: Linked firmware ELF (e.g. build/app.elf). A .map file next to it is picked up automatically
+    pub elf: Option<String>,
+
+    /// Optional: GNU ld map file (-Wl,-Map=app.map). Adds MEMORY regions and per-object sizes
+    pub map: Option<String>,
+
+    /// Optional: Linker script whose MEMORY block defines the regions, when no map file is available
+    pub linker_script: Option<String>,
+
+    /// Optional: Flash size (e.g. 64K, 0x10000) used when no MEMORY regions are known
+    pub flash_size: Option<String>,
+
+    /// Optional: RAM size (e.g. 20K) used when no MEMORY regions are known
+    pub ram_size: Option<String>,
+
+    /// Optional: Previous build (ELF or map file) to report growth against
+    pub previous: Option<String>,
+
+    /// Optional: Number of symbols and object files to list (default 10)
+    pub top: Option<usize>,
+}
+
+const DEFAULT_TOP: usize = 10;
+/// Usage at which a region is reported as nearly full
+const WARN_PERCENT: f64 = 90.0;
+
+pub struct FirmwareSize;
+
+impl FirmwareSize {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Reads an ELF and its sibling map, or a map file alone
+    fn load(elf: Option<&Path>, map: Option<&Path>) -> Result<(Image, Vec<PathBuf>), String> {
+        let read = |path: &Path| {
+            std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))
+        };
+        let map = map.map(Path::to_path_buf).or_else(|| {
+            let elf = elf?;
+            let mut appended = elf.as_os_str().to_owned();
+            appended.push(".map");
+            [elf.with_extension("map"), PathBuf::from(appended)]
+                .into_iter()
+                .find(|candidate| candidate.is_file())
+        });
+
+        let mut sources = Vec::new();
+        let mut image = match elf {
+            Some(path) => {
+                let image = elf_image::read(&read(path)?)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                sources.push(path.to_path_buf());
+                Some(image)
+            }
+            None => None,
+        };
+        if let Some(path) = map {
+            let data = read(&path)?;
+            let parsed = linker_map::parse(&String::from_utf8_lossy(&data));
+            if parsed.sections.is_empty() && parsed.regions.is_empty() {
+                return Err(format!("{} is not a GNU ld map file", path.display()));
+            }
+            sources.push(path);
+            image = Some(match image {
+                // Sections and symbols from the ELF, objects and regions from the map
+                Some(mut image) => {
+                    image.contributions = parsed.contributions;
+                    image.regions = parsed.regions;
+                    image
+                }
+                None => parsed,
+            });
+        }
+        image
+            .map(|image| (image, sources))
+            .ok_or_else(|| "Provide `elf`, `map` or both".to_string())
+    }
+
+    /// A previous build: an ELF (with its map, if any) or a map file
+    fn load_previous(path: &Path) -> Result<Image, String> {
+        let mut header = [0u8; 4];
+        let is_elf = std::fs::File::open(path)
+            .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+            .is_ok()
+            && elf_image::is_elf(&header);
+        if is_elf {
+            Self::load(Some(path), None).map(|(image, _)| image)
+        } else {
+            Self::load(None, Some(path)).map(|(image, _)| image)
+        }
+    }
+
+    fn layout(args: &FirmwareSizeArgs, image: &Image) -> Result<Layout, String> {
+        if !image.regions.is_empty() {
+            return Ok(Layout::new(image.regions.clone()));
+        }
+        if let Some(script) = &args.linker_script {
+            let text = std::fs::read_to_string(script)
+                .map_err(|e| format!("Cannot read {}: {}", script, e))?;
+            let regions = parse_memory(&text);
+            if regions.is_empty() {
+                return Err(format!("No MEMORY block found in {}", script));
+            }
+            return Ok(Layout::new(regions));
+        }
+        let size = |text: &Option<String>| text.as_deref().map(parse_size).transpose();
+        Ok(Layout::by_kind(
+            size(&args.flash_size)?,
+            size(&args.ram_size)?,
+        ))
+    }
+
+    fn region_names(layout: &Layout, regions: &[usize]) -> String {
+        if regions.is_empty() {
+            return "-".to_string();
+        }
+        regions
+            .iter()
+            .map(|&r| layout.regions[r].name.as_str())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+
+    /// Bytes per object file and region, largest first
+    fn objects(layout: &Layout, image: &Image) -> Vec<(String, Vec<u64>)> {
+        let mut objects: HashMap<String, Vec<u64>> = HashMap::new();
+        for part in &image.contributions {
+            let object = if part.object == "*fill*" {
+                "(alignment padding)".to_string()
+            } else {
+                short_object(&part.object)
+            };
+            let sizes = objects
+                .entry(object)
+                .or_insert_with(|| vec![0; layout.regions.len()]);
+            for region in layout.contribution_regions(image, part) {
+                sizes[region] += part.size;
+            }
+        }
+        let mut objects: Vec<_> = objects.into_iter().collect();
+        objects.sort_by(|a, b| {
+            let total = |sizes: &[u64]| sizes.iter().sum::<u64>();
+            total(&b.1).cmp(&total(&a.1)).then_with(|| a.0.cmp(&b.0))
+        });
+        objects
+    }
+
+    /// Size changes by name, largest change first, with a note for names
+    /// that appeared or disappeared
+    fn deltas(
+        before: HashMap<String, u64>,
+        after: HashMap<String, u64>,
+    ) -> Vec<(String, i64, &'static str)> {
+        let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+        names.sort();
+        names.dedup();
+        let mut deltas: Vec<(String, i64, &'static str)> = names
+            .into_iter()
+            .filter_map(|name| match (before.get(name), after.get(name)) {
+                (None, Some(&size)) => Some((name.clone(), size as i64, "new")),
+                (Some(&size), None) => Some((name.clone(), -(size as i64), "removed")),
+                (Some(&old), Some(&new)) if old != new => {
+                    Some((name.clone(), new as i64 - old as i64, ""))
+                }
+                _ => None,
+            })
+            .collect();
+        deltas.sort_by_key(|(_, delta, _)| std::cmp::Reverse(delta.abs()));
+        deltas
+    }
+
+    fn symbol_sizes(image: &Image) -> HashMap<String, u64> {
+        let mut sizes = HashMap::new();
+        for symbol in &image.symbols {
+            *sizes.entry(symbol.name.clone()).or_insert(0) += symbol.size;
+        }
+        sizes
+    }
+
+    fn object_sizes(image: &Image) -> HashMap<String, u64> {
+        let mut sizes = HashMap::new();
+        for part in image.contributions.iter().filter(|p| p.object != "*fill*") {
+            *sizes.entry(short_object(&part.object)).or_insert(0) += part.size;
+        }
+        sizes
+    }
+
+    fn signed_bytes(delta: i64) -> String {
+        let sign = if delta < 0 { "-" } else { "+" };
+        format!("{}{}", sign, delta.unsigned_abs())
+    }
+
+    fn format_growth(
+        layout: &Layout,
+        image: &Image,
+        previous: &Image,
+        path: &str,
+        top: usize,
+    ) -> String {
+        let mut out = format!("## Growth vs {}\n\n", path);
+        let (after, _) = layout.usage(image);
+        let (before, _) = layout.usage(previous);
+        out.push_str("| Region | Before | After | Change |\n|---|---|---|---|\n");
+        for (i, region) in layout.regions.iter().enumerate() {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} B |\n",
+                region.name,
+                before[i],
+                after[i],
+                Self::signed_bytes(after[i] as i64 - before[i] as i64)
+            ));
+        }
+        out.push('\n');
+
+        let sections = [
+            (
+                "Symbol",
+                Self::deltas(Self::symbol_sizes(previous), Self::symbol_sizes(image)),
+                !image.symbols.is_empty() && !previous.symbols.is_empty(),
+            ),
+            (
+                "Object",
+                Self::deltas(Self::object_sizes(previous), Self::object_sizes(image)),
+                !image.contributions.is_empty() && !previous.contributions.is_empty(),
+            ),
+        ];
+        for (label, deltas, available) in sections {
+            if !available || deltas.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("| {} | Change | |\n|---|---|---|\n", label));
+            for (name, delta, note) in deltas.iter().take(top) {
+                out.push_str(&format!(
+                    "| {} | {} B | {} |\n",
+                    name,
+                    Self::signed_bytes(*delta),
+                    note
+                ));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn format_report(
+        layout: &Layout,
+        image: &Image,
+        sources: &[PathBuf],
+        top: usize,
+    ) -> (String, Vec<serde_json::Value>) {
+        let sources: Vec<String> = sources.iter().map(|p| p.display().to_string()).collect();
+        let mut out = format!("# Firmware size: {}\n\n", sources.join(" + "));
+        let (used, outside) = layout.usage(image);
+
+        out.push_str("| Region | Origin | Size | Used | Free | Usage |\n");
+        out.push_str("|---|---|---|---|---|---|\n");
+        let mut warnings = Vec::new();
+        let mut regions = Vec::new();
+        for (region, &used) in layout.regions.iter().zip(&used) {
+            let origin = if layout.is_by_kind() {
+                "-".to_string()
+            } else {
+                format!("0x{:08X}", region.origin)
+            };
+            let percent = (region.length > 0).then(|| used as f64 * 100.0 / region.length as f64);
+            let (size, free, usage) = match percent {
+                Some(percent) => (
+                    format_bytes(region.length),
+                    if used > region.length {
+                        format!("-{}", format_bytes(used - region.length))
+                    } else {
+                        format_bytes(region.length - used)
+                    },
+                    format!("{:.1}%", percent),
+                ),
+                None => ("?".to_string(), "?".to_string(), "-".to_string()),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} ({} B) | {} | {} |\n",
+                region.name,
+                origin,
+                size,
+                format_bytes(used),
+                used,
+                free,
+                usage
+            ));
+            match percent {
+                Some(_) if used > region.length => warnings.push(format!(
+                    "**{} overflows by {} B.** The image does not fit.",
+                    region.name,
+                    used - region.length
+                )),
+                Some(percent) if percent >= WARN_PERCENT => warnings.push(format!(
+                    "**{} is {:.1}% full**: {} B left.",
+                    region.name,
+                    percent,
+                    region.length - used
+                )),
+                _ => {}
+            }
+            regions.push(json!({
+                "name": region.name,
+                "origin": region.origin,
+                "length": region.length,
+                "used": used,
+                "percent": percent,
+            }));
+        }
+        out.push('\n');
+        for warning in &warnings {
+            out.push_str(&format!("{}\n", warning));
+        }
+        if !warnings.is_empty() {
+            out.push('\n');
+        }
+        if layout.is_by_kind() {
+            if layout.regions.iter().any(|r| r.length == 0) {
+                out.push_str(
+                    "No MEMORY regions known; pass `linker_script`, or `flash_size` and `ram_size`, for percentages.\n\n",
+                );
+            }
+        } else if outside > 0 {
+            out.push_str(&format!(
+                "{} B of allocated sections lie outside every MEMORY region.\n\n",
+                outside
+            ));
+        }
+
+        let (text, data, bss) = image.totals();
+        out.push_str(&format!(
+            "text {} B, data {} B, bss {} B (flash = text + data, RAM = data + bss)\n\n",
+            text, data, bss
+        ));
+
+        out.push_str("## Sections\n\n| Section | Address | Load address | Size | Region |\n");
+        out.push_str("|---|---|---|---|---|\n");
+        for section in &image.sections {
+            let regions = layout.place(section.kind, section.addr, section.lma);
+            out.push_str(&format!(
+                "| {} | 0x{:08X} | {} | {} | {} |\n",
+                section.name,
+                section.addr,
+                if section.lma == section.addr {
+                    "-".to_string()
+                } else {
+                    format!("0x{:08X}", section.lma)
+                },
+                section.size,
+                Self::region_names(layout, &regions)
+            ));
+        }
+        out.push('\n');
+
+        if !image.symbols.is_empty() {
+            let mut symbols: Vec<_> = image.symbols.iter().collect();
+            symbols.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+            out.push_str(
+                "## Largest symbols\n\n| Symbol | Size | Region | Section |\n|---|---|---|---|\n",
+            );
+            for symbol in symbols.into_iter().take(top) {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    symbol.name,
+                    symbol.size,
+                    Self::region_names(layout, &layout.symbol_regions(image, symbol)),
+                    symbol.section
+                ));
+            }
+            out.push('\n');
+        } else if !image.contributions.is_empty() {
+            let mut parts: Vec<_> = image
+                .contributions
+                .iter()
+                .filter(|p| p.object != "*fill*")
+                .collect();
+            parts.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.input.cmp(&b.input)));
+            out.push_str(
+                "## Largest input sections\n\n| Input section | Object | Size | Region |\n|---|---|---|---|\n",
+            );
+            for part in parts.into_iter().take(top) {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    part.input,
+                    short_object(&part.object),
+                    part.size,
+                    Self::region_names(layout, &layout.contribution_regions(image, part))
+                ));
+            }
+            out.push('\n');
+        }
+
+        if image.contributions.is_empty() {
+            out.push_str("Per-object sizes need the linker map (`-Wl,-Map=app.map`).\n\n");
+        } else {
+            let names: Vec<&str> = layout.regions.iter().map(|r| r.name.as_str()).collect();
+            out.push_str(&format!(
+                "## Largest object files\n\n| Object | {} |\n|---|{}\n",
+                names.join(" | "),
+                "---|".repeat(names.len())
+            ));
+            for (object, sizes) in Self::objects(layout, image).into_iter().take(top) {
+                let sizes: Vec<String> = sizes.iter().map(u64::to_string).collect();
+                out.push_str(&format!("| {} | {} |\n", object, sizes.join(" | ")));
+            }
+            out.push('\n');
+        }
+        (out, regions)
+    }
+}
+
+impl Default for FirmwareSize {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for FirmwareSize {
+    type Params = FirmwareSizeArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Read]
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        let (image, sources) = match Self::load(
+            args.elf.as_deref().map(Path::new),
+            args.map.as_deref().map(Path::new),
+        ) {
+            Ok(loaded) => loaded,
+            Err(e) => return ToolResult::error(e),
+        };
+        let layout = match Self::layout(&args, &image) {
+            Ok(layout) => layout,
+            Err(e) => return ToolResult::error(e),
+        };
+        let top = args.top.unwrap_or(DEFAULT_TOP).max(1);
+
+        let (mut out, regions) = Self::format_report(&layout, &image, &sources, top);
+        if let Some(previous) = &args.previous {
+            match Self::load_previous(Path::new(previous)) {
+                Ok(before) => out.push_str(&Self::format_growth(
+                    &layout, &image, &before, previous, top,
+                )),
+                Err(e) => out.push_str(&format!("Cannot compare with {}: {}\n", previous, e)),
+            }
+        }
+
+        let (text, data, bss) = image.totals();
+        let mut metadata = HashMap::new();
+        metadata.insert("regions".to_string(), json!(regions));
+        metadata.insert(
+            "totals".to_string(),
+            json!({ "text": text, "data": data, "bss": bss }),
+        );
+        ToolResult::success_with_metadata(out.trim_end().to_string(), metadata)
+    }
+}
+
+impl ToolDescription for FirmwareSize {
+    fn name(&self) -> &'static str {
+        "firmware_size"
+    }
+
+    fn description(&self) -> &'static str {
+        "Report firmware memory usage from a linked ELF and/or GNU ld map file: flash and RAM used per linker MEMORY region with free space, sections, the largest symbols and object files, and growth against a previous build. Use this to find what fills flash or RAM before it overflows."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(FirmwareSizeArgs))
+            .unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_elf::{self, TestSection, TestSymbol};
+    use super::*;
+
+    const MAP: &str = "\
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x0000000008000000 0x0000000000000800 xr
+RAM              0x0000000020000000 0x0000000000005000 xrw
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+.text           0x0000000008000000      0x780
+ .text.main     0x0000000008000000       0x80 build/main.o
+ .text.HAL_RCC_OscConfig
+                0x0000000008000080      0x700 build/stm32f1xx_hal_rcc.o
+
+.data           0x0000000020000000        0x8 load address 0x0000000008000780
+ .data          0x0000000020000000        0x8 build/main.o
+
+.bss            0x0000000020000008      0x400
+ .bss           0x0000000020000008      0x400 build/main.o
+";
+
+    fn elf(text: usize, hal: bool) -> Vec<u8> {
+        let mut symbols = vec![
+            TestSymbol::func("main", 0x0800_0001, 0x80, 0),
+            TestSymbol::object("counter", 0x2000_0000, 8, 1),
+            TestSymbol::object("rx_buffer", 0x2000_0008, 0x400, 2),
+        ];
+        if hal {
+            symbols.push(TestSymbol::func("HAL_RCC_OscConfig", 0x0800_0081, 0x700, 0));
+        }
+        test_elf::build(
+            &[
+                TestSection::code(".text", 0x0800_0000, text),
+                TestSection::data(".data", 0x2000_0000, 0x0800_0000 + text as u64, 8),
+                TestSection::bss(".bss", 0x2000_0008, 0x400),
+            ],
+            &symbols,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_firmware_size_report_and_growth() {
+        let dir = tempfile::tempdir().unwrap();
+        let elf_path = dir.path().join("app.elf");
+        std::fs::write(&elf_path, elf(0x780, true)).unwrap();
+        std::fs::write(dir.path().join("app.map"), MAP).unwrap();
+        let previous = dir.path().join("previous.elf");
+        std::fs::write(&previous, elf(0x80, false)).unwrap();
+
+        let result = FirmwareSize::new()
+            .execute(FirmwareSizeArgs {
+                elf: Some(elf_path.display().to_string()),
+                map: None,
+                linker_script: None,
+                flash_size: None,
+                ram_size: None,
+                previous: Some(previous.display().to_string()),
+                top: Some(5),
+            })
+            .await;
+        let ToolResult::Success { output, metadata } = result else {
+            panic!("{:?}", result);
+        };
+        // 0x780 text + 8 data in a 2 KiB flash
+        assert!(
+            output.contains("| FLASH | 0x08000000 | 2.0 KiB | 1.9 KiB (1928 B) | 120 B | 94.1% |")
+        );
+        assert!(output.contains("**FLASH is 94.1% full**: 120 B left."));
+        assert!(output.contains("| RAM | 0x20000000 | 20.0 KiB | 1.0 KiB (1032 B) |"));
+        assert!(output.contains("text 1920 B, data 8 B, bss 1024 B"));
+        assert!(output.contains("| HAL_RCC_OscConfig | 1792 | FLASH | .text |"));
+        assert!(output.contains("| counter | 8 | RAM+FLASH | .data |"));
+        assert!(output.contains("| main.o | 136 | 1032 |"));
+        assert!(output.contains("| stm32f1xx_hal_rcc.o | 1792 | 0 |"));
+        assert!(output.contains("| FLASH | 136 | 1928 | +1792 B |"));
+        assert!(output.contains("| HAL_RCC_OscConfig | +1792 B | new |"));
+        let regions = metadata.unwrap()["regions"].clone();
+        assert_eq!(regions[0]["used"], 1928);
+
+        // A map file alone lists input sections instead of symbols
+        let map_path = dir.path().join("app.map");
+        let result = FirmwareSize::new()
+            .execute(FirmwareSizeArgs {
+                elf: None,
+                map: Some(map_path.display().to_string()),
+                linker_script: None,
+                flash_size: None,
+                ram_size: None,
+                previous: None,
+                top: None,
+            })
+            .await;
+        let ToolResult::Success { output, .. } = result else {
+            panic!("{:?}", result);
+        };
+        assert!(output.contains("| .text.HAL_RCC_OscConfig | stm32f1xx_hal_rcc.o | 1792 | FLASH |"));
+    }
+}
@@ -0,0 +1,927 @@
+//! Programmer backends for the flash tool.
+//!
+//! Builds command lines for probe-rs, OpenOCD, esptool and avrdude, and turns
+//! their console output into a report: detected chip, bytes written, verify
+//! result and, when something went wrong, the most likely cause.
+
+use regex::Regex;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    ProbeRs,
+    OpenOcd,
+    Esptool,
+    Avrdude,
+}
+
+impl Backend {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().replace(['_', ' '], "-").as_str() {
+            "probe-rs" | "probers" | "cargo-flash" => Some(Backend::ProbeRs),
+            "openocd" => Some(Backend::OpenOcd),
+            "esptool" | "esptool.py" => Some(Backend::Esptool),
+            "avrdude" => Some(Backend::Avrdude),
+            _ => None,
+        }
+    }
+
+    /// ESP parts use esptool, AVR parts avrdude, everything else probe-rs
+    pub fn detect(chip: Option<&str>) -> Self {
+        let chip = chip.unwrap_or_default().to_lowercase();
+        if chip.starts_with("esp") {
+            Backend::Esptool
+        } else if chip.starts_with("atmega")
+            || chip.starts_with("attiny")
+            || chip.starts_with("avr")
+            || (chip.len() > 1 && chip.starts_with('m') && chip[1..].starts_with(char::is_numeric))
+            || (chip.len() > 1 && chip.starts_with('t') && chip[1..].starts_with(char::is_numeric))
+        {
+            Backend::Avrdude
+        } else {
+            Backend::ProbeRs
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Backend::ProbeRs => "probe-rs",
+            Backend::OpenOcd => "OpenOCD",
+            Backend::Esptool => "esptool",
+            Backend::Avrdude => "avrdude",
+        }
+    }
+
+    /// Executable names to look for, in order
+    pub fn executables(&self) -> &'static [&'static str] {
+        match self {
+            Backend::ProbeRs => &["probe-rs"],
+            Backend::OpenOcd => &["openocd"],
+            Backend::Esptool => &["esptool", "esptool.py"],
+            Backend::Avrdude => &["avrdude"],
+        }
+    }
+
+    pub fn install_hint(&self) -> &'static str {
+        match self {
+            Backend::ProbeRs => "Install it with `cargo install probe-rs-tools` (see probe.rs)",
+            Backend::OpenOcd => "Install it from your package manager (`apt install openocd`, `brew install open-ocd`)",
+            Backend::Esptool => "Install it with `pip install esptool`, or run from an ESP-IDF shell",
+            Backend::Avrdude => "Install it from your package manager (`apt install avrdude`, `brew install avrdude`)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Write the image, verify and reset
+    Flash,
+    Verify,
+    Erase,
+    Reset,
+    /// Connect and report the probe and chip
+    Info,
+}
+
+impl Action {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "flash" | "program" | "write" | "download" => Some(Action::Flash),
+            "verify" => Some(Action::Verify),
+            "erase" | "erase-all" | "mass-erase" => Some(Action::Erase),
+            "reset" => Some(Action::Reset),
+            "info" | "probe" | "detect" => Some(Action::Info),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Flash => "flash",
+            Action::Verify => "verify",
+            Action::Erase => "erase",
+            Action::Reset => "reset",
+            Action::Info => "info",
+        }
+    }
+
+    /// What running the action does to the target, for the confirmation
+    pub fn effect(&self) -> &'static str {
+        match self {
+            Action::Flash => "Overwrites the firmware on the attached target and resets it.",
+            Action::Verify => "Reads the target's flash and compares it with the image.",
+            Action::Erase => "Erases the flash of the attached target; the firmware is lost.",
+            Action::Reset => "Resets the attached target.",
+            Action::Info => "Connects to the target and reads its identification only.",
+        }
+    }
+
+    pub fn needs_image(&self) -> bool {
+        matches!(self, Action::Flash | Action::Verify)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Elf,
+    Hex,
+    Bin,
+}
+
+impl ImageFormat {
+    /// From the file extension; build outputs without one are ELF
+    pub fn of(path: &str) -> Self {
+        let ext = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "hex" | "ihex" | "ihx" => ImageFormat::Hex,
+            "bin" | "img" => ImageFormat::Bin,
+            _ => ImageFormat::Elf,
+        }
+    }
+}
+
+/// Settings a command line is built from
+#[derive(Clone, Debug, Default)]
+pub struct Target {
+    pub chip: Option<String>,
+    pub file: Option<String>,
+    /// Load address of a raw binary
+    pub address: Option<u64>,
+    pub port: Option<String>,
+    /// OpenOCD interface, avrdude programmer id or probe-rs probe selector
+    pub programmer: Option<String>,
+    /// OpenOCD target config
+    pub target_config: Option<String>,
+    pub baud_rate: Option<u32>,
+    pub speed_khz: Option<u32>,
+    pub verify: bool,
+    pub extra_args: Vec<String>,
+}
+
+impl Target {
+    fn chip(&self, backend: Backend) -> Result<&str, String> {
+        self.chip.as_deref().ok_or_else(|| match backend {
+            Backend::ProbeRs => {
+                "`chip` is required for probe-rs (e.g. STM32F411CEUx, nRF52840_xxAA, RP2040); \
+                 `probe-rs chip list` shows the names"
+                    .to_string()
+            }
+            Backend::Avrdude => {
+                "`chip` is required for avrdude (e.g. m328p, atmega2560)".to_string()
+            }
+            _ => "`chip` is required".to_string(),
+        })
+    }
+
+    fn file(&self) -> Result<&str, String> {
+        self.file
+            .as_deref()
+            .ok_or_else(|| "`file` is required to flash or verify".to_string())
+    }
+
+    fn address(&self, what: &str) -> Result<u64, String> {
+        self.address
+            .ok_or_else(|| format!("`address` is required for raw .bin images ({})", what))
+    }
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// OpenOCD `-f` argument: a bare name is looked up in the scripts folder
+fn openocd_config(kind: &str, name: &str) -> String {
+    if name.ends_with(".cfg") || name.contains('/') {
+        name.to_string()
+    } else {
+        format!("{}/{}.cfg", kind, name)
+    }
+}
+
+/// OpenOCD target config name derived from a part number
+pub fn openocd_target(chip: &str) -> Option<String> {
+    let chip = chip.to_lowercase();
+    if let Some(rest) = chip.strip_prefix("stm32") {
+        let family: String = rest.chars().take(2).collect();
+        return (family.len() == 2).then(|| format!("stm32{}x", family));
+    }
+    [
+        "nrf51", "nrf52", "nrf53", "nrf91", "rp2040", "rp2350", "esp32",
+    ]
+    .into_iter()
+    .find(|prefix| chip.starts_with(prefix))
+    .map(str::to_string)
+}
+
+/// Argument lists (without the executable), one per program run
+pub fn commands(
+    backend: Backend,
+    action: Action,
+    target: &Target,
+) -> Result<Vec<Vec<String>>, String> {
+    let mut commands = match backend {
+        Backend::ProbeRs => probe_rs_commands(action, target)?,
+        Backend::OpenOcd => openocd_commands(action, target)?,
+        Backend::Esptool => esptool_commands(action, target)?,
+        Backend::Avrdude => avrdude_commands(action, target)?,
+    };
+    if let Some(first) = commands.first_mut() {
+        // OpenOCD exits inside its `-c` script, so options must come before it
+        let at = match backend {
+            Backend::OpenOcd => first.len() - 2,
+            _ => first.len(),
+        };
+        first.splice(at..at, target.extra_args.iter().cloned());
+    }
+    Ok(commands)
+}
+
+fn probe_rs_commands(action: Action, target: &Target) -> Result<Vec<Vec<String>>, String> {
+    let mut connect = Vec::new();
+    if action != Action::Info {
+        connect.extend(strings(&["--chip", target.chip(Backend::ProbeRs)?]));
+    }
+    if let Some(probe) = &target.programmer {
+        connect.extend(strings(&["--probe", probe]));
+    }
+    if let Some(speed) = target.speed_khz {
+        connect.extend(["--speed".to_string(), speed.to_string()]);
+    }
+    let image = |command: &str| -> Result<Vec<String>, String> {
+        let file = target.file()?;
+        let mut args = vec![command.to_string()];
+        args.extend(connect.iter().cloned());
+        match ImageFormat::of(file) {
+            ImageFormat::Elf => {}
+            ImageFormat::Hex => args.extend(strings(&["--binary-format", "hex"])),
+            ImageFormat::Bin => {
+                let address = target.address("e.g. 0x08000000 for STM32 flash")?;
+                args.extend(strings(&["--binary-format", "bin", "--base-address"]));
+                args.push(format!("0x{:X}", address));
+            }
+        }
+        if command == "download" && target.verify {
+            args.push("--verify".to_string());
+        }
+        args.push(file.to_string());
+        Ok(args)
+    };
+    let with = |command: &str| {
+        let mut args = vec![command.to_string()];
+        args.extend(connect.iter().cloned());
+        args
+    };
+    Ok(match action {
+        Action::Flash => vec![image("download")?, with("reset")],
+        Action::Verify => vec![image("verify")?],
+        Action::Erase => vec![with("erase")],
+        Action::Reset => vec![with("reset")],
+        Action::Info => vec![with("info")],
+    })
+}
+
+fn openocd_commands(action: Action, target: &Target) -> Result<Vec<Vec<String>>, String> {
+    let interface = target.programmer.as_deref().unwrap_or("stlink");
+    let target_config = match (&target.target_config, &target.chip) {
+        (Some(config), _) => config.clone(),
+        (None, Some(chip)) => openocd_target(chip).ok_or_else(|| {
+            format!(
+                "No OpenOCD target config is known for '{}'; set `target_config` (e.g. stm32f4x, nrf52, rp2040)",
+                chip
+            )
+        })?,
+        (None, None) => {
+            return Err("`target_config` or `chip` is required for OpenOCD".to_string());
+        }
+    };
+    let mut args = vec![
+        "-f".to_string(),
+        openocd_config("interface", interface),
+        "-f".to_string(),
+        openocd_config("target", &target_config),
+    ];
+    if let Some(speed) = target.speed_khz {
+        args.extend(["-c".to_string(), format!("adapter speed {}", speed)]);
+    }
+    let image = || -> Result<(String, String), String> {
+        let file = target.file()?;
+        let address = match ImageFormat::of(file) {
+            ImageFormat::Bin => format!(" 0x{:X}", target.address("e.g. 0x08000000")?),
+            _ => String::new(),
+        };
+        Ok((format!("{{{}}}", file), address))
+    };
+    let script = match action {
+        Action::Flash => {
+            let (file, address) = image()?;
+            format!(
+                "program {}{} reset exit{}",
+                file,
+                if target.verify { " verify" } else { "" },
+                address
+            )
+        }
+        Action::Verify => {
+            let (file, address) = image()?;
+            format!(
+                "init; reset halt; verify_image {}{}; reset run; shutdown",
+                file, address
+            )
+        }
+        Action::Erase => {
+            "init; reset halt; flash erase_sector 0 0 last; reset run; shutdown".to_string()
+        }
+        Action::Reset => "init; reset run; shutdown".to_string(),
+        Action::Info => "init; flash probe 0; flash banks; shutdown".to_string(),
+    };
+    args.extend(["-c".to_string(), script]);
+    Ok(vec![args])
+}
+
+fn esptool_commands(action: Action, target: &Target) -> Result<Vec<Vec<String>>, String> {
+    let mut args = vec![
+        "--chip".to_string(),
+        target.chip.clone().unwrap_or_else(|| "auto".to_string()),
+    ];
+    if let Some(port) = &target.port {
+        args.extend(["--port".to_string(), port.clone()]);
+    }
+    if let Some(baud) = target.baud_rate {
+        args.extend(["--baud".to_string(), baud.to_string()]);
+    }
+    let image = || -> Result<(String, String), String> {
+        let file = target.file()?;
+        if ImageFormat::of(file) != ImageFormat::Bin {
+            return Err(format!(
+                "esptool writes raw .bin images; flash the .bin from the build directory \
+                 (or convert with `esptool elf2image`) instead of '{}'",
+                file
+            ));
+        }
+        let address = target.address("0x0 for a merged image, 0x10000 for an ESP-IDF app")?;
+        Ok((format!("0x{:X}", address), file.to_string()))
+    };
+    match action {
+        Action::Flash => {
+            let (address, file) = image()?;
+            args.extend(["write_flash".to_string(), address, file]);
+        }
+        Action::Verify => {
+            let (address, file) = image()?;
+            args.extend(["verify_flash".to_string(), address, file]);
+        }
+        Action::Erase => args.push("erase_flash".to_string()),
+        Action::Reset => args.push("run".to_string()),
+        Action::Info => args.push("flash_id".to_string()),
+    }
+    Ok(vec![args])
+}
+
+fn avrdude_commands(action: Action, target: &Target) -> Result<Vec<Vec<String>>, String> {
+    let mut args = strings(&[
+        "-p",
+        target.chip(Backend::Avrdude)?,
+        "-c",
+        target.programmer.as_deref().unwrap_or("arduino"),
+    ]);
+    if let Some(port) = &target.port {
+        args.extend(["-P".to_string(), port.clone()]);
+    }
+    if let Some(baud) = target.baud_rate {
+        args.extend(["-b".to_string(), baud.to_string()]);
+    }
+    let memory = |op: char| -> Result<String, String> {
+        let file = target.file()?;
+        let format = match ImageFormat::of(file) {
+            ImageFormat::Elf => 'e',
+            ImageFormat::Hex => 'i',
+            ImageFormat::Bin => 'r',
+        };
+        Ok(format!("flash:{}:{}:{}", op, file, format))
+    };
+    match action {
+        Action::Flash => {
+            args.extend(["-U".to_string(), memory('w')?]);
+            if !target.verify {
+                args.push("-V".to_string());
+            }
+        }
+        Action::Verify => args.extend(["-U".to_string(), memory('v')?]),
+        Action::Erase => args.push("-e".to_string()),
+        // Connecting resets the board and reads the signature
+        Action::Reset | Action::Info => {}
+    }
+    Ok(vec![args])
+}
+
+/// Backend output without ANSI colours, with progress bars redrawn via `\r`
+/// reduced to their final state
+pub fn clean_output(text: &str) -> Vec<String> {
+    let ansi = Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    let text = ansi.replace_all(text, "");
+    text.lines()
+        .filter_map(|line| {
+            line.split('\r')
+                .rev()
+                .map(str::trim_end)
+                .find(|part| !part.trim().is_empty())
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// What the backend reported
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub chip: Option<String>,
+    pub erased: bool,
+    pub bytes_written: Option<u64>,
+    pub address: Option<u64>,
+    pub seconds: Option<f64>,
+    pub verified: Option<bool>,
+    pub reset: bool,
+}
+
+fn capture<'t>(pattern: &str, text: &'t str) -> Option<regex::Captures<'t>> {
+    Regex::new(pattern).unwrap().captures(text)
+}
+
+fn size_in_bytes(value: &str, unit: &str) -> u64 {
+    let value: f64 = value.parse().unwrap_or(0.0);
+    let scale = match unit {
+        "KiB" | "KB" => 1024.0,
+        "MiB" | "MB" => 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+    (value * scale).round() as u64
+}
+
+pub fn parse(backend: Backend, lines: &[String]) -> Report {
+    let text = lines.join("\n");
+    let mut report = Report::default();
+    match backend {
+        Backend::ProbeRs => {
+            let size = Regex::new(r"([\d.]+)\s*(B|KiB|MiB)\b").unwrap();
+            for line in lines {
+                if line.contains("Erasing") && line.contains('✔') {
+                    report.erased = true;
+                }
+                if line.contains("Programming") && line.contains('✔') {
+                    let sizes = line.split('@').next().unwrap_or_default();
+                    if let Some(last) = size.captures_iter(sizes).last() {
+                        report.bytes_written = Some(size_in_bytes(&last[1], &last[2]));
+                    }
+                }
+                if line.contains("Verifying") && line.contains('✔') {
+                    report.verified = Some(true);
+                }
+            }
+            if let Some(c) = capture(r"Finished in ([\d.]+)\s*s", &text) {
+                report.seconds = c[1].parse().ok();
+            }
+            if let Some(c) = capture(r"(?i)\b(IDCODE|DPIDR)\b:?\s*(0x[0-9a-f]+)", &text) {
+                report.chip = Some(format!("{} {}", &c[1], &c[2]));
+            }
+            if Regex::new(r"(?i)verification (successful|ok)")
+                .unwrap()
+                .is_match(&text)
+            {
+                report.verified = Some(true);
+            }
+        }
+        Backend::OpenOcd => {
+            if let Some(c) = capture(r"wrote (\d+) bytes from file .*? in ([\d.]+)s", &text) {
+                report.bytes_written = c[1].parse().ok();
+                report.seconds = c[2].parse().ok();
+            }
+            if text.contains("** Verified OK **") || capture(r"verified \d+ bytes", &text).is_some()
+            {
+                report.verified = Some(true);
+            }
+            report.erased = text.contains("erased sectors");
+            report.reset = text.contains("** Resetting Target **");
+            let id = capture(r"(?i)device id = (0x[0-9a-f]+)", &text)
+                .map(|c| format!("device id {}", &c[1]))
+                .or_else(|| {
+                    capture(r"(?i)(SWD|JTAG) DPIDR (0x[0-9a-f]+)", &text)
+                        .map(|c| format!("{} DPIDR {}", &c[1], &c[2]))
+                })
+                .or_else(|| {
+                    capture(r"(?i)tap/device found: (0x[0-9a-f]+)", &text)
+                        .map(|c| format!("IDCODE {}", &c[1]))
+                });
+            let flash = capture(r"(?i)flash size = (\d+ ?[KM]i?B)", &text)
+                .map(|c| format!("{} flash", &c[1]));
+            report.chip = match (id, flash) {
+                (Some(id), Some(flash)) => Some(format!("{}, {}", id, flash)),
+                (id, flash) => id.or(flash),
+            };
+        }
+        Backend::Esptool => {
+            let chip =
+                capture(r"(?m)^Chip (?:is|type:)\s+(.+?)\s*$", &text).map(|c| c[1].to_string());
+            let mac = capture(r"(?m)^MAC:\s+([0-9a-fA-F:]{17})", &text).map(|c| c[1].to_string());
+            let flash = capture(r"Detected flash size:\s+(\S+)", &text).map(|c| c[1].to_string());
+            if let Some(mut chip) = chip {
+                if let Some(mac) = mac {
+                    chip.push_str(&format!(", MAC {}", mac));
+                }
+                if let Some(flash) = flash {
+                    chip.push_str(&format!(", {} flash", flash));
+                }
+                report.chip = Some(chip);
+            }
+            let wrote = Regex::new(
+                r"Wrote (\d+) bytes(?: \(\d+ compressed\))? at (0x[0-9a-fA-F]+) in ([\d.]+) seconds",
+            )
+            .unwrap();
+            for c in wrote.captures_iter(&text) {
+                let bytes: u64 = c[1].parse().unwrap_or(0);
+                report.bytes_written = Some(report.bytes_written.unwrap_or(0) + bytes);
+                report.seconds =
+                    Some(report.seconds.unwrap_or(0.0) + c[3].parse::<f64>().unwrap_or(0.0));
+                if report.address.is_none() {
+                    report.address = u64::from_str_radix(&c[2][2..], 16).ok();
+                }
+            }
+            if text.contains("Hash of data verified") || text.contains("verify OK") {
+                report.verified = Some(true);
+            }
+            report.erased = text.contains("Chip erase completed successfully");
+            report.reset = text.contains("Hard resetting");
+        }
+        Backend::Avrdude => {
+            if let Some(c) = capture(
+                r"(?i)Device signature = (0x[0-9a-f]+)(?: \(probably (\S+)\))?",
+                &text,
+            ) {
+                report.chip = Some(match c.get(2) {
+                    Some(part) => format!("signature {} ({})", &c[1], part.as_str()),
+                    None => format!("signature {}", &c[1]),
+                });
+            }
+            if let Some(c) = capture(r"(\d+) bytes of flash written", &text) {
+                report.bytes_written = c[1].parse().ok();
+            }
+            if let Some(c) = capture(r"Writing \|.*\| 100% ([\d.]+)\s*s", &text) {
+                report.seconds = c[1].parse().ok();
+            }
+            if capture(r"\d+ bytes of flash verified", &text).is_some() {
+                report.verified = Some(true);
+            }
+            report.erased = text.contains("erasing chip");
+        }
+    }
+    let verify_failed = Regex::new(
+        r"(?i)(verif(y|ication) (failed|error|mismatch)|md5 of file does not match|contents differ)",
+    )
+    .unwrap();
+    if verify_failed.is_match(&text) {
+        report.verified = Some(false);
+    }
+    report
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    Permission,
+    PortBusy,
+    WrongChip,
+    UnknownChip,
+    Locked,
+    TooLarge,
+    VerifyFailed,
+    NoProbe,
+    NotResponding,
+    NoTarget,
+    FileError,
+}
+
+impl FailureKind {
+    /// Checked in order; the first matching line wins
+    const ALL: [FailureKind; 11] = [
+        FailureKind::Permission,
+        FailureKind::PortBusy,
+        FailureKind::WrongChip,
+        FailureKind::UnknownChip,
+        FailureKind::Locked,
+        FailureKind::TooLarge,
+        FailureKind::VerifyFailed,
+        FailureKind::NoProbe,
+        FailureKind::NotResponding,
+        FailureKind::NoTarget,
+        FailureKind::FileError,
+    ];
+
+    fn pattern(&self) -> &'static str {
+        match self {
+            FailureKind::Permission => {
+                r"(?i)(permission denied|libusb_error_access|insufficient permissions|access denied)"
+            }
+            FailureKind::PortBusy => {
+                r"(?i)(resource busy|could not exclusively lock port|port is busy|already in use)"
+            }
+            FailureKind::WrongChip => {
+                r"(?i)(expected signature for|this chip is .*not|wrong --chip|unexpected idcode|does not match the (chip|target))"
+            }
+            FailureKind::UnknownChip => {
+                r"(?i)(chip .*(could not be found|not found)|failed to find chip|avr part .* not found|unknown chip|invalid chip)"
+            }
+            FailureKind::Locked => {
+                r"(?i)(read[- ]?out protect|flash is (read[- ]?protected|locked|write[- ]?protected)|device is (locked|secured)|target is locked|\brdp\b|approtect|security bit)"
+            }
+            FailureKind::TooLarge => {
+                r"(?i)(does not fit|exceeds? (the )?(flash|available|size)|no flash bank found for address|not enough space|address .*out of range|too (big|large) for)"
+            }
+            FailureKind::VerifyFailed => {
+                r"(?i)(verif(y|ication) (failed|error|mismatch)|md5 of file does not match|contents differ)"
+            }
+            FailureKind::NoProbe => {
+                r"(?i)(no (connected )?probes? (were |was )?found|no debug probe|unable to find a matching cmsis-dap|error: open failed|no j-link device found|could not open port|can't open device|ser_open\(\)|no such device)"
+            }
+            FailureKind::NotResponding => {
+                r"(?i)(failed to connect to (an )?esp|no serial data received|wrong boot mode|not in sync|programmer is not responding|timed out waiting for packet)"
+            }
+            FailureKind::NoTarget => {
+                r"(?i)(unable to connect to the target|connecting to the chip was unsuccessful|failed to attach|target not examined|target voltage may be too low|error connecting dp|could not connect to target|arm specific error|jtag scan chain interrogation failed)"
+            }
+            FailureKind::FileError => {
+                r"(?i)(no such file|couldn't open .*\.(elf|hex|bin)|error opening|invalid (elf|hex) file|failed to parse)"
+            }
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FailureKind::Permission => "No permission to access the probe or port",
+            FailureKind::PortBusy => "The port is in use by another program",
+            FailureKind::WrongChip => "The connected chip is not the one requested",
+            FailureKind::UnknownChip => "The backend does not know this chip name",
+            FailureKind::Locked => "The flash is read or write protected",
+            FailureKind::TooLarge => "The image does not fit in flash at this address",
+            FailureKind::VerifyFailed => "Flash contents do not match the image",
+            FailureKind::NoProbe => "No debug probe, programmer or port found",
+            FailureKind::NotResponding => "The bootloader is not responding",
+            FailureKind::NoTarget => "The probe cannot talk to the target",
+            FailureKind::FileError => "The firmware file could not be read",
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            FailureKind::Permission => {
+                "Install the probe's udev rules or add the user to the `dialout`/`plugdev` group, then replug the device."
+            }
+            FailureKind::PortBusy => {
+                "Close serial monitors using the port (including an open `serial` tool session) and retry."
+            }
+            FailureKind::WrongChip => {
+                "Check `chip` against the board; the ID the backend detected is in the output."
+            }
+            FailureKind::UnknownChip => {
+                "Use a name the backend knows: `probe-rs chip list`, `avrdude -p ?`, or esptool's `--chip` choices."
+            }
+            FailureKind::Locked => {
+                "Remove read-out protection first (e.g. `probe-rs erase --allow-erase-all`, RDP level 0 with STM32CubeProgrammer, `nrfjprog --recover`). This erases the chip."
+            }
+            FailureKind::TooLarge => {
+                "Check the load address and the linker script memory regions against the chip's flash size."
+            }
+            FailureKind::VerifyFailed => {
+                "Erase and flash again; check write protection, option bytes and supply stability during programming."
+            }
+            FailureKind::NoProbe => {
+                "Check the USB cable and that the probe or port shows up (`probe-rs list`, `lsusb`, `serial` tool `list`)."
+            }
+            FailureKind::NotResponding => {
+                "Put the chip in its bootloader: hold BOOT/GPIO0 while pressing RESET on ESP32 boards; check `programmer`, `port` and `baud_rate` for Arduino bootloaders."
+            }
+            FailureKind::NoTarget => {
+                "Check target power, SWD/JTAG wiring and common ground; lower `speed_khz` or connect under reset if the firmware disables the debug pins."
+            }
+            FailureKind::FileError => "Check the `file` path and that it is a valid ELF, Intel HEX or binary image.",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnosis {
+    pub kind: FailureKind,
+    /// Output line that gave it away
+    pub evidence: String,
+}
+
+pub fn diagnose(lines: &[String]) -> Option<Diagnosis> {
+    FailureKind::ALL.iter().find_map(|kind| {
+        let re = Regex::new(kind.pattern()).unwrap();
+        lines
+            .iter()
+            .find(|line| re.is_match(line))
+            .map(|line| Diagnosis {
+                kind: *kind,
+                evidence: line.trim().to_string(),
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        clean_output(text)
+    }
+
+    #[test]
+    fn test_commands() {
+        let target = Target {
+            chip: Some("STM32F411CEUx".to_string()),
+            file: Some("build/app.elf".to_string()),
+            verify: true,
+            speed_khz: Some(4000),
+            ..Default::default()
+        };
+        let probe_rs = commands(Backend::ProbeRs, Action::Flash, &target).unwrap();
+        assert_eq!(
+            probe_rs[0].join(" "),
+            "download --chip STM32F411CEUx --speed 4000 --verify build/app.elf"
+        );
+        assert_eq!(
+            probe_rs[1].join(" "),
+            "reset --chip STM32F411CEUx --speed 4000"
+        );
+
+        let openocd = commands(Backend::OpenOcd, Action::Flash, &target).unwrap();
+        assert_eq!(
+            openocd[0],
+            [
+                "-f",
+                "interface/stlink.cfg",
+                "-f",
+                "target/stm32f4x.cfg",
+                "-c",
+                "adapter speed 4000",
+                "-c",
+                "program {build/app.elf} verify reset exit"
+            ]
+        );
+        let extra = Target {
+            programmer: Some("cmsis-dap".to_string()),
+            extra_args: vec!["-d2".to_string()],
+            speed_khz: None,
+            ..target.clone()
+        };
+        assert_eq!(
+            commands(Backend::OpenOcd, Action::Reset, &extra).unwrap()[0].join(" "),
+            "-f interface/cmsis-dap.cfg -f target/stm32f4x.cfg -d2 -c init; reset run; shutdown"
+        );
+
+        let bin = Target {
+            chip: Some("esp32s3".to_string()),
+            file: Some("app.bin".to_string()),
+            port: Some("/dev/ttyUSB0".to_string()),
+            address: Some(0x10000),
+            baud_rate: Some(921_600),
+            ..Default::default()
+        };
+        assert_eq!(
+            commands(Backend::Esptool, Action::Flash, &bin).unwrap()[0].join(" "),
+            "--chip esp32s3 --port /dev/ttyUSB0 --baud 921600 write_flash 0x10000 app.bin"
+        );
+        let err = commands(Backend::Esptool, Action::Flash, &target).unwrap_err();
+        assert!(err.contains("raw .bin images"));
+        let err = commands(
+            Backend::ProbeRs,
+            Action::Flash,
+            &Target {
+                address: None,
+                ..bin.clone()
+            },
+        )
+        .unwrap_err();
+        assert!(err.contains("`address` is required"));
+
+        let avr = Target {
+            chip: Some("m328p".to_string()),
+            file: Some("blink.hex".to_string()),
+            port: Some("/dev/ttyACM0".to_string()),
+            extra_args: vec!["-D".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            commands(Backend::Avrdude, Action::Flash, &avr).unwrap()[0].join(" "),
+            "-p m328p -c arduino -P /dev/ttyACM0 -U flash:w:blink.hex:i -V -D"
+        );
+
+        assert_eq!(Backend::detect(Some("ESP32-C3")), Backend::Esptool);
+        assert_eq!(Backend::detect(Some("atmega2560")), Backend::Avrdude);
+        assert_eq!(Backend::detect(Some("m328p")), Backend::Avrdude);
+        assert_eq!(Backend::detect(Some("nRF52840_xxAA")), Backend::ProbeRs);
+        assert_eq!(openocd_target("STM32G431KB").as_deref(), Some("stm32g4x"));
+    }
+
+    #[test]
+    fn test_parse_reports() {
+        let probe_rs = lines(
+            "      Erasing ⠁ [00:00:00] [#####-----] 8.00 KiB/16.00 KiB\r      Erasing ✔ [00:00:00] [##########] 16.00 KiB/16.00 KiB @ 52.30 KiB/s (eta 0s )\n  \
+             Programming ✔ [00:00:01] [##########] 12.50 KiB/12.50 KiB @ 10.00 KiB/s (eta 0s )\n    \
+             Finished in 1.42s\n",
+        );
+        assert_eq!(probe_rs.len(), 3);
+        let report = parse(Backend::ProbeRs, &probe_rs);
+        assert!(report.erased);
+        assert_eq!(report.bytes_written, Some(12_800));
+        assert_eq!(report.seconds, Some(1.42));
+
+        let openocd = lines(
+            "Info : STLINK V2J37S7 (API v2) VID:PID 0483:3748\n\
+             Info : [stm32f4x.cpu] Cortex-M4 r0p1 processor detected\n\
+             Info : device id = 0x10006431\n\
+             Info : flash size = 512 KiB\n\
+             ** Programming Started **\n\
+             ** Programming Finished **\n\
+             ** Verify Started **\n\
+             ** Verified OK **\n\
+             ** Resetting Target **\n\
+             wrote 16384 bytes from file build/app.elf in 0.742s (21.563 KiB/s)\n",
+        );
+        let report = parse(Backend::OpenOcd, &openocd);
+        assert_eq!(
+            report.chip.as_deref(),
+            Some("device id 0x10006431, 512 KiB flash")
+        );
+        assert_eq!(report.bytes_written, Some(16384));
+        assert_eq!(report.verified, Some(true));
+        assert!(report.reset);
+
+        let esptool = lines(
+            "Chip is ESP32-D0WD-V3 (revision v3.1)\nMAC: 24:0a:c4:12:34:56\n\
+             Writing at 0x00010000... (10 %)\rWriting at 0x0001c000... (100 %)\n\
+             Wrote 183456 bytes (98213 compressed) at 0x00010000 in 2.6 seconds (effective 564.3 kbit/s)...\n\
+             Hash of data verified.\n\nLeaving...\nHard resetting via RTS pin...\n",
+        );
+        let report = parse(Backend::Esptool, &esptool);
+        assert_eq!(
+            report.chip.as_deref(),
+            Some("ESP32-D0WD-V3 (revision v3.1), MAC 24:0a:c4:12:34:56")
+        );
+        assert_eq!(
+            (report.bytes_written, report.address),
+            (Some(183_456), Some(0x10000))
+        );
+        assert_eq!(report.verified, Some(true));
+        assert!(report.reset);
+
+        let avrdude = lines(
+            "avrdude: Device signature = 0x1e950f (probably m328p)\n\
+             avrdude: writing flash (924 bytes):\n\
+             Writing | ################################################## | 100% 0.16s\n\
+             avrdude: 924 bytes of flash written\n\
+             avrdude: verification error, first mismatch at byte 0x0000\n         0x0c != 0xff\n",
+        );
+        let report = parse(Backend::Avrdude, &avrdude);
+        assert_eq!(report.chip.as_deref(), Some("signature 0x1e950f (m328p)"));
+        assert_eq!(report.bytes_written, Some(924));
+        assert_eq!(report.verified, Some(false));
+        assert_eq!(diagnose(&avrdude).unwrap().kind, FailureKind::VerifyFailed);
+    }
+
+    #[test]
+    fn test_diagnose_failures() {
+        let cases = [
+            ("Error: No connected probes were found.", FailureKind::NoProbe),
+            (
+                "A fatal error occurred: could not open port '/dev/ttyUSB0': [Errno 13] Permission denied: '/dev/ttyUSB0'",
+                FailureKind::Permission,
+            ),
+            (
+                "A fatal error occurred: Failed to connect to ESP32: No serial data received.",
+                FailureKind::NotResponding,
+            ),
+            ("avrdude: stk500_getsync() attempt 10 of 10: not in sync: resp=0x00", FailureKind::NotResponding),
+            (
+                "Error: init mode failed (unable to connect to the target)",
+                FailureKind::NoTarget,
+            ),
+            (
+                "avrdude: Expected signature for ATmega328P is 1E 95 0F",
+                FailureKind::WrongChip,
+            ),
+            (
+                "A fatal error occurred: This chip is ESP32-S3 not ESP32. Wrong --chip argument?",
+                FailureKind::WrongChip,
+            ),
+            ("Error: no flash bank found for address 0x08100000", FailureKind::TooLarge),
+            ("Error: The target is locked (APPROTECT)", FailureKind::Locked),
+        ];
+        for (text, kind) in cases {
+            let diagnosis = diagnose(&lines(text)).unwrap();
+            assert_eq!(diagnosis.kind, kind, "{}", text);
+            assert_eq!(diagnosis.evidence, text);
+        }
+        assert!(diagnose(&lines("avrdude done.  Thank you.")).is_none());
+    }
+}
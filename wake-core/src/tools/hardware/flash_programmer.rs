@@ -0,0 +1,676 @@
+use super::flash_backend::{self, Action, Backend, Diagnosis, Report, Target};
+use super::svd::parse_int;
+use crate::tools::{Tool, ToolCapability, ToolResult};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+use tracing::debug;
+use wake_llm::ToolDescription;
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FlashProgrammerArgs {
+    /// Operation: flash (write the image, verify and reset), verify, erase, reset or info (connect and identify the chip)
+    pub action: String,
+
+    /// Optional: Programmer backend (probe-rs, openocd, esptool, avrdude). Guessed from `chip` when omitted: ESP parts use esptool, AVR parts avrdude, everything else probe-rs
+    pub backend: Option<String>,
+
+    /// Optional: Firmware image for flash and verify (.elf, .hex or .bin)
+    pub file: Option<String>,
+
+    /// Optional: Target chip. probe-rs chip name (STM32F411CEUx), esptool chip (esp32s3), avrdude part (m328p), or a part number the OpenOCD target config is derived from
+    pub chip: Option<String>,
+
+    /// Optional: Load address for raw .bin images (e.g. 0x08000000, or 0x10000 for an ESP-IDF app)
+    pub address: Option<String>,
+
+    /// Optional: Serial port for esptool and avrdude (e.g. /dev/ttyUSB0, COM3)
+    pub port: Option<String>,
+
+    /// Optional: Probe or programmer. OpenOCD interface config (stlink, cmsis-dap, jlink; default stlink), avrdude programmer id (arduino, usbasp, avrisp2; default arduino) or probe-rs probe selector (VID:PID[:serial])
+    pub programmer: Option<String>,
+
+    /// Optional: OpenOCD target config (e.g. stm32f4x, nrf52, rp2040) when it cannot be derived from `chip`
+    pub target_config: Option<String>,
+
+    /// Optional: Serial baud rate for esptool and avrdude
+    pub baud_rate: Option<u32>,
+
+    /// Optional: SWD/JTAG clock in kHz for probe-rs and OpenOCD
+    pub speed_khz: Option<u32>,
+
+    /// Optional: Verify after writing (default true)
+    pub verify: Option<bool>,
+
+    /// Optional: Extra arguments passed to the backend unchanged
+    pub extra_args: Option<Vec<String>>,
+
+    /// Optional: Path to the backend executable when it is not on PATH
+    pub executable: Option<String>,
+
+    /// Optional: Timeout in seconds (default 300)
+    pub timeout_secs: Option<u64>,
+}
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+/// Output lines shown after the report
+const OUTPUT_TAIL_LINES: usize = 40;
+
+/// What will run for a request
+struct Plan {
+    backend: Backend,
+    action: Action,
+    program: PathBuf,
+    commands: Vec<Vec<String>>,
+    /// Image path and size, for flash and verify
+    image: Option<(String, u64)>,
+    address: Option<u64>,
+    timeout: Duration,
+}
+
+impl Plan {
+    fn command_lines(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .map(|args| {
+                std::iter::once(self.program.display().to_string())
+                    .chain(args.iter().cloned())
+                    .map(|arg| shell_quote(&arg))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+}
+
+/// Quotes an argument for display as a copy-pasteable shell command
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Forwards `pipe` to `tx` as it arrives, in pieces ending at `\n` or at
+/// the `\r` of a redrawn progress bar, tagged with `stream`
+fn forward(
+    stream: usize,
+    mut pipe: impl AsyncRead + Unpin + Send + 'static,
+    tx: mpsc::UnboundedSender<(usize, String)>,
+) {
+    tokio::spawn(async move {
+        let mut pending = Vec::new();
+        let mut buf = [0u8; 4096];
+        while let Ok(n @ 1..) = pipe.read(&mut buf).await {
+            pending.extend_from_slice(&buf[..n]);
+            while let Some(end) = pending.iter().position(|b| *b == b'\n' || *b == b'\r') {
+                let piece: Vec<u8> = pending.drain(..=end).collect();
+                if tx
+                    .send((stream, String::from_utf8_lossy(&piece).into_owned()))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+        if !pending.is_empty() {
+            let _ = tx.send((stream, String::from_utf8_lossy(&pending).into_owned()));
+        }
+    });
+}
+
+/// Output of one backend run
+struct Run {
+    lines: Vec<String>,
+    exit_code: Option<i32>,
+    timed_out: bool,
+}
+
+pub struct FlashProgrammer {
+    /// Directories searched for backend executables instead of `PATH`
+    search_path: Option<OsString>,
+}
+
+impl FlashProgrammer {
+    pub fn new() -> Self {
+        Self { search_path: None }
+    }
+
+    /// Looks up backend executables in `path` (a `PATH`-style list) instead
+    /// of the process `PATH`
+    pub fn with_search_path(path: impl Into<OsString>) -> Self {
+        Self {
+            search_path: Some(path.into()),
+        }
+    }
+
+    fn find_executable(&self, backend: Backend) -> Option<PathBuf> {
+        let path = self
+            .search_path
+            .clone()
+            .or_else(|| std::env::var_os("PATH"))?;
+        std::env::split_paths(&path).find_map(|dir| {
+            backend.executables().iter().find_map(|name| {
+                [name.to_string(), format!("{}.exe", name)]
+                    .into_iter()
+                    .map(|file| dir.join(file))
+                    .find(|candidate| candidate.is_file())
+            })
+        })
+    }
+
+    fn plan(&self, args: &FlashProgrammerArgs) -> Result<Plan, String> {
+        let action = Action::parse(&args.action).ok_or_else(|| {
+            format!(
+                "Unknown action '{}'; use flash, verify, erase, reset or info",
+                args.action
+            )
+        })?;
+        let backend = match &args.backend {
+            Some(name) => Backend::parse(name).ok_or_else(|| {
+                format!(
+                    "Unknown backend '{}'; use probe-rs, openocd, esptool or avrdude",
+                    name
+                )
+            })?,
+            None => Backend::detect(args.chip.as_deref()),
+        };
+        let address = args
+            .address
+            .as_deref()
+            .map(|text| parse_int(text).map_err(|_| format!("Invalid address '{}'", text)))
+            .transpose()?;
+
+        let image = match (&args.file, action.needs_image()) {
+            (Some(file), true) => {
+                let size = std::fs::metadata(file)
+                    .map_err(|e| format!("Cannot read firmware file {}: {}", file, e))?
+                    .len();
+                Some((file.clone(), size))
+            }
+            _ => None,
+        };
+
+        let target = Target {
+            chip: args.chip.clone(),
+            file: args.file.clone(),
+            address,
+            port: args.port.clone(),
+            programmer: args.programmer.clone(),
+            target_config: args.target_config.clone(),
+            baud_rate: args.baud_rate,
+            speed_khz: args.speed_khz,
+            verify: args.verify.unwrap_or(true),
+            extra_args: args.extra_args.clone().unwrap_or_default(),
+        };
+        let commands = flash_backend::commands(backend, action, &target)?;
+
+        let program = match &args.executable {
+            Some(path) if Path::new(path).is_file() => PathBuf::from(path),
+            Some(path) => return Err(format!("Backend executable {} does not exist", path)),
+            None => self.find_executable(backend).ok_or_else(|| {
+                format!(
+                    "{} was not found on PATH (looked for {}). {}, or set `executable`.",
+                    backend.label(),
+                    backend.executables().join(", "),
+                    backend.install_hint()
+                )
+            })?,
+        };
+
+        Ok(Plan {
+            backend,
+            action,
+            program,
+            commands,
+            image,
+            address,
+            timeout: Duration::from_secs(args.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)),
+        })
+    }
+
+    /// Runs one backend command, logging its output as it arrives so progress
+    /// is visible; on timeout the backend is stopped and what it printed kept
+    async fn run(program: &Path, args: &[String], timeout: Duration) -> Result<Run, String> {
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", program.display(), e))?;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward(0, stdout, tx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward(1, stderr, tx);
+        }
+
+        // Progress and errors go to stderr for most backends
+        let mut text = [String::new(), String::new()];
+        let finished = tokio::time::timeout(timeout, async {
+            while let Some((stream, piece)) = rx.recv().await {
+                if !piece.trim().is_empty() {
+                    debug!(target: "tools::flash", "{}: {}", program.display(), piece.trim());
+                }
+                text[stream].push_str(&piece);
+            }
+            child.wait().await
+        })
+        .await;
+        let lines = flash_backend::clean_output(&text.join("\n"));
+        match finished {
+            Ok(Ok(status)) => Ok(Run {
+                lines,
+                exit_code: status.code(),
+                timed_out: false,
+            }),
+            Ok(Err(e)) => Err(format!("Failed to run {}: {}", program.display(), e)),
+            Err(_) => {
+                let _ = child.kill().await;
+                Ok(Run {
+                    lines,
+                    exit_code: None,
+                    timed_out: true,
+                })
+            }
+        }
+    }
+
+    fn preview(plan: &Plan) -> String {
+        let mut out = format!(
+            "Confirm before running on the attached hardware. Nothing has been executed yet.\n{}\n\n{} {} with {}",
+            plan.action.effect(),
+            plan.backend.label(),
+            plan.action.label(),
+            plan.program.display()
+        );
+        if let Some((file, size)) = &plan.image {
+            out.push_str(&format!("\nImage: {} ({} bytes", file, size));
+            if let Some(address) = plan.address {
+                out.push_str(&format!(" at 0x{:08X}", address));
+            }
+            out.push(')');
+        }
+        out.push_str("\n\n```\n");
+        for line in plan.command_lines() {
+            out.push_str(&format!("$ {}\n", line));
+        }
+        out.push_str("```\n");
+        out
+    }
+
+    fn format_report(
+        plan: &Plan,
+        report: &Report,
+        diagnosis: Option<&Diagnosis>,
+        succeeded: bool,
+    ) -> String {
+        let mut out = format!(
+            "## {} {} {}\n\n",
+            plan.backend.label(),
+            plan.action.label(),
+            if succeeded { "succeeded" } else { "failed" }
+        );
+        let mut rows = Vec::new();
+        if let Some(chip) = &report.chip {
+            rows.push(("Chip", chip.clone()));
+        }
+        if report.erased {
+            rows.push(("Erase", "done".to_string()));
+        }
+        if let Some(bytes) = report.bytes_written {
+            let mut write = format!("{} bytes", bytes);
+            if let Some(address) = report.address.or(plan.address) {
+                write.push_str(&format!(" at 0x{:08X}", address));
+            }
+            if let Some(seconds) = report.seconds {
+                write.push_str(&format!(" in {:.2} s", seconds));
+            }
+            rows.push(("Write", write));
+        } else if plan.action == Action::Flash && succeeded {
+            if let Some((_, size)) = &plan.image {
+                rows.push(("Write", format!("done ({} byte image)", size)));
+            }
+        }
+        match report.verified {
+            Some(true) => rows.push(("Verify", "OK".to_string())),
+            Some(false) => rows.push(("Verify", "FAILED".to_string())),
+            None => {}
+        }
+        if report.reset {
+            rows.push(("Reset", "done".to_string()));
+        }
+        if !rows.is_empty() {
+            out.push_str("| Step | Result |\n|------|--------|\n");
+            for (step, result) in rows {
+                out.push_str(&format!("| {} | {} |\n", step, result));
+            }
+            out.push('\n');
+        }
+        if let Some(diagnosis) = diagnosis {
+            out.push_str(&format!(
+                "**Likely cause:** {}\n> {}\n\n**Fix:** {}\n\n",
+                diagnosis.kind.label(),
+                diagnosis.evidence,
+                diagnosis.kind.hint()
+            ));
+        }
+        out
+    }
+}
+
+impl Default for FlashProgrammer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for FlashProgrammer {
+    type Params = FlashProgrammerArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Write, ToolCapability::Device]
+    }
+
+    async fn execute_preview(&self, args: Self::Params) -> Option<ToolResult> {
+        Some(match self.plan(&args) {
+            Ok(plan) => {
+                let mut metadata = HashMap::new();
+                metadata.insert("backend".to_string(), json!(plan.backend.label()));
+                metadata.insert("action".to_string(), json!(plan.action.label()));
+                metadata.insert("commands".to_string(), json!(plan.command_lines()));
+                metadata.insert("dry_run".to_string(), json!(true));
+                ToolResult::success_with_metadata(Self::preview(&plan), metadata)
+            }
+            Err(e) => ToolResult::error(e),
+        })
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        let plan = match self.plan(&args) {
+            Ok(plan) => plan,
+            Err(e) => return ToolResult::error(e),
+        };
+
+        let mut lines = Vec::new();
+        let mut exit_code = Some(0);
+        let mut timed_out = false;
+        let mut completed = 0;
+        for command in &plan.commands {
+            let run = match Self::run(&plan.program, command, plan.timeout).await {
+                Ok(run) => run,
+                Err(e) => return ToolResult::error(e),
+            };
+            lines.extend(run.lines);
+            exit_code = run.exit_code;
+            timed_out = run.timed_out;
+            if timed_out || exit_code != Some(0) {
+                break;
+            }
+            completed += 1;
+        }
+
+        let mut report = flash_backend::parse(plan.backend, &lines);
+        let all_ran = completed == plan.commands.len();
+        // probe-rs resets with a separate command after downloading
+        if all_ran
+            && (plan.action == Action::Reset
+                || plan.backend == Backend::ProbeRs && plan.action == Action::Flash)
+        {
+            report.reset = true;
+        }
+        if all_ran && plan.action == Action::Verify && report.verified.is_none() {
+            report.verified = Some(true);
+        }
+        let succeeded = all_ran && report.verified != Some(false);
+        let diagnosis = if succeeded {
+            None
+        } else {
+            flash_backend::diagnose(&lines)
+        };
+
+        let mut out = Self::format_report(&plan, &report, diagnosis.as_ref(), succeeded);
+        if timed_out {
+            out.push_str(&format!(
+                "The backend did not finish within {} s and was stopped; below is what it printed until then.\n\n",
+                plan.timeout.as_secs()
+            ));
+        } else if !all_ran {
+            out.push_str(&format!(
+                "The backend exited with {}.\n\n",
+                exit_code.map_or("a signal".to_string(), |code| format!("code {}", code))
+            ));
+        }
+        out.push_str("```\n");
+        for line in plan.command_lines().iter().take(completed + 1) {
+            out.push_str(&format!("$ {}\n", line));
+        }
+        let skip = lines.len().saturating_sub(OUTPUT_TAIL_LINES);
+        if skip > 0 {
+            out.push_str(&format!("[... {} earlier lines ...]\n", skip));
+        }
+        for line in &lines[skip..] {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str("```\n");
+
+        let mut metadata = HashMap::new();
+        metadata.insert("backend".to_string(), json!(plan.backend.label()));
+        metadata.insert("action".to_string(), json!(plan.action.label()));
+        metadata.insert("exit_code".to_string(), json!(exit_code));
+        metadata.insert("chip".to_string(), json!(report.chip));
+        metadata.insert("bytes_written".to_string(), json!(report.bytes_written));
+        metadata.insert("verified".to_string(), json!(report.verified));
+        metadata.insert("erased".to_string(), json!(report.erased));
+        metadata.insert("reset".to_string(), json!(report.reset));
+        if let Some(diagnosis) = &diagnosis {
+            metadata.insert("cause".to_string(), json!(diagnosis.kind.label()));
+        }
+        if succeeded {
+            ToolResult::success_with_metadata(out, metadata)
+        } else {
+            ToolResult::error_with_metadata(out, metadata)
+        }
+    }
+}
+
+impl ToolDescription for FlashProgrammer {
+    fn name(&self) -> &'static str {
+        "flash"
+    }
+
+    fn description(&self) -> &'static str {
+        "Flash, verify, erase, reset or identify a microcontroller through probe-rs, OpenOCD, esptool or avrdude. Builds the command line for the backend, runs it and reports the detected chip, bytes written, verify result and, on failure, the likely cause with a fix. Every call asks the user first, showing the exact commands and their effect on the target. Use this instead of running programmers through bash."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(FlashProgrammerArgs))
+            .unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Shell script standing in for a backend: logs its arguments next to
+    /// itself, prints `output` to stderr and exits with `code`
+    fn fake_backend(dir: &Path, name: &str, output: &str, code: i32) {
+        let path = dir.join(name);
+        std::fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> \"$(dirname \"$0\")/{}.log\"\ncat >&2 <<'EOF'\n{}\nEOF\nexit {}\n",
+                name, output, code
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn args(action: &str, chip: &str, file: Option<&Path>) -> FlashProgrammerArgs {
+        FlashProgrammerArgs {
+            action: action.to_string(),
+            backend: None,
+            file: file.map(|f| f.display().to_string()),
+            chip: Some(chip.to_string()),
+            address: None,
+            port: None,
+            programmer: None,
+            target_config: None,
+            baud_rate: None,
+            speed_khz: None,
+            verify: None,
+            extra_args: None,
+            executable: None,
+            timeout_secs: None,
+        }
+    }
+
+    #[test]
+    fn test_flash_requires_device_write() {
+        let tool = FlashProgrammer::new();
+        assert_eq!(tool.name(), "flash");
+        assert!(tool.capabilities().contains(&ToolCapability::Write));
+        assert!(tool.capabilities().contains(&ToolCapability::Device));
+    }
+
+    #[tokio::test]
+    async fn test_flash_with_fake_probe_rs() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("app.elf");
+        std::fs::write(&image, [0u8; 2048]).unwrap();
+        fake_backend(
+            dir.path(),
+            "probe-rs",
+            "      Erasing ✔ [00:00:00] [##########] 16.00 KiB/16.00 KiB @ 52.30 KiB/s (eta 0s )\n  \
+             Programming ✔ [00:00:00] [##########] 2.00 KiB/2.00 KiB @ 10.00 KiB/s (eta 0s )\n    \
+             Finished in 0.31s",
+            0,
+        );
+        let tool = FlashProgrammer::with_search_path(dir.path());
+        let request = args("flash", "STM32F411CEUx", Some(&image));
+
+        // The confirmation shows the commands without running them
+        let preview = tool.execute_preview(request.clone()).await.unwrap();
+        let text = preview.to_string();
+        assert!(text.contains("Nothing has been executed yet"), "{}", text);
+        assert!(text.contains("Overwrites the firmware"), "{}", text);
+        assert!(text.contains("Image: "), "{}", text);
+        assert!(
+            text.contains("download --chip STM32F411CEUx --verify"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("/probe-rs reset --chip STM32F411CEUx\n"),
+            "{}",
+            text
+        );
+        assert!(!dir.path().join("probe-rs.log").exists());
+
+        match tool.execute(request).await {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("## probe-rs flash succeeded"), "{}", output);
+                assert!(
+                    output.contains("| Write | 2048 bytes in 0.31 s |"),
+                    "{}",
+                    output
+                );
+                assert!(output.contains("| Reset | done |"), "{}", output);
+                assert_eq!(metadata.unwrap()["bytes_written"], json!(2048));
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+        let log = std::fs::read_to_string(dir.path().join("probe-rs.log")).unwrap();
+        let calls: Vec<&str> = log.lines().collect();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].starts_with("download --chip STM32F411CEUx --verify "));
+        assert_eq!(calls[1], "reset --chip STM32F411CEUx");
+    }
+
+    #[tokio::test]
+    async fn test_flash_timeout_keeps_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("probe-rs");
+        std::fs::write(
+            &path,
+            "#!/bin/sh\necho 'Probe connected, halting core' >&2\nexec sleep 10\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut request = args("info", "STM32F411CEUx", None);
+        request.timeout_secs = Some(1);
+        match FlashProgrammer::with_search_path(dir.path())
+            .execute(request)
+            .await
+        {
+            ToolResult::Error { error, .. } => {
+                assert!(error.contains("did not finish within 1 s"), "{}", error);
+                assert!(error.contains("Probe connected, halting core"), "{}", error);
+            }
+            ToolResult::Success { output, .. } => panic!("{}", output),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flash_failure_diagnosis() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("blink.hex");
+        std::fs::write(&image, ":00000001FF\n").unwrap();
+        fake_backend(
+            dir.path(),
+            "avrdude",
+            "avrdude: stk500_recv(): programmer is not responding\n\
+             avrdude: stk500_getsync() attempt 10 of 10: not in sync: resp=0x00\n\n\
+             avrdude done.  Thank you.",
+            1,
+        );
+        let tool = FlashProgrammer::with_search_path(dir.path());
+        let mut request = args("flash", "m328p", Some(&image));
+        request.port = Some("/dev/ttyACM0".to_string());
+
+        match tool.execute(request).await {
+            ToolResult::Error { error, metadata } => {
+                assert!(error.contains("## avrdude flash failed"), "{}", error);
+                assert!(error.contains("**Likely cause:** The bootloader is not responding"));
+                assert!(error.contains("> avrdude: stk500_recv(): programmer is not responding"));
+                assert!(error.contains("exited with code 1"));
+                assert_eq!(
+                    metadata.unwrap()["cause"],
+                    json!("The bootloader is not responding")
+                );
+            }
+            ToolResult::Success { output, .. } => panic!("{}", output),
+        }
+
+        // Missing backends are reported before anything runs
+        let request = args("erase", "esp32", None);
+        match tool.execute_preview(request).await.unwrap() {
+            ToolResult::Error { error, .. } => {
+                assert!(error.contains("esptool was not found on PATH"), "{}", error);
+                assert!(error.contains("pip install esptool"));
+            }
+            other => panic!("{}", other),
+        }
+    }
+}
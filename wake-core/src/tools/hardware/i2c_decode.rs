@@ -0,0 +1,588 @@
+//! I2C transaction decoding from logic-analyzer captures.
+//!
+//! Walks SCL/SDA edges: SDA falling while SCL is high is a START (a repeated
+//! START inside a transaction), SDA rising while SCL is high is a STOP, and
+//! SDA is sampled on each SCL rising edge, eight data bits then ACK/NACK.
+//! The first byte after a START is the 7-bit address and R/W bit.
+
+use super::capture::Capture;
+use super::netlist::format_si;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    Stop,
+    RepeatedStart,
+    /// Capture ended inside the transaction
+    Missing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Byte {
+    pub value: u8,
+    pub ack: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    /// Time of the START in seconds
+    pub time: f64,
+    /// Began with a repeated START
+    pub repeated: bool,
+    pub address: Option<u8>,
+    pub read: bool,
+    pub address_ack: bool,
+    pub data: Vec<Byte>,
+    pub end: End,
+}
+
+impl Transaction {
+    fn new(time: f64, repeated: bool) -> Self {
+        Self {
+            time,
+            repeated,
+            address: None,
+            read: false,
+            address_ack: false,
+            data: Vec::new(),
+            end: End::Missing,
+        }
+    }
+
+    fn target(&self) -> String {
+        match self.address {
+            Some(address) => format!("0x{:02X} ({})", address, if self.read { "R" } else { "W" }),
+            None => "no address".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    Nack,
+    MissingStop,
+    ClockStretch,
+    RepeatedStart,
+    BusError,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub time: f64,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    pub transactions: Vec<Transaction>,
+    pub issues: Vec<Issue>,
+    /// Median SCL frequency inside bytes
+    pub scl_frequency: Option<f64>,
+    /// Longest SCL low period inside a transaction, in seconds
+    pub longest_low: f64,
+}
+
+impl Decoded {
+    pub fn has(&self, kind: IssueKind) -> bool {
+        self.issues.iter().any(|i| i.kind == kind)
+    }
+}
+
+/// Decode a capture; SCL low periods longer than `stretch_limit` seconds are flagged
+pub fn decode(capture: &Capture, scl: usize, sda: usize, stretch_limit: f64) -> Decoded {
+    let mut transactions: Vec<Transaction> = Vec::new();
+    let mut issues = Vec::new();
+    let mut current: Option<Transaction> = None;
+    let mut bits = 0u32;
+    let mut shift = 0u16;
+    let mut bit_rises: Vec<f64> = Vec::new();
+    let mut periods: Vec<f64> = Vec::new();
+    let mut low_since: Option<f64> = None;
+    let mut longest_low = 0.0f64;
+    let mut idle_clocks = 0usize;
+    let mut idle_since = None;
+
+    // The SCL rise that precedes a STOP or repeated START clocks one stray bit
+    let interrupted = |issues: &mut Vec<Issue>, bits: u32, time: f64, what: &str| {
+        if bits > 1 {
+            issues.push(Issue {
+                time,
+                kind: IssueKind::BusError,
+                message: format!(
+                    "{} after {} of 9 bits; the byte was cut short",
+                    what,
+                    bits - 1
+                ),
+            });
+        }
+    };
+
+    for pair in capture.samples.windows(2) {
+        let ((_, before), (time, after)) = (pair[0], pair[1]);
+        let (scl_was, sda_was) = (Capture::level(before, scl), Capture::level(before, sda));
+        let (scl_is, sda_is) = (Capture::level(after, scl), Capture::level(after, sda));
+
+        if scl_was && scl_is && sda_was != sda_is {
+            if !sda_is {
+                // START or repeated START
+                let repeated = current.is_some();
+                if let Some(mut t) = current.take() {
+                    interrupted(&mut issues, bits, time, "Repeated START");
+                    t.end = End::RepeatedStart;
+                    transactions.push(t);
+                }
+                current = Some(Transaction::new(time, repeated));
+            } else if let Some(mut t) = current.take() {
+                interrupted(&mut issues, bits, time, "STOP");
+                t.end = End::Stop;
+                transactions.push(t);
+            }
+            bits = 0;
+            shift = 0;
+            bit_rises.clear();
+            low_since = None;
+            continue;
+        }
+
+        if scl_was && !scl_is {
+            low_since = Some(time);
+        } else if !scl_was && scl_is {
+            let Some(t) = current.as_mut() else {
+                if idle_clocks == 0 {
+                    idle_since = Some(time);
+                }
+                idle_clocks += 1;
+                continue;
+            };
+            if let Some(since) = low_since.take() {
+                let low = time - since;
+                longest_low = longest_low.max(low);
+                if low > stretch_limit {
+                    issues.push(Issue {
+                        time: since,
+                        kind: IssueKind::ClockStretch,
+                        message: format!(
+                            "SCL held low for {} during {} (limit {})",
+                            format_si(low, "s"),
+                            if t.address.is_some() {
+                                t.target()
+                            } else {
+                                "the address byte".to_string()
+                            },
+                            format_si(stretch_limit, "s")
+                        ),
+                    });
+                }
+            }
+            if let Some(last) = bit_rises.last() {
+                periods.push(time - last);
+            }
+            bit_rises.push(time);
+
+            shift = shift << 1 | sda_is as u16;
+            bits += 1;
+            if bits == 9 {
+                let value = (shift >> 1) as u8;
+                let ack = shift & 1 == 0;
+                if t.address.is_none() {
+                    t.address = Some(value >> 1);
+                    t.read = value & 1 == 1;
+                    t.address_ack = ack;
+                } else {
+                    t.data.push(Byte { value, ack });
+                }
+                bits = 0;
+                shift = 0;
+                bit_rises.clear();
+            }
+        }
+    }
+    if let Some(mut t) = current.take() {
+        t.end = End::Missing;
+        transactions.push(t);
+    }
+
+    if let Some(time) = idle_since {
+        issues.push(Issue {
+            time,
+            kind: IssueKind::BusError,
+            message: format!(
+                "SCL pulsed {} times outside a START/STOP frame (bus recovery clocks or a missed START)",
+                idle_clocks
+            ),
+        });
+    }
+    for (index, t) in transactions.iter().enumerate() {
+        check(t, transactions.get(index + 1), &mut issues);
+    }
+    issues.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    periods.sort_by(f64::total_cmp);
+    Decoded {
+        transactions,
+        issues,
+        scl_frequency: periods
+            .get(periods.len() / 2)
+            .filter(|p| **p > 0.0)
+            .map(|p| 1.0 / p),
+        longest_low,
+    }
+}
+
+/// Protocol rules for one transaction; `next` follows it on the bus
+fn check(t: &Transaction, next: Option<&Transaction>, issues: &mut Vec<Issue>) {
+    let mut issue = |kind, message: String| {
+        issues.push(Issue {
+            time: t.time,
+            kind,
+            message,
+        })
+    };
+    let target = t.target();
+
+    match t.address {
+        None if t.end == End::Missing => {}
+        None => issue(
+            IssueKind::BusError,
+            format!(
+                "{} directly after START with no address byte",
+                if t.end == End::Stop {
+                    "STOP"
+                } else {
+                    "Repeated START"
+                }
+            ),
+        ),
+        Some(address) if !t.address_ack => {
+            issue(
+                IssueKind::Nack,
+                format!(
+                    "Address 0x{:02X} not acknowledged: no device answered",
+                    address
+                ),
+            );
+            if !t.data.is_empty() {
+                issue(
+                    IssueKind::BusError,
+                    format!(
+                        "Master kept clocking {} bytes after the address NACK",
+                        t.data.len()
+                    ),
+                );
+            }
+        }
+        Some(_) if t.read => {
+            let last = t.data.len().saturating_sub(1);
+            if let Some(index) = t.data[..last].iter().position(|b| !b.ack) {
+                issue(
+                    IssueKind::Nack,
+                    format!(
+                        "Master NACKed read byte {} of {} from {} and kept reading",
+                        index + 1,
+                        t.data.len(),
+                        target
+                    ),
+                );
+            }
+            if t.data.last().is_some_and(|b| b.ack) && t.end != End::Missing {
+                issue(
+                    IssueKind::Nack,
+                    format!(
+                        "Last byte read from {} was ACKed; the master must NACK the final byte before STOP or the device may hold SDA low",
+                        target
+                    ),
+                );
+            }
+        }
+        Some(_) => {
+            if let Some(index) = t.data.iter().position(|b| !b.ack) {
+                issue(
+                    IssueKind::Nack,
+                    format!(
+                        "Byte {} (0x{:02X}) written to {} not acknowledged{}",
+                        index + 1,
+                        t.data[index].value,
+                        target,
+                        if index + 1 < t.data.len() {
+                            "; the master kept writing"
+                        } else {
+                            ""
+                        }
+                    ),
+                );
+            }
+        }
+    }
+
+    match (t.end, next) {
+        (End::Missing, _) => issue(
+            IssueKind::MissingStop,
+            format!(
+                "Transaction to {} has no STOP before the capture ends",
+                target
+            ),
+        ),
+        (End::RepeatedStart, None) => issue(
+            IssueKind::MissingStop,
+            format!(
+                "Repeated START after {} is never followed by a transfer or STOP",
+                target
+            ),
+        ),
+        (End::RepeatedStart, Some(next)) => {
+            let nacked = !t.address_ack || t.data.iter().any(|b| !b.ack && !t.read);
+            if t.address.is_some() && nacked {
+                issue(
+                    IssueKind::RepeatedStart,
+                    format!(
+                        "Repeated START after a NACK from {}; send STOP to end the failed transfer",
+                        target
+                    ),
+                );
+            }
+            if let (Some(from), Some(to)) = (t.address, next.address) {
+                if from != to {
+                    issue(
+                        IssueKind::RepeatedStart,
+                        format!(
+                            "Repeated START switches from 0x{:02X} to 0x{:02X}; a combined transfer normally stays on one device",
+                            from, to
+                        ),
+                    );
+                } else if t.read {
+                    issue(
+                        IssueKind::RepeatedStart,
+                        format!(
+                            "Repeated START after reading from {}; a combined transfer writes the register address first, then reads",
+                            target
+                        ),
+                    );
+                } else if !next.read && t.address_ack {
+                    issue(
+                        IssueKind::RepeatedStart,
+                        format!(
+                            "Write to {} followed by a repeated START and another write; register pointer writes are normally followed by a read",
+                            target
+                        ),
+                    );
+                }
+            }
+        }
+        (End::Stop, _) => {}
+    }
+}
+
+/// Markdown table of decoded transactions, at most `limit` rows
+pub fn table(decoded: &Decoded, limit: usize) -> String {
+    let mut out = String::from(
+        "| # | Time | Start | Address | Data | End |\n\
+         |---|------|-------|---------|------|-----|\n",
+    );
+    for (index, t) in decoded.transactions.iter().take(limit).enumerate() {
+        let address = match t.address {
+            Some(address) => format!(
+                "0x{:02X} {} {}",
+                address,
+                if t.read { "R" } else { "W" },
+                if t.address_ack { "ACK" } else { "NACK" }
+            ),
+            None => "-".to_string(),
+        };
+        let data = t
+            .data
+            .iter()
+            .map(|b| {
+                if b.ack {
+                    format!("{:02X}", b.value)
+                } else {
+                    format!("{:02X} NACK", b.value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            index + 1,
+            format_si(t.time, "s"),
+            if t.repeated { "Sr" } else { "S" },
+            address,
+            if data.is_empty() {
+                "-".to_string()
+            } else {
+                data
+            },
+            match t.end {
+                End::Stop => "P",
+                End::RepeatedStart => "Sr",
+                End::Missing => "none",
+            }
+        ));
+    }
+    if decoded.transactions.len() > limit {
+        out.push_str(&format!(
+            "\n... {} more transactions\n",
+            decoded.transactions.len() - limit
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Bit-banged SCL/SDA capture (channel 0 = SCL, 1 = SDA) at 100 kHz
+    pub(crate) struct Bus {
+        pub capture: Capture,
+        time: f64,
+        scl: bool,
+        sda: bool,
+    }
+
+    impl Bus {
+        const HALF: f64 = 5e-6;
+
+        pub fn new() -> Self {
+            let mut capture = Capture {
+                channels: vec!["SCL".to_string(), "SDA".to_string()],
+                samples: Vec::new(),
+            };
+            capture.samples.push((0.0, 0b11));
+            Self {
+                capture,
+                time: 0.0,
+                scl: true,
+                sda: true,
+            }
+        }
+
+        fn set(&mut self, scl: bool, sda: bool, after: f64) {
+            self.time += after;
+            self.scl = scl;
+            self.sda = sda;
+            self.capture
+                .samples
+                .push((self.time, scl as u64 | (sda as u64) << 1));
+        }
+
+        pub fn start(&mut self) -> &mut Self {
+            if !self.scl {
+                self.set(false, true, Self::HALF);
+                self.set(true, true, Self::HALF);
+            }
+            self.set(true, false, Self::HALF);
+            self.set(false, false, Self::HALF);
+            self
+        }
+
+        pub fn stop(&mut self) -> &mut Self {
+            self.set(false, false, Self::HALF);
+            self.set(true, false, Self::HALF);
+            self.set(true, true, Self::HALF);
+            self
+        }
+
+        fn bit(&mut self, high: bool, low_time: f64) {
+            self.set(false, high, Self::HALF / 2.0);
+            self.set(true, high, low_time - Self::HALF / 2.0);
+            self.set(false, high, Self::HALF);
+        }
+
+        pub fn byte(&mut self, value: u8, ack: bool) -> &mut Self {
+            self.stretched(value, ack, Self::HALF)
+        }
+
+        /// Byte whose ACK clock is held low for `low_time`
+        pub fn stretched(&mut self, value: u8, ack: bool, low_time: f64) -> &mut Self {
+            for bit in (0..8).rev() {
+                self.bit(value >> bit & 1 == 1, Self::HALF);
+            }
+            self.bit(!ack, low_time);
+            self
+        }
+    }
+
+    #[test]
+    fn test_register_read() {
+        let mut bus = Bus::new();
+        bus.start()
+            .byte(0x76 << 1, true)
+            .byte(0xD0, true)
+            .start()
+            .byte(0x76 << 1 | 1, true)
+            .byte(0x60, true)
+            .byte(0x12, false)
+            .stop();
+        let decoded = decode(&bus.capture, 0, 1, 1e-3);
+
+        assert_eq!(decoded.transactions.len(), 2);
+        let (write, read) = (&decoded.transactions[0], &decoded.transactions[1]);
+        assert_eq!(
+            (write.address, write.read, write.end),
+            (Some(0x76), false, End::RepeatedStart)
+        );
+        assert_eq!(
+            write.data,
+            vec![Byte {
+                value: 0xD0,
+                ack: true
+            }]
+        );
+        assert!(read.repeated && read.read && read.end == End::Stop);
+        assert_eq!(
+            read.data
+                .iter()
+                .map(|b| (b.value, b.ack))
+                .collect::<Vec<_>>(),
+            vec![(0x60, true), (0x12, false)]
+        );
+        assert!(decoded.issues.is_empty(), "{:?}", decoded.issues);
+        assert!((decoded.scl_frequency.unwrap() - 100e3).abs() < 1.0);
+
+        let table = table(&decoded, 10);
+        assert!(table.contains("| 1 | 5.000 µs | S | 0x76 W ACK | D0 | Sr |"));
+        assert!(table.contains("| Sr | 0x76 R ACK | 60 12 NACK | P |"));
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut bus = Bus::new();
+        bus.start().byte(0x50 << 1, false).start(); // Sr after NACK
+        bus.byte(0x76 << 1, true)
+            .stretched(0xF4, true, 2e-3)
+            .byte(0x27, false)
+            .stop();
+        bus.start()
+            .byte(0x76 << 1 | 1, true)
+            .byte(0x01, true)
+            .stop(); // last read ACKed
+        bus.start().byte(0x76 << 1, true).byte(0x00, true); // no STOP
+        let decoded = decode(&bus.capture, 0, 1, 1e-3);
+
+        let kinds: Vec<_> = decoded.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                IssueKind::Nack,
+                IssueKind::RepeatedStart,
+                IssueKind::RepeatedStart,
+                IssueKind::Nack,
+                IssueKind::ClockStretch,
+                IssueKind::Nack,
+                IssueKind::MissingStop,
+            ],
+            "{:#?}",
+            decoded.issues
+        );
+        assert!(decoded.issues[0]
+            .message
+            .contains("Address 0x50 not acknowledged"));
+        assert!(decoded.issues[2].message.contains("from 0x50 to 0x76"));
+        assert!(decoded.issues[4]
+            .message
+            .contains("SCL held low for 2.000 ms"));
+        assert!(decoded.issues[3].message.contains("Byte 2 (0x27)"));
+        assert!(decoded.issues[5].message.contains("final byte"));
+        assert!(decoded.longest_low >= 2e-3);
+    }
+}
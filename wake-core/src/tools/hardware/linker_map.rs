@@ -0,0 +1,282 @@
+//! GNU ld map files (`-Wl,-Map=app.map`).
+//!
+//! Reads the memory configuration, the output sections with their load
+//! addresses and the input sections every object file contributed. Symbol
+//! lines carry no sizes and are skipped; the ELF symbol table has those.
+
+use super::memory_usage::{Contribution, Image, Region, Section, SectionKind};
+use regex::Regex;
+use std::path::Path;
+
+/// Output sections that are not loaded into the target
+fn is_debug(name: &str) -> bool {
+    [
+        ".debug",
+        ".stab",
+        ".comment",
+        ".ARM.attributes",
+        ".gnu.attributes",
+    ]
+    .iter()
+    .any(|prefix| name.starts_with(prefix))
+        || name == "/DISCARD/"
+}
+
+fn hex(text: &str) -> u64 {
+    u64::from_str_radix(text.trim_start_matches("0x"), 16).unwrap_or(0)
+}
+
+/// `libc_nano.a(lib_a-memcpy.o)` or `main.o`, without the directories
+pub fn short_object(object: &str) -> String {
+    let (path, member) = match object.find('(') {
+        Some(open) if object.ends_with(')') => (&object[..open], &object[open..]),
+        _ => (object, ""),
+    };
+    let file = Path::new(path)
+        .file_name()
+        .map_or(path.to_string(), |f| f.to_string_lossy().to_string());
+    format!("{}{}", file, member)
+}
+
+pub fn parse(text: &str) -> Image {
+    let memory = Regex::new(r"^(\S+)\s+(0x[0-9a-fA-F]+)\s+(0x[0-9a-fA-F]+)").unwrap();
+    let output = Regex::new(
+        r"^(\S+)\s+(0x[0-9a-fA-F]+)\s+(0x[0-9a-fA-F]+)(?:\s+load address (0x[0-9a-fA-F]+))?",
+    )
+    .unwrap();
+    let input = Regex::new(r"^ (\S+)\s+(0x[0-9a-fA-F]+)\s+(0x[0-9a-fA-F]+)\s*(.*)$").unwrap();
+    // Second line of an entry whose name was too long to share a line
+    let wrapped = Regex::new(
+        r"^\s+(0x[0-9a-fA-F]+)\s+(0x[0-9a-fA-F]+)(?:\s+load address (0x[0-9a-fA-F]+))?\s*(.*)$",
+    )
+    .unwrap();
+
+    let mut image = Image::default();
+    let mut in_memory = false;
+    let mut in_map = false;
+    // Index of the output section being read, if it is one we keep
+    let mut current: Option<usize> = None;
+    let mut pending_output: Option<String> = None;
+    let mut pending_input: Option<String> = None;
+
+    let add_output = |image: &mut Image, name: &str, addr: u64, size: u64, load: Option<u64>| {
+        if is_debug(name) || size == 0 {
+            return None;
+        }
+        image.sections.push(Section {
+            name: name.to_string(),
+            addr,
+            lma: load.unwrap_or(addr),
+            size,
+            kind: match SectionKind::from_name(name) {
+                // Anything stored elsewhere is initialised data
+                SectionKind::Code if load.is_some_and(|l| l != addr) => SectionKind::Data,
+                kind => kind,
+            },
+        });
+        Some(image.sections.len() - 1)
+    };
+    let add_input =
+        |image: &mut Image, current: Option<usize>, name: &str, addr, size, object: &str| {
+            let Some(index) = current else { return };
+            if size == 0 {
+                return;
+            }
+            let object = match (name, object.trim()) {
+                ("*fill*", _) => "*fill*".to_string(),
+                (_, "") => "?".to_string(),
+                (_, object) => object.to_string(),
+            };
+            let section = image.sections[index].name.clone();
+            image.contributions.push(Contribution {
+                object,
+                input: name.to_string(),
+                section,
+                addr,
+                size,
+            });
+        };
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line == "Memory Configuration" {
+            in_memory = true;
+            continue;
+        }
+        if line == "Linker script and memory map" {
+            in_memory = false;
+            in_map = true;
+            continue;
+        }
+        if in_memory {
+            if let Some(c) = memory.captures(line) {
+                if &c[1] != "*default*" {
+                    image.regions.push(Region {
+                        name: c[1].to_string(),
+                        origin: hex(&c[2]),
+                        length: hex(&c[3]),
+                    });
+                }
+            }
+            continue;
+        }
+        if !in_map || line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = pending_output.take() {
+            if let Some(c) = wrapped.captures(line) {
+                let load = c.get(3).map(|m| hex(m.as_str()));
+                current = add_output(&mut image, &name, hex(&c[1]), hex(&c[2]), load);
+                continue;
+            }
+        }
+        if let Some(name) = pending_input.take() {
+            if let Some(c) = wrapped.captures(line) {
+                add_input(&mut image, current, &name, hex(&c[1]), hex(&c[2]), &c[4]);
+                continue;
+            }
+        }
+
+        if !line.starts_with(' ') {
+            current = None;
+            if let Some(c) = output.captures(line) {
+                let load = c.get(4).map(|m| hex(m.as_str()));
+                current = add_output(&mut image, &c[1], hex(&c[2]), hex(&c[3]), load);
+            } else if line.starts_with('.') && !line.contains(' ') {
+                pending_output = Some(line.to_string());
+            }
+        } else if !line.starts_with("  ") {
+            if let Some(c) = input.captures(line) {
+                add_input(&mut image, current, &c[1], hex(&c[2]), hex(&c[3]), &c[4]);
+            } else {
+                let name = line.trim();
+                if !name.contains(' ') && (name.starts_with('.') || name == "COMMON") {
+                    pending_input = Some(name.to_string());
+                }
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MAP: &str = "\
+Archive member included to satisfy reference by file (symbol)
+
+/usr/lib/arm-none-eabi/lib/thumb/v7-m/nofp/libc_nano.a(lib_a-memcpy.o)
+                              build/main.o (memcpy)
+
+Discarded input sections
+
+ .text          0x00000000        0x0 build/main.o
+ .text.unused   0x00000000       0x20 build/main.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x0000000008000000 0x0000000000010000 xr
+RAM              0x0000000020000000 0x0000000000005000 xrw
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+LOAD build/startup_stm32f103xb.o
+LOAD build/main.o
+                0x0000000020005000                _estack = (ORIGIN (RAM) + LENGTH (RAM))
+
+.isr_vector     0x0000000008000000      0x10c
+                0x0000000008000000                . = ALIGN (0x4)
+ *(.isr_vector)
+ .isr_vector    0x0000000008000000      0x10c build/startup_stm32f103xb.o
+                0x0000000008000000                g_pfnVectors
+
+.text           0x0000000008000110      0x5f0
+ *(.text)
+ .text          0x0000000008000110       0x40 /usr/lib/gcc/arm-none-eabi/13.2.1/thumb/v7-m/nofp/crtbegin.o
+ .text.main     0x0000000008000150       0x88 build/main.o
+                0x0000000008000150                main
+ .text.HAL_RCC_OscConfig
+                0x00000000080001d8      0x4a8 build/stm32f1xx_hal_rcc.o
+                0x00000000080001d8                HAL_RCC_OscConfig
+ *fill*         0x0000000008000680        0x2
+ .text          0x0000000008000682       0x1e /usr/lib/arm-none-eabi/lib/thumb/v7-m/nofp/libc_nano.a(lib_a-memcpy.o)
+                0x0000000008000682                memcpy
+
+.rodata         0x00000000080006a0       0x20
+ .rodata.table  0x00000000080006a0       0x20 build/main.o
+                0x00000000080006c0                _sidata = LOADADDR (.data)
+
+.data           0x0000000020000000        0xc load address 0x00000000080006c0
+ .data.counter  0x0000000020000000        0x4 build/main.o
+ .data.SystemCoreClock
+                0x0000000020000004        0x8 build/system_stm32f1xx.o
+
+.bss            0x000000002000000c      0x410
+ .bss.rx_buffer
+                0x000000002000000c      0x400 build/main.o
+ COMMON         0x000000002000040c       0x10 build/stm32f1xx_hal.o
+
+._user_heap_stack
+                0x000000002000041c      0x600
+                0x0000000020000a1c                . = (. + 0x600)
+
+.debug_info     0x0000000000000000     0x9a3c
+ .debug_info    0x0000000000000000      0x12c build/main.o
+OUTPUT(build/app.elf elf32-littlearm)
+";
+
+    #[test]
+    fn test_parse_map() {
+        let image = parse(TEST_MAP);
+        assert_eq!(image.regions.len(), 2);
+        assert_eq!(image.regions[1].name, "RAM");
+        assert_eq!(image.regions[1].length, 0x5000);
+
+        let sections: Vec<(&str, u64, SectionKind)> = image
+            .sections
+            .iter()
+            .map(|s| (s.name.as_str(), s.size, s.kind))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                (".isr_vector", 0x10c, SectionKind::Code),
+                (".text", 0x5f0, SectionKind::Code),
+                (".rodata", 0x20, SectionKind::Code),
+                (".data", 0xc, SectionKind::Data),
+                (".bss", 0x410, SectionKind::Bss),
+                ("._user_heap_stack", 0x600, SectionKind::Bss),
+            ]
+        );
+        assert_eq!(image.section(".data").unwrap().lma, 0x0800_06c0);
+
+        let parts: Vec<(&str, &str, u64)> = image
+            .contributions
+            .iter()
+            .map(|c| (c.input.as_str(), c.object.as_str(), c.size))
+            .collect();
+        assert_eq!(parts.len(), 11);
+        assert!(parts.contains(&(
+            ".text.HAL_RCC_OscConfig",
+            "build/stm32f1xx_hal_rcc.o",
+            0x4a8
+        )));
+        assert!(parts.contains(&("*fill*", "*fill*", 2)));
+        assert!(parts.contains(&(".bss.rx_buffer", "build/main.o", 0x400)));
+        assert!(parts.contains(&("COMMON", "build/stm32f1xx_hal.o", 0x10)));
+        assert!(parts.contains(&(".data.SystemCoreClock", "build/system_stm32f1xx.o", 8)));
+        assert!(!parts
+            .iter()
+            .any(|p| p.0 == ".text.unused" || p.0 == ".debug_info"));
+
+        assert_eq!(
+            short_object("/usr/lib/arm-none-eabi/lib/thumb/v7-m/nofp/libc_nano.a(lib_a-memcpy.o)"),
+            "libc_nano.a(lib_a-memcpy.o)"
+        );
+        assert_eq!(short_object("build/main.o"), "main.o");
+    }
+}
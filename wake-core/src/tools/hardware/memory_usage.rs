@@ -0,0 +1,356 @@
+//! Firmware memory model shared by the ELF and GNU ld map readers.
+//!
+//! An [`Image`] holds the allocated output sections, sized symbols and the
+//! input sections each object file contributed. A [`Layout`] assigns them to
+//! the linker `MEMORY` regions; initialised data counts twice, in RAM where
+//! it runs and in flash where its initial values are stored.
+
+use regex::Regex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionKind {
+    /// Code and read-only data
+    Code,
+    /// Initialised RAM data, copied from flash at startup
+    Data,
+    /// Zero-initialised or uninitialised RAM
+    Bss,
+}
+
+impl SectionKind {
+    /// Guess from a section name, for map files that carry no flags
+    pub fn from_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.starts_with(".bss")
+            || name.starts_with(".sbss")
+            || name.starts_with(".tbss")
+            || name == "common"
+            || name.contains("noinit")
+            || name.contains("heap")
+            || name.contains("stack")
+        {
+            SectionKind::Bss
+        } else if name.starts_with(".data")
+            || name.starts_with(".sdata")
+            || name.starts_with(".tdata")
+            || name.starts_with(".ramfunc")
+        {
+            SectionKind::Data
+        } else {
+            SectionKind::Code
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub addr: u64,
+    /// Load address: where the initial contents are stored
+    pub lma: u64,
+    pub size: u64,
+    pub kind: SectionKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+    /// Output section the symbol lives in
+    pub section: String,
+}
+
+/// Input section an object file contributed to an output section
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contribution {
+    pub object: String,
+    pub input: String,
+    pub section: String,
+    pub addr: u64,
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub origin: u64,
+    /// Zero when unknown
+    pub length: u64,
+}
+
+impl Region {
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.origin && addr - self.origin < self.length
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Image {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+    pub contributions: Vec<Contribution>,
+    /// `MEMORY` regions, when the artifact describes them
+    pub regions: Vec<Region>,
+}
+
+impl Image {
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// text, data and bss totals, as binutils `size` reports them
+    pub fn totals(&self) -> (u64, u64, u64) {
+        let sum = |kind| {
+            self.sections
+                .iter()
+                .filter(|s| s.kind == kind)
+                .map(|s| s.size)
+                .sum()
+        };
+        (
+            sum(SectionKind::Code),
+            sum(SectionKind::Data),
+            sum(SectionKind::Bss),
+        )
+    }
+
+    /// Kind and load address of something placed at `addr` in `section`
+    fn placement(&self, section: &str, addr: u64) -> (SectionKind, u64) {
+        match self.section(section) {
+            Some(s) => (s.kind, addr.wrapping_sub(s.addr).wrapping_add(s.lma)),
+            None => (SectionKind::from_name(section), addr),
+        }
+    }
+}
+
+/// Where bytes are counted
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub regions: Vec<Region>,
+    /// No `MEMORY` regions are known: count FLASH and RAM by section kind
+    by_kind: bool,
+}
+
+impl Layout {
+    pub fn new(regions: Vec<Region>) -> Self {
+        Self {
+            regions,
+            by_kind: false,
+        }
+    }
+
+    /// FLASH and RAM by section kind, with sizes when the caller knows them
+    pub fn by_kind(flash: Option<u64>, ram: Option<u64>) -> Self {
+        let region = |name: &str, length: Option<u64>| Region {
+            name: name.to_string(),
+            origin: 0,
+            length: length.unwrap_or(0),
+        };
+        Self {
+            regions: vec![region("FLASH", flash), region("RAM", ram)],
+            by_kind: true,
+        }
+    }
+
+    pub fn is_by_kind(&self) -> bool {
+        self.by_kind
+    }
+
+    fn region_of(&self, addr: u64) -> Option<usize> {
+        self.regions.iter().position(|r| r.contains(addr))
+    }
+
+    /// Regions occupied by bytes running at `addr` and stored at `lma`
+    pub fn place(&self, kind: SectionKind, addr: u64, lma: u64) -> Vec<usize> {
+        if self.by_kind {
+            return match kind {
+                SectionKind::Code => vec![0],
+                SectionKind::Data => vec![0, 1],
+                SectionKind::Bss => vec![1],
+            };
+        }
+        let mut regions: Vec<usize> = self.region_of(addr).into_iter().collect();
+        if lma != addr {
+            if let Some(load) = self.region_of(lma) {
+                if !regions.contains(&load) {
+                    regions.push(load);
+                }
+            }
+        }
+        regions
+    }
+
+    /// Bytes used per region, and bytes that fall outside every region
+    pub fn usage(&self, image: &Image) -> (Vec<u64>, u64) {
+        let mut used = vec![0; self.regions.len()];
+        let mut outside = 0;
+        for section in &image.sections {
+            let regions = self.place(section.kind, section.addr, section.lma);
+            if regions.is_empty() {
+                outside += section.size;
+            }
+            for region in regions {
+                used[region] += section.size;
+            }
+        }
+        (used, outside)
+    }
+
+    /// Bytes per region for one symbol
+    pub fn symbol_regions(&self, image: &Image, symbol: &Symbol) -> Vec<usize> {
+        let (kind, lma) = image.placement(&symbol.section, symbol.addr);
+        self.place(kind, symbol.addr, lma)
+    }
+
+    /// Bytes per region for one input section
+    pub fn contribution_regions(&self, image: &Image, part: &Contribution) -> Vec<usize> {
+        let (kind, lma) = image.placement(&part.section, part.addr);
+        self.place(kind, part.addr, lma)
+    }
+}
+
+/// Linker script size or address: `0x10000`, `65536`, `64K`, `1M`, or sums
+/// and differences of those such as `64K - 2K`
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid size '{}'; use e.g. 64K, 0x10000 or 65536", text);
+    let term = |t: &str| -> Option<u64> {
+        let t = t.trim();
+        let (digits, scale) = match t.chars().last()? {
+            'k' | 'K' => (&t[..t.len() - 1], 1024),
+            'm' | 'M' => (&t[..t.len() - 1], 1024 * 1024),
+            _ => (t, 1),
+        };
+        let digits = digits.trim();
+        let value = match digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+            None => digits.parse().ok()?,
+        };
+        value.checked_mul(scale)
+    };
+    let mut total: i128 = 0;
+    let mut sign = 1;
+    let mut rest = text.trim();
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        total += sign * term(&rest[..end]).ok_or_else(invalid)? as i128;
+        if end == rest.len() {
+            break;
+        }
+        sign = if rest[end..].starts_with('-') { -1 } else { 1 };
+        rest = &rest[end + 1..];
+    }
+    u64::try_from(total).map_err(|_| invalid())
+}
+
+/// Regions from the `MEMORY { ... }` block of a linker script
+pub fn parse_memory(script: &str) -> Vec<Region> {
+    let comments = Regex::new(r"(?s)/\*.*?\*/|//[^\n]*").unwrap();
+    let script = comments.replace_all(script, "");
+    let Some(block) = Regex::new(r"(?s)MEMORY\s*\{(.*?)\}")
+        .unwrap()
+        .captures(&script)
+    else {
+        return Vec::new();
+    };
+    let line = Regex::new(
+        r"(?i)(\w+)\s*(?:\([^)]*\))?\s*:\s*(?:ORIGIN|org|o)\s*=\s*([^,]+),\s*(?:LENGTH|len|l)\s*=\s*([^,;\n]+)",
+    )
+    .unwrap();
+    line.captures_iter(&block[1])
+        .filter_map(|c| {
+            Some(Region {
+                name: c[1].to_string(),
+                origin: parse_size(&c[2]).ok()?,
+                length: parse_size(&c[3]).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// `61.2 KiB` or `512 B`
+pub fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_regions_and_placement() {
+        assert_eq!(parse_size("64K").unwrap(), 65536);
+        assert_eq!(parse_size("0x5000").unwrap(), 0x5000);
+        assert_eq!(parse_size("128K - 2K").unwrap(), 129024);
+        assert!(parse_size("lots").is_err());
+
+        let regions = parse_memory(
+            "/* STM32F103C8 */\nENTRY(Reset_Handler)\nMEMORY\n{\n  \
+             RAM (xrw)   : ORIGIN = 0x20000000, LENGTH = 20K\n  \
+             FLASH (rx)  : org = 0x8000000, len = 64K - 2K // last page holds settings\n}\n",
+        );
+        assert_eq!(
+            regions,
+            vec![
+                Region {
+                    name: "RAM".to_string(),
+                    origin: 0x2000_0000,
+                    length: 20 * 1024
+                },
+                Region {
+                    name: "FLASH".to_string(),
+                    origin: 0x0800_0000,
+                    length: 62 * 1024
+                },
+            ]
+        );
+
+        let image = Image {
+            sections: vec![
+                Section {
+                    name: ".text".to_string(),
+                    addr: 0x0800_0000,
+                    lma: 0x0800_0000,
+                    size: 1000,
+                    kind: SectionKind::Code,
+                },
+                Section {
+                    name: ".data".to_string(),
+                    addr: 0x2000_0000,
+                    lma: 0x0800_03E8,
+                    size: 24,
+                    kind: SectionKind::Data,
+                },
+                Section {
+                    name: ".bss".to_string(),
+                    addr: 0x2000_0018,
+                    lma: 0x2000_0018,
+                    size: 100,
+                    kind: SectionKind::Bss,
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(image.totals(), (1000, 24, 100));
+        let layout = Layout::new(regions);
+        assert_eq!(layout.usage(&image), (vec![124, 1024], 0));
+        assert_eq!(
+            Layout::by_kind(None, None).usage(&image),
+            (vec![1024, 124], 0)
+        );
+        let counter = Symbol {
+            name: "counter".to_string(),
+            addr: 0x2000_0004,
+            size: 4,
+            section: ".data".to_string(),
+        };
+        assert_eq!(layout.symbol_regions(&image, &counter), vec![0, 1]);
+    }
+}
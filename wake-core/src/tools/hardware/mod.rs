@@ -1,15 +1,51 @@
 // Hardware-specific tools for Wake
+pub mod can_timing;
+pub mod can_trace;
+pub mod capture;
 pub mod circuit_analyzer;
 pub mod datasheet_analyzer;
+pub mod datasheet_pdf;
+pub mod dbc;
 pub mod driver_generator;
+pub mod e_series;
+pub mod elf_image;
+pub mod fault_decoder;
+pub mod fault_status;
+pub mod firmware_size;
+pub mod flash_backend;
+pub mod flash_programmer;
+pub mod i2c_decode;
+pub mod linker_map;
+pub mod memory_usage;
+pub mod modbus;
+pub mod netlist;
+pub mod package;
+pub mod pin_codegen;
+pub mod pin_database;
+pub mod pin_solver;
 pub mod pinout_mapper;
+pub mod power_budget;
 pub mod protocol_debugger;
+pub mod register_decoder;
+pub mod register_driver;
+pub mod register_map;
+pub mod scaffold;
+pub mod spi_decode;
+pub mod svd;
+pub mod symbolizer;
+#[cfg(test)]
+pub(crate) mod test_elf;
 pub mod timing_calculator;
+pub mod uart_decode;
 
 // Re-export all hardware tools
 pub use circuit_analyzer::CircuitAnalyzer;
 pub use datasheet_analyzer::DatasheetAnalyzer;
 pub use driver_generator::DriverGenerator;
-pub use pinout_mapper::PinoutMapper;
+pub use fault_decoder::FaultDecoder;
+pub use firmware_size::FirmwareSize;
+pub use flash_programmer::FlashProgrammer;
+pub use pinout_mapper::{PinoutCodegen, PinoutMapper};
 pub use protocol_debugger::ProtocolDebugger;
+pub use register_decoder::RegisterDecoder;
 pub use timing_calculator::TimingCalculator;
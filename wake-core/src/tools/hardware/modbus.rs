@@ -0,0 +1,1039 @@
+//! Modbus RTU, ASCII and TCP frame analysis.
+//!
+//! Frames come from hex dumps (one line per frame or a continuous stream,
+//! optionally prefixed with timestamps and TX/RX markers), `:`-prefixed
+//! ASCII lines, MBAP-framed TCP payloads, or UART bytes decoded from a logic
+//! capture. RTU frames are delimited by their CRC, so dumps that run frames
+//! together still split correctly; with a baud rate the 1.5- and
+//! 3.5-character silences that delimit frames on the wire are checked too.
+
+use super::netlist::format_si;
+use std::collections::HashMap;
+
+/// Largest RTU/ASCII ADU: address, 253-byte PDU and CRC
+const MAX_ADU: usize = 256;
+
+/// Values listed per timeline row
+const TIMELINE_VALUES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Rtu,
+    Ascii,
+    Tcp,
+}
+
+impl Mode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mode::Rtu => "Modbus RTU",
+            Mode::Ascii => "Modbus ASCII",
+            Mode::Tcp => "Modbus TCP",
+        }
+    }
+}
+
+/// RTU character timing at a baud rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    pub baud: f64,
+    /// Duration of one character in the line's frame format
+    pub char_time: f64,
+    /// Longest silence allowed inside a frame
+    pub t15: f64,
+    /// Shortest silence required between frames
+    pub t35: f64,
+}
+
+impl Timing {
+    /// The specification counts 11-bit characters and fixes t1.5/t3.5 at
+    /// 750 µs/1.75 ms above 19200 baud
+    pub fn new(baud: f64, frame_bits: u32) -> Self {
+        let (t15, t35) = if baud > 19200.0 {
+            (750e-6, 1.75e-3)
+        } else {
+            (1.5 * 11.0 / baud, 3.5 * 11.0 / baud)
+        };
+        Self {
+            baud,
+            char_time: frame_bits as f64 / baud,
+            t15,
+            t35,
+        }
+    }
+
+    fn chars(&self, time: f64) -> f64 {
+        time * self.baud / 11.0
+    }
+}
+
+/// Bytes received together: one hex-dump line, or a burst of UART characters
+/// without a silence longer than t1.5
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub time: Option<f64>,
+    pub end: Option<f64>,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dump {
+    pub mode: Mode,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub time: Option<f64>,
+    pub end: Option<f64>,
+    /// MBAP transaction identifier
+    pub transaction: Option<u16>,
+    pub unit: u8,
+    pub function: u8,
+    pub data: Vec<u8>,
+    /// CRC or LRC matched (always true for TCP)
+    pub checksum_ok: bool,
+}
+
+impl Frame {
+    pub fn is_exception(&self) -> bool {
+        self.function & 0x80 != 0
+    }
+
+    fn word(&self, index: usize) -> Option<u16> {
+        Some(u16::from_be_bytes([
+            *self.data.get(index)?,
+            *self.data.get(index + 1)?,
+        ]))
+    }
+}
+
+/// A request and its response; corrupt frames stand alone as requests
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub request: Option<usize>,
+    pub response: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    Checksum,
+    /// Less than t3.5 of silence before a frame
+    InterFrameGap,
+    /// More than t1.5 of silence inside a frame
+    IntraFrameGap,
+    IllegalFunction,
+    Exception,
+    Malformed,
+    NoResponse,
+    UnexpectedResponse,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    /// Index into `Decoded::frames`
+    pub frame: usize,
+    pub time: Option<f64>,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    pub mode: Mode,
+    pub timing: Option<Timing>,
+    pub frames: Vec<Frame>,
+    pub transactions: Vec<Transaction>,
+    pub issues: Vec<Issue>,
+}
+
+impl Decoded {
+    pub fn has(&self, kind: IssueKind) -> bool {
+        self.issues.iter().any(|i| i.kind == kind)
+    }
+}
+
+/// CRC-16/MODBUS; a frame including its CRC (low byte first) yields zero
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in bytes {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Two's complement of the byte sum
+pub fn lrc(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg()
+}
+
+pub fn function_name(function: u8) -> &'static str {
+    match function & 0x7F {
+        1 => "Read Coils",
+        2 => "Read Discrete Inputs",
+        3 => "Read Holding Registers",
+        4 => "Read Input Registers",
+        5 => "Write Single Coil",
+        6 => "Write Single Register",
+        7 => "Read Exception Status",
+        8 => "Diagnostics",
+        11 => "Get Comm Event Counter",
+        12 => "Get Comm Event Log",
+        15 => "Write Multiple Coils",
+        16 => "Write Multiple Registers",
+        17 => "Report Server ID",
+        20 => "Read File Record",
+        21 => "Write File Record",
+        22 => "Mask Write Register",
+        23 => "Read/Write Multiple Registers",
+        24 => "Read FIFO Queue",
+        43 => "Encapsulated Interface Transport",
+        65..=72 | 100..=110 => "User-Defined Function",
+        _ => "Unknown Function",
+    }
+}
+
+pub fn exception_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        1 => "illegal function",
+        2 => "illegal data address",
+        3 => "illegal data value",
+        4 => "server device failure",
+        5 => "acknowledge",
+        6 => "server device busy",
+        8 => "memory parity error",
+        0x0A => "gateway path unavailable",
+        0x0B => "gateway target device failed to respond",
+        _ => return None,
+    })
+}
+
+/// Seconds from `12.345`, `12.345s`, `[12.345]` or `10:30:12.345`
+fn timestamp(token: &str) -> Option<f64> {
+    let token = token
+        .trim_matches(|c| c == '[' || c == ']' || c == '(' || c == ')')
+        .trim_end_matches('s');
+    if !token.contains('.') || token.starts_with(':') {
+        return None;
+    }
+    token.split(':').try_fold(0.0, |total, part| {
+        Some(total * 60.0 + part.parse::<f64>().ok()?)
+    })
+}
+
+fn is_direction(token: &str) -> bool {
+    let word = token
+        .trim_matches(|c: char| "[]<>-:".contains(c))
+        .to_lowercase();
+    matches!(
+        word.as_str(),
+        "" | "tx" | "rx" | "in" | "out" | "req" | "res" | "resp" | "request" | "response"
+    )
+}
+
+fn hex_pairs(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parse a hex dump: one chunk per line, `:` lines as Modbus ASCII, and
+/// MBAP headers as Modbus TCP
+pub fn parse_dump(text: &str) -> Result<Dump, String> {
+    let mut chunks = Vec::new();
+    let mut ascii = false;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("//")
+            || line.starts_with(';')
+        {
+            continue;
+        }
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        let time = tokens.first().and_then(|t| timestamp(t));
+        if time.is_some() {
+            tokens.remove(0);
+        }
+        tokens.retain(|t| !is_direction(t));
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        let bytes =
+            if let Some(frame) = first.strip_prefix(':') {
+                ascii = true;
+                let hex: String = std::iter::once(frame)
+                    .chain(tokens[1..].iter().copied())
+                    .collect();
+                hex_pairs(&hex)
+                    .ok_or_else(|| format!("line {}: invalid Modbus ASCII frame", number + 1))?
+            } else {
+                let mut bytes = Vec::new();
+                for token in &tokens {
+                    bytes.extend(hex_pairs(token.trim_end_matches(',')).ok_or_else(|| {
+                        format!("line {}: invalid hex byte '{}'", number + 1, token)
+                    })?);
+                }
+                bytes
+            };
+        chunks.push(Chunk {
+            time,
+            end: None,
+            bytes,
+        });
+    }
+    if chunks.is_empty() {
+        return Err("no Modbus frames".to_string());
+    }
+
+    let mode = if ascii {
+        Mode::Ascii
+    } else if chunks.iter().all(is_mbap) {
+        Mode::Tcp
+    } else {
+        Mode::Rtu
+    };
+    Ok(Dump { mode, chunks })
+}
+
+/// Starts with an MBAP header whose length fits and no valid RTU CRC
+fn is_mbap(chunk: &Chunk) -> bool {
+    let bytes = &chunk.bytes;
+    bytes.len() >= 8
+        && bytes[2..4] == [0, 0]
+        && u16::from_be_bytes([bytes[4], bytes[5]]) as usize + 6 <= bytes.len()
+        && crc16(bytes) != 0
+}
+
+/// Group time-stamped UART bytes `(start time, line, value)` into chunks at
+/// silences longer than t1.5 or where the other line starts talking
+pub fn chunks(bytes: &[(f64, usize, u8)], timing: &Timing) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut last: Option<(f64, usize)> = None;
+    for &(time, line, value) in bytes {
+        let split = last.is_none_or(|(previous, previous_line)| {
+            line != previous_line || time - previous - timing.char_time > timing.t15
+        });
+        if split {
+            chunks.push(Chunk {
+                time: Some(time),
+                end: None,
+                bytes: Vec::new(),
+            });
+        }
+        if let Some(chunk) = chunks.last_mut() {
+            chunk.bytes.push(value);
+            chunk.end = Some(time + timing.char_time);
+        }
+        last = Some((time, line));
+    }
+    chunks
+}
+
+pub fn decode(mut chunks: Vec<Chunk>, mode: Mode, timing: Option<Timing>) -> Decoded {
+    if let Some(timing) = &timing {
+        for chunk in &mut chunks {
+            if let (Some(time), None) = (chunk.time, chunk.end) {
+                chunk.end = Some(time + chunk.bytes.len() as f64 * timing.char_time);
+            }
+        }
+    }
+    let mut issues = Vec::new();
+    let frames = match mode {
+        Mode::Rtu => rtu_frames(&chunks, timing.as_ref(), &mut issues),
+        Mode::Ascii => ascii_frames(&chunks, &mut issues),
+        Mode::Tcp => tcp_frames(&chunks, &mut issues),
+    };
+    let transactions = pair(&frames, mode, &mut issues);
+    issues.sort_by_key(|i| i.frame);
+    Decoded {
+        mode,
+        timing,
+        frames,
+        transactions,
+        issues,
+    }
+}
+
+fn issue(
+    issues: &mut Vec<Issue>,
+    frames: &[Frame],
+    frame: usize,
+    kind: IssueKind,
+    message: String,
+) {
+    issues.push(Issue {
+        frame,
+        time: frames.get(frame).and_then(|f| f.time),
+        kind,
+        message,
+    });
+}
+
+fn frame(time: Option<f64>, end: Option<f64>, payload: &[u8], checksum_ok: bool) -> Frame {
+    Frame {
+        time,
+        end,
+        transaction: None,
+        unit: payload[0],
+        function: payload[1],
+        data: payload[2..].to_vec(),
+        checksum_ok,
+    }
+}
+
+/// Shortest prefix of at least four bytes ending in a valid CRC
+fn crc_prefix(bytes: &[u8]) -> Option<usize> {
+    (4..=bytes.len().min(MAX_ADU)).find(|&n| crc16(&bytes[..n]) == 0)
+}
+
+fn rtu_frames(chunks: &[Chunk], timing: Option<&Timing>, issues: &mut Vec<Issue>) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = Vec::new();
+    // Pauses inside frames, and frames that follow another within one chunk
+    let mut pauses = Vec::new();
+    let mut run_together = Vec::new();
+    let mut rest = chunks.iter().peekable();
+    while let Some(chunk) = rest.next() {
+        let (time, mut end, mut bytes) = (chunk.time, chunk.end, chunk.bytes.clone());
+        let mut first = true;
+        while !bytes.is_empty() {
+            let n = match crc_prefix(&bytes) {
+                Some(n) => n,
+                None => {
+                    // A frame split by a pause continues in the next chunk
+                    let joined = rest.peek().and_then(|next| {
+                        let mut joined = bytes.clone();
+                        joined.extend(&next.bytes);
+                        crc_prefix(&joined)
+                            .filter(|n| *n > bytes.len())
+                            .map(|_| (joined, next.time, next.end))
+                    });
+                    if let Some((joined, next_time, next_end)) = joined {
+                        if let (Some(start), Some(stop)) = (next_time, end) {
+                            pauses.push((frames.len(), start - stop));
+                        }
+                        bytes = joined;
+                        end = next_end;
+                        rest.next();
+                        continue;
+                    }
+                    bytes.len()
+                }
+            };
+            let adu: Vec<u8> = bytes.drain(..n).collect();
+            if adu.len() < 4 {
+                issue(
+                    issues,
+                    &frames,
+                    frames.len(),
+                    IssueKind::Malformed,
+                    format!(
+                        "{}-byte fragment {:02X?} is too short for a frame",
+                        adu.len(),
+                        adu
+                    ),
+                );
+                continue;
+            }
+            if !first && time.is_some() {
+                run_together.push(frames.len());
+            }
+            let ok = crc16(&adu) == 0;
+            let end = if bytes.is_empty() { end } else { None };
+            frames.push(frame(time, end, &adu[..adu.len() - 2], ok));
+            if !ok {
+                let expected = crc16(&adu[..adu.len() - 2]);
+                issue(
+                    issues,
+                    &frames,
+                    frames.len() - 1,
+                    IssueKind::Checksum,
+                    format!(
+                        "CRC {:02X} {:02X} does not match the computed {:02X} {:02X}",
+                        adu[adu.len() - 2],
+                        adu[adu.len() - 1],
+                        expected & 0xFF,
+                        expected >> 8
+                    ),
+                );
+            }
+            first = false;
+        }
+    }
+
+    let Some(timing) = timing else {
+        return frames;
+    };
+    for (index, gap) in pauses.into_iter().filter(|(_, gap)| *gap > timing.t15) {
+        issue(
+                issues,
+                &frames,
+                index,
+                IssueKind::IntraFrameGap,
+                format!(
+                    "{} ({:.1} characters) of silence inside the frame; receivers discard frames with pauses over t1.5 = {}",
+                    format_si(gap, "s"),
+                    timing.chars(gap),
+                    format_si(timing.t15, "s")
+                ),
+            );
+    }
+    let gaps = (1..frames.len()).filter_map(|index| {
+        let (end, start) = (frames[index - 1].end?, frames[index].time?);
+        Some((index, start - end))
+    });
+    let short: Vec<(usize, f64)> = run_together
+        .into_iter()
+        .map(|index| (index, 0.0))
+        .chain(gaps)
+        .filter(|(_, gap)| *gap < timing.t35)
+        .collect();
+    for (index, gap) in short {
+        let silence = if gap < timing.char_time * 0.01 {
+            "No silence".to_string()
+        } else {
+            format!(
+                "Only {} ({:.1} characters) of silence",
+                format_si(gap, "s"),
+                timing.chars(gap)
+            )
+        };
+        issue(
+            issues,
+            &frames,
+            index,
+            IssueKind::InterFrameGap,
+            format!(
+                "{} before this frame; Modbus RTU needs t3.5 = {}",
+                silence,
+                format_si(timing.t35, "s")
+            ),
+        );
+    }
+    frames
+}
+
+fn ascii_frames(chunks: &[Chunk], issues: &mut Vec<Issue>) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    for chunk in chunks {
+        let bytes = &chunk.bytes;
+        if bytes.len() < 3 {
+            issue(
+                issues,
+                &frames,
+                frames.len(),
+                IssueKind::Malformed,
+                format!("{}-byte ASCII frame is too short", bytes.len()),
+            );
+            continue;
+        }
+        let (payload, checksum) = bytes.split_at(bytes.len() - 1);
+        let ok = lrc(payload) == checksum[0];
+        frames.push(frame(chunk.time, chunk.end, payload, ok));
+        if !ok {
+            issue(
+                issues,
+                &frames,
+                frames.len() - 1,
+                IssueKind::Checksum,
+                format!(
+                    "LRC {:02X} does not match the computed {:02X}",
+                    checksum[0],
+                    lrc(payload)
+                ),
+            );
+        }
+    }
+    frames
+}
+
+fn tcp_frames(chunks: &[Chunk], issues: &mut Vec<Issue>) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = Vec::new();
+    for chunk in chunks {
+        let mut bytes = &chunk.bytes[..];
+        while !bytes.is_empty() {
+            let length = bytes
+                .get(4..6)
+                .map(|l| u16::from_be_bytes([l[0], l[1]]) as usize);
+            let Some(length) = length.filter(|l| *l >= 2 && bytes.len() >= 6 + l) else {
+                issue(
+                    issues,
+                    &frames,
+                    frames.len(),
+                    IssueKind::Malformed,
+                    format!("{} bytes do not form an MBAP frame", bytes.len()),
+                );
+                break;
+            };
+            let mut frame = frame(chunk.time, chunk.end, &bytes[6..6 + length], true);
+            frame.transaction = Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+            if bytes[2..4] != [0, 0] {
+                issue(
+                    issues,
+                    &frames,
+                    frames.len(),
+                    IssueKind::Malformed,
+                    format!(
+                        "MBAP protocol identifier 0x{:02X}{:02X} is not 0 (Modbus)",
+                        bytes[2], bytes[3]
+                    ),
+                );
+            }
+            frames.push(frame);
+            bytes = &bytes[6 + length..];
+        }
+    }
+    frames
+}
+
+/// A valid response to `request` by its layout
+fn answers(request: &Frame, response: &Frame) -> bool {
+    if request.unit != response.unit || request.function != response.function & 0x7F {
+        return false;
+    }
+    if response.is_exception() {
+        return true;
+    }
+    match response.function {
+        1..=4 => response.data.first().map(|n| *n as usize + 1) == Some(response.data.len()),
+        5 | 6 | 15 | 16 => response.data.len() == 4,
+        _ => true,
+    }
+}
+
+fn pair(frames: &[Frame], mode: Mode, issues: &mut Vec<Issue>) -> Vec<Transaction> {
+    let mut transactions: Vec<Transaction> = Vec::new();
+    // Open requests by MBAP transaction ID (TCP) or the one outstanding request
+    let mut open: HashMap<Option<u16>, usize> = HashMap::new();
+    let unanswered = |t: &Transaction, issues: &mut Vec<Issue>| {
+        let Some(request) = t.request else { return };
+        let frame = &frames[request];
+        if frame.unit != 0 || mode == Mode::Tcp {
+            issue(
+                issues,
+                frames,
+                request,
+                IssueKind::NoResponse,
+                format!(
+                    "No response from unit {} to {}",
+                    frame.unit,
+                    function_name(frame.function)
+                ),
+            );
+        }
+    };
+
+    for (index, frame) in frames.iter().enumerate() {
+        if !frame.checksum_ok {
+            transactions.push(Transaction {
+                request: Some(index),
+                response: None,
+            });
+            continue;
+        }
+        let key = frame.transaction;
+        let pending = open.get(&key).copied().filter(|t| {
+            transactions[*t]
+                .request
+                .is_some_and(|r| answers(&frames[r], frame))
+        });
+        if let Some(t) = pending {
+            open.remove(&key);
+            transactions[t].response = Some(index);
+            check_response(frames, transactions[t], issues);
+        } else if frame.is_exception() {
+            issue(
+                issues,
+                frames,
+                index,
+                IssueKind::UnexpectedResponse,
+                format!(
+                    "Exception response from unit {} without a request",
+                    frame.unit
+                ),
+            );
+            transactions.push(Transaction {
+                request: None,
+                response: Some(index),
+            });
+            check_response(frames, transactions[transactions.len() - 1], issues);
+        } else {
+            if let Some(t) = open.remove(&key) {
+                unanswered(&transactions[t], issues);
+            }
+            open.insert(key, transactions.len());
+            transactions.push(Transaction {
+                request: Some(index),
+                response: None,
+            });
+            check_request(frames, index, issues);
+        }
+    }
+    // Requests still open when the trace ends may simply have been cut off
+    let last = frames.len().saturating_sub(1);
+    for t in open.into_values() {
+        if transactions[t].request != Some(last) {
+            unanswered(&transactions[t], issues);
+        }
+    }
+    transactions
+}
+
+fn check_request(frames: &[Frame], index: usize, issues: &mut Vec<Issue>) {
+    let frame = &frames[index];
+    let name = function_name(frame.function);
+    let mut malformed = |message: String| {
+        issue(
+            issues,
+            frames,
+            index,
+            IssueKind::Malformed,
+            format!("{}: {}", name, message),
+        )
+    };
+    let quantity = frame.word(2).unwrap_or(0) as usize;
+    match frame.function {
+        1..=4 if frame.data.len() != 4 => malformed(format!(
+            "request has {} data bytes instead of 4",
+            frame.data.len()
+        )),
+        1 | 2 if !(1..=2000).contains(&quantity) => {
+            malformed(format!("quantity {} outside 1-2000", quantity))
+        }
+        3 | 4 if !(1..=125).contains(&quantity) => {
+            malformed(format!("quantity {} outside 1-125", quantity))
+        }
+        5 | 6 if frame.data.len() != 4 => malformed(format!(
+            "request has {} data bytes instead of 4",
+            frame.data.len()
+        )),
+        5 if !matches!(frame.word(2), Some(0xFF00 | 0x0000)) => malformed(format!(
+            "coil value 0x{:04X} is neither 0xFF00 (ON) nor 0x0000 (OFF)",
+            quantity
+        )),
+        15 | 16 => {
+            let expected = if frame.function == 15 {
+                quantity.div_ceil(8)
+            } else {
+                quantity * 2
+            };
+            let count = frame.data.get(4).map_or(0, |c| *c as usize);
+            let limit = if frame.function == 15 { 1968 } else { 123 };
+            if !(1..=limit).contains(&quantity) {
+                malformed(format!("quantity {} outside 1-{}", quantity, limit));
+            } else if count != expected || frame.data.len() != 5 + count {
+                malformed(format!(
+                    "byte count {} with {} value bytes; {} items need {}",
+                    count,
+                    frame.data.len().saturating_sub(5),
+                    quantity,
+                    expected
+                ));
+            }
+        }
+        function if function_name(function) == "Unknown Function" => issue(
+            issues,
+            frames,
+            index,
+            IssueKind::IllegalFunction,
+            format!("Function code 0x{:02X} is not a Modbus function", function),
+        ),
+        _ => {}
+    }
+}
+
+fn check_response(frames: &[Frame], transaction: Transaction, issues: &mut Vec<Issue>) {
+    let Some(index) = transaction.response else {
+        return;
+    };
+    let frame = &frames[index];
+    let name = function_name(frame.function);
+    if frame.is_exception() {
+        let code = frame.data.first().copied().unwrap_or(0);
+        let (kind, reason) = match exception_name(code) {
+            Some(reason) => (IssueKind::Exception, reason.to_string()),
+            None => (
+                IssueKind::IllegalFunction,
+                "an unknown exception code".to_string(),
+            ),
+        };
+        issue(
+            issues,
+            frames,
+            index,
+            kind,
+            format!(
+                "Unit {} rejected {} with exception 0x{:02X} ({})",
+                frame.unit, name, code, reason
+            ),
+        );
+        return;
+    }
+    let Some(request) = transaction.request.map(|r| &frames[r]) else {
+        return;
+    };
+    let quantity = request.word(2).unwrap_or(0) as usize;
+    let expected = match frame.function {
+        1 | 2 => quantity.div_ceil(8),
+        3 | 4 => quantity * 2,
+        _ => return,
+    };
+    let count = frame.data.len().saturating_sub(1);
+    if count != expected {
+        issue(
+            issues,
+            frames,
+            index,
+            IssueKind::Malformed,
+            format!(
+                "{}: response carries {} bytes for {} requested items ({} expected)",
+                name, count, quantity, expected
+            ),
+        );
+    }
+}
+
+/// Up to `TIMELINE_VALUES` items, then an ellipsis
+fn list(items: impl Iterator<Item = String>) -> String {
+    let items: Vec<String> = items.collect();
+    let mut out = items
+        .iter()
+        .take(TIMELINE_VALUES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if items.len() > TIMELINE_VALUES {
+        out.push_str(" …");
+    }
+    out
+}
+
+fn bits(bytes: &[u8], count: usize) -> String {
+    list((0..count.min(bytes.len() * 8)).map(|i| (bytes[i / 8] >> (i % 8) & 1).to_string()))
+}
+
+fn registers(bytes: &[u8]) -> String {
+    list(
+        bytes
+            .chunks_exact(2)
+            .map(|w| u16::from_be_bytes([w[0], w[1]]).to_string()),
+    )
+}
+
+/// Address column and value column of a transaction
+fn describe(request: Option<&Frame>, response: Option<&Frame>) -> (String, String) {
+    let frame = request.or(response).expect("transaction has a frame");
+    let address = request
+        .and_then(|r| r.word(0))
+        .filter(|_| matches!(frame.function, 1..=6 | 15 | 16));
+    let quantity = request.and_then(|r| r.word(2)).unwrap_or(0) as usize;
+    let address = match (address, frame.function) {
+        (Some(a), 1..=4 | 15 | 16) => format!("0x{:04X} ×{}", a, quantity),
+        (Some(a), _) => format!("0x{:04X}", a),
+        (None, _) => "-".to_string(),
+    };
+    let values = match (frame.function, request, response) {
+        (_, _, Some(r)) if r.is_exception() => String::new(),
+        (1 | 2, _, Some(r)) => bits(r.data.get(1..).unwrap_or(&[]), quantity),
+        (3 | 4, _, Some(r)) => registers(r.data.get(1..).unwrap_or(&[])),
+        (5, Some(r), _) => match r.word(2) {
+            Some(0xFF00) => "ON".to_string(),
+            Some(0x0000) => "OFF".to_string(),
+            _ => "?".to_string(),
+        },
+        (6, Some(r), _) => r.word(2).map_or(String::new(), |v| v.to_string()),
+        (15, Some(r), _) => bits(r.data.get(5..).unwrap_or(&[]), quantity),
+        (16, Some(r), _) => registers(r.data.get(5..).unwrap_or(&[])),
+        (1..=4, _, None) => String::new(),
+        _ => list(frame.data.iter().map(|b| format!("{:02X}", b))),
+    };
+    (address, values)
+}
+
+/// Markdown request/response timeline
+pub fn timeline(decoded: &Decoded, limit: usize) -> String {
+    let mut out = String::from(
+        "| # | Time | Unit | Function | Address | Values | Result |\n\
+         |---|------|------|----------|---------|--------|--------|\n",
+    );
+    for (n, t) in decoded.transactions.iter().take(limit).enumerate() {
+        let request = t.request.map(|i| &decoded.frames[i]);
+        let response = t.response.map(|i| &decoded.frames[i]);
+        let frame = request.or(response).expect("transaction has a frame");
+        let time = frame.time.map_or("-".to_string(), |t| format_si(t, "s"));
+        let (address, values) = describe(request, response);
+        let result = match (request, response) {
+            (Some(r), _) if !r.checksum_ok => match decoded.mode {
+                Mode::Ascii => "LRC error".to_string(),
+                _ => "CRC error".to_string(),
+            },
+            (_, Some(r)) if r.is_exception() => {
+                let code = r.data.first().copied().unwrap_or(0);
+                format!(
+                    "Exception 0x{:02X} ({})",
+                    code,
+                    exception_name(code).unwrap_or("unknown")
+                )
+            }
+            (Some(r), None) if r.unit == 0 && decoded.mode != Mode::Tcp => "Broadcast".to_string(),
+            (Some(_), None) => "No response".to_string(),
+            (None, Some(_)) => "Response only".to_string(),
+            (Some(request), Some(response)) => match (request.end, response.time) {
+                (Some(end), Some(start)) => format!("OK in {}", format_si(start - end, "s")),
+                _ => "OK".to_string(),
+            },
+            (None, None) => String::new(),
+        };
+        out.push_str(&format!(
+            "| {} | {} | {} | {:02} {} | {} | {} | {} |\n",
+            n + 1,
+            time,
+            frame.unit,
+            frame.function & 0x7F,
+            function_name(frame.function),
+            address,
+            values,
+            result
+        ));
+    }
+    if decoded.transactions.len() > limit {
+        out.push_str(&format!(
+            "\n... {} more transactions\n",
+            decoded.transactions.len() - limit
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtu_dump_transactions() {
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]), 0x0BC4);
+        let dump = "# poll log\n\
+            0.000 TX: 01 03 00 00 00 02 C4 0B\n\
+            0.020 RX: 01 03 04 00 0A 00 14 DA 3E\n\
+            0.100 TX: 01 06 00 01 00 64 D9 E1\n\
+            0.200 TX: 01 03 00 00 00 02 C4 0B\n\
+            0.220 RX: 01 83 02 C0 F1\n\
+            0.300 TX: 11 03 00 6B 00 03 76 88\n\
+            0.400 TX: 01 29 C1 FE\n\
+            0.450 RX: 01 29 C1 FE\n";
+        let parsed = parse_dump(dump).unwrap();
+        assert_eq!(parsed.mode, Mode::Rtu);
+        let decoded = decode(parsed.chunks, parsed.mode, Some(Timing::new(9600.0, 11)));
+        assert_eq!(decoded.frames.len(), 8);
+        assert_eq!(decoded.transactions.len(), 5);
+        let kinds: Vec<IssueKind> = decoded.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                IssueKind::NoResponse,
+                IssueKind::Exception,
+                IssueKind::Checksum,
+                IssueKind::IllegalFunction
+            ]
+        );
+        assert!(decoded.issues[2].message.contains("computed 76 87"));
+
+        let table = timeline(&decoded, 50);
+        assert!(table.contains(
+            "| 1 | 0 s | 1 | 03 Read Holding Registers | 0x0000 ×2 | 10 20 | OK in 10.833 ms |"
+        ));
+        assert!(table.contains("| 06 Write Single Register | 0x0001 | 100 | No response |"));
+        assert!(table.contains("Exception 0x02 (illegal data address)"));
+        assert!(table.contains("| CRC error |"));
+
+        // Frames run together in one line still split on their CRCs
+        let stream = parse_dump("11100001000204000A0102C6F0 111000010002 1298").unwrap();
+        let decoded = decode(stream.chunks, Mode::Rtu, None);
+        assert_eq!(decoded.transactions.len(), 1);
+        assert!(decoded.issues.is_empty());
+        assert!(timeline(&decoded, 50).contains("| 0x0001 ×2 | 10 258 | OK |"));
+
+        assert!(parse_dump("01 03 zz").unwrap_err().contains("'zz'"));
+    }
+
+    #[test]
+    fn test_ascii_and_tcp() {
+        let ascii = parse_dump(":010300000002FA\r\n:01030\n").unwrap_err();
+        assert!(ascii.contains("line 2"));
+        let parsed = parse_dump(":010300000002FA\r\n:01030400 0A0014DA\r\n").unwrap();
+        assert_eq!(parsed.mode, Mode::Ascii);
+        let decoded = decode(parsed.chunks, parsed.mode, None);
+        assert_eq!(decoded.transactions.len(), 1);
+        assert!(decoded.issues.is_empty(), "{:?}", decoded.issues);
+
+        let tcp = "00 01 00 00 00 06 11 03 00 6B 00 03\n\
+            00 02 00 00 00 06 11 03 00 6B 00 03\n\
+            00 01 00 00 00 09 11 03 06 02 2B 00 00 00 64\n";
+        let parsed = parse_dump(tcp).unwrap();
+        assert_eq!(parsed.mode, Mode::Tcp);
+        let decoded = decode(parsed.chunks, parsed.mode, None);
+        assert_eq!(decoded.transactions[0].response, Some(2));
+        assert!(timeline(&decoded, 50).contains("| 555 0 100 | OK |"));
+        // Transaction 2 is last open but not the last frame
+        assert_eq!(decoded.issues.len(), 1);
+        assert_eq!(decoded.issues[0].kind, IssueKind::NoResponse);
+    }
+
+    #[test]
+    fn test_rtu_character_timing() {
+        let timing = Timing::new(9600.0, 10);
+        assert!((timing.t35 - 3.5 * 11.0 / 9600.0).abs() < 1e-12);
+        assert_eq!(Timing::new(115200.0, 10).t35, 1.75e-3);
+
+        let mut bytes = Vec::new();
+        let mut time = 0.0;
+        let mut send = |frame: &[u8], line: usize, pause_after: Option<usize>| {
+            for (i, b) in frame.iter().enumerate() {
+                bytes.push((time, line, *b));
+                time += timing.char_time;
+                if pause_after == Some(i) {
+                    time += 5.0 * timing.char_time;
+                }
+            }
+        };
+        send(
+            &[0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xC4, 0x0B],
+            0,
+            Some(3),
+        );
+        send(
+            &[0x01, 0x03, 0x04, 0x00, 0x0A, 0x00, 0x14, 0xDA, 0x3E],
+            1,
+            None,
+        );
+        let chunks = chunks(&bytes, &timing);
+        assert_eq!(chunks.len(), 3);
+        let decoded = decode(chunks, Mode::Rtu, Some(timing));
+        assert_eq!(decoded.frames.len(), 2);
+        let kinds: Vec<IssueKind> = decoded.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(kinds, [IssueKind::IntraFrameGap, IssueKind::InterFrameGap]);
+        assert!(decoded.issues[1]
+            .message
+            .starts_with("No silence before this frame"));
+    }
+}
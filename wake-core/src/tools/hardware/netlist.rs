@@ -0,0 +1,1766 @@
+//! SPICE-subset netlist parsing and modified nodal analysis (MNA).
+//!
+//! Supported elements (one per line, `*` comments, `.` directives are kept but not executed):
+//! - `Rname n1 n2 value`, `Cname n1 n2 value`, `Lname n1 n2 value`
+//! - `Vname n+ n- [DC] value [AC mag [phase]]`, `Iname n+ n- [DC] value [AC mag [phase]]`
+//! - `Dname anode cathode [LED|SCHOTTKY] [IS=..] [N=..] [VF=..]`
+//! - `Xname in+ in- out OPAMP` (ideal op-amp)
+//!
+//! `.ac dec|oct|lin points fstart fstop` sets the AC sweep and `.print ac v(node)` or
+//! `.probe v(node)` picks the output node.
+//!
+//! Node `0` (or `gnd`) is ground. Values accept SPICE suffixes (`f p n u m k meg g t`)
+//! and the `4k7` / `2R2` notation.
+
+use serde::Serialize;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// Thermal voltage at 300 K
+const VT: f64 = 0.025852;
+
+/// Conductance from every node to ground so floating nodes stay solvable
+const GMIN: f64 = 1e-12;
+
+const MAX_NEWTON_ITERATIONS: usize = 200;
+
+/// Share of a node's current that may flow through GMIN before its DC
+/// voltage counts as set by GMIN rather than the circuit
+const GMIN_DOMINANCE: f64 = 1e-2;
+
+/// Shockley diode parameters
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DiodeModel {
+    pub is: f64,
+    pub n: f64,
+}
+
+impl DiodeModel {
+    /// Forward voltage `VF` is taken at this current when computing IS
+    const VF_REFERENCE_CURRENT: f64 = 0.02;
+
+    fn from_params(model: Option<&str>, params: &[(String, f64)]) -> Self {
+        let mut diode = match model.map(|m| m.to_uppercase()) {
+            Some(m) if m == "LED" => DiodeModel::with_forward_voltage(2.0, 2.0),
+            Some(m) if m == "SCHOTTKY" => DiodeModel { is: 1e-6, n: 1.05 },
+            _ => DiodeModel { is: 1e-14, n: 1.0 },
+        };
+        let mut vf = None;
+        for (key, value) in params {
+            match key.as_str() {
+                "IS" => diode.is = *value,
+                "N" => diode.n = *value,
+                "VF" => vf = Some(*value),
+                _ => {}
+            }
+        }
+        if let Some(vf) = vf {
+            diode = DiodeModel::with_forward_voltage(vf, diode.n);
+        }
+        diode
+    }
+
+    fn with_forward_voltage(vf: f64, n: f64) -> Self {
+        DiodeModel {
+            is: Self::VF_REFERENCE_CURRENT / (vf / (n * VT)).exp(),
+            n,
+        }
+    }
+
+    /// Diode current and small-signal conductance at `vd`
+    pub fn evaluate(&self, vd: f64) -> (f64, f64) {
+        let nvt = self.n * VT;
+        let e = (vd / nvt).min(80.0).exp();
+        (self.is * (e - 1.0), self.is * e / nvt)
+    }
+
+    fn critical_voltage(&self) -> f64 {
+        let nvt = self.n * VT;
+        nvt * (nvt / (std::f64::consts::SQRT_2 * self.is)).ln()
+    }
+
+    /// SPICE `pnjlim`: damp large forward steps so Newton does not overflow
+    fn limit(&self, v_new: f64, v_old: f64) -> f64 {
+        let nvt = self.n * VT;
+        let v_crit = self.critical_voltage();
+        if v_new > v_crit && (v_new - v_old).abs() > 2.0 * nvt {
+            if v_old > 0.0 {
+                let arg = 1.0 + (v_new - v_old) / nvt;
+                if arg > 0.0 {
+                    v_old + nvt * arg.ln()
+                } else {
+                    v_crit
+                }
+            } else {
+                nvt * (v_new / nvt).ln()
+            }
+        } else {
+            v_new
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ElementKind {
+    Resistor {
+        ohms: f64,
+    },
+    Capacitor {
+        farads: f64,
+    },
+    Inductor {
+        henries: f64,
+    },
+    VoltageSource {
+        volts: f64,
+        ac_magnitude: f64,
+        ac_phase_deg: f64,
+    },
+    CurrentSource {
+        amps: f64,
+        ac_magnitude: f64,
+        ac_phase_deg: f64,
+    },
+    Diode {
+        model: DiodeModel,
+    },
+    OpAmp,
+}
+
+impl ElementKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ElementKind::Resistor { .. } => "resistor",
+            ElementKind::Capacitor { .. } => "capacitor",
+            ElementKind::Inductor { .. } => "inductor",
+            ElementKind::VoltageSource { .. } => "voltage source",
+            ElementKind::CurrentSource { .. } => "current source",
+            ElementKind::Diode { .. } => "diode",
+            ElementKind::OpAmp => "op-amp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Element {
+    pub name: String,
+    /// Node names in netlist order
+    pub nodes: Vec<String>,
+    #[serde(flatten)]
+    pub kind: ElementKind,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Netlist {
+    pub title: Option<String>,
+    pub elements: Vec<Element>,
+    /// Dot directives (`.op`, `.ac ...`) in order of appearance
+    pub directives: Vec<String>,
+    /// Non-ground node names in order of first appearance
+    pub nodes: Vec<String>,
+}
+
+pub fn is_ground(node: &str) -> bool {
+    node == "0" || node.eq_ignore_ascii_case("gnd")
+}
+
+/// Parse a SPICE value such as `4k7`, `100n`, `1meg`, `2.2uF`, `10mA` or `1e-3`
+pub fn parse_value(raw: &str) -> Result<f64, String> {
+    let s = raw.trim().to_lowercase();
+    let err = || format!("Invalid value '{}'", raw);
+
+    let num_end = s
+        .char_indices()
+        .find(|(i, c)| {
+            !(c.is_ascii_digit()
+                || *c == '.'
+                || ((*c == '-' || *c == '+') && (*i == 0 || s[..*i].ends_with('e')))
+                || (*c == 'e'
+                    && s[i + 1..]
+                        .chars()
+                        .next()
+                        .is_some_and(|n| n.is_ascii_digit() || n == '-' || n == '+')))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+    let (number, suffix) = s.split_at(num_end);
+    if number.is_empty() {
+        return Err(err());
+    }
+
+    let multiplier = |suffix: &str| -> Option<(f64, usize)> {
+        if suffix.starts_with("meg") {
+            return Some((1e6, 3));
+        }
+        let m = match suffix.chars().next()? {
+            'f' => 1e-15,
+            'p' => 1e-12,
+            'n' => 1e-9,
+            'u' | 'µ' => 1e-6,
+            'm' => 1e-3,
+            'k' => 1e3,
+            'g' => 1e9,
+            't' => 1e12,
+            'r' => 1.0,
+            _ => return None,
+        };
+        Some((m, suffix.chars().next()?.len_utf8()))
+    };
+
+    let base: f64 = number.parse().map_err(|_| err())?;
+    match multiplier(suffix) {
+        Some((m, len)) => {
+            // `4k7` style: digits after the multiplier are the fractional part
+            let rest = &suffix[len..];
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if !digits.is_empty() && !number.contains('.') && !number.contains('e') {
+                let value: f64 = format!("{}.{}", number, digits)
+                    .parse()
+                    .map_err(|_| err())?;
+                Ok(value * m)
+            } else {
+                Ok(base * m)
+            }
+        }
+        // Unit letters only (`5V`, `10ohm`)
+        None if suffix.chars().all(|c| c.is_alphabetic() || c == 'Ω') => Ok(base),
+        None => Err(err()),
+    }
+}
+
+impl Netlist {
+    pub fn parse(text: &str) -> Result<Netlist, String> {
+        let mut netlist = Netlist::default();
+
+        for (line_no, raw_line) in text.lines().enumerate() {
+            let line = raw_line.split(';').next().unwrap_or("").trim().to_string();
+            if line.is_empty() || line.starts_with('*') {
+                continue;
+            }
+            if line.starts_with('.') {
+                let directive = line.to_lowercase();
+                if directive.starts_with(".title") {
+                    netlist.title = Some(line[6..].trim().to_string());
+                } else if directive != ".end" {
+                    netlist.directives.push(line);
+                }
+                continue;
+            }
+
+            let element = Self::parse_element(&line)
+                .map_err(|e| format!("Line {}: {} ('{}')", line_no + 1, e, line))?;
+            for node in &element.nodes {
+                if !is_ground(node) && !netlist.nodes.contains(node) {
+                    netlist.nodes.push(node.clone());
+                }
+            }
+            if netlist.elements.iter().any(|e| e.name == element.name) {
+                return Err(format!(
+                    "Line {}: duplicate element name {}",
+                    line_no + 1,
+                    element.name
+                ));
+            }
+            netlist.elements.push(element);
+        }
+
+        if netlist.elements.is_empty() {
+            return Err("Netlist contains no elements".to_string());
+        }
+        let grounded = netlist
+            .elements
+            .iter()
+            .any(|e| e.nodes.iter().any(|n| is_ground(n)));
+        if !grounded {
+            return Err("Netlist has no ground node (0 or gnd)".to_string());
+        }
+
+        Ok(netlist)
+    }
+
+    fn parse_element(line: &str) -> Result<Element, String> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let name = tokens[0].to_string();
+        let prefix = name
+            .chars()
+            .next()
+            .map(|c| c.to_ascii_uppercase())
+            .unwrap_or(' ');
+
+        let require = |count: usize| {
+            if tokens.len() < count {
+                Err(format!("expected at least {} fields", count))
+            } else {
+                Ok(())
+            }
+        };
+        let two_nodes = || vec![tokens[1].to_string(), tokens[2].to_string()];
+
+        let (nodes, kind) = match prefix {
+            'R' | 'C' | 'L' => {
+                require(4)?;
+                let value = parse_value(tokens[3])?;
+                if value <= 0.0 {
+                    return Err("value must be positive".to_string());
+                }
+                let kind = match prefix {
+                    'R' => ElementKind::Resistor { ohms: value },
+                    'C' => ElementKind::Capacitor { farads: value },
+                    _ => ElementKind::Inductor { henries: value },
+                };
+                (two_nodes(), kind)
+            }
+            'V' => {
+                require(4)?;
+                let (volts, ac_magnitude, ac_phase_deg) = Self::parse_source(&tokens[3..])?;
+                (
+                    two_nodes(),
+                    ElementKind::VoltageSource {
+                        volts,
+                        ac_magnitude,
+                        ac_phase_deg,
+                    },
+                )
+            }
+            'I' => {
+                require(4)?;
+                let (amps, ac_magnitude, ac_phase_deg) = Self::parse_source(&tokens[3..])?;
+                (
+                    two_nodes(),
+                    ElementKind::CurrentSource {
+                        amps,
+                        ac_magnitude,
+                        ac_phase_deg,
+                    },
+                )
+            }
+            'D' => {
+                require(3)?;
+                let mut model = None;
+                let mut params = Vec::new();
+                for token in &tokens[3..] {
+                    match token.split_once('=') {
+                        Some((k, v)) => params.push((k.to_uppercase(), parse_value(v)?)),
+                        None => model = Some(*token),
+                    }
+                }
+                (
+                    two_nodes(),
+                    ElementKind::Diode {
+                        model: DiodeModel::from_params(model, &params),
+                    },
+                )
+            }
+            'X' | 'U' => {
+                require(5)?;
+                if !tokens[4].eq_ignore_ascii_case("opamp") {
+                    return Err(format!(
+                        "unsupported subcircuit '{}', only OPAMP is built in",
+                        tokens[4]
+                    ));
+                }
+                (
+                    vec![
+                        tokens[1].to_string(),
+                        tokens[2].to_string(),
+                        tokens[3].to_string(),
+                    ],
+                    ElementKind::OpAmp,
+                )
+            }
+            _ => return Err(format!("unsupported element type '{}'", prefix)),
+        };
+
+        Ok(Element { name, nodes, kind })
+    }
+
+    /// `[DC] value [AC mag [phase]]` -> (dc, ac magnitude, ac phase in degrees)
+    fn parse_source(tokens: &[&str]) -> Result<(f64, f64, f64), String> {
+        let mut dc = None;
+        let mut ac = (0.0, 0.0);
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = tokens[i];
+            if token.eq_ignore_ascii_case("dc") {
+                let value = tokens.get(i + 1).ok_or("missing DC value")?;
+                dc = Some(parse_value(value)?);
+                i += 2;
+            } else if token.eq_ignore_ascii_case("ac") {
+                // SPICE defaults the AC magnitude to 1
+                let magnitude = match tokens.get(i + 1) {
+                    Some(v) => parse_value(v)?,
+                    None => 1.0,
+                };
+                let phase = tokens.get(i + 2).and_then(|v| parse_value(v).ok());
+                ac = (magnitude, phase.unwrap_or(0.0));
+                i += if phase.is_some() { 3 } else { 2 };
+            } else if dc.is_none() {
+                dc = Some(parse_value(token)?);
+                i += 1;
+            } else {
+                return Err(format!("unexpected source field '{}'", token));
+            }
+        }
+        if dc.is_none() && ac.0 == 0.0 {
+            return Err("missing source value".to_string());
+        }
+        Ok((dc.unwrap_or(0.0), ac.0, ac.1))
+    }
+
+    /// Matrix index of a node, None for ground
+    pub fn node_index(&self, node: &str) -> Option<usize> {
+        if is_ground(node) {
+            None
+        } else {
+            self.nodes.iter().position(|n| n == node)
+        }
+    }
+}
+
+/// Current through and power absorbed by one element at the operating point
+#[derive(Clone, Debug, Serialize)]
+pub struct BranchResult {
+    pub name: String,
+    pub kind: &'static str,
+    pub nodes: Vec<String>,
+    /// Voltage across the first two nodes (output node for op-amps)
+    pub voltage: f64,
+    /// Current entering the first node and leaving the second (sourced current for op-amps)
+    pub current: f64,
+    /// Power absorbed; negative when the element delivers power. None for ideal op-amps
+    pub power: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DcSolution {
+    pub node_voltages: Vec<(String, f64)>,
+    pub branches: Vec<BranchResult>,
+    pub iterations: usize,
+}
+
+impl DcSolution {
+    pub fn voltage(&self, node: &str) -> Option<f64> {
+        if is_ground(node) {
+            return Some(0.0);
+        }
+        self.node_voltages
+            .iter()
+            .find(|(n, _)| n == node)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn branch(&self, name: &str) -> Option<&BranchResult> {
+        self.branches
+            .iter()
+            .find(|b| b.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Total power dissipated by passive elements and diodes
+    pub fn total_dissipation(&self) -> f64 {
+        self.branches
+            .iter()
+            .filter(|b| b.kind == "resistor" || b.kind == "diode")
+            .filter_map(|b| b.power)
+            .sum()
+    }
+}
+
+/// Complex number for small-signal analysis
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_polar(magnitude: f64, phase_rad: f64) -> Self {
+        Self::new(magnitude * phase_rad.cos(), magnitude * phase_rad.sin())
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, o: Complex) -> Complex {
+        Complex::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, o: Complex) -> Complex {
+        Complex::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, o: Complex) -> Complex {
+        Complex::new(
+            self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, o: Complex) -> Complex {
+        let d = o.re * o.re + o.im * o.im;
+        Complex::new(
+            (self.re * o.re + self.im * o.im) / d,
+            (self.im * o.re - self.re * o.im) / d,
+        )
+    }
+}
+
+/// Scalar type the MNA matrix is built from (f64 for DC, Complex for AC)
+trait Scalar:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+{
+    fn zero() -> Self;
+    fn real(value: f64) -> Self;
+    fn magnitude(&self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+    fn real(value: f64) -> Self {
+        value
+    }
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+}
+
+impl Scalar for Complex {
+    fn zero() -> Self {
+        Complex::default()
+    }
+    fn real(value: f64) -> Self {
+        Complex::new(value, 0.0)
+    }
+    fn magnitude(&self) -> f64 {
+        self.norm()
+    }
+}
+
+/// Dense linear system solved with partial pivoting
+fn solve_linear<T: Scalar>(mut a: Vec<Vec<T>>, mut b: Vec<T>) -> Result<Vec<T>, String> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].magnitude().total_cmp(&a[j][col].magnitude()))
+            .unwrap_or(col);
+        if a[pivot][col].magnitude() < 1e-18 {
+            return Err(
+                "Singular circuit matrix: check for voltage-source loops or op-amps without feedback"
+                    .to_string(),
+            );
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            if factor.magnitude() == 0.0 {
+                continue;
+            }
+            for (value, p) in a[row].iter_mut().zip(&pivot_row).skip(col) {
+                *value = *value - factor * *p;
+            }
+            b[row] = b[row] - factor * b[col];
+        }
+    }
+
+    let mut x = vec![T::zero(); n];
+    for row in (0..n).rev() {
+        let sum = (row + 1..n).fold(T::zero(), |acc, k| acc + a[row][k] * x[k]);
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Ok(x)
+}
+
+/// MNA matrix builder; ground rows/columns are dropped
+struct Mna<T: Scalar> {
+    g: Vec<Vec<T>>,
+    rhs: Vec<T>,
+}
+
+impl<T: Scalar> Mna<T> {
+    fn new(size: usize, node_count: usize) -> Self {
+        let mut g = vec![vec![T::zero(); size]; size];
+        for (n, row) in g.iter_mut().enumerate().take(node_count) {
+            row[n] = T::real(GMIN);
+        }
+        Self {
+            g,
+            rhs: vec![T::zero(); size],
+        }
+    }
+
+    fn add(&mut self, row: Option<usize>, col: Option<usize>, value: T) {
+        if let (Some(r), Some(c)) = (row, col) {
+            self.g[r][c] = self.g[r][c] + value;
+        }
+    }
+
+    fn add_rhs(&mut self, row: Option<usize>, value: T) {
+        if let Some(r) = row {
+            self.rhs[r] = self.rhs[r] + value;
+        }
+    }
+
+    fn admittance(&mut self, a: Option<usize>, b: Option<usize>, y: T) {
+        self.add(a, a, y);
+        self.add(b, b, y);
+        self.add(a, b, T::zero() - y);
+        self.add(b, a, T::zero() - y);
+    }
+
+    /// Current `i` injected from `a` into `b` through an ideal source
+    fn current_source(&mut self, a: Option<usize>, b: Option<usize>, i: T) {
+        self.add_rhs(a, T::zero() - i);
+        self.add_rhs(b, i);
+    }
+
+    /// Branch current variable `k` flowing from `a` to `b` with
+    /// V(a) - V(b) - impedance * I(k) = volts
+    fn voltage_branch(
+        &mut self,
+        a: Option<usize>,
+        b: Option<usize>,
+        k: usize,
+        volts: T,
+        impedance: T,
+    ) {
+        let one = T::real(1.0);
+        self.add(a, Some(k), one);
+        self.add(b, Some(k), T::zero() - one);
+        self.add(Some(k), a, one);
+        self.add(Some(k), b, T::zero() - one);
+        self.add(Some(k), Some(k), T::zero() - impedance);
+        self.rhs[k] = self.rhs[k] + volts;
+    }
+
+    /// Ideal op-amp: output current variable `k`, constraint V(in+) = V(in-)
+    fn op_amp(&mut self, inp: Option<usize>, inn: Option<usize>, out: Option<usize>, k: usize) {
+        let one = T::real(1.0);
+        self.add(out, Some(k), one);
+        self.add(Some(k), inp, one);
+        self.add(Some(k), inn, T::zero() - one);
+    }
+}
+
+/// Extra MNA unknowns: one current per voltage source, inductor and op-amp output
+fn branch_variables(netlist: &Netlist) -> (Vec<Option<usize>>, usize) {
+    let mut branch_vars = vec![None; netlist.elements.len()];
+    let mut size = netlist.nodes.len();
+    for (i, e) in netlist.elements.iter().enumerate() {
+        if matches!(
+            e.kind,
+            ElementKind::VoltageSource { .. } | ElementKind::Inductor { .. } | ElementKind::OpAmp
+        ) {
+            branch_vars[i] = Some(size);
+            size += 1;
+        }
+    }
+    (branch_vars, size)
+}
+
+/// Nodes without a DC path to ground: only capacitors, current sources or
+/// op-amp inputs connect them (and diodes unless `through_diodes`). Op-amp
+/// outputs are driven, and the virtual short ties the inputs together.
+fn floating_nodes(netlist: &Netlist, through_diodes: bool) -> Vec<String> {
+    let ground = netlist.nodes.len();
+    let idx = |node: &String| netlist.node_index(node).unwrap_or(ground);
+    let mut parent: Vec<usize> = (0..=ground).collect();
+    fn root(parent: &mut [usize], mut n: usize) -> usize {
+        while parent[n] != n {
+            parent[n] = parent[parent[n]];
+            n = parent[n];
+        }
+        n
+    }
+    let mut join = |a: usize, b: usize| {
+        let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+        parent[a] = b;
+    };
+    for e in &netlist.elements {
+        match e.kind {
+            ElementKind::Resistor { .. }
+            | ElementKind::Inductor { .. }
+            | ElementKind::VoltageSource { .. } => join(idx(&e.nodes[0]), idx(&e.nodes[1])),
+            ElementKind::Diode { .. } if through_diodes => join(idx(&e.nodes[0]), idx(&e.nodes[1])),
+            ElementKind::OpAmp => {
+                join(idx(&e.nodes[0]), idx(&e.nodes[1]));
+                join(idx(&e.nodes[2]), ground);
+            }
+            _ => {}
+        }
+    }
+    let ground = root(&mut parent, ground);
+    netlist
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(n, _)| root(&mut parent, *n) != ground)
+        .map(|(_, node)| node.clone())
+        .collect()
+}
+
+/// Solve the DC operating point: capacitors open, inductors shorted, diodes by Newton-Raphson.
+/// Nodes without a DC path to ground, or held only by GMIN (e.g. behind a
+/// reverse-biased diode), have no defined DC voltage and are an error.
+pub fn solve_dc(netlist: &Netlist) -> Result<DcSolution, String> {
+    let floating = floating_nodes(netlist, true);
+    if !floating.is_empty() {
+        return Err(format!(
+            "Floating node(s) {}: no DC path to ground, only capacitors or current sources connect them; add a DC path such as a bleed resistor",
+            floating.join(", ")
+        ));
+    }
+    let dc = solve_operating_point(netlist)?;
+
+    // Nodes that reach ground only through diodes, with the current each
+    // exchanges with the circuit against what GMIN carries
+    let behind_diodes = floating_nodes(netlist, false);
+    let mut through = vec![0.0; netlist.nodes.len()];
+    for b in &dc.branches {
+        let nodes = if b.kind == ElementKind::OpAmp.label() {
+            &b.nodes[2..]
+        } else {
+            &b.nodes[..2]
+        };
+        for n in nodes.iter().filter_map(|n| netlist.node_index(n)) {
+            through[n] += b.current.abs();
+        }
+    }
+    let held: Vec<&str> = dc
+        .node_voltages
+        .iter()
+        .zip(&through)
+        .filter(|((node, v), i)| {
+            behind_diodes.contains(node) && GMIN * v.abs() > GMIN_DOMINANCE * **i
+        })
+        .map(|((node, _), _)| node.as_str())
+        .collect();
+    if !held.is_empty() {
+        return Err(format!(
+            "Floating node(s) {}: the DC voltage is set by the {} GMIN leakage, not the circuit (e.g. behind a reverse-biased diode); add a DC path such as a bleed resistor",
+            held.join(", "),
+            format_si(GMIN, "S")
+        ));
+    }
+    Ok(dc)
+}
+
+/// DC operating point without the floating-node checks, as the bias point for
+/// AC analysis where DC-isolated nodes (capacitive dividers, AC coupling) are fine
+pub fn solve_operating_point(netlist: &Netlist) -> Result<DcSolution, String> {
+    let idx = |node: &String| netlist.node_index(node);
+    let (branch_vars, size) = branch_variables(netlist);
+
+    let diodes: Vec<usize> = netlist
+        .elements
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e.kind, ElementKind::Diode { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    let mut vd = vec![0.6; netlist.elements.len()];
+
+    let mut iterations = 0;
+    let x = loop {
+        iterations += 1;
+        let mut mna = Mna::<f64>::new(size, netlist.nodes.len());
+
+        for (i, e) in netlist.elements.iter().enumerate() {
+            let a = idx(&e.nodes[0]);
+            let b = idx(&e.nodes[1]);
+            match &e.kind {
+                ElementKind::Resistor { ohms } => mna.admittance(a, b, 1.0 / ohms),
+                ElementKind::Capacitor { .. } => {}
+                ElementKind::Inductor { .. } => {
+                    mna.voltage_branch(a, b, branch_vars[i].unwrap(), 0.0, 0.0)
+                }
+                ElementKind::VoltageSource { volts, .. } => {
+                    mna.voltage_branch(a, b, branch_vars[i].unwrap(), *volts, 0.0)
+                }
+                ElementKind::CurrentSource { amps, .. } => mna.current_source(a, b, *amps),
+                ElementKind::Diode { model } => {
+                    let (id, gd) = model.evaluate(vd[i]);
+                    mna.admittance(a, b, gd);
+                    mna.current_source(a, b, id - gd * vd[i]);
+                }
+                ElementKind::OpAmp => mna.op_amp(a, b, idx(&e.nodes[2]), branch_vars[i].unwrap()),
+            }
+        }
+
+        let x = solve_linear(mna.g, mna.rhs)?;
+        let voltage = |node: Option<usize>| node.map_or(0.0, |n| x[n]);
+
+        let mut converged = true;
+        for &i in &diodes {
+            let e = &netlist.elements[i];
+            let raw = voltage(idx(&e.nodes[0])) - voltage(idx(&e.nodes[1]));
+            if (raw - vd[i]).abs() > 1e-9 + 1e-6 * raw.abs() {
+                converged = false;
+            }
+            if let ElementKind::Diode { model } = &e.kind {
+                vd[i] = model.limit(raw, vd[i]);
+            }
+        }
+
+        if converged {
+            break x;
+        }
+        if iterations >= MAX_NEWTON_ITERATIONS {
+            return Err(format!(
+                "DC operating point did not converge after {} iterations",
+                MAX_NEWTON_ITERATIONS
+            ));
+        }
+    };
+
+    let voltage = |node: &String| idx(node).map_or(0.0, |n| x[n]);
+    let branches = netlist
+        .elements
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let v = voltage(&e.nodes[0]) - voltage(&e.nodes[1]);
+            let (voltage_out, current, power) = match &e.kind {
+                ElementKind::Resistor { ohms } => (v, v / ohms, Some(v * v / ohms)),
+                ElementKind::Capacitor { .. } => (v, 0.0, Some(0.0)),
+                ElementKind::Inductor { .. } => {
+                    let i_l = x[branch_vars[i].unwrap()];
+                    (v, i_l, Some(0.0))
+                }
+                ElementKind::VoltageSource { .. } => {
+                    let i_v = x[branch_vars[i].unwrap()];
+                    (v, i_v, Some(v * i_v))
+                }
+                ElementKind::CurrentSource { amps, .. } => (v, *amps, Some(v * amps)),
+                ElementKind::Diode { model } => {
+                    let (id, _) = model.evaluate(v);
+                    (v, id, Some(v * id))
+                }
+                ElementKind::OpAmp => {
+                    let i_out = -x[branch_vars[i].unwrap()];
+                    (voltage(&e.nodes[2]), i_out, None)
+                }
+            };
+            BranchResult {
+                name: e.name.clone(),
+                kind: e.kind.label(),
+                nodes: e.nodes.clone(),
+                voltage: voltage_out,
+                current,
+                power,
+            }
+        })
+        .collect();
+
+    Ok(DcSolution {
+        node_voltages: netlist
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), x[i]))
+            .collect(),
+        branches,
+        iterations,
+    })
+}
+
+/// Default sweep when the netlist has no `.ac` directive: 1 Hz - 10 MHz, 20 points/decade
+const DEFAULT_AC_SWEEP: (&str, usize, f64, f64) = ("dec", 20, 1.0, 10e6);
+
+/// Upper bound on sweep length to keep tool output bounded
+const MAX_AC_POINTS: usize = 10_000;
+
+/// Sweep frequencies from the `.ac` directive, or the default sweep
+pub fn ac_sweep(netlist: &Netlist) -> Result<Vec<f64>, String> {
+    let directive = netlist
+        .directives
+        .iter()
+        .find(|d| d.to_lowercase().starts_with(".ac"));
+    let (kind, points, start, stop) = match directive {
+        Some(d) => {
+            let tokens: Vec<&str> = d.split_whitespace().collect();
+            if tokens.len() < 5 {
+                return Err(format!(
+                    "Invalid '{}': expected .ac dec|oct|lin points fstart fstop",
+                    d
+                ));
+            }
+            let points = tokens[2]
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid .ac point count '{}'", tokens[2]))?;
+            (
+                tokens[1].to_lowercase(),
+                points,
+                parse_value(tokens[3])?,
+                parse_value(tokens[4])?,
+            )
+        }
+        None => {
+            let (kind, points, start, stop) = DEFAULT_AC_SWEEP;
+            (kind.to_string(), points, start, stop)
+        }
+    };
+
+    if points == 0 || start <= 0.0 || stop < start {
+        return Err("AC sweep needs points > 0 and 0 < fstart <= fstop".to_string());
+    }
+    let freqs: Vec<f64> = match kind.as_str() {
+        "dec" | "oct" => {
+            let base: f64 = if kind == "dec" { 10.0 } else { 2.0 };
+            let span = (stop / start).log(base);
+            let steps = (span * points as f64).round().max(1.0) as usize;
+            if steps >= MAX_AC_POINTS {
+                return Err(format!("AC sweep exceeds {} points", MAX_AC_POINTS));
+            }
+            (0..=steps)
+                .map(|i| start * (stop / start).powf(i as f64 / steps as f64))
+                .collect()
+        }
+        "lin" => {
+            if points > MAX_AC_POINTS {
+                return Err(format!("AC sweep exceeds {} points", MAX_AC_POINTS));
+            }
+            if points == 1 {
+                vec![start]
+            } else {
+                (0..points)
+                    .map(|i| start + (stop - start) * i as f64 / (points - 1) as f64)
+                    .collect()
+            }
+        }
+        other => return Err(format!("Unknown .ac sweep type '{}'", other)),
+    };
+    Ok(freqs)
+}
+
+/// Output node from `.print ac v(node)` / `.probe v(node)`, a node named
+/// out/vout/output, or the last node in the netlist
+pub fn ac_output_node(netlist: &Netlist) -> Result<String, String> {
+    for directive in &netlist.directives {
+        let lower = directive.to_lowercase();
+        if !(lower.starts_with(".print") || lower.starts_with(".probe")) {
+            continue;
+        }
+        let probe = directive.split_whitespace().skip(1).find_map(|t| {
+            t.split_once('(')
+                .map(|(_, rest)| rest.trim_end_matches(')'))
+        });
+        if let Some(node) = probe {
+            return netlist
+                .nodes
+                .iter()
+                .find(|n| n.eq_ignore_ascii_case(node))
+                .cloned()
+                .ok_or_else(|| format!("Output node '{}' not found in netlist", node));
+        }
+    }
+    ["out", "vout", "output"]
+        .iter()
+        .find_map(|name| netlist.nodes.iter().find(|n| n.eq_ignore_ascii_case(name)))
+        .or_else(|| netlist.nodes.last())
+        .cloned()
+        .ok_or_else(|| "Netlist has no output node".to_string())
+}
+
+/// One point of a small-signal frequency sweep
+#[derive(Clone, Debug, Serialize)]
+pub struct AcPoint {
+    pub freq_hz: f64,
+    pub magnitude: f64,
+    pub magnitude_db: f64,
+    /// Unwrapped phase in degrees
+    pub phase_deg: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AcResponse {
+    pub input_source: String,
+    pub output_node: String,
+    /// True when no source declared `AC`, so the first voltage source was driven with AC 1
+    pub implicit_input: bool,
+    /// V(output) relative to the input source's AC stimulus
+    pub points: Vec<AcPoint>,
+}
+
+/// Small-signal sweep around the DC operating point. Diodes are linearized
+/// at their DC bias, op-amps are ideal and DC-only sources are zeroed.
+pub fn solve_ac(
+    netlist: &Netlist,
+    dc: &DcSolution,
+    freqs: &[f64],
+    output_node: &str,
+) -> Result<AcResponse, String> {
+    let idx = |node: &String| netlist.node_index(node);
+    let (branch_vars, size) = branch_variables(netlist);
+    let out = netlist
+        .node_index(output_node)
+        .ok_or_else(|| format!("Output node '{}' not found in netlist", output_node))?;
+
+    let stimulus = |kind: &ElementKind| match kind {
+        ElementKind::VoltageSource {
+            ac_magnitude,
+            ac_phase_deg,
+            ..
+        }
+        | ElementKind::CurrentSource {
+            ac_magnitude,
+            ac_phase_deg,
+            ..
+        } if *ac_magnitude != 0.0 => Some(Complex::from_polar(
+            *ac_magnitude,
+            ac_phase_deg.to_radians(),
+        )),
+        _ => None,
+    };
+    let explicit = netlist
+        .elements
+        .iter()
+        .find(|e| stimulus(&e.kind).is_some());
+    let (input, implicit_input) = match explicit {
+        Some(e) => (e, false),
+        None => (
+            netlist
+                .elements
+                .iter()
+                .find(|e| matches!(e.kind, ElementKind::VoltageSource { .. }))
+                .ok_or("AC analysis needs a source with an AC value or a voltage source")?,
+            true,
+        ),
+    };
+    let drive = |e: &Element| {
+        if implicit_input && e.name == input.name {
+            Some(Complex::new(1.0, 0.0))
+        } else {
+            stimulus(&e.kind)
+        }
+    };
+    let reference = drive(input).unwrap_or_default();
+
+    // Small-signal diode conductances at the operating point
+    let gd: Vec<f64> = netlist
+        .elements
+        .iter()
+        .map(|e| match (&e.kind, dc.branch(&e.name)) {
+            (ElementKind::Diode { model }, Some(b)) => model.evaluate(b.voltage).1,
+            _ => 0.0,
+        })
+        .collect();
+
+    let mut points: Vec<AcPoint> = Vec::with_capacity(freqs.len());
+    for &freq in freqs {
+        let w = 2.0 * std::f64::consts::PI * freq;
+        let mut mna = Mna::<Complex>::new(size, netlist.nodes.len());
+        for (i, e) in netlist.elements.iter().enumerate() {
+            let a = idx(&e.nodes[0]);
+            let b = idx(&e.nodes[1]);
+            let source = drive(e).unwrap_or_default();
+            match &e.kind {
+                ElementKind::Resistor { ohms } => {
+                    mna.admittance(a, b, Complex::new(1.0 / ohms, 0.0))
+                }
+                ElementKind::Capacitor { farads } => {
+                    mna.admittance(a, b, Complex::new(0.0, w * farads))
+                }
+                ElementKind::Inductor { henries } => mna.voltage_branch(
+                    a,
+                    b,
+                    branch_vars[i].unwrap(),
+                    Complex::default(),
+                    Complex::new(0.0, w * henries),
+                ),
+                ElementKind::VoltageSource { .. } => {
+                    mna.voltage_branch(a, b, branch_vars[i].unwrap(), source, Complex::default())
+                }
+                ElementKind::CurrentSource { .. } => mna.current_source(a, b, source),
+                ElementKind::Diode { .. } => mna.admittance(a, b, Complex::new(gd[i], 0.0)),
+                ElementKind::OpAmp => mna.op_amp(a, b, idx(&e.nodes[2]), branch_vars[i].unwrap()),
+            }
+        }
+
+        let x = solve_linear(mna.g, mna.rhs)?;
+        let h = x[out] / reference;
+        let magnitude = h.norm();
+        let mut phase_deg = h.arg().to_degrees();
+        if let Some(prev) = points.last() {
+            phase_deg += 360.0 * ((prev.phase_deg - phase_deg) / 360.0).round();
+        }
+        points.push(AcPoint {
+            freq_hz: freq,
+            magnitude,
+            magnitude_db: 20.0 * magnitude.max(1e-30).log10(),
+            phase_deg,
+        });
+    }
+
+    Ok(AcResponse {
+        input_source: input.name.clone(),
+        output_node: output_node.to_string(),
+        implicit_input,
+        points,
+    })
+}
+
+/// Re-solves the sweep at a single frequency, as the probe for `characterize`
+pub fn ac_probe<'a>(
+    netlist: &'a Netlist,
+    dc: &'a DcSolution,
+    output_node: &'a str,
+) -> impl Fn(f64) -> Option<AcPoint> + 'a {
+    move |freq| {
+        solve_ac(netlist, dc, &[freq], output_node)
+            .ok()?
+            .points
+            .pop()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    BandStop,
+    Flat,
+}
+
+impl FilterType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FilterType::LowPass => "low-pass",
+            FilterType::HighPass => "high-pass",
+            FilterType::BandPass => "band-pass",
+            FilterType::BandStop => "band-stop",
+            FilterType::Flat => "flat",
+        }
+    }
+}
+
+/// Figures of merit extracted from a sweep
+#[derive(Clone, Debug, Serialize)]
+pub struct FilterCharacteristics {
+    pub filter_type: FilterType,
+    /// Reference level the half-power (-3 dB) points are measured from
+    pub passband_gain_db: f64,
+    pub cutoffs_hz: Vec<f64>,
+    pub center_hz: Option<f64>,
+    pub q: Option<f64>,
+    /// Frequency where the gain falls through 0 dB, for responses that start above it
+    pub unity_gain_hz: Option<f64>,
+    pub phase_margin_deg: Option<f64>,
+}
+
+/// Slope threshold separating a flat sweep end from a roll-off, in dB/decade
+const FLAT_SLOPE_DB_PER_DECADE: f64 = 3.0;
+
+/// Half-power level below the reference, 10·log10(2) ≈ 3.01 dB
+const HALF_POWER_DB: f64 = 3.010_299_956_639_812;
+
+/// Bisection steps when re-solving near a crossing or the peak; 40 halvings
+/// of a sweep step are far below the printed precision
+const REFINE_STEPS: usize = 40;
+
+/// Midpoint of two sweep frequencies, on a log scale when both are positive
+fn mid_freq(a: f64, b: f64) -> f64 {
+    if a > 0.0 && b > 0.0 {
+        (a * b).sqrt()
+    } else {
+        (a + b) / 2.0
+    }
+}
+
+/// `probe` result at `freq` with its phase unwrapped next to `near`
+fn probe_at(probe: &impl Fn(f64) -> Option<AcPoint>, freq: f64, near: &AcPoint) -> Option<AcPoint> {
+    let mut point = probe(freq)?;
+    point.phase_deg += 360.0 * ((near.phase_deg - point.phase_deg) / 360.0).round();
+    Some(point)
+}
+
+/// Narrow the bracket `a`..`b` around where `value` crosses `level` by
+/// re-solving at its midpoint; `None` when the probe fails
+fn refine_crossing(
+    a: &AcPoint,
+    b: &AcPoint,
+    level: f64,
+    value: fn(&AcPoint) -> f64,
+    probe: &impl Fn(f64) -> Option<AcPoint>,
+) -> Option<(AcPoint, AcPoint)> {
+    let (mut lo, mut hi) = (a.clone(), b.clone());
+    let below = value(&lo) < level;
+    for _ in 0..REFINE_STEPS {
+        let mid = probe_at(probe, mid_freq(lo.freq_hz, hi.freq_hz), &lo)?;
+        if (value(&mid) < level) == below {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some((lo, hi))
+}
+
+/// Frequency where the magnitude crosses `level` between `a` and `b`,
+/// re-solved when a probe is available
+fn refined_crossing(
+    a: &AcPoint,
+    b: &AcPoint,
+    level: f64,
+    probe: &impl Fn(f64) -> Option<AcPoint>,
+) -> f64 {
+    match refine_crossing(a, b, level, |p| p.magnitude_db, probe) {
+        Some((lo, hi)) => crossing(&lo, &hi, level),
+        None => crossing(a, b, level),
+    }
+}
+
+/// Maximum of the magnitude between the sweep points either side of
+/// `points[i]`, by golden-section search
+fn refine_peak(
+    points: &[AcPoint],
+    i: usize,
+    probe: &impl Fn(f64) -> Option<AcPoint>,
+) -> Option<AcPoint> {
+    if i == 0 || i + 1 >= points.len() || points[i - 1].freq_hz <= 0.0 {
+        return None;
+    }
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = (points[i - 1].freq_hz.ln(), points[i + 1].freq_hz.ln());
+    let at = |x: f64| probe_at(probe, x.exp(), &points[i]);
+    let mut c = at(b - ratio * (b - a))?;
+    let mut d = at(a + ratio * (b - a))?;
+    for _ in 0..REFINE_STEPS {
+        if c.magnitude_db > d.magnitude_db {
+            b = d.freq_hz.ln();
+            d = c;
+            c = at(b - ratio * (b - a))?;
+        } else {
+            a = c.freq_hz.ln();
+            c = d;
+            d = at(a + ratio * (b - a))?;
+        }
+    }
+    let best = if c.magnitude_db > d.magnitude_db {
+        c
+    } else {
+        d
+    };
+    (best.magnitude_db >= points[i].magnitude_db).then_some(best)
+}
+
+/// Interpolated frequency (log scale) where `level` is crossed between two points
+fn crossing(a: &AcPoint, b: &AcPoint, level: f64) -> f64 {
+    let t = (level - a.magnitude_db) / (b.magnitude_db - a.magnitude_db);
+    if a.freq_hz > 0.0 && b.freq_hz > 0.0 {
+        (a.freq_hz.ln() + t * (b.freq_hz / a.freq_hz).ln()).exp()
+    } else {
+        a.freq_hz + t * (b.freq_hz - a.freq_hz)
+    }
+}
+
+/// All frequencies where the magnitude crosses `level`, with the direction (true = falling)
+fn crossings(points: &[AcPoint], level: f64) -> Vec<(usize, f64, bool)> {
+    points
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| (w[0].magnitude_db - level) * (w[1].magnitude_db - level) < 0.0)
+        .map(|(i, w)| {
+            (
+                i,
+                crossing(&w[0], &w[1], level),
+                w[1].magnitude_db < w[0].magnitude_db,
+            )
+        })
+        .collect()
+}
+
+fn end_slope(a: &AcPoint, b: &AcPoint) -> f64 {
+    let decades = (b.freq_hz / a.freq_hz).log10();
+    if decades.abs() < f64::EPSILON || !decades.is_finite() {
+        0.0
+    } else {
+        (b.magnitude_db - a.magnitude_db) / decades
+    }
+}
+
+/// Classify the response and measure cutoff, Q and phase margin. `probe`
+/// re-solves the circuit at one frequency so the -3 dB points, the peak and
+/// the phase crossings are exact rather than interpolated between sweep
+/// points; a probe returning `None` keeps the interpolation.
+pub fn characterize(
+    points: &[AcPoint],
+    probe: impl Fn(f64) -> Option<AcPoint>,
+) -> FilterCharacteristics {
+    let mut result = FilterCharacteristics {
+        filter_type: FilterType::Flat,
+        passband_gain_db: points.first().map_or(0.0, |p| p.magnitude_db),
+        cutoffs_hz: Vec::new(),
+        center_hz: None,
+        q: None,
+        unity_gain_hz: None,
+        phase_margin_deg: None,
+    };
+    if points.len() < 3 {
+        return result;
+    }
+
+    let first = &points[0];
+    let last = &points[points.len() - 1];
+    let by_db = |a: &&AcPoint, b: &&AcPoint| a.magnitude_db.total_cmp(&b.magnitude_db);
+    let (peak_idx, peak) = points
+        .iter()
+        .enumerate()
+        .max_by(|a, b| by_db(&a.1, &b.1))
+        .unwrap();
+    let peak = &refine_peak(points, peak_idx, &probe).unwrap_or_else(|| peak.clone());
+    let (dip_idx, dip) = points
+        .iter()
+        .enumerate()
+        .min_by(|a, b| by_db(&a.1, &b.1))
+        .unwrap();
+
+    let low_slope = end_slope(&points[0], &points[1]);
+    let high_slope = end_slope(&points[points.len() - 2], last);
+    let rising = |s: f64| s > FLAT_SLOPE_DB_PER_DECADE;
+    let falling = |s: f64| s < -FLAT_SLOPE_DB_PER_DECADE;
+    let flat = |s: f64| !rising(s) && !falling(s);
+    let ends_db = (first.magnitude_db + last.magnitude_db) / 2.0;
+
+    let (filter_type, reference) = if rising(low_slope) && falling(high_slope) {
+        (FilterType::BandPass, peak.magnitude_db)
+    } else if flat(low_slope) && falling(high_slope) {
+        (FilterType::LowPass, first.magnitude_db)
+    } else if rising(low_slope) && flat(high_slope) {
+        (FilterType::HighPass, last.magnitude_db)
+    } else if dip.magnitude_db < first.magnitude_db.min(last.magnitude_db) - 3.0 {
+        (FilterType::BandStop, ends_db)
+    } else if peak.magnitude_db > first.magnitude_db.max(last.magnitude_db) + 3.0 {
+        (FilterType::BandPass, peak.magnitude_db)
+    } else if first.magnitude_db > last.magnitude_db + 3.0 {
+        (FilterType::LowPass, first.magnitude_db)
+    } else if last.magnitude_db > first.magnitude_db + 3.0 {
+        (FilterType::HighPass, last.magnitude_db)
+    } else {
+        (FilterType::Flat, ends_db)
+    };
+    result.filter_type = filter_type;
+    result.passband_gain_db = reference;
+
+    let level = reference - HALF_POWER_DB;
+    let cuts: Vec<(usize, f64, bool)> = crossings(points, level)
+        .into_iter()
+        .map(|(i, _, falling)| {
+            let freq = refined_crossing(&points[i], &points[i + 1], level, &probe);
+            (i, freq, falling)
+        })
+        .collect();
+    let around = |center: usize, want_falling_before: bool| {
+        let before = cuts
+            .iter()
+            .rev()
+            .find(|(i, _, f)| *i < center && *f == want_falling_before)
+            .map(|c| c.1);
+        let after = cuts
+            .iter()
+            .find(|(i, _, f)| *i >= center && *f != want_falling_before)
+            .map(|c| c.1);
+        (before, after)
+    };
+    match filter_type {
+        FilterType::LowPass => {
+            result.cutoffs_hz = cuts.iter().find(|c| c.2).map(|c| c.1).into_iter().collect();
+        }
+        FilterType::HighPass => {
+            result.cutoffs_hz = cuts
+                .iter()
+                .rev()
+                .find(|c| !c.2)
+                .map(|c| c.1)
+                .into_iter()
+                .collect();
+        }
+        FilterType::BandPass | FilterType::BandStop => {
+            let (center, falling_first) = if filter_type == FilterType::BandPass {
+                (peak_idx, false)
+            } else {
+                (dip_idx, true)
+            };
+            let (lower, upper) = around(center, falling_first);
+            result.cutoffs_hz = lower.into_iter().chain(upper).collect();
+            result.center_hz = Some(match (lower, upper) {
+                (Some(lo), Some(hi)) => {
+                    result.q = Some((lo * hi).sqrt() / (hi - lo));
+                    (lo * hi).sqrt()
+                }
+                _ if filter_type == FilterType::BandPass => peak.freq_hz,
+                _ => points[center].freq_hz,
+            });
+        }
+        FilterType::Flat => {}
+    }
+
+    // Second-order (and higher) low/high-pass: Q is the gain at the 90° phase point
+    // relative to the passband
+    if matches!(filter_type, FilterType::LowPass | FilterType::HighPass) {
+        // Asymptotic phase at the passband end: 0° or 180° for an inverting stage
+        let asymptote = |phase: f64| 180.0 * (phase / 180.0).round();
+        let (anchor, target) = if filter_type == FilterType::LowPass {
+            (first.phase_deg, asymptote(first.phase_deg) - 90.0)
+        } else {
+            (last.phase_deg, asymptote(last.phase_deg) + 90.0)
+        };
+        let span = points
+            .iter()
+            .map(|p| (p.phase_deg - anchor).abs())
+            .fold(0.0, f64::max);
+        if span > 135.0 {
+            let natural = points.windows(2).find_map(|w| {
+                let (a, b) = (w[0].phase_deg - target, w[1].phase_deg - target);
+                (a * b <= 0.0 && a != b).then(|| {
+                    let (lo, hi) = refine_crossing(&w[0], &w[1], target, |p| p.phase_deg, &probe)
+                        .unwrap_or_else(|| (w[0].clone(), w[1].clone()));
+                    let (a, b) = (lo.phase_deg - target, hi.phase_deg - target);
+                    let t = if a == b { 0.0 } else { a / (a - b) };
+                    lo.magnitude_db + t * (hi.magnitude_db - lo.magnitude_db)
+                })
+            });
+            result.q = natural.map(|db| 10f64.powf((db - reference) / 20.0));
+        }
+    }
+
+    // Loop-gain style responses: phase margin at the 0 dB crossing
+    if first.magnitude_db > 3.0 {
+        if let Some(w) = points
+            .windows(2)
+            .find(|w| w[0].magnitude_db >= 0.0 && w[1].magnitude_db < 0.0)
+        {
+            let (lo, hi) = refine_crossing(&w[0], &w[1], 0.0, |p| p.magnitude_db, &probe)
+                .unwrap_or_else(|| (w[0].clone(), w[1].clone()));
+            let freq = crossing(&lo, &hi, 0.0);
+            let t = lo.magnitude_db / (lo.magnitude_db - hi.magnitude_db);
+            let phase = lo.phase_deg + t * (hi.phase_deg - lo.phase_deg);
+            result.unity_gain_hz = Some(freq);
+            // Phase lag taken in (-360, 0]
+            let lag = phase - 360.0 * (phase / 360.0).ceil();
+            result.phase_margin_deg = Some(180.0 + lag);
+        }
+    }
+
+    result
+}
+
+/// Format a value with an SI prefix, e.g. `0.00123` + "A" -> "1.230 mA"
+pub fn format_si(value: f64, unit: &str) -> String {
+    if value == 0.0 || !value.is_finite() {
+        // -0.0 would print as "-0"
+        let value = if value == 0.0 { 0.0 } else { value };
+        return format!("{} {}", value, unit);
+    }
+    const PREFIXES: [(f64, &str); 9] = [
+        (1e12, "T"),
+        (1e9, "G"),
+        (1e6, "M"),
+        (1e3, "k"),
+        (1.0, ""),
+        (1e-3, "m"),
+        (1e-6, "µ"),
+        (1e-9, "n"),
+        (1e-12, "p"),
+    ];
+    let magnitude = value.abs();
+    let (scale, prefix) = PREFIXES
+        .iter()
+        .find(|(scale, _)| magnitude >= *scale * 0.9995)
+        .copied()
+        .unwrap_or((1e-15, "f"));
+    format!("{:.3} {}{}", value / scale, prefix, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} ± {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_close(parse_value("4k7").unwrap(), 4700.0, 1e-9);
+        assert_close(parse_value("100n").unwrap(), 100e-9, 1e-18);
+        assert_close(parse_value("1meg").unwrap(), 1e6, 1e-6);
+        assert_close(parse_value("2R2").unwrap(), 2.2, 1e-12);
+        assert_close(parse_value("2.2uF").unwrap(), 2.2e-6, 1e-15);
+        assert_close(parse_value("10mA").unwrap(), 0.01, 1e-12);
+        assert_close(parse_value("5V").unwrap(), 5.0, 1e-12);
+        assert_close(parse_value("1e-3").unwrap(), 1e-3, 1e-15);
+        assert_close(parse_value("-12").unwrap(), -12.0, 1e-12);
+        assert!(parse_value("abc").is_err());
+        assert!(parse_value("4x7").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Netlist::parse("* only a comment").is_err());
+        assert!(Netlist::parse("R1 a b 1k").is_err());
+        assert!(Netlist::parse("Q1 c b e 2N3904\nR1 c 0 1k").is_err());
+        assert!(Netlist::parse("R1 a 0 1k\nR1 a 0 2k").is_err());
+        let err = Netlist::parse("V1 in 0 5\nR1 in 0").unwrap_err();
+        assert!(err.starts_with("Line 2"));
+    }
+
+    #[test]
+    fn test_voltage_divider() {
+        let netlist =
+            Netlist::parse("V1 in 0 DC 12\nR1 in out 10k\nR2 out 0 4k7\n.op\n.end").unwrap();
+        assert_eq!(netlist.directives, vec![".op".to_string()]);
+        let dc = solve_dc(&netlist).unwrap();
+        assert_close(dc.voltage("out").unwrap(), 12.0 * 4700.0 / 14700.0, 1e-6);
+        let source = dc.branch("V1").unwrap();
+        assert_close(source.current, -12.0 / 14700.0, 1e-9);
+        assert!(source.power.unwrap() < 0.0);
+        assert_close(dc.total_dissipation(), 144.0 / 14700.0, 1e-9);
+    }
+
+    #[test]
+    fn test_current_source_and_inductor() {
+        let dc = solve_dc(&Netlist::parse("I1 0 a 1m\nL1 a b 10u\nR1 b 0 1k\nC1 a 0 1u").unwrap())
+            .unwrap();
+        assert_close(dc.voltage("a").unwrap(), 1.0, 1e-6);
+        assert_close(dc.branch("L1").unwrap().current, 1e-3, 1e-9);
+        assert_eq!(dc.branch("C1").unwrap().current, 0.0);
+    }
+
+    #[test]
+    fn test_led_resistor() {
+        let dc = solve_dc(&Netlist::parse("V1 vcc 0 5\nR1 vcc a 150\nD1 a 0 LED VF=2.0").unwrap())
+            .unwrap();
+        let led = dc.branch("D1").unwrap();
+        assert!(led.voltage > 1.9 && led.voltage < 2.0);
+        assert_close(led.current, (5.0 - led.voltage) / 150.0, 1e-6);
+        assert!(led.current > 0.019 && led.current < 0.021);
+    }
+
+    #[test]
+    fn test_silicon_diode_forward_drop() {
+        let dc = solve_dc(&Netlist::parse("V1 in 0 10\nR1 in a 1k\nD1 a 0").unwrap()).unwrap();
+        let vd = dc.voltage("a").unwrap();
+        assert!(vd > 0.6 && vd < 0.75, "vd = {}", vd);
+    }
+
+    #[test]
+    fn test_non_inverting_opamp() {
+        let dc = solve_dc(
+            &Netlist::parse("V1 in 0 1\nX1 in fb out OPAMP\nR1 out fb 9k\nR2 fb 0 1k\nRL out 0 1k")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_close(dc.voltage("out").unwrap(), 10.0, 1e-6);
+        assert_close(dc.branch("X1").unwrap().current, 10e-3 + 1e-3, 1e-9);
+    }
+
+    #[test]
+    fn test_floating_nodes() {
+        // Only a capacitor and a current source reach b and c
+        let err = solve_dc(&Netlist::parse("V1 a 0 5\nR1 a 0 1k\nC1 a b 1u\nI1 0 c 1m").unwrap())
+            .unwrap_err();
+        assert!(
+            err.starts_with("Floating node(s) b, c: no DC path"),
+            "{}",
+            err
+        );
+        // Behind a reverse-biased diode only GMIN sets the voltage
+        let err = solve_dc(&Netlist::parse("V1 a 0 5\nR1 a 0 1k\nD1 b a\nC1 b 0 1u").unwrap())
+            .unwrap_err();
+        assert!(
+            err.starts_with("Floating node(s) b: the DC voltage is set by"),
+            "{}",
+            err
+        );
+        // AC analysis still biases a capacitive divider
+        let divider = Netlist::parse("V1 in 0 AC 1\nC1 in a 1u\nC2 a 0 1u").unwrap();
+        assert!(solve_dc(&divider).is_err());
+        assert!(solve_operating_point(&divider).is_ok());
+    }
+
+    #[test]
+    fn test_voltage_source_loop_is_singular() {
+        let netlist = Netlist::parse("V1 a 0 5\nV2 a 0 3").unwrap();
+        assert!(solve_dc(&netlist).is_err());
+    }
+
+    #[test]
+    fn test_ac_source_syntax() {
+        let netlist = Netlist::parse(
+            "V1 in 0 DC 1.65 AC 1 90\nV2 a 0 AC\nI1 0 b 1m AC 0.5\nR1 in a 1k\nR2 a b 1k\nR3 b 0 1k",
+        )
+        .unwrap();
+        assert_eq!(
+            netlist.elements[0].kind,
+            ElementKind::VoltageSource {
+                volts: 1.65,
+                ac_magnitude: 1.0,
+                ac_phase_deg: 90.0
+            }
+        );
+        assert_eq!(
+            netlist.elements[1].kind,
+            ElementKind::VoltageSource {
+                volts: 0.0,
+                ac_magnitude: 1.0,
+                ac_phase_deg: 0.0
+            }
+        );
+        assert_eq!(
+            netlist.elements[2].kind,
+            ElementKind::CurrentSource {
+                amps: 1e-3,
+                ac_magnitude: 0.5,
+                ac_phase_deg: 0.0
+            }
+        );
+        assert!(Netlist::parse("V1 in 0 5 6\nR1 in 0 1k").is_err());
+    }
+
+    #[test]
+    fn test_ac_sweep_directive() {
+        let netlist = Netlist::parse("V1 in 0 AC 1\nR1 in out 1k\n.ac dec 10 10 10k").unwrap();
+        let freqs = ac_sweep(&netlist).unwrap();
+        assert_eq!(freqs.len(), 31);
+        assert_close(freqs[0], 10.0, 1e-9);
+        assert_close(freqs[30], 10e3, 1e-6);
+
+        let netlist = Netlist::parse("V1 in 0 AC 1\nR1 in out 1k\n.ac lin 5 100 500").unwrap();
+        assert_eq!(
+            ac_sweep(&netlist).unwrap(),
+            vec![100.0, 200.0, 300.0, 400.0, 500.0]
+        );
+    }
+
+    #[test]
+    fn test_rc_low_pass_cutoff() {
+        let netlist =
+            Netlist::parse("V1 in 0 DC 0 AC 1\nR1 in out 1k\nC1 out 0 100n\n.ac dec 50 1 1meg")
+                .unwrap();
+        let dc = solve_dc(&netlist).unwrap();
+        let output = ac_output_node(&netlist).unwrap();
+        let ac = solve_ac(&netlist, &dc, &ac_sweep(&netlist).unwrap(), &output).unwrap();
+        assert!(!ac.implicit_input);
+
+        let filter = characterize(&ac.points, ac_probe(&netlist, &dc, &output));
+        assert_eq!(filter.filter_type, FilterType::LowPass);
+        let expected = 1.0 / (2.0 * std::f64::consts::PI * 1e3 * 100e-9);
+        assert_close(filter.cutoffs_hz[0], expected, expected * 1e-5);
+        assert_close(filter.passband_gain_db, 0.0, 1e-3);
+        assert!(filter.q.is_none());
+        assert_close(ac.points.last().unwrap().phase_deg, -90.0, 0.5);
+
+        // The default 20 points/decade sweep lands between the sweep points
+        let netlist = Netlist::parse("V1 in 0 AC 1\nR1 in out 1k\nC1 out 0 160n").unwrap();
+        let dc = solve_dc(&netlist).unwrap();
+        let ac = solve_ac(&netlist, &dc, &ac_sweep(&netlist).unwrap(), "out").unwrap();
+        let expected = 1.0 / (2.0 * std::f64::consts::PI * 1e3 * 160e-9);
+        let filter = characterize(&ac.points, ac_probe(&netlist, &dc, "out"));
+        assert_close(filter.cutoffs_hz[0], expected, expected * 1e-5);
+    }
+
+    #[test]
+    fn test_rlc_band_pass_q() {
+        // f0 = 1 / (2π√(LC)) ≈ 5.03 kHz, Q = R⁻¹√(L/C) = 10
+        let netlist = Netlist::parse(
+            "V1 in 0 5\nL1 in mid 10m\nC1 mid out 100n\nR1 out 0 31.62\n.ac dec 200 100 1meg",
+        )
+        .unwrap();
+        let dc = solve_dc(&netlist).unwrap();
+        let ac = solve_ac(&netlist, &dc, &ac_sweep(&netlist).unwrap(), "out").unwrap();
+        assert!(ac.implicit_input);
+        assert_eq!(ac.input_source, "V1");
+
+        let filter = characterize(&ac.points, ac_probe(&netlist, &dc, "out"));
+        assert_eq!(filter.filter_type, FilterType::BandPass);
+        assert_close(filter.center_hz.unwrap(), 5032.9, 0.1);
+        assert_close(filter.q.unwrap(), 10.0, 1e-3);
+
+        // Same Q from the coarse default sweep
+        let netlist =
+            Netlist::parse("V1 in 0 5\nL1 in mid 10m\nC1 mid out 100n\nR1 out 0 31.62").unwrap();
+        let ac = solve_ac(&netlist, &dc, &ac_sweep(&netlist).unwrap(), "out").unwrap();
+        let filter = characterize(&ac.points, ac_probe(&netlist, &dc, "out"));
+        assert_close(filter.q.unwrap(), 10.0, 1e-3);
+    }
+
+    #[test]
+    fn test_sallen_key_q_and_probe() {
+        // Unity-gain Sallen-Key low-pass, Q = √(C1 C2 R1 R2) / (C2 (R1 + R2)) = 0.707
+        let netlist = Netlist::parse(
+            "V1 in 0 AC 1\nR1 in a 10k\nR2 a b 10k\nC1 a fb 22n\nC2 b 0 11n\n\
+             X1 b fb fb OPAMP\n.probe v(fb)\n.ac dec 100 10 100k",
+        )
+        .unwrap();
+        assert_eq!(ac_output_node(&netlist).unwrap(), "fb");
+        let dc = solve_dc(&netlist).unwrap();
+        let ac = solve_ac(&netlist, &dc, &ac_sweep(&netlist).unwrap(), "fb").unwrap();
+        let filter = characterize(&ac.points, ac_probe(&netlist, &dc, "fb"));
+        assert_eq!(filter.filter_type, FilterType::LowPass);
+        assert_close(filter.q.unwrap(), std::f64::consts::FRAC_1_SQRT_2, 1e-3);
+    }
+
+    #[test]
+    fn test_phase_margin_of_integrator_loop() {
+        // Gain of 100 with a single pole at 10 Hz crosses 0 dB near 1 kHz with ~90° margin
+        let response = |f: f64| {
+            let h = Complex::new(100.0, 0.0) / Complex::new(1.0, f / 10.0);
+            AcPoint {
+                freq_hz: f,
+                magnitude: h.norm(),
+                magnitude_db: 20.0 * h.norm().log10(),
+                phase_deg: h.arg().to_degrees(),
+            }
+        };
+        let points: Vec<AcPoint> = (0..=60)
+            .map(|i| response(10f64.powf(i as f64 / 10.0)))
+            .collect();
+        let filter = characterize(&points, |_| None);
+        assert_close(filter.unity_gain_hz.unwrap(), 1000.0, 20.0);
+        assert_close(filter.phase_margin_deg.unwrap(), 90.6, 0.5);
+        // |H| = 1 at f = 10·√(100² - 1)
+        let filter = characterize(&points, |f| Some(response(f)));
+        assert_close(filter.unity_gain_hz.unwrap(), 999.95, 0.01);
+        assert_close(filter.phase_margin_deg.unwrap(), 90.573, 1e-3);
+    }
+
+    #[test]
+    fn test_format_si() {
+        assert_eq!(format_si(0.00123, "A"), "1.230 mA");
+        assert_eq!(format_si(4700.0, "Ω"), "4.700 kΩ");
+        assert_eq!(format_si(-2.5, "W"), "-2.500 W");
+        assert_eq!(format_si(-0.0, "V"), "0 V");
+    }
+}
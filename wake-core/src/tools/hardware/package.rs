@@ -0,0 +1,158 @@
+//! Generated file packages.
+//!
+//! Generator tools build a list of files relative to an output directory;
+//! this module previews them as a file tree with diffs against what is on
+//! disk, and writes them once the user has approved.
+
+use serde::Serialize;
+use similar::TextDiff;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PackageFile {
+    /// Path relative to the package directory, `/`-separated
+    pub path: String,
+    pub content: String,
+}
+
+impl PackageFile {
+    pub fn new(path: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    New,
+    Modified(String),
+    Unchanged,
+}
+
+impl FileStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileStatus::New => "new",
+            FileStatus::Modified(_) => "modified",
+            FileStatus::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// Compare a file with what is on disk; `Modified` carries the old content
+pub fn status(dir: &Path, file: &PackageFile) -> FileStatus {
+    match std::fs::read_to_string(dir.join(&file.path)) {
+        Ok(existing) if existing == file.content => FileStatus::Unchanged,
+        Ok(existing) => FileStatus::Modified(existing),
+        Err(_) => FileStatus::New,
+    }
+}
+
+/// Indented file tree of the package, each file tagged new/modified/unchanged
+pub fn tree(dir: &Path, files: &[PackageFile]) -> String {
+    let mut paths: Vec<&PackageFile> = files.iter().collect();
+    paths.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut out = format!("{}/\n", dir.display().to_string().trim_end_matches('/'));
+    let mut shown: Vec<String> = Vec::new();
+    for file in paths {
+        let parts: Vec<&str> = file.path.split('/').collect();
+        for depth in 0..parts.len() - 1 {
+            let directory = parts[..=depth].join("/");
+            if !shown.contains(&directory) {
+                out.push_str(&format!("{}{}/\n", "  ".repeat(depth + 1), parts[depth]));
+                shown.push(directory);
+            }
+        }
+        out.push_str(&format!(
+            "{}{} ({}, {} lines)\n",
+            "  ".repeat(parts.len()),
+            parts[parts.len() - 1],
+            status(dir, file).label(),
+            file.content.lines().count()
+        ));
+    }
+    out
+}
+
+/// File tree followed by each new file's content and a unified diff for each modified file
+pub fn preview(dir: &Path, files: &[PackageFile]) -> String {
+    let mut out = tree(dir, files);
+    for file in files {
+        match status(dir, file) {
+            FileStatus::New => {
+                out.push_str(&format!("\n--- /dev/null\n+++ {}\n", file.path));
+                out.push_str(&file.content);
+                if !file.content.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            FileStatus::Modified(existing) => {
+                out.push('\n');
+                out.push_str(
+                    &TextDiff::from_lines(&existing, &file.content)
+                        .unified_diff()
+                        .context_radius(3)
+                        .header(&format!("a/{}", file.path), &format!("b/{}", file.path))
+                        .to_string(),
+                );
+            }
+            FileStatus::Unchanged => {}
+        }
+    }
+    out
+}
+
+/// Write the package under `dir`, returning the written paths
+pub fn write(dir: &Path, files: &[PackageFile]) -> Result<Vec<String>, String> {
+    files
+        .iter()
+        .map(|file| {
+            let path = dir.join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            std::fs::write(&path, &file.content)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Ok(path.display().to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_preview_and_write() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![
+            PackageFile::new("src/lib.rs", "fn a() {}\nfn b() {}\n"),
+            PackageFile::new("Cargo.toml", "[package]\n"),
+            PackageFile::new("examples/basic.rs", "fn main() {}\n"),
+        ];
+        let tree = tree(dir.path(), &files);
+        assert!(tree.ends_with(
+            "  Cargo.toml (new, 1 lines)\n  examples/\n    basic.rs (new, 1 lines)\n  src/\n    lib.rs (new, 2 lines)\n"
+        ));
+
+        let written = write(dir.path(), &files).unwrap();
+        assert_eq!(written.len(), 3);
+        assert!(dir.path().join("examples/basic.rs").exists());
+
+        let changed = vec![
+            PackageFile::new("src/lib.rs", "fn a() {}\nfn c() {}\n"),
+            PackageFile::new("Cargo.toml", "[package]\n"),
+        ];
+        let preview = preview(dir.path(), &changed);
+        assert!(preview.contains("lib.rs (modified, 2 lines)"));
+        assert!(preview.contains("Cargo.toml (unchanged, 1 lines)"));
+        assert!(preview.contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+        assert!(preview.contains("-fn b() {}\n+fn c() {}\n"));
+        assert!(!preview.contains("+++ Cargo.toml"));
+    }
+}
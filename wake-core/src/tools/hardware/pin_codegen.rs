@@ -0,0 +1,959 @@
+//! Pin-mux initialization code from a solved pin assignment.
+//!
+//! Emits STM32 HAL C (`MX_GPIO_Init`), embassy-stm32 Rust, ESP-IDF C and
+//! Zephyr devicetree overlays. Signals left on `*` (GPIO matrix / PSEL
+//! routing without a fixed pin) produce `#error` lines so the output does not
+//! build until a pin is chosen.
+
+use super::pin_database::{Family, PinFunction, ANY_PIN};
+use super::pin_solver::Assignment;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodegenFormat {
+    StmHal,
+    Embassy,
+    EspIdf,
+    Zephyr,
+}
+
+impl CodegenFormat {
+    pub fn all() -> [CodegenFormat; 4] {
+        [
+            CodegenFormat::StmHal,
+            CodegenFormat::Embassy,
+            CodegenFormat::EspIdf,
+            CodegenFormat::Zephyr,
+        ]
+    }
+
+    pub fn parse(s: &str) -> Option<CodegenFormat> {
+        match s.trim().to_lowercase().as_str() {
+            "stm32-hal" | "hal" | "stm32" | "c" | "cubemx" => Some(CodegenFormat::StmHal),
+            "embassy" | "embassy-stm32" | "rust" => Some(CodegenFormat::Embassy),
+            "esp-idf" | "espidf" | "idf" => Some(CodegenFormat::EspIdf),
+            "zephyr" | "overlay" | "devicetree" | "dts" => Some(CodegenFormat::Zephyr),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CodegenFormat::StmHal => "stm32-hal",
+            CodegenFormat::Embassy => "embassy",
+            CodegenFormat::EspIdf => "esp-idf",
+            CodegenFormat::Zephyr => "zephyr",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            CodegenFormat::StmHal => "gpio_init.c",
+            CodegenFormat::Embassy => "pins.rs",
+            CodegenFormat::EspIdf => "board_pins.c",
+            CodegenFormat::Zephyr => "app.overlay",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GeneratedFile {
+    pub path: String,
+    pub format: String,
+    pub content: String,
+}
+
+/// Assigned signal split into instance, function and peripheral class
+struct Signal<'a> {
+    assignment: &'a Assignment,
+    instance: String,
+    function: String,
+    class: String,
+}
+
+impl Signal<'_> {
+    fn pin(&self) -> &str {
+        &self.assignment.pin
+    }
+
+    fn routed(&self) -> bool {
+        self.assignment.pin != ANY_PIN
+    }
+
+    fn lower(&self) -> String {
+        self.assignment.signal.to_lowercase()
+    }
+}
+
+fn signals(assignment: &[Assignment]) -> Vec<Signal<'_>> {
+    assignment
+        .iter()
+        .filter(|a| !a.signal.starts_with("SYS_"))
+        .map(|a| {
+            let f = PinFunction {
+                pin: a.pin.clone(),
+                signal: a.signal.clone(),
+                af: a.af,
+            };
+            Signal {
+                assignment: a,
+                instance: f.instance().to_string(),
+                function: f.function().to_string(),
+                class: f.class(),
+            }
+        })
+        .collect()
+}
+
+/// Signals grouped by peripheral instance, in assignment order
+fn by_instance<'s, 'a>(signals: &'s [Signal<'a>]) -> Vec<(&'s str, Vec<&'s Signal<'a>>)> {
+    let mut groups: Vec<(&str, Vec<&Signal>)> = Vec::new();
+    for s in signals {
+        match groups.iter_mut().find(|(i, _)| *i == s.instance) {
+            Some((_, group)) => group.push(s),
+            None => groups.push((&s.instance, vec![s])),
+        }
+    }
+    groups
+}
+
+fn unrouted_error(s: &Signal, prefix: &str) -> String {
+    format!(
+        "#error \"Choose a pin for {}: fix it in the requirements, e.g. {}={}\"\n",
+        s.assignment.signal, s.assignment.signal, prefix
+    )
+}
+
+/// `PA9` -> ('A', 9)
+fn stm32_pin(pin: &str) -> Option<(char, u32)> {
+    let rest = pin.strip_prefix('P')?;
+    let mut chars = rest.chars();
+    let port = chars.next().filter(char::is_ascii_uppercase)?;
+    Some((port, chars.as_str().parse().ok()?))
+}
+
+/// `GPIO17` -> 17
+fn gpio_number(pin: &str) -> Option<u32> {
+    pin.strip_prefix("GPIO")?.parse().ok()
+}
+
+/// `P1.05` -> (1, 5)
+fn nrf_pin(pin: &str) -> Option<(u32, u32)> {
+    let (port, pin) = pin.strip_prefix('P')?.split_once('.')?;
+    Some((port.parse().ok()?, pin.parse().ok()?))
+}
+
+fn is_stm32(family: &Family, signals: &[Signal]) -> bool {
+    family.name.to_uppercase().starts_with("STM32")
+        && signals
+            .iter()
+            .all(|s| !s.routed() || stm32_pin(s.pin()).is_some())
+}
+
+fn header(device: &str, format: CodegenFormat) -> String {
+    format!(
+        "Pin setup for {} ({}), generated by pinout_mapper",
+        device,
+        format.label()
+    )
+}
+
+fn stm_hal(device: &str, family: &Family, signals: &[Signal]) -> Result<String, String> {
+    if !is_stm32(family, signals) {
+        return Err(format!(
+            "stm32-hal output needs an STM32 pin assignment, not {}",
+            family.name
+        ));
+    }
+    let mut out = format!(
+        "/* {} */\n\n#include \"main.h\"\n\nvoid MX_GPIO_Init(void)\n{{\n  GPIO_InitTypeDef GPIO_InitStruct = {{0}};\n\n",
+        header(device, CodegenFormat::StmHal)
+    );
+
+    let mut ports: Vec<char> = signals
+        .iter()
+        .filter_map(|s| stm32_pin(s.pin()))
+        .map(|(port, _)| port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    for port in &ports {
+        out.push_str(&format!("  __HAL_RCC_GPIO{}_CLK_ENABLE();\n", port));
+    }
+
+    // One HAL_GPIO_Init per port and identical configuration, like CubeMX
+    struct Block {
+        port: char,
+        pins: Vec<u32>,
+        mode: &'static str,
+        pull: &'static str,
+        speed: &'static str,
+        alternate: Option<String>,
+        comment: Vec<String>,
+    }
+    let mut blocks: Vec<Block> = Vec::new();
+    for s in signals {
+        let (port, number) = stm32_pin(s.pin()).expect("checked by is_stm32");
+        let (mode, pull, speed) = match s.class.as_str() {
+            "I2C" => (
+                "GPIO_MODE_AF_OD",
+                "GPIO_NOPULL",
+                "GPIO_SPEED_FREQ_VERY_HIGH",
+            ),
+            "ADC" => ("GPIO_MODE_ANALOG", "GPIO_NOPULL", ""),
+            "GPIO" => ("GPIO_MODE_OUTPUT_PP", "GPIO_NOPULL", "GPIO_SPEED_FREQ_LOW"),
+            "PWM" => ("GPIO_MODE_AF_PP", "GPIO_NOPULL", "GPIO_SPEED_FREQ_LOW"),
+            _ => (
+                "GPIO_MODE_AF_PP",
+                "GPIO_NOPULL",
+                "GPIO_SPEED_FREQ_VERY_HIGH",
+            ),
+        };
+        let alternate = match (mode, s.assignment.af) {
+            ("GPIO_MODE_AF_PP" | "GPIO_MODE_AF_OD", Some(af)) => {
+                let instance = match s.instance.as_str() {
+                    "USB" => "OTG_FS",
+                    other => other,
+                };
+                Some(format!("GPIO_AF{}_{}", af, instance))
+            }
+            _ => None,
+        };
+        let comment = format!("{} {}", s.pin(), s.assignment.signal);
+        match blocks.iter_mut().find(|b| {
+            b.port == port && b.mode == mode && b.pull == pull && b.alternate == alternate
+        }) {
+            Some(block) => {
+                block.pins.push(number);
+                block.comment.push(comment);
+            }
+            None => blocks.push(Block {
+                port,
+                pins: vec![number],
+                mode,
+                pull,
+                speed,
+                alternate,
+                comment: vec![comment],
+            }),
+        }
+    }
+
+    for block in &blocks {
+        let pins = block
+            .pins
+            .iter()
+            .map(|n| format!("GPIO_PIN_{}", n))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        out.push_str(&format!("\n  /* {} */\n", block.comment.join(", ")));
+        if block.mode == "GPIO_MODE_OUTPUT_PP" {
+            out.push_str(&format!(
+                "  HAL_GPIO_WritePin(GPIO{}, {}, GPIO_PIN_RESET);\n",
+                block.port, pins
+            ));
+        }
+        out.push_str(&format!("  GPIO_InitStruct.Pin = {};\n", pins));
+        out.push_str(&format!("  GPIO_InitStruct.Mode = {};\n", block.mode));
+        out.push_str(&format!("  GPIO_InitStruct.Pull = {};\n", block.pull));
+        if !block.speed.is_empty() {
+            out.push_str(&format!("  GPIO_InitStruct.Speed = {};\n", block.speed));
+        }
+        if let Some(alternate) = &block.alternate {
+            out.push_str(&format!("  GPIO_InitStruct.Alternate = {};\n", alternate));
+        }
+        out.push_str(&format!(
+            "  HAL_GPIO_Init(GPIO{}, &GPIO_InitStruct);\n",
+            block.port
+        ));
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn embassy(device: &str, family: &Family, signals: &[Signal]) -> Result<String, String> {
+    if !is_stm32(family, signals) {
+        return Err(format!(
+            "embassy output supports STM32 pin assignments (embassy-stm32), not {}",
+            family.name
+        ));
+    }
+    let groups = by_instance(signals);
+    let has = |class: &str| signals.iter().any(|s| s.class == class);
+    let mut uses = vec![];
+    if has("ADC") {
+        uses.push("use embassy_stm32::adc::Adc;".to_string());
+    }
+    let mut gpio = Vec::new();
+    if has("GPIO") {
+        gpio.extend(["Level", "Output", "Speed"]);
+    }
+    if has("PWM") {
+        gpio.push("OutputType");
+    }
+    gpio.sort_unstable();
+    if !gpio.is_empty() {
+        uses.push(format!("use embassy_stm32::gpio::{{{}}};", gpio.join(", ")));
+    }
+    if has("I2C") {
+        uses.push("use embassy_stm32::i2c::{self, I2c};".to_string());
+    }
+    if has("SPI") {
+        uses.push("use embassy_stm32::spi::{self, Spi};".to_string());
+    }
+    if has("I2C") || has("PWM") {
+        uses.push("use embassy_stm32::time::Hertz;".to_string());
+    }
+    if has("PWM") {
+        uses.push("use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm};".to_string());
+    }
+    if has("UART") {
+        uses.push("use embassy_stm32::usart::{self, Uart};".to_string());
+    }
+
+    let mut out = format!("//! {}\n", header(device, CodegenFormat::Embassy));
+    out.push_str(
+        "//! embassy-stm32 picks each pin's alternate function from its type, so only\n\
+         //! the pin-to-peripheral pairing is spelled out here.\n\n",
+    );
+    for u in &uses {
+        out.push_str(u);
+        out.push('\n');
+    }
+    out.push_str("\npub fn init_pins(p: embassy_stm32::Peripherals) {\n");
+
+    let pin_of = |group: &[&Signal], functions: &[&str]| {
+        group
+            .iter()
+            .find(|s| functions.contains(&s.function.as_str()))
+            .map(|s| format!("p.{}", s.pin()))
+    };
+    for (instance, group) in &groups {
+        let var = instance.to_lowercase();
+        let pins = group
+            .iter()
+            .map(|s| format!("{} {}", s.function, s.pin()).trim().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let class = group[0].class.as_str();
+        out.push_str(&format!("    // {}: {}\n", instance, pins));
+        match class {
+            "UART" => match (pin_of(group, &["RX"]), pin_of(group, &["TX"])) {
+                (Some(rx), Some(tx)) => out.push_str(&format!(
+                    "    let _{} = Uart::new_blocking(p.{}, {}, {}, usart::Config::default()).unwrap();\n",
+                    var, instance, rx, tx
+                )),
+                _ => out.push_str("    // Needs both TX and RX for Uart::new_blocking; see UartTx/UartRx\n"),
+            },
+            "I2C" => match (pin_of(group, &["SCL"]), pin_of(group, &["SDA"])) {
+                (Some(scl), Some(sda)) => out.push_str(&format!(
+                    "    let _{} = I2c::new_blocking(p.{}, {}, {}, Hertz(100_000), i2c::Config::default());\n",
+                    var, instance, scl, sda
+                )),
+                _ => out.push_str("    // Needs SCL and SDA\n"),
+            },
+            "SPI" => match (
+                pin_of(group, &["SCK"]),
+                pin_of(group, &["MOSI"]),
+                pin_of(group, &["MISO"]),
+            ) {
+                (Some(sck), Some(mosi), Some(miso)) => out.push_str(&format!(
+                    "    let _{} = Spi::new_blocking(p.{}, {}, {}, {}, spi::Config::default());\n",
+                    var, instance, sck, mosi, miso
+                )),
+                (Some(sck), Some(mosi), None) => out.push_str(&format!(
+                    "    let _{} = Spi::new_blocking_txonly(p.{}, {}, {}, spi::Config::default());\n",
+                    var, instance, sck, mosi
+                )),
+                _ => out.push_str("    // Needs SCK and MOSI\n"),
+            },
+            "PWM" => {
+                let channel = |n: u32| {
+                    pin_of(group, &[format!("CH{}", n).as_str()]).map_or("None".to_string(), |pin| {
+                        format!("Some(PwmPin::new_ch{}({}, OutputType::PushPull))", n, pin)
+                    })
+                };
+                out.push_str(&format!(
+                    "    let _pwm_{} = SimplePwm::new(\n        p.{},\n        {},\n        {},\n        {},\n        {},\n        Hertz(1_000),\n        Default::default(),\n    );\n",
+                    var,
+                    instance,
+                    channel(1),
+                    channel(2),
+                    channel(3),
+                    channel(4)
+                ));
+            }
+            "ADC" => {
+                out.push_str(&format!(
+                    "    let mut _{} = Adc::new(p.{});\n",
+                    var, instance
+                ));
+                for s in group {
+                    out.push_str(&format!(
+                        "    let mut _{} = p.{}; // read with _{}.blocking_read(&mut _{})\n",
+                        s.pin().to_lowercase(),
+                        s.pin(),
+                        var,
+                        s.pin().to_lowercase()
+                    ));
+                }
+            }
+            "GPIO" => {
+                for s in group {
+                    out.push_str(&format!(
+                        "    let _{} = Output::new(p.{}, Level::Low, Speed::Low);\n",
+                        s.pin().to_lowercase(),
+                        s.pin()
+                    ));
+                }
+            }
+            _ => out.push_str(&format!(
+                "    // {} pins are set up by the {} driver\n",
+                instance,
+                instance.to_lowercase()
+            )),
+        }
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn esp_idf(device: &str, family: &Family, signals: &[Signal]) -> Result<String, String> {
+    if !family.name.to_uppercase().starts_with("ESP32") {
+        return Err(format!(
+            "esp-idf output supports ESP32 pin assignments, not {}",
+            family.name
+        ));
+    }
+    let mut errors = String::new();
+    let pin = |s: &Signal, errors: &mut String| -> String {
+        match gpio_number(s.pin()) {
+            Some(n) => n.to_string(),
+            None => {
+                errors.push_str(&unrouted_error(s, "GPIO25"));
+                "-1".to_string()
+            }
+        }
+    };
+    let pin_of = |group: &[&Signal], function: &str, errors: &mut String| {
+        group
+            .iter()
+            .find(|s| s.function == function)
+            .map_or("-1".to_string(), |s| pin(s, errors))
+    };
+
+    let groups = by_instance(signals);
+    let has = |class: &str| signals.iter().any(|s| s.class == class);
+    let mut includes = vec!["#include \"esp_err.h\"", "#include \"driver/gpio.h\""];
+    if has("UART") {
+        includes.push("#include \"driver/uart.h\"");
+    }
+    if has("I2C") {
+        includes.push("#include \"driver/i2c.h\"");
+    }
+    if has("SPI") {
+        includes.push("#include \"driver/spi_master.h\"");
+    }
+    if has("PWM") {
+        includes.push("#include \"driver/ledc.h\"");
+    }
+    if has("ADC") {
+        includes.push("#include \"esp_adc/adc_oneshot.h\"");
+    }
+
+    let mut body = String::new();
+    let mut statics = String::new();
+    let mut ledc_timer = false;
+    for (instance, group) in &groups {
+        let number = &instance[instance
+            .trim_end_matches(|c: char| c.is_ascii_digit())
+            .len()..];
+        match group[0].class.as_str() {
+            "UART" => body.push_str(&format!(
+                "\n    /* {} */\n    ESP_ERROR_CHECK(uart_set_pin(UART_NUM_{}, {}, {}, {}, {}));\n",
+                instance,
+                number,
+                pin_of(group, "TX", &mut errors),
+                pin_of(group, "RX", &mut errors),
+                pin_of(group, "RTS", &mut errors).replace("-1", "UART_PIN_NO_CHANGE"),
+                pin_of(group, "CTS", &mut errors).replace("-1", "UART_PIN_NO_CHANGE"),
+            )),
+            "I2C" => body.push_str(&format!(
+                "\n    /* {} */\n    i2c_config_t {} = {{\n        .mode = I2C_MODE_MASTER,\n        .sda_io_num = {},\n        .scl_io_num = {},\n        .sda_pullup_en = GPIO_PULLUP_ENABLE,\n        .scl_pullup_en = GPIO_PULLUP_ENABLE,\n        .master.clk_speed = 100000,\n    }};\n    ESP_ERROR_CHECK(i2c_param_config(I2C_NUM_{}, &{}));\n",
+                instance,
+                instance.to_lowercase(),
+                pin_of(group, "SDA", &mut errors),
+                pin_of(group, "SCL", &mut errors),
+                number,
+                instance.to_lowercase()
+            )),
+            "SPI" => body.push_str(&format!(
+                "\n    /* {} */\n    spi_bus_config_t {} = {{\n        .mosi_io_num = {},\n        .miso_io_num = {},\n        .sclk_io_num = {},\n        .quadwp_io_num = -1,\n        .quadhd_io_num = -1,\n    }};\n    ESP_ERROR_CHECK(spi_bus_initialize({}_HOST, &{}, SPI_DMA_CH_AUTO));\n",
+                instance,
+                instance.to_lowercase(),
+                pin_of(group, "MOSI", &mut errors),
+                pin_of(group, "MISO", &mut errors),
+                pin_of(group, "SCK", &mut errors),
+                instance,
+                instance.to_lowercase()
+            )),
+            "PWM" => {
+                if !ledc_timer {
+                    ledc_timer = true;
+                    body.push_str(
+                        "\n    /* LEDC timer shared by all PWM channels */\n    ledc_timer_config_t ledc_timer = {\n        .speed_mode = LEDC_LOW_SPEED_MODE,\n        .duty_resolution = LEDC_TIMER_10_BIT,\n        .timer_num = LEDC_TIMER_0,\n        .freq_hz = 1000,\n        .clk_cfg = LEDC_AUTO_CLK,\n    };\n    ESP_ERROR_CHECK(ledc_timer_config(&ledc_timer));\n",
+                    );
+                }
+                for s in group {
+                    body.push_str(&format!(
+                        "    ledc_channel_config_t ledc_{} = {{\n        .gpio_num = {},\n        .speed_mode = LEDC_LOW_SPEED_MODE,\n        .channel = LEDC_CHANNEL_{},\n        .timer_sel = LEDC_TIMER_0,\n        .duty = 0,\n        .hpoint = 0,\n    }};\n    ESP_ERROR_CHECK(ledc_channel_config(&ledc_{}));\n",
+                        s.function.to_lowercase(),
+                        pin(s, &mut errors),
+                        s.function.trim_start_matches("CH"),
+                        s.function.to_lowercase()
+                    ));
+                }
+            }
+            "ADC" => {
+                let unit = instance.trim_start_matches("ADC");
+                statics.push_str(&format!(
+                    "static adc_oneshot_unit_handle_t adc{}_handle;\n",
+                    unit
+                ));
+                body.push_str(&format!(
+                    "\n    /* {} */\n    adc_oneshot_unit_init_cfg_t adc{}_cfg = {{ .unit_id = ADC_UNIT_{} }};\n    ESP_ERROR_CHECK(adc_oneshot_new_unit(&adc{}_cfg, &adc{}_handle));\n    adc_oneshot_chan_cfg_t adc{}_chan = {{ .atten = ADC_ATTEN_DB_12, .bitwidth = ADC_BITWIDTH_DEFAULT }};\n",
+                    instance, unit, unit, unit, unit, unit
+                ));
+                for s in group {
+                    body.push_str(&format!(
+                        "    ESP_ERROR_CHECK(adc_oneshot_config_channel(adc{}_handle, ADC_CHANNEL_{}, &adc{}_chan)); /* {} */\n",
+                        unit,
+                        s.function.trim_start_matches("CH"),
+                        unit,
+                        s.pin()
+                    ));
+                }
+            }
+            "GPIO" => {
+                let mask = group
+                    .iter()
+                    .map(|s| format!("(1ULL << {})", pin(s, &mut errors)))
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                body.push_str(&format!(
+                    "\n    /* Plain GPIO outputs */\n    gpio_config_t io_conf = {{\n        .pin_bit_mask = {},\n        .mode = GPIO_MODE_OUTPUT,\n        .pull_up_en = GPIO_PULLUP_DISABLE,\n        .pull_down_en = GPIO_PULLDOWN_DISABLE,\n        .intr_type = GPIO_INTR_DISABLE,\n    }};\n    ESP_ERROR_CHECK(gpio_config(&io_conf));\n",
+                    mask
+                ));
+            }
+            _ => body.push_str(&format!(
+                "\n    /* {}: set up by its driver */\n",
+                instance
+            )),
+        }
+    }
+
+    let mut out = format!(
+        "/* {} */\n\n{}\n",
+        header(device, CodegenFormat::EspIdf),
+        includes.join("\n")
+    );
+    if !errors.is_empty() {
+        out.push('\n');
+        out.push_str(&errors);
+    }
+    if !statics.is_empty() {
+        out.push('\n');
+        out.push_str(&statics);
+    }
+    out.push_str("\nvoid board_pins_init(void)\n{");
+    out.push_str(&body);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Zephyr node label of a peripheral instance
+fn zephyr_node(family: &str, instance: &str) -> String {
+    let lower = instance.to_lowercase();
+    if family.starts_with("NRF52") {
+        // UARTE0 -> uart0, TWIM1 -> i2c1, SPIM2 -> spi2, SAADC -> adc
+        let number: String = lower.chars().filter(char::is_ascii_digit).collect();
+        return match lower.trim_end_matches(|c: char| c.is_ascii_digit()) {
+            "uarte" => format!("uart{}", number),
+            "twim" => format!("i2c{}", number),
+            "spim" => format!("spi{}", number),
+            "saadc" => "adc".to_string(),
+            _ => lower,
+        };
+    }
+    if family.starts_with("RP2040") {
+        return match lower.trim_end_matches(|c: char| c.is_ascii_digit()) {
+            "pwm" => "pwm".to_string(),
+            _ => lower,
+        };
+    }
+    match lower.trim_end_matches(|c: char| c.is_ascii_digit()) {
+        "tim" => lower.replace("tim", "timers"),
+        "usb" => "usbotg_fs".to_string(),
+        _ => lower,
+    }
+}
+
+fn zephyr_enable(node: &str, pinctrl: &str, extra: &str) -> String {
+    format!(
+        "&{} {{\n\tpinctrl-0 = <{}>;\n\tpinctrl-names = \"default\";\n{}\tstatus = \"okay\";\n}};\n",
+        node, pinctrl, extra
+    )
+}
+
+fn zephyr_gpios(gpios: &[String]) -> String {
+    if gpios.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\n/ {{\n\tzephyr,user {{\n\t\tgpios = {};\n\t}};\n}};\n",
+        gpios.join(",\n\t\t\t")
+    )
+}
+
+fn zephyr(device: &str, family: &Family, signals: &[Signal]) -> Result<String, String> {
+    let name = family.name.to_uppercase();
+    let mut out = format!("/* {} */\n", header(device, CodegenFormat::Zephyr));
+    let groups = by_instance(signals);
+
+    if is_stm32(family, signals) {
+        let mut gpios = Vec::new();
+        for (instance, group) in &groups {
+            let class = group[0].class.as_str();
+            if class == "GPIO" {
+                for s in group {
+                    let (port, n) = stm32_pin(s.pin()).expect("checked by is_stm32");
+                    gpios.push(format!(
+                        "<&gpio{} {} GPIO_ACTIVE_HIGH>",
+                        port.to_ascii_lowercase(),
+                        n
+                    ));
+                }
+                continue;
+            }
+            let labels = group
+                .iter()
+                .map(|s| {
+                    let signal = match s.instance.as_str() {
+                        "USB" => format!("usb_otg_fs_{}", s.function.to_lowercase()),
+                        _ => s.lower(),
+                    };
+                    format!("&{}_{}", signal, s.pin().to_lowercase())
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let node = zephyr_node(&name, instance);
+            out.push('\n');
+            if class == "PWM" {
+                let n = instance.trim_start_matches("TIM");
+                out.push_str(&format!(
+                    "&{} {{\n\tstatus = \"okay\";\n\n\tpwm{}: pwm {{\n\t\tpinctrl-0 = <{}>;\n\t\tpinctrl-names = \"default\";\n\t\tstatus = \"okay\";\n\t}};\n}};\n",
+                    node, n, labels
+                ));
+            } else {
+                let extra = if class == "I2C" {
+                    "\tclock-frequency = <I2C_BITRATE_STANDARD>;\n"
+                } else {
+                    ""
+                };
+                out.push_str(&zephyr_enable(&node, &labels, extra));
+            }
+        }
+        out.push_str(&zephyr_gpios(&gpios));
+        return Ok(out);
+    }
+
+    if name.starts_with("RP2040") {
+        let mut pinctrl = String::new();
+        let mut nodes = String::new();
+        let mut gpios = Vec::new();
+        // PWM slices and ADC channels share one node each
+        let mut merged: Vec<(String, Vec<&Signal>)> = Vec::new();
+        for (instance, group) in &groups {
+            let node = zephyr_node(&name, instance);
+            match merged.iter_mut().find(|(n, _)| *n == node) {
+                Some((_, existing)) => existing.extend(group.iter().copied()),
+                None => merged.push((node, group.clone())),
+            }
+        }
+        for (node, group) in &merged {
+            if group[0].class == "GPIO" {
+                for s in group {
+                    gpios.push(format!(
+                        "<&gpio0 {} GPIO_ACTIVE_HIGH>",
+                        gpio_number(s.pin()).unwrap_or(0)
+                    ));
+                }
+                continue;
+            }
+            pinctrl.push_str(&format!("\t{}_default: {}_default {{\n", node, node));
+            for (i, s) in group.iter().enumerate() {
+                let n = gpio_number(s.pin()).unwrap_or(0);
+                let macro_name = match s.class.as_str() {
+                    // PWM3_A -> PWM_3A_P6
+                    "PWM" => format!(
+                        "PWM_{}{}_P{}",
+                        s.instance.trim_start_matches("PWM"),
+                        s.function,
+                        n
+                    ),
+                    "ADC" => format!("ADC_CH{}_P{}", s.function.trim_start_matches("IN"), n),
+                    _ => format!("{}_P{}", s.assignment.signal, n),
+                };
+                let input = matches!(s.function.as_str(), "RX" | "CTS" | "SDA" | "SCL");
+                let bias = if s.class == "I2C" {
+                    "\t\t\tbias-pull-up;\n"
+                } else {
+                    ""
+                };
+                pinctrl.push_str(&format!(
+                    "\t\tgroup{} {{\n\t\t\tpinmux = <{}>;\n{}{}\t\t}};\n",
+                    i + 1,
+                    macro_name,
+                    if input { "\t\t\tinput-enable;\n" } else { "" },
+                    bias
+                ));
+            }
+            pinctrl.push_str("\t};\n");
+            nodes.push('\n');
+            nodes.push_str(&zephyr_enable(
+                node,
+                &format!("&{}_default", node),
+                if group[0].class == "I2C" {
+                    "\tclock-frequency = <I2C_BITRATE_STANDARD>;\n"
+                } else {
+                    ""
+                },
+            ));
+        }
+        if !pinctrl.is_empty() {
+            out.push_str(&format!("\n&pinctrl {{\n{}}};\n", pinctrl));
+        }
+        out.push_str(&nodes);
+        out.push_str(&zephyr_gpios(&gpios));
+        return Ok(out);
+    }
+
+    if name.starts_with("NRF52") {
+        let mut pinctrl = String::new();
+        let mut nodes = String::new();
+        let mut errors = String::new();
+        let mut gpios = Vec::new();
+        for (instance, group) in &groups {
+            let node = zephyr_node(&name, instance);
+            if group[0].class == "GPIO" {
+                for s in group {
+                    if let Some((port, pin)) = nrf_pin(s.pin()) {
+                        gpios.push(format!("<&gpio{} {} GPIO_ACTIVE_HIGH>", port, pin));
+                    }
+                }
+                continue;
+            }
+            if group[0].class == "ADC" {
+                nodes.push_str(&format!(
+                    "\n&{} {{\n\t#address-cells = <1>;\n\t#size-cells = <0>;\n\tstatus = \"okay\";\n",
+                    node
+                ));
+                for s in group {
+                    let channel = s.function.trim_start_matches("AIN");
+                    nodes.push_str(&format!(
+                        "\n\tchannel@{} {{\n\t\treg = <{}>;\n\t\tzephyr,gain = \"ADC_GAIN_1_6\";\n\t\tzephyr,reference = \"ADC_REF_INTERNAL\";\n\t\tzephyr,acquisition-time = <ADC_ACQ_TIME_DEFAULT>;\n\t\tzephyr,input-positive = <NRF_SAADC_AIN{}>;\n\t\tzephyr,resolution = <12>;\n\t}};\n",
+                        channel, channel, channel
+                    ));
+                }
+                nodes.push_str("};\n");
+                continue;
+            }
+            let kind = match group[0].class.as_str() {
+                "UART" => "UART",
+                "I2C" => "TWIM",
+                "SPI" => "SPIM",
+                "PWM" => "PWM",
+                other => other,
+            };
+            let psels = group
+                .iter()
+                .map(|s| match nrf_pin(s.pin()) {
+                    Some((port, pin)) => {
+                        let function = match s.function.as_str() {
+                            "TXD" => "TX".to_string(),
+                            "RXD" => "RX".to_string(),
+                            other => other.to_string(),
+                        };
+                        format!("<NRF_PSEL({}_{}, {}, {})>", kind, function, port, pin)
+                    }
+                    None => {
+                        errors.push_str(&unrouted_error(s, "P0.26"));
+                        String::new()
+                    }
+                })
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>();
+            if psels.is_empty() {
+                continue;
+            }
+            pinctrl.push_str(&format!(
+                "\t{}_default: {}_default {{\n\t\tgroup1 {{\n\t\t\tpsels = {};\n\t\t}};\n\t}};\n",
+                node,
+                node,
+                psels.join(",\n\t\t\t\t")
+            ));
+            nodes.push('\n');
+            nodes.push_str(&zephyr_enable(
+                &node,
+                &format!("&{}_default", node),
+                if kind == "TWIM" {
+                    "\tclock-frequency = <I2C_BITRATE_STANDARD>;\n"
+                } else {
+                    ""
+                },
+            ));
+        }
+        if !errors.is_empty() {
+            out.push('\n');
+            out.push_str(&errors);
+        }
+        if !pinctrl.is_empty() {
+            out.push_str(&format!("\n&pinctrl {{\n{}}};\n", pinctrl));
+        }
+        out.push_str(&nodes);
+        out.push_str(&zephyr_gpios(&gpios));
+        return Ok(out);
+    }
+
+    Err(format!(
+        "zephyr output supports STM32, RP2040 and nRF52 pin assignments, not {}",
+        family.name
+    ))
+}
+
+/// Generate one file in `format` for a solved assignment
+pub fn generate(
+    format: CodegenFormat,
+    device: &str,
+    family: &Family,
+    assignment: &[Assignment],
+) -> Result<GeneratedFile, String> {
+    let signals = signals(assignment);
+    let content = match format {
+        CodegenFormat::StmHal => stm_hal(device, family, &signals)?,
+        CodegenFormat::Embassy => embassy(device, family, &signals)?,
+        CodegenFormat::EspIdf => esp_idf(device, family, &signals)?,
+        CodegenFormat::Zephyr => zephyr(device, family, &signals)?,
+    };
+    Ok(GeneratedFile {
+        path: format.file_name().to_string(),
+        format: format.label().to_string(),
+        content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::pin_database::find;
+    use crate::tools::hardware::pin_solver::{parse_requirements, solve, Solution};
+
+    fn solved(device: &str, requirements: &str) -> (Family, Vec<Assignment>) {
+        let family = find(device, None).unwrap();
+        let (reqs, avoid) = parse_requirements(requirements, &family).unwrap();
+        match solve(&family, &reqs, &avoid).unwrap() {
+            Solution::Solved(assignment) => (family, assignment),
+            other => panic!("Expected a solution, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stm32_hal_and_embassy() {
+        let (family, assignment) = solved(
+            "STM32F401RE",
+            "USART2_TX=PA2 + USART2_RX=PA3 + I2C1_SCL=PB8 + I2C1_SDA=PB9 + TIM3_CH1=PA6 + ADC1_IN4 + GPIO",
+        );
+        let c = generate(CodegenFormat::StmHal, "STM32F401RE", &family, &assignment)
+            .unwrap()
+            .content;
+        assert!(c.contains("void MX_GPIO_Init(void)"));
+        assert!(c.contains("__HAL_RCC_GPIOA_CLK_ENABLE();\n  __HAL_RCC_GPIOB_CLK_ENABLE();"));
+        // TX and RX share one init block
+        assert!(c.contains(
+            "/* PA2 USART2_TX, PA3 USART2_RX */\n  GPIO_InitStruct.Pin = GPIO_PIN_2 | GPIO_PIN_3;"
+        ));
+        assert!(c.contains("GPIO_InitStruct.Alternate = GPIO_AF7_USART2;"));
+        assert!(c.contains("GPIO_InitStruct.Mode = GPIO_MODE_AF_OD;"));
+        assert!(c.contains("GPIO_InitStruct.Alternate = GPIO_AF4_I2C1;"));
+        assert!(c.contains("GPIO_InitStruct.Alternate = GPIO_AF2_TIM3;"));
+        assert!(c.contains("GPIO_InitStruct.Mode = GPIO_MODE_ANALOG;"));
+        assert!(c.contains("HAL_GPIO_WritePin("));
+
+        let rust = generate(CodegenFormat::Embassy, "STM32F401RE", &family, &assignment)
+            .unwrap()
+            .content;
+        assert!(rust.contains(
+            "let _usart2 = Uart::new_blocking(p.USART2, p.PA3, p.PA2, usart::Config::default()).unwrap();"
+        ));
+        assert!(rust.contains("I2c::new_blocking(p.I2C1, p.PB8, p.PB9, Hertz(100_000)"));
+        assert!(rust.contains("Some(PwmPin::new_ch1(p.PA6, OutputType::PushPull))"));
+        assert!(rust.contains("let mut _adc1 = Adc::new(p.ADC1);"));
+
+        assert!(generate(CodegenFormat::EspIdf, "STM32F401RE", &family, &assignment).is_err());
+    }
+
+    #[test]
+    fn test_zephyr_overlays() {
+        let (family, assignment) = solved("STM32F401RE", "USART1 + I2C1 + TIM2_CH1=PA5");
+        let dts = generate(CodegenFormat::Zephyr, "STM32F401RE", &family, &assignment)
+            .unwrap()
+            .content;
+        assert!(dts.contains("&usart1 {\n\tpinctrl-0 = <&usart1_tx_pa9 &usart1_rx_pa10>;"));
+        assert!(dts.contains("clock-frequency = <I2C_BITRATE_STANDARD>;"));
+        assert!(dts.contains(
+            "&timers2 {\n\tstatus = \"okay\";\n\n\tpwm2: pwm {\n\t\tpinctrl-0 = <&tim2_ch1_pa5>;"
+        ));
+
+        let (family, assignment) =
+            solved("rp2040", "UART0_TX=GPIO0 + UART0_RX=GPIO1 + PWM3_A=GPIO6");
+        let dts = generate(CodegenFormat::Zephyr, "rp2040", &family, &assignment)
+            .unwrap()
+            .content;
+        assert!(dts.contains("pinmux = <UART0_TX_P0>;"));
+        assert!(dts.contains("pinmux = <UART0_RX_P1>;\n\t\t\tinput-enable;"));
+        assert!(dts.contains("pinmux = <PWM_3A_P6>;"));
+        assert!(dts.contains("&pwm {\n\tpinctrl-0 = <&pwm_default>;"));
+
+        let (family, assignment) = solved(
+            "nrf52840",
+            "UARTE0_TXD=P0.06 + UARTE0_RXD=P0.08 + TWIM0 + SAADC_AIN0",
+        );
+        let dts = generate(CodegenFormat::Zephyr, "nrf52840", &family, &assignment)
+            .unwrap()
+            .content;
+        assert!(dts.contains("<NRF_PSEL(UART_TX, 0, 6)>"));
+        assert!(dts.contains("&uart0 {\n\tpinctrl-0 = <&uart0_default>;"));
+        assert!(dts.contains("#error \"Choose a pin for TWIM0_SCL"));
+        assert!(dts.contains("zephyr,input-positive = <NRF_SAADC_AIN0>;"));
+
+        let (family, assignment) = solved("atmega328p", "UART");
+        assert!(generate(CodegenFormat::Zephyr, "uno", &family, &assignment).is_err());
+    }
+
+    #[test]
+    fn test_esp_idf() {
+        let (family, assignment) = solved("esp32", "UART2 + I2C0 + SPI3 + 2 PWM + ADC1_CH6 + I2C1");
+        let c = generate(CodegenFormat::EspIdf, "ESP32", &family, &assignment)
+            .unwrap()
+            .content;
+        assert!(
+            c.contains("uart_set_pin(UART_NUM_2, 17, 16, UART_PIN_NO_CHANGE, UART_PIN_NO_CHANGE)")
+        );
+        assert!(c.contains(".sda_io_num = 21,"));
+        assert!(c.contains("spi_bus_initialize(SPI3_HOST, &spi3, SPI_DMA_CH_AUTO)"));
+        assert!(c.contains(".channel = LEDC_CHANNEL_0,"));
+        assert!(c.contains("adc_oneshot_config_channel(adc1_handle, ADC_CHANNEL_6, &adc1_chan)"));
+        // I2C1 has no IO_MUX pins, so its pins must be chosen
+        assert!(c.contains("#error \"Choose a pin for I2C1_SDA"));
+    }
+}
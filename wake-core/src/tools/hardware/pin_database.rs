@@ -0,0 +1,740 @@
+//! MCU pin database.
+//!
+//! Maps peripheral signals (USART2_TX, I2C1_SCL, TIM3_CH2, ADC1_IN5) to the
+//! pins that can carry them and their alternate-function numbers. Families
+//! are plain tables: the bundled ones live in `pin_data/*.csv`, and users can
+//! add their own as CSV or JSON, or point at STM32CubeMX MCU XML files.
+//!
+//! CSV tables have a `pin,signal,af` header preceded by optional
+//! `# family:`, `# aliases:` and `# note:` lines. JSON tables use
+//! `{"family", "aliases", "notes", "pins": [{"pin", "signal", "af"}]}`.
+//! A pin of `*` means the signal can be routed to any GPIO (ESP32 GPIO
+//! matrix, nRF52 PSEL).
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Pin placeholder for signals routable to any GPIO
+pub const ANY_PIN: &str = "*";
+
+const BUNDLED: [(&str, &str); 6] = [
+    ("stm32f4.csv", include_str!("pin_data/stm32f4.csv")),
+    ("stm32g0.csv", include_str!("pin_data/stm32g0.csv")),
+    ("esp32.csv", include_str!("pin_data/esp32.csv")),
+    ("rp2040.csv", include_str!("pin_data/rp2040.csv")),
+    ("nrf52.csv", include_str!("pin_data/nrf52.csv")),
+    ("atmega328p.csv", include_str!("pin_data/atmega328p.csv")),
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PinFunction {
+    pub pin: String,
+    pub signal: String,
+    /// Alternate-function (or function-select) number, None for fixed/analog functions
+    #[serde(default)]
+    pub af: Option<u8>,
+}
+
+impl PinFunction {
+    /// Peripheral instance, e.g. `USART2` of `USART2_TX`
+    pub fn instance(&self) -> &str {
+        self.signal
+            .split_once('_')
+            .map_or(self.signal.as_str(), |(i, _)| i)
+    }
+
+    /// Signal within the instance, e.g. `TX` of `USART2_TX`
+    pub fn function(&self) -> &str {
+        self.signal.split_once('_').map_or("", |(_, f)| f)
+    }
+
+    pub fn is_routable(&self) -> bool {
+        self.pin == ANY_PIN
+    }
+
+    /// Peripheral class used for lookups: UART, I2C, SPI, PWM, ADC, DAC, USB, CAN or the instance name
+    pub fn class(&self) -> String {
+        let instance = self.instance().to_uppercase();
+        let kind = instance.trim_end_matches(|c: char| c.is_ascii_digit());
+        let function = self.function().to_uppercase();
+        let class = match kind {
+            "USART" | "UART" | "LPUART" | "UARTE" => "UART",
+            "I2C" | "TWI" | "TWIM" | "TWIS" => "I2C",
+            "SPI" | "SPIM" | "SPIS" => "SPI",
+            "ADC" | "SAADC" => "ADC",
+            "DAC" => "DAC",
+            "PWM" | "LEDC" => "PWM",
+            "TIM" | "TC" | "LPTIM" if function.starts_with("CH") || function.starts_with("OC") => {
+                "PWM"
+            }
+            "USB" | "OTG" => "USB",
+            "CAN" | "FDCAN" | "TWAI" => "CAN",
+            _ => kind,
+        };
+        class.to_string()
+    }
+}
+
+/// Normalize user peripheral names to the classes returned by [`PinFunction::class`]
+pub fn peripheral_class(name: &str) -> String {
+    let upper = name.trim().to_uppercase();
+    match upper.as_str() {
+        "USART" | "UART" | "SERIAL" | "LPUART" => "UART",
+        "I2C" | "IIC" | "TWI" | "TWIM" => "I2C",
+        "SPI" | "SPIM" => "SPI",
+        "PWM" | "TIMER" | "TIM" | "LEDC" => "PWM",
+        "ADC" | "ANALOG" | "SAADC" => "ADC",
+        "CAN" | "FDCAN" | "TWAI" => "CAN",
+        _ => return upper,
+    }
+    .to_string()
+}
+
+/// Instance names match with or without the S of USART
+pub fn instance_matches(instance: &str, wanted: &str) -> bool {
+    let strip = |s: &str| s.to_uppercase().replace("USART", "UART");
+    instance.eq_ignore_ascii_case(wanted) || strip(instance) == strip(wanted)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Family {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub notes: Vec<String>,
+    /// Where the table came from: `bundled`, a file path, or a CubeMX file
+    pub source: String,
+    /// CubeMX RefName pattern such as `STM32F401C(B-C)Ux`
+    pub ref_name: Option<String>,
+    pub functions: Vec<PinFunction>,
+}
+
+#[derive(Deserialize)]
+struct JsonFamily {
+    family: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    notes: Vec<String>,
+    pins: Vec<PinFunction>,
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Pin name without CubeMX decorations (`PC14-OSC32_IN` -> `PC14`)
+fn base_pin(name: &str) -> &str {
+    name.split([' ', '-', '/', '(']).next().unwrap_or(name)
+}
+
+/// Match a device against a CubeMX RefName: `(B-C)` lists alternatives, `x` is any
+/// character, and the trailing package/temperature code may be omitted
+pub fn ref_name_matches(pattern: &str, device: &str) -> bool {
+    let device: Vec<char> = device
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let mut tokens: Vec<Vec<char>> = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                let group: String = chars.by_ref().take_while(|&c| c != ')').collect();
+                tokens.push(
+                    group
+                        .split('-')
+                        .filter_map(|alt| alt.chars().next())
+                        .map(|c| c.to_ascii_uppercase())
+                        .collect(),
+                );
+            }
+            // Wildcard
+            'x' => tokens.push(Vec::new()),
+            c if c.is_ascii_alphanumeric() => tokens.push(vec![c.to_ascii_uppercase()]),
+            _ => {}
+        }
+    }
+
+    for (i, token) in tokens.iter().enumerate() {
+        match device.get(i) {
+            Some(c) if token.is_empty() || token.contains(c) => {}
+            Some(_) => return false,
+            None => return i > 0 && tokens.len() - i <= 2,
+        }
+    }
+    true
+}
+
+impl Family {
+    pub fn parse_csv(text: &str, source: &str) -> Result<Family, String> {
+        let mut family = Family {
+            name: String::new(),
+            aliases: Vec::new(),
+            notes: Vec::new(),
+            source: source.to_string(),
+            ref_name: None,
+            functions: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix('#') {
+                match comment.split_once(':') {
+                    Some((key, value)) if key.trim() == "family" => {
+                        family.name = value.trim().to_string()
+                    }
+                    Some((key, value)) if key.trim() == "aliases" => family.aliases.extend(
+                        value
+                            .split(',')
+                            .map(|a| a.trim().to_string())
+                            .filter(|a| !a.is_empty()),
+                    ),
+                    Some((key, value)) if key.trim() == "note" => {
+                        family.notes.push(value.trim().to_string())
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if line.is_empty() || line.eq_ignore_ascii_case("pin,signal,af") {
+                continue;
+            }
+            let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+            let (pin, signal, af) = match cols.as_slice() {
+                [pin, signal] => (pin, signal, ""),
+                [pin, signal, af, ..] => (pin, signal, *af),
+                _ => return Err(format!("{}:{}: expected pin,signal,af", source, number + 1)),
+            };
+            let af = af.trim_start_matches("AF").trim_start_matches("af");
+            family.functions.push(PinFunction {
+                pin: pin.to_string(),
+                signal: signal.to_uppercase(),
+                af: if af.is_empty() {
+                    None
+                } else {
+                    Some(af.parse().map_err(|_| {
+                        format!("{}:{}: invalid AF number '{}'", source, number + 1, af)
+                    })?)
+                },
+            });
+        }
+        family.finish(source)
+    }
+
+    pub fn parse_json(text: &str, source: &str) -> Result<Family, String> {
+        let json: JsonFamily = serde_json::from_str(text)
+            .map_err(|e| format!("{}: invalid pin table: {}", source, e))?;
+        Family {
+            name: json.family,
+            aliases: json.aliases,
+            notes: json.notes,
+            source: source.to_string(),
+            ref_name: None,
+            functions: json
+                .pins
+                .into_iter()
+                .map(|f| PinFunction {
+                    signal: f.signal.to_uppercase(),
+                    ..f
+                })
+                .collect(),
+        }
+        .finish(source)
+    }
+
+    /// Import an STM32CubeMX MCU description (`db/mcu/STM32F401C(B-C)Ux.xml`), with AF
+    /// numbers from its GPIO modes file (`db/mcu/IP/GPIO-<version>_Modes.xml`) when given
+    pub fn parse_cubemx(
+        mcu_xml: &str,
+        gpio_modes_xml: Option<&str>,
+        source: &str,
+    ) -> Result<Family, String> {
+        let doc = roxmltree::Document::parse(mcu_xml)
+            .map_err(|e| format!("{}: invalid XML: {}", source, e))?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "Mcu" {
+            return Err(format!("{}: not a CubeMX MCU file (no <Mcu> root)", source));
+        }
+        let ref_name = root
+            .attribute("RefName")
+            .ok_or_else(|| format!("{}: <Mcu> without RefName", source))?
+            .to_string();
+
+        // (pin, signal) -> AF from the GPIO modes file
+        let mut afs = std::collections::HashMap::new();
+        if let Some(modes) = gpio_modes_xml {
+            let modes = roxmltree::Document::parse(modes)
+                .map_err(|e| format!("{}: invalid GPIO modes XML: {}", source, e))?;
+            for pin in modes.descendants().filter(|n| n.has_tag_name("GPIO_Pin")) {
+                let Some(pin_name) = pin.attribute("Name").map(base_pin) else {
+                    continue;
+                };
+                for signal in pin.children().filter(|n| n.has_tag_name("PinSignal")) {
+                    let af = signal
+                        .descendants()
+                        .filter(|n| n.has_tag_name("PossibleValue"))
+                        .filter_map(|n| n.text())
+                        .find_map(|v| {
+                            let digits: String = v
+                                .trim()
+                                .strip_prefix("GPIO_AF")?
+                                .chars()
+                                .take_while(char::is_ascii_digit)
+                                .collect();
+                            digits.parse::<u8>().ok()
+                        });
+                    if let (Some(name), Some(af)) = (signal.attribute("Name"), af) {
+                        afs.insert((pin_name.to_string(), name.to_string()), af);
+                    }
+                }
+            }
+        }
+
+        let mut functions = Vec::new();
+        for pin in root.children().filter(|n| n.has_tag_name("Pin")) {
+            if pin.attribute("Type") != Some("I/O") {
+                continue;
+            }
+            let Some(pin_name) = pin.attribute("Name").map(base_pin) else {
+                continue;
+            };
+            for signal in pin.children().filter(|n| n.has_tag_name("Signal")) {
+                let Some(name) = signal.attribute("Name") else {
+                    continue;
+                };
+                if name == "GPIO" {
+                    continue;
+                }
+                functions.push(PinFunction {
+                    pin: pin_name.to_string(),
+                    signal: name.to_string(),
+                    af: afs.get(&(pin_name.to_string(), name.to_string())).copied(),
+                });
+            }
+        }
+
+        let mut notes = vec![format!(
+            "Imported from STM32CubeMX ({} package)",
+            root.attribute("Package").unwrap_or("unknown")
+        )];
+        if gpio_modes_xml.is_none() {
+            notes.push("GPIO modes file not found; AF numbers are unavailable".to_string());
+        }
+        Family {
+            name: ref_name.clone(),
+            aliases: Vec::new(),
+            notes,
+            source: source.to_string(),
+            ref_name: Some(ref_name),
+            functions,
+        }
+        .finish(source)
+    }
+
+    /// Load a CSV, JSON or CubeMX XML table, chosen by extension
+    pub fn load(path: &Path) -> Result<Family, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let source = path.display().to_string();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "csv" => Family::parse_csv(&text, &source),
+            "json" => Family::parse_json(&text, &source),
+            "xml" => {
+                let modes =
+                    cubemx_modes_path(path, &text).and_then(|p| std::fs::read_to_string(p).ok());
+                Family::parse_cubemx(&text, modes.as_deref(), &source)
+            }
+            _ => Err(format!(
+                "Unknown pin table format {}; expected .csv, .json or CubeMX .xml",
+                source
+            )),
+        }
+    }
+
+    fn finish(mut self, source: &str) -> Result<Family, String> {
+        if self.functions.is_empty() {
+            return Err(format!("{}: pin table has no entries", source));
+        }
+        if self.name.is_empty() {
+            self.name = Path::new(source)
+                .file_stem()
+                .map_or_else(|| source.to_string(), |s| s.to_string_lossy().to_string());
+        }
+        Ok(self)
+    }
+
+    pub fn matches(&self, device: &str) -> bool {
+        if let Some(pattern) = &self.ref_name {
+            return ref_name_matches(pattern, device);
+        }
+        let device = normalize(device);
+        !device.is_empty()
+            && std::iter::once(&self.name)
+                .chain(&self.aliases)
+                .map(|a| normalize(a))
+                .any(|alias| !alias.is_empty() && device.starts_with(&alias))
+    }
+
+    /// Physical pins in table order
+    pub fn pins(&self) -> Vec<&str> {
+        let mut pins: Vec<&str> = Vec::new();
+        for f in &self.functions {
+            if !f.is_routable() && !pins.contains(&f.pin.as_str()) {
+                pins.push(&f.pin);
+            }
+        }
+        pins
+    }
+
+    /// Pin of this family named by `query` (`pa2`, `GPIO4`, `4`, `P0.02`)
+    pub fn pin(&self, query: &str) -> Option<&str> {
+        let query = query.trim();
+        let gpio = format!("GPIO{}", query);
+        self.pins()
+            .into_iter()
+            .find(|p| p.eq_ignore_ascii_case(query) || p.eq_ignore_ascii_case(&gpio))
+    }
+
+    pub fn functions_on(&self, pin: &str) -> Vec<&PinFunction> {
+        self.functions
+            .iter()
+            .filter(|f| f.pin.eq_ignore_ascii_case(pin))
+            .collect()
+    }
+
+    /// Candidate pins for a signal, including `*` routing entries
+    pub fn candidates(&self, signal: &str) -> Vec<&PinFunction> {
+        self.functions
+            .iter()
+            .filter(|f| f.signal.eq_ignore_ascii_case(signal))
+            .collect()
+    }
+
+    /// Signals in table order, optionally limited to a peripheral class
+    pub fn signals(&self, class: Option<&str>) -> Vec<&str> {
+        let mut signals: Vec<&str> = Vec::new();
+        for f in &self.functions {
+            if class.is_some_and(|c| f.class() != c) {
+                continue;
+            }
+            if !signals.contains(&f.signal.as_str()) {
+                signals.push(&f.signal);
+            }
+        }
+        signals
+    }
+
+    /// Peripheral classes present in the table
+    pub fn classes(&self) -> Vec<String> {
+        let mut classes: Vec<String> = Vec::new();
+        for f in &self.functions {
+            let class = f.class();
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        classes
+    }
+}
+
+/// GPIO modes file referenced by a CubeMX MCU file: `<dir>/IP/GPIO-<Version>_Modes.xml`
+fn cubemx_modes_path(mcu_path: &Path, mcu_xml: &str) -> Option<PathBuf> {
+    let doc = roxmltree::Document::parse(mcu_xml).ok()?;
+    let version = doc
+        .root_element()
+        .children()
+        .find(|n| n.has_tag_name("IP") && n.attribute("Name") == Some("GPIO"))?
+        .attribute("Version")?;
+    let path = mcu_path
+        .parent()?
+        .join("IP")
+        .join(format!("GPIO-{}_Modes.xml", version));
+    path.is_file().then_some(path)
+}
+
+/// The bundled families
+pub fn bundled() -> Vec<Family> {
+    BUNDLED
+        .iter()
+        .map(|(name, text)| {
+            Family::parse_csv(text, &format!("bundled {}", name))
+                .unwrap_or_else(|e| panic!("invalid bundled pin table: {}", e))
+        })
+        .collect()
+}
+
+/// CubeMX MCU directories from `STM32CUBEMX_PATH` or the default install location
+fn cubemx_dirs() -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = std::env::var_os("STM32CUBEMX_PATH")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    roots.extend(dirs::home_dir().map(|home| home.join("STM32CubeMX")));
+    roots
+        .into_iter()
+        .map(|root| root.join("db").join("mcu"))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Tables in a directory that may describe `device`; CubeMX files are picked by file name
+fn search_dir(dir: &Path, device: &str) -> Result<Option<Family>, String> {
+    for entry in WalkDir::new(dir)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+    {
+        let path = entry.path();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let candidate = match extension.as_str() {
+            "csv" | "json" => true,
+            "xml" => path
+                .file_stem()
+                .is_some_and(|stem| ref_name_matches(&stem.to_string_lossy(), device)),
+            _ => false,
+        };
+        if !candidate {
+            continue;
+        }
+        let family = Family::load(path)?;
+        if family.matches(device) {
+            return Ok(Some(family));
+        }
+    }
+    Ok(None)
+}
+
+/// Pin table for `device`: user `database` first, then a local CubeMX install for
+/// STM32 parts, then the bundled families
+pub fn find(device: &str, database: Option<&Path>) -> Result<Family, String> {
+    if let Some(path) = database {
+        if path.is_file() {
+            return Family::load(path);
+        }
+        if !path.is_dir() {
+            return Err(format!("Pin database {} does not exist", path.display()));
+        }
+        if let Some(family) = search_dir(path, device)? {
+            return Ok(family);
+        }
+    }
+    if normalize(device).starts_with("stm32") {
+        for dir in cubemx_dirs() {
+            if let Ok(Some(family)) = search_dir(&dir, device) {
+                return Ok(family);
+            }
+        }
+    }
+
+    let families = bundled();
+    let names: Vec<String> = families.iter().map(|f| f.name.clone()).collect();
+    families
+        .into_iter()
+        .find(|f| f.matches(device))
+        .ok_or_else(|| {
+            format!(
+                "No pin database for '{}'. Bundled families: {}. Provide `database` with a JSON/CSV pin table or an STM32CubeMX MCU XML file",
+                device,
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+pub(crate) const TEST_CUBEMX_MCU: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Mcu xmlns="http://mcd.rennes.st.com" ClockTree="STM32F4" DBVersion="V3.0" Family="STM32F4" IOType="" Line="STM32F401" Package="LQFP64" RefName="STM32F401R(D-E)Tx">
+  <IP InstanceName="GPIO" Name="GPIO" Version="STM32F401_gpio_v1_0"/>
+  <Pin Name="VBAT" Position="1" Type="Power"/>
+  <Pin Name="PC14-OSC32_IN" Position="3" Type="I/O">
+    <Signal Name="RCC_OSC32_IN"/>
+    <Signal Name="GPIO"/>
+  </Pin>
+  <Pin Name="PA2" Position="16" Type="I/O">
+    <Signal Name="ADC1_IN2"/>
+    <Signal Name="TIM2_CH3"/>
+    <Signal Name="USART2_TX"/>
+    <Signal Name="GPIO"/>
+  </Pin>
+  <Pin Name="PA3" Position="17" Type="I/O">
+    <Signal Name="USART2_RX"/>
+  </Pin>
+</Mcu>
+"#;
+
+#[cfg(test)]
+pub(crate) const TEST_CUBEMX_MODES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<IP xmlns="http://mcd.rennes.st.com" Name="GPIO" Version="STM32F401_gpio_v1_0">
+  <GPIO_Pin PortName="PA" Name="PA2">
+    <PinSignal Name="TIM2_CH3">
+      <SpecificParameter Name="GPIO_AF"><PossibleValue>GPIO_AF1_TIM2</PossibleValue></SpecificParameter>
+    </PinSignal>
+    <PinSignal Name="USART2_TX">
+      <SpecificParameter Name="GPIO_AF"><PossibleValue>GPIO_AF7_USART2</PossibleValue></SpecificParameter>
+    </PinSignal>
+  </GPIO_Pin>
+  <GPIO_Pin PortName="PA" Name="PA3">
+    <PinSignal Name="USART2_RX">
+      <SpecificParameter Name="GPIO_AF"><PossibleValue>GPIO_AF7_USART2</PossibleValue></SpecificParameter>
+    </PinSignal>
+  </GPIO_Pin>
+</IP>
+"#;
+
+/// Write the CubeMX fixture in the `db/mcu` layout
+#[cfg(test)]
+pub(crate) fn write_cubemx(dir: &Path) -> PathBuf {
+    std::fs::create_dir_all(dir.join("IP")).unwrap();
+    std::fs::write(
+        dir.join("IP").join("GPIO-STM32F401_gpio_v1_0_Modes.xml"),
+        TEST_CUBEMX_MODES,
+    )
+    .unwrap();
+    let path = dir.join("STM32F401R(D-E)Tx.xml");
+    std::fs::write(&path, TEST_CUBEMX_MCU).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn af(family: &Family, pin: &str, signal: &str) -> Option<u8> {
+        family
+            .candidates(signal)
+            .into_iter()
+            .find(|f| f.pin == pin)
+            .and_then(|f| f.af)
+    }
+
+    #[test]
+    fn test_bundled_families() {
+        let families = bundled();
+        assert_eq!(families.len(), 6);
+        for family in &families {
+            assert!(!family.notes.is_empty(), "{}", family.name);
+        }
+
+        let f4 = find("STM32F401RE", None).unwrap();
+        assert_eq!(f4.name, "STM32F4");
+        assert_eq!(af(&f4, "PA2", "USART2_TX"), Some(7));
+        assert_eq!(af(&f4, "PC6", "USART6_TX"), Some(8));
+        assert_eq!(af(&f4, "PB3", "I2C2_SDA"), Some(9));
+        assert_eq!(af(&f4, "PA0", "ADC1_IN0"), None);
+
+        let g0 = find("stm32g071rb", None).unwrap();
+        assert_eq!(af(&g0, "PA2", "USART2_TX"), Some(1));
+        assert_eq!(af(&g0, "PB6", "USART1_TX"), Some(0));
+
+        let pico = find("Raspberry Pi Pico", None).unwrap();
+        assert_eq!(pico.name, "RP2040");
+        assert_eq!(af(&pico, "GPIO4", "UART1_TX"), Some(2));
+        assert_eq!(af(&pico, "GPIO12", "UART0_TX"), Some(2));
+        assert_eq!(af(&pico, "GPIO10", "SPI1_SCK"), Some(1));
+        assert_eq!(af(&pico, "GPIO15", "PWM7_B"), Some(4));
+
+        let uno = find("Arduino Uno", None).unwrap();
+        assert_eq!(uno.name, "ATmega328P");
+        assert_eq!(uno.candidates("TWI_SDA")[0].pin, "PC4");
+
+        let esp = find("ESP32-WROOM-32", None).unwrap();
+        assert!(esp.candidates("I2C1_SDA").iter().all(|f| f.is_routable()));
+        assert!(find("nrf52840", None).unwrap().candidates("SPIM3_SCK")[0].is_routable());
+
+        assert!(find("PIC16F84", None)
+            .unwrap_err()
+            .contains("Bundled families"));
+    }
+
+    #[test]
+    fn test_classes_and_pins() {
+        let f = |signal: &str| PinFunction {
+            pin: "PA0".to_string(),
+            signal: signal.to_string(),
+            af: None,
+        };
+        assert_eq!(f("USART2_TX").class(), "UART");
+        assert_eq!(f("LPUART1_RX").class(), "UART");
+        assert_eq!(f("TWIM0_SCL").class(), "I2C");
+        assert_eq!(f("TIM3_CH2").class(), "PWM");
+        assert_eq!(f("TIM3_ETR").class(), "TIM");
+        assert_eq!(f("TC1_OC1A").class(), "PWM");
+        assert_eq!(f("ADC1_IN0").instance(), "ADC1");
+        assert_eq!(f("TIM1_CH1N").function(), "CH1N");
+        assert_eq!(peripheral_class("usart"), "UART");
+        assert_eq!(peripheral_class("timer"), "PWM");
+
+        let esp = find("esp32", None).unwrap();
+        assert_eq!(esp.pin("4"), Some("GPIO4"));
+        assert_eq!(find("stm32f411", None).unwrap().pin("pa2"), Some("PA2"));
+    }
+
+    #[test]
+    fn test_ref_name_matching() {
+        assert!(ref_name_matches("STM32F401C(B-C)Ux", "STM32F401CCU6"));
+        assert!(ref_name_matches("STM32F401C(B-C)Ux", "stm32f401cbu"));
+        assert!(!ref_name_matches("STM32F401C(B-C)Ux", "STM32F401CEU6"));
+        assert!(ref_name_matches("STM32G071R(6-8)Tx", "STM32G071R8"));
+        assert!(!ref_name_matches("STM32G071R(6-8)Tx", "STM32G071"));
+    }
+
+    #[test]
+    fn test_user_tables() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("board.csv"),
+            "# family: MyBoard\n# aliases: my-board\npin,signal,af\nPB8,i2c1_scl,AF4\nPB9,I2C1_SDA,4\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("other.json"),
+            r#"{"family": "Other", "pins": [{"pin": "P1", "signal": "UART0_TX"}]}"#,
+        )
+        .unwrap();
+
+        let board = find("My Board rev B", Some(dir.path())).unwrap();
+        assert_eq!(board.name, "MyBoard");
+        assert_eq!(af(&board, "PB8", "I2C1_SCL"), Some(4));
+        let other = find("other", Some(dir.path())).unwrap();
+        assert_eq!(other.candidates("UART0_TX")[0].af, None);
+        // Unknown to the user tables, still found among the bundled ones
+        assert_eq!(find("rp2040", Some(dir.path())).unwrap().name, "RP2040");
+
+        assert!(Family::parse_csv("PA1\n", "bad.csv").is_err());
+        assert!(Family::parse_csv("PA1,USART1_TX,AFx\n", "bad.csv").is_err());
+    }
+
+    #[test]
+    fn test_cubemx_import() {
+        let dir = TempDir::new().unwrap();
+        let path = write_cubemx(dir.path());
+
+        let family = Family::load(&path).unwrap();
+        assert_eq!(family.name, "STM32F401R(D-E)Tx");
+        assert_eq!(family.pins(), ["PC14", "PA2", "PA3"]);
+        assert_eq!(af(&family, "PA2", "USART2_TX"), Some(7));
+        assert_eq!(af(&family, "PA2", "TIM2_CH3"), Some(1));
+        assert_eq!(af(&family, "PA2", "ADC1_IN2"), None);
+        assert!(family.candidates("GPIO").is_empty());
+
+        // Directory search picks the MCU file by name and ignores the IP folder
+        let found = find("STM32F401RET6", Some(dir.path())).unwrap();
+        assert_eq!(found.ref_name.as_deref(), Some("STM32F401R(D-E)Tx"));
+
+        let without_modes = Family::parse_cubemx(TEST_CUBEMX_MCU, None, "mcu.xml").unwrap();
+        assert_eq!(af(&without_modes, "PA2", "USART2_TX"), None);
+        assert!(Family::parse_cubemx(TEST_CUBEMX_MODES, None, "x.xml").is_err());
+    }
+}
@@ -0,0 +1,937 @@
+//! Pin assignment solver.
+//!
+//! Turns a requirement set such as `USART1 + I2C1 + SPI2 + 3 PWM channels +
+//! 2 ADC inputs, avoid PA13/PA14` into slots (one per signal that needs a
+//! pin) and backtracks over the pin database until every slot has a pin, no
+//! pin or signal is used twice and each bus uses a single instance. When no
+//! assignment exists, a minimal set of clashing requirements is reported.
+
+use super::pin_database::{instance_matches, peripheral_class, Family, PinFunction};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Search nodes visited before giving up
+const MAX_STEPS: usize = 200_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Requirement {
+    /// All roles of one bus instance; `instance` None lets the solver pick one
+    Bus {
+        label: String,
+        class: String,
+        instance: Option<String>,
+        /// Each role lists accepted function names, e.g. `["MISO", "RX"]`
+        roles: Vec<Vec<String>>,
+    },
+    /// Distinct channels of a class (PWM, ADC)
+    Channels {
+        label: String,
+        class: String,
+        count: usize,
+    },
+    /// One named signal, optionally fixed to a pin
+    Signal {
+        label: String,
+        signal: String,
+        pin: Option<String>,
+    },
+    /// Plain GPIO pins
+    Gpio { label: String, count: usize },
+}
+
+impl Requirement {
+    pub fn label(&self) -> &str {
+        match self {
+            Requirement::Bus { label, .. }
+            | Requirement::Channels { label, .. }
+            | Requirement::Signal { label, .. }
+            | Requirement::Gpio { label, .. } => label,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Assignment {
+    pub requirement: String,
+    pub signal: String,
+    /// Physical pin, or `*` for signals routed through a GPIO matrix
+    pub pin: String,
+    pub af: Option<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Solution {
+    Solved(Vec<Assignment>),
+    Conflict {
+        /// Requirements that cannot be met together; dropping any one fixes the rest
+        clashing: Vec<String>,
+        /// Why the clashing requirements fail
+        reasons: Vec<String>,
+        /// Assignment for everything except the requirement named in the tuple
+        partial: Option<(String, Vec<Assignment>)>,
+    },
+}
+
+/// Roles a bus needs when the user names only the instance
+fn default_roles(class: &str) -> Option<Vec<Vec<String>>> {
+    let roles: &[&[&str]] = match class {
+        "UART" => &[&["TX", "TXD"], &["RX", "RXD"]],
+        "I2C" => &[&["SCL"], &["SDA"]],
+        "SPI" => &[
+            &["SCK", "CLK"],
+            &["MISO", "RX", "CIPO"],
+            &["MOSI", "TX", "COPI"],
+        ],
+        "CAN" => &[&["TX"], &["RX"]],
+        "USB" => &[&["DM"], &["DP"]],
+        _ => return None,
+    };
+    Some(
+        roles
+            .iter()
+            .map(|alts| alts.iter().map(|a| a.to_string()).collect())
+            .collect(),
+    )
+}
+
+/// Split on `+ , ; newline` and ` and `, keeping `USART2(TX, RTS)` together
+fn split_items(text: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in text.replace(" and ", "+").chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | ',' | ';' | '\n' if depth == 0 => {
+                items.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    items.push(current);
+    items
+        .into_iter()
+        .map(|i| i.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .collect()
+}
+
+/// `3 PWM channels`, `2x ADC`, `PWM x 3` -> (count, word)
+fn counted(item: &str) -> Option<(usize, String)> {
+    let words: Vec<&str> = item
+        .split(|c: char| c.is_whitespace() || c == '*')
+        .filter(|w| !w.is_empty() && !w.eq_ignore_ascii_case("x"))
+        .collect();
+    let number = |n: &str| n.trim_end_matches(['x', 'X']).parse::<usize>().ok();
+    match words.as_slice() {
+        [n, word, ..] if number(n).is_some() => number(n).map(|n| (n, word.to_string())),
+        [word, n] => number(n).map(|n| (n, word.to_string())),
+        _ => None,
+    }
+}
+
+/// Parse a requirement set; returns the requirements and the pins to avoid
+pub fn parse_requirements(
+    text: &str,
+    family: &Family,
+) -> Result<(Vec<Requirement>, Vec<String>), String> {
+    let mut requirements = Vec::new();
+    let mut avoid: Vec<String> = Vec::new();
+    let mut avoiding = false;
+    let classes = family.classes();
+
+    for item in split_items(text) {
+        let lower = item.to_lowercase();
+        let item = match lower.strip_prefix("avoid") {
+            Some(_) => {
+                avoiding = true;
+                item[5..].trim().to_string()
+            }
+            None => item,
+        };
+        // Pins after `avoid`: `PA13/PA14`, `PA13 PA14`, `PA13, PA14`
+        let pins: Vec<Option<&str>> = item
+            .split(['/', ' '])
+            .filter(|p| !p.is_empty())
+            .map(|p| family.pin(p))
+            .collect();
+        if avoiding && !pins.is_empty() && pins.iter().all(Option::is_some) {
+            avoid.extend(pins.into_iter().flatten().map(str::to_string));
+            continue;
+        }
+        if item.is_empty() {
+            continue;
+        }
+        avoiding = false;
+        requirements.push(parse_item(&item, family, &classes)?);
+    }
+    if requirements.is_empty() {
+        return Err("No requirements given".to_string());
+    }
+    Ok((requirements, avoid))
+}
+
+fn parse_item(item: &str, family: &Family, classes: &[String]) -> Result<Requirement, String> {
+    let label = item.to_string();
+
+    // SIGNAL=PIN fixes a signal to a pin
+    if let Some((signal, pin)) = item.split_once(['=', '@']) {
+        let signal = signal.trim().to_uppercase();
+        let pin = family
+            .pin(pin.trim())
+            .ok_or_else(|| format!("'{}': {} has no pin {}", item, family.name, pin.trim()))?;
+        if family.candidates(&signal).is_empty() {
+            return Err(format!(
+                "'{}': {} has no signal {}",
+                item, family.name, signal
+            ));
+        }
+        return Ok(Requirement::Signal {
+            label,
+            signal,
+            pin: Some(pin.to_string()),
+        });
+    }
+
+    // USART2(TX, RX, RTS) lists the functions explicitly
+    if let Some((instance, functions)) = item.split_once('(') {
+        let instance = instance.trim();
+        let function = find_instance(family, instance)
+            .ok_or_else(|| format!("'{}': {} has no instance {}", item, family.name, instance))?;
+        let roles = functions
+            .trim_end_matches(')')
+            .split([',', ' ', '/'])
+            .filter(|f| !f.is_empty())
+            .map(|f| vec![f.trim().to_uppercase()])
+            .collect();
+        return Ok(Requirement::Bus {
+            label,
+            class: function.class(),
+            instance: Some(function.instance().to_string()),
+            roles,
+        });
+    }
+
+    let (count, word) = counted(item).unwrap_or((1, item.to_string()));
+    let class = peripheral_class(&word);
+
+    if class == "GPIO" {
+        return Ok(Requirement::Gpio { label, count });
+    }
+    if count == 1 && !family.candidates(&word).is_empty() {
+        return Ok(Requirement::Signal {
+            label,
+            signal: word.to_uppercase(),
+            pin: None,
+        });
+    }
+    if let Some(function) = find_instance(family, &word) {
+        let class = function.class();
+        let roles = default_roles(&class).ok_or_else(|| {
+            format!(
+                "'{}': name the {} signals, e.g. {}({}) or {}",
+                item,
+                function.instance(),
+                function.instance(),
+                function.function(),
+                function.signal
+            )
+        })?;
+        return Ok(Requirement::Bus {
+            label,
+            class,
+            instance: Some(function.instance().to_string()),
+            roles,
+        });
+    }
+    if classes.contains(&class) {
+        return Ok(match default_roles(&class) {
+            Some(roles) if count == 1 => Requirement::Bus {
+                label,
+                class,
+                instance: None,
+                roles,
+            },
+            Some(_) => {
+                return Err(format!(
+                    "'{}': list {} buses separately (e.g. {} + {})",
+                    item, class, class, class
+                ))
+            }
+            None => Requirement::Channels {
+                label,
+                class,
+                count,
+            },
+        });
+    }
+    Err(format!(
+        "'{}' is not a peripheral, instance or signal of {}. Available peripherals: {}",
+        item,
+        family.name,
+        classes.join(", ")
+    ))
+}
+
+fn find_instance<'a>(family: &'a Family, instance: &str) -> Option<&'a PinFunction> {
+    family
+        .functions
+        .iter()
+        .find(|f| instance_matches(f.instance(), instance))
+}
+
+/// One signal that needs a pin
+#[derive(Clone, Debug)]
+struct Slot {
+    requirement: usize,
+    role: String,
+    candidates: Vec<Candidate>,
+    /// Interchangeable with the other slots of its requirement (channels, GPIO)
+    symmetric: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Candidate {
+    signal: String,
+    instance: String,
+    pin: String,
+    af: Option<u8>,
+    routable: bool,
+}
+
+impl Candidate {
+    fn from(f: &PinFunction) -> Candidate {
+        Candidate {
+            signal: f.signal.clone(),
+            instance: f.instance().to_string(),
+            pin: f.pin.clone(),
+            af: f.af,
+            routable: f.is_routable(),
+        }
+    }
+}
+
+/// Slots for one requirement, or the reason it cannot be met on its own
+fn slots_for(
+    index: usize,
+    requirement: &Requirement,
+    family: &Family,
+    avoid: &[String],
+) -> Result<Vec<Slot>, String> {
+    let allowed = |f: &PinFunction| f.is_routable() || !avoid.contains(&f.pin);
+    let avoided_reason = |what: &str, all: &[&PinFunction]| {
+        format!(
+            "{}: every candidate pin is avoided ({})",
+            what,
+            all.iter()
+                .map(|f| f.pin.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+
+    match requirement {
+        Requirement::Bus {
+            label,
+            class,
+            instance,
+            roles,
+        } => {
+            let mut slots = Vec::new();
+            for role in roles {
+                let all: Vec<&PinFunction> = family
+                    .functions
+                    .iter()
+                    .filter(|f| f.class() == *class)
+                    .filter(|f| instance.as_ref().is_none_or(|i| f.instance() == i))
+                    .filter(|f| role.iter().any(|r| f.function().eq_ignore_ascii_case(r)))
+                    .collect();
+                let role_name = role[0].clone();
+                if all.is_empty() {
+                    return Err(format!(
+                        "{}: {} has no {} signal",
+                        label,
+                        instance.as_deref().unwrap_or(class),
+                        role_name
+                    ));
+                }
+                let candidates: Vec<Candidate> = all
+                    .iter()
+                    .filter(|f| allowed(f))
+                    .map(|f| Candidate::from(f))
+                    .collect();
+                if candidates.is_empty() {
+                    return Err(avoided_reason(&format!("{} {}", label, role_name), &all));
+                }
+                slots.push(Slot {
+                    requirement: index,
+                    role: role_name,
+                    candidates,
+                    symmetric: false,
+                });
+            }
+            Ok(slots)
+        }
+        Requirement::Channels {
+            label,
+            class,
+            count,
+        } => {
+            // Complementary outputs (TIM1_CH1N) share their channel's compare value
+            let all: Vec<&PinFunction> = family
+                .functions
+                .iter()
+                .filter(|f| f.class() == *class)
+                .filter(|f| !(f.function().starts_with("CH") && f.function().ends_with('N')))
+                .collect();
+            let candidates: Vec<Candidate> = all
+                .iter()
+                .filter(|f| allowed(f))
+                .map(|f| Candidate::from(f))
+                .collect();
+            let signals = all
+                .iter()
+                .map(|f| f.signal.as_str())
+                .collect::<std::collections::HashSet<_>>()
+                .len();
+            if signals < *count {
+                return Err(format!(
+                    "{}: {} has only {} {} channels",
+                    label, family.name, signals, class
+                ));
+            }
+            if candidates.is_empty() {
+                return Err(avoided_reason(label, &all));
+            }
+            Ok((0..*count)
+                .map(|i| Slot {
+                    requirement: index,
+                    role: format!("{} #{}", class, i + 1),
+                    candidates: candidates.clone(),
+                    symmetric: true,
+                })
+                .collect())
+        }
+        Requirement::Signal { label, signal, pin } => {
+            let all = family.candidates(signal);
+            let candidates: Vec<Candidate> = all
+                .iter()
+                .filter(|f| allowed(f))
+                .filter(|f| pin.as_ref().is_none_or(|p| f.pin == *p || f.is_routable()))
+                .map(|f| {
+                    let mut c = Candidate::from(f);
+                    // A routable signal fixed to a pin uses that pin
+                    if let Some(p) = pin {
+                        c.pin = p.clone();
+                        c.routable = false;
+                    }
+                    c
+                })
+                .collect();
+            if candidates.is_empty() {
+                return Err(match pin {
+                    Some(p) if avoid.contains(p) => format!("{}: pin {} is avoided", label, p),
+                    Some(p) => format!(
+                        "{}: {} is not available on {} (candidates: {})",
+                        label,
+                        signal,
+                        p,
+                        all.iter()
+                            .map(|f| f.pin.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    None => avoided_reason(label, &all),
+                });
+            }
+            Ok(vec![Slot {
+                requirement: index,
+                role: signal.clone(),
+                candidates,
+                symmetric: false,
+            }])
+        }
+        Requirement::Gpio { label, count } => {
+            let candidates: Vec<Candidate> = family
+                .pins()
+                .into_iter()
+                .filter(|p| !avoid.iter().any(|a| a == p))
+                .map(|p| Candidate {
+                    signal: format!("GPIO:{}", p),
+                    instance: String::new(),
+                    pin: p.to_string(),
+                    af: None,
+                    routable: false,
+                })
+                .collect();
+            if candidates.len() < *count {
+                return Err(format!(
+                    "{}: only {} free pins in {}",
+                    label,
+                    candidates.len(),
+                    family.name
+                ));
+            }
+            Ok((0..*count)
+                .map(|i| Slot {
+                    requirement: index,
+                    role: format!("GPIO #{}", i + 1),
+                    candidates: candidates.clone(),
+                    symmetric: true,
+                })
+                .collect())
+        }
+    }
+}
+
+enum Outcome {
+    Found(Vec<usize>),
+    Unsatisfiable,
+    Exhausted,
+}
+
+struct Search<'a> {
+    slots: &'a [Slot],
+    bus: Vec<bool>,
+    chosen: Vec<usize>,
+    pins: HashMap<&'a str, usize>,
+    signals: HashMap<&'a str, usize>,
+    /// Requirement -> instance it has committed to, with the number of slots using it
+    instances: HashMap<usize, (&'a str, usize)>,
+    steps: usize,
+}
+
+impl<'a> Search<'a> {
+    fn fits(&self, slot: &Slot, c: &'a Candidate) -> bool {
+        if !c.routable && self.pins.contains_key(c.pin.as_str()) {
+            return false;
+        }
+        if self.signals.contains_key(c.signal.as_str()) {
+            return false;
+        }
+        if self.bus[slot.requirement] {
+            if let Some((instance, _)) = self.instances.get(&slot.requirement) {
+                if *instance != c.instance {
+                    return false;
+                }
+            }
+            // An instance serves a single bus requirement
+            if self
+                .instances
+                .iter()
+                .any(|(req, (i, _))| *req != slot.requirement && *i == c.instance)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn run(&mut self, depth: usize) -> Option<bool> {
+        if depth == self.slots.len() {
+            return Some(true);
+        }
+        let slots = self.slots;
+        let slot = &slots[depth];
+        // Interchangeable slots take candidates in increasing order
+        let first = match depth.checked_sub(1) {
+            Some(prev) if slot.symmetric && slots[prev].requirement == slot.requirement => {
+                self.chosen[prev] + 1
+            }
+            _ => 0,
+        };
+        for (i, c) in slot.candidates.iter().enumerate().skip(first) {
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return None;
+            }
+            if !self.fits(slot, c) {
+                continue;
+            }
+            if !c.routable {
+                self.pins.insert(&c.pin, depth);
+            }
+            self.signals.insert(&c.signal, depth);
+            if self.bus[slot.requirement] {
+                self.instances
+                    .entry(slot.requirement)
+                    .or_insert((&c.instance, 0))
+                    .1 += 1;
+            }
+            self.chosen.push(i);
+
+            match self.run(depth + 1) {
+                Some(true) => return Some(true),
+                None => return None,
+                Some(false) => {}
+            }
+
+            self.chosen.pop();
+            if self.bus[slot.requirement] {
+                let entry = self.instances.get_mut(&slot.requirement).unwrap();
+                entry.1 -= 1;
+                if entry.1 == 0 {
+                    self.instances.remove(&slot.requirement);
+                }
+            }
+            self.signals.remove(c.signal.as_str());
+            if !c.routable {
+                self.pins.remove(c.pin.as_str());
+            }
+        }
+        Some(false)
+    }
+}
+
+/// Backtracking search over the slots of the selected requirements
+fn search(requirements: &[Requirement], slots: &[Slot]) -> (Outcome, Vec<Slot>) {
+    // Most constrained slots first; slots of one requirement stay together
+    let mut ordered: Vec<Slot> = slots.to_vec();
+    let width = |req: usize| {
+        slots
+            .iter()
+            .filter(|s| s.requirement == req)
+            .map(|s| s.candidates.len())
+            .min()
+            .unwrap_or(0)
+    };
+    ordered.sort_by_key(|s| (width(s.requirement), s.requirement));
+
+    let mut search = Search {
+        slots: &ordered,
+        bus: requirements
+            .iter()
+            .map(|r| matches!(r, Requirement::Bus { .. }))
+            .collect(),
+        chosen: Vec::new(),
+        pins: HashMap::new(),
+        signals: HashMap::new(),
+        instances: HashMap::new(),
+        steps: 0,
+    };
+    let outcome = match search.run(0) {
+        Some(true) => Outcome::Found(search.chosen.clone()),
+        Some(false) => Outcome::Unsatisfiable,
+        None => Outcome::Exhausted,
+    };
+    (outcome, ordered)
+}
+
+fn assignments(requirements: &[Requirement], slots: &[Slot], chosen: &[usize]) -> Vec<Assignment> {
+    let mut result: Vec<(usize, Assignment)> = slots
+        .iter()
+        .zip(chosen)
+        .map(|(slot, &i)| {
+            let c = &slot.candidates[i];
+            let signal = if c.signal.starts_with("GPIO:") {
+                "GPIO".to_string()
+            } else {
+                c.signal.clone()
+            };
+            (
+                slot.requirement,
+                Assignment {
+                    requirement: requirements[slot.requirement].label().to_string(),
+                    signal,
+                    pin: c.pin.clone(),
+                    af: c.af,
+                },
+            )
+        })
+        .collect();
+    result.sort_by_key(|(req, _)| *req);
+    result.into_iter().map(|(_, a)| a).collect()
+}
+
+/// Solve the subset of requirements selected by `active`
+fn solve_subset(
+    requirements: &[Requirement],
+    slots: &[Vec<Slot>],
+    active: &[usize],
+) -> Result<Option<Vec<Assignment>>, String> {
+    let selected: Vec<Slot> = active.iter().flat_map(|&i| slots[i].clone()).collect();
+    match search(requirements, &selected) {
+        (Outcome::Found(chosen), ordered) => Ok(Some(assignments(requirements, &ordered, &chosen))),
+        (Outcome::Unsatisfiable, _) => Ok(None),
+        (Outcome::Exhausted, _) => Err(format!(
+            "Pin search gave up after {} steps; split the requirement set or fix some signals to pins",
+            MAX_STEPS
+        )),
+    }
+}
+
+/// Candidate pins of a requirement, for conflict explanations
+fn candidate_pins(slots: &[Slot]) -> Vec<String> {
+    let mut pins: Vec<String> = Vec::new();
+    for c in slots.iter().flat_map(|s| &s.candidates) {
+        if !c.routable && !pins.contains(&c.pin) {
+            pins.push(c.pin.clone());
+        }
+    }
+    pins
+}
+
+pub fn solve(
+    family: &Family,
+    requirements: &[Requirement],
+    avoid: &[String],
+) -> Result<Solution, String> {
+    let mut slots = Vec::new();
+    let mut reasons = Vec::new();
+    for (i, requirement) in requirements.iter().enumerate() {
+        match slots_for(i, requirement, family, avoid) {
+            Ok(s) => slots.push(s),
+            Err(reason) => {
+                reasons.push((i, reason));
+                slots.push(Vec::new());
+            }
+        }
+    }
+    if !reasons.is_empty() {
+        let feasible: Vec<usize> = (0..requirements.len())
+            .filter(|i| !reasons.iter().any(|(r, _)| r == i))
+            .collect();
+        let partial = match (
+            reasons.as_slice(),
+            solve_subset(requirements, &slots, &feasible)?,
+        ) {
+            ([(dropped, _)], Some(assignment)) => {
+                Some((requirements[*dropped].label().to_string(), assignment))
+            }
+            _ => None,
+        };
+        return Ok(Solution::Conflict {
+            clashing: reasons
+                .iter()
+                .map(|(i, _)| requirements[*i].label().to_string())
+                .collect(),
+            reasons: reasons.into_iter().map(|(_, r)| r).collect(),
+            partial,
+        });
+    }
+
+    let all: Vec<usize> = (0..requirements.len()).collect();
+    if let Some(assignment) = solve_subset(requirements, &slots, &all)? {
+        return Ok(Solution::Solved(assignment));
+    }
+
+    // Shrink to a minimal clashing set: drop requirements the conflict survives without
+    let mut core = all.clone();
+    for i in all.iter().rev() {
+        let without: Vec<usize> = core.iter().copied().filter(|j| j != i).collect();
+        if solve_subset(requirements, &slots, &without)?.is_none() {
+            core = without;
+        }
+    }
+
+    let mut reasons = Vec::new();
+    for (a, &i) in core.iter().enumerate() {
+        for &j in &core[a + 1..] {
+            let pins_i = candidate_pins(&slots[i]);
+            let shared: Vec<String> = candidate_pins(&slots[j])
+                .into_iter()
+                .filter(|p| pins_i.contains(p))
+                .collect();
+            if !shared.is_empty() {
+                reasons.push(format!(
+                    "{} and {} compete for {}",
+                    requirements[i].label(),
+                    requirements[j].label(),
+                    shared.join(", ")
+                ));
+            }
+        }
+    }
+    if reasons.is_empty() {
+        reasons.push(
+            "The requirements need more distinct instances or channels than exist".to_string(),
+        );
+    }
+    for &i in &core {
+        reasons.push(format!(
+            "{} can use {}",
+            requirements[i].label(),
+            slots[i]
+                .iter()
+                .map(|s| {
+                    let pins: Vec<&str> = s
+                        .candidates
+                        .iter()
+                        .map(|c| {
+                            if c.routable {
+                                "any GPIO"
+                            } else {
+                                c.pin.as_str()
+                            }
+                        })
+                        .collect();
+                    format!("{}: {}", s.role, pins.join("/"))
+                })
+                .collect::<Vec<_>>()
+                .join("; ")
+        ));
+    }
+
+    let dropped = *core.last().unwrap_or(&0);
+    let rest: Vec<usize> = all.iter().copied().filter(|&i| i != dropped).collect();
+    let partial = solve_subset(requirements, &slots, &rest)?
+        .map(|assignment| (requirements[dropped].label().to_string(), assignment));
+
+    Ok(Solution::Conflict {
+        clashing: core
+            .iter()
+            .map(|&i| requirements[i].label().to_string())
+            .collect(),
+        reasons,
+        partial,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::pin_database::find;
+
+    fn pin_of<'a>(assignment: &'a [Assignment], signal: &str) -> &'a str {
+        &assignment.iter().find(|a| a.signal == signal).unwrap().pin
+    }
+
+    #[test]
+    fn test_parse_requirements() {
+        let f4 = find("stm32f401", None).unwrap();
+        let (reqs, avoid) = parse_requirements(
+            "USART1 + I2C1 + SPI2 + 3 PWM channels + 2 ADC inputs, avoid PA13/PA14",
+            &f4,
+        )
+        .unwrap();
+        assert_eq!(avoid, ["PA13", "PA14"]);
+        assert_eq!(reqs.len(), 5);
+        assert!(
+            matches!(&reqs[0], Requirement::Bus { instance: Some(i), roles, .. } if i == "USART1" && roles.len() == 2)
+        );
+        assert!(matches!(&reqs[2], Requirement::Bus { roles, .. } if roles.len() == 3));
+        assert!(
+            matches!(&reqs[3], Requirement::Channels { class, count: 3, .. } if class == "PWM")
+        );
+        assert!(
+            matches!(&reqs[4], Requirement::Channels { class, count: 2, .. } if class == "ADC")
+        );
+
+        let (reqs, _) =
+            parse_requirements("UART2(TX, RTS); TIM2_CH1=PA5; 2x GPIO and I2C", &f4).unwrap();
+        assert!(
+            matches!(&reqs[0], Requirement::Bus { instance: Some(i), roles, .. } if i == "USART2" && roles.len() == 2)
+        );
+        assert!(matches!(&reqs[1], Requirement::Signal { pin: Some(p), .. } if p == "PA5"));
+        assert!(matches!(&reqs[2], Requirement::Gpio { count: 2, .. }));
+        assert!(matches!(&reqs[3], Requirement::Bus { instance: None, .. }));
+
+        assert!(parse_requirements("CAN1", &f4)
+            .unwrap_err()
+            .contains("Available peripherals"));
+        assert!(parse_requirements("TIM3", &f4)
+            .unwrap_err()
+            .contains("TIM3_CH"));
+        assert!(parse_requirements("USART2_TX=PZ9", &f4).is_err());
+    }
+
+    #[test]
+    fn test_solves_board_requirements() {
+        let f4 = find("stm32f401", None).unwrap();
+        let (reqs, avoid) = parse_requirements(
+            "USART1 + I2C1 + SPI2 + 3 PWM channels + 2 ADC inputs + USART2, avoid PA13/PA14",
+            &f4,
+        )
+        .unwrap();
+        let Solution::Solved(assignment) = solve(&f4, &reqs, &avoid).unwrap() else {
+            panic!("Expected a solution");
+        };
+        assert_eq!(assignment.len(), 2 + 2 + 3 + 3 + 2 + 2);
+        let mut pins: Vec<&str> = assignment.iter().map(|a| a.pin.as_str()).collect();
+        pins.sort();
+        pins.dedup();
+        assert_eq!(
+            pins.len(),
+            assignment.len(),
+            "pins reused: {:?}",
+            assignment
+        );
+        assert!(!pins.contains(&"PA13") && !pins.contains(&"PA14"));
+        // USART1 and I2C1 share PB6/PB7, so they must end up on different pins
+        assert_ne!(
+            pin_of(&assignment, "USART1_TX"),
+            pin_of(&assignment, "I2C1_SCL")
+        );
+        let usart1 = assignment.iter().find(|a| a.signal == "USART1_TX").unwrap();
+        assert_eq!(usart1.af, Some(7));
+    }
+
+    #[test]
+    fn test_instance_consistency_and_routable() {
+        let f4 = find("stm32f401", None).unwrap();
+        let (reqs, avoid) = parse_requirements("I2C + I2C + I2C", &f4).unwrap();
+        let Solution::Solved(assignment) = solve(&f4, &reqs, &avoid).unwrap() else {
+            panic!("Expected a solution");
+        };
+        let instances: Vec<&str> = assignment
+            .iter()
+            .map(|a| a.signal.split('_').next().unwrap())
+            .collect();
+        assert_eq!(instances[0], instances[1]);
+        assert_eq!(instances[2], instances[3]);
+        assert_ne!(instances[0], instances[2]);
+        assert_ne!(instances[2], instances[4]);
+
+        let esp = find("esp32", None).unwrap();
+        let (reqs, avoid) = parse_requirements("I2C0 + I2C1 + UART2 + 4 PWM", &esp).unwrap();
+        let Solution::Solved(assignment) = solve(&esp, &reqs, &avoid).unwrap() else {
+            panic!("Expected a solution");
+        };
+        assert_eq!(pin_of(&assignment, "I2C0_SDA"), "GPIO21");
+        assert_eq!(pin_of(&assignment, "I2C1_SDA"), "*");
+    }
+
+    #[test]
+    fn test_explains_conflicts() {
+        let uno = find("atmega328p", None).unwrap();
+        // SPI MOSI (PB3) is also OC2A; fixing OC2A there leaves SPI without MOSI
+        let (reqs, avoid) = parse_requirements("SPI + TC2_OC2A + UART", &uno).unwrap();
+        let Solution::Conflict {
+            clashing,
+            reasons,
+            partial,
+        } = solve(&uno, &reqs, &avoid).unwrap()
+        else {
+            panic!("Expected a conflict");
+        };
+        assert_eq!(clashing, ["SPI", "TC2_OC2A"]);
+        assert!(
+            reasons[0].contains("SPI and TC2_OC2A compete for PB3"),
+            "{:?}",
+            reasons
+        );
+        let (dropped, assignment) = partial.unwrap();
+        assert_eq!(dropped, "TC2_OC2A");
+        assert_eq!(pin_of(&assignment, "USART0_TXD"), "PD1");
+
+        let f4 = find("stm32f401", None).unwrap();
+        let (reqs, avoid) = parse_requirements("USART2, I2C1, avoid PA2 PD5", &f4).unwrap();
+        let Solution::Conflict { reasons, .. } = solve(&f4, &reqs, &avoid).unwrap() else {
+            panic!("Expected a conflict");
+        };
+        assert!(
+            reasons[0].contains("every candidate pin is avoided (PA2, PD5)"),
+            "{:?}",
+            reasons
+        );
+
+        let (reqs, avoid) = parse_requirements("20 ADC", &f4).unwrap();
+        let Solution::Conflict { reasons, .. } = solve(&f4, &reqs, &avoid).unwrap() else {
+            panic!("Expected a conflict");
+        };
+        assert!(reasons[0].contains("only 16 ADC channels"));
+    }
+}
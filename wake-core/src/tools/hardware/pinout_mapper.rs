@@ -1,7 +1,14 @@
+use super::package::{self, PackageFile};
+use super::pin_codegen::{self, CodegenFormat, GeneratedFile};
+use super::pin_database::{self, instance_matches, peripheral_class, Family, PinFunction};
+use super::pin_solver::{self, Assignment, Requirement, Solution};
 use crate::tools::{Tool, ToolCapability, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
 use wake_llm::ToolDescription;
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -9,11 +16,149 @@ pub struct PinoutMapperArgs {
     /// Microcontroller or board name
     pub device: String,
 
-    /// Peripheral to map (UART, I2C, SPI, PWM, ADC, GPIO)
+    /// Peripheral to map (UART, I2C, SPI, PWM, ADC, GPIO), a pin name (PA2, GPIO4) to list its functions,
+    /// or a requirement set to assign together (e.g. "USART1 + I2C1 + SPI2 + 3 PWM + 2 ADC, avoid PA13/PA14")
     pub peripheral: String,
 
     /// Optional: Specific instance (UART1, I2C2, etc.)
     pub instance: Option<String>,
+
+    /// Optional: Extra pin database: JSON/CSV pin table, STM32CubeMX MCU XML file, or a directory of them
+    pub database: Option<String>,
+
+    /// Optional: Pins to keep free when assigning a requirement set
+    pub avoid: Option<Vec<String>>,
+
+    /// Optional: Init code to generate from the assignment: stm32-hal (MX_GPIO_Init C), embassy (embassy-stm32 Rust),
+    /// esp-idf (gpio_config_t C) or zephyr (.overlay with pinctrl nodes)
+    pub codegen: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PinoutCodegenArgs {
+    /// Microcontroller or board name
+    pub device: String,
+
+    /// Requirement set to assign (e.g. "USART1 + I2C1 + 3 PWM, avoid PA13/PA14")
+    pub peripheral: String,
+
+    /// Optional: Extra pin database: JSON/CSV pin table, STM32CubeMX MCU XML file, or a directory of them
+    pub database: Option<String>,
+
+    /// Optional: Pins to keep free
+    pub avoid: Option<Vec<String>>,
+
+    /// Init code to generate: stm32-hal, embassy, esp-idf or zephyr
+    pub codegen: Vec<String>,
+
+    /// Directory to write the generated files to
+    pub output_dir: String,
+}
+
+impl PinoutCodegenArgs {
+    /// The same request as `pinout_mapper` sees it
+    fn mapping(&self) -> PinoutMapperArgs {
+        PinoutMapperArgs {
+            device: self.device.clone(),
+            peripheral: self.peripheral.clone(),
+            instance: None,
+            database: self.database.clone(),
+            avoid: self.avoid.clone(),
+            codegen: Some(self.codegen.clone()),
+        }
+    }
+}
+
+/// Several requirements (`USART1 + I2C1`), counts (`3 PWM`), fixed pins or an avoid list
+fn is_requirement_set(args: &PinoutMapperArgs) -> bool {
+    let text = args.peripheral.trim();
+    args.avoid.is_some()
+        || args.codegen.is_some()
+        || text.contains(['+', ',', ';', '\n', '(', '='])
+        || text.to_lowercase().contains("avoid")
+        || text.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn assignment_table(assignment: &[Assignment]) -> String {
+    let mut out =
+        "| Requirement | Signal | Pin | AF |\n|-------------|--------|-----|----|\n".to_string();
+    for a in assignment {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            a.requirement,
+            a.signal,
+            if a.pin == pin_database::ANY_PIN {
+                "any free GPIO"
+            } else {
+                &a.pin
+            },
+            a.af.map_or("-".to_string(), |af| format!("AF{}", af))
+        ));
+    }
+    out
+}
+
+fn codegen_formats(args: &PinoutMapperArgs) -> Result<Vec<CodegenFormat>, String> {
+    let mut formats = Vec::new();
+    for name in args.codegen.iter().flatten() {
+        let format = CodegenFormat::parse(name).ok_or_else(|| {
+            format!(
+                "Unknown codegen format '{}'. Available: {}",
+                name,
+                CodegenFormat::all()
+                    .iter()
+                    .map(|f| f.label())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    if formats.is_empty() {
+        return Err("codegen needs at least one format".to_string());
+    }
+    Ok(formats)
+}
+
+fn fence_language(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("rs") => "rust",
+        Some("overlay") => "dts",
+        _ => "c",
+    }
+}
+
+fn package_files(files: &[GeneratedFile]) -> Vec<PackageFile> {
+    files
+        .iter()
+        .map(|f| PackageFile::new(f.path.clone(), f.content.clone()))
+        .collect()
+}
+
+async fn load_family(args: &PinoutMapperArgs) -> Result<Family, String> {
+    let device = args.device.clone();
+    let database = args.database.clone();
+    // CubeMX databases hold thousands of XML files
+    tokio::task::spawn_blocking(move || {
+        pin_database::find(&device, database.as_deref().map(Path::new))
+    })
+    .await
+    .map_err(|e| format!("Pin database lookup failed: {}", e))?
+}
+
+/// Pin list for one signal, e.g. `PA2 (AF7), PD5 (AF7)`
+fn pin_list(candidates: &[&PinFunction]) -> String {
+    candidates
+        .iter()
+        .map(|f| match (f.is_routable(), f.af) {
+            (true, _) => "any GPIO".to_string(),
+            (false, Some(af)) => format!("{} (AF{})", f.pin, af),
+            (false, None) => f.pin.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 pub struct PinoutMapper;
@@ -22,6 +167,267 @@ impl PinoutMapper {
     pub fn new() -> Self {
         Self
     }
+
+    fn header(&self, args: &PinoutMapperArgs, family: &Family) -> String {
+        format!(
+            "## Pinout Mapping for {}\n\nPin database: {} ({})\n",
+            args.device, family.name, family.source
+        )
+    }
+
+    fn notes(&self, family: &Family) -> String {
+        if family.notes.is_empty() {
+            return String::new();
+        }
+        let mut out = "\n### Notes\n\n".to_string();
+        for note in &family.notes {
+            out.push_str(&format!("- {}\n", note));
+        }
+        out
+    }
+
+    fn map_pin(&self, args: &PinoutMapperArgs, family: &Family, pin: &str) -> ToolResult {
+        let mut output = self.header(args, family);
+        output.push_str(&format!(
+            "\n### Pin {}\n\n| Signal | Peripheral | AF |\n|--------|------------|----|\n",
+            pin
+        ));
+        let functions = family.functions_on(pin);
+        for f in &functions {
+            output.push_str(&format!(
+                "| {} | {} | {} |\n",
+                f.signal,
+                f.class(),
+                f.af.map_or("-".to_string(), |af| format!("AF{}", af))
+            ));
+        }
+        let routable: Vec<&str> = family
+            .functions
+            .iter()
+            .filter(|f| f.is_routable())
+            .map(|f| f.instance())
+            .fold(Vec::new(), |mut acc, i| {
+                if !acc.contains(&i) {
+                    acc.push(i);
+                }
+                acc
+            });
+        if !routable.is_empty() {
+            output.push_str(&format!(
+                "\nAlso routable to this pin: {}\n",
+                routable.join(", ")
+            ));
+        }
+        output.push_str(&self.notes(family));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("family".to_string(), json!(family.name));
+        metadata.insert("pin".to_string(), json!(pin));
+        metadata.insert("functions".to_string(), json!(functions));
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    /// Parse and solve the requirement set in `args.peripheral`
+    fn solution(
+        &self,
+        args: &PinoutMapperArgs,
+        family: &Family,
+    ) -> Result<(Vec<Requirement>, Vec<String>, Solution), String> {
+        let (requirements, mut avoid) = pin_solver::parse_requirements(&args.peripheral, family)?;
+        for pin in args.avoid.iter().flatten() {
+            match family.pin(pin) {
+                Some(pin) if !avoid.iter().any(|a| a == pin) => avoid.push(pin.to_string()),
+                Some(_) => {}
+                None => return Err(format!("{} has no pin {} to avoid", family.name, pin)),
+            }
+        }
+        let solution = pin_solver::solve(family, &requirements, &avoid)?;
+        Ok((requirements, avoid, solution))
+    }
+
+    /// Init code for the requested formats; Err when nothing can be generated
+    fn generate(
+        &self,
+        args: &PinoutMapperArgs,
+        family: &Family,
+        assignment: &[Assignment],
+    ) -> Result<Vec<GeneratedFile>, String> {
+        codegen_formats(args)?
+            .into_iter()
+            .map(|format| pin_codegen::generate(format, &args.device, family, assignment))
+            .collect()
+    }
+
+    fn solve(&self, args: &PinoutMapperArgs, family: &Family) -> ToolResult {
+        let (requirements, avoid, solution) = match self.solution(args, family) {
+            Ok(solved) => solved,
+            Err(e) => return ToolResult::error(e),
+        };
+        // A conflict is still explained below, just without code
+        let files = match (&args.codegen, &solution) {
+            (Some(_), Solution::Solved(assignment)) => {
+                match self.generate(args, family, assignment) {
+                    Ok(files) => files,
+                    Err(e) => return ToolResult::error(e),
+                }
+            }
+            _ => Vec::new(),
+        };
+
+        let mut output = self.header(args, family);
+        output.push_str(&format!(
+            "\nRequirements: {}\n",
+            requirements
+                .iter()
+                .map(|r| r.label())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if !avoid.is_empty() {
+            output.push_str(&format!("Avoided pins: {}\n", avoid.join(", ")));
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("family".to_string(), json!(family.name));
+        metadata.insert("avoid".to_string(), json!(avoid));
+        match solution {
+            Solution::Solved(assignment) => {
+                output.push_str("\n### Pin Assignment\n\n");
+                output.push_str(&assignment_table(&assignment));
+                metadata.insert("solved".to_string(), json!(true));
+                metadata.insert("assignment".to_string(), json!(assignment));
+            }
+            Solution::Conflict {
+                clashing,
+                reasons,
+                partial,
+            } => {
+                output.push_str(&format!(
+                    "\n### ❌ No conflict-free assignment\n\nThese requirements cannot be met together: {}\n\n",
+                    clashing.join(", ")
+                ));
+                for reason in &reasons {
+                    output.push_str(&format!("- {}\n", reason));
+                }
+                if let Some((dropped, assignment)) = &partial {
+                    output.push_str(&format!(
+                        "\n### Assignment without {}\n\n{}",
+                        dropped,
+                        assignment_table(assignment)
+                    ));
+                }
+                metadata.insert("solved".to_string(), json!(false));
+                metadata.insert("clashing".to_string(), json!(clashing));
+                metadata.insert("reasons".to_string(), json!(reasons));
+                if let Some((dropped, assignment)) = partial {
+                    metadata.insert("dropped".to_string(), json!(dropped));
+                    metadata.insert("assignment".to_string(), json!(assignment));
+                }
+            }
+        }
+        if args.codegen.is_some() && files.is_empty() {
+            output.push_str("\nNo init code generated until the conflict is resolved.\n");
+        }
+        for file in &files {
+            output.push_str(&format!(
+                "\n### {} ({})\n\n```{}\n{}```\n",
+                file.path,
+                file.format,
+                fence_language(&file.path),
+                file.content
+            ));
+        }
+        if !files.is_empty() {
+            metadata.insert("files".to_string(), json!(files));
+        }
+        output.push_str(&self.notes(family));
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn map_gpio(&self, args: &PinoutMapperArgs, family: &Family) -> ToolResult {
+        let mut output = self.header(args, family);
+        let pins = family.pins();
+        output.push_str(&format!(
+            "\n### GPIO ({} pins)\n\n| Pin | Functions |\n|-----|-----------|\n",
+            pins.len()
+        ));
+        for pin in &pins {
+            let signals: Vec<&str> = family
+                .functions_on(pin)
+                .iter()
+                .map(|f| f.signal.as_str())
+                .collect();
+            output.push_str(&format!("| {} | {} |\n", pin, signals.join(", ")));
+        }
+        output.push_str(&self.notes(family));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("family".to_string(), json!(family.name));
+        metadata.insert("pins".to_string(), json!(pins));
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn map_peripheral(&self, args: &PinoutMapperArgs, family: &Family) -> ToolResult {
+        let class = peripheral_class(&args.peripheral);
+        let signals: Vec<&str> = family
+            .signals(Some(&class))
+            .into_iter()
+            .filter(|signal| {
+                let f = &family.candidates(signal)[0];
+                args.instance.as_deref().is_none_or(|wanted| {
+                    instance_matches(f.instance(), wanted)
+                        || signal.eq_ignore_ascii_case(wanted)
+                        || signal
+                            .to_uppercase()
+                            .starts_with(&format!("{}_", wanted.to_uppercase()))
+                })
+            })
+            .collect();
+
+        if signals.is_empty() {
+            let what = match &args.instance {
+                Some(instance) => format!("{} {}", class, instance),
+                None => class,
+            };
+            return ToolResult::error(format!(
+                "No {} signals for {} in the {} pin database. Available peripherals: {}",
+                what,
+                args.device,
+                family.name,
+                family.classes().join(", ")
+            ));
+        }
+
+        let mut output = self.header(args, family);
+        output.push_str(&format!(
+            "\n### {}\n\n| Instance | Signal | Pins |\n|----------|--------|------|\n",
+            peripheral_class(&args.peripheral)
+        ));
+        let mut found = Vec::new();
+        for signal in &signals {
+            let candidates = family.candidates(signal);
+            let first = candidates[0];
+            output.push_str(&format!(
+                "| {} | {} | {} |\n",
+                first.instance(),
+                first.function(),
+                pin_list(&candidates)
+            ));
+            found.push(json!({
+                "signal": signal,
+                "instance": first.instance(),
+                "pins": candidates,
+            }));
+        }
+        output.push_str(&self.notes(family));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("family".to_string(), json!(family.name));
+        metadata.insert("source".to_string(), json!(family.source));
+        metadata.insert("signals".to_string(), json!(found));
+        ToolResult::success_with_metadata(output, metadata)
+    }
 }
 
 #[async_trait]
@@ -33,19 +439,22 @@ impl Tool for PinoutMapper {
     }
 
     async fn execute(&self, args: Self::Params) -> ToolResult {
-        let mapping = format!(
-            "## Pinout Mapping for {}\n\n\
-            ### Peripheral: {}\n\n\
-            This tool provides:\n\
-            - Pin assignments for peripherals\n\
-            - Alternate function configurations\n\
-            - Conflict detection\n\
-            - Power and ground pin locations\n\
-            - Compatible pin combinations\n",
-            args.device, args.peripheral
-        );
+        let family = match load_family(&args).await {
+            Ok(family) => family,
+            Err(e) => return ToolResult::error(e),
+        };
 
-        ToolResult::success(mapping)
+        if is_requirement_set(&args) {
+            return self.solve(&args, &family);
+        }
+        if let Some(pin) = family.pin(&args.peripheral) {
+            let pin = pin.to_string();
+            return self.map_pin(&args, &family, &pin);
+        }
+        if peripheral_class(&args.peripheral) == "GPIO" {
+            return self.map_gpio(&args, &family);
+        }
+        self.map_peripheral(&args, &family)
     }
 }
 
@@ -55,7 +464,7 @@ impl ToolDescription for PinoutMapper {
     }
 
     fn description(&self) -> &'static str {
-        "Map microcontroller pins to peripherals, identify alternate functions, and detect pin conflicts."
+        "Map microcontroller pins to peripherals, identify alternate functions, and detect pin conflicts. Solves whole requirement sets (e.g. \"USART1 + I2C1 + 3 PWM + 2 ADC, avoid PA13/PA14\") into a conflict-free assignment or explains which requirements clash, and generates the matching init code (STM32 HAL MX_GPIO_Init, embassy-stm32, ESP-IDF or a Zephyr pinctrl overlay) inline; `pinout_codegen` writes it to a directory. Includes pin databases for STM32F4, STM32G0, ESP32, RP2040, nRF52 and ATmega328P; more can be added as JSON/CSV pin tables or STM32CubeMX MCU XML files."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -63,3 +472,309 @@ impl ToolDescription for PinoutMapper {
             .unwrap_or_else(|_| serde_json::json!({}))
     }
 }
+
+/// Writing generated init code changes files, so unlike `pinout_mapper` this
+/// needs the write permission
+pub struct PinoutCodegen {
+    mapper: PinoutMapper,
+}
+
+impl Default for PinoutCodegen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PinoutCodegen {
+    pub fn new() -> Self {
+        Self {
+            mapper: PinoutMapper::new(),
+        }
+    }
+
+    /// Solve the requirement set and generate its init code
+    async fn files(
+        &self,
+        args: &PinoutMapperArgs,
+    ) -> Result<(Family, Vec<Assignment>, Vec<GeneratedFile>), String> {
+        let family = load_family(args).await?;
+        let assignment = match self.mapper.solution(args, &family)? {
+            (_, _, Solution::Solved(assignment)) => assignment,
+            (_, _, Solution::Conflict { clashing, .. }) => {
+                return Err(format!(
+                    "No conflict-free assignment ({} clash), so no init code was generated",
+                    clashing.join(", ")
+                ))
+            }
+        };
+        let files = self.mapper.generate(args, &family, &assignment)?;
+        Ok((family, assignment, files))
+    }
+}
+
+#[async_trait]
+impl Tool for PinoutCodegen {
+    type Params = PinoutCodegenArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Write]
+    }
+
+    async fn execute_preview(&self, args: Self::Params) -> Option<ToolResult> {
+        let files = match self.files(&args.mapping()).await {
+            Ok((_, _, files)) => files,
+            Err(e) => return Some(ToolResult::error(e)),
+        };
+
+        let output = package::preview(Path::new(&args.output_dir), &package_files(&files));
+        let mut metadata = HashMap::new();
+        metadata.insert("path".to_string(), json!(args.output_dir));
+        metadata.insert(
+            "files".to_string(),
+            json!(files.iter().map(|f| &f.path).collect::<Vec<_>>()),
+        );
+        metadata.insert("content_length".to_string(), json!(output.len()));
+        metadata.insert("line_count".to_string(), json!(output.lines().count()));
+        metadata.insert("operation".to_string(), json!("write_preview"));
+        Some(ToolResult::Success {
+            output,
+            metadata: Some(metadata),
+        })
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        let mapping = args.mapping();
+        let (family, assignment, files) = match self.files(&mapping).await {
+            Ok(generated) => generated,
+            Err(e) => return ToolResult::error(e),
+        };
+        let written = match package::write(Path::new(&args.output_dir), &package_files(&files)) {
+            Ok(written) => written,
+            Err(e) => return ToolResult::error(e),
+        };
+
+        let mut output = self.mapper.header(&mapping, &family);
+        output.push_str("\n### Pin Assignment\n\n");
+        output.push_str(&assignment_table(&assignment));
+        output.push_str(&format!("\nWrote {}\n", written.join(", ")));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("family".to_string(), json!(family.name));
+        metadata.insert("assignment".to_string(), json!(assignment));
+        metadata.insert("written".to_string(), json!(written));
+        ToolResult::success_with_metadata(output, metadata)
+    }
+}
+
+impl ToolDescription for PinoutCodegen {
+    fn name(&self) -> &'static str {
+        "pinout_codegen"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write pin-mux init code for a microcontroller to a directory. Solves the requirement set like `pinout_mapper` (e.g. \"USART1 + I2C1 + 3 PWM, avoid PA13/PA14\") and writes the init code for each `codegen` format (stm32-hal MX_GPIO_Init, embassy-stm32, ESP-IDF gpio_config_t or a Zephyr pinctrl overlay) into `output_dir`, previewing new and changed files first. Use `pinout_mapper` to explore pins or to see the code without writing it."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(PinoutCodegenArgs))
+            .unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::pin_database::write_cubemx;
+    use tempfile::TempDir;
+
+    fn args(device: &str, peripheral: &str, instance: Option<&str>) -> PinoutMapperArgs {
+        PinoutMapperArgs {
+            device: device.to_string(),
+            peripheral: peripheral.to_string(),
+            instance: instance.map(str::to_string),
+            database: None,
+            avoid: None,
+            codegen: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peripheral_lookup() {
+        let tool = PinoutMapper::new();
+        let result = tool
+            .execute(args("STM32F411CEU6", "UART", Some("USART2")))
+            .await;
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("| USART2 | TX | PA2 (AF7), PD5 (AF7) |"));
+            assert!(output.contains("| USART2 | RX | PA3 (AF7), PD6 (AF7) |"));
+            assert!(!output.contains("USART1"));
+            assert!(output.contains("SWDIO"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["family"], "STM32F4");
+            assert_eq!(metadata["signals"][0]["pins"][0]["af"], 7);
+        } else {
+            panic!("Expected success result");
+        }
+
+        // Timer channel instance and routable signals
+        let output = tool
+            .execute(args("stm32g071", "PWM", Some("TIM3_CH2")))
+            .await
+            .to_string();
+        assert!(output.contains("| TIM3 | CH2 | PA7 (AF1), PB5 (AF1), PC7 (AF1) |"));
+        let output = tool.execute(args("esp32", "i2c", None)).await.to_string();
+        assert!(output.contains("| I2C0 | SDA | GPIO21, any GPIO |"));
+        assert!(output.contains("| I2C1 | SCL | any GPIO |"));
+    }
+
+    #[tokio::test]
+    async fn test_pin_and_gpio_lookup() {
+        let tool = PinoutMapper::new();
+        let output = tool
+            .execute(args("rp2040", "GPIO4", None))
+            .await
+            .to_string();
+        assert!(output.contains("### Pin GPIO4"));
+        assert!(output.contains("| UART1_TX | UART | AF2 |"));
+        assert!(output.contains("| I2C0_SDA | I2C | AF3 |"));
+
+        let output = tool
+            .execute(args("arduino nano", "GPIO", None))
+            .await
+            .to_string();
+        assert!(output.contains("| PD1 | USART0_TXD |"));
+        assert!(output.contains("D8-D13 = PB0-PB5"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_peripheral_and_cubemx() {
+        let tool = PinoutMapper::new();
+        match tool.execute(args("atmega328p", "CAN", None)).await {
+            ToolResult::Error { error, .. } => {
+                assert!(error.contains("Available peripherals: UART"), "{}", error)
+            }
+            _ => panic!("Expected error"),
+        }
+
+        let dir = TempDir::new().unwrap();
+        write_cubemx(dir.path());
+        let mut request = args("STM32F401RET6", "uart", None);
+        request.database = Some(dir.path().display().to_string());
+        let output = tool.execute(request).await.to_string();
+        assert!(output.contains("Pin database: STM32F401R(D-E)Tx"));
+        assert!(output.contains("| USART2 | TX | PA2 (AF7) |"));
+    }
+
+    #[tokio::test]
+    async fn test_requirement_set() {
+        let tool = PinoutMapper::new();
+        let mut request = args(
+            "STM32F401RE",
+            "USART1 + I2C1 + SPI2 + 3 PWM channels + 2 ADC inputs",
+            None,
+        );
+        request.avoid = Some(vec!["pa13".to_string(), "PA14".to_string()]);
+        let result = tool.execute(request).await;
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("### Pin Assignment"));
+            assert!(output.contains("Avoided pins: PA13, PA14"));
+            assert!(output.contains("| USART1 | USART1_TX |"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["solved"], true);
+            assert_eq!(metadata["assignment"].as_array().unwrap().len(), 12);
+        } else {
+            panic!("Expected success result");
+        }
+
+        let result = tool
+            .execute(args("arduino uno", "SPI + TC2_OC2A=PB3 + UART", None))
+            .await;
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("These requirements cannot be met together: SPI, TC2_OC2A=PB3"));
+            assert!(output.contains("### Assignment without TC2_OC2A=PB3"));
+            assert_eq!(metadata.unwrap()["solved"], false);
+        } else {
+            panic!("Expected success result");
+        }
+
+        let mut request = args("rp2040", "I2C0", None);
+        request.avoid = Some(vec!["PA1".to_string()]);
+        assert!(matches!(
+            tool.execute(request).await,
+            ToolResult::Error { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_codegen_preview_and_write() {
+        let tool = PinoutMapper::new();
+        assert_eq!(tool.capabilities(), &[ToolCapability::Read]);
+        let mut request = args("STM32F401RE", "USART2 + I2C1", None);
+        request.codegen = Some(vec!["stm32-hal".to_string(), "zephyr".to_string()]);
+
+        // The mapper only returns the code
+        assert!(tool.execute_preview(request.clone()).await.is_none());
+        let output = tool.execute(request.clone()).await.to_string();
+        assert!(output.contains("### gpio_init.c (stm32-hal)\n\n```c\n"));
+        assert!(output.contains("GPIO_InitStruct.Alternate = GPIO_AF7_USART2;"));
+        assert!(output.contains("```dts\n"));
+
+        let writer = PinoutCodegen::new();
+        assert_eq!(writer.capabilities(), &[ToolCapability::Write]);
+        let dir = TempDir::new().unwrap();
+        let mut request = PinoutCodegenArgs {
+            device: "STM32F401RE".to_string(),
+            peripheral: "USART2 + I2C1".to_string(),
+            database: None,
+            avoid: None,
+            codegen: vec!["stm32-hal".to_string(), "zephyr".to_string()],
+            output_dir: dir.path().display().to_string(),
+        };
+        let preview = writer.execute_preview(request.clone()).await.unwrap();
+        if let ToolResult::Success { output, metadata } = preview {
+            assert!(output.contains("void MX_GPIO_Init(void)"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["operation"], "write_preview");
+            assert_eq!(metadata["files"], json!(["gpio_init.c", "app.overlay"]));
+        } else {
+            panic!("Expected success result");
+        }
+        assert!(!dir.path().join("gpio_init.c").exists());
+
+        let result = writer.execute(request.clone()).await;
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("| USART2 | USART2_TX | PA2 | AF7 |"));
+            assert_eq!(metadata.unwrap()["written"].as_array().unwrap().len(), 2);
+        } else {
+            panic!("Expected success result");
+        }
+        let overlay = std::fs::read_to_string(dir.path().join("app.overlay")).unwrap();
+        assert!(overlay.contains("&usart2 {\n\tpinctrl-0 = <&usart2_tx_pa2 &usart2_rx_pa3>;"));
+
+        // Wrong family for the format, unknown format and conflicts write nothing
+        request.codegen = vec!["esp-idf".to_string()];
+        assert!(matches!(
+            writer.execute_preview(request.clone()).await,
+            Some(ToolResult::Error { .. })
+        ));
+        request.codegen = vec!["arduino".to_string()];
+        match writer.execute(request.clone()).await {
+            ToolResult::Error { error, .. } => assert!(error.contains("Available: stm32-hal")),
+            _ => panic!("Expected error"),
+        }
+        let mut conflict = args("arduino uno", "SPI + TC2_OC2A=PB3", None);
+        conflict.codegen = Some(vec!["stm32-hal".to_string()]);
+        let output = tool.execute(conflict).await.to_string();
+        assert!(output.contains("No init code generated until the conflict is resolved."));
+        request.device = "arduino uno".to_string();
+        request.peripheral = "SPI + TC2_OC2A=PB3".to_string();
+        request.codegen = vec!["stm32-hal".to_string()];
+        request.output_dir = dir.path().join("uno").display().to_string();
+        match writer.execute_preview(request.clone()).await {
+            Some(ToolResult::Error { error, .. }) => assert!(error.contains("no init code")),
+            _ => panic!("Expected error preview"),
+        }
+        assert!(writer.execute(request).await.is_error());
+        assert!(!dir.path().join("uno").exists());
+    }
+}
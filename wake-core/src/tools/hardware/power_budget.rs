@@ -0,0 +1,614 @@
+//! Power budget and battery-life estimation.
+//!
+//! One entry per line, `key=value` fields, `*` or `#` comments:
+//! - `battery capacity=2000mAh chemistry=li-ion [cells=1] [voltage=3.7] [cutoff=3.0] [usable=85%] [self_discharge=2%]`
+//! - `supply voltage=5` (fixed source instead of a battery)
+//! - `ldo NAME out=RAIL vout=3.3 [in=RAIL] [iq=50u] [dropout=0.2] [theta_ja=200]`
+//! - `buck|boost|buck-boost NAME out=RAIL vout=1.8 efficiency=90% [in=RAIL] [iq=..] [theta_ja=..]`
+//! - `load NAME [rail=RAIL] active=12m [sleep=2u] [duty=1% | on=50ms period=60s]` or `current=2m`
+//! - `ambient=25` (°C)
+//!
+//! The battery or supply drives the rail named `battery`; regulators default to it as input.
+//! Percentages need a `%` sign, otherwise the value is a fraction.
+
+use super::netlist::parse_value;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Hours per average month, for self-discharge rates
+const HOURS_PER_MONTH: f64 = 730.5;
+
+/// θJA assumed when a regulator gives none (SOT-23 class package)
+pub const DEFAULT_THETA_JA: f64 = 200.0;
+
+/// Junction temperature above which a regulator is flagged
+const MAX_JUNCTION_C: f64 = 125.0;
+
+/// Capacity fraction usable by default (aging, temperature, cutoff margin)
+const DEFAULT_USABLE_FRACTION: f64 = 0.85;
+
+/// Switcher output current below which rated efficiency is unrealistic
+const LIGHT_LOAD_A: f64 = 1e-3;
+
+const ROOT_RAIL: &str = "battery";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Chemistry {
+    LiIon,
+    LiFePo4,
+    Alkaline,
+    NiMh,
+    LithiumCoin,
+    LiSoCl2,
+}
+
+impl Chemistry {
+    pub fn parse(name: &str) -> Option<Chemistry> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "li-ion" | "liion" | "lipo" | "li-po" | "lithium-ion" => Some(Chemistry::LiIon),
+            "lifepo4" | "lfp" => Some(Chemistry::LiFePo4),
+            "alkaline" | "aa" | "aaa" => Some(Chemistry::Alkaline),
+            "nimh" | "ni-mh" => Some(Chemistry::NiMh),
+            "coin" | "cr2032" | "li-mno2" | "limno2" => Some(Chemistry::LithiumCoin),
+            "lisocl2" | "li-socl2" | "thionyl" => Some(Chemistry::LiSoCl2),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Chemistry::LiIon => "Li-ion/LiPo",
+            Chemistry::LiFePo4 => "LiFePO4",
+            Chemistry::Alkaline => "alkaline",
+            Chemistry::NiMh => "NiMH",
+            Chemistry::LithiumCoin => "Li-MnO2 coin",
+            Chemistry::LiSoCl2 => "Li-SOCl2",
+        }
+    }
+
+    /// Per-cell (nominal volts, cutoff volts, self-discharge fraction per month)
+    pub fn defaults(&self) -> (f64, f64, f64) {
+        match self {
+            Chemistry::LiIon => (3.7, 3.0, 0.02),
+            Chemistry::LiFePo4 => (3.2, 2.5, 0.03),
+            Chemistry::Alkaline => (1.5, 0.9, 0.0025),
+            Chemistry::NiMh => (1.2, 1.0, 0.20),
+            Chemistry::LithiumCoin => (3.0, 2.0, 0.001),
+            Chemistry::LiSoCl2 => (3.6, 3.0, 0.001),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Battery {
+    pub capacity_ah: f64,
+    pub chemistry: Option<Chemistry>,
+    pub nominal_v: f64,
+    pub cutoff_v: f64,
+    pub usable_fraction: f64,
+    pub self_discharge_per_month: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegulatorKind {
+    Ldo,
+    Buck,
+    Boost,
+    BuckBoost,
+}
+
+impl RegulatorKind {
+    fn parse(name: &str) -> Option<RegulatorKind> {
+        match name {
+            "ldo" | "linear" => Some(RegulatorKind::Ldo),
+            "buck" => Some(RegulatorKind::Buck),
+            "boost" => Some(RegulatorKind::Boost),
+            "buck-boost" | "buckboost" => Some(RegulatorKind::BuckBoost),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RegulatorKind::Ldo => "LDO",
+            RegulatorKind::Buck => "buck",
+            RegulatorKind::Boost => "boost",
+            RegulatorKind::BuckBoost => "buck-boost",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Regulator {
+    pub name: String,
+    pub kind: RegulatorKind,
+    pub input: String,
+    pub output: String,
+    pub vout: f64,
+    pub iq_a: f64,
+    /// Switching regulators only
+    pub efficiency: Option<f64>,
+    /// LDO only
+    pub dropout_v: f64,
+    pub theta_ja: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Load {
+    pub name: String,
+    pub rail: String,
+    pub active_a: f64,
+    pub sleep_a: f64,
+    /// Fraction of time spent active
+    pub duty: f64,
+}
+
+impl Load {
+    pub fn average_a(&self) -> f64 {
+        self.active_a * self.duty + self.sleep_a * (1.0 - self.duty)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PowerBudget {
+    pub battery: Option<Battery>,
+    /// Fixed supply voltage when there is no battery
+    pub supply_v: Option<f64>,
+    pub regulators: Vec<Regulator>,
+    pub loads: Vec<Load>,
+    pub ambient_c: Option<f64>,
+}
+
+/// Fraction from `90%` or `0.9`
+fn parse_fraction(raw: &str) -> Result<f64, String> {
+    match raw.strip_suffix('%') {
+        Some(percent) => Ok(parse_value(percent)? / 100.0),
+        None => parse_value(raw),
+    }
+}
+
+impl PowerBudget {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut budget = PowerBudget::default();
+        for (line_no, raw) in text.lines().enumerate() {
+            let line = raw.split('#').next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('*') {
+                continue;
+            }
+            budget
+                .parse_line(line)
+                .map_err(|e| format!("Line {}: {} ('{}')", line_no + 1, e, line))?;
+        }
+
+        if budget.battery.is_some() && budget.supply_v.is_some() {
+            return Err("Specify either a battery or a supply, not both".to_string());
+        }
+        if budget.battery.is_none() && budget.supply_v.is_none() {
+            return Err("Power budget needs a 'battery' or 'supply' line".to_string());
+        }
+        if budget.loads.is_empty() {
+            return Err("Power budget has no loads".to_string());
+        }
+
+        let mut rails = vec![ROOT_RAIL.to_string()];
+        for r in &budget.regulators {
+            if rails.contains(&r.output) {
+                return Err(format!("Rail '{}' is driven twice", r.output));
+            }
+            rails.push(r.output.clone());
+        }
+        let unknown = budget
+            .regulators
+            .iter()
+            .map(|r| (&r.name, &r.input))
+            .chain(budget.loads.iter().map(|l| (&l.name, &l.rail)))
+            .find(|(_, rail)| !rails.contains(rail));
+        if let Some((name, rail)) = unknown {
+            return Err(format!("{} uses undefined rail '{}'", name, rail));
+        }
+        Ok(budget)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let mut tokens = line.split_whitespace();
+        let head = tokens.next().unwrap_or_default().to_lowercase();
+
+        // `ambient=25` stands alone
+        if let Some(value) = head.strip_prefix("ambient=") {
+            self.ambient_c = Some(parse_value(value)?);
+            return Ok(());
+        }
+
+        let takes_name = head == "load" || RegulatorKind::parse(&head).is_some();
+        let name = if takes_name {
+            tokens
+                .next()
+                .filter(|t| !t.contains('='))
+                .ok_or_else(|| format!("{} needs a name", head))?
+                .to_string()
+        } else {
+            String::new()
+        };
+        let mut fields = HashMap::new();
+        for token in tokens {
+            let (key, value) = token
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got '{}'", token))?;
+            fields.insert(key.to_lowercase(), value.to_string());
+        }
+        let get = |key: &str| fields.get(key).map(String::as_str);
+        let number = |key: &str| get(key).map(parse_value).transpose();
+        let fraction = |key: &str| get(key).map(parse_fraction).transpose();
+        let rail = |key: &str| get(key).unwrap_or(ROOT_RAIL).to_lowercase();
+
+        match head.as_str() {
+            "battery" => {
+                let chemistry = match get("chemistry") {
+                    Some(c) => Some(
+                        Chemistry::parse(c).ok_or_else(|| format!("unknown chemistry '{}'", c))?,
+                    ),
+                    None => None,
+                };
+                let (nominal, cutoff, self_discharge) = chemistry.map_or((None, None, 0.0), |c| {
+                    let (n, v, s) = c.defaults();
+                    (Some(n), Some(v), s)
+                });
+                let cells = number("cells")?.unwrap_or(1.0);
+                let nominal_v = match number("voltage")? {
+                    Some(v) => v,
+                    None => nominal.ok_or("battery needs chemistry= or voltage=")? * cells,
+                };
+                self.battery = Some(Battery {
+                    capacity_ah: number("capacity")?.ok_or("battery needs capacity=")?,
+                    chemistry,
+                    nominal_v,
+                    cutoff_v: number("cutoff")?
+                        .or(cutoff.map(|c| c * cells))
+                        .unwrap_or(nominal_v),
+                    usable_fraction: fraction("usable")?.unwrap_or(DEFAULT_USABLE_FRACTION),
+                    self_discharge_per_month: fraction("self_discharge")?.unwrap_or(self_discharge),
+                });
+            }
+            "supply" => self.supply_v = Some(number("voltage")?.ok_or("supply needs voltage=")?),
+            "load" => {
+                let (active, duty) = match (number("current")?, number("active")?) {
+                    (Some(current), _) => (current, 1.0),
+                    (None, Some(active)) => {
+                        let duty = match (fraction("duty")?, number("on")?, number("period")?) {
+                            (Some(d), ..) => d,
+                            (None, Some(on), Some(period)) if period > 0.0 => on / period,
+                            (None, None, None) => 1.0,
+                            _ => return Err("on= needs a positive period=".to_string()),
+                        };
+                        (active, duty)
+                    }
+                    (None, None) => return Err("load needs active= or current=".to_string()),
+                };
+                if !(0.0..=1.0).contains(&duty) {
+                    return Err(format!("duty cycle {} is outside 0-100%", duty));
+                }
+                self.loads.push(Load {
+                    name,
+                    rail: rail("rail"),
+                    active_a: active,
+                    sleep_a: number("sleep")?.unwrap_or(0.0),
+                    duty,
+                });
+            }
+            kind => {
+                let kind = RegulatorKind::parse(kind).ok_or_else(|| {
+                    format!(
+                        "unknown entry '{}', expected battery, supply, ldo, buck, boost, buck-boost, load or ambient",
+                        kind
+                    )
+                })?;
+                let efficiency = fraction("efficiency")?;
+                if kind != RegulatorKind::Ldo {
+                    match efficiency {
+                        Some(e) if e > 0.0 && e <= 1.0 => {}
+                        Some(_) => return Err("efficiency must be within (0, 100%]".to_string()),
+                        None => return Err(format!("{} needs efficiency=", kind.label())),
+                    }
+                }
+                self.regulators.push(Regulator {
+                    name,
+                    kind,
+                    input: rail("in"),
+                    output: get("out").ok_or("regulator needs out=")?.to_lowercase(),
+                    vout: number("vout")?.ok_or("regulator needs vout=")?,
+                    iq_a: number("iq")?.unwrap_or(0.0),
+                    efficiency,
+                    dropout_v: number("dropout")?.unwrap_or(0.0),
+                    theta_ja: number("theta_ja")?,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RegulatorReport {
+    pub name: String,
+    pub kind: RegulatorKind,
+    pub vin: f64,
+    pub vout: f64,
+    pub output_avg_a: f64,
+    pub output_peak_a: f64,
+    pub input_avg_a: f64,
+    pub input_peak_a: f64,
+    pub dissipation_avg_w: f64,
+    pub dissipation_peak_w: f64,
+    pub theta_ja: f64,
+    /// Junction temperature with every load active at once
+    pub junction_peak_c: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PowerReport {
+    pub source_v: f64,
+    pub average_current_a: f64,
+    pub peak_current_a: f64,
+    pub average_power_w: f64,
+    pub ambient_c: f64,
+    pub usable_capacity_ah: Option<f64>,
+    pub self_discharge_a: Option<f64>,
+    pub battery_life_hours: Option<f64>,
+    pub regulators: Vec<RegulatorReport>,
+    pub warnings: Vec<String>,
+}
+
+struct RailSolver<'a> {
+    budget: &'a PowerBudget,
+    source_v: f64,
+}
+
+impl RailSolver<'_> {
+    fn voltage(&self, rail: &str) -> f64 {
+        self.budget
+            .regulators
+            .iter()
+            .find(|r| r.output == rail)
+            .map_or(self.source_v, |r| r.vout)
+    }
+
+    /// Current drawn from `rail`, either averaged or with every load active
+    fn rail_current(&self, rail: &str, peak: bool, depth: usize) -> Result<f64, String> {
+        if depth > self.budget.regulators.len() {
+            return Err(format!("Regulator loop through rail '{}'", rail));
+        }
+        let loads: f64 = self
+            .budget
+            .loads
+            .iter()
+            .filter(|l| l.rail == rail)
+            .map(|l| if peak { l.active_a } else { l.average_a() })
+            .sum();
+        let mut regulators = 0.0;
+        for r in self.budget.regulators.iter().filter(|r| r.input == rail) {
+            let out = self.rail_current(&r.output, peak, depth + 1)?;
+            regulators += self.input_current(r, out);
+        }
+        Ok(loads + regulators)
+    }
+
+    fn input_current(&self, r: &Regulator, output_a: f64) -> f64 {
+        match r.efficiency {
+            Some(eff) if r.kind != RegulatorKind::Ldo => {
+                r.vout * output_a / (eff * self.voltage(&r.input)) + r.iq_a
+            }
+            _ => output_a + r.iq_a,
+        }
+    }
+}
+
+pub fn analyze(budget: &PowerBudget) -> Result<PowerReport, String> {
+    let source_v = budget
+        .battery
+        .as_ref()
+        .map(|b| b.nominal_v)
+        .or(budget.supply_v)
+        .ok_or("Power budget needs a battery or supply")?;
+    let solver = RailSolver { budget, source_v };
+    let ambient_c = budget.ambient_c.unwrap_or(25.0);
+    let mut warnings = Vec::new();
+
+    let mut regulators = Vec::new();
+    for r in &budget.regulators {
+        let vin = solver.voltage(&r.input);
+        let output_avg_a = solver.rail_current(&r.output, false, 0)?;
+        let output_peak_a = solver.rail_current(&r.output, true, 0)?;
+        let input_avg_a = solver.input_current(r, output_avg_a);
+        let input_peak_a = solver.input_current(r, output_peak_a);
+        let dissipation = |i_in: f64, i_out: f64| (vin * i_in - r.vout * i_out).max(0.0);
+        let theta_ja = r.theta_ja.unwrap_or(DEFAULT_THETA_JA);
+        let dissipation_peak_w = dissipation(input_peak_a, output_peak_a);
+        let junction_peak_c = ambient_c + dissipation_peak_w * theta_ja;
+
+        // Lowest input the regulator sees: battery cutoff or the upstream rail
+        let vin_min = match (&budget.battery, r.input == ROOT_RAIL) {
+            (Some(b), true) => b.cutoff_v,
+            _ => vin,
+        };
+        match r.kind {
+            RegulatorKind::Ldo if vin < r.vout + r.dropout_v => warnings.push(format!(
+                "{} cannot regulate: input {:.2} V is below {:.2} V output + {:.2} V dropout",
+                r.name, vin, r.vout, r.dropout_v
+            )),
+            RegulatorKind::Ldo if vin_min < r.vout + r.dropout_v => warnings.push(format!(
+                "{} drops out once the battery falls below {:.2} V (cutoff {:.2} V)",
+                r.name,
+                r.vout + r.dropout_v,
+                vin_min
+            )),
+            RegulatorKind::Buck if vin_min <= r.vout => warnings.push(format!(
+                "{} (buck) input can fall to {:.2} V, at or below its {:.2} V output",
+                r.name, vin_min, r.vout
+            )),
+            RegulatorKind::Boost if vin >= r.vout => warnings.push(format!(
+                "{} (boost) input {:.2} V is not below its {:.2} V output",
+                r.name, vin, r.vout
+            )),
+            _ => {}
+        }
+        if r.kind != RegulatorKind::Ldo && output_avg_a < LIGHT_LOAD_A {
+            warnings.push(format!(
+                "{} averages {:.1} µA out; switcher efficiency at light load is usually far below the rated figure",
+                r.name,
+                output_avg_a * 1e6
+            ));
+        }
+        if junction_peak_c > MAX_JUNCTION_C {
+            warnings.push(format!(
+                "{} junction reaches {:.0} °C at peak load",
+                r.name, junction_peak_c
+            ));
+        }
+
+        regulators.push(RegulatorReport {
+            name: r.name.clone(),
+            kind: r.kind,
+            vin,
+            vout: r.vout,
+            output_avg_a,
+            output_peak_a,
+            input_avg_a,
+            input_peak_a,
+            dissipation_avg_w: dissipation(input_avg_a, output_avg_a),
+            dissipation_peak_w,
+            theta_ja,
+            junction_peak_c,
+        });
+    }
+
+    let average_current_a = solver.rail_current(ROOT_RAIL, false, 0)?;
+    let peak_current_a = solver.rail_current(ROOT_RAIL, true, 0)?;
+
+    let (usable_capacity_ah, self_discharge_a, battery_life_hours) = match &budget.battery {
+        Some(b) => {
+            let usable = b.capacity_ah * b.usable_fraction;
+            let self_discharge = b.capacity_ah * b.self_discharge_per_month / HOURS_PER_MONTH;
+            let drain = average_current_a + self_discharge;
+            let life = if drain > 0.0 {
+                usable / drain
+            } else {
+                f64::INFINITY
+            };
+            (Some(usable), Some(self_discharge), Some(life))
+        }
+        None => (None, None, None),
+    };
+
+    Ok(PowerReport {
+        source_v,
+        average_current_a,
+        peak_current_a,
+        average_power_w: average_current_a * source_v,
+        ambient_c,
+        usable_capacity_ah,
+        self_discharge_a,
+        battery_life_hours,
+        regulators,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} ± {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    const SENSOR: &str = "\
+        battery capacity=2000mAh chemistry=li-ion usable=100% self_discharge=0%\n\
+        ldo U1 out=3v3 vout=3.3 iq=5u dropout=0.2 theta_ja=250\n\
+        load MCU rail=3v3 active=10m sleep=2u duty=1%\n\
+        load RADIO rail=3v3 active=100m sleep=1u on=50ms period=60s\n";
+
+    #[test]
+    fn test_parse_budget() {
+        let budget = PowerBudget::parse(SENSOR).unwrap();
+        let battery = budget.battery.as_ref().unwrap();
+        assert_close(battery.capacity_ah, 2.0, 1e-12);
+        assert_eq!(battery.nominal_v, 3.7);
+        assert_eq!(battery.cutoff_v, 3.0);
+        assert_eq!(budget.regulators[0].output, "3v3");
+        assert_close(budget.loads[0].duty, 0.01, 1e-12);
+        assert_close(budget.loads[1].duty, 0.05 / 60.0, 1e-12);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(PowerBudget::parse("load MCU active=1m").is_err());
+        assert!(PowerBudget::parse("supply voltage=5\nload MCU rail=3v3 active=1m").is_err());
+        assert!(PowerBudget::parse(
+            "supply voltage=5\nbuck U1 out=3v3 vout=3.3\nload A current=1m"
+        )
+        .is_err());
+        assert!(
+            PowerBudget::parse("battery capacity=1 chemistry=zinc\nload A current=1m").is_err()
+        );
+        assert!(PowerBudget::parse("supply voltage=5\nload A active=1m duty=150%").is_err());
+    }
+
+    #[test]
+    fn test_ldo_sensor_battery_life() {
+        let report = analyze(&PowerBudget::parse(SENSOR).unwrap()).unwrap();
+        let mcu = 10e-3 * 0.01 + 2e-6 * 0.99;
+        let d = 0.05 / 60.0;
+        let radio = 100e-3 * d + 1e-6 * (1.0 - d);
+        let total = mcu + radio + 5e-6;
+        assert_close(report.average_current_a, total, 1e-12);
+        assert_close(report.peak_current_a, 110e-3 + 5e-6, 1e-12);
+        assert_close(report.battery_life_hours.unwrap(), 2.0 / total, 1e-6);
+
+        let ldo = &report.regulators[0];
+        // (3.7 - 3.3) · 110 mA + 3.7 · 5 µA
+        assert_close(ldo.dissipation_peak_w, 0.4 * 0.110 + 3.7 * 5e-6, 1e-9);
+        assert_close(
+            ldo.junction_peak_c,
+            25.0 + ldo.dissipation_peak_w * 250.0,
+            1e-9,
+        );
+        // 3.3 V + 0.2 V dropout is above the 3.0 V Li-ion cutoff
+        assert!(report.warnings.iter().any(|w| w.contains("drops out")));
+    }
+
+    #[test]
+    fn test_cascaded_switcher_and_self_discharge() {
+        let budget = PowerBudget::parse(
+            "battery capacity=1000mAh chemistry=alkaline cells=2 usable=80%\n\
+             boost U1 out=5v vout=5 efficiency=80%\n\
+             ldo U2 in=5v out=3v3 vout=3.3\n\
+             load LED rail=5v current=20m\n\
+             load MCU rail=3v3 current=10m\n\
+             ambient=40",
+        )
+        .unwrap();
+        let report = analyze(&budget).unwrap();
+        assert_eq!(report.source_v, 3.0);
+        assert_eq!(report.ambient_c, 40.0);
+        // 5 V · 30 mA / (0.8 · 3 V)
+        assert_close(report.average_current_a, 0.0625, 1e-12);
+        let self_discharge = 1.0 * 0.0025 / HOURS_PER_MONTH;
+        assert_close(report.self_discharge_a.unwrap(), self_discharge, 1e-12);
+        assert_close(
+            report.battery_life_hours.unwrap(),
+            0.8 / (0.0625 + self_discharge),
+            1e-9,
+        );
+        let boost = &report.regulators[0];
+        assert_close(boost.dissipation_avg_w, 0.0625 * 3.0 - 0.150, 1e-12);
+        assert!(report.warnings.is_empty());
+    }
+}
@@ -1,12 +1,24 @@
+use super::can_timing;
+use super::can_trace;
+use super::capture::{self, Capture};
+use super::dbc::{self, Database};
+use super::i2c_decode;
+use super::modbus;
+use super::netlist::format_si;
+use super::spi_decode::{self, Lines};
+use super::uart_decode;
 use crate::tools::{Tool, ToolCapability, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 use wake_llm::ToolDescription;
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ProtocolDebuggerArgs {
-    /// Communication protocol to debug (I2C, SPI, UART, CAN, OneWire)
+    /// Communication protocol to debug (I2C, SPI, UART, CAN, Modbus, OneWire);
+    /// Modbus-RTU, Modbus-ASCII or Modbus-TCP selects the framing explicitly
     pub protocol: String,
 
     /// The issue or error being experienced
@@ -21,10 +33,52 @@ pub struct ProtocolDebuggerArgs {
     /// Error messages or symptoms
     pub error_messages: Option<String>,
 
-    /// Captured data or logic analyzer output (if available)
+    /// Captured data or logic analyzer output (if available): a path to a
+    /// sigrok/PulseView CSV, Saleae CSV or VCD capture (for CAN: a candump log,
+    /// Vector ASC or CSV trace; for Modbus: a hex dump), the capture text
+    /// itself, or free-form notes
     pub captured_data: Option<String>,
+
+    /// Optional: capture channel for each signal, e.g. "SCL=D0,SDA=D1",
+    /// "CS=D0,SCK=D1,MOSI=D2,MISO=D3" or "RX=D0" (defaults to channels named
+    /// after the signals)
+    pub channels: Option<String>,
+
+    /// Optional: longest acceptable I2C clock stretch in microseconds (default 1000)
+    pub stretch_limit_us: Option<f64>,
+
+    /// Optional: SPI mode (0-3) the firmware is configured for; compared with the
+    /// mode detected in the capture
+    pub spi_mode: Option<u8>,
+
+    /// Optional: UART baud rate the receiver is configured for; compared with the
+    /// rate measured in the capture, and used for Modbus RTU t1.5/t3.5 checks on
+    /// time-stamped hex dumps
+    pub baud_rate: Option<u32>,
+
+    /// Optional: UART frame format such as 8N1 or 7E2 (default 8N1)
+    pub uart_format: Option<String>,
+
+    /// Optional: path to a DBC file used to decode CAN signals
+    pub dbc: Option<String>,
+
+    /// Optional: nominal CAN bitrate in bit/s (default 500000); used for bus
+    /// load and bit timing
+    pub can_bitrate: Option<u32>,
+
+    /// Optional: CAN controller clock in Hz; adds BRP/TSEG1/TSEG2/SJW settings
+    /// for the bitrate
+    pub can_clock_hz: Option<u32>,
 }
 
+/// Rows shown in decoded transaction tables
+const TABLE_ROWS: usize = 50;
+
+/// Frames shown in the decoded CAN frame log
+const CAN_LOG_ROWS: usize = 20;
+
+const DEFAULT_CAN_BITRATE: u32 = 500_000;
+
 pub struct ProtocolDebugger;
 
 impl ProtocolDebugger {
@@ -32,14 +86,25 @@ impl ProtocolDebugger {
         Self
     }
 
-    fn analyze_i2c_issue(&self, args: &ProtocolDebuggerArgs) -> String {
+    fn analyze_i2c_issue(
+        &self,
+        args: &ProtocolDebuggerArgs,
+        decoded: Option<&i2c_decode::Decoded>,
+    ) -> String {
+        use i2c_decode::IssueKind;
         let mut analysis = String::from("## I2C Protocol Debug Analysis\n\n");
+        let found = |kind| decoded.is_some_and(|d| d.has(kind));
+
+        if let Some(decoded) = decoded {
+            analysis.push_str(&decoded_i2c(decoded));
+        }
 
         analysis.push_str("### Common I2C Issues Check:\n\n");
 
         // Check for common I2C problems
         if args.issue.to_lowercase().contains("nack")
             || args.issue.to_lowercase().contains("not acknowledged")
+            || found(IssueKind::Nack)
         {
             analysis.push_str("**NACK (Not Acknowledged) Issues:**\n");
             analysis.push_str(
@@ -67,7 +132,38 @@ impl ProtocolDebugger {
             analysis.push_str("   - Maximum: 400pF for standard mode\n\n");
         }
 
-        if args.issue.to_lowercase().contains("stuck") || args.issue.to_lowercase().contains("hang")
+        if found(IssueKind::ClockStretch) {
+            analysis.push_str("**Clock Stretching:**\n");
+            analysis.push_str(
+                "1. **Controller Support**: The master must wait while a device holds SCL low\n",
+            );
+            analysis.push_str(
+                "   - Some controllers (e.g. Raspberry Pi BCM2835) mishandle long stretches\n",
+            );
+            analysis.push_str(
+                "2. **Timeouts**: Raise the driver timeout above the longest stretch, or poll the device's ready bit instead\n",
+            );
+            analysis.push_str(
+                "3. **Slow Conversions**: Sensors stretch during measurements; start a conversion, wait, then read\n\n",
+            );
+        }
+
+        if found(IssueKind::RepeatedStart) {
+            analysis.push_str("**Repeated START Usage:**\n");
+            analysis.push_str(
+                "1. **Register Reads**: Write the register address, repeated START, then read from the same address\n",
+            );
+            analysis.push_str(
+                "   - Use the combined call (`i2c_write_read`, `HAL_I2C_Mem_Read`, `Wire.endTransmission(false)`)\n",
+            );
+            analysis
+                .push_str("2. **After a NACK**: End the transfer with STOP before retrying\n\n");
+        }
+
+        if args.issue.to_lowercase().contains("stuck")
+            || args.issue.to_lowercase().contains("hang")
+            || found(IssueKind::MissingStop)
+            || found(IssueKind::BusError)
         {
             analysis.push_str("**Bus Stuck/Hanging Issues:**\n");
             analysis.push_str("1. **SDA Stuck Low**: Device might be holding SDA low\n");
@@ -114,13 +210,24 @@ impl ProtocolDebugger {
         analysis
     }
 
-    fn analyze_spi_issue(&self, args: &ProtocolDebuggerArgs) -> String {
+    fn analyze_spi_issue(
+        &self,
+        args: &ProtocolDebuggerArgs,
+        decoded: Option<&spi_decode::Decoded>,
+    ) -> String {
+        use spi_decode::IssueKind;
         let mut analysis = String::from("## SPI Protocol Debug Analysis\n\n");
+        let found = |kind| decoded.is_some_and(|d| d.has(kind));
+
+        if let Some(decoded) = decoded {
+            analysis.push_str(&decoded_spi(decoded));
+        }
 
         analysis.push_str("### Common SPI Issues Check:\n\n");
 
         if args.issue.to_lowercase().contains("miso")
             || args.issue.to_lowercase().contains("no response")
+            || decoded.is_some_and(miso_idle)
         {
             analysis.push_str("**MISO/No Response Issues:**\n");
             analysis.push_str(
@@ -132,6 +239,8 @@ impl ProtocolDebugger {
 
         if args.issue.to_lowercase().contains("wrong")
             || args.issue.to_lowercase().contains("shifted")
+            || found(IssueKind::ModeMismatch)
+            || found(IssueKind::IdleLevel)
         {
             analysis.push_str("**Data Corruption/Shifted Issues:**\n");
             analysis.push_str("1. **SPI Mode Mismatch**: Check CPOL and CPHA settings\n");
@@ -143,6 +252,19 @@ impl ProtocolDebugger {
             analysis.push_str("3. **Clock Speed Too High**: Try reducing SPI clock\n\n");
         }
 
+        if found(IssueKind::PartialWord) || found(IssueKind::ClockOutsideFrame) {
+            analysis.push_str("**Chip Select/Framing Issues:**\n");
+            analysis.push_str(
+                "1. **CS Timing**: Keep CS low for the whole transfer and release it after the last clock\n",
+            );
+            analysis.push_str(
+                "2. **Hardware NSS**: Automatic CS can toggle between bytes; drive CS from a GPIO instead\n",
+            );
+            analysis.push_str(
+                "3. **SCK Glitches**: Ringing on long clock wires adds edges; add a series resistor (22-33Ω) near the master\n\n",
+            );
+        }
+
         analysis.push_str("### SPI Timing Diagram:\n");
         analysis.push_str("```\n");
         analysis.push_str("CS    ‾‾‾\\_______________/‾‾‾\n");
@@ -171,13 +293,27 @@ impl ProtocolDebugger {
         analysis
     }
 
-    fn analyze_uart_issue(&self, args: &ProtocolDebuggerArgs) -> String {
+    fn analyze_uart_issue(
+        &self,
+        args: &ProtocolDebuggerArgs,
+        decoded: &[uart_decode::Decoded],
+    ) -> String {
+        use uart_decode::IssueKind;
         let mut analysis = String::from("## UART Protocol Debug Analysis\n\n");
+        let found = |kind| decoded.iter().any(|d| d.has(kind));
+
+        if !decoded.is_empty() {
+            analysis.push_str(&decoded_uart(decoded));
+        }
 
         analysis.push_str("### Common UART Issues Check:\n\n");
 
         if args.issue.to_lowercase().contains("garbage")
             || args.issue.to_lowercase().contains("symbols")
+            || found(IssueKind::BaudDeviation)
+            || found(IssueKind::BaudMismatch)
+            || found(IssueKind::Framing)
+            || found(IssueKind::Parity)
         {
             analysis.push_str("**Garbage Data/Wrong Characters:**\n");
             analysis.push_str("1. **Baud Rate Mismatch**: Most common issue!\n");
@@ -190,8 +326,20 @@ impl ProtocolDebugger {
             analysis.push_str("   - Format notation: 8N1 = 8 data, No parity, 1 stop\n\n");
         }
 
+        if found(IssueKind::BaudDeviation) {
+            analysis.push_str("**Clock Accuracy:**\n");
+            analysis.push_str(
+                "1. **Oscillator**: Internal RC oscillators drift by several percent; use a crystal or calibrate\n",
+            );
+            analysis.push_str(
+                "2. **Divider Error**: Check the baud divider error for your peripheral clock (timing_calculator)\n\n",
+            );
+        }
+
         if args.issue.to_lowercase().contains("no data")
             || args.issue.to_lowercase().contains("not receiving")
+            || found(IssueKind::Inverted)
+            || found(IssueKind::Idle)
         {
             analysis.push_str("**No Data Received:**\n");
             analysis.push_str("1. **TX/RX Swapped**: Most common wiring issue\n");
@@ -232,6 +380,183 @@ impl ProtocolDebugger {
         analysis
     }
 
+    fn analyze_can_issue(
+        &self,
+        args: &ProtocolDebuggerArgs,
+        decoded: Option<&CanAnalysis>,
+    ) -> String {
+        use can_trace::IssueKind;
+        let mut analysis = String::from("## CAN Protocol Debug Analysis\n\n");
+        let found = |kind| decoded.is_some_and(|d| d.issues.iter().any(|i| i.kind == kind));
+        let error_class = |class: &str| {
+            decoded.is_some_and(|d| {
+                d.frames
+                    .iter()
+                    .any(|f| f.error && can_trace::error_classes(f.id).contains(&class))
+            })
+        };
+        let issue = args.issue.to_lowercase();
+
+        if let Some(decoded) = decoded {
+            analysis.push_str(&decoded_can(decoded, args));
+        }
+
+        analysis.push_str("### Common CAN Issues Check:\n\n");
+
+        if issue.contains("ack")
+            || issue.contains("error frame")
+            || issue.contains("no communication")
+            || found(IssueKind::ErrorFrame)
+        {
+            analysis.push_str("**Error Frames / No ACK:**\n");
+            analysis.push_str("1. **Termination**: 120Ω at each end of the bus\n");
+            analysis.push_str("   - Measure CANH-CANL with power off: ~60Ω is correct, ~120Ω means one terminator is missing\n\n");
+            analysis.push_str("2. **Single Node**: A frame is only acknowledged by another node\n");
+            analysis.push_str("   - A lone node sees ACK errors and retransmits forever\n");
+            analysis.push_str("   - Use loopback mode to test one board on its own\n\n");
+            analysis
+                .push_str("3. **Bitrate/Sample Point**: Every node must use the same bitrate\n");
+            analysis.push_str("4. **Transceiver**: Check the standby/silent pin and the supply (5V vs 3.3V parts)\n\n");
+        }
+
+        if issue.contains("bus off") || issue.contains("bus-off") || error_class("bus off") {
+            analysis.push_str("**Bus-Off:**\n");
+            analysis.push_str(
+                "- The controller leaves the bus once its transmit error counter passes 255\n",
+            );
+            analysis
+                .push_str("- Recovery needs 128 × 11 recessive bits; enable automatic recovery\n");
+            analysis.push_str(
+                "  (ABOM on bxCAN, `ip link set can0 type can restart-ms 100` on Linux)\n",
+            );
+            analysis.push_str(
+                "- Repeated bus-off points at wiring or a bitrate mismatch, not software\n\n",
+            );
+        }
+
+        if issue.contains("collision")
+            || issue.contains("duplicate")
+            || found(IssueKind::IdCollision)
+            || found(IssueKind::LengthMismatch)
+        {
+            analysis.push_str("**ID Collision:**\n");
+            analysis.push_str("- Each ID must have exactly one transmitter; two senders win arbitration together and then cause bit errors\n");
+            analysis.push_str("- Check node IDs/addresses configured in each ECU and the DBC sender for the message\n\n");
+        }
+
+        if issue.contains("load")
+            || issue.contains("latency")
+            || issue.contains("missing")
+            || found(IssueKind::Jitter)
+            || found(IssueKind::Dropout)
+            || decoded
+                .and_then(|d| d.bus_load)
+                .is_some_and(|(_, peak)| peak > 70.0)
+        {
+            analysis.push_str("**Bus Load & Cycle Times:**\n");
+            analysis.push_str("- Keep the average load under ~50% and peaks under ~70%\n");
+            analysis.push_str(
+                "- High-numbered (low-priority) IDs are delayed first when the bus is busy\n",
+            );
+            analysis.push_str("- Jitter and dropouts also come from full TX mailboxes or a blocked sending task\n\n");
+        }
+
+        if let Some(clock) = args.can_clock_hz {
+            analysis.push_str("### Bit Timing:\n\n");
+            analysis.push_str(&can_timing::report(
+                clock,
+                args.can_bitrate.unwrap_or(DEFAULT_CAN_BITRATE),
+            ));
+            analysis.push('\n');
+        }
+
+        analysis.push_str("### Debug Tips:\n");
+        analysis.push_str(
+            "1. **Log Everything**: `candump -ta -e any,0:0,#FFFFFFFF` includes error frames\n",
+        );
+        analysis.push_str("2. **Controller State**: `ip -details -statistics link show can0` shows error counters and state\n");
+        analysis.push_str("3. **Check Levels**: Recessive is ~2.5V on both lines; dominant is CANH ≈ 3.5V, CANL ≈ 1.5V\n");
+        analysis.push_str(
+            "4. **Use a DBC**: Pass dbc to decode signals and check lengths and cycle times\n",
+        );
+
+        analysis
+    }
+
+    fn analyze_modbus_issue(
+        &self,
+        args: &ProtocolDebuggerArgs,
+        decoded: Option<&modbus::Decoded>,
+    ) -> String {
+        use modbus::IssueKind;
+        let mut analysis = String::from("## Modbus Protocol Debug Analysis\n\n");
+        let found = |kind| decoded.is_some_and(|d| d.has(kind));
+        let issue = args.issue.to_lowercase();
+
+        if let Some(decoded) = decoded {
+            analysis.push_str(&decoded_modbus(decoded));
+        }
+
+        analysis.push_str("### Common Modbus Issues Check:\n\n");
+
+        if issue.contains("crc") || issue.contains("garbage") || found(IssueKind::Checksum) {
+            analysis.push_str("**CRC/LRC Errors:**\n");
+            analysis.push_str(
+                "1. **Serial Settings**: Baud rate and parity must match on every device\n",
+            );
+            analysis.push_str("   - The Modbus default is 8E1; 8N2 is the no-parity equivalent, 8N1 is common but non-standard\n\n");
+            analysis.push_str(
+                "2. **RS-485 Bus**: 120Ω termination at both ends and failsafe biasing on A/B\n",
+            );
+            analysis.push_str("3. **CRC Byte Order**: The RTU CRC is sent low byte first\n\n");
+        }
+
+        if issue.contains("timeout")
+            || issue.contains("no response")
+            || found(IssueKind::NoResponse)
+        {
+            analysis.push_str("**No Response / Timeouts:**\n");
+            analysis.push_str("1. **Unit ID**: The server only answers its own address (0 is broadcast and never answered)\n");
+            analysis
+                .push_str("2. **Direction Control**: DE/RE must switch after the last stop bit\n");
+            analysis.push_str("   - Released too early truncates the request; too late collides with the reply\n\n");
+            analysis.push_str("3. **A/B Polarity**: Swapped A/B lines invert every bit\n");
+            analysis.push_str("4. **Response Timeout**: Allow for the server's processing time plus the reply length\n\n");
+        }
+
+        if found(IssueKind::InterFrameGap) || found(IssueKind::IntraFrameGap) {
+            analysis.push_str("**Frame Timing:**\n");
+            analysis.push_str("- Frames need ≥ 3.5 characters of silence between them (1.75 ms above 19200 baud)\n");
+            analysis
+                .push_str("- Pauses over 1.5 characters inside a frame make receivers drop it\n");
+            analysis.push_str("  - Send from a buffer with DMA or the TX FIFO instead of byte-by-byte from a task\n");
+            analysis.push_str("  - USB-serial adapters batch bytes; lower the latency timer or use a native UART\n\n");
+        }
+
+        if issue.contains("exception")
+            || found(IssueKind::Exception)
+            || found(IssueKind::IllegalFunction)
+            || found(IssueKind::Malformed)
+        {
+            analysis.push_str("**Exceptions & Illegal Requests:**\n");
+            analysis.push_str(
+                "- 0x01 illegal function: the server does not implement this function code\n",
+            );
+            analysis.push_str("- 0x02 illegal data address: register 40001 is address 0 on the wire; check for an off-by-one\n");
+            analysis.push_str("- 0x03 illegal data value: quantity limits are 125 registers or 2000 coils per read\n");
+            analysis.push_str("- 0x06 server busy: retry after a delay\n\n");
+        }
+
+        analysis.push_str("### Debug Tips:\n");
+        analysis.push_str("1. **Known-Good Master**: `mbpoll -m rtu -a 1 -r 1 -c 2 -b 9600 -P even /dev/ttyUSB0`\n");
+        analysis.push_str(
+            "2. **Read One Register**: Start with a single holding register before block reads\n",
+        );
+        analysis.push_str("3. **Capture the Wire**: Decode the UART with a logic analyzer to see gaps the software log hides\n");
+
+        analysis
+    }
+
     fn analyze_general_issue(&self, args: &ProtocolDebuggerArgs) -> String {
         format!(
             "## {} Protocol Debug Analysis\n\n\
@@ -267,22 +592,149 @@ impl Tool for ProtocolDebugger {
     }
 
     async fn execute(&self, args: Self::Params) -> ToolResult {
-        let analysis = match args.protocol.to_uppercase().as_str() {
-            "I2C" | "IIC" | "TWI" => self.analyze_i2c_issue(&args),
-            "SPI" => self.analyze_spi_issue(&args),
-            "UART" | "SERIAL" | "RS232" | "RS-232" => self.analyze_uart_issue(&args),
+        let protocol = args.protocol.to_uppercase();
+        let can = matches!(protocol.as_str(), "CAN" | "CAN-FD" | "CANFD");
+        let modbus = protocol.starts_with("MODBUS");
+        // CAN traces and Modbus dumps are not logic captures
+        let capture = match can || modbus {
+            true => None,
+            false => match load_capture(args.captured_data.as_deref()).await {
+                Ok(capture) => capture,
+                Err(e) => return ToolResult::error(e),
+            },
+        };
+        let mut metadata = HashMap::new();
+        let mut decoded_capture = false;
+
+        let analysis = match protocol.as_str() {
+            "I2C" | "IIC" | "TWI" => {
+                let decoded = match capture.as_ref().map(|c| decode_i2c(c, &args)).transpose() {
+                    Ok(decoded) => decoded,
+                    Err(e) => return ToolResult::error(e),
+                };
+                if let Some(decoded) = &decoded {
+                    decoded_capture = true;
+                    metadata.insert(
+                        "transactions".to_string(),
+                        serde_json::json!(decoded.transactions.len()),
+                    );
+                    metadata.insert(
+                        "violations".to_string(),
+                        violation_list(decoded.issues.iter().map(|i| &i.message)),
+                    );
+                }
+                self.analyze_i2c_issue(&args, decoded.as_ref())
+            }
+            "SPI" => {
+                let decoded = match capture.as_ref().map(|c| decode_spi(c, &args)).transpose() {
+                    Ok(decoded) => decoded,
+                    Err(e) => return ToolResult::error(e),
+                };
+                if let Some(decoded) = &decoded {
+                    decoded_capture = true;
+                    metadata.insert(
+                        "frames".to_string(),
+                        serde_json::json!(decoded.frames.len()),
+                    );
+                    metadata.insert("spi_mode".to_string(), serde_json::json!(decoded.mode));
+                    metadata.insert(
+                        "violations".to_string(),
+                        violation_list(decoded.issues.iter().map(|i| &i.message)),
+                    );
+                }
+                self.analyze_spi_issue(&args, decoded.as_ref())
+            }
+            "UART" | "SERIAL" | "RS232" | "RS-232" => {
+                let decoded = match capture.as_ref().map(|c| decode_uart(c, &args)).transpose() {
+                    Ok(decoded) => decoded.unwrap_or_default(),
+                    Err(e) => return ToolResult::error(e),
+                };
+                if !decoded.is_empty() {
+                    decoded_capture = true;
+                    metadata.insert(
+                        "baud_rates".to_string(),
+                        serde_json::json!(decoded
+                            .iter()
+                            .map(|d| (d.line.clone(), d.baud.map(f64::round)))
+                            .collect::<HashMap<_, _>>()),
+                    );
+                    metadata.insert(
+                        "violations".to_string(),
+                        violation_list(decoded.iter().flat_map(|d| &d.issues).map(|i| &i.message)),
+                    );
+                }
+                self.analyze_uart_issue(&args, &decoded)
+            }
+            "CAN" | "CAN-FD" | "CANFD" => {
+                let decoded = match decode_can(&args).await {
+                    Ok(decoded) => decoded,
+                    Err(e) => return ToolResult::error(e),
+                };
+                if let Some(decoded) = &decoded {
+                    decoded_capture = true;
+                    metadata.insert(
+                        "frames".to_string(),
+                        serde_json::json!(decoded.frames.len()),
+                    );
+                    if let Some((overall, peak)) = decoded.bus_load {
+                        metadata.insert(
+                            "bus_load".to_string(),
+                            serde_json::json!({ "overall": overall, "peak": peak }),
+                        );
+                    }
+                    metadata.insert(
+                        "violations".to_string(),
+                        violation_list(decoded.issues.iter().map(|i| &i.message)),
+                    );
+                }
+                self.analyze_can_issue(&args, decoded.as_ref())
+            }
+            _ if modbus => {
+                let decoded = match decode_modbus(&args, &protocol).await {
+                    Ok(decoded) => decoded,
+                    Err(e) => return ToolResult::error(e),
+                };
+                if let Some(decoded) = &decoded {
+                    decoded_capture = true;
+                    metadata.insert(
+                        "frames".to_string(),
+                        serde_json::json!(decoded.frames.len()),
+                    );
+                    metadata.insert(
+                        "transactions".to_string(),
+                        serde_json::json!(decoded.transactions.len()),
+                    );
+                    metadata.insert(
+                        "violations".to_string(),
+                        violation_list(decoded.issues.iter().map(|i| &i.message)),
+                    );
+                }
+                self.analyze_modbus_issue(&args, decoded.as_ref())
+            }
             _ => self.analyze_general_issue(&args),
         };
 
         let mut result = analysis;
 
         // Add captured data analysis if provided
-        if let Some(data) = &args.captured_data {
-            result.push_str("\n### Captured Data Analysis:\n");
-            result.push_str(&format!("```\n{}\n```\n", data));
-            result.push_str("- Look for timing violations\n");
-            result.push_str("- Check for signal integrity issues\n");
-            result.push_str("- Verify protocol compliance\n");
+        match (&capture, &args.captured_data) {
+            _ if decoded_capture => {}
+            (Some(capture), _) => {
+                result.push_str("\n### Captured Data Analysis:\n");
+                result.push_str(&format!("Capture: {}\n", capture.summary()));
+                result.push_str(&format!(
+                    "- No {} decoder yet; look for timing violations on the edges\n",
+                    args.protocol
+                ));
+            }
+            (None, Some(data)) => {
+                result.push_str("\n### Captured Data Analysis:\n");
+                result.push_str(&format!("```\n{}\n```\n", data));
+                result.push_str("- Look for timing violations\n");
+                result.push_str("- Check for signal integrity issues\n");
+                result.push_str("- Verify protocol compliance\n");
+            }
+            (None, None) => {}
         }
 
         // Add specific recommendations based on error messages
@@ -302,8 +754,575 @@ impl Tool for ProtocolDebugger {
             }
         }
 
-        ToolResult::success(result)
+        if metadata.is_empty() {
+            ToolResult::success(result)
+        } else {
+            ToolResult::success_with_metadata(result, metadata)
+        }
+    }
+}
+
+/// Extensions of the capture and trace files `captured_data` may name
+const DATA_EXTENSIONS: &[&str] = &["csv", "vcd", "sr", "log", "candump", "asc", "txt"];
+
+/// `captured_data` that names a file rather than holding data or notes: one
+/// line with a capture/trace extension, or a path separator and no spaces
+fn looks_like_path(data: &str) -> bool {
+    let data = data.trim();
+    if data.is_empty() || data.contains('\n') {
+        return false;
+    }
+    let extension = Path::new(data)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    extension.is_some_and(|e| DATA_EXTENSIONS.contains(&e.as_str()))
+        || (data.contains(['/', '\\']) && !data.contains(char::is_whitespace))
+}
+
+/// `captured_data` text that is meant as a capture or trace rather than
+/// notes: VCD, sigrok CSV comments, candump log lines, or a header followed
+/// by comma-separated or numeric rows
+fn looks_like_data(text: &str) -> bool {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let Some(first) = lines.next() else {
+        return false;
+    };
+    if first.starts_with(['$', '(', ';']) {
+        return true;
+    }
+    let rows: Vec<&str> = lines.collect();
+    let numeric = |row: &&str| {
+        row.chars()
+            .all(|c| c.is_ascii_digit() || c.is_whitespace() || ",.-+eE".contains(c))
+    };
+    !rows.is_empty()
+        && (first.contains(',')
+            || first
+                .split_whitespace()
+                .nth(2)
+                .is_some_and(|t| t.starts_with('['))
+            || rows.iter().all(numeric))
+}
+
+/// Capture named by `captured_data` when it is a capture file or capture text;
+/// anything else is free-form notes
+async fn load_capture(data: Option<&str>) -> Result<Option<Capture>, String> {
+    let Some(data) = data else {
+        return Ok(None);
+    };
+    if Path::new(data.trim()).is_file() {
+        let path = Path::new(data.trim()).to_path_buf();
+        return match tokio::task::spawn_blocking(move || capture::load(&path)).await {
+            Ok(result) => result.map(Some),
+            Err(e) => Err(format!("Capture import failed: {}", e)),
+        };
+    }
+    if looks_like_path(data) {
+        return Err(format!("Capture file not found: {}", data.trim()));
+    }
+    if !looks_like_data(data) {
+        return Ok(None);
+    }
+    capture::parse(data)
+        .map(Some)
+        .map_err(|e| format!("Cannot read the capture in captured_data: {}", e))
+}
+
+/// Imported CAN trace with its per-message statistics and findings
+struct CanAnalysis {
+    frames: Vec<can_trace::Frame>,
+    dbc: Option<Database>,
+    stats: Vec<can_trace::MessageStats>,
+    /// Overall and busiest-100 ms load in percent; `None` without timestamps
+    bus_load: Option<(f64, f64)>,
+    issues: Vec<can_trace::Issue>,
+}
+
+/// Trace named by `captured_data` when it is a trace file or trace text,
+/// decoded with the `dbc` database if one is given
+async fn decode_can(args: &ProtocolDebuggerArgs) -> Result<Option<CanAnalysis>, String> {
+    let Some(data) = args.captured_data.as_deref() else {
+        return Ok(None);
+    };
+    let frames = if Path::new(data.trim()).is_file() {
+        let path = Path::new(data.trim()).to_path_buf();
+        match tokio::task::spawn_blocking(move || can_trace::load(&path)).await {
+            Ok(result) => result?,
+            Err(e) => return Err(format!("Trace import failed: {}", e)),
+        }
+    } else if looks_like_path(data) {
+        return Err(format!("Trace file not found: {}", data.trim()));
+    } else {
+        match can_trace::parse(data) {
+            Ok(frames) => frames,
+            Err(e) if looks_like_data(data) => {
+                return Err(format!("Cannot read the CAN trace in captured_data: {}", e))
+            }
+            Err(_) => return Ok(None),
+        }
+    };
+    let dbc = match &args.dbc {
+        Some(path) => {
+            let path = Path::new(path).to_path_buf();
+            match tokio::task::spawn_blocking(move || Database::load(&path)).await {
+                Ok(result) => Some(result?),
+                Err(e) => return Err(format!("DBC import failed: {}", e)),
+            }
+        }
+        None => None,
+    };
+
+    let stats = can_trace::message_stats(&frames);
+    let timed = frames.first().map(|f| f.time) != frames.last().map(|f| f.time);
+    let bitrate = args.can_bitrate.unwrap_or(DEFAULT_CAN_BITRATE);
+    if bitrate == 0 {
+        return Err("can_bitrate must be greater than zero".to_string());
     }
+    let bus_load = timed.then(|| can_trace::bus_load(&frames, bitrate, 0.1));
+    let issues = can_trace::check(&frames, &stats, dbc.as_ref());
+    Ok(Some(CanAnalysis {
+        frames,
+        dbc,
+        stats,
+        bus_load,
+        issues,
+    }))
+}
+
+/// Frames from a hex dump or from UART bytes in a logic capture; the
+/// protocol name may fix the framing (Modbus-RTU/-ASCII/-TCP)
+async fn decode_modbus(
+    args: &ProtocolDebuggerArgs,
+    protocol: &str,
+) -> Result<Option<modbus::Decoded>, String> {
+    let Some(data) = args.captured_data.as_deref() else {
+        return Ok(None);
+    };
+    let file = Path::new(data.trim()).is_file();
+    let text = if file {
+        let path = Path::new(data.trim()).to_path_buf();
+        match tokio::task::spawn_blocking(move || std::fs::read_to_string(path)).await {
+            Ok(result) => result.map_err(|e| format!("Cannot read {}: {}", data.trim(), e))?,
+            Err(e) => return Err(format!("Capture import failed: {}", e)),
+        }
+    } else if looks_like_path(data) {
+        return Err(format!("Capture file not found: {}", data.trim()));
+    } else {
+        data.to_string()
+    };
+    let forced = if protocol.contains("TCP") {
+        Some(modbus::Mode::Tcp)
+    } else if protocol.contains("ASCII") {
+        Some(modbus::Mode::Ascii)
+    } else if protocol.contains("RTU") {
+        Some(modbus::Mode::Rtu)
+    } else {
+        None
+    };
+    let format = match &args.uart_format {
+        Some(format) => uart_decode::Format::parse(format)?,
+        None => uart_decode::Format::default(),
+    };
+
+    if let Ok(dump) = modbus::parse_dump(&text) {
+        let mode = match dump.mode {
+            modbus::Mode::Ascii => modbus::Mode::Ascii,
+            detected => forced.unwrap_or(detected),
+        };
+        let timing = args
+            .baud_rate
+            .map(|baud| modbus::Timing::new(baud as f64, format.frame_bits()));
+        return Ok(Some(modbus::decode(dump.chunks, mode, timing)));
+    }
+    let capture = match capture::parse(&text) {
+        Ok(capture) => capture,
+        Err(e) if file => {
+            return Err(format!(
+                "{}: neither a Modbus hex dump nor a logic capture ({})",
+                data.trim(),
+                e
+            ))
+        }
+        Err(_) => return Ok(None),
+    };
+
+    // Bytes from every decoded line, in time order
+    let lines = decode_uart(&capture, args)?;
+    let mut bytes: Vec<(f64, usize, u8)> = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(n, line)| line.words.iter().map(move |(t, w)| (*t, n, w.value as u8)))
+        .collect();
+    bytes.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if bytes.first().is_some_and(|b| b.2 == b':') {
+        let text: String = bytes.iter().map(|b| b.2 as char).collect();
+        let dump = modbus::parse_dump(&text)?;
+        return Ok(Some(modbus::decode(dump.chunks, modbus::Mode::Ascii, None)));
+    }
+    let Some((line, baud)) = lines.iter().find_map(|l| l.baud.map(|baud| (l, baud))) else {
+        return Ok(None);
+    };
+    let timing = modbus::Timing::new(baud, line.format.frame_bits());
+    let chunks = modbus::chunks(&bytes, &timing);
+    Ok(Some(modbus::decode(
+        chunks,
+        forced.unwrap_or(modbus::Mode::Rtu),
+        Some(timing),
+    )))
+}
+
+fn decode_i2c(
+    capture: &Capture,
+    args: &ProtocolDebuggerArgs,
+) -> Result<i2c_decode::Decoded, String> {
+    let roles = capture.roles(args.channels.as_deref(), &["SCL", "SDA"])?;
+    let limit = args.stretch_limit_us.unwrap_or(1000.0) * 1e-6;
+    Ok(i2c_decode::decode(capture, roles[0], roles[1], limit))
+}
+
+fn decode_spi(
+    capture: &Capture,
+    args: &ProtocolDebuggerArgs,
+) -> Result<spi_decode::Decoded, String> {
+    if args.spi_mode.is_some_and(|m| m > 3) {
+        return Err(format!(
+            "Invalid SPI mode {}; expected 0-3",
+            args.spi_mode.unwrap_or(0)
+        ));
+    }
+    let mapping = args.channels.as_deref();
+    let sck = capture.roles(mapping, &["SCK|SCLK|CLK"])?[0];
+    let roles = capture.optional_roles(mapping, &["CS|SS|NSS", "MOSI|COPI", "MISO|CIPO"])?;
+    if roles[1].is_none() && roles[2].is_none() {
+        return Err(format!(
+            "No MOSI or MISO channel in the capture (channels: {}); map them with channels, e.g. 'CS=D0,SCK=D1,MOSI=D2,MISO=D3'",
+            capture.channels.join(", ")
+        ));
+    }
+    let lines = Lines {
+        cs: roles[0],
+        sck,
+        mosi: roles[1],
+        miso: roles[2],
+    };
+    Ok(spi_decode::decode(capture, lines, args.spi_mode))
+}
+
+/// Each TX/RX line in the capture, or its only channel
+fn decode_uart(
+    capture: &Capture,
+    args: &ProtocolDebuggerArgs,
+) -> Result<Vec<uart_decode::Decoded>, String> {
+    let format = match &args.uart_format {
+        Some(format) => uart_decode::Format::parse(format)?,
+        None => uart_decode::Format::default(),
+    };
+    let mut lines: Vec<usize> = capture
+        .optional_roles(args.channels.as_deref(), &["TX|TXD", "RX|RXD"])?
+        .into_iter()
+        .flatten()
+        .collect();
+    lines.dedup();
+    if lines.is_empty() {
+        if capture.channels.len() != 1 {
+            return Err(format!(
+                "No TX or RX channel in the capture (channels: {}); map one with channels, e.g. 'RX=D0'",
+                capture.channels.join(", ")
+            ));
+        }
+        lines.push(0);
+    }
+    lines
+        .into_iter()
+        .map(|line| uart_decode::decode(capture, line, format, args.baud_rate))
+        .collect()
+}
+
+fn violation_list<'a>(messages: impl Iterator<Item = &'a String>) -> serde_json::Value {
+    serde_json::json!(messages.collect::<Vec<_>>())
+}
+
+/// `### Protocol Violations` list of (time, message)
+fn violations<'a>(issues: impl Iterator<Item = (f64, &'a str)>) -> String {
+    let mut out = String::from("\n### Protocol Violations:\n\n");
+    let mut any = false;
+    for (time, message) in issues {
+        out.push_str(&format!("- {}: {}\n", format_si(time, "s"), message));
+        any = true;
+    }
+    if !any {
+        out.push_str("None found.\n");
+    }
+    out.push('\n');
+    out
+}
+
+/// Decoded transaction table and protocol violations
+fn decoded_i2c(decoded: &i2c_decode::Decoded) -> String {
+    let mut out = format!(
+        "### Decoded Capture:\n\n{} transactions",
+        decoded.transactions.len()
+    );
+    if let Some(frequency) = decoded.scl_frequency {
+        out.push_str(&format!(", SCL ≈ {}", format_si(frequency, "Hz")));
+    }
+    out.push_str(&format!(
+        ", longest SCL low {}\n\n",
+        format_si(decoded.longest_low, "s")
+    ));
+    out.push_str(&i2c_decode::table(decoded, TABLE_ROWS));
+    out.push_str(&violations(
+        decoded.issues.iter().map(|i| (i.time, i.message.as_str())),
+    ));
+    out
+}
+
+/// Decoded frame table, detected mode and protocol violations
+fn decoded_spi(decoded: &spi_decode::Decoded) -> String {
+    let (cpha0, cpha1) = decoded.phase_votes;
+    let mut out = format!(
+        "### Decoded Capture:\n\n{} frames, {}",
+        decoded.frames.len(),
+        spi_decode::mode_label(decoded.mode)
+    );
+    if decoded.phase_detected() {
+        out.push_str(&format!(
+            " detected ({} of {} data edges agree on CPHA)",
+            cpha0.max(cpha1),
+            cpha0 + cpha1
+        ));
+    }
+    if let Some(frequency) = decoded.sck_frequency {
+        out.push_str(&format!(", SCK ≈ {}", format_si(frequency, "Hz")));
+    }
+    out.push_str("\n\n");
+    out.push_str(&spi_decode::table(decoded, TABLE_ROWS));
+    out.push_str(&violations(
+        decoded.issues.iter().map(|i| (i.time, i.message.as_str())),
+    ));
+    out
+}
+
+/// MISO never leaves its idle level: nothing is driving it
+fn miso_idle(decoded: &spi_decode::Decoded) -> bool {
+    let mut miso = decoded.frames.iter().flat_map(|f| &f.miso).peekable();
+    miso.peek().is_some() && (miso.clone().all(|b| *b == 0xFF) || miso.all(|b| *b == 0x00))
+}
+
+/// Per-line summaries, data dumps and protocol violations
+fn decoded_uart(decoded: &[uart_decode::Decoded]) -> String {
+    let mut out = String::from("### Decoded Capture:\n\n");
+    for line in decoded {
+        out.push_str(&uart_decode::dump(line, TABLE_ROWS));
+        out.push('\n');
+    }
+    let mut issues: Vec<_> = decoded.iter().flat_map(|d| &d.issues).collect();
+    issues.sort_by(|a, b| a.time.total_cmp(&b.time));
+    out.push_str(violations(issues.iter().map(|i| (i.time, i.message.as_str()))).trim_start());
+    out
+}
+
+/// Bus load, per-message statistics, decoded signals and protocol violations
+fn decoded_can(decoded: &CanAnalysis, args: &ProtocolDebuggerArgs) -> String {
+    let errors = decoded.frames.iter().filter(|f| f.error).count();
+    let mut out = format!(
+        "### Decoded Trace:\n\n{} frames, {} IDs, {} error frames",
+        decoded.frames.len(),
+        decoded.stats.len(),
+        errors
+    );
+    match decoded.bus_load {
+        Some((overall, peak)) => {
+            out.push_str(&format!(
+                ", bus load {:.1}% (peak {:.1}% over 100 ms) at {}",
+                overall,
+                peak,
+                format_si(
+                    args.can_bitrate.unwrap_or(DEFAULT_CAN_BITRATE) as f64,
+                    "bit/s"
+                )
+            ));
+            if args.can_bitrate.is_none() {
+                out.push_str(" (assumed; set can_bitrate)");
+            }
+        }
+        None => out.push_str("; no timestamps, so no load or cycle-time statistics"),
+    }
+    out.push_str("\n\n");
+
+    let name = |id, extended| decoded.dbc.as_ref().and_then(|db| db.message(id, extended));
+    out.push_str("| ID | Message | Frames | DLC | Period | Expected | Jitter |\n");
+    out.push_str("|----|---------|--------|-----|--------|----------|--------|\n");
+    for s in decoded.stats.iter().take(TABLE_ROWS) {
+        let message = name(s.id, s.extended);
+        let lengths: Vec<String> = s.lengths.iter().map(|l| l.to_string()).collect();
+        let jitter = match s.jitter_percent() {
+            Some(j) if j > can_trace::JITTER_LIMIT_PERCENT => format!("⚠️ {:.1}%", j),
+            Some(j) => format!("{:.1}%", j),
+            None => "-".to_string(),
+        };
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {} |\n",
+            can_trace::id_label(s.id, s.extended),
+            message.map_or("-", |m| m.name.as_str()),
+            s.count,
+            lengths.join("/"),
+            s.period.map_or("-".to_string(), |p| format_si(p, "s")),
+            message
+                .and_then(|m| m.cycle_time)
+                .map_or("-".to_string(), |c| format_si(c, "s")),
+            jitter
+        ));
+    }
+    if decoded.stats.len() > TABLE_ROWS {
+        out.push_str(&format!(
+            "\n... {} more IDs\n",
+            decoded.stats.len() - TABLE_ROWS
+        ));
+    }
+
+    if let Some(dbc) = &decoded.dbc {
+        out.push_str(&decoded_signals(decoded, dbc));
+        let unknown: Vec<String> = decoded
+            .stats
+            .iter()
+            .filter(|s| dbc.message(s.id, s.extended).is_none())
+            .map(|s| can_trace::id_label(s.id, s.extended))
+            .collect();
+        if !unknown.is_empty() {
+            out.push_str(&format!("\nIDs not in the DBC: {}\n", unknown.join(", ")));
+        }
+    }
+
+    out.push_str(&violations(
+        decoded.issues.iter().map(|i| (i.time, i.message.as_str())),
+    ));
+    out
+}
+
+/// Last, minimum and maximum of every decoded signal, then the first frames
+fn decoded_signals(decoded: &CanAnalysis, dbc: &Database) -> String {
+    struct Summary<'a> {
+        message: &'a str,
+        signal: &'a dbc::Signal,
+        last: String,
+        min: f64,
+        max: f64,
+    }
+    let mut summaries: Vec<Summary> = Vec::new();
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut log =
+        String::from("| Time | ID | Message | Signals |\n|------|----|---------|---------|\n");
+
+    for (n, frame) in decoded
+        .frames
+        .iter()
+        .filter(|f| !f.error && !f.remote)
+        .enumerate()
+    {
+        let Some(message) = dbc.message(frame.id, frame.extended) else {
+            continue;
+        };
+        let mut values = Vec::new();
+        for signal in message.active_signals(&frame.data) {
+            let (Some(value), Some(text)) = (signal.value(&frame.data), signal.format(&frame.data))
+            else {
+                continue;
+            };
+            if n < CAN_LOG_ROWS {
+                values.push(format!("{}={}", signal.name, text));
+            }
+            let key = (message.name.as_str(), signal.name.as_str());
+            let i = *index.entry(key).or_insert_with(|| {
+                summaries.push(Summary {
+                    message: &message.name,
+                    signal,
+                    last: String::new(),
+                    min: value,
+                    max: value,
+                });
+                summaries.len() - 1
+            });
+            let summary = &mut summaries[i];
+            summary.last = text;
+            summary.min = summary.min.min(value);
+            summary.max = summary.max.max(value);
+        }
+        if n < CAN_LOG_ROWS {
+            log.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                format_si(frame.time, "s"),
+                frame.id_label(),
+                message.name,
+                values.join(", ")
+            ));
+        }
+    }
+    if summaries.is_empty() {
+        return "\nNo frames match a DBC message.\n".to_string();
+    }
+
+    let mut out = String::from("\n#### Decoded Signals:\n\n");
+    out.push_str("| Message | Signal | Last | Min | Max | Unit |\n");
+    out.push_str("|---------|--------|------|-----|-----|------|\n");
+    for s in summaries.iter().take(TABLE_ROWS) {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            s.message,
+            s.signal.name,
+            s.last,
+            dbc::number(s.min),
+            dbc::number(s.max),
+            s.signal.unit
+        ));
+    }
+    if summaries.len() > TABLE_ROWS {
+        out.push_str(&format!(
+            "\n... {} more signals\n",
+            summaries.len() - TABLE_ROWS
+        ));
+    }
+    out.push_str("\n#### Frame Log:\n\n");
+    out.push_str(&log);
+    out
+}
+
+/// Framing summary, request/response timeline and protocol violations
+fn decoded_modbus(decoded: &modbus::Decoded) -> String {
+    let mut out = format!(
+        "### Decoded Frames:\n\n{}, {} frames, {} transactions",
+        decoded.mode.label(),
+        decoded.frames.len(),
+        decoded.transactions.len()
+    );
+    if let Some(timing) = &decoded.timing {
+        out.push_str(&format!(
+            " at {:.0} baud (t1.5 = {}, t3.5 = {})",
+            timing.baud,
+            format_si(timing.t15, "s"),
+            format_si(timing.t35, "s")
+        ));
+    }
+    out.push_str("\n\n");
+    out.push_str(&modbus::timeline(decoded, TABLE_ROWS));
+
+    out.push_str("\n### Protocol Violations:\n\n");
+    for issue in &decoded.issues {
+        match issue.time {
+            Some(time) => out.push_str(&format!(
+                "- Frame {} ({}): {}\n",
+                issue.frame + 1,
+                format_si(time, "s"),
+                issue.message
+            )),
+            None => out.push_str(&format!("- Frame {}: {}\n", issue.frame + 1, issue.message)),
+        }
+    }
+    if decoded.issues.is_empty() {
+        out.push_str("None found.\n");
+    }
+    out.push('\n');
+    out
 }
 
 impl ToolDescription for ProtocolDebugger {
@@ -312,7 +1331,7 @@ impl ToolDescription for ProtocolDebugger {
     }
 
     fn description(&self) -> &'static str {
-        "Debug hardware communication protocol issues. Analyzes I2C, SPI, UART, CAN and other protocol problems, providing specific troubleshooting steps and solutions."
+        "Debug hardware communication protocol issues. Analyzes I2C, SPI, UART, CAN and other protocol problems, providing specific troubleshooting steps and solutions. Decodes I2C, SPI and UART traffic from sigrok/PulseView CSV, Saleae CSV or VCD logic-analyzer captures, detecting the SPI mode and UART baud rate and flagging protocol violations. Reads CAN candump, ASC and CSV traces, decodes signals with a DBC file and reports error frames, bus load, ID collisions, cycle-time jitter and bit timing. Parses Modbus RTU/ASCII/TCP hex dumps or decoded UART bytes into a register read/write timeline, validating CRC/LRC, 3.5-character frame gaps and function/exception codes."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -320,3 +1339,363 @@ impl ToolDescription for ProtocolDebugger {
             .unwrap_or_else(|_| serde_json::json!({}))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::i2c_decode::tests::Bus;
+    use tempfile::TempDir;
+
+    fn args(protocol: &str, issue: &str, captured_data: Option<String>) -> ProtocolDebuggerArgs {
+        ProtocolDebuggerArgs {
+            protocol: protocol.to_string(),
+            issue: issue.to_string(),
+            hardware_setup: None,
+            parameters: None,
+            error_messages: None,
+            captured_data,
+            channels: None,
+            stretch_limit_us: None,
+            spi_mode: None,
+            baud_rate: None,
+            uart_format: None,
+            dbc: None,
+            can_bitrate: None,
+            can_clock_hz: None,
+        }
+    }
+
+    /// Saleae-style CSV export of a bus capture
+    fn saleae_csv(capture: &Capture, names: [&str; 2]) -> String {
+        let mut csv = format!("Time [s],{},{}\n", names[0], names[1]);
+        for (time, levels) in &capture.samples {
+            csv.push_str(&format!("{:.9},{},{}\n", time, levels & 1, levels >> 1 & 1));
+        }
+        csv
+    }
+
+    #[tokio::test]
+    async fn test_i2c_capture_diagnosis() {
+        let mut bus = Bus::new();
+        bus.start().byte(0x3C << 1, false).stop();
+        bus.start().byte(0x76 << 1, true).byte(0xF4, true).start();
+        bus.byte(0x76 << 1 | 1, true).byte(0x55, false).stop();
+        bus.start().byte(0x76 << 1, true);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("capture.csv");
+        std::fs::write(&path, saleae_csv(&bus.capture, ["Channel 0", "Channel 1"])).unwrap();
+
+        let mut a = args(
+            "I2C",
+            "sensor does not answer",
+            Some(path.display().to_string()),
+        );
+        let result = ProtocolDebugger::new().execute(a.clone()).await;
+        match result {
+            ToolResult::Error { error, .. } => assert!(error.contains("No SCL channel")),
+            _ => panic!("expected a channel mapping error"),
+        }
+
+        a.channels = Some("SCL=D0,SDA=D1".to_string());
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("4 transactions, SCL ≈ 100.000 kHz"));
+                assert!(output.contains("| 1 | 5.000 µs | S | 0x3C W NACK | - | P |"));
+                assert!(output.contains("| Sr | 0x76 R ACK | 55 NACK | P |"));
+                assert!(output.contains("Address 0x3C not acknowledged"));
+                assert!(output.contains("has no STOP before the capture ends"));
+                assert!(output.contains("**NACK (Not Acknowledged) Issues:**"));
+                assert!(output.contains("**Bus Stuck/Hanging Issues:**"));
+                assert!(!output.contains("### Captured Data Analysis"));
+                let metadata = metadata.unwrap();
+                assert_eq!(metadata["transactions"], 4);
+                assert_eq!(metadata["violations"].as_array().unwrap().len(), 2);
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inline_capture_and_notes() {
+        let mut bus = Bus::new();
+        bus.start().stretched(0x76 << 1, true, 3e-3).stop();
+        let mut a = args(
+            "i2c",
+            "timeouts",
+            Some(saleae_csv(&bus.capture, ["SCL", "SDA"])),
+        );
+        a.stretch_limit_us = Some(500.0);
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Success { output, .. } => {
+                assert!(output.contains(
+                    "SCL held low for 3.000 ms during the address byte (limit 500.000 µs)"
+                ));
+                assert!(output.contains("**Clock Stretching:**"));
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+
+        let notes = args("I2C", "nack", Some("scope shows 0x76 NACK".to_string()));
+        match ProtocolDebugger::new().execute(notes).await {
+            ToolResult::Success { output, .. } => {
+                assert!(output.contains("```\nscope shows 0x76 NACK\n```"));
+                assert!(!output.contains("### Decoded Capture"));
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+
+        // A mistyped path or a broken capture is reported, not treated as notes
+        for (data, expected) in [
+            (
+                "captures/i2c_run3.csv",
+                "Capture file not found: captures/i2c_run3.csv",
+            ),
+            (
+                "Time [s],SCL,SDA\n0,1,high\n",
+                "Cannot read the capture in captured_data",
+            ),
+        ] {
+            match ProtocolDebugger::new()
+                .execute(args("I2C", "nack", Some(data.to_string())))
+                .await
+            {
+                ToolResult::Error { error, .. } => assert!(error.contains(expected), "{}", error),
+                ToolResult::Success { output, .. } => panic!("{}", output),
+            }
+        }
+    }
+
+    /// VCD dump of a capture with a 1 ns timescale
+    fn vcd(capture: &Capture) -> String {
+        let ids = ["!", "\"", "#", "$"];
+        let mut vcd = "$timescale 1 ns $end\n$scope module spi $end\n".to_string();
+        for (id, name) in ids.iter().zip(&capture.channels) {
+            vcd.push_str(&format!("$var wire 1 {} {} $end\n", id, name));
+        }
+        vcd.push_str("$upscope $end\n$enddefinitions $end\n");
+        for (time, levels) in &capture.samples {
+            vcd.push_str(&format!("#{}\n", (time * 1e9).round()));
+            for (channel, id) in ids.iter().enumerate().take(capture.channels.len()) {
+                vcd.push_str(&format!("{}{}\n", levels >> channel & 1, id));
+            }
+        }
+        vcd
+    }
+
+    #[tokio::test]
+    async fn test_spi_mode_mismatch() {
+        let mut bus = crate::tools::hardware::spi_decode::tests::Bus::new(3);
+        bus.frame(&[(0x9F, 0x00), (0x00, 0xEF), (0x00, 0x40)]);
+        let mut a = args("SPI", "reads return garbage", Some(vcd(&bus.capture)));
+        a.spi_mode = Some(0);
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("1 frames, mode 3 (CPOL=1, CPHA=1) detected"));
+                assert!(output.contains("SCK ≈ 1.000 MHz"));
+                assert!(output.contains("| 9F 00 00 | 00 EF 40 |"));
+                assert!(output.contains("but the firmware uses mode 0 (CPOL=0, CPHA=0)"));
+                assert!(output.contains("**SPI Mode Mismatch**"));
+                assert_eq!(metadata.unwrap()["spi_mode"], 3);
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uart_baud_drift() {
+        use crate::tools::hardware::uart_decode::{self, tests::line};
+        // 4% slow clock sampled by sigrok at 1 MHz
+        let capture = line(9600.0 * 0.96, uart_decode::Format::default(), b"OK\r\n");
+        let mut csv = "; CSV generated by libsigrok 0.5.2\n; Samplerate: 1 MHz\nRX\n".to_string();
+        let end = capture.samples.last().unwrap().0;
+        for sample in 0..=(end * 1e6) as usize {
+            csv.push_str(if capture.level_at(0, sample as f64 * 1e-6) {
+                "1\n"
+            } else {
+                "0\n"
+            });
+        }
+        let mut a = args("UART", "occasional wrong characters", Some(csv));
+        a.baud_rate = Some(9600);
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("**RX**: 4 frames"));
+                assert!(output.contains("4F 4B 0D 0A"));
+                assert!(output.contains("-4.0% from 9600"));
+                assert!(output.contains("Receiver is set to 9600 baud"));
+                assert!(output.contains("**Clock Accuracy:**"));
+                assert!(output.contains("**Garbage Data/Wrong Characters:**"));
+                let baud = metadata.unwrap()["baud_rates"]["RX"].as_f64().unwrap();
+                assert!((baud - 9216.0).abs() < 5.0, "{}", baud);
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+
+        let mut a = args("UART", "", None);
+        a.captured_data = Some("Time [s],A,B\n0,1,1\n0.001,0,1\n".to_string());
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Error { error, .. } => assert!(error.contains("No TX or RX channel")),
+            _ => panic!("expected a channel mapping error"),
+        }
+
+        // A line that never toggles is a finding, not a failed call
+        let idle = "Time [s],RX\n0,1\n0.010,1\n".to_string();
+        match ProtocolDebugger::new()
+            .execute(args("UART", "no data", Some(idle)))
+            .await
+        {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("**RX**: no frames, idle line"));
+                assert!(output.contains("RX has no edges; the line is stuck high"));
+                assert!(output.contains("**No Data Received:**"));
+                assert!(metadata.unwrap()["baud_rates"]["RX"].is_null());
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_can_trace_with_dbc() {
+        let dir = TempDir::new().unwrap();
+        let dbc = dir.path().join("vehicle.dbc");
+        std::fs::write(&dbc, crate::tools::hardware::dbc::TEST_DBC).unwrap();
+        let mut log = String::new();
+        for i in 0..100 {
+            // EngineData every 10 ms at 3000 rpm, one cycle missing
+            if i != 60 {
+                log.push_str(&format!(
+                    "({:.6}) can0 100#E02E5A0300000000\n",
+                    i as f64 * 0.010
+                ));
+            }
+            if i % 50 == 0 {
+                log.push_str(&format!("({:.6}) can0 7FF#01\n", i as f64 * 0.010 + 0.001));
+            }
+        }
+        log.push_str("(1.000000) can0 20000020#0000000000000000\n");
+        let trace = dir.path().join("trace.log");
+        std::fs::write(&trace, log).unwrap();
+
+        let mut a = args("CAN", "messages missing", Some(trace.display().to_string()));
+        a.dbc = Some(dbc.display().to_string());
+        a.can_bitrate = Some(250_000);
+        a.can_clock_hz = Some(36_000_000);
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("102 frames, 2 IDs, 1 error frames"));
+                assert!(output.contains("| 0x100 | EngineData | 99 | 8 | 10.000 ms | 10.000 ms |"));
+                assert!(
+                    output.contains("| EngineData | EngineSpeed | 3000 rpm | 3000 | 3000 | rpm |")
+                );
+                assert!(output.contains("Gear=D"));
+                assert!(output.contains("IDs not in the DBC: 0x7FF"));
+                assert!(output.contains("0x100 EngineData missed 1 cycle(s)"));
+                assert!(output.contains("1 error frame(s): no ACK"));
+                assert!(output.contains("**Error Frames / No ACK:**"));
+                assert!(output.contains("**Bus Load & Cycle Times:**"));
+                assert!(output.contains("Recommended: BRP=8 TSEG1=15 TSEG2=2"));
+                assert!(!output.contains("Captured Data Analysis"));
+                let metadata = metadata.unwrap();
+                assert_eq!(metadata["frames"], 102);
+                let load = metadata["bus_load"]["overall"].as_f64().unwrap();
+                assert!(load > 4.0 && load < 6.0, "{}", load);
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+
+        // Notes are not a trace; a missing DBC is an error
+        let notes = args(
+            "CAN",
+            "bus-off after a minute",
+            Some("TEC climbs".to_string()),
+        );
+        match ProtocolDebugger::new().execute(notes).await {
+            ToolResult::Success { output, .. } => {
+                assert!(output.contains("**Bus-Off:**"));
+                assert!(output.contains("TEC climbs"));
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+        // A mistyped path or a broken trace is reported, not treated as notes
+        for (data, expected) in [
+            ("logs/can0.log", "Trace file not found: logs/can0.log"),
+            (
+                "(0.000000) can0 1G0#00\n(0.010000) can0 100#00\n",
+                "Cannot read the CAN trace in captured_data",
+            ),
+        ] {
+            match ProtocolDebugger::new()
+                .execute(args("CAN", "", Some(data.to_string())))
+                .await
+            {
+                ToolResult::Error { error, .. } => assert!(error.contains(expected), "{}", error),
+                ToolResult::Success { output, .. } => panic!("{}", output),
+            }
+        }
+        let mut a = args("CAN", "", Some(trace.display().to_string()));
+        a.dbc = Some(dir.path().join("missing.dbc").display().to_string());
+        assert!(matches!(
+            ProtocolDebugger::new().execute(a).await,
+            ToolResult::Error { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_modbus_dump_and_capture() {
+        let dir = TempDir::new().unwrap();
+        let dump = dir.path().join("poll.txt");
+        std::fs::write(
+            &dump,
+            "0.000 TX: 01 03 00 00 00 02 C4 0B\n\
+             0.050 RX: 01 83 02 C0 F1\n\
+             0.100 TX: 01 06 00 01 00 64 D9 E1\n\
+             0.112 RX: 01 06 00 01 00 64 D9 E1\n",
+        )
+        .unwrap();
+        let mut a = args("Modbus", "exception", Some(dump.display().to_string()));
+        a.baud_rate = Some(9600);
+        match ProtocolDebugger::new().execute(a).await {
+            ToolResult::Success { output, metadata } => {
+                assert!(output.contains("Modbus RTU, 4 frames, 2 transactions at 9600 baud"));
+                assert!(output.contains("| Exception 0x02 (illegal data address) |"));
+                assert!(output.contains("Frame 4 (112.000 ms): Only 3.667 ms"));
+                assert!(output.contains("**Exceptions & Illegal Requests:**"));
+                assert!(output.contains("**Frame Timing:**"));
+                assert_eq!(metadata.unwrap()["transactions"], 2);
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+
+        // Request and reply back to back on one UART line
+        use crate::tools::hardware::uart_decode::{self, tests::line};
+        let capture = line(
+            9600.0,
+            uart_decode::Format::default(),
+            &[
+                0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xC4, 0x0B, 0x01, 0x03, 0x04, 0x00, 0x0A, 0x00,
+                0x14, 0xDA, 0x3E,
+            ],
+        );
+        match ProtocolDebugger::new()
+            .execute(args("Modbus-RTU", "", Some(vcd(&capture))))
+            .await
+        {
+            ToolResult::Success { output, .. } => {
+                assert!(output.contains("| 0x0000 ×2 | 10 20 | OK |"));
+                assert!(output.contains("No silence before this frame"));
+            }
+            ToolResult::Error { error, .. } => panic!("{}", error),
+        }
+
+        match ProtocolDebugger::new()
+            .execute(args("Modbus", "", Some("dumps/poll.txt".to_string())))
+            .await
+        {
+            ToolResult::Error { error, .. } => {
+                assert!(error.contains("Capture file not found: dumps/poll.txt"))
+            }
+            ToolResult::Success { output, .. } => panic!("{}", output),
+        }
+    }
+}
@@ -0,0 +1,704 @@
+use super::register_map;
+use super::svd::{parse_int, Access, Device, Field, Peripheral, Register, SvdMatch};
+use crate::tools::{Tool, ToolCapability, ToolResult};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use wake_llm::ToolDescription;
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RegisterDecoderArgs {
+    /// Register and value to decode (e.g. "RCC_CFGR = 0x0018840A", "USART1.BRR"), or a pasted
+    /// register dump with one `NAME = value` per line (GDB `p/x RCC->CFGR` and `p/x *RCC` output is understood)
+    pub register: String,
+
+    /// Register model: CMSIS-SVD, YAML or JSON register map, or a directory containing one
+    pub model: String,
+
+    /// Optional: Device name used to pick the model file when `model` is a directory
+    pub device: Option<String>,
+
+    /// Optional: Raw value when `register` holds only a name (0x..., #binary or decimal)
+    pub value: Option<String>,
+
+    /// Optional: Field assignments to encode into a value (e.g. ["SW=PLL", "HPRE=0x8"]).
+    /// Starts from `value` or the register reset value
+    pub fields: Option<Vec<String>>,
+}
+
+/// One register named in the input, with the value read for it
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    name: String,
+    value: Option<u64>,
+}
+
+/// `name = value` split of a dump line; the value is the first token after the separator
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line
+        .split_once('=')
+        .or_else(|| line.split_once(':'))
+        .or_else(|| line.rsplit_once([' ', '\t']))?;
+    let value = value.split_whitespace().next()?;
+    Some((name.trim(), value.trim_end_matches(',')))
+}
+
+fn register_name(name: &str) -> String {
+    name.trim().trim_start_matches('*').replace("->", ".")
+}
+
+/// Parse a single value or a register dump into entries
+fn parse_entries(input: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = Vec::new();
+    // Expression of the last GDB print command, awaiting its `$n = ...` result
+    let mut pending: Option<String> = None;
+
+    for line in input.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix("(gdb)").unwrap_or(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((command, expr)) = line.split_once(char::is_whitespace) {
+            if command == "p"
+                || command == "print"
+                || command.starts_with("p/")
+                || command.starts_with("print/")
+            {
+                pending = Some(expr.trim().to_string());
+                continue;
+            }
+        }
+
+        // GDB value history: `$1 = 0x18840a` or `$2 = {CR = 0x83, CFGR = 0x18840a}`
+        if line.starts_with('$') {
+            let (_, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Cannot read '{}'", line))?;
+            let value = value.trim();
+            let expr = pending.take().ok_or_else(|| {
+                format!(
+                    "'{}' has no register name; paste the print command too",
+                    line
+                )
+            })?;
+            if let Some(members) = value.strip_prefix('{') {
+                let peripheral = register_name(&expr);
+                for member in members.trim_end_matches('}').split(',') {
+                    if let Some((name, value)) = split_assignment(member) {
+                        // Skip nested structs and arrays
+                        if let Ok(value) = parse_int(value) {
+                            entries.push(Entry {
+                                name: format!("{}.{}", peripheral, name),
+                                value: Some(value),
+                            });
+                        }
+                    }
+                }
+            } else {
+                let token = value.split_whitespace().next().unwrap_or(value);
+                entries.push(Entry {
+                    name: register_name(&expr),
+                    value: Some(parse_int(token)?),
+                });
+            }
+            continue;
+        }
+
+        match split_assignment(line) {
+            Some((name, value)) if parse_int(value).is_ok() => entries.push(Entry {
+                name: register_name(name),
+                value: parse_int(value).ok(),
+            }),
+            Some((_, value)) if line.contains('=') => {
+                return Err(format!("Invalid register value '{}' in '{}'", value, line));
+            }
+            _ => entries.push(Entry {
+                name: register_name(line),
+                value: None,
+            }),
+        }
+    }
+    if let Some(expr) = pending {
+        entries.push(Entry {
+            name: register_name(&expr),
+            value: None,
+        });
+    }
+    Ok(entries)
+}
+
+/// Register named by `name`; ambiguous bare names use the first peripheral
+fn find_register<'a>(
+    device: &'a Device,
+    name: &str,
+) -> Result<(&'a Peripheral, &'a Register, Vec<String>), String> {
+    let matches = device.lookup(name);
+    let mut registers = matches.iter().filter_map(|m| match m {
+        SvdMatch::Register(p, r) => Some((*p, *r)),
+        _ => None,
+    });
+    match (registers.next(), matches.first()) {
+        (Some((p, r)), _) => {
+            let others = registers
+                .map(|(p, r)| format!("{}_{}", p.name, r.name))
+                .collect();
+            Ok((p, r, others))
+        }
+        (None, Some(SvdMatch::Peripheral(p))) => Err(format!(
+            "'{}' is a peripheral; name one of its registers: {}",
+            name,
+            p.registers
+                .iter()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        (None, Some(SvdMatch::Field(p, r, f))) => Err(format!(
+            "'{}' is field {} of {}_{}; name the register",
+            name, f.name, p.name, r.name
+        )),
+        _ => Err(format!("No register named '{}' in {}", name, device.name)),
+    }
+}
+
+fn hex(value: u64, size: u32) -> String {
+    format!("0x{:0width$X}", value, width = size.div_ceil(4) as usize)
+}
+
+fn size_mask(size: u32) -> u64 {
+    if size >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << size) - 1
+    }
+}
+
+fn meaning(field: &Field, value: u64) -> String {
+    if field.enumerated_values.is_empty() {
+        return String::new();
+    }
+    match field.meaning(value) {
+        Some(e) => match &e.description {
+            Some(d) => format!("{}: {}", e.name, d),
+            None => e.name.clone(),
+        },
+        None => "undefined value".to_string(),
+    }
+}
+
+/// Field value from a number or an enumerated value name
+fn field_value(field: &Field, text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let value = match parse_int(text) {
+        Ok(value) => value,
+        Err(_) => field
+            .enumerated_values
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(text))
+            .and_then(|e| e.value)
+            .ok_or_else(|| {
+                let names: Vec<_> = field
+                    .enumerated_values
+                    .iter()
+                    .filter(|e| e.value.is_some())
+                    .map(|e| e.name.as_str())
+                    .collect();
+                if names.is_empty() {
+                    format!("Field {} takes a number, not '{}'", field.name, text)
+                } else {
+                    format!(
+                        "Field {} has no value '{}'. Known values: {}",
+                        field.name,
+                        text,
+                        names.join(", ")
+                    )
+                }
+            })?,
+    };
+    if value > field.mask() >> field.bit_offset {
+        return Err(format!(
+            "Value {} does not fit field {} {} ({} bits)",
+            value,
+            field.name,
+            field.bit_range(),
+            field.bit_width
+        ));
+    }
+    Ok(value)
+}
+
+pub struct RegisterDecoder;
+
+impl RegisterDecoder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn decode(
+        &self,
+        peripheral: &Peripheral,
+        register: &Register,
+        value: u64,
+        output: &mut String,
+    ) -> serde_json::Value {
+        let address = peripheral.base_address + register.address_offset;
+        output.push_str(&format!(
+            "\n### {}_{} = {}\n\nAddress 0x{:08X}, {} bits, reset {}\n\n\
+            | Bits | Field | Value | Hex | Meaning |\n\
+            |------|-------|-------|-----|---------|\n",
+            peripheral.name,
+            register.name,
+            hex(value, register.size),
+            address,
+            register.size,
+            hex(register.reset_value, register.size)
+        ));
+
+        let mut fields = Vec::new();
+        for field in register.fields.iter().rev() {
+            let field_value = field.extract(value);
+            let changed = field_value != field.extract(register.reset_value);
+            let meaning = meaning(field, field_value);
+            output.push_str(&format!(
+                "| {} | {}{} | {} | 0x{:X} | {} |\n",
+                field.bit_range(),
+                field.name,
+                if changed { " *" } else { "" },
+                field_value,
+                field_value,
+                meaning
+            ));
+            fields.push(json!({
+                "name": field.name,
+                "bits": field.bit_range(),
+                "value": field_value,
+                "meaning": meaning,
+                "changed": changed,
+            }));
+        }
+        if register.fields.is_empty() {
+            output.push_str("| - | (no fields defined) | | | |\n");
+        } else if register
+            .fields
+            .iter()
+            .any(|f| f.extract(value) != f.extract(register.reset_value))
+        {
+            output.push_str("\n`*` differs from the reset value.\n");
+        }
+
+        let covered = register.fields.iter().fold(0, |mask, f| mask | f.mask());
+        if value & !size_mask(register.size) != 0 {
+            output.push_str(&format!(
+                "\n⚠️ Value is wider than the {}-bit register.\n",
+                register.size
+            ));
+        }
+        let stray = value & size_mask(register.size) & !covered;
+        if !register.fields.is_empty() && stray != 0 {
+            output.push_str(&format!(
+                "\n⚠️ Bits set outside any defined field: {} (reserved bits should keep their reset value)\n",
+                hex(stray, register.size)
+            ));
+        }
+
+        json!({
+            "peripheral": peripheral.name,
+            "register": register.name,
+            "address": address,
+            "value": value,
+            "fields": fields,
+        })
+    }
+
+    fn encode(
+        &self,
+        peripheral: &Peripheral,
+        register: &Register,
+        start: Option<u64>,
+        assignments: &[String],
+        output: &mut String,
+    ) -> Result<serde_json::Value, String> {
+        let base = start.unwrap_or(register.reset_value);
+        let mut value = base;
+        let mut modify_mask = 0;
+        let mut rows = String::new();
+        let mut warnings = Vec::new();
+
+        for assignment in assignments {
+            let (name, text) = assignment
+                .split_once('=')
+                .ok_or_else(|| format!("Expected FIELD=VALUE, got '{}'", assignment))?;
+            let field = register.field(name.trim()).ok_or_else(|| {
+                format!(
+                    "{}_{} has no field '{}'. Fields: {}",
+                    peripheral.name,
+                    register.name,
+                    name.trim(),
+                    register
+                        .fields
+                        .iter()
+                        .map(|f| f.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+            let field_value = field_value(field, text)?;
+            if field.access == Some(Access::ReadOnly) {
+                warnings.push(format!("{} is read-only; writes are ignored", field.name));
+            }
+            rows.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                field.bit_range(),
+                field.name,
+                field.extract(value),
+                field_value,
+                meaning(field, field_value)
+            ));
+            value = (value & !field.mask()) | (field_value << field.bit_offset);
+            modify_mask |= field.mask();
+        }
+
+        output.push_str(&format!(
+            "\n### {}_{} ← {}\n\nStarting from {} ({})\n\n\
+            | Bits | Field | Old | New | Meaning |\n\
+            |------|-------|-----|-----|---------|\n{}",
+            peripheral.name,
+            register.name,
+            hex(value, register.size),
+            hex(base, register.size),
+            if start.is_some() {
+                "given value"
+            } else {
+                "reset value"
+            },
+            rows
+        ));
+        output.push_str(&format!(
+            "\n**Value:** {}\n\n**Read-modify-write:** clear mask {}, set bits {}\n",
+            hex(value, register.size),
+            hex(modify_mask, register.size),
+            hex(value & modify_mask, register.size)
+        ));
+        for warning in &warnings {
+            output.push_str(&format!("\n⚠️ {}\n", warning));
+        }
+
+        Ok(json!({
+            "peripheral": peripheral.name,
+            "register": register.name,
+            "address": peripheral.base_address + register.address_offset,
+            "value": value,
+            "mask": modify_mask,
+        }))
+    }
+
+    fn run(&self, args: &RegisterDecoderArgs, path: &Path, device: &Device) -> ToolResult {
+        let mut entries = match parse_entries(&args.register) {
+            Ok(entries) if !entries.is_empty() => entries,
+            Ok(_) => return ToolResult::error("No register given".to_string()),
+            Err(e) => return ToolResult::error(e),
+        };
+        if let Some(value) = &args.value {
+            match (entries.as_mut_slice(), parse_int(value)) {
+                ([entry], Ok(value)) => entry.value = Some(value),
+                (_, Err(e)) => return ToolResult::error(e),
+                _ => {
+                    return ToolResult::error(
+                        "`value` applies to a single register; put values in `register` as NAME = value lines"
+                            .to_string(),
+                    )
+                }
+            }
+        }
+        let assignments = args.fields.clone().unwrap_or_default();
+        if !assignments.is_empty() && entries.len() != 1 {
+            return ToolResult::error("`fields` encodes a single register".to_string());
+        }
+
+        let mut output = format!(
+            "## Register Decode\n\nModel: {} ({})\n",
+            device.name,
+            path.display()
+        );
+        let mut decoded = Vec::new();
+        let mut errors = Vec::new();
+
+        for entry in &entries {
+            let (peripheral, register, others) = match find_register(device, &entry.name) {
+                Ok(found) => found,
+                Err(e) if entries.len() > 1 => {
+                    errors.push(e);
+                    continue;
+                }
+                Err(e) => return ToolResult::error(e),
+            };
+            if !others.is_empty() {
+                output.push_str(&format!(
+                    "\n'{}' also matches {}; using {}_{}\n",
+                    entry.name,
+                    others.join(", "),
+                    peripheral.name,
+                    register.name
+                ));
+            }
+
+            if !assignments.is_empty() {
+                match self.encode(peripheral, register, entry.value, &assignments, &mut output) {
+                    Ok(result) => decoded.push(result),
+                    Err(e) => return ToolResult::error(e),
+                }
+            } else {
+                let value = entry.value.unwrap_or(register.reset_value);
+                if entry.value.is_none() {
+                    output.push_str(&format!(
+                        "\nNo value given for {}; showing the reset value.\n",
+                        entry.name
+                    ));
+                }
+                decoded.push(self.decode(peripheral, register, value, &mut output));
+            }
+        }
+
+        if !errors.is_empty() {
+            output.push_str("\n### Not decoded\n\n");
+            for e in &errors {
+                output.push_str(&format!("- {}\n", e));
+            }
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("model".to_string(), json!(path.display().to_string()));
+        metadata.insert("device".to_string(), json!(device.name));
+        metadata.insert(
+            "mode".to_string(),
+            json!(if assignments.is_empty() {
+                "decode"
+            } else {
+                "encode"
+            }),
+        );
+        metadata.insert("registers".to_string(), json!(decoded));
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+}
+
+impl Default for RegisterDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for RegisterDecoder {
+    type Params = RegisterDecoderArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Read]
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        let path = match register_map::resolve(
+            Path::new(&args.model),
+            args.device.as_deref().unwrap_or_default(),
+        ) {
+            Ok(path) => path,
+            Err(e) => return ToolResult::error(e),
+        };
+
+        // Vendor SVD files run to several megabytes
+        let load_path = path.clone();
+        let device = match tokio::task::spawn_blocking(move || register_map::load(&load_path)).await
+        {
+            Ok(Ok(device)) => device,
+            Ok(Err(e)) => return ToolResult::error(e),
+            Err(e) => return ToolResult::error(format!("Loading register model failed: {}", e)),
+        };
+        self.run(&args, &path, &device)
+    }
+}
+
+impl ToolDescription for RegisterDecoder {
+    fn name(&self) -> &'static str {
+        "register_decode"
+    }
+
+    fn description(&self) -> &'static str {
+        "Decode raw register values into named bitfields, values and enumerated meanings, or encode field assignments into a register value. Uses a CMSIS-SVD file or a project YAML/JSON register map. Accepts `RCC_CFGR = 0x0018840A` style lines and pasted GDB register dumps."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(RegisterDecoderArgs))
+            .unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::register_map::TEST_YAML;
+    use crate::tools::hardware::svd::TEST_SVD;
+    use tempfile::TempDir;
+
+    fn args(register: &str, model: &Path) -> RegisterDecoderArgs {
+        RegisterDecoderArgs {
+            register: register.to_string(),
+            model: model.display().to_string(),
+            device: None,
+            value: None,
+            fields: None,
+        }
+    }
+
+    fn model_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("STM32F4x.svd"), TEST_SVD).unwrap();
+        std::fs::write(dir.path().join("bme280.yaml"), TEST_YAML).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        assert_eq!(
+            parse_entries("RCC_CFGR = 0x0018840A").unwrap(),
+            [Entry {
+                name: "RCC_CFGR".to_string(),
+                value: Some(0x0018_840A)
+            }]
+        );
+        let dump = "(gdb) p/x RCC->CFGR\n$1 = 0x18840a\n(gdb) p/x *USART2\n\
+                    $2 = {SR = 0xc0, DR = 0x0, BRR = 0x683}\nUSART1.BRR: 0x8b";
+        let entries = parse_entries(dump).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "RCC.CFGR",
+                "USART2.SR",
+                "USART2.DR",
+                "USART2.BRR",
+                "USART1.BRR"
+            ]
+        );
+        assert_eq!(entries[3].value, Some(0x683));
+        assert_eq!(entries[4].value, Some(0x8b));
+
+        assert_eq!(parse_entries("USART1.BRR").unwrap()[0].value, None);
+        assert!(parse_entries("RCC_CFGR = banana").is_err());
+        assert!(parse_entries("$1 = 0x5").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decode_svd_register() {
+        let dir = model_dir();
+        let mut request = args("RCC_CFGR = 0x0018840A", dir.path());
+        request.device = Some("stm32f4".to_string());
+        let result = RegisterDecoder::new().execute(request).await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("### RCC_CFGR = 0x0018840A"));
+            assert!(output.contains("| [1:0] | SW * | 2 | 0x2 | PLL: PLL selected |"));
+            assert!(output.contains("| [3:2] | SWS * | 2 | 0x2 | PLL |"));
+            assert!(output.contains("| [7:4] | HPRE | 0 | 0x0 | Div1 |"));
+            // 0b001 has no enumerated value and falls back to the default entry
+            assert!(output.contains("| [12:10] | PPRE1 * | 1 | 0x1 | Other |"));
+            assert!(output.contains("| [15:13] | PPRE2 * | 4 | 0x4 |"));
+            assert!(output.contains("| [20:16] | RTCPRE * | 24 | 0x18 |"));
+            assert!(!output.contains("outside any defined field"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["mode"], "decode");
+            assert_eq!(metadata["registers"][0]["address"], 0x4002_3808u64);
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_gdb_dump_with_unknown_register() {
+        let dir = model_dir();
+        let mut request = args(
+            "(gdb) p/x *USART2\n$1 = {SR = 0xc0, NOPE = 0x1, BRR = 0x683}",
+            &dir.path().join("STM32F4x.svd"),
+        );
+        request.device = None;
+        let result = RegisterDecoder::new().execute(request).await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("### USART2_BRR = 0x00000683"));
+            assert!(output.contains("| [15:4] | DIV_Mantissa * | 104 | 0x68 |"));
+            assert!(output.contains("| [7] | TXE * | 1 | 0x1 |"));
+            // Bit 6 of SR is not a defined field in the model
+            assert!(output.contains("Bits set outside any defined field: 0x00000040"));
+            assert!(output.contains("No register named 'USART2.NOPE'"));
+            assert_eq!(metadata.unwrap()["registers"].as_array().unwrap().len(), 2);
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encode_yaml_fields() {
+        let dir = model_dir();
+        let mut request = args("CTRL_MEAS", dir.path());
+        request.device = Some("BME280".to_string());
+        request.fields = Some(vec![
+            "osrs_t=X2".to_string(),
+            "OSRS_P=0x5".to_string(),
+            "MODE=normal".to_string(),
+        ]);
+        let result = RegisterDecoder::new().execute(request).await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            // 010 101 11
+            assert!(output.contains("**Value:** 0x57"));
+            assert!(output.contains("Starting from 0x00 (reset value)"));
+            assert!(output.contains("| [1:0] | MODE | 0 | 3 | NORMAL |"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["mode"], "encode");
+            assert_eq!(metadata["registers"][0]["value"], 0x57);
+            assert_eq!(metadata["registers"][0]["mask"], 0xFF);
+        } else {
+            panic!("Expected success result");
+        }
+
+        // Starting value is kept outside the assigned fields
+        let mut request = args("BME280.CTRL_MEAS = 0xFF", dir.path());
+        request.device = Some("BME280".to_string());
+        request.fields = Some(vec!["MODE=SLEEP".to_string()]);
+        let output = RegisterDecoder::new().execute(request).await.to_string();
+        assert!(output.contains("**Value:** 0xFC"));
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let dir = model_dir();
+        let tool = RegisterDecoder::new();
+        let cases = [
+            ("RCC", None, "is a peripheral"),
+            ("RCC.CFGR.SW", None, "is field SW"),
+            ("RCC_CFGR", Some("SW=LSE"), "Known values: HSI, HSE, PLL"),
+            ("RCC_CFGR", Some("SW=7"), "does not fit field SW"),
+            ("RCC_CFGR", Some("XYZ=1"), "has no field 'XYZ'"),
+        ];
+        for (register, field, expected) in cases {
+            let mut request = args(register, dir.path());
+            request.device = Some("STM32F4".to_string());
+            request.fields = field.map(|f| vec![f.to_string()]);
+            match tool.execute(request).await {
+                ToolResult::Error { error, .. } => assert!(error.contains(expected), "{}", error),
+                _ => panic!("Expected error for {}", register),
+            }
+        }
+
+        let request = args("RCC_CFGR", &dir.path().join("missing"));
+        assert!(matches!(
+            tool.execute(request).await,
+            ToolResult::Error { .. }
+        ));
+    }
+}
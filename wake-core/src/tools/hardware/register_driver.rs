@@ -0,0 +1,1429 @@
+//! Register-map-driven driver generation.
+//!
+//! Turns one peripheral of a register model (CMSIS-SVD or a YAML/JSON map)
+//! into a C, Rust or MicroPython driver carrying the part's register
+//! addresses, reset values, bitfield accessors and device-ID check. Off-chip
+//! parts are reached over I2C or SPI; SVD peripherals with a base address are
+//! accessed memory-mapped.
+
+use super::register_map;
+use super::svd::{parse_int, Access, Device, Field, Peripheral, Register};
+use std::path::Path;
+
+/// How the driver reaches the registers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interface {
+    I2c,
+    Spi,
+    Mmio,
+}
+
+impl Interface {
+    pub fn parse(protocol: &str, peripheral: &Peripheral) -> Result<Interface, String> {
+        match protocol.trim().to_uppercase().as_str() {
+            "I2C" | "IIC" | "TWI" => Ok(Interface::I2c),
+            "SPI" => Ok(Interface::Spi),
+            "MMIO" | "MEMORY-MAPPED" | "MEMORY MAPPED" | "APB" | "AHB" => Ok(Interface::Mmio),
+            _ if peripheral.base_address != 0 => Ok(Interface::Mmio),
+            _ => Err(format!(
+                "Register-map drivers talk I2C, SPI or memory-mapped (MMIO) registers, not {}",
+                protocol
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceId {
+    pub register: String,
+    pub value: u64,
+}
+
+/// Peripheral of a register model plus what the driver needs around it
+#[derive(Clone, Debug)]
+pub struct DriverModel {
+    pub source: String,
+    pub peripheral: Peripheral,
+    pub interface: Interface,
+    pub device_id: Option<DeviceId>,
+}
+
+impl DriverModel {
+    pub fn new(
+        source: &str,
+        device: &Device,
+        component: &str,
+        peripheral: Option<&str>,
+        protocol: &str,
+        device_id: Option<&str>,
+    ) -> Result<DriverModel, String> {
+        let peripheral = select(device, component, peripheral)?.clone();
+        if peripheral.registers.is_empty() {
+            return Err(format!("{} has no registers", peripheral.name));
+        }
+        let interface = Interface::parse(protocol, &peripheral)?;
+        let device_id = find_device_id(&peripheral, device_id)?;
+        Ok(DriverModel {
+            source: source.to_string(),
+            peripheral,
+            interface,
+            device_id,
+        })
+    }
+
+    /// Bus register addresses take two bytes
+    pub fn wide_address(&self) -> bool {
+        self.address_type().0 == "uint16_t"
+    }
+
+    /// Bytes spanned by the register block
+    pub fn block_size(&self) -> u64 {
+        self.peripheral
+            .registers
+            .iter()
+            .map(|r| r.address_offset + r.size.div_ceil(8) as u64)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Register addresses as used in the driver: offsets for MMIO, bus addresses otherwise
+    fn address_type(&self) -> (&'static str, &'static str) {
+        let widest = self
+            .peripheral
+            .registers
+            .iter()
+            .map(|r| r.address_offset)
+            .max()
+            .unwrap_or(0);
+        match (self.interface, widest) {
+            (Interface::Mmio, _) => ("uint32_t", "u32"),
+            (_, 0..=0xFF) => ("uint8_t", "u8"),
+            _ => ("uint16_t", "u16"),
+        }
+    }
+
+    fn register(&self, name: &str) -> Option<&Register> {
+        self.peripheral.register(name)
+    }
+}
+
+/// Register model from a file, a directory holding one named after the component, or inline YAML/JSON
+pub fn load(register_map: &str, component: &str) -> Result<(String, Device), String> {
+    let text = register_map.trim();
+    if text.starts_with('{') {
+        return Ok(("inline JSON".to_string(), register_map::parse_json(text)?));
+    }
+    if text.contains('\n') {
+        return Ok(("inline YAML".to_string(), register_map::parse_yaml(text)?));
+    }
+    let path = register_map::resolve(Path::new(text), component)?;
+    let device = register_map::load(&path)?;
+    Ok((path.display().to_string(), device))
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_uppercase()
+}
+
+fn select<'a>(
+    device: &'a Device,
+    component: &str,
+    peripheral: Option<&str>,
+) -> Result<&'a Peripheral, String> {
+    if let Some(name) = peripheral {
+        return device.peripheral(name).ok_or_else(|| {
+            format!(
+                "No peripheral named '{}' in {}; available: {}",
+                name,
+                device.name,
+                peripheral_names(device)
+            )
+        });
+    }
+    if let [only] = device.peripherals.as_slice() {
+        return Ok(only);
+    }
+    let component = normalize(component);
+    device
+        .peripherals
+        .iter()
+        .find(|p| normalize(&p.name) == component)
+        .ok_or_else(|| {
+            format!(
+                "{} has {} peripherals; choose one with `peripheral`: {}",
+                device.name,
+                device.peripherals.len(),
+                peripheral_names(device)
+            )
+        })
+}
+
+fn peripheral_names(device: &Device) -> String {
+    let names: Vec<&str> = device.peripherals.iter().map(|p| p.name.as_str()).collect();
+    if names.len() > 20 {
+        format!(
+            "{}, ... ({} more)",
+            names[..20].join(", "),
+            names.len() - 20
+        )
+    } else {
+        names.join(", ")
+    }
+}
+
+fn is_id_register(name: &str) -> bool {
+    let name = normalize(name);
+    matches!(
+        name.as_str(),
+        "WHOAMI" | "ID" | "CHIPID" | "DEVICEID" | "DEVID" | "PARTID" | "PRODUCTID" | "PID"
+    ) || name.ends_with("WHOAMI")
+        || name.ends_with("CHIPID")
+        || name.ends_with("DEVICEID")
+}
+
+/// Device-ID check from `REGISTER=VALUE`, a bare value for the ID register, or the ID register's reset value
+fn find_device_id(peripheral: &Peripheral, spec: Option<&str>) -> Result<Option<DeviceId>, String> {
+    let id_register = || {
+        peripheral
+            .registers
+            .iter()
+            .find(|r| is_id_register(&r.name) && r.access != Some(Access::WriteOnly))
+    };
+    let Some(spec) = spec else {
+        return Ok(id_register()
+            .filter(|r| r.reset_value != 0)
+            .map(|r| DeviceId {
+                register: r.name.clone(),
+                value: r.reset_value,
+            }));
+    };
+    let (register, value) = match spec.split_once('=') {
+        Some((register, value)) => {
+            let register = peripheral.register(register.trim()).ok_or_else(|| {
+                format!(
+                    "device_id names unknown register '{}' of {}",
+                    register.trim(),
+                    peripheral.name
+                )
+            })?;
+            (register, value)
+        }
+        None => (
+            id_register().ok_or_else(|| {
+                format!(
+                    "{} has no ID register; give device_id as REGISTER=VALUE",
+                    peripheral.name
+                )
+            })?,
+            spec,
+        ),
+    };
+    Ok(Some(DeviceId {
+        register: register.name.clone(),
+        value: parse_int(value.trim())?,
+    }))
+}
+
+/// `MPU-6050` -> `MPU_6050`
+fn upper_ident(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    while out.contains("__") {
+        out = out.replace("__", "_");
+    }
+    let out = out.trim_matches('_').to_string();
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", out)
+    } else {
+        out
+    }
+}
+
+const RUST_KEYWORDS: [&str; 20] = [
+    "as", "box", "const", "crate", "enum", "fn", "for", "if", "impl", "in", "loop", "match", "mod",
+    "move", "ref", "self", "static", "struct", "type", "use",
+];
+
+fn snake_ident(name: &str) -> String {
+    let ident = upper_ident(name).to_lowercase();
+    if RUST_KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{}", ident)
+    } else {
+        ident
+    }
+}
+
+/// `bme280` -> `Bme280`
+pub fn camel_ident(name: &str) -> String {
+    let camel: String = upper_ident(name)
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let lower = part.to_lowercase();
+            let mut chars = lower.chars();
+            chars.next().map_or(String::new(), |c| {
+                c.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect();
+    if camel.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("Device{}", camel)
+    } else {
+        camel
+    }
+}
+
+/// File and module stem of a component: `MPU-6050` -> `mpu_6050`
+pub fn file_stem(component: &str) -> String {
+    upper_ident(component).to_lowercase()
+}
+
+/// Smallest C and Rust integer types holding `bits`
+fn value_type(bits: u32) -> (&'static str, &'static str) {
+    match bits {
+        0..=8 => ("uint8_t", "u8"),
+        9..=16 => ("uint16_t", "u16"),
+        17..=32 => ("uint32_t", "u32"),
+        _ => ("uint64_t", "u64"),
+    }
+}
+
+fn hex(value: u64, bits: u32) -> String {
+    format!(
+        "0x{:0width$X}",
+        value,
+        width = bits.div_ceil(4).max(2) as usize
+    )
+}
+
+fn readable(r: &Register) -> bool {
+    r.access != Some(Access::WriteOnly) && r.access != Some(Access::WriteOnce)
+}
+
+fn writable(r: &Register) -> bool {
+    r.access != Some(Access::ReadOnly)
+}
+
+fn summary(r: &Register) -> String {
+    let mut text = format!(
+        "{} ({}, reset {})",
+        r.name,
+        r.access.map_or("RW", |a| a.label()),
+        hex(r.reset_value, r.size)
+    );
+    if let Some(description) = &r.description {
+        text.push_str(&format!(
+            ": {}",
+            description.split_whitespace().collect::<Vec<_>>().join(" ")
+        ));
+    }
+    text
+}
+
+/// Enumerated values with a name and a value
+fn enum_values(f: &Field) -> impl Iterator<Item = (String, u64)> + '_ {
+    f.enumerated_values
+        .iter()
+        .filter_map(|e| e.value.map(|v| (upper_ident(&e.name), v)))
+}
+
+fn header(
+    model: &DriverModel,
+    component: &str,
+    platform: &str,
+    protocol: &str,
+    comment: &str,
+) -> String {
+    let mut out = format!(
+        "{c} {} Driver for {}\n{c} Communication Protocol: {}\n{c} Register map: {} ({}, {} registers)\n",
+        component,
+        platform,
+        protocol,
+        model.source,
+        model.peripheral.name,
+        model.peripheral.registers.len(),
+        c = comment
+    );
+    if let Some(id) = &model.device_id {
+        out.push_str(&format!(
+            "{} Device ID: {} == {}\n",
+            comment,
+            id.register,
+            hex(id.value, model.register(&id.register).map_or(8, |r| r.size))
+        ));
+    }
+    out.push_str(&format!(
+        "{} Generated by Wake - Hardware-First Coding Agent\n",
+        comment
+    ));
+    out
+}
+
+pub fn generate_c(
+    model: &DriverModel,
+    component: &str,
+    platform: &str,
+    protocol: &str,
+    includes: &str,
+) -> String {
+    let prefix = upper_ident(component);
+    let lower = prefix.to_lowercase();
+    let (addr_c, _) = model.address_type();
+    let mut out = format!(
+        "/**\n{} */\n\n#ifndef {p}_H\n#define {p}_H\n\n#include <stddef.h>\n#include <stdint.h>\n#include <stdbool.h>\n\n// Hardware-specific includes\n{}\n\n#ifdef __cplusplus\nextern \"C\" {{\n#endif\n",
+        header(model, component, platform, protocol, " *"),
+        includes,
+        p = prefix
+    );
+
+    if model.interface == Interface::Mmio {
+        out.push_str(&format!(
+            "\n#define {}_BASE 0x{:08X}UL\n",
+            prefix, model.peripheral.base_address
+        ));
+    }
+    out.push_str(&format!("\n// {} register addresses\n", component));
+    for r in &model.peripheral.registers {
+        out.push_str(&format!(
+            "#define {}_REG_{} {}U /* {} */\n",
+            prefix,
+            upper_ident(&r.name),
+            hex(r.address_offset, 8),
+            summary(r)
+        ));
+    }
+    out.push_str("\n// Reset values\n");
+    for r in &model.peripheral.registers {
+        out.push_str(&format!(
+            "#define {}_{}_RESET {}U\n",
+            prefix,
+            upper_ident(&r.name),
+            hex(r.reset_value, r.size)
+        ));
+    }
+
+    for r in model
+        .peripheral
+        .registers
+        .iter()
+        .filter(|r| !r.fields.is_empty())
+    {
+        let reg = format!("{}_{}", prefix, upper_ident(&r.name));
+        out.push_str(&format!("\n// {} fields\n", r.name));
+        for f in &r.fields {
+            let field = format!("{}_{}", reg, upper_ident(&f.name));
+            out.push_str(&format!(
+                "#define {f}_POS {}U\n#define {f}_MSK {}U /* {} */\n#define {f}_GET(reg) (((reg) & {f}_MSK) >> {f}_POS)\n#define {f}_SET(reg, val) (((reg) & ~{f}_MSK) | (((val) << {f}_POS) & {f}_MSK))\n",
+                f.bit_offset,
+                hex(f.mask(), r.size),
+                f.bit_range(),
+                f = field
+            ));
+            for (name, value) in enum_values(f) {
+                out.push_str(&format!("#define {}_{} {}U\n", field, name, value));
+            }
+        }
+    }
+    if let Some(id) = &model.device_id {
+        out.push_str(&format!(
+            "\n#define {}_DEVICE_ID {}U\n",
+            prefix,
+            hex(id.value, 8)
+        ));
+    }
+
+    match model.interface {
+        Interface::Mmio => {
+            out.push('\n');
+            for r in &model.peripheral.registers {
+                let (ty, _) = value_type(r.size);
+                let name = upper_ident(&r.name);
+                let access = format!(
+                    "(*(volatile {} *)({p}_BASE + {p}_REG_{}))",
+                    ty,
+                    name,
+                    p = prefix
+                );
+                if readable(r) {
+                    out.push_str(&format!(
+                        "static inline {} {}_read_{}(void) {{ return {}; }}\n",
+                        ty,
+                        lower,
+                        name.to_lowercase(),
+                        access
+                    ));
+                }
+                if writable(r) {
+                    out.push_str(&format!(
+                        "static inline void {}_write_{}({} value) {{ {} = value; }}\n",
+                        lower,
+                        name.to_lowercase(),
+                        ty,
+                        access
+                    ));
+                }
+            }
+            out.push_str(&format!(
+                "\n/**\n * Check the {} device ID\n * @return 0 on success, -1 on a wrong ID\n */\nint {}_init(void);\n",
+                component, lower
+            ));
+        }
+        Interface::I2c | Interface::Spi => {
+            let spi = model.interface == Interface::Spi;
+            out.push_str(&format!(
+                "\n/* Bus transfers supplied by the application (e.g. an I2C handle and address in ctx) */\ntypedef int (*{l}_read_fn)(void *ctx, {a} reg, uint8_t *data, size_t len);\ntypedef int (*{l}_write_fn)(void *ctx, {a} reg, const uint8_t *data, size_t len);\n\n// {} Handle Structure\ntypedef struct {{\n    {l}_read_fn read;\n    {l}_write_fn write;\n    void *ctx;\n}} {l}_t;\n",
+                component,
+                l = lower,
+                a = addr_c
+            ));
+            if spi {
+                out.push_str(&format!(
+                    "\n/* SPI reads set bit 7 of the register address */\n#define {}_SPI_READ 0x80U\n",
+                    prefix
+                ));
+            }
+            out.push_str("\n/* Multi-byte registers are transferred MSB first */\n");
+            for r in &model.peripheral.registers {
+                let (ty, _) = value_type(r.size);
+                let bytes = r.size.div_ceil(8);
+                let name = upper_ident(&r.name);
+                let reg = format!("{}_REG_{}", prefix, name);
+                if readable(r) {
+                    let address = if spi {
+                        format!("({})({} | {}_SPI_READ)", addr_c, reg, prefix)
+                    } else {
+                        reg.clone()
+                    };
+                    let assemble = if bytes == 1 {
+                        "    *value = buf[0];\n".to_string()
+                    } else {
+                        format!(
+                            "    *value = 0;\n    for (size_t i = 0; i < {}; i++) *value = ({})((*value << 8) | buf[i]);\n",
+                            bytes, ty
+                        )
+                    };
+                    out.push_str(&format!(
+                        "static inline int {l}_read_{n}({l}_t *dev, {ty} *value)\n{{\n    uint8_t buf[{b}];\n    int err = dev->read(dev->ctx, {addr}, buf, {b});\n    if (err) return err;\n{assemble}    return 0;\n}}\n",
+                        l = lower,
+                        n = name.to_lowercase(),
+                        ty = ty,
+                        b = bytes,
+                        addr = address,
+                        assemble = assemble
+                    ));
+                }
+                if writable(r) {
+                    let split = if bytes == 1 {
+                        "    uint8_t buf[1] = { value };\n".to_string()
+                    } else {
+                        format!(
+                            "    uint8_t buf[{b}];\n    for (size_t i = 0; i < {b}; i++) buf[i] = (uint8_t)(value >> (8 * ({b} - 1 - i)));\n",
+                            b = bytes
+                        )
+                    };
+                    out.push_str(&format!(
+                        "static inline int {l}_write_{n}({l}_t *dev, {ty} value)\n{{\n{split}    return dev->write(dev->ctx, {reg}, buf, {b});\n}}\n",
+                        l = lower,
+                        n = name.to_lowercase(),
+                        ty = ty,
+                        split = split,
+                        reg = reg,
+                        b = bytes
+                    ));
+                }
+            }
+            out.push_str(&format!(
+                "\n/**\n * Initialize the {} device and check its ID\n * @param dev Device handle with bus callbacks set\n * @return 0 on success, a bus error code, or -1 on a wrong device ID\n */\nint {}_init({}_t *dev);\n",
+                component, lower, lower
+            ));
+        }
+    }
+    out.push_str(&format!(
+        "\n#ifdef __cplusplus\n}}\n#endif\n\n#endif // {}_H\n",
+        prefix
+    ));
+    out
+}
+
+/// C source holding the functions declared by [`generate_c`]
+pub fn generate_c_source(model: &DriverModel, component: &str, header: &str) -> String {
+    let prefix = upper_ident(component);
+    let lower = prefix.to_lowercase();
+    let mut out = format!(
+        "/**\n * {} driver implementation\n * Generated by Wake - Hardware-First Coding Agent\n */\n\n#include \"{}\"\n\n",
+        component, header
+    );
+    let id = model.device_id.as_ref().map(|id| {
+        (
+            upper_ident(&id.register).to_lowercase(),
+            value_type(model.register(&id.register).map_or(8, |r| r.size)).0,
+        )
+    });
+    match (model.interface, id) {
+        (Interface::Mmio, Some((register, _))) => out.push_str(&format!(
+            "int {l}_init(void)\n{{\n    return {l}_read_{}() == {}_DEVICE_ID ? 0 : -1;\n}}\n",
+            register,
+            prefix,
+            l = lower
+        )),
+        (Interface::Mmio, None) => out.push_str(&format!(
+            "int {}_init(void)\n{{\n    // The register map has no ID register\n    return 0;\n}}\n",
+            lower
+        )),
+        (_, Some((register, ty))) => out.push_str(&format!(
+            "int {l}_init({l}_t *dev)\n{{\n    {} id;\n    int err = {l}_read_{}(dev, &id);\n    if (err) return err;\n    return id == {}_DEVICE_ID ? 0 : -1;\n}}\n",
+            ty,
+            register,
+            prefix,
+            l = lower
+        )),
+        (_, None) => out.push_str(&format!(
+            "int {l}_init({l}_t *dev)\n{{\n    // The register map has no ID register\n    (void)dev;\n    return 0;\n}}\n",
+            l = lower
+        )),
+    }
+    out
+}
+
+/// First register the example can read
+fn example_register(model: &DriverModel) -> Option<&Register> {
+    model
+        .device_id
+        .as_ref()
+        .and_then(|id| model.register(&id.register))
+        .or_else(|| model.peripheral.registers.iter().find(|r| readable(r)))
+}
+
+/// C program bringing the driver up
+pub fn c_example(model: &DriverModel, component: &str, header: &str) -> String {
+    let lower = upper_ident(component).to_lowercase();
+    let (addr_c, _) = model.address_type();
+    let mut out = format!(
+        "/* {} example */\n\n#include <stddef.h>\n#include \"{}\"\n",
+        component, header
+    );
+    let read = example_register(model)
+        .map(|r| (upper_ident(&r.name).to_lowercase(), value_type(r.size).0));
+    if model.interface == Interface::Mmio {
+        out.push_str(&format!(
+            "\nint main(void)\n{{\n    if ({}_init() != 0) {{\n        return 1;\n    }}\n",
+            lower
+        ));
+        if let Some((name, ty)) = read {
+            out.push_str(&format!(
+                "    {ty} {n} = {l}_read_{n}();\n    (void){n};\n",
+                ty = ty,
+                n = name,
+                l = lower
+            ));
+        }
+    } else {
+        out.push_str(&format!(
+            "\n/* Replace with the board's {bus} transfers */\nstatic int bus_read(void *ctx, {a} reg, uint8_t *data, size_t len)\n{{\n    (void)ctx; (void)reg; (void)data; (void)len;\n    return -1;\n}}\n\nstatic int bus_write(void *ctx, {a} reg, const uint8_t *data, size_t len)\n{{\n    (void)ctx; (void)reg; (void)data; (void)len;\n    return -1;\n}}\n\nint main(void)\n{{\n    {l}_t dev = {{ .read = bus_read, .write = bus_write, .ctx = NULL }};\n    if ({l}_init(&dev) != 0) {{\n        return 1;\n    }}\n",
+            bus = if model.interface == Interface::Spi { "SPI" } else { "I2C" },
+            a = addr_c,
+            l = lower
+        ));
+        if let Some((name, ty)) = read {
+            out.push_str(&format!(
+                "    {ty} {n};\n    if ({l}_read_{n}(&dev, &{n}) != 0) {{\n        return 1;\n    }}\n",
+                ty = ty,
+                n = name,
+                l = lower
+            ));
+        }
+    }
+    out.push_str("    return 0;\n}\n");
+    out
+}
+
+/// Rust example for `examples/`, bringing the driver up on any embedded-hal bus
+pub fn rust_example(
+    model: &DriverModel,
+    component: &str,
+    crate_name: &str,
+    asynchronous: bool,
+) -> String {
+    let driver = camel_ident(component);
+    if model.interface != Interface::Mmio {
+        return bus_example(
+            component,
+            crate_name,
+            model.interface == Interface::Spi,
+            asynchronous,
+        );
+    }
+    format!(
+        "//! {c} example\n\nuse {k}::{{registers, {d}}};\n\n/// Bring the {c} up; only valid on the target itself\n#[allow(dead_code)]\nfn bring_up() -> {d} {{\n    // SAFETY: the base address comes from the register map and nothing else owns the block\n    unsafe {{ {d}::new(registers::BASE_ADDRESS) }}\n}}\n\nfn main() {{\n    // Call bring_up() from the firmware's entry point\n}}\n",
+        k = crate_name.replace('-', "_"),
+        d = driver,
+        c = component
+    )
+}
+
+/// Example of a bus driver crate: a generic `bring_up` over the embedded-hal
+/// 1.0 (or embedded-hal-async) I2c/SpiDevice trait
+pub fn bus_example(component: &str, crate_name: &str, spi: bool, asynchronous: bool) -> String {
+    let driver = camel_ident(component);
+    let (asyncness, wait) = if asynchronous {
+        ("async ", ".await")
+    } else {
+        ("", "")
+    };
+    let mut out = format!("//! {} example\n\n", component);
+    if spi {
+        out.push_str(&format!(
+            "use {h}::spi::SpiDevice;\nuse {k}::{{Error, {d}}};\n\n/// Bring the {c} up on an SPI device (bus plus chip select, e.g. from embedded-hal-bus)\n#[allow(dead_code)]\n{a}fn bring_up<SPI: SpiDevice>(spi: SPI) -> Result<{d}<SPI>, Error<SPI::Error>> {{\n    let mut device = {d}::new(spi);\n    device.init(){w}?;\n    Ok(device)\n}}\n",
+            h = hal_crate(asynchronous),
+            k = crate_name.replace('-', "_"),
+            d = driver,
+            c = component,
+            a = asyncness,
+            w = wait
+        ));
+    } else {
+        out.push_str(&format!(
+            "use {h}::i2c::I2c;\nuse {k}::{{Error, {d}}};\n\n/// 7-bit I2C address of the part (see its datasheet)\nconst ADDRESS: u8 = 0x00;\n\n/// Bring the {c} up on the board's I2C bus\n#[allow(dead_code)]\n{a}fn bring_up<I2C: I2c>(i2c: I2C) -> Result<{d}<I2C>, Error<I2C::Error>> {{\n    let mut device = {d}::new(i2c, ADDRESS);\n    device.init(){w}?;\n    Ok(device)\n}}\n",
+            h = hal_crate(asynchronous),
+            k = crate_name.replace('-', "_"),
+            d = driver,
+            c = component,
+            a = asyncness,
+            w = wait
+        ));
+    }
+    out.push_str(&format!(
+        "\nfn main() {{\n    // Create the bus with your board's HAL and pass it to bring_up(){}\n}}\n",
+        if asynchronous {
+            " from an async executor (e.g. an Embassy task)"
+        } else {
+            ""
+        }
+    ));
+    out
+}
+
+/// MicroPython script using the driver module
+pub fn micropython_example(model: &DriverModel, component: &str, module: &str) -> String {
+    let class = camel_ident(component);
+    let mut out = format!("\"\"\"{} example\"\"\"\n\n", component);
+    match model.interface {
+        Interface::I2c => out.push_str(&format!(
+            "from machine import I2C\nfrom {m} import {c}\n\nADDRESS = 0x00  # 7-bit I2C address of the part\n\ndevice = {c}(I2C(0), ADDRESS)\n",
+            m = module,
+            c = class
+        )),
+        Interface::Spi => out.push_str(&format!(
+            "from machine import Pin, SPI\nfrom {m} import {c}\n\ndevice = {c}(SPI(1, baudrate=1000000), Pin(5, Pin.OUT))\n",
+            m = module,
+            c = class
+        )),
+        Interface::Mmio => out.push_str(&format!(
+            "from {m} import {c}\n\ndevice = {c}()\n",
+            m = module,
+            c = class
+        )),
+    }
+    out.push_str("device.init()\n");
+    if let Some(r) = example_register(model) {
+        let size = match model.interface {
+            Interface::Mmio => r.size.div_ceil(8).next_power_of_two().min(4),
+            _ => r.size.div_ceil(8),
+        };
+        out.push_str(&format!(
+            "print(\"{n} =\", hex(device.read_register(device.REG_{u}, {s})))\n",
+            n = r.name,
+            u = upper_ident(&r.name),
+            s = size
+        ));
+    }
+    out
+}
+
+fn hal_crate(asynchronous: bool) -> &'static str {
+    if asynchronous {
+        "embedded_hal_async"
+    } else {
+        "embedded_hal"
+    }
+}
+
+/// `[dependencies]` and `[dev-dependencies]` of a generated driver crate
+pub fn cargo_dependencies(bus: bool, asynchronous: bool) -> String {
+    if !bus {
+        return "[dependencies]\n".to_string();
+    }
+    if asynchronous {
+        "[dependencies]\nembedded-hal-async = \"1.0\"\n\n[dev-dependencies]\nembedded-hal-mock = { version = \"0.11\", default-features = false, features = [\"eh1\", \"embedded-hal-async\"] }\nfutures = { version = \"0.3\", default-features = false, features = [\"executor\"] }\n".to_string()
+    } else {
+        "[dependencies]\nembedded-hal = \"1.0\"\n\n[dev-dependencies]\nembedded-hal-mock = { version = \"0.11\", default-features = false, features = [\"eh1\"] }\n".to_string()
+    }
+}
+
+/// `0x12, 0x34` list of the low `bytes` bytes of `value`, MSB first
+fn byte_list(value: u64, bytes: u32) -> String {
+    (0..bytes)
+        .rev()
+        .map(|i| format!("0x{:02X}", (value >> (8 * i)) & 0xFF))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Register address as sent on the bus, with the SPI read bit when `read`
+fn address_frame(model: &DriverModel, r: &Register, read: bool) -> String {
+    let bytes = if model.address_type().1 == "u8" { 1 } else { 2 };
+    let mut address = r.address_offset;
+    if read && model.interface == Interface::Spi {
+        address |= 0x80 << (8 * (bytes - 1));
+    }
+    byte_list(address, bytes)
+}
+
+/// Expected bus transactions of one register access
+fn expectation(model: &DriverModel, r: &Register, read: bool, data: &str) -> String {
+    let address = address_frame(model, r, read);
+    match (model.interface, read) {
+        (Interface::Spi, true) => format!(
+            "            Transaction::transaction_start(),\n            Transaction::write_vec(vec![{}]),\n            Transaction::read_vec(vec![{}]),\n            Transaction::transaction_end(),\n",
+            address, data
+        ),
+        (Interface::Spi, false) => format!(
+            "            Transaction::transaction_start(),\n            Transaction::write_vec(vec![{}]),\n            Transaction::write_vec(vec![{}]),\n            Transaction::transaction_end(),\n",
+            address, data
+        ),
+        (_, true) => format!(
+            "            Transaction::write_read(ADDRESS, vec![{}], vec![{}]),\n",
+            address, data
+        ),
+        (_, false) => format!(
+            "            Transaction::write(ADDRESS, vec![{}, {}]),\n",
+            address, data
+        ),
+    }
+}
+
+/// `#[cfg(test)]` suite of a generated driver: bus drivers replay recorded
+/// embedded-hal-mock transactions, MMIO drivers run against a RAM copy of
+/// the register block
+fn rust_tests(model: &DriverModel, driver: &str, asynchronous: bool) -> String {
+    let mut out = "\n#[cfg(test)]\nmod tests {\n    use super::*;\n".to_string();
+    let rw = model
+        .peripheral
+        .registers
+        .iter()
+        .find(|r| readable(r) && writable(r));
+
+    if model.interface == Interface::Mmio {
+        let words = model.block_size().max(4).div_ceil(4);
+        out.push_str(&format!(
+            "\n    #[test]\n    fn registers_in_ram() {{\n        // A RAM copy of the register block stands in for the peripheral\n        let mut block = [0u32; {}];\n        let base = block.as_mut_ptr() as usize;\n        let {}device = unsafe {{ {}::new(base) }};\n",
+            words,
+            if rw.is_some() { "mut " } else { "" },
+            driver
+        ));
+        if let Some(r) = rw {
+            out.push_str(&format!(
+                "\n        device.write_{n}(registers::{m}::RESET);\n        assert_eq!(device.read_{n}(), registers::{m}::RESET);\n",
+                n = snake_ident(&r.name).trim_start_matches("r#"),
+                m = snake_ident(&r.name)
+            ));
+        }
+        if let Some(id) = &model.device_id {
+            if let Some(r) = model.register(&id.register) {
+                out.push_str(&format!(
+                    "\n        unsafe {{\n            core::ptr::write_volatile((base + registers::{m}::ADDRESS as usize) as *mut {ty}, DEVICE_ID);\n        }}\n        assert!(device.verify_device_id().is_ok());\n",
+                    m = snake_ident(&r.name),
+                    ty = value_type(r.size).1
+                ));
+            }
+        }
+        out.push_str("    }\n}\n");
+        return out;
+    }
+
+    let spi = model.interface == Interface::Spi;
+    out.push_str(&format!(
+        "    use embedded_hal_mock::eh1::{}::{{Mock, Transaction}};\n",
+        if spi { "spi" } else { "i2c" }
+    ));
+    if asynchronous {
+        out.push_str("    use futures::executor::block_on;\n");
+    }
+    let new = if spi {
+        format!("{}::new(bus.clone())", driver)
+    } else {
+        out.push_str("\n    const ADDRESS: u8 = 0x42;\n");
+        format!("{}::new(bus.clone(), ADDRESS)", driver)
+    };
+    let call = |expr: String| {
+        if asynchronous {
+            format!("block_on({})", expr)
+        } else {
+            expr
+        }
+    };
+    let test = |name: &str, expectations: &str, body: &str| {
+        format!(
+            "\n    #[test]\n    fn {}() {{\n        let mut bus = Mock::new(&[\n{}        ]);\n        let mut device = {};\n{}        bus.done();\n    }}\n",
+            name, expectations, new, body
+        )
+    };
+
+    match model
+        .device_id
+        .as_ref()
+        .and_then(|id| model.register(&id.register).map(|r| (id, r)))
+    {
+        Some((id, r)) => {
+            let bytes = r.size.div_ceil(8);
+            let wrong = !id.value & (u64::MAX >> (64 - 8 * bytes.min(8)));
+            out.push_str(&test(
+                "init_checks_device_id",
+                &expectation(model, r, true, &byte_list(id.value, bytes)),
+                &format!("        {}.unwrap();\n", call("device.init()".to_string())),
+            ));
+            out.push_str(&test(
+                "init_rejects_wrong_device_id",
+                &expectation(model, r, true, &byte_list(wrong, bytes)),
+                &format!(
+                    "        assert!(matches!(\n            {},\n            Err(Error::WrongDeviceId(_))\n        ));\n",
+                    call("device.init()".to_string())
+                ),
+            ));
+        }
+        None => out.push_str(&test(
+            "init_without_device_id",
+            "",
+            &format!("        {}.unwrap();\n", call("device.init()".to_string())),
+        )),
+    }
+
+    // Multi-byte registers travel MSB first
+    let pattern = 0x1234_5678_9ABC_DEF0u64;
+    if let Some(r) = model.peripheral.registers.iter().find(|r| readable(r)) {
+        let bytes = r.size.div_ceil(8);
+        let value = pattern >> (64 - 8 * bytes.min(8));
+        let name = snake_ident(&r.name);
+        let name = name.trim_start_matches("r#");
+        out.push_str(&test(
+            &format!("read_{}", name),
+            &expectation(model, r, true, &byte_list(value, bytes)),
+            &format!(
+                "        assert_eq!({}.unwrap(), {});\n",
+                call(format!("device.read_{}()", name)),
+                hex(value, r.size)
+            ),
+        ));
+    }
+    if let Some(r) = model.peripheral.registers.iter().find(|r| writable(r)) {
+        let bytes = r.size.div_ceil(8);
+        let value = pattern >> (64 - 8 * bytes.min(8));
+        let name = snake_ident(&r.name);
+        let name = name.trim_start_matches("r#");
+        out.push_str(&test(
+            &format!("write_{}", name),
+            &expectation(model, r, false, &byte_list(value, bytes)),
+            &format!(
+                "        {}.unwrap();\n",
+                call(format!("device.write_{}({})", name, hex(value, r.size)))
+            ),
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn generate_rust(
+    model: &DriverModel,
+    component: &str,
+    platform: &str,
+    protocol: &str,
+    asynchronous: bool,
+) -> String {
+    let driver = camel_ident(component);
+    let (_, addr_rs) = model.address_type();
+    let hal = hal_crate(asynchronous);
+    let mut out = header(model, component, platform, protocol, "//!");
+    out.push_str("\n#![cfg_attr(not(test), no_std)]\n\n");
+    match model.interface {
+        Interface::I2c => out.push_str(&format!("use {}::i2c::I2c;\n\n", hal)),
+        Interface::Spi => out.push_str(&format!("use {}::spi::{{Operation, SpiDevice}};\n\n", hal)),
+        Interface::Mmio => {}
+    }
+
+    out.push_str(&format!(
+        "/// {} register definitions\npub mod registers {{\n",
+        component
+    ));
+    if model.interface == Interface::Mmio {
+        out.push_str(&format!(
+            "    /// Peripheral base address\n    pub const BASE_ADDRESS: usize = 0x{:08X};\n\n",
+            model.peripheral.base_address
+        ));
+    }
+    for (i, r) in model.peripheral.registers.iter().enumerate() {
+        let (_, ty) = value_type(r.size);
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "    /// {}\n    pub mod {} {{\n        pub const ADDRESS: {} = {};\n        pub const RESET: {} = {};\n",
+            summary(r),
+            snake_ident(&r.name),
+            addr_rs,
+            hex(r.address_offset, 8),
+            ty,
+            hex(r.reset_value, r.size)
+        ));
+        for f in &r.fields {
+            out.push_str(&format!(
+                "\n        /// {} field {}\n        pub mod {} {{\n            pub const SHIFT: u32 = {};\n            pub const MASK: {ty} = {};\n",
+                f.name,
+                f.bit_range(),
+                snake_ident(&f.name),
+                f.bit_offset,
+                hex(f.mask(), r.size),
+                ty = ty
+            ));
+            for (name, value) in enum_values(f) {
+                out.push_str(&format!(
+                    "            pub const {}: {} = {};\n",
+                    name, ty, value
+                ));
+            }
+            out.push_str(&format!(
+                "\n            pub const fn get(reg: {ty}) -> {ty} {{\n                (reg & MASK) >> SHIFT\n            }}\n\n            pub const fn set(reg: {ty}, value: {ty}) -> {ty} {{\n                (reg & !MASK) | ((value << SHIFT) & MASK)\n            }}\n        }}\n",
+                ty = ty
+            ));
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+
+    let id_type = model
+        .device_id
+        .as_ref()
+        .map(|id| value_type(model.register(&id.register).map_or(8, |r| r.size)).1);
+    if let (Some(id), Some(ty)) = (&model.device_id, id_type) {
+        out.push_str(&format!(
+            "\n/// Expected value of the {} register\npub const DEVICE_ID: {} = {};\n",
+            id.register,
+            ty,
+            hex(id.value, 8)
+        ));
+    }
+
+    if model.interface == Interface::Mmio {
+        out.push_str(&format!(
+            "\n/// {c} memory-mapped registers\npub struct {d} {{\n    base: usize,\n}}\n\nimpl {d} {{\n    /// # Safety\n    ///\n    /// `base` must be the address of the {c} register block and no other\n    /// code may access it concurrently.\n    pub const unsafe fn new(base: usize) -> Self {{\n        Self {{ base }}\n    }}\n",
+            c = component,
+            d = driver
+        ));
+        for r in &model.peripheral.registers {
+            let (_, ty) = value_type(r.size);
+            let module = snake_ident(&r.name);
+            let name = module.trim_start_matches("r#");
+            if readable(r) {
+                out.push_str(&format!(
+                    "\n    pub fn read_{n}(&self) -> {ty} {{\n        // SAFETY: `new` guarantees the register block address\n        unsafe {{ core::ptr::read_volatile((self.base + registers::{m}::ADDRESS as usize) as *const {ty}) }}\n    }}\n",
+                    n = name,
+                    m = module,
+                    ty = ty
+                ));
+            }
+            if writable(r) {
+                out.push_str(&format!(
+                    "\n    pub fn write_{n}(&mut self, value: {ty}) {{\n        // SAFETY: `new` guarantees the register block address\n        unsafe {{ core::ptr::write_volatile((self.base + registers::{m}::ADDRESS as usize) as *mut {ty}, value) }}\n    }}\n",
+                    n = name,
+                    m = module,
+                    ty = ty
+                ));
+            }
+        }
+        if let (Some(id), Some(ty)) = (&model.device_id, id_type) {
+            out.push_str(&format!(
+                "\n    /// Check the device ID, returning the value read on a mismatch\n    pub fn verify_device_id(&self) -> Result<(), {}> {{\n        let id = self.read_{}();\n        if id == DEVICE_ID {{\n            Ok(())\n        }} else {{\n            Err(id)\n        }}\n    }}\n",
+                ty,
+                snake_ident(&id.register).trim_start_matches("r#")
+            ));
+        }
+        out.push_str("}\n");
+        out.push_str(&rust_tests(model, &driver, asynchronous));
+        return out;
+    }
+
+    let spi = model.interface == Interface::Spi;
+    let err = format!("Error<{}::Error>", if spi { "SPI" } else { "I2C" });
+    let (asyncness, wait) = if asynchronous {
+        ("async ", ".await")
+    } else {
+        ("", "")
+    };
+    let addr_bytes = if addr_rs == "u8" { 1 } else { 2 };
+    out.push_str(&format!(
+        "\n/// Driver errors\n#[derive(Debug)]\npub enum Error<E> {{\n    /// Bus error\n    Bus(E),\n    /// The ID register did not hold the expected value\n    WrongDeviceId(u64),\n}}\n\n/// {} driver structure\n",
+        component
+    ));
+    if spi {
+        out.push_str(&format!(
+            "pub struct {d}<SPI> {{\n    spi: SPI,\n}}\n\nimpl<SPI: SpiDevice> {d}<SPI> {{\n    /// Create a new {c} driver instance on an SPI device (bus plus chip select)\n    pub fn new(spi: SPI) -> Self {{\n        Self {{ spi }}\n    }}\n\n    /// Release the SPI device\n    pub fn release(self) -> SPI {{\n        self.spi\n    }}\n",
+            d = driver,
+            c = component
+        ));
+    } else {
+        out.push_str(&format!(
+            "pub struct {d}<I2C> {{\n    i2c: I2C,\n    address: u8,\n}}\n\nimpl<I2C: I2c> {d}<I2C> {{\n    /// Create a new {c} driver instance at a 7-bit I2C address\n    pub fn new(i2c: I2C, address: u8) -> Self {{\n        Self {{ i2c, address }}\n    }}\n\n    /// Release the I2C bus\n    pub fn release(self) -> I2C {{\n        self.i2c\n    }}\n",
+            d = driver,
+            c = component
+        ));
+    }
+
+    out.push_str(&format!(
+        "\n    /// Initialize the {} device and check its ID\n    pub {}fn init(&mut self) -> Result<(), {}> {{\n",
+        component, asyncness, err
+    ));
+    if model.device_id.is_some() {
+        out.push_str(&format!(
+            "        self.verify_device_id(){}\n    }}\n",
+            wait
+        ));
+    } else {
+        out.push_str("        // The register map has no ID register\n        Ok(())\n    }\n");
+    }
+    if let (Some(id), Some(_)) = (&model.device_id, id_type) {
+        out.push_str(&format!(
+            "\n    /// Check the device ID\n    pub {}fn verify_device_id(&mut self) -> Result<(), {}> {{\n        let id = self.read_{}(){}?;\n        if id != DEVICE_ID {{\n            return Err(Error::WrongDeviceId(id as u64));\n        }}\n        Ok(())\n    }}\n",
+            asyncness,
+            err,
+            snake_ident(&id.register).trim_start_matches("r#"),
+            wait
+        ));
+    }
+
+    // Raw transfers; multi-byte registers go MSB first
+    let address_bytes = if addr_bytes == 1 {
+        "[reg]".to_string()
+    } else {
+        "reg.to_be_bytes()".to_string()
+    };
+    if spi {
+        out.push_str(&format!(
+            "\n    /// Read `buf.len()` bytes starting at `reg`\n    pub {asy}fn read_bytes(&mut self, reg: {a}, buf: &mut [u8]) -> Result<(), {err}> {{\n        let mut header = {hdr};\n        header[0] |= 0x80;\n        let result = self\n            .spi\n            .transaction(&mut [Operation::Write(&header), Operation::Read(buf)]){w};\n        result.map_err(Error::Bus)\n    }}\n\n    /// Write `data` starting at `reg`\n    pub {asy}fn write_bytes(&mut self, reg: {a}, data: &[u8]) -> Result<(), {err}> {{\n        let header = {hdr};\n        let result = self\n            .spi\n            .transaction(&mut [Operation::Write(&header), Operation::Write(data)]){w};\n        result.map_err(Error::Bus)\n    }}\n",
+            asy = asyncness,
+            a = addr_rs,
+            err = err,
+            hdr = address_bytes,
+            w = wait
+        ));
+    } else {
+        out.push_str(&format!(
+            "\n    /// Read `buf.len()` bytes starting at `reg`\n    pub {asy}fn read_bytes(&mut self, reg: {a}, buf: &mut [u8]) -> Result<(), {err}> {{\n        let result = self.i2c.write_read(self.address, &{hdr}, buf){w};\n        result.map_err(Error::Bus)\n    }}\n\n    /// Write `data` starting at `reg`\n    pub {asy}fn write_bytes(&mut self, reg: {a}, data: &[u8]) -> Result<(), {err}> {{\n        let mut frame = [0u8; {max}];\n        let header = {hdr};\n        let len = header.len() + data.len();\n        frame[..header.len()].copy_from_slice(&header);\n        frame[header.len()..len].copy_from_slice(data);\n        let result = self.i2c.write(self.address, &frame[..len]){w};\n        result.map_err(Error::Bus)\n    }}\n",
+            asy = asyncness,
+            a = addr_rs,
+            err = err,
+            max = addr_bytes + 8,
+            hdr = address_bytes,
+            w = wait
+        ));
+    }
+
+    for r in &model.peripheral.registers {
+        let (_, ty) = value_type(r.size);
+        let bytes = r.size.div_ceil(8);
+        let module = snake_ident(&r.name);
+        let name = module.trim_start_matches("r#");
+        let buffer_bytes = match ty {
+            "u8" => 1,
+            "u16" => 2,
+            "u32" => 4,
+            _ => 8,
+        };
+        if readable(r) {
+            let convert = if buffer_bytes == bytes {
+                format!("{}::from_be_bytes(buf)", ty)
+            } else {
+                format!("buf.iter().fold(0, |value, &b| (value << 8) | b as {})", ty)
+            };
+            out.push_str(&format!(
+                "\n    /// Read {}\n    pub {asy}fn read_{n}(&mut self) -> Result<{ty}, {err}> {{\n        let mut buf = [0u8; {b}];\n        self.read_bytes(registers::{m}::ADDRESS, &mut buf){w}?;\n        Ok({convert})\n    }}\n",
+                r.name,
+                asy = asyncness,
+                err = err,
+                w = wait,
+                n = name,
+                m = module,
+                ty = ty,
+                b = bytes,
+                convert = convert
+            ));
+        }
+        if writable(r) {
+            let split = if buffer_bytes == bytes {
+                "value.to_be_bytes()".to_string()
+            } else {
+                format!("&value.to_be_bytes()[{}..]", buffer_bytes - bytes)
+            };
+            out.push_str(&format!(
+                "\n    /// Write {}\n    pub {asy}fn write_{n}(&mut self, value: {ty}) -> Result<(), {err}> {{\n        self.write_bytes(registers::{m}::ADDRESS, {amp}{split}){w}\n    }}\n",
+                r.name,
+                asy = asyncness,
+                err = err,
+                w = wait,
+                n = name,
+                m = module,
+                ty = ty,
+                amp = if buffer_bytes == bytes { "&" } else { "" },
+                split = split
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out.push_str(&rust_tests(model, &driver, asynchronous));
+    out
+}
+
+pub fn generate_micropython(
+    model: &DriverModel,
+    component: &str,
+    platform: &str,
+    protocol: &str,
+) -> String {
+    let class = camel_ident(component);
+    let mut out = format!(
+        "\"\"\"\n{}\"\"\"\n\n",
+        header(model, component, platform, protocol, "")
+            .replace("\n ", "\n")
+            .trim_start()
+    );
+    if model.interface == Interface::Mmio {
+        out.push_str("import machine\n\n");
+    }
+    out.push_str(&format!(
+        "class {}:\n    \"\"\"Driver for {} hardware component\"\"\"\n\n",
+        class, component
+    ));
+    if model.interface == Interface::Mmio {
+        out.push_str(&format!(
+            "    BASE = 0x{:08X}\n\n",
+            model.peripheral.base_address
+        ));
+    }
+    out.push_str("    # Register addresses and reset values\n");
+    for r in &model.peripheral.registers {
+        let name = upper_ident(&r.name);
+        out.push_str(&format!(
+            "    REG_{n} = {}\n    {n}_RESET = {}\n",
+            hex(r.address_offset, 8),
+            hex(r.reset_value, r.size),
+            n = name
+        ));
+    }
+    for r in model
+        .peripheral
+        .registers
+        .iter()
+        .filter(|r| !r.fields.is_empty())
+    {
+        out.push_str(&format!("\n    # {} fields: (shift, mask)\n", r.name));
+        for f in &r.fields {
+            let field = format!("{}_{}", upper_ident(&r.name), upper_ident(&f.name));
+            out.push_str(&format!(
+                "    {} = ({}, {})\n",
+                field,
+                f.bit_offset,
+                hex(f.mask(), r.size)
+            ));
+            for (name, value) in enum_values(f) {
+                out.push_str(&format!("    {}_{} = {}\n", field, name, value));
+            }
+        }
+    }
+    if let Some(id) = &model.device_id {
+        out.push_str(&format!("\n    DEVICE_ID = {}\n", hex(id.value, 8)));
+    }
+
+    let sizes: Vec<(String, u32)> = model
+        .peripheral
+        .registers
+        .iter()
+        .map(|r| (upper_ident(&r.name), r.size.div_ceil(8)))
+        .collect();
+    match model.interface {
+        Interface::I2c => out.push_str(
+            "\n    def __init__(self, i2c, address):\n        self.i2c = i2c\n        self.address = address\n\n    def read_register(self, reg, size=1):\n        \"\"\"Read a register, MSB first\"\"\"\n        return int.from_bytes(self.i2c.readfrom_mem(self.address, reg, size), \"big\")\n\n    def write_register(self, reg, value, size=1):\n        \"\"\"Write a register, MSB first\"\"\"\n        self.i2c.writeto_mem(self.address, reg, value.to_bytes(size, \"big\"))\n",
+        ),
+        Interface::Spi => out.push_str(
+            "\n    def __init__(self, spi, cs):\n        self.spi = spi\n        self.cs = cs\n        self.cs.value(1)\n\n    def read_register(self, reg, size=1):\n        \"\"\"Read a register, MSB first; bit 7 of the address marks a read\"\"\"\n        self.cs.value(0)\n        try:\n            self.spi.write(bytes([reg | 0x80]))\n            data = self.spi.read(size)\n        finally:\n            self.cs.value(1)\n        return int.from_bytes(data, \"big\")\n\n    def write_register(self, reg, value, size=1):\n        \"\"\"Write a register, MSB first\"\"\"\n        self.cs.value(0)\n        try:\n            self.spi.write(bytes([reg]) + value.to_bytes(size, \"big\"))\n        finally:\n            self.cs.value(1)\n",
+        ),
+        Interface::Mmio => out.push_str(
+            "\n    def __init__(self, base=None):\n        self.base = self.BASE if base is None else base\n\n    def read_register(self, reg, size=4):\n        mem = {1: machine.mem8, 2: machine.mem16, 4: machine.mem32}[size]\n        return mem[self.base + reg]\n\n    def write_register(self, reg, value, size=4):\n        mem = {1: machine.mem8, 2: machine.mem16, 4: machine.mem32}[size]\n        mem[self.base + reg] = value\n",
+        ),
+    }
+    out.push_str(
+        "\n    @staticmethod\n    def get_field(value, field):\n        shift, mask = field\n        return (value & mask) >> shift\n\n    @staticmethod\n    def set_field(value, field, field_value):\n        shift, mask = field\n        return (value & ~mask) | ((field_value << shift) & mask)\n",
+    );
+    out.push_str(&format!(
+        "\n    def init(self):\n        \"\"\"Initialize the {} device and check its ID\"\"\"\n",
+        component
+    ));
+    match &model.device_id {
+        Some(id) => {
+            let name = upper_ident(&id.register);
+            let size = sizes
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map_or(1, |(_, s)| *s);
+            out.push_str(&format!(
+                "        device_id = self.read_register(self.REG_{}, {})\n        if device_id != self.DEVICE_ID:\n            raise OSError(\"Unexpected device ID {{:#x}}\".format(device_id))\n",
+                name, size
+            ));
+        }
+        None => out.push_str("        # The register map has no ID register\n        pass\n"),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::hardware::register_map::{parse_yaml, TEST_YAML};
+    use crate::tools::hardware::svd::TEST_SVD;
+
+    const ID_YAML: &str = "  - name: CHIP_ID\n    address: 0xD0\n    access: read-only\n    reset: 0x60\n  - name: PRESS\n    address: 0xF7\n    size: 24\n    access: read-only\n";
+
+    fn bme280(protocol: &str) -> DriverModel {
+        let device = parse_yaml(&format!("{}{}", TEST_YAML, ID_YAML)).unwrap();
+        DriverModel::new("bme280.yaml", &device, "BME280", None, protocol, None).unwrap()
+    }
+
+    #[test]
+    fn test_model_selection_and_device_id() {
+        let model = bme280("I2C");
+        assert_eq!(
+            model.device_id,
+            Some(DeviceId {
+                register: "CHIP_ID".to_string(),
+                value: 0x60
+            })
+        );
+        assert_eq!(model.interface, Interface::I2c);
+
+        let device = Device::parse(TEST_SVD).unwrap();
+        let err = DriverModel::new("x.svd", &device, "stm32f4", None, "MMIO", None).unwrap_err();
+        assert!(
+            err.contains("choose one with `peripheral`: RCC, USART1, USART2"),
+            "{}",
+            err
+        );
+        let model =
+            DriverModel::new("x.svd", &device, "USART2", None, "UART", Some("SR=0xC0")).unwrap();
+        assert_eq!(model.interface, Interface::Mmio);
+        assert_eq!(model.peripheral.base_address, 0x40004400);
+        assert_eq!(model.device_id.unwrap().value, 0xC0);
+
+        let device = parse_yaml(TEST_YAML).unwrap();
+        assert!(DriverModel::new("m", &device, "BME280", None, "UART", None).is_err());
+        assert!(DriverModel::new("m", &device, "BME280", None, "I2C", Some("0x60")).is_err());
+        assert!(DriverModel::new("m", &device, "BME280", None, "I2C", None)
+            .unwrap()
+            .device_id
+            .is_none());
+    }
+
+    #[test]
+    fn test_c_driver() {
+        let c = generate_c(
+            &bme280("I2C"),
+            "BME280",
+            "STM32",
+            "I2C",
+            "#include \"stm32f4xx_hal.h\"",
+        );
+        assert!(c.contains("#define BME280_REG_CTRL_MEAS 0xF4U"));
+        assert!(c.contains("#define BME280_CHIP_ID_RESET 0x60U"));
+        assert!(c.contains("#define BME280_CTRL_MEAS_OSRS_T_POS 5U"));
+        assert!(c.contains("#define BME280_CTRL_MEAS_OSRS_T_MSK 0xE0U /* [7:5] */"));
+        assert!(c.contains("#define BME280_CTRL_MEAS_MODE_NORMAL 3U"));
+        assert!(c.contains("#define BME280_DEVICE_ID 0x60U"));
+        assert!(c.contains("static inline int bme280_read_press(bme280_t *dev, uint32_t *value)"));
+        assert!(!c.contains("bme280_write_status"));
+        assert!(c.contains("int bme280_init(bme280_t *dev);"));
+        let source = generate_c_source(&bme280("I2C"), "BME280", "bme280.h");
+        assert!(source.contains("#include \"bme280.h\""));
+        assert!(source.contains("int err = bme280_read_chip_id(dev, &id);"));
+        assert!(source.contains("return id == BME280_DEVICE_ID ? 0 : -1;"));
+        let example = c_example(&bme280("I2C"), "BME280", "bme280.h");
+        assert!(example
+            .contains("bme280_t dev = { .read = bus_read, .write = bus_write, .ctx = NULL };"));
+        assert!(!c.contains("WHO_AM_I"));
+
+        let spi = generate_c(&bme280("SPI"), "BME280", "STM32", "SPI", "");
+        assert!(spi.contains("(uint8_t)(BME280_REG_CTRL_MEAS | BME280_SPI_READ)"));
+
+        let device = Device::parse(TEST_SVD).unwrap();
+        let model = DriverModel::new("x.svd", &device, "USART1", None, "MMIO", None).unwrap();
+        let c = generate_c(&model, "USART1", "STM32", "MMIO", "");
+        assert!(c.contains("#define USART1_BASE 0x40011000UL"));
+        assert!(c.contains("#define USART1_SR_RESET 0x00C00000U"));
+        assert!(c.contains("static inline uint32_t usart1_read_sr(void)"));
+    }
+
+    #[test]
+    fn test_rust_and_micropython_drivers() {
+        let rust = generate_rust(&bme280("I2C"), "BME280", "ESP32", "I2C", false);
+        assert!(rust.contains("use embedded_hal::i2c::I2c;"));
+        assert!(rust.contains("pub mod ctrl_meas {\n        pub const ADDRESS: u8 = 0xF4;\n        pub const RESET: u8 = 0x00;"));
+        assert!(rust.contains("pub mod osrs_t {\n            pub const SHIFT: u32 = 5;\n            pub const MASK: u8 = 0xE0;"));
+        assert!(rust.contains("pub const NORMAL: u8 = 3;"));
+        assert!(rust.contains("pub const DEVICE_ID: u8 = 0x60;"));
+        assert!(rust.contains("impl<I2C: I2c> Bme280<I2C> {"));
+        assert!(rust.contains("pub fn read_press(&mut self) -> Result<u32, Error<I2C::Error>>"));
+        assert!(rust.contains("buf.iter().fold(0, |value, &b| (value << 8) | b as u32)"));
+        assert!(rust.contains("return Err(Error::WrongDeviceId(id as u64));"));
+        // Recorded transactions: the ID read, then its complement
+        assert!(rust.contains("Transaction::write_read(ADDRESS, vec![0xD0], vec![0x60]),"));
+        assert!(rust.contains("Transaction::write_read(ADDRESS, vec![0xD0], vec![0x9F]),"));
+
+        let spi = generate_rust(&bme280("SPI"), "BME280", "ESP32", "SPI", true);
+        assert!(spi.contains("use embedded_hal_async::spi::{Operation, SpiDevice};"));
+        assert!(spi.contains("impl<SPI: SpiDevice> Bme280<SPI> {"));
+        assert!(spi.contains("pub async fn init(&mut self) -> Result<(), Error<SPI::Error>> {\n        self.verify_device_id().await\n"));
+        assert!(spi.contains("header[0] |= 0x80;"));
+        assert!(spi.contains("Transaction::transaction_start(),\n            Transaction::write_vec(vec![0xD0]),\n            Transaction::read_vec(vec![0x60]),"));
+        assert!(spi.contains("block_on(device.init()).unwrap();"));
+
+        let mmio = DriverModel::new(
+            "x.svd",
+            &Device::parse(TEST_SVD).unwrap(),
+            "USART1",
+            None,
+            "MMIO",
+            None,
+        )
+        .unwrap();
+        let rust = generate_rust(&mmio, "USART1", "STM32", "MMIO", false);
+        assert!(rust.contains("fn registers_in_ram()"));
+        assert!(!rust.contains("embedded_hal"));
+        assert_eq!(cargo_dependencies(false, true), "[dependencies]\n");
+        assert!(
+            cargo_dependencies(true, true).contains("features = [\"eh1\", \"embedded-hal-async\"]")
+        );
+
+        let py = generate_micropython(&bme280("I2C"), "BME280", "ESP32", "I2C");
+        assert!(py.contains("    REG_CTRL_MEAS = 0xF4\n    CTRL_MEAS_RESET = 0x00\n"));
+        assert!(py.contains("    CTRL_MEAS_OSRS_T = (5, 0xE0)\n"));
+        assert!(py.contains("device_id = self.read_register(self.REG_CHIP_ID, 1)"));
+        assert!(py.contains("self.i2c.readfrom_mem(self.address, reg, size)"));
+    }
+}
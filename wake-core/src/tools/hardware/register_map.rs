@@ -0,0 +1,420 @@
+//! Project register maps.
+//!
+//! Loads the register model used by `register_decode` from a CMSIS-SVD file or
+//! a hand-written YAML/JSON map. YAML/JSON maps describe either a list of
+//! `peripherals` or, for off-chip devices such as I2C sensors, a flat list of
+//! `registers` addressed from zero:
+//!
+//! ```yaml
+//! name: BME280
+//! size: 8
+//! registers:
+//!   - name: CTRL_MEAS
+//!     address: 0xF4
+//!     reset: 0x00
+//!     fields:
+//!       - { name: OSRS_T, bits: "7:5", values: { SKIP: 0, X1: 1, X2: 2 } }
+//!       - { name: MODE, bits: "1:0", values: { SLEEP: 0, FORCED: 1, NORMAL: 3 } }
+//! ```
+
+use super::datasheet_pdf;
+use super::svd::{parse_int, Access, Device, EnumeratedValue, Field, Peripheral, Register};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File extensions recognised as register models, in lookup order
+pub const EXTENSIONS: [&str; 4] = ["svd", "yaml", "yml", "json"];
+
+/// Number written as an integer or as text (`0x40`, `#0101`, `12`)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum Int {
+    Number(u64),
+    Text(String),
+}
+
+impl Int {
+    fn value(&self) -> Result<u64, String> {
+        match self {
+            Int::Number(n) => Ok(*n),
+            Int::Text(t) => parse_int(t),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ValueSpec {
+    name: String,
+    value: Option<Int>,
+    description: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum Values {
+    Map(BTreeMap<String, Int>),
+    List(Vec<ValueSpec>),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct FieldSpec {
+    name: String,
+    description: Option<String>,
+    /// `"7:5"`, `"[7:5]"` or a single bit number
+    bits: Option<Int>,
+    bit_offset: Option<u32>,
+    bit_width: Option<u32>,
+    access: Option<String>,
+    values: Option<Values>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct RegisterSpec {
+    name: String,
+    description: Option<String>,
+    #[serde(alias = "offset", alias = "address_offset")]
+    address: Int,
+    size: Option<u32>,
+    access: Option<String>,
+    #[serde(alias = "reset_value")]
+    reset: Option<Int>,
+    #[serde(default)]
+    fields: Vec<FieldSpec>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct PeripheralSpec {
+    name: String,
+    description: Option<String>,
+    #[serde(alias = "group_name")]
+    group: Option<String>,
+    #[serde(alias = "base", alias = "address")]
+    base_address: Option<Int>,
+    size: Option<u32>,
+    access: Option<String>,
+    #[serde(default)]
+    registers: Vec<RegisterSpec>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct MapSpec {
+    #[serde(alias = "device")]
+    name: String,
+    description: Option<String>,
+    /// Default register size in bits
+    size: Option<u32>,
+    access: Option<String>,
+    #[serde(default)]
+    peripherals: Vec<PeripheralSpec>,
+    #[serde(default)]
+    registers: Vec<RegisterSpec>,
+}
+
+fn parse_access(text: Option<&String>) -> Result<Option<Access>, String> {
+    text.map(|a| Access::parse(a).ok_or_else(|| format!("Unknown access '{}'", a)))
+        .transpose()
+}
+
+/// `(offset, width)` of `"msb:lsb"`, `"[msb:lsb]"` or a single bit
+fn parse_bits(bits: &Int) -> Result<(u32, u32), String> {
+    let text = match bits {
+        Int::Number(bit) => return Ok((*bit as u32, 1)),
+        Int::Text(t) => t.trim().trim_start_matches('[').trim_end_matches(']'),
+    };
+    let bit = |s: &str| {
+        s.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid bit range '{}'", text))
+    };
+    match text.split_once(':') {
+        Some((a, b)) => {
+            let (a, b) = (bit(a)?, bit(b)?);
+            Ok((a.min(b), a.max(b) - a.min(b) + 1))
+        }
+        None => Ok((bit(text)?, 1)),
+    }
+}
+
+impl FieldSpec {
+    fn build(&self, register: &str, size: u32, access: Option<Access>) -> Result<Field, String> {
+        let context = |e: String| format!("{}.{}: {}", register, self.name, e);
+        let (bit_offset, bit_width) = match (&self.bits, self.bit_offset) {
+            (Some(bits), _) => parse_bits(bits).map_err(context)?,
+            (None, Some(offset)) => (offset, self.bit_width.unwrap_or(1)),
+            (None, None) => return Err(context("needs `bits` or `bit_offset`".to_string())),
+        };
+        if bit_width == 0 || bit_offset + bit_width > size {
+            return Err(context(format!(
+                "bits {}..{} do not fit a {}-bit register",
+                bit_offset,
+                bit_offset + bit_width,
+                size
+            )));
+        }
+
+        let mut enumerated_values = match &self.values {
+            None => Vec::new(),
+            Some(Values::Map(map)) => map
+                .iter()
+                .map(|(name, value)| {
+                    Ok(EnumeratedValue {
+                        name: name.clone(),
+                        description: None,
+                        value: Some(value.value()?),
+                    })
+                })
+                .collect::<Result<_, String>>()
+                .map_err(context)?,
+            Some(Values::List(list)) => list
+                .iter()
+                .map(|v| {
+                    Ok(EnumeratedValue {
+                        name: v.name.clone(),
+                        description: v.description.clone(),
+                        value: v.value.as_ref().map(Int::value).transpose()?,
+                    })
+                })
+                .collect::<Result<_, String>>()
+                .map_err(context)?,
+        };
+        enumerated_values.sort_by_key(|e| e.value.unwrap_or(u64::MAX));
+
+        Ok(Field {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            bit_offset,
+            bit_width,
+            access: parse_access(self.access.as_ref())
+                .map_err(context)?
+                .or(access),
+            enumerated_values,
+        })
+    }
+}
+
+impl RegisterSpec {
+    fn build(&self, size: u32, access: Option<Access>) -> Result<Register, String> {
+        let context = |e: String| format!("{}: {}", self.name, e);
+        let size = self.size.unwrap_or(size);
+        let access = parse_access(self.access.as_ref())
+            .map_err(context)?
+            .or(access);
+        let mut fields = self
+            .fields
+            .iter()
+            .map(|f| f.build(&self.name, size, access))
+            .collect::<Result<Vec<_>, _>>()?;
+        fields.sort_by_key(|f| f.bit_offset);
+
+        Ok(Register {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            address_offset: self.address.value().map_err(context)?,
+            size,
+            access,
+            reset_value: self
+                .reset
+                .as_ref()
+                .map(Int::value)
+                .transpose()
+                .map_err(context)?
+                .unwrap_or(0),
+            reset_mask: if size >= 64 {
+                u64::MAX
+            } else {
+                (1u64 << size) - 1
+            },
+            fields,
+        })
+    }
+}
+
+impl MapSpec {
+    fn build(self) -> Result<Device, String> {
+        let size = self.size.unwrap_or(32);
+        let access = parse_access(self.access.as_ref())?;
+        let mut peripherals = Vec::new();
+
+        if !self.registers.is_empty() {
+            peripherals.push(Peripheral {
+                name: self.name.clone(),
+                description: self.description.clone(),
+                group_name: None,
+                base_address: 0,
+                derived_from: None,
+                registers: self
+                    .registers
+                    .iter()
+                    .map(|r| r.build(size, access))
+                    .collect::<Result<_, _>>()?,
+            });
+        }
+        for p in &self.peripherals {
+            let size = p.size.unwrap_or(size);
+            let access = parse_access(p.access.as_ref())?.or(access);
+            peripherals.push(Peripheral {
+                name: p.name.clone(),
+                description: p.description.clone(),
+                group_name: p.group.clone(),
+                base_address: p
+                    .base_address
+                    .as_ref()
+                    .map(Int::value)
+                    .transpose()?
+                    .unwrap_or(0),
+                derived_from: None,
+                registers: p
+                    .registers
+                    .iter()
+                    .map(|r| r.build(size, access))
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("{}.{}", p.name, e))?,
+            });
+        }
+        if peripherals.is_empty() {
+            return Err("Register map has no `registers` or `peripherals`".to_string());
+        }
+
+        Ok(Device {
+            name: self.name,
+            description: self.description,
+            peripherals,
+        })
+    }
+}
+
+pub fn parse_yaml(text: &str) -> Result<Device, String> {
+    serde_yaml::from_str::<MapSpec>(text)
+        .map_err(|e| format!("Invalid register map: {}", e))?
+        .build()
+}
+
+pub fn parse_json(text: &str) -> Result<Device, String> {
+    serde_json::from_str::<MapSpec>(text)
+        .map_err(|e| format!("Invalid register map: {}", e))?
+        .build()
+}
+
+/// Load an SVD, YAML or JSON register model, chosen by file extension
+pub fn load(path: &Path) -> Result<Device, String> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if extension == "svd" {
+        return Device::load(path);
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    match extension.as_str() {
+        "yaml" | "yml" => parse_yaml(&text),
+        "json" => parse_json(&text),
+        _ => Err(format!(
+            "Unknown register model format '{}'; expected .svd, .yaml or .json",
+            path.display()
+        )),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Register model file for `device` from a file path or a project directory
+pub fn resolve(path: &Path, device: &str) -> Result<PathBuf, String> {
+    datasheet_pdf::resolve(path, device, &EXTENSIONS)
+}
+
+#[cfg(test)]
+pub(crate) const TEST_YAML: &str = r#"
+name: BME280
+description: Humidity sensor
+size: 8
+registers:
+  - name: CTRL_MEAS
+    address: 0xF4
+    reset: 0x00
+    fields:
+      - name: OSRS_T
+        bits: "7:5"
+        values: { SKIP: 0, X1: 1, X2: 2, X4: 3, X8: 4, X16: 5 }
+      - name: OSRS_P
+        bits: "[4:2]"
+        values: { SKIP: 0, X1: 1, X2: 2, X4: 3, X8: 4, X16: 5 }
+      - name: MODE
+        bits: "1:0"
+        values:
+          - { name: SLEEP, value: 0 }
+          - { name: FORCED, value: 1, description: One measurement then sleep }
+          - { name: NORMAL, value: 3 }
+  - name: STATUS
+    address: 0xF3
+    access: read-only
+    fields:
+      - { name: MEASURING, bits: 3 }
+      - { name: IM_UPDATE, bit_offset: 0 }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_yaml_register_map() {
+        let device = parse_yaml(TEST_YAML).unwrap();
+        assert_eq!(device.name, "BME280");
+        let p = device.peripheral("bme280").unwrap();
+        assert_eq!(p.base_address, 0);
+
+        let ctrl = p.register("ctrl_meas").unwrap();
+        assert_eq!((ctrl.address_offset, ctrl.size), (0xF4, 8));
+        let names: Vec<_> = ctrl.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["MODE", "OSRS_P", "OSRS_T"]);
+        let osrs_t = ctrl.field("OSRS_T").unwrap();
+        assert_eq!((osrs_t.bit_offset, osrs_t.bit_width), (5, 3));
+        assert_eq!(osrs_t.meaning(2).unwrap().name, "X2");
+        assert_eq!(
+            ctrl.field("MODE")
+                .unwrap()
+                .meaning(1)
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("One measurement then sleep")
+        );
+
+        let status = p.register("STATUS").unwrap();
+        assert_eq!(status.access, Some(Access::ReadOnly));
+        assert_eq!(status.field("MEASURING").unwrap().mask(), 0x08);
+        assert_eq!(status.field("IM_UPDATE").unwrap().mask(), 0x01);
+    }
+
+    #[test]
+    fn test_json_peripherals_and_errors() {
+        let device = parse_json(
+            r#"{"name": "MCU", "peripherals": [{"name": "TIM2", "base_address": "0x40000000",
+                "registers": [{"name": "CR1", "offset": 0, "fields": [{"name": "CEN", "bits": 0}]}]}]}"#,
+        )
+        .unwrap();
+        let p = device.peripheral("TIM2").unwrap();
+        assert_eq!(p.base_address, 0x4000_0000);
+        assert_eq!(p.register("CR1").unwrap().size, 32);
+
+        let err = parse_yaml("name: X\nsize: 8\nregisters:\n  - {name: R, address: 1, fields: [{name: F, bits: \"9:8\"}]}\n")
+            .unwrap_err();
+        assert!(err.contains("R.F"), "{}", err);
+        assert!(parse_yaml("name: X\n").is_err());
+    }
+
+    #[test]
+    fn test_load_and_resolve() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("bme280.yaml"), TEST_YAML).unwrap();
+        std::fs::write(dir.path().join("STM32F4x.svd"), super::super::svd::TEST_SVD).unwrap();
+
+        let svd = resolve(dir.path(), "STM32F4").unwrap();
+        assert_eq!(load(&svd).unwrap().name, "STM32F4X");
+        let yaml = resolve(dir.path(), "BME280").unwrap();
+        assert_eq!(load(&yaml).unwrap().name, "BME280");
+        assert!(load(&dir.path().join("missing.yaml")).is_err());
+    }
+}
@@ -0,0 +1,757 @@
+//! Build-system scaffolding for generated C/C++ drivers.
+//!
+//! Zephyr, ESP-IDF and PlatformIO expect a driver to arrive as a module,
+//! component or library of their build system rather than as a loose header.
+//! This module lays the generated header and source out the way each one
+//! expects, adds the build files and writes the glue that connects the
+//! driver's bus callbacks to the framework's I2C/SPI API. Glue and examples
+//! are C in both languages; only the driver source follows the language.
+
+use super::package::PackageFile;
+use super::register_driver::Interface;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Framework {
+    Zephyr,
+    EspIdf,
+    PlatformIo,
+}
+
+impl Framework {
+    /// Framework named by a `platform` argument; plain boards (STM32, ESP32, ...) are not frameworks
+    pub fn parse(platform: &str) -> Option<Self> {
+        match platform
+            .to_lowercase()
+            .replace(['-', '_', ' '], "")
+            .as_str()
+        {
+            "zephyr" => Some(Framework::Zephyr),
+            "espidf" | "idf" => Some(Framework::EspIdf),
+            "platformio" | "pio" => Some(Framework::PlatformIo),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Framework::Zephyr => "Zephyr",
+            Framework::EspIdf => "ESP-IDF",
+            Framework::PlatformIo => "PlatformIO",
+        }
+    }
+}
+
+/// How the driver talks to the part, which decides the framework glue
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// Register-map bus driver: `{stem}_t` with read/write callbacks, `{stem}_init(dev)`
+    Callbacks {
+        interface: Interface,
+        wide_address: bool,
+    },
+    /// Register-map MMIO driver: `{stem}_init(void)` on a fixed register block
+    Mmio { base: u64, size: u64 },
+    /// Template driver: `{component}_t` with an opaque interface, `{lower}_init(dev, config)`
+    Template { protocol: String },
+}
+
+impl Binding {
+    fn bus(&self) -> Option<Interface> {
+        match self {
+            Binding::Callbacks { interface, .. } => Some(*interface),
+            Binding::Mmio { .. } => Some(Interface::Mmio),
+            Binding::Template { protocol } => match protocol.to_uppercase().as_str() {
+                "I2C" => Some(Interface::I2c),
+                "SPI" => Some(Interface::Spi),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// A generated C/C++ driver ready to be placed into a framework layout
+pub struct CDriver {
+    pub component: String,
+    pub stem: String,
+    /// `h`/`c` or `hpp`/`cpp`
+    pub header_ext: &'static str,
+    pub source_ext: &'static str,
+    pub header: String,
+    pub source: String,
+    /// Framework-neutral example including the header by its bare name
+    pub example: Option<String>,
+    pub binding: Binding,
+}
+
+impl CDriver {
+    fn header_name(&self) -> String {
+        format!("{}.{}", self.stem, self.header_ext)
+    }
+
+    fn source_name(&self) -> String {
+        format!("{}.{}", self.stem, self.source_ext)
+    }
+
+    /// Devicetree compatible, e.g. `wake,bme280`
+    fn compatible(&self) -> String {
+        format!("wake,{}", self.stem.replace('_', "-"))
+    }
+
+    /// Kconfig symbol, matching the compatible
+    fn symbol(&self) -> String {
+        format!("WAKE_{}", self.stem.to_uppercase())
+    }
+
+    /// C type of the driver handle
+    fn handle(&self) -> String {
+        match &self.binding {
+            Binding::Template { .. } => format!("{}_t", self.component),
+            _ => format!("{}_t", self.stem),
+        }
+    }
+
+    /// Init call on the handle pointer `var`; template drivers also take a `config`
+    fn init_call(&self, var: &str) -> String {
+        match &self.binding {
+            Binding::Template { .. } => {
+                format!("{}_init({}, &config)", self.component.to_lowercase(), var)
+            }
+            Binding::Mmio { .. } => format!("{}_init()", self.stem),
+            Binding::Callbacks { .. } => format!("{}_init({})", self.stem, var),
+        }
+    }
+}
+
+/// Files of `driver` laid out for `framework`
+pub fn scaffold(framework: Framework, driver: &CDriver) -> Result<Vec<PackageFile>, String> {
+    match framework {
+        Framework::Zephyr => zephyr(driver),
+        Framework::EspIdf => Ok(esp_idf(driver)),
+        Framework::PlatformIo => Ok(platformio(driver)),
+    }
+}
+
+const GENERATED: &str = "Generated by Wake - Hardware-First Coding Agent";
+
+/// Address bytes of `reg` as a C initializer, MSB first
+fn address_initializer(wide_address: bool) -> &'static str {
+    if wide_address {
+        "{ (uint8_t)(reg >> 8), (uint8_t)reg }"
+    } else {
+        "{ reg }"
+    }
+}
+
+fn address_type(wide_address: bool) -> &'static str {
+    if wide_address {
+        "uint16_t"
+    } else {
+        "uint8_t"
+    }
+}
+
+fn zephyr(driver: &CDriver) -> Result<Vec<PackageFile>, String> {
+    let bus = driver.binding.bus().ok_or_else(|| {
+        format!(
+            "Zephyr drivers sit on I2C or SPI buses or memory-mapped registers; {} has no devicetree bus binding here",
+            match &driver.binding {
+                Binding::Template { protocol } => protocol.as_str(),
+                _ => "this protocol",
+            }
+        )
+    })?;
+    let stem = &driver.stem;
+    let symbol = driver.symbol();
+    let compatible = driver.compatible();
+    let drv_compat = compatible.replace([',', '-'], "_");
+    let mut files = Vec::new();
+
+    files.push(PackageFile::new(
+        "zephyr/module.yml",
+        format!(
+            "# {}\nname: wake-{}\nbuild:\n  cmake: .\n  kconfig: Kconfig\n  settings:\n    dts_root: .\n",
+            GENERATED,
+            stem.replace('_', "-")
+        ),
+    ));
+    files.push(PackageFile::new(
+        "CMakeLists.txt",
+        format!(
+            "# {c} driver\n# {g}\n\nif(CONFIG_{s})\n  zephyr_library()\n  zephyr_library_sources({src} {stem}_zephyr.c)\n  zephyr_include_directories(include)\nendif()\n",
+            c = driver.component,
+            g = GENERATED,
+            s = symbol,
+            src = driver.source_name(),
+            stem = stem
+        ),
+    ));
+    let select = match bus {
+        Interface::I2c => "\tselect I2C\n",
+        Interface::Spi => "\tselect SPI\n",
+        Interface::Mmio => "",
+    };
+    files.push(PackageFile::new(
+        "Kconfig",
+        format!(
+            "# {c} driver\n# {g}\n\nconfig {s}\n\tbool \"{c} driver\"\n\tdefault y\n\tdepends on DT_HAS_{dt}_ENABLED\n{select}\thelp\n\t  Driver for {c} devices in the devicetree ({compat}).\n\nif {s}\n\nconfig {s}_INIT_PRIORITY\n\tint \"{c} init priority\"\n\tdefault 90\n\thelp\n\t  Device initialization priority{order}.\n\nmodule = {s}\nmodule-str = {lower}\nsource \"subsys/logging/Kconfig.template.log_config\"\n\nendif # {s}\n",
+            c = driver.component,
+            g = GENERATED,
+            s = symbol,
+            dt = drv_compat.to_uppercase(),
+            select = select,
+            compat = compatible,
+            lower = symbol.to_lowercase(),
+            order = if bus == Interface::Mmio {
+                ""
+            } else {
+                "; must come after the bus driver"
+            }
+        ),
+    ));
+    let include = match bus {
+        Interface::I2c => "include: i2c-device.yaml\n".to_string(),
+        Interface::Spi => "include: spi-device.yaml\n".to_string(),
+        Interface::Mmio => {
+            "include: base.yaml\n\nproperties:\n  reg:\n    required: true\n".to_string()
+        }
+    };
+    files.push(PackageFile::new(
+        format!("dts/bindings/{}.yaml", compatible),
+        format!(
+            "# {}\n\ndescription: {} driver\n\ncompatible: \"{}\"\n\n{}",
+            GENERATED, driver.component, compatible, include
+        ),
+    ));
+
+    files.push(PackageFile::new(
+        format!("include/{}", driver.header_name()),
+        driver.header.clone(),
+    ));
+    files.push(PackageFile::new(
+        driver.source_name(),
+        driver.source.clone(),
+    ));
+    let handle = driver.handle();
+    if bus != Interface::Mmio {
+        files.push(PackageFile::new(
+            format!("include/{}_zephyr.h", stem),
+            format!(
+                "/*\n * {c} Zephyr device driver\n * {g}\n */\n\n#ifndef {u}_ZEPHYR_H\n#define {u}_ZEPHYR_H\n\n#include <zephyr/device.h>\n\n#include \"{h}\"\n\n/* Driver handle of a {c} devicetree instance */\n{handle} *{stem}_zephyr_handle(const struct device *dev);\n\n#endif /* {u}_ZEPHYR_H */\n",
+                c = driver.component,
+                g = GENERATED,
+                u = stem.to_uppercase(),
+                h = driver.header_name(),
+                handle = handle,
+                stem = stem
+            ),
+        ));
+    }
+    files.push(PackageFile::new(
+        format!("{}_zephyr.c", stem),
+        zephyr_glue(driver, bus, &drv_compat, &symbol),
+    ));
+
+    if driver.example.is_some() {
+        files.push(PackageFile::new(
+            "examples/app.overlay",
+            zephyr_overlay(driver, bus, &compatible),
+        ));
+        let mut main = format!(
+            "/* {c} example */\n\n#include <zephyr/device.h>\n#include <zephyr/kernel.h>\n\n",
+            c = driver.component
+        );
+        if bus != Interface::Mmio {
+            main.push_str(&format!("#include \"{}_zephyr.h\"\n\n", stem));
+        }
+        main.push_str(&format!(
+            "int main(void)\n{{\n\tconst struct device *dev = DEVICE_DT_GET_ONE({d});\n\n\tif (!device_is_ready(dev)) {{\n\t\tprintk(\"{c} not ready\\n\");\n\t\treturn 0;\n\t}}\n",
+            d = drv_compat,
+            c = driver.component
+        ));
+        if bus != Interface::Mmio {
+            main.push_str(&format!(
+                "\n\t{handle} *{stem} = {stem}_zephyr_handle(dev);\n\n\t/* Call the driver's register accessors on {stem} */\n\t(void){stem};\n",
+                handle = handle,
+                stem = stem
+            ));
+        }
+        main.push_str(&format!(
+            "\tprintk(\"{} ready\\n\");\n\treturn 0;\n}}\n",
+            driver.component
+        ));
+        files.push(PackageFile::new("examples/main.c", main));
+    }
+    Ok(files)
+}
+
+fn zephyr_glue(driver: &CDriver, bus: Interface, drv_compat: &str, symbol: &str) -> String {
+    let stem = &driver.stem;
+    let handle = driver.handle();
+    let mut out = format!(
+        "/*\n * {c} Zephyr device driver\n * {g}\n */\n\n#define DT_DRV_COMPAT {d}\n\n#include <errno.h>\n\n#include <zephyr/device.h>\n",
+        c = driver.component,
+        g = GENERATED,
+        d = drv_compat
+    );
+    match bus {
+        Interface::I2c => out.push_str("#include <zephyr/drivers/i2c.h>\n"),
+        Interface::Spi => out.push_str("#include <zephyr/drivers/spi.h>\n"),
+        Interface::Mmio => {}
+    }
+    out.push_str(&format!(
+        "#include <zephyr/logging/log.h>\n\n#include \"{}\"\n",
+        driver.header_name()
+    ));
+    if bus != Interface::Mmio {
+        out.push_str(&format!("#include \"{}_zephyr.h\"\n", stem));
+    }
+    out.push_str(&format!(
+        "\nLOG_MODULE_REGISTER({}, CONFIG_{}_LOG_LEVEL);\n",
+        symbol.to_lowercase(),
+        symbol
+    ));
+
+    if bus == Interface::Mmio {
+        out.push_str(&format!(
+            "\nstatic int {stem}_zephyr_init(const struct device *dev)\n{{\n\tARG_UNUSED(dev);\n\n\t/* The register map fixes the register block address */\n\tif ({stem}_init() != 0) {{\n\t\tLOG_ERR(\"Device check failed\");\n\t\treturn -EIO;\n\t}}\n\treturn 0;\n}}\n\n#define {u}_DEFINE(inst) \\\n\tDEVICE_DT_INST_DEFINE(inst, {stem}_zephyr_init, NULL, NULL, NULL, POST_KERNEL, \\\n\t\t\t      CONFIG_{s}_INIT_PRIORITY, NULL);\n\nDT_INST_FOREACH_STATUS_OKAY({u}_DEFINE)\n",
+            stem = stem,
+            u = stem.to_uppercase(),
+            s = symbol
+        ));
+        return out;
+    }
+
+    let (spec, ready, spec_get) = match bus {
+        Interface::Spi => (
+            "struct spi_dt_spec",
+            "spi_is_ready_dt",
+            "SPI_DT_SPEC_INST_GET(inst, SPI_OP_MODE_MASTER | SPI_WORD_SET(8) | SPI_TRANSFER_MSB, 0)",
+        ),
+        _ => ("struct i2c_dt_spec", "i2c_is_ready_dt", "I2C_DT_SPEC_INST_GET(inst)"),
+    };
+    out.push_str(&format!(
+        "\nstruct {stem}_zephyr_config {{\n\t{spec} bus;\n}};\n\nstruct {stem}_zephyr_data {{\n\t{handle} dev;\n}};\n",
+        stem = stem,
+        spec = spec,
+        handle = handle
+    ));
+
+    let setup = match &driver.binding {
+        Binding::Callbacks {
+            interface,
+            wide_address,
+        } => {
+            let addr = address_initializer(*wide_address);
+            let ty = address_type(*wide_address);
+            let transfers = if *interface == Interface::Spi {
+                format!(
+                    "\nstatic int {stem}_bus_read(void *ctx, {ty} reg, uint8_t *data, size_t len)\n{{\n\tconst struct {stem}_zephyr_config *cfg = ctx;\n\tuint8_t addr[] = {addr};\n\tconst struct spi_buf tx_buf = {{ .buf = addr, .len = sizeof(addr) }};\n\tconst struct spi_buf_set tx = {{ .buffers = &tx_buf, .count = 1 }};\n\tstruct spi_buf rx_bufs[] = {{\n\t\t{{ .buf = NULL, .len = sizeof(addr) }},\n\t\t{{ .buf = data, .len = len }},\n\t}};\n\tconst struct spi_buf_set rx = {{ .buffers = rx_bufs, .count = ARRAY_SIZE(rx_bufs) }};\n\n\treturn spi_transceive_dt(&cfg->bus, &tx, &rx);\n}}\n\nstatic int {stem}_bus_write(void *ctx, {ty} reg, const uint8_t *data, size_t len)\n{{\n\tconst struct {stem}_zephyr_config *cfg = ctx;\n\tuint8_t addr[] = {addr};\n\tconst struct spi_buf tx_bufs[] = {{\n\t\t{{ .buf = addr, .len = sizeof(addr) }},\n\t\t{{ .buf = (uint8_t *)data, .len = len }},\n\t}};\n\tconst struct spi_buf_set tx = {{ .buffers = tx_bufs, .count = ARRAY_SIZE(tx_bufs) }};\n\n\treturn spi_write_dt(&cfg->bus, &tx);\n}}\n",
+                    stem = stem,
+                    ty = ty,
+                    addr = addr
+                )
+            } else {
+                format!(
+                    "\nstatic int {stem}_bus_read(void *ctx, {ty} reg, uint8_t *data, size_t len)\n{{\n\tconst struct {stem}_zephyr_config *cfg = ctx;\n\tuint8_t addr[] = {addr};\n\n\treturn i2c_write_read_dt(&cfg->bus, addr, sizeof(addr), data, len);\n}}\n\nstatic int {stem}_bus_write(void *ctx, {ty} reg, const uint8_t *data, size_t len)\n{{\n\tconst struct {stem}_zephyr_config *cfg = ctx;\n\tuint8_t addr[] = {addr};\n\tstruct i2c_msg msgs[] = {{\n\t\t{{ .buf = addr, .len = sizeof(addr), .flags = I2C_MSG_WRITE }},\n\t\t{{ .buf = (uint8_t *)data, .len = len, .flags = I2C_MSG_WRITE | I2C_MSG_STOP }},\n\t}};\n\n\treturn i2c_transfer_dt(&cfg->bus, msgs, ARRAY_SIZE(msgs));\n}}\n",
+                    stem = stem,
+                    ty = ty,
+                    addr = addr
+                )
+            };
+            out.push_str(&transfers);
+            format!(
+                "\tdrv->read = {stem}_bus_read;\n\tdrv->write = {stem}_bus_write;\n\tdrv->ctx = (void *)cfg;\n",
+                stem = stem
+            )
+        }
+        _ => format!(
+            "\tconst {c}_config_t config = {{\n\t\t.address = {address},\n\t\t.mode = 0x01,\n\t\t.sample_rate = 0,\n\t}};\n\n\tdrv->interface = (void *)&cfg->bus;\n",
+            c = driver.component,
+            address = if bus == Interface::I2c {
+                "cfg->bus.addr"
+            } else {
+                "0"
+            }
+        ),
+    };
+
+    out.push_str(&format!(
+        "\n{handle} *{stem}_zephyr_handle(const struct device *dev)\n{{\n\tstruct {stem}_zephyr_data *data = dev->data;\n\n\treturn &data->dev;\n}}\n\nstatic int {stem}_zephyr_init(const struct device *dev)\n{{\n\tconst struct {stem}_zephyr_config *cfg = dev->config;\n\t{handle} *drv = {stem}_zephyr_handle(dev);\n\n\tif (!{ready}(&cfg->bus)) {{\n\t\tLOG_ERR(\"Bus %s not ready\", cfg->bus.bus->name);\n\t\treturn -ENODEV;\n\t}}\n\n",
+        handle = handle,
+        stem = stem,
+        ready = ready
+    ));
+    out.push_str(&setup);
+    out.push_str(&format!(
+        "\tif ({} != 0) {{\n\t\tLOG_ERR(\"Device check failed\");\n\t\treturn -EIO;\n\t}}\n\treturn 0;\n}}\n",
+        driver.init_call("drv")
+    ));
+    out.push_str(&format!(
+        "\n#define {u}_DEFINE(inst) \\\n\tstatic struct {stem}_zephyr_data {stem}_data_##inst; \\\n\tstatic const struct {stem}_zephyr_config {stem}_config_##inst = {{ \\\n\t\t.bus = {spec_get}, \\\n\t}}; \\\n\tDEVICE_DT_INST_DEFINE(inst, {stem}_zephyr_init, NULL, &{stem}_data_##inst, \\\n\t\t\t      &{stem}_config_##inst, POST_KERNEL, \\\n\t\t\t      CONFIG_{s}_INIT_PRIORITY, NULL);\n\nDT_INST_FOREACH_STATUS_OKAY({u}_DEFINE)\n",
+        u = stem.to_uppercase(),
+        stem = stem,
+        spec_get = spec_get,
+        s = symbol
+    ));
+    out
+}
+
+fn zephyr_overlay(driver: &CDriver, bus: Interface, compatible: &str) -> String {
+    let node = driver.stem.replace('_', "-");
+    match (&driver.binding, bus) {
+        (Binding::Mmio { base, size }, _) => format!(
+            "/* {c} register block; the driver uses the address from its register map */\n/ {{\n\t{n}@{b:x} {{\n\t\tcompatible = \"{compat}\";\n\t\treg = <0x{b:08x} 0x{s:x}>;\n\t}};\n}};\n",
+            c = driver.component,
+            n = node,
+            b = base,
+            s = size,
+            compat = compatible
+        ),
+        (_, Interface::Spi) => format!(
+            "/* Add the {c} to the board's SPI bus; reg is its chip-select index in cs-gpios */\n&spi0 {{\n\t{n}@0 {{\n\t\tcompatible = \"{compat}\";\n\t\treg = <0>;\n\t\tspi-max-frequency = <1000000>;\n\t}};\n}};\n",
+            c = driver.component,
+            n = node,
+            compat = compatible
+        ),
+        _ => format!(
+            "/* Add the {c} to the board's I2C bus; set reg to its 7-bit address */\n&i2c0 {{\n\t{n}@0 {{\n\t\tcompatible = \"{compat}\";\n\t\treg = <0x0>;\n\t}};\n}};\n",
+            c = driver.component,
+            n = node,
+            compat = compatible
+        ),
+    }
+}
+
+fn esp_idf(driver: &CDriver) -> Vec<PackageFile> {
+    let stem = &driver.stem;
+    let glue = match &driver.binding {
+        Binding::Callbacks {
+            interface,
+            wide_address,
+        } if *interface != Interface::Mmio => Some((*interface, *wide_address)),
+        _ => None,
+    };
+    let mut sources = format!("\"{}\"", driver.source_name());
+    if glue.is_some() {
+        sources.push_str(&format!(" \"{}_idf.c\"", stem));
+    }
+
+    let mut files = vec![
+        PackageFile::new(
+            "CMakeLists.txt",
+            format!(
+                "# {c} driver\n# {g}\n\nidf_component_register(SRCS {src}\n                       INCLUDE_DIRS \"include\"\n                       REQUIRES driver)\n",
+                c = driver.component,
+                g = GENERATED,
+                src = sources
+            ),
+        ),
+        PackageFile::new(
+            "idf_component.yml",
+            format!(
+                "# {}\nversion: \"0.1.0\"\ndescription: \"{} driver\"\ndependencies:\n  idf: \">=5.2\"\n",
+                GENERATED, driver.component
+            ),
+        ),
+        PackageFile::new(
+            format!("include/{}", driver.header_name()),
+            driver.header.clone(),
+        ),
+        PackageFile::new(driver.source_name(), driver.source.clone()),
+    ];
+
+    let handle = driver.handle();
+    let bind = match glue {
+        Some((interface, wide_address)) => {
+            let spi = interface == Interface::Spi;
+            let (bus_header, bus_handle) = if spi {
+                ("driver/spi_master.h", "spi_device_handle_t")
+            } else {
+                ("driver/i2c_master.h", "i2c_master_dev_handle_t")
+            };
+            files.push(PackageFile::new(
+                format!("include/{}_idf.h", stem),
+                format!(
+                    "/*\n * {c} ESP-IDF bus glue\n * {g}\n */\n\n#pragma once\n\n#include \"{bh}\"\n\n#include \"{h}\"\n\n/* Route the driver's register transfers through an ESP-IDF {bus} device */\nvoid {stem}_idf_bind({handle} *dev, {bus_handle} handle);\n",
+                    c = driver.component,
+                    g = GENERATED,
+                    bh = bus_header,
+                    h = driver.header_name(),
+                    bus = if spi { "SPI" } else { "I2C" },
+                    stem = stem,
+                    handle = handle,
+                    bus_handle = bus_handle
+                ),
+            ));
+            files.push(PackageFile::new(
+                format!("{}_idf.c", stem),
+                idf_glue(driver, spi, wide_address, bus_handle),
+            ));
+            true
+        }
+        None => false,
+    };
+
+    if driver.example.is_some() {
+        let mut main = format!(
+            "/* {c} example */\n\n#include <stddef.h>\n\n#include \"esp_log.h\"\n\n#include \"{h}\"\n",
+            c = driver.component,
+            h = driver.header_name()
+        );
+        if bind {
+            main.push_str(&format!("#include \"{}_idf.h\"\n", stem));
+        }
+        main.push_str(&format!(
+            "\nstatic const char *TAG = \"{}\";\n\nvoid app_main(void)\n{{\n",
+            stem
+        ));
+        match (&driver.binding, glue) {
+            (_, Some((Interface::Spi, _))) => main.push_str(&format!(
+                "    spi_bus_config_t bus_config = {{\n        .mosi_io_num = GPIO_NUM_23,\n        .miso_io_num = GPIO_NUM_19,\n        .sclk_io_num = GPIO_NUM_18,\n        .quadwp_io_num = -1,\n        .quadhd_io_num = -1,\n    }};\n    ESP_ERROR_CHECK(spi_bus_initialize(SPI2_HOST, &bus_config, SPI_DMA_CH_AUTO));\n\n    spi_device_interface_config_t dev_config = {{\n        .mode = 0,\n        .clock_speed_hz = 1000000,\n        .spics_io_num = GPIO_NUM_5,\n        .queue_size = 1,\n    }};\n    spi_device_handle_t handle;\n    ESP_ERROR_CHECK(spi_bus_add_device(SPI2_HOST, &dev_config, &handle));\n\n    {handle} device;\n    {handle} *dev = &device;\n    {stem}_idf_bind(dev, handle);\n",
+                handle = handle,
+                stem = stem
+            )),
+            (_, Some(_)) => main.push_str(&format!(
+                "    i2c_master_bus_config_t bus_config = {{\n        .i2c_port = I2C_NUM_0,\n        .sda_io_num = GPIO_NUM_21,\n        .scl_io_num = GPIO_NUM_22,\n        .clk_source = I2C_CLK_SRC_DEFAULT,\n        .glitch_ignore_cnt = 7,\n        .flags.enable_internal_pullup = true,\n    }};\n    i2c_master_bus_handle_t bus;\n    ESP_ERROR_CHECK(i2c_new_master_bus(&bus_config, &bus));\n\n    i2c_device_config_t dev_config = {{\n        .dev_addr_length = I2C_ADDR_BIT_LEN_7,\n        .device_address = 0x00, /* The part's 7-bit I2C address */\n        .scl_speed_hz = 100000,\n    }};\n    i2c_master_dev_handle_t handle;\n    ESP_ERROR_CHECK(i2c_master_bus_add_device(bus, &dev_config, &handle));\n\n    {handle} device;\n    {handle} *dev = &device;\n    {stem}_idf_bind(dev, handle);\n",
+                handle = handle,
+                stem = stem
+            )),
+            (Binding::Template { .. }, None) => main.push_str(&format!(
+                "    {handle} device;\n    {handle} *dev = &device;\n    const {c}_config_t config = {{ .address = 0x00, .mode = 0x01, .sample_rate = 0 }};\n    dev->interface = NULL; /* The board's bus handle */\n",
+                handle = handle,
+                c = driver.component
+            )),
+            _ => {}
+        }
+        main.push_str(&format!(
+            "\n    if ({} != 0) {{\n        ESP_LOGE(TAG, \"Device check failed\");\n        return;\n    }}\n    ESP_LOGI(TAG, \"{} ready\");\n}}\n",
+            driver.init_call("dev"),
+            driver.component
+        ));
+        files.push(PackageFile::new("examples/main.c", main));
+    }
+    files
+}
+
+fn idf_glue(driver: &CDriver, spi: bool, wide_address: bool, bus_handle: &str) -> String {
+    let stem = &driver.stem;
+    let u = stem.to_uppercase();
+    let ty = address_type(wide_address);
+    let addr = address_initializer(wide_address);
+    let mut out = format!(
+        "/*\n * {c} ESP-IDF bus glue\n * {g}\n */\n\n#include <string.h>\n\n#include \"{stem}_idf.h\"\n\n#define {u}_IDF_TIMEOUT_MS 100\n/* Longest register the driver's accessors transfer */\n#define {u}_IDF_MAX_DATA 8\n",
+        c = driver.component,
+        g = GENERATED,
+        stem = stem,
+        u = u
+    );
+    if spi {
+        out.push_str(&format!(
+            "\nstatic int {stem}_idf_read(void *ctx, {ty} reg, uint8_t *data, size_t len)\n{{\n    uint8_t addr[] = {addr};\n    uint8_t tx[sizeof(addr) + {u}_IDF_MAX_DATA] = {{0}};\n    uint8_t rx[sizeof(addr) + {u}_IDF_MAX_DATA];\n    spi_transaction_t t;\n\n    if (len > {u}_IDF_MAX_DATA) {{\n        return -1;\n    }}\n    memcpy(tx, addr, sizeof(addr));\n    memset(&t, 0, sizeof(t));\n    t.length = 8 * (sizeof(addr) + len);\n    t.tx_buffer = tx;\n    t.rx_buffer = rx;\n    if (spi_device_polling_transmit(({bh})ctx, &t) != ESP_OK) {{\n        return -1;\n    }}\n    memcpy(data, rx + sizeof(addr), len);\n    return 0;\n}}\n\nstatic int {stem}_idf_write(void *ctx, {ty} reg, const uint8_t *data, size_t len)\n{{\n    uint8_t addr[] = {addr};\n    uint8_t frame[sizeof(addr) + {u}_IDF_MAX_DATA];\n    spi_transaction_t t;\n\n    if (len > {u}_IDF_MAX_DATA) {{\n        return -1;\n    }}\n    memcpy(frame, addr, sizeof(addr));\n    memcpy(frame + sizeof(addr), data, len);\n    memset(&t, 0, sizeof(t));\n    t.length = 8 * (sizeof(addr) + len);\n    t.tx_buffer = frame;\n    return spi_device_polling_transmit(({bh})ctx, &t) == ESP_OK ? 0 : -1;\n}}\n",
+            stem = stem,
+            ty = ty,
+            addr = addr,
+            u = u,
+            bh = bus_handle
+        ));
+    } else {
+        out.push_str(&format!(
+            "\nstatic int {stem}_idf_read(void *ctx, {ty} reg, uint8_t *data, size_t len)\n{{\n    uint8_t addr[] = {addr};\n\n    return i2c_master_transmit_receive(({bh})ctx, addr, sizeof(addr), data, len,\n                                       {u}_IDF_TIMEOUT_MS) == ESP_OK ? 0 : -1;\n}}\n\nstatic int {stem}_idf_write(void *ctx, {ty} reg, const uint8_t *data, size_t len)\n{{\n    uint8_t addr[] = {addr};\n    uint8_t frame[sizeof(addr) + {u}_IDF_MAX_DATA];\n\n    if (len > {u}_IDF_MAX_DATA) {{\n        return -1;\n    }}\n    memcpy(frame, addr, sizeof(addr));\n    memcpy(frame + sizeof(addr), data, len);\n    return i2c_master_transmit(({bh})ctx, frame, sizeof(addr) + len,\n                               {u}_IDF_TIMEOUT_MS) == ESP_OK ? 0 : -1;\n}}\n",
+            stem = stem,
+            ty = ty,
+            addr = addr,
+            u = u,
+            bh = bus_handle
+        ));
+    }
+    out.push_str(&format!(
+        "\nvoid {stem}_idf_bind({handle} *dev, {bh} handle)\n{{\n    dev->read = {stem}_idf_read;\n    dev->write = {stem}_idf_write;\n    dev->ctx = handle;\n}}\n",
+        stem = stem,
+        handle = driver.handle(),
+        bh = bus_handle
+    ));
+    out
+}
+
+fn platformio(driver: &CDriver) -> Vec<PackageFile> {
+    let bus = match driver.binding.bus() {
+        Some(Interface::I2c) => "i2c",
+        Some(Interface::Spi) => "spi",
+        Some(Interface::Mmio) => "mmio",
+        None => match &driver.binding {
+            Binding::Template { protocol } => protocol.as_str(),
+            _ => "",
+        },
+    };
+    let library = serde_json::json!({
+        "name": format!("{}-driver", driver.stem.replace('_', "-")),
+        "version": "0.1.0",
+        "description": format!("{} driver ({})", driver.component, GENERATED),
+        "keywords": [driver.stem, bus.to_lowercase(), "driver"],
+        "frameworks": "*",
+        "platforms": "*",
+        "build": {
+            "srcDir": "src",
+            "includeDir": "include"
+        }
+    });
+    let mut files = vec![
+        PackageFile::new(
+            "library.json",
+            format!(
+                "{}\n",
+                serde_json::to_string_pretty(&library).unwrap_or_default()
+            ),
+        ),
+        PackageFile::new(
+            format!("include/{}", driver.header_name()),
+            driver.header.clone(),
+        ),
+        PackageFile::new(
+            format!("src/{}", driver.source_name()),
+            driver.source.clone(),
+        ),
+    ];
+    if let Some(example) = &driver.example {
+        files.push(PackageFile::new(
+            format!("examples/basic/main.{}", driver.source_ext),
+            example.clone(),
+        ));
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn driver(binding: Binding) -> CDriver {
+        CDriver {
+            component: "BME280".to_string(),
+            stem: "bme280".to_string(),
+            header_ext: "h",
+            source_ext: "c",
+            header: "/* header */\n".to_string(),
+            source: "/* source */\n".to_string(),
+            example: Some("#include \"bme280.h\"\n".to_string()),
+            binding,
+        }
+    }
+
+    fn paths(files: &[PackageFile]) -> Vec<&str> {
+        files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    fn content<'a>(files: &'a [PackageFile], path: &str) -> &'a str {
+        &files.iter().find(|f| f.path == path).unwrap().content
+    }
+
+    #[test]
+    fn test_parse_framework() {
+        assert_eq!(Framework::parse("Zephyr"), Some(Framework::Zephyr));
+        assert_eq!(Framework::parse("ESP-IDF"), Some(Framework::EspIdf));
+        assert_eq!(Framework::parse("platformio"), Some(Framework::PlatformIo));
+        assert_eq!(Framework::parse("ESP32"), None);
+    }
+
+    #[test]
+    fn test_zephyr_module() {
+        let i2c = driver(Binding::Callbacks {
+            interface: Interface::I2c,
+            wide_address: false,
+        });
+        let files = scaffold(Framework::Zephyr, &i2c).unwrap();
+        assert_eq!(
+            paths(&files),
+            [
+                "zephyr/module.yml",
+                "CMakeLists.txt",
+                "Kconfig",
+                "dts/bindings/wake,bme280.yaml",
+                "include/bme280.h",
+                "bme280.c",
+                "include/bme280_zephyr.h",
+                "bme280_zephyr.c",
+                "examples/app.overlay",
+                "examples/main.c"
+            ]
+        );
+        assert!(content(&files, "Kconfig")
+            .contains("depends on DT_HAS_WAKE_BME280_ENABLED\n\tselect I2C\n"));
+        assert!(content(&files, "dts/bindings/wake,bme280.yaml")
+            .contains("compatible: \"wake,bme280\"\n\ninclude: i2c-device.yaml\n"));
+        assert!(content(&files, "CMakeLists.txt")
+            .contains("zephyr_library_sources(bme280.c bme280_zephyr.c)"));
+        let glue = content(&files, "bme280_zephyr.c");
+        assert!(glue.contains("#define DT_DRV_COMPAT wake_bme280"));
+        assert!(
+            glue.contains("return i2c_write_read_dt(&cfg->bus, addr, sizeof(addr), data, len);")
+        );
+        assert!(glue.contains("\tdrv->read = bme280_bus_read;\n"));
+        assert!(glue.contains("\tif (bme280_init(drv) != 0) {"));
+        assert!(glue.contains(".bus = I2C_DT_SPEC_INST_GET(inst), \\"));
+
+        let spi = driver(Binding::Callbacks {
+            interface: Interface::Spi,
+            wide_address: true,
+        });
+        let glue = scaffold(Framework::Zephyr, &spi).unwrap();
+        let glue = content(&glue, "bme280_zephyr.c");
+        assert!(glue.contains("uint8_t addr[] = { (uint8_t)(reg >> 8), (uint8_t)reg };"));
+        assert!(glue.contains("return spi_transceive_dt(&cfg->bus, &tx, &rx);"));
+
+        let mmio = driver(Binding::Mmio {
+            base: 0x4001_1000,
+            size: 0x400,
+        });
+        let files = scaffold(Framework::Zephyr, &mmio).unwrap();
+        assert!(!paths(&files).contains(&"include/bme280_zephyr.h"));
+        assert!(content(&files, "examples/app.overlay").contains("reg = <0x40011000 0x400>;"));
+
+        let uart = driver(Binding::Template {
+            protocol: "UART".to_string(),
+        });
+        assert!(scaffold(Framework::Zephyr, &uart)
+            .unwrap_err()
+            .contains("UART"));
+    }
+
+    #[test]
+    fn test_esp_idf_component_and_platformio_library() {
+        let i2c = driver(Binding::Callbacks {
+            interface: Interface::I2c,
+            wide_address: false,
+        });
+        let files = scaffold(Framework::EspIdf, &i2c).unwrap();
+        assert!(content(&files, "CMakeLists.txt")
+            .contains("idf_component_register(SRCS \"bme280.c\" \"bme280_idf.c\""));
+        assert!(content(&files, "idf_component.yml").contains("idf: \">=5.2\""));
+        assert!(content(&files, "bme280_idf.c")
+            .contains("i2c_master_transmit_receive((i2c_master_dev_handle_t)ctx"));
+        assert!(content(&files, "examples/main.c").contains("bme280_idf_bind(dev, handle);"));
+
+        let template = driver(Binding::Template {
+            protocol: "UART".to_string(),
+        });
+        let files = scaffold(Framework::EspIdf, &template).unwrap();
+        assert!(!paths(&files).contains(&"bme280_idf.c"));
+        assert!(
+            content(&files, "examples/main.c").contains("if (bme280_init(dev, &config) != 0) {")
+        );
+
+        let files = scaffold(Framework::PlatformIo, &template).unwrap();
+        assert_eq!(
+            paths(&files),
+            [
+                "library.json",
+                "include/bme280.h",
+                "src/bme280.c",
+                "examples/basic/main.c"
+            ]
+        );
+        let library: serde_json::Value =
+            serde_json::from_str(content(&files, "library.json")).unwrap();
+        assert_eq!(library["name"], "bme280-driver");
+        assert_eq!(library["keywords"][1], "uart");
+    }
+}
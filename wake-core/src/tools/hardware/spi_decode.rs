@@ -0,0 +1,453 @@
+//! SPI decoding and mode detection from logic-analyzer captures.
+//!
+//! Frames are the periods with CS low (the whole capture without a CS
+//! channel). CPOL is the SCK level when a frame starts; CPHA follows from
+//! when the data lines change: a device in CPHA=0 shifts data out before the
+//! first clock edge and after each trailing edge and samples on leading
+//! edges, while CPHA=1 shifts on leading edges and samples on trailing ones.
+//! Bits are read MSB first in 8-bit words.
+
+use super::capture::Capture;
+use super::netlist::format_si;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lines {
+    pub cs: Option<usize>,
+    pub sck: usize,
+    pub mosi: Option<usize>,
+    pub miso: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub time: f64,
+    pub end: f64,
+    pub mosi: Vec<u8>,
+    pub miso: Vec<u8>,
+    /// Clocked bits, including any partial word
+    pub bits: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    ModeMismatch,
+    UnknownPhase,
+    IdleLevel,
+    PartialWord,
+    ClockOutsideFrame,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub time: f64,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    /// SPI mode the frames were decoded with (CPOL * 2 + CPHA)
+    pub mode: u8,
+    /// Data changes pointing at CPHA=0 and CPHA=1
+    pub phase_votes: (usize, usize),
+    pub frames: Vec<Frame>,
+    pub issues: Vec<Issue>,
+    /// Median SCK frequency inside frames
+    pub sck_frequency: Option<f64>,
+}
+
+impl Decoded {
+    pub fn has(&self, kind: IssueKind) -> bool {
+        self.issues.iter().any(|i| i.kind == kind)
+    }
+
+    /// Whether CPHA came from the data edges rather than a fallback
+    pub fn phase_detected(&self) -> bool {
+        self.phase_votes.0 + self.phase_votes.1 > 0
+    }
+}
+
+pub fn mode_label(mode: u8) -> String {
+    format!("mode {} (CPOL={}, CPHA={})", mode, mode >> 1, mode & 1)
+}
+
+/// (start, end) of each CS-low period, or the whole capture without CS
+fn frames(capture: &Capture, cs: Option<usize>) -> Vec<(f64, f64)> {
+    let first = capture.samples[0].0;
+    let last = capture.samples[capture.samples.len() - 1].0;
+    let Some(cs) = cs else {
+        return vec![(first, last)];
+    };
+    let mut frames = Vec::new();
+    let mut start = (!Capture::level(capture.samples[0].1, cs)).then_some(first);
+    for pair in capture.samples.windows(2) {
+        let (was, is) = (Capture::level(pair[0].1, cs), Capture::level(pair[1].1, cs));
+        if was && !is {
+            start = Some(pair[1].0);
+        } else if !was && is {
+            if let Some(start) = start.take() {
+                frames.push((start, pair[1].0));
+            }
+        }
+    }
+    if let Some(start) = start {
+        frames.push((start, last));
+    }
+    frames
+}
+
+/// Decode a capture. `configured` is the mode the firmware uses; it is
+/// reported when it differs from the capture and used when CPHA cannot be told
+/// from the data.
+pub fn decode(capture: &Capture, lines: Lines, configured: Option<u8>) -> Decoded {
+    let spans = frames(capture, lines.cs);
+    let mut issues = Vec::new();
+
+    // CPOL: SCK idle level as each frame starts
+    let idle: Vec<bool> = spans
+        .iter()
+        .map(|(start, _)| capture.level_at(lines.sck, *start))
+        .collect();
+    let high = idle.iter().filter(|l| **l).count();
+    let cpol = high * 2 > idle.len();
+    let minority = high.min(idle.len() - high);
+    if minority > 0 {
+        let index = idle.iter().position(|level| *level != cpol).unwrap_or(0);
+        issues.push(Issue {
+            time: spans[index].0,
+            kind: IssueKind::IdleLevel,
+            message: format!(
+                "SCK starts {} in {} of {} frames; the clock polarity changes between transfers",
+                if cpol { "low" } else { "high" },
+                minority,
+                idle.len()
+            ),
+        });
+    }
+
+    // CPHA: which clock edge the data lines change after
+    let data: Vec<usize> = [lines.mosi, lines.miso].into_iter().flatten().collect();
+    let mut votes = (0, 0);
+    for (start, end) in &spans {
+        let mut last_leading: Option<bool> = None;
+        for pair in capture.samples.windows(2) {
+            let ((_, before), (time, after)) = (pair[0], pair[1]);
+            if time <= *start || time > *end {
+                continue;
+            }
+            let changed = before ^ after;
+            if Capture::level(changed, lines.sck) {
+                last_leading = Some(Capture::level(after, lines.sck) != cpol);
+            }
+            if data.iter().any(|d| Capture::level(changed, *d)) {
+                match last_leading {
+                    Some(true) => votes.1 += 1,
+                    _ => votes.0 += 1,
+                }
+            }
+        }
+    }
+    let cpha = match votes {
+        (0, 0) => {
+            let fallback = configured.is_some_and(|m| m & 1 == 1);
+            issues.push(Issue {
+                time: spans.first().map_or(0.0, |s| s.0),
+                kind: IssueKind::UnknownPhase,
+                message: format!(
+                    "Data lines never change inside a frame, so CPHA cannot be measured; decoding with CPHA={}",
+                    fallback as u8
+                ),
+            });
+            fallback
+        }
+        (cpha0, cpha1) => cpha1 > cpha0,
+    };
+    let mode = (cpol as u8) << 1 | cpha as u8;
+
+    if let Some(configured) = configured {
+        // Without data edges CPHA already fell back to the configured one
+        if configured != mode {
+            let misread = decode_frames(capture, lines, &spans[..1.min(spans.len())], configured);
+            issues.push(Issue {
+                time: spans.first().map_or(0.0, |s| s.0),
+                kind: IssueKind::ModeMismatch,
+                message: format!(
+                    "Capture shows {} but the firmware uses {}{}",
+                    mode_label(mode),
+                    mode_label(configured),
+                    misread
+                        .first()
+                        .filter(|f| !f.miso.is_empty())
+                        .map(|f| format!(
+                            "; in that mode the first frame reads MISO as {}",
+                            hex(&f.miso)
+                        ))
+                        .unwrap_or_default()
+                ),
+            });
+        }
+    }
+
+    let frames = decode_frames(capture, lines, &spans, mode);
+    for frame in &frames {
+        if !frame.bits.is_multiple_of(8) {
+            issues.push(Issue {
+                time: frame.time,
+                kind: IssueKind::PartialWord,
+                message: format!(
+                    "Frame ended after {} clocks, not a whole number of bytes (glitch on SCK or CS released early)",
+                    frame.bits
+                ),
+            });
+        }
+    }
+
+    // Clock edges while CS is released
+    if lines.cs.is_some() {
+        let outside: Vec<f64> = capture
+            .samples
+            .windows(2)
+            .filter(|w| Capture::level(w[0].1 ^ w[1].1, lines.sck))
+            .map(|w| w[1].0)
+            .filter(|t| !spans.iter().any(|(start, end)| t > start && t <= end))
+            .collect();
+        if let Some(first) = outside.first() {
+            issues.push(Issue {
+                time: *first,
+                kind: IssueKind::ClockOutsideFrame,
+                message: format!(
+                    "SCK toggles {} times while CS is high (shared bus, active-high CS or wrong CS pin)",
+                    outside.len()
+                ),
+            });
+        }
+    }
+    issues.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let mut periods: Vec<f64> = Vec::new();
+    for (start, end) in &spans {
+        let rises: Vec<f64> = capture
+            .samples
+            .windows(2)
+            .filter(|w| w[1].0 > *start && w[1].0 <= *end)
+            .filter(|w| !Capture::level(w[0].1, lines.sck) && Capture::level(w[1].1, lines.sck))
+            .map(|w| w[1].0)
+            .collect();
+        periods.extend(rises.windows(2).map(|r| r[1] - r[0]));
+    }
+    periods.sort_by(f64::total_cmp);
+
+    Decoded {
+        mode,
+        phase_votes: votes,
+        frames,
+        issues,
+        sck_frequency: periods
+            .get(periods.len() / 2)
+            .filter(|p| **p > 0.0)
+            .map(|p| 1.0 / p),
+    }
+}
+
+fn decode_frames(capture: &Capture, lines: Lines, spans: &[(f64, f64)], mode: u8) -> Vec<Frame> {
+    let cpol = mode & 2 != 0;
+    let sample_on_leading = mode & 1 == 0;
+    spans
+        .iter()
+        .map(|(start, end)| {
+            let mut frame = Frame {
+                time: *start,
+                end: *end,
+                mosi: Vec::new(),
+                miso: Vec::new(),
+                bits: 0,
+            };
+            let (mut mosi, mut miso) = (0u8, 0u8);
+            for pair in capture.samples.windows(2) {
+                let ((_, before), (time, after)) = (pair[0], pair[1]);
+                if time <= *start || time > *end || !Capture::level(before ^ after, lines.sck) {
+                    continue;
+                }
+                let leading = Capture::level(after, lines.sck) != cpol;
+                if leading != sample_on_leading {
+                    continue;
+                }
+                // Data is set up before the sampling edge
+                let bit = |line: Option<usize>| line.is_some_and(|l| Capture::level(before, l));
+                mosi = mosi << 1 | bit(lines.mosi) as u8;
+                miso = miso << 1 | bit(lines.miso) as u8;
+                frame.bits += 1;
+                if frame.bits.is_multiple_of(8) {
+                    if lines.mosi.is_some() {
+                        frame.mosi.push(mosi);
+                    }
+                    if lines.miso.is_some() {
+                        frame.miso.push(miso);
+                    }
+                }
+            }
+            frame
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Markdown table of decoded frames, at most `limit` rows
+pub fn table(decoded: &Decoded, limit: usize) -> String {
+    let mut out = String::from(
+        "| # | Time | Bytes | MOSI | MISO |\n\
+         |---|------|-------|------|------|\n",
+    );
+    let cell = |bytes: &[u8]| {
+        if bytes.is_empty() {
+            "-".to_string()
+        } else {
+            hex(bytes)
+        }
+    };
+    for (index, frame) in decoded.frames.iter().take(limit).enumerate() {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            index + 1,
+            format_si(frame.time, "s"),
+            frame.bits / 8,
+            cell(&frame.mosi),
+            cell(&frame.miso)
+        ));
+    }
+    if decoded.frames.len() > limit {
+        out.push_str(&format!(
+            "\n... {} more frames\n",
+            decoded.frames.len() - limit
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Capture of an SPI master (channels CS, SCK, MOSI, MISO) at 1 MHz
+    pub(crate) struct Bus {
+        pub capture: Capture,
+        time: f64,
+        state: [bool; 4],
+        cpol: bool,
+        cpha: bool,
+    }
+
+    impl Bus {
+        const HALF: f64 = 0.5e-6;
+
+        pub fn new(mode: u8) -> Self {
+            let cpol = mode & 2 != 0;
+            let mut bus = Self {
+                capture: Capture {
+                    channels: ["CS", "SCK", "MOSI", "MISO"].map(String::from).to_vec(),
+                    samples: Vec::new(),
+                },
+                time: 0.0,
+                state: [true, cpol, true, true],
+                cpol,
+                cpha: mode & 1 == 1,
+            };
+            bus.set(0.0, |_| {});
+            bus
+        }
+
+        fn set(&mut self, after: f64, change: impl FnOnce(&mut [bool; 4])) {
+            self.time += after;
+            change(&mut self.state);
+            let levels = self
+                .state
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, l)| acc | (*l as u64) << i);
+            self.capture.samples.push((self.time, levels));
+        }
+
+        fn data(&mut self, after: f64, mosi: bool, miso: bool) {
+            self.set(after, |s| {
+                s[2] = mosi;
+                s[3] = miso;
+            });
+        }
+
+        /// One CS-framed transfer of (MOSI, MISO) byte pairs
+        pub fn frame(&mut self, bytes: &[(u8, u8)]) -> &mut Self {
+            let (idle, active) = (self.cpol, !self.cpol);
+            self.set(Self::HALF, |s| s[0] = false);
+            for (mosi, miso) in bytes {
+                for bit in (0..8).rev() {
+                    let (o, i) = (mosi >> bit & 1 == 1, miso >> bit & 1 == 1);
+                    if self.cpha {
+                        self.set(Self::HALF, |s| s[1] = active);
+                        self.data(Self::HALF / 2.0, o, i);
+                        self.set(Self::HALF / 2.0, |s| s[1] = idle);
+                    } else {
+                        self.data(Self::HALF / 2.0, o, i);
+                        self.set(Self::HALF / 2.0, |s| s[1] = active);
+                        self.set(Self::HALF, |s| s[1] = idle);
+                    }
+                }
+            }
+            self.set(Self::HALF, |s| s[0] = true);
+            self
+        }
+    }
+
+    const LINES: Lines = Lines {
+        cs: Some(0),
+        sck: 1,
+        mosi: Some(2),
+        miso: Some(3),
+    };
+
+    #[test]
+    fn test_mode_detection() {
+        for mode in 0..4 {
+            let mut bus = Bus::new(mode);
+            bus.frame(&[(0xD0, 0x00), (0x00, 0x60)])
+                .frame(&[(0x9F, 0xA5)]);
+            let decoded = decode(&bus.capture, LINES, Some(mode));
+            assert_eq!(decoded.mode, mode, "{:?}", decoded.phase_votes);
+            assert!(decoded.issues.is_empty(), "{:?}", decoded.issues);
+            assert_eq!(decoded.frames.len(), 2);
+            assert_eq!(decoded.frames[0].mosi, vec![0xD0, 0x00]);
+            assert_eq!(decoded.frames[0].miso, vec![0x00, 0x60]);
+            assert_eq!(decoded.frames[1].miso, vec![0xA5]);
+            assert!((decoded.sck_frequency.unwrap() - 1e6).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_mode_mismatch_and_table() {
+        let mut bus = Bus::new(3);
+        bus.frame(&[(0xD0, 0x00), (0x00, 0x60)]);
+        let decoded = decode(&bus.capture, LINES, Some(0));
+        assert_eq!(decoded.mode, 3);
+        assert_eq!(decoded.issues.len(), 1);
+        assert_eq!(decoded.issues[0].kind, IssueKind::ModeMismatch);
+        assert!(decoded.issues[0].message.starts_with(
+            "Capture shows mode 3 (CPOL=1, CPHA=1) but the firmware uses mode 0 (CPOL=0, CPHA=0)"
+        ));
+
+        let table = table(&decoded, 10);
+        assert!(table.contains("| 1 | 500.000 ns | 2 | D0 00 | 00 60 |"));
+
+        // No CS channel and a stray half byte
+        let mut capture = bus.capture.clone();
+        capture.samples.truncate(capture.samples.len() - 20);
+        let decoded = decode(&capture, Lines { cs: None, ..LINES }, None);
+        assert_eq!(decoded.frames[0].bits, 9);
+        assert!(decoded.has(IssueKind::PartialWord));
+    }
+}
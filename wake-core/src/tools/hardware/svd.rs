@@ -0,0 +1,759 @@
+//! CMSIS-SVD register model.
+//!
+//! Loads peripherals, registers, fields and enumerated values from vendor SVD
+//! files. Register properties (size, access, reset value/mask) are inherited
+//! from device to peripheral to cluster to register, `derivedFrom` peripherals
+//! and registers are resolved, clusters are flattened into their registers and
+//! `dim` arrays are expanded.
+
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Access {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+    WriteOnce,
+    ReadWriteOnce,
+}
+
+impl Access {
+    pub fn parse(text: &str) -> Option<Access> {
+        match text.trim() {
+            "read-only" => Some(Access::ReadOnly),
+            "write-only" => Some(Access::WriteOnly),
+            "read-write" => Some(Access::ReadWrite),
+            "writeOnce" => Some(Access::WriteOnce),
+            "read-writeOnce" => Some(Access::ReadWriteOnce),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Access::ReadOnly => "RO",
+            Access::WriteOnly => "WO",
+            Access::ReadWrite => "RW",
+            Access::WriteOnce => "W1",
+            Access::ReadWriteOnce => "RW1",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EnumeratedValue {
+    pub name: String,
+    pub description: Option<String>,
+    /// None for the `isDefault` catch-all entry
+    pub value: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub description: Option<String>,
+    pub bit_offset: u32,
+    pub bit_width: u32,
+    pub access: Option<Access>,
+    pub enumerated_values: Vec<EnumeratedValue>,
+}
+
+impl Field {
+    pub fn mask(&self) -> u64 {
+        if self.bit_width >= 64 {
+            u64::MAX
+        } else {
+            ((1u64 << self.bit_width) - 1) << self.bit_offset
+        }
+    }
+
+    pub fn extract(&self, register_value: u64) -> u64 {
+        (register_value & self.mask()) >> self.bit_offset
+    }
+
+    /// `[msb:lsb]`, or `[bit]` for single-bit fields
+    pub fn bit_range(&self) -> String {
+        if self.bit_width == 1 {
+            format!("[{}]", self.bit_offset)
+        } else {
+            format!(
+                "[{}:{}]",
+                self.bit_offset + self.bit_width - 1,
+                self.bit_offset
+            )
+        }
+    }
+
+    /// Enumerated meaning of a field value, falling back to the default entry
+    pub fn meaning(&self, value: u64) -> Option<&EnumeratedValue> {
+        self.enumerated_values
+            .iter()
+            .find(|e| e.value == Some(value))
+            .or_else(|| self.enumerated_values.iter().find(|e| e.value.is_none()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Register {
+    pub name: String,
+    pub description: Option<String>,
+    /// Offset from the peripheral base address
+    pub address_offset: u64,
+    pub size: u32,
+    pub access: Option<Access>,
+    pub reset_value: u64,
+    pub reset_mask: u64,
+    pub fields: Vec<Field>,
+}
+
+impl Register {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Peripheral {
+    pub name: String,
+    pub description: Option<String>,
+    pub group_name: Option<String>,
+    pub base_address: u64,
+    pub derived_from: Option<String>,
+    pub registers: Vec<Register>,
+}
+
+impl Peripheral {
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Device {
+    pub name: String,
+    pub description: Option<String>,
+    pub peripherals: Vec<Peripheral>,
+}
+
+/// Result of a name lookup
+#[derive(Clone, Debug, PartialEq)]
+pub enum SvdMatch<'a> {
+    Peripheral(&'a Peripheral),
+    Register(&'a Peripheral, &'a Register),
+    Field(&'a Peripheral, &'a Register, &'a Field),
+}
+
+/// SVD scaled integer: decimal, `0x` hex or `#` binary (`x` don't-care bits read as 0)
+pub fn parse_int(text: &str) -> Result<u64, String> {
+    let t = text.trim();
+    let err = || format!("Invalid SVD number '{}'", text);
+    if let Some(hex) = t.strip_prefix("0x").or_else(|| t.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).map_err(|_| err())
+    } else if let Some(bin) = t.strip_prefix('#').or_else(|| t.strip_prefix("0b")) {
+        u64::from_str_radix(&bin.replace(['x', 'X'], "0"), 2).map_err(|_| err())
+    } else {
+        t.parse().map_err(|_| err())
+    }
+}
+
+type Node<'a, 'input> = roxmltree::Node<'a, 'input>;
+
+fn child<'a, 'input>(node: Node<'a, 'input>, tag: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|c| c.is_element() && c.tag_name().name() == tag)
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    tag: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |c| c.is_element() && c.tag_name().name() == tag)
+}
+
+fn text(node: Node, tag: &str) -> Option<String> {
+    child(node, tag)
+        .and_then(|c| c.text())
+        .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|t| !t.is_empty())
+}
+
+fn int(node: Node, tag: &str) -> Result<Option<u64>, String> {
+    text(node, tag).map(|t| parse_int(&t)).transpose()
+}
+
+fn required(node: Node, tag: &str) -> Result<String, String> {
+    text(node, tag).ok_or_else(|| format!("<{}> without <{}>", node.tag_name().name(), tag))
+}
+
+/// Register properties inherited down the hierarchy
+#[derive(Clone, Copy, Debug, Default)]
+struct Properties {
+    size: Option<u32>,
+    access: Option<Access>,
+    reset_value: Option<u64>,
+    reset_mask: Option<u64>,
+}
+
+impl Properties {
+    fn inherit(self, node: Node) -> Result<Self, String> {
+        Ok(Properties {
+            size: int(node, "size")?.map(|s| s as u32).or(self.size),
+            access: text(node, "access")
+                .and_then(|a| Access::parse(&a))
+                .or(self.access),
+            reset_value: int(node, "resetValue")?.or(self.reset_value),
+            reset_mask: int(node, "resetMask")?.or(self.reset_mask),
+        })
+    }
+}
+
+/// `dim` array expansion: (name, offset step index) per element
+fn dim_elements(node: Node, name: &str) -> Result<Vec<(String, u64)>, String> {
+    let Some(dim) = int(node, "dim")? else {
+        return Ok(vec![(name.to_string(), 0)]);
+    };
+    let increment = int(node, "dimIncrement")?.unwrap_or(0);
+    let indices: Vec<String> = match text(node, "dimIndex") {
+        Some(index) => match index.split_once('-') {
+            Some((a, b)) if !index.contains(',') => {
+                match (a.trim().parse::<u64>(), b.trim().parse::<u64>()) {
+                    (Ok(a), Ok(b)) => (a..=b).map(|i| i.to_string()).collect(),
+                    // Letter ranges such as A-D
+                    _ => {
+                        let (a, b) = (a.trim().chars().next(), b.trim().chars().next());
+                        match (a, b) {
+                            (Some(a), Some(b)) => (a..=b).map(|c| c.to_string()).collect(),
+                            _ => return Err(format!("Invalid dimIndex '{}'", index)),
+                        }
+                    }
+                }
+            }
+            _ => index.split(',').map(|s| s.trim().to_string()).collect(),
+        },
+        None => (0..dim).map(|i| i.to_string()).collect(),
+    };
+    if indices.len() as u64 != dim {
+        return Err(format!(
+            "dimIndex of {} does not have {} entries",
+            name, dim
+        ));
+    }
+    Ok(indices
+        .iter()
+        .enumerate()
+        .map(|(i, index)| (name.replace("%s", index), i as u64 * increment))
+        .collect())
+}
+
+fn parse_field(node: Node, register_access: Option<Access>) -> Result<Vec<Field>, String> {
+    let name = required(node, "name")?;
+    let (bit_offset, bit_width) = if let Some(offset) = int(node, "bitOffset")? {
+        (offset as u32, int(node, "bitWidth")?.unwrap_or(1) as u32)
+    } else if let (Some(lsb), Some(msb)) = (int(node, "lsb")?, int(node, "msb")?) {
+        let width = msb
+            .checked_sub(lsb)
+            .ok_or_else(|| format!("Field {} has lsb {} above msb {}", name, lsb, msb))?;
+        (lsb as u32, width as u32 + 1)
+    } else if let Some(range) = text(node, "bitRange") {
+        let inner = range.trim_matches(|c| c == '[' || c == ']');
+        let (msb, lsb) = inner
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid bitRange '{}' in field {}", range, name))?;
+        let msb = parse_int(msb)? as u32;
+        let lsb = parse_int(lsb)? as u32;
+        (lsb, msb.saturating_sub(lsb) + 1)
+    } else {
+        return Err(format!("Field {} has no bit position", name));
+    };
+
+    let mut enumerated_values = Vec::new();
+    for group in children(node, "enumeratedValues") {
+        for value in children(group, "enumeratedValue") {
+            let is_default = text(value, "isDefault").is_some_and(|d| d == "true" || d == "1");
+            enumerated_values.push(EnumeratedValue {
+                name: required(value, "name")?,
+                description: text(value, "description"),
+                value: if is_default {
+                    None
+                } else {
+                    int(value, "value")?
+                },
+            });
+        }
+    }
+
+    let description = text(node, "description");
+    let access = text(node, "access")
+        .and_then(|a| Access::parse(&a))
+        .or(register_access);
+    Ok(dim_elements(node, &name)?
+        .into_iter()
+        .map(|(name, step)| Field {
+            name,
+            description: description.clone(),
+            bit_offset: bit_offset + step as u32,
+            bit_width,
+            access,
+            enumerated_values: enumerated_values.clone(),
+        })
+        .collect())
+}
+
+fn parse_register(
+    node: Node,
+    inherited: Properties,
+    prefix: &str,
+    base_offset: u64,
+) -> Result<Vec<Register>, String> {
+    let name = required(node, "name")?;
+    let props = inherited.inherit(node)?;
+    let offset = int(node, "addressOffset")?
+        .ok_or_else(|| format!("Register {} has no addressOffset", name))?;
+    let size = props.size.unwrap_or(32);
+
+    let mut fields = Vec::new();
+    if let Some(list) = child(node, "fields") {
+        for field in children(list, "field") {
+            fields.extend(parse_field(field, props.access)?);
+        }
+    }
+    fields.sort_by_key(|f| f.bit_offset);
+
+    let description = text(node, "description");
+    let full_mask = if size >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << size) - 1
+    };
+    Ok(dim_elements(node, &name)?
+        .into_iter()
+        .map(|(name, step)| Register {
+            name: format!("{}{}", prefix, name),
+            description: description.clone(),
+            address_offset: base_offset + offset + step,
+            size,
+            access: props.access,
+            reset_value: props.reset_value.unwrap_or(0),
+            reset_mask: props.reset_mask.unwrap_or(full_mask),
+            fields: fields.clone(),
+        })
+        .collect())
+}
+
+/// Registers of a `<registers>` or `<cluster>` node, clusters flattened as `CLUSTER_REG`
+fn parse_registers(
+    node: Node,
+    inherited: Properties,
+    prefix: &str,
+    base_offset: u64,
+) -> Result<Vec<Register>, String> {
+    let mut registers = Vec::new();
+    for item in node.children().filter(|c| c.is_element()) {
+        match item.tag_name().name() {
+            "register" => {
+                let derived = item.attribute("derivedFrom");
+                let mut parsed = parse_register(item, inherited, prefix, base_offset)?;
+                if let Some(base) = derived {
+                    let base = format!("{}{}", prefix, base);
+                    if let Some(source) = registers.iter().find(|r: &&Register| r.name == base) {
+                        let source: Register = source.clone();
+                        for r in parsed.iter_mut().filter(|r| r.fields.is_empty()) {
+                            r.fields = source.fields.clone();
+                            r.description = r.description.take().or(source.description.clone());
+                        }
+                    }
+                }
+                registers.extend(parsed);
+            }
+            "cluster" => {
+                let name = required(item, "name")?;
+                let props = inherited.inherit(item)?;
+                let offset = int(item, "addressOffset")?.unwrap_or(0);
+                for (cluster_name, step) in dim_elements(item, &name)? {
+                    let cluster_prefix =
+                        format!("{}{}_", prefix, cluster_name.replace(['[', ']'], ""));
+                    registers.extend(parse_registers(
+                        item,
+                        props,
+                        &cluster_prefix,
+                        base_offset + offset + step,
+                    )?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(registers)
+}
+
+impl Device {
+    pub fn parse(xml: &str) -> Result<Device, String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|e| format!("Invalid SVD XML: {}", e))?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "device" {
+            return Err("Not a CMSIS-SVD file: root element is not <device>".to_string());
+        }
+        let device_props = Properties::default().inherit(root)?;
+
+        let mut peripherals = Vec::new();
+        if let Some(list) = child(root, "peripherals") {
+            for node in children(list, "peripheral") {
+                let name = required(node, "name")?;
+                let props = device_props.inherit(node)?;
+                let registers = match child(node, "registers") {
+                    Some(registers) => parse_registers(registers, props, "", 0)?,
+                    None => Vec::new(),
+                };
+                let base_address = int(node, "baseAddress")?
+                    .ok_or_else(|| format!("Peripheral {} has no baseAddress", name))?;
+                let description = text(node, "description");
+                let group_name = text(node, "groupName");
+                let derived_from = node.attribute("derivedFrom").map(str::to_string);
+                for (name, step) in dim_elements(node, &name)? {
+                    peripherals.push(Peripheral {
+                        name,
+                        description: description.clone(),
+                        group_name: group_name.clone(),
+                        base_address: base_address + step,
+                        derived_from: derived_from.clone(),
+                        registers: registers.clone(),
+                    });
+                }
+            }
+        }
+
+        // derivedFrom peripherals share the register layout of their base
+        for i in 0..peripherals.len() {
+            let Some(base_name) = peripherals[i].derived_from.clone() else {
+                continue;
+            };
+            let base = peripherals
+                .iter()
+                .find(|p| p.name == base_name)
+                .cloned()
+                .ok_or_else(|| {
+                    format!(
+                        "Peripheral {} derives from unknown {}",
+                        peripherals[i].name, base_name
+                    )
+                })?;
+            let derived = &mut peripherals[i];
+            if derived.registers.is_empty() {
+                derived.registers = base.registers;
+            }
+            derived.description = derived.description.take().or(base.description);
+            derived.group_name = derived.group_name.take().or(base.group_name);
+        }
+
+        Ok(Device {
+            name: required(root, "name")?,
+            description: text(root, "description"),
+            peripherals,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Device, String> {
+        let xml = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        Device::parse(&xml).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn peripheral(&self, name: &str) -> Option<&Peripheral> {
+        self.peripherals
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Resolve `PERIPH`, `PERIPH.REG`, `PERIPH_REG`, `PERIPH.REG.FIELD` or a bare
+    /// register name (every peripheral that has it)
+    pub fn lookup(&self, query: &str) -> Vec<SvdMatch<'_>> {
+        let query = query.trim();
+        let parts: Vec<&str> = query
+            .split(['.', '/', ':', ' '])
+            .filter(|p| !p.is_empty())
+            .collect();
+        let resolve = |periph: &str, rest: &[&str]| -> Option<SvdMatch<'_>> {
+            let p = self.peripheral(periph)?;
+            match rest {
+                [] => Some(SvdMatch::Peripheral(p)),
+                [reg] => p.register(reg).map(|r| SvdMatch::Register(p, r)),
+                [reg, field] => {
+                    let r = p.register(reg)?;
+                    r.field(field).map(|f| SvdMatch::Field(p, r, f))
+                }
+                _ => None,
+            }
+        };
+
+        if let Some((first, rest)) = parts.split_first() {
+            if let Some(m) = resolve(first, rest) {
+                return vec![m];
+            }
+        }
+        // `RCC_CFGR` style: try every underscore as the peripheral/register split
+        if parts.len() == 1 {
+            for (i, _) in query.match_indices('_') {
+                if let Some(m) = resolve(&query[..i], &[&query[i + 1..]]) {
+                    return vec![m];
+                }
+            }
+        }
+        if let [register] = parts.as_slice() {
+            return self
+                .peripherals
+                .iter()
+                .filter_map(|p| p.register(register).map(|r| SvdMatch::Register(p, r)))
+                .collect();
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) const TEST_SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device schemaVersion="1.1" xmlns:xs="http://www.w3.org/2001/XMLSchema-instance">
+  <name>STM32F4X</name>
+  <description>Test subset</description>
+  <size>32</size>
+  <access>read-write</access>
+  <resetValue>0x00000000</resetValue>
+  <resetMask>0xFFFFFFFF</resetMask>
+  <peripherals>
+    <peripheral>
+      <name>RCC</name>
+      <description>Reset and clock control</description>
+      <groupName>RCC</groupName>
+      <baseAddress>0x40023800</baseAddress>
+      <registers>
+        <register>
+          <name>CFGR</name>
+          <description>clock configuration register</description>
+          <addressOffset>0x08</addressOffset>
+          <resetValue>0x00000000</resetValue>
+          <fields>
+            <field>
+              <name>SW</name>
+              <description>System clock switch</description>
+              <bitOffset>0</bitOffset>
+              <bitWidth>2</bitWidth>
+              <enumeratedValues>
+                <enumeratedValue><name>HSI</name><description>HSI selected</description><value>0</value></enumeratedValue>
+                <enumeratedValue><name>HSE</name><description>HSE selected</description><value>1</value></enumeratedValue>
+                <enumeratedValue><name>PLL</name><description>PLL selected</description><value>2</value></enumeratedValue>
+              </enumeratedValues>
+            </field>
+            <field>
+              <name>SWS</name>
+              <description>System clock switch status</description>
+              <bitRange>[3:2]</bitRange>
+              <access>read-only</access>
+              <enumeratedValues>
+                <enumeratedValue><name>HSI</name><value>#00</value></enumeratedValue>
+                <enumeratedValue><name>HSE</name><value>#01</value></enumeratedValue>
+                <enumeratedValue><name>PLL</name><value>#10</value></enumeratedValue>
+              </enumeratedValues>
+            </field>
+            <field>
+              <name>HPRE</name>
+              <description>AHB prescaler</description>
+              <lsb>4</lsb>
+              <msb>7</msb>
+              <enumeratedValues>
+                <enumeratedValue><name>Div1</name><value>0</value></enumeratedValue>
+                <enumeratedValue><name>Div2</name><value>8</value></enumeratedValue>
+                <enumeratedValue><name>Div4</name><value>9</value></enumeratedValue>
+              </enumeratedValues>
+            </field>
+            <field>
+              <name>PPRE1</name>
+              <description>APB Low speed prescaler (APB1)</description>
+              <bitOffset>10</bitOffset>
+              <bitWidth>3</bitWidth>
+              <enumeratedValues>
+                <enumeratedValue><name>Div1</name><value>0</value></enumeratedValue>
+                <enumeratedValue><name>Div2</name><value>4</value></enumeratedValue>
+                <enumeratedValue><name>Div4</name><value>5</value></enumeratedValue>
+                <enumeratedValue><name>Other</name><isDefault>true</isDefault></enumeratedValue>
+              </enumeratedValues>
+            </field>
+            <field>
+              <name>PPRE2</name>
+              <description>APB high-speed prescaler (APB2)</description>
+              <bitOffset>13</bitOffset>
+              <bitWidth>3</bitWidth>
+            </field>
+            <field>
+              <name>RTCPRE</name>
+              <description>HSE division factor for RTC clock</description>
+              <bitOffset>16</bitOffset>
+              <bitWidth>5</bitWidth>
+            </field>
+          </fields>
+        </register>
+        <register>
+          <name>AHB1ENR</name>
+          <description>AHB1 peripheral clock register</description>
+          <addressOffset>0x30</addressOffset>
+          <resetValue>0x00100000</resetValue>
+          <fields>
+            <field>
+              <name>GPIO%sEN</name>
+              <description>IO port clock enable</description>
+              <bitOffset>0</bitOffset>
+              <bitWidth>1</bitWidth>
+              <dim>3</dim>
+              <dimIncrement>1</dimIncrement>
+              <dimIndex>A-C</dimIndex>
+            </field>
+          </fields>
+        </register>
+      </registers>
+    </peripheral>
+    <peripheral>
+      <name>USART1</name>
+      <description>Universal synchronous asynchronous receiver transmitter</description>
+      <baseAddress>0x40011000</baseAddress>
+      <registers>
+        <register>
+          <name>SR</name>
+          <addressOffset>0x0</addressOffset>
+          <size>0x20</size>
+          <access>read-only</access>
+          <resetValue>0x00C00000</resetValue>
+          <fields>
+            <field><name>TXE</name><bitOffset>7</bitOffset><bitWidth>1</bitWidth></field>
+          </fields>
+        </register>
+        <register>
+          <name>BRR</name>
+          <addressOffset>0x8</addressOffset>
+          <fields>
+            <field><name>DIV_Fraction</name><bitOffset>0</bitOffset><bitWidth>4</bitWidth></field>
+            <field><name>DIV_Mantissa</name><bitOffset>4</bitOffset><bitWidth>12</bitWidth></field>
+          </fields>
+        </register>
+        <cluster>
+          <name>CH[%s]</name>
+          <dim>2</dim>
+          <dimIncrement>0x10</dimIncrement>
+          <addressOffset>0x40</addressOffset>
+          <register>
+            <name>CTRL</name>
+            <addressOffset>0x4</addressOffset>
+            <fields>
+              <field><name>EN</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth></field>
+            </fields>
+          </register>
+        </cluster>
+      </registers>
+    </peripheral>
+    <peripheral derivedFrom="USART1">
+      <name>USART2</name>
+      <baseAddress>0x40004400</baseAddress>
+    </peripheral>
+  </peripherals>
+</device>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int("0x1F").unwrap(), 31);
+        assert_eq!(parse_int("0X20").unwrap(), 32);
+        assert_eq!(parse_int("#101").unwrap(), 5);
+        assert_eq!(parse_int("#1x1").unwrap(), 5);
+        assert_eq!(parse_int(" 42 ").unwrap(), 42);
+        assert!(parse_int("0xZZ").is_err());
+    }
+
+    #[test]
+    fn test_parse_device() {
+        let device = Device::parse(TEST_SVD).unwrap();
+        assert_eq!(device.name, "STM32F4X");
+        assert_eq!(device.peripherals.len(), 3);
+
+        let rcc = device.peripheral("rcc").unwrap();
+        assert_eq!(rcc.base_address, 0x4002_3800);
+        let cfgr = rcc.register("CFGR").unwrap();
+        assert_eq!(cfgr.address_offset, 0x08);
+        assert_eq!(cfgr.size, 32);
+        assert_eq!(cfgr.access, Some(Access::ReadWrite));
+
+        let sws = cfgr.field("SWS").unwrap();
+        assert_eq!((sws.bit_offset, sws.bit_width), (2, 2));
+        assert_eq!(sws.access, Some(Access::ReadOnly));
+        assert_eq!(sws.enumerated_values[2].value, Some(2));
+        let hpre = cfgr.field("HPRE").unwrap();
+        assert_eq!((hpre.bit_offset, hpre.bit_width), (4, 4));
+        assert_eq!(hpre.mask(), 0xF0);
+        assert_eq!(hpre.bit_range(), "[7:4]");
+
+        let ppre1 = cfgr.field("PPRE1").unwrap();
+        assert_eq!(ppre1.meaning(4).unwrap().name, "Div2");
+        assert_eq!(ppre1.meaning(7).unwrap().name, "Other");
+
+        let ahb1enr = rcc.register("AHB1ENR").unwrap();
+        let names: Vec<&str> = ahb1enr.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["GPIOAEN", "GPIOBEN", "GPIOCEN"]);
+        assert_eq!(ahb1enr.field("GPIOCEN").unwrap().bit_offset, 2);
+        assert_eq!(ahb1enr.reset_value, 0x0010_0000);
+    }
+
+    #[test]
+    fn test_clusters_and_derived_peripherals() {
+        let device = Device::parse(TEST_SVD).unwrap();
+        let usart1 = device.peripheral("USART1").unwrap();
+        assert_eq!(
+            usart1.register("SR").unwrap().access,
+            Some(Access::ReadOnly)
+        );
+        assert_eq!(usart1.register("CH0_CTRL").unwrap().address_offset, 0x44);
+        assert_eq!(usart1.register("CH1_CTRL").unwrap().address_offset, 0x54);
+
+        let usart2 = device.peripheral("USART2").unwrap();
+        assert_eq!(usart2.base_address, 0x4000_4400);
+        assert_eq!(usart2.registers, usart1.registers);
+        assert_eq!(usart2.description, usart1.description);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let device = Device::parse(TEST_SVD).unwrap();
+        assert!(matches!(
+            device.lookup("RCC_CFGR").as_slice(),
+            [SvdMatch::Register(p, r)] if p.name == "RCC" && r.name == "CFGR"
+        ));
+        assert!(matches!(
+            device.lookup("rcc.cfgr.sw").as_slice(),
+            [SvdMatch::Field(_, _, f)] if f.name == "SW"
+        ));
+        assert!(matches!(
+            device.lookup("USART2").as_slice(),
+            [SvdMatch::Peripheral(_)]
+        ));
+        // A bare register name matches every peripheral that has it
+        assert_eq!(device.lookup("BRR").len(), 2);
+        assert!(device.lookup("RCC.NOPE").is_empty());
+    }
+
+    #[test]
+    fn test_rejects_non_svd() {
+        assert!(Device::parse("<html></html>").is_err());
+        assert!(Device::parse("not xml").is_err());
+        let reversed = TEST_SVD.replace("<lsb>4</lsb>", "<lsb>9</lsb>");
+        assert_eq!(
+            Device::parse(&reversed).unwrap_err(),
+            "Field HPRE has lsb 9 above msb 7"
+        );
+    }
+}
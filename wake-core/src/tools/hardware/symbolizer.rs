@@ -0,0 +1,225 @@
+//! Address-to-source lookup in a firmware ELF.
+//!
+//! Uses the DWARF line tables and subprogram entries when the image was
+//! built with `-g`, and falls back to the symbol table otherwise.
+
+use super::elf_image;
+use super::memory_usage::{Image, SectionKind};
+use gimli::{EndianSlice, RunTimeEndian};
+use object::{Object, ObjectSection};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceFrame {
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl SourceFrame {
+    fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?;
+        Some(match self.line {
+            Some(line) => format!("{}:{}", file, line),
+            None => file.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Resolved {
+    /// Innermost first: inlined functions, then the function they were
+    /// inlined into
+    pub frames: Vec<SourceFrame>,
+    /// Symbol containing the address and the offset into it
+    pub symbol: Option<(String, u64)>,
+    /// Output section containing the address
+    pub section: Option<(String, SectionKind)>,
+}
+
+impl Resolved {
+    pub fn function(&self) -> Option<String> {
+        self.frames
+            .iter()
+            .find_map(|f| f.function.clone())
+            .or_else(|| self.symbol.as_ref().map(|(name, _)| name.clone()))
+    }
+
+    pub fn is_code(&self) -> bool {
+        matches!(self.section, Some((_, SectionKind::Code)))
+    }
+
+    /// `read_sensor at src/sensor.c:42 (inlined into main at src/main.c:17)`,
+    /// `main+0x1a`, or where the address lies when nothing matches
+    pub fn describe(&self) -> String {
+        if let Some(first) = self.frames.first() {
+            let name = |frame: &SourceFrame| {
+                frame
+                    .function
+                    .clone()
+                    .or_else(|| self.symbol.as_ref().map(|(name, _)| name.clone()))
+                    .unwrap_or_else(|| "??".to_string())
+            };
+            let mut out = match first.location() {
+                Some(location) => format!("{} at {}", name(first), location),
+                None => name(first),
+            };
+            for frame in &self.frames[1..] {
+                out.push_str(&format!(" (inlined into {}", name(frame)));
+                if let Some(location) = frame.location() {
+                    out.push_str(&format!(" at {}", location));
+                }
+                out.push(')');
+            }
+            return out;
+        }
+        match (&self.symbol, &self.section) {
+            (Some((name, 0)), _) => name.clone(),
+            (Some((name, offset)), _) => format!("{}+0x{:x}", name, offset),
+            (None, Some((section, _))) => format!("{}, no symbol", section),
+            (None, None) => "outside the ELF image".to_string(),
+        }
+    }
+}
+
+type Reader<'data> = EndianSlice<'data, RunTimeEndian>;
+
+pub struct Symbolizer<'data> {
+    context: Option<addr2line::Context<Reader<'data>>>,
+    image: Image,
+}
+
+impl<'data> Symbolizer<'data> {
+    pub fn new(data: &'data [u8]) -> Result<Self, String> {
+        let image = elf_image::read(data)?;
+        let file = object::File::parse(data).map_err(|e| format!("Invalid ELF file: {}", e))?;
+        let context = if file.section_by_name(".debug_info").is_some() {
+            let endian = if file.is_little_endian() {
+                RunTimeEndian::Little
+            } else {
+                RunTimeEndian::Big
+            };
+            let dwarf = gimli::Dwarf::load(|id| -> Result<Reader<'data>, gimli::Error> {
+                let data = file
+                    .section_by_name(id.name())
+                    .and_then(|section| section.data().ok())
+                    .unwrap_or(&[]);
+                Ok(EndianSlice::new(data, endian))
+            })
+            .map_err(|e| format!("Invalid DWARF data: {}", e))?;
+            Some(
+                addr2line::Context::from_dwarf(dwarf)
+                    .map_err(|e| format!("Invalid DWARF data: {}", e))?,
+            )
+        } else {
+            None
+        };
+        Ok(Self { context, image })
+    }
+
+    /// Whether file:line information is available
+    pub fn has_debug_info(&self) -> bool {
+        self.context.is_some()
+    }
+
+    pub fn resolve(&self, addr: u64) -> Resolved {
+        let mut frames = Vec::new();
+        if let Some(context) = &self.context {
+            if let Ok(mut iter) = context.find_frames(addr).skip_all_loads() {
+                while let Ok(Some(frame)) = iter.next() {
+                    frames.push(SourceFrame {
+                        function: frame
+                            .function
+                            .as_ref()
+                            .and_then(|f| f.demangle().ok())
+                            .map(|name| name.to_string()),
+                        file: frame
+                            .location
+                            .as_ref()
+                            .and_then(|l| l.file)
+                            .map(str::to_string),
+                        line: frame.location.as_ref().and_then(|l| l.line),
+                    });
+                }
+            }
+        }
+        let symbol = self
+            .image
+            .symbols
+            .iter()
+            .filter(|s| addr >= s.addr && addr - s.addr < s.size)
+            .min_by_key(|s| s.size)
+            .map(|s| (s.name.clone(), addr - s.addr));
+        let section = self
+            .image
+            .sections
+            .iter()
+            .find(|s| addr >= s.addr && addr - s.addr < s.size)
+            .map(|s| (s.name.clone(), s.kind));
+        Resolved {
+            frames,
+            symbol,
+            section,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_elf::{self, TestFunction, TestSection, TestSymbol};
+    use super::*;
+
+    #[test]
+    fn test_resolve_with_and_without_dwarf() {
+        let functions = [
+            TestFunction {
+                name: "main",
+                addr: 0x0800_0100,
+                size: 0x20,
+                lines: vec![(0, 10), (0x8, 12), (0x14, 13)],
+            },
+            TestFunction {
+                name: "read_sensor",
+                addr: 0x0800_0120,
+                size: 0x10,
+                lines: vec![(0, 30), (0x6, 31)],
+            },
+        ];
+        let mut sections = vec![
+            TestSection::code(".text", 0x0800_0100, 0x30),
+            TestSection::bss(".bss", 0x2000_0000, 0x100),
+        ];
+        let symbols = [
+            TestSymbol::func("main", 0x0800_0101, 0x20, 0),
+            TestSymbol::func("read_sensor", 0x0800_0121, 0x10, 0),
+            TestSymbol::object("samples", 0x2000_0040, 0x40, 1),
+        ];
+        let stripped = test_elf::build(&sections, &symbols);
+        sections.extend(test_elf::debug_sections("src/main.c", &functions));
+        let data = test_elf::build(&sections, &symbols);
+
+        let symbolizer = Symbolizer::new(&data).unwrap();
+        assert!(symbolizer.has_debug_info());
+        let pc = symbolizer.resolve(0x0800_0128);
+        assert_eq!(pc.describe(), "read_sensor at /work/src/main.c:31");
+        assert!(pc.is_code());
+        assert_eq!(symbolizer.resolve(0x0800_0110).frames[0].line, Some(12));
+        let data_addr = symbolizer.resolve(0x2000_0044);
+        assert_eq!(data_addr.symbol, Some(("samples".to_string(), 4)));
+        assert_eq!(data_addr.describe(), "samples+0x4");
+        assert_eq!(
+            symbolizer.resolve(0x2000_0000).describe(),
+            ".bss, no symbol"
+        );
+        assert_eq!(
+            symbolizer.resolve(0x0900_0000).describe(),
+            "outside the ELF image"
+        );
+
+        let symbolizer = Symbolizer::new(&stripped).unwrap();
+        assert!(!symbolizer.has_debug_info());
+        assert_eq!(
+            symbolizer.resolve(0x0800_0128).describe(),
+            "read_sensor+0x8"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Minimal 32-bit ARM ELF executables for tests.
+
+use gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections,
+};
+use gimli::{Encoding, Format, LineEncoding};
+use object::elf;
+use object::write::elf::{FileHeader, ProgramHeader, SectionHeader, Sym, Writer};
+use object::Endianness;
+
+pub struct TestSection {
+    pub name: &'static str,
+    pub addr: u64,
+    /// Load address; differs from `addr` for initialised RAM data
+    pub lma: u64,
+    pub data: Vec<u8>,
+    /// Size of a NOBITS section (`data` is then ignored)
+    pub bss: Option<u64>,
+    pub flags: u64,
+}
+
+impl TestSection {
+    pub fn code(name: &'static str, addr: u64, size: usize) -> Self {
+        Self {
+            name,
+            addr,
+            lma: addr,
+            data: vec![0; size],
+            bss: None,
+            flags: u64::from(elf::SHF_ALLOC | elf::SHF_EXECINSTR),
+        }
+    }
+
+    pub fn data(name: &'static str, addr: u64, lma: u64, size: usize) -> Self {
+        Self {
+            name,
+            addr,
+            lma,
+            data: vec![0; size],
+            bss: None,
+            flags: u64::from(elf::SHF_ALLOC | elf::SHF_WRITE),
+        }
+    }
+
+    pub fn bss(name: &'static str, addr: u64, size: u64) -> Self {
+        Self {
+            name,
+            addr,
+            lma: addr,
+            data: Vec::new(),
+            bss: Some(size),
+            flags: u64::from(elf::SHF_ALLOC | elf::SHF_WRITE),
+        }
+    }
+
+    /// Non-allocated section such as `.debug_line`
+    pub fn info(name: &'static str, data: Vec<u8>) -> Self {
+        Self {
+            name,
+            addr: 0,
+            lma: 0,
+            data,
+            bss: None,
+            flags: 0,
+        }
+    }
+
+    fn alloc(&self) -> bool {
+        self.flags & u64::from(elf::SHF_ALLOC) != 0
+    }
+
+    fn size(&self) -> u64 {
+        self.bss.unwrap_or(self.data.len() as u64)
+    }
+}
+
+pub struct TestSymbol {
+    pub name: &'static str,
+    pub value: u64,
+    pub size: u64,
+    /// Index into the section list
+    pub section: usize,
+    pub func: bool,
+}
+
+impl TestSymbol {
+    pub fn func(name: &'static str, value: u64, size: u64, section: usize) -> Self {
+        Self {
+            name,
+            value,
+            size,
+            section,
+            func: true,
+        }
+    }
+
+    pub fn object(name: &'static str, value: u64, size: u64, section: usize) -> Self {
+        Self {
+            name,
+            value,
+            size,
+            section,
+            func: false,
+        }
+    }
+}
+
+pub fn build(sections: &[TestSection], symbols: &[TestSymbol]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut w = Writer::new(Endianness::Little, false, &mut out);
+    let loads: Vec<usize> = (0..sections.len())
+        .filter(|&i| sections[i].alloc())
+        .collect();
+
+    w.reserve_file_header();
+    w.reserve_program_headers(loads.len() as u32);
+    let names: Vec<_> = sections
+        .iter()
+        .map(|s| w.add_section_name(s.name.as_bytes()))
+        .collect();
+    w.reserve_null_section_index();
+    let indices: Vec<_> = sections.iter().map(|_| w.reserve_section_index()).collect();
+    let offsets: Vec<usize> = sections
+        .iter()
+        .map(|s| match s.bss {
+            Some(_) => w.reserved_len(),
+            None => w.reserve(s.data.len(), 4),
+        })
+        .collect();
+    let symbol_names: Vec<_> = symbols
+        .iter()
+        .map(|s| w.add_string(s.name.as_bytes()))
+        .collect();
+    w.reserve_null_symbol_index();
+    for symbol in symbols {
+        w.reserve_symbol_index(Some(indices[symbol.section]));
+    }
+    w.reserve_symtab_section_index();
+    w.reserve_symtab();
+    w.reserve_strtab_section_index();
+    w.reserve_strtab();
+    w.reserve_shstrtab_section_index();
+    w.reserve_shstrtab();
+    w.reserve_section_headers();
+
+    w.write_file_header(&FileHeader {
+        os_abi: elf::ELFOSABI_NONE,
+        abi_version: 0,
+        e_type: elf::ET_EXEC,
+        e_machine: elf::EM_ARM,
+        e_entry: 0,
+        e_flags: 0x0500_0000,
+    })
+    .unwrap();
+    w.write_align_program_headers();
+    for &i in &loads {
+        let s = &sections[i];
+        w.write_program_header(&ProgramHeader {
+            p_type: elf::PT_LOAD,
+            p_flags: elf::PF_R,
+            p_offset: offsets[i] as u64,
+            p_vaddr: s.addr,
+            p_paddr: s.lma,
+            p_filesz: if s.bss.is_some() { 0 } else { s.size() },
+            p_memsz: s.size(),
+            p_align: 4,
+        });
+    }
+    for s in sections.iter().filter(|s| s.bss.is_none()) {
+        w.write_align(4);
+        w.write(&s.data);
+    }
+    w.write_null_symbol();
+    for (symbol, name) in symbols.iter().zip(&symbol_names) {
+        let kind = if symbol.func {
+            elf::STT_FUNC
+        } else {
+            elf::STT_OBJECT
+        };
+        w.write_symbol(&Sym {
+            name: Some(*name),
+            section: Some(indices[symbol.section]),
+            st_info: (elf::STB_GLOBAL << 4) | kind,
+            st_other: 0,
+            st_shndx: 0,
+            st_value: symbol.value,
+            st_size: symbol.size,
+        });
+    }
+    w.write_strtab();
+    w.write_shstrtab();
+
+    w.write_null_section_header();
+    for (i, s) in sections.iter().enumerate() {
+        w.write_section_header(&SectionHeader {
+            name: Some(names[i]),
+            sh_type: if s.bss.is_some() {
+                elf::SHT_NOBITS
+            } else {
+                elf::SHT_PROGBITS
+            },
+            sh_flags: s.flags,
+            sh_addr: s.addr,
+            sh_offset: offsets[i] as u64,
+            sh_size: s.size(),
+            sh_link: 0,
+            sh_info: 0,
+            sh_addralign: 4,
+            sh_entsize: 0,
+        });
+    }
+    w.write_symtab_section_header(1);
+    w.write_strtab_section_header();
+    w.write_shstrtab_section_header();
+    out
+}
+
+/// A function with line table rows, for [`debug_sections`]
+pub struct TestFunction {
+    pub name: &'static str,
+    pub addr: u64,
+    pub size: u64,
+    /// (offset from `addr`, line) rows
+    pub lines: Vec<(u64, u64)>,
+}
+
+/// DWARF 4 sections describing `functions` in one compilation unit for
+/// `file`, compiled in `/work`. Append them after the allocated sections.
+pub fn debug_sections(file: &'static str, functions: &[TestFunction]) -> Vec<TestSection> {
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 4,
+    };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b"/work".to_vec()),
+        LineString::String(file.as_bytes().to_vec()),
+        None,
+    );
+    let directory = program.default_directory();
+    let file_id = program.add_file(
+        LineString::String(file.as_bytes().to_vec()),
+        directory,
+        None,
+    );
+    for function in functions {
+        program.begin_sequence(Some(Address::Constant(function.addr)));
+        for &(offset, line) in &function.lines {
+            let row = program.row();
+            row.address_offset = offset;
+            row.file = file_id;
+            row.line = line;
+            program.generate_row();
+        }
+        program.end_sequence(function.size);
+    }
+    dwarf.unit.line_program = program;
+
+    let low = functions.iter().map(|f| f.addr).min().unwrap_or(0);
+    let high = functions.iter().map(|f| f.addr + f.size).max().unwrap_or(0);
+    let root = dwarf.unit.root();
+    let unit = dwarf.unit.get_mut(root);
+    unit.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(file.as_bytes().to_vec()),
+    );
+    unit.set(
+        gimli::DW_AT_comp_dir,
+        AttributeValue::String(b"/work".to_vec()),
+    );
+    unit.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(low)),
+    );
+    unit.set(gimli::DW_AT_high_pc, AttributeValue::Udata(high - low));
+    unit.set(gimli::DW_AT_stmt_list, AttributeValue::LineProgramRef);
+    for function in functions {
+        let id = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(id);
+        entry.set(
+            gimli::DW_AT_name,
+            AttributeValue::String(function.name.as_bytes().to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(function.addr)),
+        );
+        entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(function.size));
+    }
+
+    let mut sections = Sections::new(EndianVec::new(gimli::LittleEndian));
+    dwarf.write(&mut sections).unwrap();
+    let mut out = Vec::new();
+    sections
+        .for_each(|id, data| {
+            if !data.slice().is_empty() {
+                out.push(TestSection::info(id.name(), data.slice().to_vec()));
+            }
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+    out
+}
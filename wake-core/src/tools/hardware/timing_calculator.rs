@@ -2,6 +2,8 @@ use crate::tools::{Tool, ToolCapability, ToolResult};
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use wake_llm::ToolDescription;
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -15,27 +17,926 @@ pub struct TimingCalculatorArgs {
     /// Desired output or target value
     pub target: u32,
 
+    /// Optional: Target MCU family (STM32, AVR, ESP32). All supported families are solved when omitted
+    pub platform: Option<String>,
+
+    /// Optional: Timer counter width in bits for pwm/timer (16 or 32, default 16)
+    pub timer_bits: Option<u8>,
+
+    /// Optional: PWM duty cycle in percent, used to compute the CCR value
+    pub duty_percent: Option<f64>,
+
+    /// Optional: Dead time in nanoseconds between complementary outputs (advanced timers)
+    pub dead_time_ns: Option<u32>,
+
+    /// Optional: SCL/SDA rise time in nanoseconds for i2c-timing (defaults to the spec maximum)
+    pub rise_time_ns: Option<u32>,
+
+    /// Optional: SCL/SDA fall time in nanoseconds for i2c-timing (defaults to the spec maximum)
+    pub fall_time_ns: Option<u32>,
+
+    /// Optional: I2C analog noise filter enabled (default true)
+    pub analog_filter: Option<bool>,
+
+    /// Optional: I2C digital noise filter length in kernel clock cycles, 0-15 (default 0)
+    pub digital_filter: Option<u8>,
+
     /// Optional: Additional constraints
     pub constraints: Option<String>,
 }
 
+/// MCU families with a known UART baud-rate generator layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UartPlatform {
+    Stm32,
+    Avr,
+    Esp32,
+}
+
+impl UartPlatform {
+    pub fn all() -> Vec<UartPlatform> {
+        vec![UartPlatform::Stm32, UartPlatform::Avr, UartPlatform::Esp32]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UartPlatform::Stm32 => "STM32 USART",
+            UartPlatform::Avr => "AVR USART",
+            UartPlatform::Esp32 => "ESP32 UART",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<UartPlatform> {
+        let s = s.to_lowercase();
+        if s.contains("stm32") {
+            Some(UartPlatform::Stm32)
+        } else if s.contains("avr")
+            || s.contains("atmega")
+            || s.contains("attiny")
+            || s.contains("arduino")
+        {
+            Some(UartPlatform::Avr)
+        } else if s.contains("esp32") {
+            Some(UartPlatform::Esp32)
+        } else {
+            None
+        }
+    }
+}
+
+/// One register configuration for a baud-rate generator
+#[derive(Clone, Debug, Serialize)]
+pub struct BaudRateSolution {
+    pub platform: &'static str,
+    /// Oversampling / speed mode (OVER16, OVER8, U2X=0, ...)
+    pub mode: &'static str,
+    /// Register or bitfield values to program
+    pub registers: BTreeMap<&'static str, u32>,
+    /// Human readable register summary
+    pub summary: String,
+    pub actual_baud: f64,
+    pub error_percent: f64,
+    /// Maximum baud-rate error the receiver tolerates in this mode
+    pub tolerance_percent: f64,
+    pub within_tolerance: bool,
+    /// Best solution for its platform
+    pub recommended: bool,
+}
+
+impl BaudRateSolution {
+    fn new(
+        platform: UartPlatform,
+        mode: &'static str,
+        registers: BTreeMap<&'static str, u32>,
+        summary: String,
+        actual_baud: f64,
+        target: u32,
+        tolerance_percent: f64,
+    ) -> Self {
+        let error_percent = (actual_baud - target as f64) / target as f64 * 100.0;
+        Self {
+            platform: platform.label(),
+            mode,
+            registers,
+            summary,
+            actual_baud,
+            error_percent,
+            tolerance_percent,
+            within_tolerance: error_percent.abs() <= tolerance_percent,
+            recommended: false,
+        }
+    }
+}
+
+/// Receiver tolerance used for UARTs whose reference manual gives no figure
+/// (16x oversampling, 8 data bits, split with the far end).
+const GENERIC_UART_TOLERANCE_PERCENT: f64 = 2.0;
+
+/// STM32 USART (BRR mantissa/fraction), OVER16 or OVER8.
+/// Tolerances are the reference manual values for ONEBIT=0.
+pub fn solve_stm32_usart(clock: u32, baud: u32, over8: bool) -> Option<BaudRateSolution> {
+    if baud == 0 {
+        return None;
+    }
+    let fraction_bits = if over8 { 3 } else { 4 };
+    // USARTDIV expressed in 1/8 or 1/16 units
+    let div = (clock as f64 / baud as f64).round() as u64;
+    let mantissa = div >> fraction_bits;
+    let fraction = div & ((1 << fraction_bits) - 1);
+    if mantissa == 0 || mantissa > 0xFFF {
+        return None;
+    }
+
+    let brr = ((mantissa << 4) | fraction) as u32;
+    let actual = clock as f64 / div as f64;
+    let tolerance = match (over8, fraction == 0) {
+        (false, true) => 3.75,
+        (false, false) => 3.41,
+        (true, true) => 2.50,
+        (true, false) => 1.82,
+    };
+
+    let mut registers = BTreeMap::new();
+    registers.insert("BRR", brr);
+    registers.insert("DIV_Mantissa", mantissa as u32);
+    registers.insert("DIV_Fraction", fraction as u32);
+    registers.insert("OVER8", over8 as u32);
+
+    Some(BaudRateSolution::new(
+        UartPlatform::Stm32,
+        if over8 { "OVER8" } else { "OVER16" },
+        registers,
+        format!(
+            "BRR=0x{:04X} (mantissa {}, fraction {})",
+            brr, mantissa, fraction
+        ),
+        actual,
+        baud,
+        tolerance,
+    ))
+}
+
+/// AVR USART (12-bit UBRR), normal or double speed (U2X).
+/// Tolerances are the datasheet recommended maximum receiver error for 8 data bits.
+pub fn solve_avr_usart(clock: u32, baud: u32, u2x: bool) -> Option<BaudRateSolution> {
+    if baud == 0 {
+        return None;
+    }
+    let divisor = if u2x { 8.0 } else { 16.0 };
+    let ubrr = (clock as f64 / (divisor * baud as f64)).round() as i64 - 1;
+    if !(0..=0xFFF).contains(&ubrr) {
+        return None;
+    }
+
+    let actual = clock as f64 / (divisor * (ubrr + 1) as f64);
+    let mut registers = BTreeMap::new();
+    registers.insert("UBRR", ubrr as u32);
+    registers.insert("UBRRH", (ubrr >> 8) as u32);
+    registers.insert("UBRRL", (ubrr & 0xFF) as u32);
+    registers.insert("U2X", u2x as u32);
+
+    Some(BaudRateSolution::new(
+        UartPlatform::Avr,
+        if u2x { "U2X=1" } else { "U2X=0" },
+        registers,
+        format!(
+            "UBRR={} (UBRRH=0x{:02X}, UBRRL=0x{:02X})",
+            ubrr,
+            ubrr >> 8,
+            ubrr & 0xFF
+        ),
+        actual,
+        baud,
+        if u2x { 1.5 } else { 2.0 },
+    ))
+}
+
+/// ESP32 UART clock divider (20-bit integer + 4-bit fractional part of UART_CLKDIV_REG).
+pub fn solve_esp32_uart(clock: u32, baud: u32) -> Option<BaudRateSolution> {
+    if baud == 0 {
+        return None;
+    }
+    let div = (16.0 * clock as f64 / baud as f64).round() as u64;
+    let integer = div >> 4;
+    let fraction = div & 0xF;
+    if integer == 0 || integer > 0xFFFFF {
+        return None;
+    }
+
+    let actual = 16.0 * clock as f64 / div as f64;
+    let mut registers = BTreeMap::new();
+    registers.insert("CLKDIV", integer as u32);
+    registers.insert("CLKDIV_FRAG", fraction as u32);
+
+    Some(BaudRateSolution::new(
+        UartPlatform::Esp32,
+        "clkdiv",
+        registers,
+        format!("UART_CLKDIV={} + {}/16", integer, fraction),
+        actual,
+        baud,
+        GENERIC_UART_TOLERANCE_PERCENT,
+    ))
+}
+
+/// Every baud-rate generator configuration for a platform, best one flagged as recommended.
+/// Ties go to the first mode listed (OVER16 / U2X=0), which has the better noise margin.
+pub fn solve_baud_rate(platform: UartPlatform, clock: u32, baud: u32) -> Vec<BaudRateSolution> {
+    let mut solutions: Vec<BaudRateSolution> = match platform {
+        UartPlatform::Stm32 => vec![
+            solve_stm32_usart(clock, baud, false),
+            solve_stm32_usart(clock, baud, true),
+        ],
+        UartPlatform::Avr => vec![
+            solve_avr_usart(clock, baud, false),
+            solve_avr_usart(clock, baud, true),
+        ],
+        UartPlatform::Esp32 => vec![solve_esp32_uart(clock, baud)],
+    }
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let best = solutions
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            let rank = |s: &BaudRateSolution| (!s.within_tolerance, s.error_percent.abs());
+            rank(a)
+                .partial_cmp(&rank(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(i, _)| i);
+    if let Some(i) = best {
+        solutions[i].recommended = true;
+    }
+
+    solutions
+}
+
+/// Prescaler / auto-reload pair for a general purpose timer
+#[derive(Clone, Debug, Serialize)]
+pub struct TimerSolution {
+    /// PSC register value (counter clock = clock / (PSC + 1))
+    pub psc: u32,
+    /// ARR register value (period = ARR + 1 counter ticks)
+    pub arr: u32,
+    pub actual_freq: f64,
+    pub error_percent: f64,
+    /// Duty-cycle resolution in bits, log2(ARR + 1)
+    pub resolution_bits: f64,
+}
+
+/// Search every PSC (16-bit) / ARR (`timer_bits` wide) pair that approximates `target_hz`.
+/// Results are ranked by frequency error first, then by duty-cycle resolution.
+pub fn solve_timer(clock: u32, target_hz: f64, timer_bits: u8) -> Vec<TimerSolution> {
+    if target_hz <= 0.0 || clock == 0 {
+        return Vec::new();
+    }
+    let arr_max = if timer_bits >= 32 {
+        u32::MAX as u64
+    } else {
+        (1u64 << timer_bits) - 1
+    };
+
+    let mut solutions = Vec::new();
+    for psc in 0..=0xFFFFu64 {
+        let ticks = (clock as f64 / ((psc + 1) as f64 * target_hz)).round() as u64;
+        if ticks < 2 {
+            // Every larger prescaler only gets coarser
+            break;
+        }
+        let arr = ticks - 1;
+        if arr > arr_max {
+            continue;
+        }
+        let actual = clock as f64 / ((psc + 1) * ticks) as f64;
+        solutions.push(TimerSolution {
+            psc: psc as u32,
+            arr: arr as u32,
+            actual_freq: actual,
+            error_percent: (actual - target_hz) / target_hz * 100.0,
+            resolution_bits: (ticks as f64).log2(),
+        });
+    }
+
+    // Errors equal up to float noise tie, so resolution decides
+    solutions.sort_by_key(|s| {
+        (
+            (s.error_percent.abs() * 1e6).round() as u64,
+            std::cmp::Reverse(s.arr),
+        )
+    });
+    solutions
+}
+
+/// Capture/compare value for a duty cycle, with the duty cycle it really produces
+pub fn compute_ccr(arr: u32, duty_percent: f64) -> (u32, f64) {
+    let period = arr as f64 + 1.0;
+    let ccr = (duty_percent.clamp(0.0, 100.0) / 100.0 * period).round() as u32;
+    (ccr, ccr as f64 / period * 100.0)
+}
+
+/// STM32 advanced-timer dead-time setting (TIMx_BDTR.DTG with TIMx_CR1.CKD)
+#[derive(Clone, Debug, Serialize)]
+pub struct DeadTimeSolution {
+    pub dtg: u8,
+    /// CKD division factor applied to t_DTS (1, 2 or 4)
+    pub ckd_division: u32,
+    pub actual_ns: f64,
+}
+
+/// Smallest DTG encoding giving at least `dead_time_ns`, rounding up so the
+/// requested dead time is always guaranteed.
+pub fn solve_dead_time(clock: u32, dead_time_ns: u32) -> Option<DeadTimeSolution> {
+    for ckd_division in [1u32, 2, 4] {
+        let t_dts_ns = 1e9 * ckd_division as f64 / clock as f64;
+        let ticks = (dead_time_ns as f64 / t_dts_ns - 1e-9).ceil().max(0.0) as u32;
+
+        let dtg = if ticks <= 127 {
+            ticks as u8
+        } else if ticks <= 254 {
+            0x80 | (ticks.div_ceil(2) - 64) as u8
+        } else if ticks <= 504 {
+            0xC0 | (ticks.div_ceil(8) - 32) as u8
+        } else if ticks <= 1008 {
+            0xE0 | (ticks.div_ceil(16) - 32) as u8
+        } else {
+            continue;
+        };
+
+        return Some(DeadTimeSolution {
+            dtg,
+            ckd_division,
+            actual_ns: dead_time_ticks(dtg) as f64 * t_dts_ns,
+        });
+    }
+    None
+}
+
+/// Dead time in t_DTS ticks encoded by a DTG value
+pub fn dead_time_ticks(dtg: u8) -> u32 {
+    let dtg = dtg as u32;
+    match dtg >> 5 {
+        0..=3 => dtg,
+        4 | 5 => (64 + (dtg & 0x3F)) * 2,
+        6 => (32 + (dtg & 0x1F)) * 8,
+        _ => (32 + (dtg & 0x1F)) * 16,
+    }
+}
+
+/// I2C bus timing limits from the I2C-bus specification (UM10204), in nanoseconds
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct I2cSpec {
+    pub mode: &'static str,
+    pub max_rate: u32,
+    pub rise_max: f64,
+    pub fall_max: f64,
+    pub hd_dat_min: f64,
+    pub vd_dat_max: f64,
+    pub su_dat_min: f64,
+    pub low_min: f64,
+    pub high_min: f64,
+}
+
+pub const I2C_STANDARD_MODE: I2cSpec = I2cSpec {
+    mode: "Sm",
+    max_rate: 100_000,
+    rise_max: 1000.0,
+    fall_max: 300.0,
+    hd_dat_min: 0.0,
+    vd_dat_max: 3450.0,
+    su_dat_min: 250.0,
+    low_min: 4700.0,
+    high_min: 4000.0,
+};
+
+pub const I2C_FAST_MODE: I2cSpec = I2cSpec {
+    mode: "Fm",
+    max_rate: 400_000,
+    rise_max: 300.0,
+    fall_max: 300.0,
+    hd_dat_min: 0.0,
+    vd_dat_max: 900.0,
+    su_dat_min: 100.0,
+    low_min: 1300.0,
+    high_min: 600.0,
+};
+
+pub const I2C_FAST_MODE_PLUS: I2cSpec = I2cSpec {
+    mode: "Fm+",
+    max_rate: 1_000_000,
+    rise_max: 120.0,
+    fall_max: 120.0,
+    hd_dat_min: 0.0,
+    vd_dat_max: 450.0,
+    su_dat_min: 50.0,
+    low_min: 500.0,
+    high_min: 260.0,
+};
+
+impl I2cSpec {
+    /// Slowest mode whose maximum rate covers the requested SCL frequency
+    pub fn for_rate(rate: u32) -> Option<I2cSpec> {
+        [I2C_STANDARD_MODE, I2C_FAST_MODE, I2C_FAST_MODE_PLUS]
+            .into_iter()
+            .find(|spec| rate <= spec.max_rate)
+    }
+}
+
+/// Bus conditions the timing is computed for
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct I2cBusConditions {
+    pub rise_ns: f64,
+    pub fall_ns: f64,
+    pub analog_filter: bool,
+    pub digital_filter: u8,
+}
+
+/// One spec parameter checked against the computed timing
+#[derive(Clone, Debug, Serialize)]
+pub struct I2cTimingCheck {
+    pub parameter: &'static str,
+    pub actual_ns: f64,
+    pub min_ns: f64,
+    pub ok: bool,
+}
+
+impl I2cTimingCheck {
+    fn new(parameter: &'static str, actual_ns: f64, min_ns: f64) -> Self {
+        Self {
+            parameter,
+            actual_ns,
+            min_ns,
+            ok: actual_ns >= min_ns,
+        }
+    }
+}
+
+/// STM32 I2C_TIMINGR fields (I2C v2 peripheral: F0/F3/F7/G0/G4/H7/L0/L4/...)
+#[derive(Clone, Debug, Serialize)]
+pub struct Stm32I2cTiming {
+    pub presc: u32,
+    pub scldel: u32,
+    pub sdadel: u32,
+    pub sclh: u32,
+    pub scll: u32,
+    pub timingr: u32,
+    pub actual_freq: f64,
+    pub checks: Vec<I2cTimingCheck>,
+}
+
+// Analog filter input delay range from the STM32 datasheets
+const I2C_AF_DELAY_MIN_NS: f64 = 50.0;
+const I2C_AF_DELAY_MAX_NS: f64 = 260.0;
+
+/// Search PRESC/SCLDEL/SDADEL/SCLH/SCLL for the closest SCL frequency not above `rate`
+/// and no lower than 80% of it, following the reference manual timing equations.
+pub fn solve_stm32_i2c_timing(
+    clock: u32,
+    rate: u32,
+    spec: &I2cSpec,
+    bus: &I2cBusConditions,
+) -> Option<Stm32I2cTiming> {
+    if clock == 0 || rate == 0 {
+        return None;
+    }
+    let t_clk = 1e9 / clock as f64;
+    let (af_min, af_max) = if bus.analog_filter {
+        (I2C_AF_DELAY_MIN_NS, I2C_AF_DELAY_MAX_NS)
+    } else {
+        (0.0, 0.0)
+    };
+    let dnf = bus.digital_filter as f64;
+    let dnf_delay = dnf * t_clk;
+
+    let sdadel_min = bus.fall_ns + spec.hd_dat_min - af_min - (dnf + 3.0) * t_clk;
+    let sdadel_max = spec.vd_dat_max - bus.rise_ns - af_max - (dnf + 4.0) * t_clk;
+    let scldel_min = bus.rise_ns + spec.su_dat_min;
+
+    // One candidate per prescaler: the smallest valid SCLDEL/SDADEL
+    let mut candidates = Vec::new();
+    for presc in 0..16u32 {
+        let t_presc = (presc + 1) as f64 * t_clk;
+        let scldel = (0..16u32).find(|l| (*l + 1) as f64 * t_presc >= scldel_min);
+        let sdadel = (0..16u32).find(|a| {
+            let t = *a as f64 * t_presc;
+            t >= sdadel_min && t <= sdadel_max
+        });
+        if let (Some(scldel), Some(sdadel)) = (scldel, sdadel) {
+            candidates.push((presc, scldel, sdadel));
+        }
+    }
+
+    let period_min = 1e9 / rate as f64;
+    let period_max = 1e9 / (rate as f64 * 0.8);
+    let t_sync = af_min + dnf_delay + 2.0 * t_clk;
+
+    let mut best: Option<(f64, Stm32I2cTiming)> = None;
+    for (presc, scldel, sdadel) in candidates {
+        let t_presc = (presc + 1) as f64 * t_clk;
+        for scll in 0..256u32 {
+            let t_low = (scll + 1) as f64 * t_presc + t_sync;
+            if t_low < spec.low_min || t_clk >= (t_low - af_min - dnf_delay) / 4.0 {
+                continue;
+            }
+            for sclh in 0..256u32 {
+                let t_high = (sclh + 1) as f64 * t_presc + t_sync;
+                let period = t_low + t_high + bus.rise_ns + bus.fall_ns;
+                if period < period_min
+                    || period > period_max
+                    || t_high < spec.high_min
+                    || t_clk >= t_high
+                {
+                    continue;
+                }
+                let error = (period - period_min).abs();
+                if best.as_ref().is_some_and(|(e, _)| *e <= error) {
+                    continue;
+                }
+
+                let checks = vec![
+                    I2cTimingCheck::new("tLOW", t_low, spec.low_min),
+                    I2cTimingCheck::new("tHIGH", t_high, spec.high_min),
+                    I2cTimingCheck::new(
+                        "tSU;DAT",
+                        (scldel + 1) as f64 * t_presc - bus.rise_ns,
+                        spec.su_dat_min,
+                    ),
+                    I2cTimingCheck::new(
+                        "tHD;DAT",
+                        sdadel as f64 * t_presc + af_min + (dnf + 3.0) * t_clk - bus.fall_ns,
+                        spec.hd_dat_min,
+                    ),
+                ];
+                best = Some((
+                    error,
+                    Stm32I2cTiming {
+                        presc,
+                        scldel,
+                        sdadel,
+                        sclh,
+                        scll,
+                        timingr: (presc << 28)
+                            | (scldel << 20)
+                            | (sdadel << 16)
+                            | (sclh << 8)
+                            | scll,
+                        actual_freq: 1e9 / period,
+                        checks,
+                    },
+                ));
+            }
+        }
+    }
+
+    best.map(|(_, timing)| timing)
+}
+
+/// SCL low/high phase lengths in peripheral clock counts for controllers with plain
+/// low/high counters. Rise time is taken out of the high phase and fall time out of the low phase.
+#[derive(Clone, Debug, Serialize)]
+pub struct GenericI2cTiming {
+    pub low_counts: u32,
+    pub high_counts: u32,
+    pub actual_freq: f64,
+    pub checks: Vec<I2cTimingCheck>,
+}
+
+pub fn solve_generic_i2c_timing(
+    clock: u32,
+    rate: u32,
+    spec: &I2cSpec,
+    bus: &I2cBusConditions,
+) -> Option<GenericI2cTiming> {
+    if clock == 0 || rate == 0 {
+        return None;
+    }
+    let t_clk = 1e9 / clock as f64;
+    let low_min = ((spec.low_min + bus.fall_ns) / t_clk).ceil() as u32;
+    let high_min = ((spec.high_min + bus.rise_ns) / t_clk).ceil() as u32;
+    // Never run faster than requested
+    let total = (clock as f64 / rate as f64).ceil() as u32;
+    let total = total.max(low_min + high_min);
+
+    // Spread spare counts in proportion to the spec minimums
+    let spare = total - low_min - high_min;
+    let low_share = spec.low_min / (spec.low_min + spec.high_min);
+    let extra_low = (spare as f64 * low_share).round() as u32;
+    let low_counts = low_min + extra_low;
+    let high_counts = high_min + spare - extra_low;
+
+    let t_low = low_counts as f64 * t_clk - bus.fall_ns;
+    let t_high = high_counts as f64 * t_clk - bus.rise_ns;
+    Some(GenericI2cTiming {
+        low_counts,
+        high_counts,
+        actual_freq: clock as f64 / total as f64,
+        checks: vec![
+            I2cTimingCheck::new("tLOW", t_low, spec.low_min),
+            I2cTimingCheck::new("tHIGH", t_high, spec.high_min),
+        ],
+    })
+}
+
 pub struct TimingCalculator;
 
 impl TimingCalculator {
     pub fn new() -> Self {
         Self
     }
-}
 
-#[async_trait]
-impl Tool for TimingCalculator {
-    type Params = TimingCalculatorArgs;
+    fn calculate_baud_rate(&self, args: &TimingCalculatorArgs) -> ToolResult {
+        if args.target == 0 {
+            return ToolResult::error("Target baud rate must be greater than 0".to_string());
+        }
 
-    fn capabilities(&self) -> &'static [ToolCapability] {
-        &[ToolCapability::Read]
+        let platforms = match &args.platform {
+            Some(p) => match UartPlatform::parse(p) {
+                Some(platform) => vec![platform],
+                None => {
+                    return ToolResult::error(format!(
+                        "Unsupported platform for baud-rate calculation: {}. Supported: STM32, AVR, ESP32",
+                        p
+                    ))
+                }
+            },
+            None => UartPlatform::all(),
+        };
+
+        let solutions: Vec<BaudRateSolution> = platforms
+            .iter()
+            .flat_map(|p| solve_baud_rate(*p, args.clock_freq, args.target))
+            .collect();
+
+        if solutions.is_empty() {
+            return ToolResult::error(format!(
+                "No baud-rate generator setting reaches {} baud from a {} Hz clock",
+                args.target, args.clock_freq
+            ));
+        }
+
+        let mut output = format!(
+            "## Timing Calculation\n\n\
+            ### Type: baud-rate\n\
+            ### Clock: {} Hz\n\
+            ### Target: {} baud\n\n\
+            | Platform | Mode | Registers | Actual baud | Error | Tolerance |\n\
+            |----------|------|-----------|-------------|-------|-----------|\n",
+            args.clock_freq, args.target
+        );
+
+        let mut warnings = Vec::new();
+        for s in &solutions {
+            output.push_str(&format!(
+                "| {} | {}{} | {} | {:.1} | {:+.2}% | ±{:.2}% |\n",
+                s.platform,
+                s.mode,
+                if s.recommended { " ✓" } else { "" },
+                s.summary,
+                s.actual_baud,
+                s.error_percent,
+                s.tolerance_percent
+            ));
+            if s.recommended && !s.within_tolerance {
+                warnings.push(format!(
+                    "{}: best achievable error {:+.2}% exceeds the ±{:.2}% receiver tolerance. \
+                    Change the clock or pick a baud rate that divides it more evenly.",
+                    s.platform, s.error_percent, s.tolerance_percent
+                ));
+            }
+        }
+
+        output.push_str("\n✓ = recommended setting for the platform\n");
+        if !warnings.is_empty() {
+            output.push_str("\n### ⚠️ Warnings\n");
+            for w in &warnings {
+                output.push_str(&format!("- {}\n", w));
+            }
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("calc_type".to_string(), json!("baud-rate"));
+        metadata.insert("clock_freq".to_string(), json!(args.clock_freq));
+        metadata.insert("target".to_string(), json!(args.target));
+        metadata.insert("solutions".to_string(), json!(solutions));
+        metadata.insert("warnings".to_string(), json!(warnings));
+
+        ToolResult::success_with_metadata(output, metadata)
     }
 
-    async fn execute(&self, args: Self::Params) -> ToolResult {
+    fn calculate_timer(&self, args: &TimingCalculatorArgs, pwm: bool) -> ToolResult {
+        let timer_bits = args.timer_bits.unwrap_or(16);
+        if timer_bits != 16 && timer_bits != 32 {
+            return ToolResult::error(format!(
+                "Unsupported timer width: {} bits. Supported: 16, 32",
+                timer_bits
+            ));
+        }
+        if args.target == 0 {
+            return ToolResult::error("Target frequency must be greater than 0".to_string());
+        }
+
+        let solutions = solve_timer(args.clock_freq, args.target as f64, timer_bits);
+        let best = match solutions.first() {
+            Some(best) => best.clone(),
+            None => {
+                return ToolResult::error(format!(
+                    "No PSC/ARR pair reaches {} Hz from a {} Hz clock with a {}-bit timer",
+                    args.target, args.clock_freq, timer_bits
+                ))
+            }
+        };
+
+        let calc_type = if pwm { "pwm" } else { "timer" };
+        let mut output = format!(
+            "## Timing Calculation\n\n\
+            ### Type: {}\n\
+            ### Clock: {} Hz\n\
+            ### Target: {} Hz ({}-bit timer)\n\n\
+            **Best solution:** PSC={}, ARR={} → {:.4} Hz ({:+.4}% error, {:.1} bits of duty resolution)\n",
+            calc_type,
+            args.clock_freq,
+            args.target,
+            timer_bits,
+            best.psc,
+            best.arr,
+            best.actual_freq,
+            best.error_percent,
+            best.resolution_bits
+        );
+
+        let mut metadata = HashMap::new();
+        metadata.insert("calc_type".to_string(), json!(calc_type));
+        metadata.insert("clock_freq".to_string(), json!(args.clock_freq));
+        metadata.insert("target".to_string(), json!(args.target));
+        metadata.insert("timer_bits".to_string(), json!(timer_bits));
+        metadata.insert("best".to_string(), json!(best));
+
+        if let Some(duty) = args.duty_percent {
+            let (ccr, actual_duty) = compute_ccr(best.arr, duty);
+            output.push_str(&format!(
+                "**Duty cycle:** CCR={} → {:.3}% (requested {}%)\n",
+                ccr, actual_duty, duty
+            ));
+            metadata.insert(
+                "duty".to_string(),
+                json!({ "ccr": ccr, "requested_percent": duty, "actual_percent": actual_duty }),
+            );
+        }
+
+        if let Some(dead_time_ns) = args.dead_time_ns {
+            match solve_dead_time(args.clock_freq, dead_time_ns) {
+                Some(dt) => {
+                    output.push_str(&format!(
+                        "**Dead time:** BDTR.DTG=0x{:02X}, CR1.CKD=/{} → {:.1} ns (requested {} ns)\n",
+                        dt.dtg, dt.ckd_division, dt.actual_ns, dead_time_ns
+                    ));
+                    metadata.insert("dead_time".to_string(), json!(dt));
+                }
+                None => output.push_str(&format!(
+                    "**Dead time:** {} ns exceeds the DTG range at this clock\n",
+                    dead_time_ns
+                )),
+            }
+        }
+
+        let alternatives: Vec<&TimerSolution> = solutions.iter().skip(1).take(10).collect();
+        if !alternatives.is_empty() {
+            output.push_str(
+                "\n### Alternatives\n\n\
+                | PSC | ARR | Actual frequency | Error | Resolution |\n\
+                |-----|-----|------------------|-------|------------|\n",
+            );
+            for s in alternatives.iter().take(5) {
+                output.push_str(&format!(
+                    "| {} | {} | {:.4} Hz | {:+.4}% | {:.1} bits |\n",
+                    s.psc, s.arr, s.actual_freq, s.error_percent, s.resolution_bits
+                ));
+            }
+        }
+        metadata.insert("alternatives".to_string(), json!(alternatives));
+
+        if pwm && best.resolution_bits < 8.0 {
+            output.push_str(&format!(
+                "\n⚠️ Only {:.1} bits of duty resolution at this frequency; raise the timer clock or lower the PWM frequency for finer control.\n",
+                best.resolution_bits
+            ));
+        }
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn calculate_i2c_timing(&self, args: &TimingCalculatorArgs) -> ToolResult {
+        let spec = match I2cSpec::for_rate(args.target) {
+            Some(spec) if args.target > 0 => spec,
+            _ => {
+                return ToolResult::error(format!(
+                    "Unsupported I2C SCL frequency: {} Hz. Supported: up to 1000000 Hz (Sm/Fm/Fm+)",
+                    args.target
+                ))
+            }
+        };
+        let digital_filter = args.digital_filter.unwrap_or(0);
+        if digital_filter > 15 {
+            return ToolResult::error(format!(
+                "Digital filter length must be 0-15, got {}",
+                digital_filter
+            ));
+        }
+        let bus = I2cBusConditions {
+            rise_ns: args.rise_time_ns.map_or(spec.rise_max, |t| t as f64),
+            fall_ns: args.fall_time_ns.map_or(spec.fall_max, |t| t as f64),
+            analog_filter: args.analog_filter.unwrap_or(true),
+            digital_filter,
+        };
+
+        let (stm32, generic) = match args.platform.as_deref().map(|p| p.to_lowercase()) {
+            Some(p) if p.contains("stm32") => (true, false),
+            Some(_) => (false, true),
+            None => (true, true),
+        };
+
+        let mut output = format!(
+            "## Timing Calculation\n\n\
+            ### Type: i2c-timing\n\
+            ### Clock: {} Hz\n\
+            ### Target: {} Hz ({} mode)\n\n\
+            Bus: tr={} ns, tf={} ns, analog filter {}, digital filter {}\n",
+            args.clock_freq,
+            args.target,
+            spec.mode,
+            bus.rise_ns,
+            bus.fall_ns,
+            if bus.analog_filter { "on" } else { "off" },
+            bus.digital_filter
+        );
+
+        let mut metadata = HashMap::new();
+        metadata.insert("calc_type".to_string(), json!("i2c-timing"));
+        metadata.insert("clock_freq".to_string(), json!(args.clock_freq));
+        metadata.insert("target".to_string(), json!(args.target));
+        metadata.insert("spec".to_string(), json!(spec));
+        metadata.insert("bus".to_string(), json!(bus));
+
+        let render_checks = |checks: &[I2cTimingCheck]| {
+            let mut table = String::from(
+                "\n| Parameter | Actual | Spec min | |\n\
+                |-----------|--------|----------|-|\n",
+            );
+            for c in checks {
+                table.push_str(&format!(
+                    "| {} | {:.0} ns | {:.0} ns | {} |\n",
+                    c.parameter,
+                    c.actual_ns,
+                    c.min_ns,
+                    if c.ok { "✓" } else { "✗" }
+                ));
+            }
+            table
+        };
+
+        if stm32 {
+            output.push_str("\n### STM32 I2C_TIMINGR\n\n");
+            match solve_stm32_i2c_timing(args.clock_freq, args.target, &spec, &bus) {
+                Some(t) => {
+                    output.push_str(&format!(
+                        "**TIMINGR = 0x{:08X}** (PRESC={}, SCLDEL={}, SDADEL={}, SCLH=0x{:02X}, SCLL=0x{:02X}) → {:.0} Hz\n",
+                        t.timingr, t.presc, t.scldel, t.sdadel, t.sclh, t.scll, t.actual_freq
+                    ));
+                    output.push_str(&render_checks(&t.checks));
+                    metadata.insert("stm32".to_string(), json!(t));
+                }
+                None => output.push_str(&format!(
+                    "No TIMINGR value meets the {} mode limits from a {} Hz kernel clock. \
+                    Raise the I2C kernel clock or relax the rise/fall times.\n",
+                    spec.mode, args.clock_freq
+                )),
+            }
+        }
+
+        if generic {
+            output.push_str("\n### Generic SCL low/high counts\n\n");
+            if let Some(t) = solve_generic_i2c_timing(args.clock_freq, args.target, &spec, &bus) {
+                output.push_str(&format!(
+                    "SCL low = {} clocks, SCL high = {} clocks → {:.0} Hz\n",
+                    t.low_counts, t.high_counts, t.actual_freq
+                ));
+                output.push_str(&render_checks(&t.checks));
+                if t.actual_freq < args.target as f64 * 0.99 {
+                    output.push_str(&format!(
+                        "\n⚠️ The {} mode minimums cannot be met at {} Hz; SCL slowed to {:.0} Hz.\n",
+                        spec.mode, args.target, t.actual_freq
+                    ));
+                }
+                metadata.insert("generic".to_string(), json!(t));
+            }
+        }
+
+        ToolResult::success_with_metadata(output, metadata)
+    }
+
+    fn describe_calculation(&self, args: &TimingCalculatorArgs) -> ToolResult {
         let calculation = format!(
             "## Timing Calculation\n\n\
             ### Type: {}\n\
@@ -53,13 +954,32 @@ impl Tool for TimingCalculator {
     }
 }
 
+#[async_trait]
+impl Tool for TimingCalculator {
+    type Params = TimingCalculatorArgs;
+
+    fn capabilities(&self) -> &'static [ToolCapability] {
+        &[ToolCapability::Read]
+    }
+
+    async fn execute(&self, args: Self::Params) -> ToolResult {
+        match args.calc_type.to_lowercase().as_str() {
+            "baud-rate" | "baud" | "baudrate" | "uart" => self.calculate_baud_rate(&args),
+            "pwm" => self.calculate_timer(&args, true),
+            "timer" => self.calculate_timer(&args, false),
+            "i2c-timing" | "i2c" => self.calculate_i2c_timing(&args),
+            _ => self.describe_calculation(&args),
+        }
+    }
+}
+
 impl ToolDescription for TimingCalculator {
     fn name(&self) -> &'static str {
         "timing_calculator"
     }
 
     fn description(&self) -> &'static str {
-        "Calculate timing parameters, prescalers, and dividers for hardware peripherals like UART, timers, PWM, and clocks."
+        "Calculate timing parameters, prescalers, and dividers for hardware peripherals like UART, timers, PWM, and clocks. For baud-rate it solves the STM32/AVR/ESP32 divider registers and reports the achieved rate and error."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -67,3 +987,243 @@ impl ToolDescription for TimingCalculator {
             .unwrap_or_else(|_| serde_json::json!({}))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(
+        calc_type: &str,
+        clock_freq: u32,
+        target: u32,
+        platform: Option<&str>,
+    ) -> TimingCalculatorArgs {
+        TimingCalculatorArgs {
+            calc_type: calc_type.to_string(),
+            clock_freq,
+            target,
+            platform: platform.map(|p| p.to_string()),
+            timer_bits: None,
+            duty_percent: None,
+            dead_time_ns: None,
+            rise_time_ns: None,
+            fall_time_ns: None,
+            analog_filter: None,
+            digital_filter: None,
+            constraints: None,
+        }
+    }
+
+    #[test]
+    fn test_stm32_over16_brr() {
+        let s = solve_stm32_usart(16_000_000, 115_200, false).unwrap();
+        assert_eq!(s.registers["BRR"], 0x8B);
+        assert_eq!(s.registers["DIV_Mantissa"], 8);
+        assert_eq!(s.registers["DIV_Fraction"], 11);
+        assert!((s.error_percent - -0.08).abs() < 0.01);
+        assert!(s.within_tolerance);
+    }
+
+    #[test]
+    fn test_stm32_over8_brr() {
+        // 84 MHz APB2, 921600 baud: USARTDIV = 11.39 -> 91/8
+        let s = solve_stm32_usart(84_000_000, 921_600, true).unwrap();
+        assert_eq!(s.registers["DIV_Mantissa"], 11);
+        assert_eq!(s.registers["DIV_Fraction"], 3);
+        assert_eq!(s.registers["BRR"], 0xB3);
+        assert_eq!(s.tolerance_percent, 1.82);
+    }
+
+    #[test]
+    fn test_avr_16mhz_115200_is_out_of_tolerance() {
+        let normal = solve_avr_usart(16_000_000, 115_200, false).unwrap();
+        assert_eq!(normal.registers["UBRR"], 8);
+        assert!((normal.error_percent - -3.55).abs() < 0.01);
+        assert!(!normal.within_tolerance);
+
+        let double = solve_avr_usart(16_000_000, 115_200, true).unwrap();
+        assert_eq!(double.registers["UBRR"], 16);
+        assert!((double.error_percent - 2.12).abs() < 0.01);
+        assert!(!double.within_tolerance);
+    }
+
+    #[test]
+    fn test_avr_prefers_exact_crystal() {
+        let solutions = solve_baud_rate(UartPlatform::Avr, 14_745_600, 115_200);
+        let best = solutions.iter().find(|s| s.recommended).unwrap();
+        assert_eq!(best.mode, "U2X=0");
+        assert_eq!(best.registers["UBRR"], 7);
+        assert_eq!(best.error_percent, 0.0);
+    }
+
+    #[test]
+    fn test_esp32_clkdiv() {
+        let s = solve_esp32_uart(80_000_000, 115_200).unwrap();
+        assert_eq!(s.registers["CLKDIV"], 694);
+        assert_eq!(s.registers["CLKDIV_FRAG"], 7);
+        assert!(s.error_percent.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_unreachable_baud_rate() {
+        assert!(solve_stm32_usart(1_000_000, 115_200, false).is_none());
+        assert!(solve_avr_usart(1_000_000, 1_000_000, false).is_none());
+    }
+
+    #[test]
+    fn test_timer_prefers_resolution_on_equal_error() {
+        let solutions = solve_timer(84_000_000, 20_000.0, 16);
+        let best = &solutions[0];
+        assert_eq!(best.psc, 0);
+        assert_eq!(best.arr, 4199);
+        assert_eq!(best.error_percent, 0.0);
+        assert!(best.resolution_bits > 12.0);
+        assert!(solutions.len() > 1);
+    }
+
+    #[test]
+    fn test_timer_low_frequency_needs_prescaler() {
+        let solutions = solve_timer(84_000_000, 1.0, 16);
+        let best = &solutions[0];
+        assert!(best.psc > 0);
+        assert!(best.arr <= 0xFFFF);
+        assert_eq!(best.error_percent, 0.0);
+        assert!(best.resolution_bits > 15.0);
+
+        let wide = solve_timer(84_000_000, 1.0, 32);
+        assert_eq!(wide[0].psc, 0);
+        assert_eq!(wide[0].arr, 83_999_999);
+    }
+
+    #[test]
+    fn test_ccr_and_dead_time() {
+        assert_eq!(compute_ccr(4199, 25.0).0, 1050);
+        assert_eq!(compute_ccr(99, 150.0).0, 100);
+
+        let dt = solve_dead_time(84_000_000, 500).unwrap();
+        assert_eq!(dt.dtg, 42);
+        assert_eq!(dt.ckd_division, 1);
+
+        let dt = solve_dead_time(84_000_000, 2_000).unwrap();
+        assert_eq!(dt.dtg, 0x94);
+        assert!(dt.actual_ns >= 2_000.0);
+
+        let dt = solve_dead_time(168_000_000, 10_000).unwrap();
+        assert_eq!(dt.ckd_division, 2);
+        assert!(dt.actual_ns >= 10_000.0);
+        assert_eq!(dead_time_ticks(0xFF), 1008);
+    }
+
+    #[tokio::test]
+    async fn test_pwm_metadata() {
+        let tool = TimingCalculator::new();
+        let mut params = args("pwm", 84_000_000, 20_000, None);
+        params.duty_percent = Some(25.0);
+        params.dead_time_ns = Some(500);
+
+        if let ToolResult::Success { output, metadata } = tool.execute(params).await {
+            assert!(output.contains("PSC=0, ARR=4199"));
+            assert!(output.contains("CCR=1050"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["best"]["arr"], json!(4199));
+            assert_eq!(metadata["duty"]["ccr"], json!(1050));
+            assert_eq!(metadata["dead_time"]["dtg"], json!(42));
+            assert!(!metadata["alternatives"].as_array().unwrap().is_empty());
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[test]
+    fn test_stm32_i2c_fast_mode_meets_spec() {
+        let bus = I2cBusConditions {
+            rise_ns: 100.0,
+            fall_ns: 10.0,
+            analog_filter: true,
+            digital_filter: 0,
+        };
+        let t = solve_stm32_i2c_timing(16_000_000, 400_000, &I2C_FAST_MODE, &bus).unwrap();
+        assert!(t.actual_freq <= 400_000.0 && t.actual_freq >= 320_000.0);
+        assert!(t.checks.iter().all(|c| c.ok));
+        assert_eq!(t.timingr >> 28, t.presc);
+        assert_eq!(t.timingr & 0xFF, t.scll);
+        assert_eq!((t.timingr >> 8) & 0xFF, t.sclh);
+        assert_eq!((t.timingr >> 16) & 0xF, t.sdadel);
+        assert_eq!((t.timingr >> 20) & 0xF, t.scldel);
+    }
+
+    #[test]
+    fn test_stm32_i2c_unreachable() {
+        let bus = I2cBusConditions {
+            rise_ns: 120.0,
+            fall_ns: 120.0,
+            analog_filter: true,
+            digital_filter: 0,
+        };
+        assert!(solve_stm32_i2c_timing(1_000_000, 1_000_000, &I2C_FAST_MODE_PLUS, &bus).is_none());
+        assert_eq!(I2cSpec::for_rate(100_000).unwrap().mode, "Sm");
+        assert_eq!(I2cSpec::for_rate(400_000).unwrap().mode, "Fm");
+        assert_eq!(I2cSpec::for_rate(1_000_000).unwrap().mode, "Fm+");
+        assert!(I2cSpec::for_rate(3_400_000).is_none());
+    }
+
+    #[test]
+    fn test_generic_i2c_slows_down_to_meet_spec() {
+        let bus = I2cBusConditions {
+            rise_ns: 300.0,
+            fall_ns: 300.0,
+            analog_filter: false,
+            digital_filter: 0,
+        };
+        let t = solve_generic_i2c_timing(16_000_000, 400_000, &I2C_FAST_MODE, &bus).unwrap();
+        assert_eq!(t.low_counts, 26);
+        assert_eq!(t.high_counts, 15);
+        assert!(t.actual_freq < 400_000.0);
+        assert!(t.checks.iter().all(|c| c.ok));
+    }
+
+    #[tokio::test]
+    async fn test_i2c_timing_metadata() {
+        let tool = TimingCalculator::new();
+        let mut params = args("i2c-timing", 48_000_000, 100_000, Some("STM32G0"));
+        params.rise_time_ns = Some(250);
+        params.fall_time_ns = Some(20);
+
+        if let ToolResult::Success { output, metadata } = tool.execute(params).await {
+            assert!(output.contains("TIMINGR = 0x"));
+            assert!(!output.contains("Generic SCL"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["spec"]["mode"], json!("Sm"));
+            assert!(metadata["stm32"]["timingr"].is_u64());
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_baud_rate_metadata_and_warning() {
+        let tool = TimingCalculator::new();
+        let result = tool
+            .execute(args("baud-rate", 16_000_000, 115_200, Some("ATmega328P")))
+            .await;
+
+        if let ToolResult::Success { output, metadata } = result {
+            assert!(output.contains("UBRR=8"));
+            assert!(output.contains("Warnings"));
+            let metadata = metadata.unwrap();
+            assert_eq!(metadata["solutions"].as_array().unwrap().len(), 2);
+            assert_eq!(metadata["warnings"].as_array().unwrap().len(), 1);
+        } else {
+            panic!("Expected success result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_baud_rate_unknown_platform() {
+        let tool = TimingCalculator::new();
+        let result = tool
+            .execute(args("baud-rate", 16_000_000, 115_200, Some("PIC16")))
+            .await;
+        assert!(result.is_error());
+    }
+}
@@ -0,0 +1,606 @@
+//! UART decoding and baud-rate estimation from logic-analyzer captures.
+//!
+//! The bit time is estimated from the shortest pulse on the line and refined
+//! by averaging every in-frame pulse over its whole number of bits, so a
+//! clock that is a few percent off shows up against the nearest standard
+//! rate. Frames are sampled mid-bit from each start-bit edge; the stop bit is
+//! checked for framing errors and the parity bit, when configured, for parity
+//! errors. A line that idles low is treated as inverted.
+
+use super::capture::Capture;
+use super::netlist::format_si;
+
+/// Standard rates the measured rate is compared against
+const STANDARD_BAUDS: [u32; 23] = [
+    300, 600, 1200, 2400, 4800, 9600, 14400, 19200, 28800, 31250, 38400, 57600, 76800, 115200,
+    230400, 250000, 460800, 500000, 921600, 1000000, 1500000, 2000000, 3000000,
+];
+
+/// Combined clock mismatch a UART link tolerates before bits slip
+pub const TOLERANCE_PERCENT: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Format {
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+        }
+    }
+}
+
+impl Format {
+    /// `8N1`, `7E1`, `8O2`, ...
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let chars: Vec<char> = text.trim().to_uppercase().chars().collect();
+        let invalid = || format!("Invalid UART format '{}'; expected e.g. 8N1 or 7E2", text);
+        let [data, parity, stop] = chars[..] else {
+            return Err(invalid());
+        };
+        let data_bits = data
+            .to_digit(10)
+            .filter(|d| (5..=9).contains(d))
+            .ok_or_else(invalid)?;
+        let parity = match parity {
+            'N' => Parity::None,
+            'E' => Parity::Even,
+            'O' => Parity::Odd,
+            _ => return Err(invalid()),
+        };
+        let stop_bits = stop
+            .to_digit(10)
+            .filter(|s| (1..=2).contains(s))
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            data_bits: data_bits as u8,
+            parity,
+            stop_bits: stop_bits as u8,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.data_bits,
+            match self.parity {
+                Parity::None => 'N',
+                Parity::Even => 'E',
+                Parity::Odd => 'O',
+            },
+            self.stop_bits
+        )
+    }
+
+    /// Bits per frame including start, parity and stop bits
+    pub fn frame_bits(&self) -> u32 {
+        1 + self.data_bits as u32 + (self.parity != Parity::None) as u32 + self.stop_bits as u32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Word {
+    pub value: u16,
+    pub framing_error: bool,
+    pub parity_error: bool,
+    /// All-zero frame with a low stop bit
+    pub brk: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    BaudDeviation,
+    BaudMismatch,
+    Framing,
+    Parity,
+    Break,
+    Glitch,
+    Inverted,
+    /// No pulses to decode: the line never toggles or changes level once
+    Idle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub time: f64,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    /// Channel name
+    pub line: String,
+    pub format: Format,
+    /// Measured bit rate; `None` when the line is idle
+    pub baud: Option<f64>,
+    /// Nearest standard rate and the deviation from it in percent
+    pub standard: Option<(u32, f64)>,
+    pub inverted: bool,
+    pub words: Vec<(f64, Word)>,
+    pub issues: Vec<Issue>,
+}
+
+impl Decoded {
+    pub fn has(&self, kind: IssueKind) -> bool {
+        self.issues.iter().any(|i| i.kind == kind)
+    }
+}
+
+/// Times of the level changes on one channel
+fn edges(capture: &Capture, channel: usize) -> Vec<f64> {
+    capture
+        .samples
+        .windows(2)
+        .filter(|w| Capture::level(w[0].1 ^ w[1].1, channel))
+        .map(|w| w[1].0)
+        .collect()
+}
+
+/// Bit time in seconds: the shortest pulse seen at least twice (a single one
+/// may be a glitch), refined over all pulses up to a frame long
+pub fn estimate_bit_time(capture: &Capture, channel: usize) -> Option<f64> {
+    let edges = edges(capture, channel);
+    let mut widths: Vec<f64> = edges.windows(2).map(|e| e[1] - e[0]).collect();
+    widths.sort_by(f64::total_cmp);
+    let shortest = match widths.len() {
+        0 => return None,
+        1 => widths[0],
+        _ => *widths
+            .iter()
+            .find(|w| widths.iter().filter(|o| **o <= **w * 1.2).count() >= 2)
+            .unwrap_or(&widths[0]),
+    };
+    if shortest <= 0.0 {
+        return None;
+    }
+    let (total, bits) = widths
+        .iter()
+        .filter(|w| **w >= shortest * 0.8 && **w <= shortest * 10.5)
+        .fold((0.0, 0.0), |(total, bits), w| {
+            (total + w, bits + (w / shortest).round())
+        });
+    Some(total / bits)
+}
+
+/// Nearest standard rate and the deviation of `baud` from it in percent
+pub fn nearest_standard(baud: f64) -> (u32, f64) {
+    let standard = STANDARD_BAUDS
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            let ratio = |s: u32| (baud / s as f64).ln().abs();
+            ratio(*a).total_cmp(&ratio(*b))
+        })
+        .unwrap_or(9600);
+    (standard, (baud - standard as f64) / standard as f64 * 100.0)
+}
+
+/// Frames on `channel` sampled at `bit_time`: (start time, word), glitches
+fn frames(
+    capture: &Capture,
+    channel: usize,
+    format: Format,
+    bit_time: f64,
+    inverted: bool,
+) -> (Vec<(f64, Word)>, Vec<f64>) {
+    let level = |time: f64| capture.level_at(channel, time) != inverted;
+    let mut words = Vec::new();
+    let mut glitches = Vec::new();
+    let mut ready = f64::NEG_INFINITY;
+    for start in edges(capture, channel) {
+        // Start bits are falling edges once the previous frame is done
+        if start < ready || level(start) {
+            continue;
+        }
+        let at = |bit: u32| level(start + (bit as f64 + 0.5) * bit_time);
+        if at(0) {
+            glitches.push(start);
+            continue;
+        }
+        let data_bits = format.data_bits as u32;
+        let value = (0..data_bits).fold(0u16, |v, bit| v | (at(1 + bit) as u16) << bit);
+        let mut next = 1 + data_bits;
+        let parity_error = match format.parity {
+            Parity::None => false,
+            parity => {
+                let ones = value.count_ones() + at(next) as u32;
+                next += 1;
+                (ones % 2 == 1) != (parity == Parity::Odd)
+            }
+        };
+        let framing_error = (0..format.stop_bits as u32).any(|s| !at(next + s));
+        let brk = framing_error && value == 0 && !(1..next).any(at);
+        words.push((
+            start,
+            Word {
+                value,
+                framing_error,
+                parity_error,
+                brk,
+            },
+        ));
+        ready = start + (format.frame_bits() as f64 - 0.5) * bit_time;
+    }
+    (words, glitches)
+}
+
+/// Decode one UART line at its measured rate. `configured` is the receiver's
+/// rate; when it differs the frames are also sampled at it to count the errors
+/// the receiver sees.
+pub fn decode(
+    capture: &Capture,
+    channel: usize,
+    format: Format,
+    configured: Option<u32>,
+) -> Result<Decoded, String> {
+    let name = capture.channels[channel].clone();
+    let first = capture.samples[0];
+    let last = capture.samples[capture.samples.len() - 1];
+    let Some(bit_time) = estimate_bit_time(capture, channel) else {
+        return Ok(idle(capture, channel, format));
+    };
+    let baud = 1.0 / bit_time;
+    let standard = nearest_standard(baud);
+    let inverted = !Capture::level(first.1, channel) && !Capture::level(last.1, channel);
+
+    let (words, glitches) = frames(capture, channel, format, bit_time, inverted);
+    let mut issues = Vec::new();
+    let mut issue = |time, kind, message| {
+        issues.push(Issue {
+            time,
+            kind,
+            message,
+        })
+    };
+
+    if inverted {
+        issue(
+            first.0,
+            IssueKind::Inverted,
+            format!(
+                "{} idles low; decoded as an inverted line (RS-232 levels without a transceiver, or an inverted TX)",
+                name
+            ),
+        );
+    }
+    if standard.1.abs() > TOLERANCE_PERCENT {
+        issue(
+            first.0,
+            IssueKind::BaudDeviation,
+            format!(
+                "{} runs at {} baud, {:+.1}% from {}; UART links tolerate about ±{}% combined clock error",
+                name,
+                baud.round(),
+                standard.1,
+                standard.0,
+                TOLERANCE_PERCENT
+            ),
+        );
+    }
+    if let Some(configured) = configured {
+        let deviation = (baud - configured as f64) / configured as f64 * 100.0;
+        if deviation.abs() > TOLERANCE_PERCENT {
+            let (misread, _) = frames(capture, channel, format, 1.0 / configured as f64, inverted);
+            let errors = misread
+                .iter()
+                .filter(|(_, w)| w.framing_error || w.parity_error)
+                .count();
+            issue(
+                first.0,
+                IssueKind::BaudMismatch,
+                format!(
+                    "Receiver is set to {} baud but {} runs at {} baud ({:+.1}%); at {} baud {} of {} frames have framing or parity errors",
+                    configured,
+                    name,
+                    baud.round(),
+                    deviation,
+                    configured,
+                    errors,
+                    misread.len()
+                ),
+            );
+        }
+    }
+
+    let count = |pick: fn(&Word) -> bool| {
+        let hits: Vec<f64> = words
+            .iter()
+            .filter(|(_, w)| pick(w))
+            .map(|(t, _)| *t)
+            .collect();
+        (hits.len(), hits.first().copied())
+    };
+    if let (n, Some(time)) = count(|w| w.brk) {
+        issue(
+            time,
+            IssueKind::Break,
+            format!(
+                "{} break condition(s) on {} (line held low past the stop bit)",
+                n, name
+            ),
+        );
+    }
+    if let (n, Some(time)) = count(|w| w.framing_error && !w.brk) {
+        issue(
+            time,
+            IssueKind::Framing,
+            format!(
+                "{} of {} frames on {} have a low stop bit (framing error: wrong baud, data bits or stop bits)",
+                n,
+                words.len(),
+                name
+            ),
+        );
+    }
+    if let (n, Some(time)) = count(|w| w.parity_error) {
+        issue(
+            time,
+            IssueKind::Parity,
+            format!(
+                "{} of {} frames on {} fail the {} parity check",
+                n,
+                words.len(),
+                name,
+                format.label()
+            ),
+        );
+    }
+    if let Some(time) = glitches.first() {
+        issue(
+            *time,
+            IssueKind::Glitch,
+            format!(
+                "{} falling edges on {} are shorter than half a bit (noise or ringing)",
+                glitches.len(),
+                name
+            ),
+        );
+    }
+    issues.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    Ok(Decoded {
+        line: name,
+        format,
+        baud: Some(baud),
+        standard: Some(standard),
+        inverted,
+        words,
+        issues,
+    })
+}
+
+/// Line without pulses to decode, reported as a finding rather than an error
+fn idle(capture: &Capture, channel: usize, format: Format) -> Decoded {
+    let name = capture.channels[channel].clone();
+    let first = capture.samples[0];
+    let last = capture.samples[capture.samples.len() - 1];
+    let level = |high: bool| if high { "high" } else { "low" };
+    let high = Capture::level(last.1, channel);
+    let cause = if high {
+        "nothing was transmitted, or the probe is on the wrong pin"
+    } else {
+        "TX not driven, held in reset, shorted to ground, or RS-232 levels without a transceiver"
+    };
+    let message = match edges(capture, channel).first() {
+        Some(time) => format!(
+            "{} changes level once ({} to {} at {}) and stays {}; no frames to decode ({})",
+            name,
+            level(!high),
+            level(high),
+            format_si(*time, "s"),
+            level(high),
+            cause
+        ),
+        None => format!(
+            "{} has no edges; the line is stuck {} for the whole capture ({})",
+            name,
+            level(high),
+            cause
+        ),
+    };
+    Decoded {
+        line: name,
+        format,
+        baud: None,
+        standard: None,
+        inverted: false,
+        words: Vec::new(),
+        issues: vec![Issue {
+            time: first.0,
+            kind: IssueKind::Idle,
+            message,
+        }],
+    }
+}
+
+/// Summary line and hex/ASCII dump of at most `limit` rows of 16 words
+pub fn dump(decoded: &Decoded, limit: usize) -> String {
+    let (Some(baud), Some(standard)) = (decoded.baud, decoded.standard) else {
+        return format!(
+            "**{}**: no frames, idle line ({})\n\n",
+            decoded.line,
+            decoded.format.label()
+        );
+    };
+    let mut out = format!(
+        "**{}**: {} frames, {} baud measured (nearest standard {} baud, {:+.2}%), {}{}\n\n",
+        decoded.line,
+        decoded.words.len(),
+        baud.round(),
+        standard.0,
+        standard.1,
+        decoded.format.label(),
+        if decoded.inverted { ", inverted" } else { "" }
+    );
+    if decoded.words.is_empty() {
+        return out;
+    }
+    out.push_str("```\n");
+    for row in decoded.words.chunks(16).take(limit) {
+        let hex: Vec<String> = row
+            .iter()
+            .map(|(_, w)| {
+                let mark = if w.framing_error || w.parity_error {
+                    "!"
+                } else {
+                    " "
+                };
+                format!("{:02X}{}", w.value, mark)
+            })
+            .collect();
+        let text: String = row
+            .iter()
+            .map(|(_, w)| match w.value {
+                0x20..=0x7E => w.value as u8 as char,
+                _ => '.',
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:>12}  {:<48} {}\n",
+            format_si(row[0].0, "s"),
+            hex.concat().trim_end(),
+            text
+        ));
+    }
+    let rows = decoded.words.len().div_ceil(16);
+    if rows > limit {
+        out.push_str(&format!("... {} more rows\n", rows - limit));
+    }
+    out.push_str("```\n(`!` marks frames with framing or parity errors)\n");
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Single-channel TX capture sampled at 10 MHz
+    pub(crate) fn line(baud: f64, format: Format, bytes: &[u8]) -> Capture {
+        let bit = 1.0 / baud;
+        let quantize = |t: f64| (t * 10e6).round() / 10e6;
+        let mut capture = Capture {
+            channels: vec!["TX".to_string()],
+            samples: vec![(0.0, 1)],
+        };
+        let mut time = 20.0 * bit;
+        let mut push = |time: f64, high: bool| capture.samples.push((quantize(time), high as u64));
+        for byte in bytes {
+            let mut bits = vec![false];
+            bits.extend((0..format.data_bits).map(|b| byte >> b & 1 == 1));
+            let ones = byte.count_ones() % 2 == 1;
+            match format.parity {
+                Parity::None => {}
+                Parity::Even => bits.push(ones),
+                Parity::Odd => bits.push(!ones),
+            }
+            bits.extend((0..format.stop_bits).map(|_| true));
+            for high in bits {
+                push(time, high);
+                time += bit;
+            }
+            time += 3.0 * bit;
+        }
+        push(time, true);
+        capture.samples.dedup_by(|b, a| a.1 == b.1);
+        capture
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::parse("7e2").unwrap().label(), "7E2");
+        assert!(Format::parse("8X1").is_err());
+        assert!(Format::parse("8N3").is_err());
+    }
+
+    #[test]
+    fn test_baud_estimation_and_decode() {
+        let format = Format::default();
+        let capture = line(115200.0, format, b"Hello, UART!");
+        let decoded = decode(&capture, 0, format, Some(115200)).unwrap();
+        let standard = decoded.standard.unwrap();
+        assert_eq!(standard.0, 115200);
+        assert!(standard.1.abs() < 0.5, "{:?}", decoded.baud);
+        let text: Vec<u8> = decoded.words.iter().map(|(_, w)| w.value as u8).collect();
+        assert_eq!(text, b"Hello, UART!");
+        assert!(decoded.issues.is_empty(), "{:?}", decoded.issues);
+        let dump = dump(&decoded, 4);
+        assert!(
+            dump.contains("**TX**: 12 frames, 115195 baud measured (nearest standard 115200 baud")
+        );
+        assert!(dump.contains("48 65 6C 6C 6F 2C 20 55 41 52 54 21"));
+        assert!(dump.contains(" Hello, UART!\n"));
+
+        // A clock 4% slow, read by a receiver expecting 9600 baud
+        let capture = line(9600.0 * 0.96, format, b"\x55\x0F\xF0");
+        let decoded = decode(&capture, 0, format, Some(9600)).unwrap();
+        let standard = decoded.standard.unwrap();
+        assert_eq!(standard.0, 9600);
+        assert!((standard.1 + 4.0).abs() < 0.3, "{}", standard.1);
+        assert!(decoded.has(IssueKind::BaudDeviation));
+        assert!(decoded.has(IssueKind::BaudMismatch));
+        assert_eq!(decoded.words.len(), 3);
+    }
+
+    #[test]
+    fn test_parity_and_framing_errors() {
+        let capture = line(19200.0, Format::parse("8E1").unwrap(), b"AB");
+        // Decoding 8E1 traffic as 8O1 fails parity; as 8N2 the parity bit
+        // sits where the second stop bit is expected
+        let odd = decode(&capture, 0, Format::parse("8O1").unwrap(), None).unwrap();
+        assert!(odd.has(IssueKind::Parity));
+        assert!(odd.issues[0]
+            .message
+            .starts_with("2 of 2 frames on TX fail"));
+        let even = decode(&capture, 0, Format::parse("8E1").unwrap(), None).unwrap();
+        assert!(even.issues.is_empty());
+        // 'B' = 0x42 has even parity, so its parity bit is low
+        let two_stop = decode(&capture, 0, Format::parse("8N2").unwrap(), None).unwrap();
+        assert!(two_stop.has(IssueKind::Framing));
+    }
+
+    #[test]
+    fn test_idle_line() {
+        let stuck = |samples| Capture {
+            channels: vec!["RX".to_string()],
+            samples,
+        };
+        let decoded = decode(
+            &stuck(vec![(0.0, 1), (0.01, 1)]),
+            0,
+            Format::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoded.baud, None);
+        assert!(decoded.has(IssueKind::Idle));
+        assert!(decoded.issues[0]
+            .message
+            .starts_with("RX has no edges; the line is stuck high"));
+        assert!(dump(&decoded, 4).contains("**RX**: no frames, idle line (8N1)"));
+
+        let decoded = decode(
+            &stuck(vec![(0.0, 1), (0.002, 0)]),
+            0,
+            Format::default(),
+            None,
+        )
+        .unwrap();
+        assert!(decoded.issues[0]
+            .message
+            .starts_with("RX changes level once (high to low at 2.000 ms) and stays low"));
+    }
+}
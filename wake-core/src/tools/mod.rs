@@ -3,6 +3,7 @@ pub mod fetch;
 pub mod fs;
 pub mod hardware;
 pub mod highlight;
+pub mod serial;
 pub mod todo;
 pub mod types;
 
@@ -21,6 +22,7 @@ pub use fs::{
     EditTool, FindTool, FsOperation, FsOperationLog, FsOperationSummary, FsOperationType, LsTool,
     MultiEditTool, ReadTool, WriteTool,
 };
+pub use serial::{SerialPortsTool, SerialTool};
 pub use todo::{
     TodoItem, TodoItemInput, TodoReadTool, TodoStatus, TodoStorage, TodoWriteParams, TodoWriteTool,
 };
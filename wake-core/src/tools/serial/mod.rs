@@ -0,0 +1,8 @@
+pub mod serial;
+pub mod structs;
+
+#[cfg(test)]
+mod tests;
+
+pub use serial::{SerialPortsTool, SerialTool};
+pub use structs::{FlowControl, LineEnding, SerialAction, SerialPortsParams, SerialToolParams};
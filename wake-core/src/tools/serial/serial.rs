@@ -0,0 +1,729 @@
+use super::structs::{FlowControl, SerialAction, SerialPortsParams, SerialToolParams};
+use crate::tools::hardware::uart_decode::{Format, Parity};
+use crate::tools::{tool, ToolResult};
+use regex::bytes::Regex;
+use serde_json::json;
+use serialport::SerialPort;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Bytes kept per port; older data is dropped
+pub const CAPTURE_LIMIT: usize = 64 * 1024;
+/// Timeout of a single blocking read in the capture thread
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+/// How often `read` checks the capture for new data
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Without `until`, `read` returns once the line has been idle this long
+const QUIET_TIME: Duration = Duration::from_millis(100);
+/// Longest text returned by one call
+const OUTPUT_LIMIT: usize = 16 * 1024;
+
+/// Rolling capture of everything received on a port
+#[derive(Default)]
+pub struct Capture {
+    data: VecDeque<u8>,
+    /// Bytes received since the port was opened
+    total: u64,
+    /// Absolute offset up to which `read` has returned data
+    cursor: u64,
+    /// Why the capture thread stopped, if it did
+    error: Option<String>,
+}
+
+impl Capture {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+        self.total += bytes.len() as u64;
+        let excess = self.data.len().saturating_sub(CAPTURE_LIMIT);
+        self.data.drain(..excess);
+    }
+
+    /// Absolute offset of the oldest byte still buffered
+    pub fn start(&self) -> u64 {
+        self.total - self.data.len() as u64
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.start()
+    }
+
+    /// Data after `offset`, and how many bytes after it were already dropped
+    pub fn since(&self, offset: u64) -> (Vec<u8>, u64) {
+        let skip = offset.saturating_sub(self.start()) as usize;
+        let lost = self.start().saturating_sub(offset);
+        (self.data.iter().skip(skip).copied().collect(), lost)
+    }
+}
+
+struct Session {
+    settings: String,
+    /// Shared so a write can run on a blocking thread without the session lock
+    writer: Arc<std::sync::Mutex<Box<dyn SerialPort>>>,
+    capture: Arc<std::sync::Mutex<Capture>>,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    fn open(path: &str, params: &SerialToolParams) -> Result<Self, String> {
+        let format = Format::parse(params.format.as_deref().unwrap_or("8N1"))?;
+        let data_bits = match format.data_bits {
+            5 => serialport::DataBits::Five,
+            6 => serialport::DataBits::Six,
+            7 => serialport::DataBits::Seven,
+            8 => serialport::DataBits::Eight,
+            bits => {
+                return Err(format!(
+                    "{} data bits are not supported by serial ports",
+                    bits
+                ))
+            }
+        };
+        let parity = match format.parity {
+            Parity::None => serialport::Parity::None,
+            Parity::Even => serialport::Parity::Even,
+            Parity::Odd => serialport::Parity::Odd,
+        };
+        let stop_bits = match format.stop_bits {
+            2 => serialport::StopBits::Two,
+            _ => serialport::StopBits::One,
+        };
+        let flow_control = match params.flow_control {
+            FlowControl::None => serialport::FlowControl::None,
+            FlowControl::Software => serialport::FlowControl::Software,
+            FlowControl::Hardware => serialport::FlowControl::Hardware,
+        };
+
+        let mut port = serialport::new(path, params.baud_rate)
+            .data_bits(data_bits)
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .flow_control(flow_control)
+            .timeout(READ_TIMEOUT)
+            .open()
+            .map_err(|e| open_error(path, e))?;
+        let mut writer = port
+            .try_clone()
+            .map_err(|e| format!("Failed to clone {}: {}", path, e))?;
+        writer
+            .set_timeout(Duration::from_millis(params.timeout_ms.max(100)))
+            .map_err(|e| format!("Failed to configure {}: {}", path, e))?;
+
+        let capture = Arc::new(std::sync::Mutex::new(Capture::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let capture = capture.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                while !stop.load(Ordering::Relaxed) {
+                    match port.read(&mut buf) {
+                        // End of file on a hung-up tty; avoid spinning
+                        Ok(0) => std::thread::sleep(READ_TIMEOUT),
+                        Ok(n) => capture.lock().unwrap().push(&buf[..n]),
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::TimedOut
+                                    | io::ErrorKind::Interrupted
+                                    | io::ErrorKind::WouldBlock
+                            ) => {}
+                        Err(e) => {
+                            capture.lock().unwrap().error = Some(e.to_string());
+                            break;
+                        }
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            settings: format!(
+                "{} {}{}",
+                params.baud_rate,
+                format.label(),
+                match params.flow_control {
+                    FlowControl::None => "",
+                    FlowControl::Software => " XON/XOFF",
+                    FlowControl::Hardware => " RTS/CTS",
+                }
+            ),
+            writer: Arc::new(std::sync::Mutex::new(writer)),
+            capture,
+            stop,
+            reader: Some(reader),
+        })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn open_error(path: &str, error: serialport::Error) -> String {
+    let hint = match error.kind() {
+        serialport::ErrorKind::NoDevice => {
+            " (the device is missing or in use by another program; try `serial_ports`)"
+        }
+        serialport::ErrorKind::Io(io::ErrorKind::NotFound) => {
+            " (no such device; try `serial_ports`)"
+        }
+        serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) => {
+            " (permission denied; the user usually needs to be in the `dialout` or `uucp` group)"
+        }
+        _ => "",
+    };
+    format!("Failed to open {}: {}{}", path, error, hint)
+}
+
+/// Parses "01 03 0A", "0x01,0x03" or "01030A"
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let invalid = || format!("Invalid hex data '{}'", text);
+    let mut bytes = Vec::new();
+    for token in text.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+        let token = token.trim_start_matches("0x").trim_start_matches("0X");
+        if token.is_empty() {
+            continue;
+        }
+        if !token.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        for pair in token.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(pair, 16).map_err(|_| invalid())?);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Received bytes as text: CRLF becomes a newline and other control or
+/// invalid bytes are shown escaped
+pub fn render(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.utf8_chunks() {
+        let mut chars = chunk.valid().chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' | '\t' => out.push(c),
+                c if c.is_control() => out.push_str(&format!("\\x{:02X}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    out
+}
+
+pub fn render_hex(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .map(|line| {
+            line.iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Keeps the end of overly long output, where the latest data is
+fn limit(text: String) -> String {
+    if text.len() <= OUTPUT_LIMIT {
+        return text;
+    }
+    let mut start = text.len() - OUTPUT_LIMIT;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!(
+        "[... {} earlier bytes not shown ...]\n{}",
+        start,
+        &text[start..]
+    )
+}
+
+/// Result of waiting for data on a port
+struct Received {
+    data: Vec<u8>,
+    lost: u64,
+    matched: bool,
+    error: Option<String>,
+}
+
+type Sessions = Arc<Mutex<HashMap<String, Session>>>;
+
+/// Opens a port on a blocking thread so the runtime and the session lock are
+/// not held up by a slow driver
+async fn open_session(path: &str, params: &SerialToolParams) -> Result<Session, String> {
+    let (path, params) = (path.to_string(), params.clone());
+    tokio::task::spawn_blocking(move || Session::open(&path, &params))
+        .await
+        .map_err(|e| format!("Failed to open the serial port: {}", e))?
+}
+
+/// Closes a port on a blocking thread; dropping a session joins its reader
+async fn close_session(session: Session) {
+    let _ = tokio::task::spawn_blocking(move || drop(session)).await;
+}
+
+#[derive(Default)]
+pub struct SerialTool {
+    sessions: Sessions,
+}
+
+impl SerialTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read-only companion tool sharing this tool's open ports
+    pub fn ports(&self) -> SerialPortsTool {
+        SerialPortsTool {
+            sessions: self.sessions.clone(),
+        }
+    }
+
+    fn port(params: &SerialToolParams) -> Result<&str, String> {
+        params
+            .port
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .ok_or_else(|| "`port` is required for this action".to_string())
+    }
+
+    fn payload(params: &SerialToolParams) -> Result<Vec<u8>, String> {
+        let data = params.data.as_deref().unwrap_or("");
+        let mut bytes = if params.hex {
+            parse_hex(data)?
+        } else {
+            data.as_bytes().to_vec()
+        };
+        bytes.extend_from_slice(params.line_ending.bytes());
+        if bytes.is_empty() {
+            return Err("`data` is required for `write`".to_string());
+        }
+        Ok(bytes)
+    }
+
+    fn until(params: &SerialToolParams) -> Result<Option<Regex>, String> {
+        params
+            .until
+            .as_deref()
+            .map(|pattern| Regex::new(pattern).map_err(|e| format!("Invalid `until` regex: {}", e)))
+            .transpose()
+    }
+
+    /// Opens the port unless this tool already has it open; the session lock
+    /// is not held while opening
+    async fn ensure_open(&self, path: &str, params: &SerialToolParams) -> Result<(), String> {
+        if self.sessions.lock().await.contains_key(path) {
+            return Ok(());
+        }
+        let session = open_session(path, params).await?;
+        let mut sessions = self.sessions.lock().await;
+        if sessions.contains_key(path) {
+            // Opened by a concurrent call meanwhile; keep that session
+            drop(sessions);
+            close_session(session).await;
+        } else {
+            sessions.insert(path.to_string(), session);
+        }
+        Ok(())
+    }
+
+    async fn capture(&self, path: &str) -> Option<Arc<std::sync::Mutex<Capture>>> {
+        self.sessions
+            .lock()
+            .await
+            .get(path)
+            .map(|session| session.capture.clone())
+    }
+
+    /// Waits for data after the read cursor until `until` matches or the
+    /// timeout expires, then advances the cursor past what is returned
+    async fn receive(
+        capture: &std::sync::Mutex<Capture>,
+        until: Option<&Regex>,
+        timeout: Duration,
+    ) -> Received {
+        let deadline = Instant::now() + timeout;
+        let mut last_total = None;
+        let mut last_change = Instant::now();
+        loop {
+            {
+                let mut capture = capture.lock().unwrap();
+                let cursor = capture.cursor;
+                let (data, lost) = capture.since(cursor);
+                let end = until.and_then(|re| re.find(&data)).map(|m| m.end());
+                let expired = Instant::now() >= deadline;
+                if last_total != Some(capture.total) {
+                    last_total = Some(capture.total);
+                    last_change = Instant::now();
+                }
+                // Without a pattern any data ends the wait once the line goes quiet
+                let settled =
+                    until.is_none() && !data.is_empty() && last_change.elapsed() >= QUIET_TIME;
+                if end.is_some() || expired || settled || capture.error.is_some() {
+                    let data = match end {
+                        Some(end) => data[..end].to_vec(),
+                        None => data,
+                    };
+                    capture.cursor = cursor + lost + data.len() as u64;
+                    return Received {
+                        data,
+                        lost,
+                        matched: end.is_some(),
+                        error: capture.error.clone(),
+                    };
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    fn format_received(
+        path: &str,
+        received: &Received,
+        params: &SerialToolParams,
+        mut output: String,
+    ) -> ToolResult {
+        let count = received.data.len();
+        if count == 0 {
+            output.push_str(&format!(
+                "No data received from {} within {} ms",
+                path, params.timeout_ms
+            ));
+        } else {
+            output.push_str(&format!("Received {} bytes from {}", count, path));
+            if received.matched {
+                output.push_str(&format!(
+                    " (matched `{}`)",
+                    params.until.as_deref().unwrap_or_default()
+                ));
+            } else if params.until.is_some() {
+                output.push_str(&format!(
+                    " (`{}` did not match within {} ms)",
+                    params.until.as_deref().unwrap_or_default(),
+                    params.timeout_ms
+                ));
+            }
+        }
+        if received.lost > 0 {
+            output.push_str(&format!(
+                "\n⚠️  {} bytes were dropped from the capture buffer before they were read",
+                received.lost
+            ));
+        }
+        if count > 0 {
+            let text = if params.hex {
+                render_hex(&received.data)
+            } else {
+                render(&received.data)
+            };
+            output.push_str("\n\n");
+            output.push_str(&limit(text));
+        }
+
+        let mut meta = HashMap::new();
+        meta.insert("port".to_string(), json!(path));
+        meta.insert("bytes".to_string(), json!(count));
+        meta.insert("matched".to_string(), json!(received.matched));
+        match &received.error {
+            Some(error) => {
+                meta.insert("error".to_string(), json!(error));
+                ToolResult::Error {
+                    error: format!("{}\n\nThe port stopped responding: {}", output, error),
+                    metadata: Some(meta),
+                }
+            }
+            None => ToolResult::success_with_metadata(output, meta),
+        }
+    }
+
+    async fn execute_internal(&self, params: SerialToolParams, preview: bool) -> ToolResult {
+        let path = match Self::port(&params) {
+            Ok(path) => path.to_string(),
+            Err(e) => return ToolResult::error(e),
+        };
+        let until = match Self::until(&params) {
+            Ok(until) => until,
+            Err(e) => return ToolResult::error(e),
+        };
+        let settings = format!(
+            "{} {}",
+            params.baud_rate,
+            params.format.as_deref().unwrap_or("8N1")
+        );
+        let timeout = Duration::from_millis(params.timeout_ms);
+
+        match params.action {
+            SerialAction::Open => {
+                if preview {
+                    return ToolResult::success(format!("Open {} at {}", path, settings));
+                }
+                // Reopening applies new settings and starts a fresh capture
+                let previous = self.sessions.lock().await.remove(&path);
+                if let Some(previous) = previous {
+                    close_session(previous).await;
+                }
+                match open_session(&path, &params).await {
+                    Ok(session) => {
+                        let output = format!(
+                            "Opened {} at {}; capturing received data",
+                            path, session.settings
+                        );
+                        let replaced = self.sessions.lock().await.insert(path, session);
+                        if let Some(replaced) = replaced {
+                            close_session(replaced).await;
+                        }
+                        ToolResult::success(output)
+                    }
+                    Err(e) => ToolResult::error(e),
+                }
+            }
+            SerialAction::Read => {
+                if preview {
+                    return ToolResult::success(format!("Read from {}", path));
+                }
+                if let Err(e) = self.ensure_open(&path, &params).await {
+                    return ToolResult::error(e);
+                }
+                let Some(capture) = self.capture(&path).await else {
+                    return ToolResult::error(format!("{} was closed", path));
+                };
+                let received = Self::receive(&capture, until.as_ref(), timeout).await;
+                Self::format_received(&path, &received, &params, String::new())
+            }
+            SerialAction::Write => {
+                let bytes = match Self::payload(&params) {
+                    Ok(bytes) => bytes,
+                    Err(e) => return ToolResult::error(e),
+                };
+                if preview {
+                    return ToolResult::success(format!(
+                        "Write {} bytes to {}:\n{}",
+                        bytes.len(),
+                        path,
+                        if params.hex {
+                            render_hex(&bytes)
+                        } else {
+                            render(&bytes)
+                        }
+                    ));
+                }
+                if let Err(e) = self.ensure_open(&path, &params).await {
+                    return ToolResult::error(e);
+                }
+                let (writer, capture) = {
+                    let sessions = self.sessions.lock().await;
+                    let Some(session) = sessions.get(&path) else {
+                        return ToolResult::error(format!("{} was closed", path));
+                    };
+                    // Replies to earlier writes should not be mistaken for this one
+                    if until.is_some() {
+                        let mut capture = session.capture.lock().unwrap();
+                        capture.cursor = capture.total;
+                    }
+                    (session.writer.clone(), session.capture.clone())
+                };
+                // Writes block until the driver accepts the data, which can take
+                // the whole timeout under flow control
+                let count = bytes.len();
+                let written = tokio::task::spawn_blocking(move || {
+                    let mut writer = writer.lock().unwrap();
+                    writer.write_all(&bytes).and_then(|_| writer.flush())
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result.map_err(|e| e.to_string()));
+                if let Err(e) = written {
+                    return ToolResult::error(format!("Failed to write to {}: {}", path, e));
+                }
+                let output = format!("Wrote {} bytes to {}", count, path);
+                if until.is_none() {
+                    let mut meta = HashMap::new();
+                    meta.insert("port".to_string(), json!(path));
+                    meta.insert("written".to_string(), json!(count));
+                    return ToolResult::success_with_metadata(output, meta);
+                }
+                let received = Self::receive(&capture, until.as_ref(), timeout).await;
+                Self::format_received(&path, &received, &params, format!("{}\n", output))
+            }
+            SerialAction::Close => {
+                if preview {
+                    return ToolResult::success(format!("Close {}", path));
+                }
+                let session = self.sessions.lock().await.remove(&path);
+                match session {
+                    Some(session) => {
+                        let total = session.capture.lock().unwrap().total;
+                        close_session(session).await;
+                        ToolResult::success(format!("Closed {} ({} bytes received)", path, total))
+                    }
+                    None => ToolResult::error(format!("{} is not open", path)),
+                }
+            }
+        }
+    }
+}
+
+#[tool(name = "serial", description = r#"Talks to a serial port (UART over USB, ACM or a native tty) without blocking. Use it instead of `cat`, `echo`, `screen` or `minicom` through bash, which hang waiting for data that never ends.
+
+**Actions:**
+- `open`: open `port` at `baud_rate`, `format` (8N1, 7E1, ...) and `flow_control`, and start capturing everything received into a rolling buffer (the last 64 KiB). Reopening applies new settings.
+- `read`: return data received since the previous read. Waits up to `timeout_ms`, or until the data matches the `until` regex (e.g. a shell prompt).
+- `write`: send `data` (text, or hex bytes with `hex=true`) with an optional `line_ending`. With `until`, also waits for the reply.
+- `close`: close the port.
+
+`read` and `write` open the port with the given settings if it is not open yet. The port stays open between calls, so boot logs and asynchronous output are captured while you do other work. To find ports or search what was captured, use `serial_ports`, which does not touch the hardware.
+
+**Examples:**
+- **Watch the boot log:** `serial(action='read', port='/dev/ttyUSB0', baud_rate=115200, until='login:', timeout_ms=10000)`
+- **Send a command:** `serial(action='write', port='/dev/ttyACM0', data='AT+GMR', line_ending='crlf', until='OK|ERROR')`
+"#, capabilities = [ToolCapability::Read, ToolCapability::Device])]
+impl SerialTool {
+    async fn execute_preview(&self, params: SerialToolParams) -> Option<ToolResult> {
+        Some(self.execute_internal(params, true).await)
+    }
+
+    async fn execute(&self, params: SerialToolParams) -> ToolResult {
+        self.execute_internal(params, false).await
+    }
+}
+
+/// Listing ports and querying a capture only read state, so unlike `serial`
+/// this runs without the device permission
+pub struct SerialPortsTool {
+    sessions: Sessions,
+}
+
+impl SerialPortsTool {
+    async fn list(&self) -> ToolResult {
+        let ports = match tokio::task::spawn_blocking(serialport::available_ports).await {
+            Ok(Ok(ports)) => ports,
+            Ok(Err(e)) => return ToolResult::error(format!("Failed to list serial ports: {}", e)),
+            Err(e) => return ToolResult::error(format!("Failed to list serial ports: {}", e)),
+        };
+        let sessions = self.sessions.lock().await;
+        let mut lines = Vec::new();
+        for port in &ports {
+            let mut line = format!("- {}", port.port_name);
+            match &port.port_type {
+                serialport::SerialPortType::UsbPort(usb) => {
+                    line.push_str(&format!(" — USB {:04x}:{:04x}", usb.vid, usb.pid));
+                    for text in [&usb.manufacturer, &usb.product].into_iter().flatten() {
+                        line.push(' ');
+                        line.push_str(text);
+                    }
+                    if let Some(serial) = &usb.serial_number {
+                        line.push_str(&format!(" (serial {})", serial));
+                    }
+                }
+                serialport::SerialPortType::PciPort => line.push_str(" — PCI"),
+                serialport::SerialPortType::BluetoothPort => line.push_str(" — Bluetooth"),
+                serialport::SerialPortType::Unknown => {}
+            }
+            if let Some(session) = sessions.get(&port.port_name) {
+                line.push_str(&format!(" [open at {}]", session.settings));
+            }
+            lines.push(line);
+        }
+        for (path, session) in sessions.iter() {
+            if !ports.iter().any(|p| &p.port_name == path) {
+                lines.push(format!("- {} [open at {}]", path, session.settings));
+            }
+        }
+        if lines.is_empty() {
+            return ToolResult::success("No serial ports found".to_string());
+        }
+        lines.sort();
+        ToolResult::success(format!("Serial ports:\n{}", lines.join("\n")))
+    }
+
+    async fn buffer(&self, path: &str, params: &SerialPortsParams) -> ToolResult {
+        let grep = match params.grep.as_deref().map(regex::Regex::new).transpose() {
+            Ok(grep) => grep,
+            Err(e) => return ToolResult::error(format!("Invalid `grep` regex: {}", e)),
+        };
+        let sessions = self.sessions.lock().await;
+        let Some(session) = sessions.get(path) else {
+            return ToolResult::error(format!("{} is not open; open it with `serial` first", path));
+        };
+        let capture = session.capture.lock().unwrap();
+        let (data, _) = capture.since(0);
+        let mut output = format!(
+            "Capture of {} ({}): {} bytes buffered, {} received",
+            path,
+            session.settings,
+            data.len(),
+            capture.total
+        );
+        if capture.dropped() > 0 {
+            output.push_str(&format!(", {} oldest dropped", capture.dropped()));
+        }
+        let text = if params.hex {
+            render_hex(&data)
+        } else {
+            render(&data)
+        };
+        let mut lines: Vec<&str> = text
+            .lines()
+            .filter(|line| grep.as_ref().is_none_or(|re| re.is_match(line)))
+            .collect();
+        if let Some(tail) = params.tail {
+            lines.drain(..lines.len().saturating_sub(tail));
+        }
+        if lines.is_empty() {
+            output.push_str(if grep.is_some() {
+                "\n\nNo lines match"
+            } else {
+                "\n\nNothing received yet"
+            });
+        } else {
+            output.push_str("\n\n");
+            output.push_str(&limit(lines.join("\n")));
+        }
+        if let Some(error) = &capture.error {
+            output.push_str(&format!("\n\n⚠️  Capture stopped: {}", error));
+        }
+
+        let mut meta = HashMap::new();
+        meta.insert("port".to_string(), json!(path));
+        meta.insert("buffered".to_string(), json!(data.len()));
+        meta.insert("received".to_string(), json!(capture.total));
+        meta.insert("lines".to_string(), json!(lines.len()));
+        ToolResult::success_with_metadata(output, meta)
+    }
+}
+
+#[tool(name = "serial_ports", description = r#"Lists serial ports and shows what the `serial` tool has captured, without touching the hardware.
+
+- Without `port`: available ports with USB vendor/product IDs, plus the ports `serial` has open and their settings.
+- With `port`: the rolling capture of everything received on a port `serial` has open (the last 64 KiB), without consuming it. Filter lines with the `grep` regex and keep the last `tail` lines.
+
+**Examples:**
+- **Find the board:** `serial_ports()`
+- **Search earlier output:** `serial_ports(port='/dev/ttyUSB0', grep='(?i)error|assert', tail=20)`
+"#, capabilities = [ToolCapability::Read])]
+impl SerialPortsTool {
+    async fn execute(&self, params: SerialPortsParams) -> ToolResult {
+        match params.port.as_deref().filter(|p| !p.trim().is_empty()) {
+            Some(path) => self.buffer(path, &params).await,
+            None => self.list().await,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SerialToolParams {
+    /// Operation to perform
+    pub action: SerialAction,
+    /// Device path, e.g. /dev/ttyUSB0, /dev/ttyACM0 or COM3
+    #[serde(default)]
+    pub port: Option<String>,
+    /// Baud rate used when opening the port (optional, defaults to 115200)
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    /// Frame format used when opening the port, e.g. 8N1 or 7E1 (optional, defaults to 8N1)
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Flow control used when opening the port (optional, defaults to none)
+    #[serde(default)]
+    pub flow_control: FlowControl,
+    /// Data to send for `write`
+    #[serde(default)]
+    pub data: Option<String>,
+    /// `write`: `data` holds hex bytes such as "01 03 00 00". `read`: show
+    /// received bytes as hex instead of text
+    #[serde(default)]
+    pub hex: bool,
+    /// Line ending appended to `data` on `write` (optional, defaults to none)
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// How long `read` (or `write` with `until`) waits for data, in milliseconds
+    /// (optional, defaults to 1000)
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Regex; `read` (or `write`) returns as soon as the received data matches
+    #[serde(default)]
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SerialPortsParams {
+    /// Port opened by `serial` whose capture to show; without it, list the ports
+    #[serde(default)]
+    pub port: Option<String>,
+    /// Show the captured bytes as hex instead of text
+    #[serde(default)]
+    pub hex: bool,
+    /// Only return the last N lines of the capture
+    #[serde(default)]
+    pub tail: Option<usize>,
+    /// Only return capture lines matching this regex
+    #[serde(default)]
+    pub grep: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[schemars(inline)]
+pub enum SerialAction {
+    /// Open (or reopen) a port with the given settings and start capturing
+    Open,
+    /// Return data received since the last read
+    Read,
+    /// Send data, optionally waiting for a reply matching `until`
+    Write,
+    /// Close the port and discard its capture
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[schemars(inline)]
+pub enum FlowControl {
+    #[default]
+    None,
+    /// XON/XOFF
+    Software,
+    /// RTS/CTS
+    Hardware,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[schemars(inline)]
+pub enum LineEnding {
+    #[default]
+    None,
+    Lf,
+    Cr,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn bytes(&self) -> &'static [u8] {
+        match self {
+            LineEnding::None => b"",
+            LineEnding::Lf => b"\n",
+            LineEnding::Cr => b"\r",
+            LineEnding::Crlf => b"\r\n",
+        }
+    }
+}
+
+fn default_baud_rate() -> u32 {
+    115_200
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
@@ -0,0 +1,205 @@
+use super::serial::{parse_hex, render, Capture, SerialTool, CAPTURE_LIMIT};
+use crate::tools::{Tool, ToolCapability, ToolResult};
+use serde_json::json;
+use wake_llm::ToolDescription;
+
+fn output(result: &ToolResult) -> &str {
+    match result {
+        ToolResult::Success { output, .. } => output,
+        ToolResult::Error { error, .. } => panic!("tool failed: {}", error),
+    }
+}
+
+async fn run(tool: &SerialTool, params: serde_json::Value) -> ToolResult {
+    tool.execute(serde_json::from_value(params).unwrap()).await
+}
+
+#[test]
+fn test_serial_tool_capabilities() {
+    let tool = SerialTool::new();
+    assert_eq!(tool.name(), "serial");
+    let perms = tool.capabilities();
+    assert!(perms.contains(&ToolCapability::Device));
+    assert!(!perms.contains(&ToolCapability::Write));
+
+    // Listing and querying the capture need no device permission
+    let ports = tool.ports();
+    assert_eq!(ports.name(), "serial_ports");
+    assert_eq!(ports.capabilities(), &[ToolCapability::Read]);
+}
+
+#[test]
+fn test_capture_and_rendering() {
+    let mut capture = Capture::default();
+    capture.push(&vec![b'a'; CAPTURE_LIMIT]);
+    capture.push(b"tail");
+    assert_eq!(capture.dropped(), 4);
+    let (data, lost) = capture.since(2);
+    assert_eq!(lost, 2);
+    assert_eq!(data.len(), CAPTURE_LIMIT);
+    assert!(data.ends_with(b"tail"));
+
+    assert_eq!(render(b"ok\r\n> \x1b[0m\xff"), "ok\n> \\x1B[0m\\xFF");
+    assert_eq!(parse_hex("01 03 0x0A,ff").unwrap(), vec![1, 3, 10, 255]);
+    assert_eq!(parse_hex("0103").unwrap(), vec![1, 3]);
+    assert!(parse_hex("1 2").is_err());
+}
+
+#[tokio::test]
+async fn test_write_preview_and_validation() {
+    let tool = SerialTool::new();
+    let preview = tool
+        .execute_preview(
+            serde_json::from_value(json!({
+                "action": "write",
+                "port": "/dev/ttyUSB0",
+                "data": "01 03 00 00",
+                "hex": true
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(output(&preview).contains("Write 4 bytes to /dev/ttyUSB0:\n01 03 00 00"));
+
+    let missing = run(&tool, json!({"action": "read"})).await;
+    assert!(matches!(missing, ToolResult::Error { error, .. } if error.contains("`port`")));
+
+    let bad = run(
+        &tool,
+        json!({"action": "open", "port": "/dev/does-not-exist", "format": "9N1"}),
+    )
+    .await;
+    assert!(matches!(bad, ToolResult::Error { error, .. } if error.contains("data bits")));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_serial_session_over_pty() {
+    use serialport::{SerialPort, TTYPort};
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    let (mut master, slave) = TTYPort::pair().expect("failed to create pty pair");
+    master.set_timeout(Duration::from_secs(2)).unwrap();
+    let path = slave.name().expect("pty has no name");
+
+    let tool = SerialTool::new();
+    let opened = run(
+        &tool,
+        json!({"action": "open", "port": path, "baud_rate": 9600}),
+    )
+    .await;
+    assert!(output(&opened).contains("at 9600 8N1"));
+
+    // Output that arrives between calls is captured
+    master.write_all(b"boot ok\r\nlogin: ").unwrap();
+    let read = run(
+        &tool,
+        json!({"action": "read", "port": path, "until": "login: ", "timeout_ms": 2000}),
+    )
+    .await;
+    let text = output(&read);
+    assert!(text.contains("(matched `login: `)"), "{}", text);
+    assert!(text.ends_with("boot ok\nlogin: "), "{}", text);
+
+    // Nothing new since the last read
+    let idle = run(
+        &tool,
+        json!({"action": "read", "port": path, "timeout_ms": 100}),
+    )
+    .await;
+    assert!(output(&idle).starts_with("No data received"));
+
+    // A write waits for the reply
+    let reply = std::thread::spawn({
+        let mut master = master.try_clone_native().unwrap();
+        move || {
+            let mut buf = [0u8; 5];
+            master.read_exact(&mut buf).unwrap();
+            master.write_all(b"root\r\n# ").unwrap();
+            buf
+        }
+    });
+    let written = run(
+        &tool,
+        json!({
+            "action": "write",
+            "port": path,
+            "data": "root",
+            "line_ending": "lf",
+            "until": "# $",
+            "timeout_ms": 2000
+        }),
+    )
+    .await;
+    assert_eq!(&reply.join().unwrap(), b"root\n");
+    let text = output(&written);
+    assert!(text.starts_with("Wrote 5 bytes"), "{}", text);
+    assert!(text.ends_with("root\n# "), "{}", text);
+
+    let ports = tool.ports();
+    let listed = ports
+        .execute(serde_json::from_value(json!({})).unwrap())
+        .await;
+    assert!(output(&listed).contains("[open at 9600 8N1]"));
+    let buffer = ports
+        .execute(serde_json::from_value(json!({"port": path, "grep": "^(boot|login)"})).unwrap())
+        .await;
+    let text = output(&buffer);
+    assert!(text.contains("24 received"), "{}", text);
+    assert!(text.ends_with("boot ok\nlogin: root"), "{}", text);
+
+    let tail = ports
+        .execute(serde_json::from_value(json!({"port": path, "tail": 1})).unwrap())
+        .await;
+    assert!(output(&tail).ends_with("\n\n# "));
+
+    let closed = run(&tool, json!({"action": "close", "port": path})).await;
+    assert!(output(&closed).contains("24 bytes received"));
+    let again = ports
+        .execute(serde_json::from_value(json!({"port": path})).unwrap())
+        .await;
+    assert!(matches!(again, ToolResult::Error { .. }));
+    drop(slave);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_blocked_write_does_not_hold_up_other_calls() {
+    use serialport::{SerialPort, TTYPort};
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    // Nobody reads the master side yet, so the write blocks once the pty buffer is full
+    let (mut master, slave) = TTYPort::pair().expect("failed to create pty pair");
+    master.set_timeout(Duration::from_secs(5)).unwrap();
+    let path = slave.name().expect("pty has no name");
+    let size = 1 << 20;
+
+    let tool = SerialTool::new();
+    let ports = tool.ports();
+    let write = run(
+        &tool,
+        json!({"action": "write", "port": path, "data": "x".repeat(size), "timeout_ms": 5000}),
+    );
+    let query = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let started = Instant::now();
+        let buffer = ports
+            .execute(serde_json::from_value(json!({"port": path})).unwrap())
+            .await;
+        let waited = started.elapsed();
+        let drain = std::thread::spawn(move || {
+            let mut data = vec![0u8; size];
+            master.read_exact(&mut data).unwrap();
+        });
+        (buffer, waited, drain)
+    };
+    let (written, (buffer, waited, drain)) = tokio::join!(write, query);
+    drain.join().unwrap();
+    assert!(waited < Duration::from_millis(500), "waited {:?}", waited);
+    assert!(output(&buffer).contains("Capture of"));
+    assert!(output(&written).starts_with(&format!("Wrote {} bytes", size)));
+    drop(slave);
+}
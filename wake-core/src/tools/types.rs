@@ -27,6 +27,9 @@ pub enum ToolCapability {
     Read,
     Write,
     Network,
+    /// Talks to attached hardware (serial ports, debug probes).
+    /// Together with `Write` the agent asks before every call
+    Device,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -129,11 +129,15 @@ fn tool_impl(args: String, input: ItemImpl) -> syn::Result<TokenStream2> {
                     "Read" => quote! { #crate_name::tools::ToolCapability::Read },
                     "Write" => quote! { #crate_name::tools::ToolCapability::Write },
                     "Network" => quote! { #crate_name::tools::ToolCapability::Network },
+                    "Device" => quote! { #crate_name::tools::ToolCapability::Device },
                     "ToolCapability::Read" => quote! { #crate_name::tools::ToolCapability::Read },
                     "ToolCapability::Write" => quote! { #crate_name::tools::ToolCapability::Write },
                     "ToolCapability::Network" => {
                         quote! { #crate_name::tools::ToolCapability::Network }
                     }
+                    "ToolCapability::Device" => {
+                        quote! { #crate_name::tools::ToolCapability::Device }
+                    }
                     _ => {
                         // Default fallback, but emit warning in generated code
                         quote! { #crate_name::tools::ToolCapability::Read }